@@ -0,0 +1,3 @@
+-- Explicit order of the tags within their tag group
+ALTER TABLE "tags"
+    ADD COLUMN "position" INT4 NOT NULL DEFAULT 0;
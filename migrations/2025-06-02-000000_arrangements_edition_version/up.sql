@@ -0,0 +1,3 @@
+-- Version of the arrangement, incremented on each edition to detect concurrent editions
+ALTER TABLE "arrangements"
+    ADD COLUMN "edition_version" INT4 NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Disabled arrangements are skipped by the grouping process
+ALTER TABLE "arrangements"
+    ADD COLUMN "enabled" BOOL NOT NULL DEFAULT TRUE;
@@ -0,0 +1,9 @@
+-- Named pictures queries saved by users, the query being stored as JSON
+CREATE TABLE "saved_searches"
+(
+    "id"      SERIAL      NOT NULL PRIMARY KEY,
+    "user_id" INT4        NOT NULL,
+    "name"    VARCHAR(32) NOT NULL,
+    "query"   TEXT        NOT NULL,
+    FOREIGN KEY ("user_id") REFERENCES "users" ("id")
+);
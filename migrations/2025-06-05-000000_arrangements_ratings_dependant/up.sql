@@ -0,0 +1,7 @@
+-- Arrangements regrouped when a rating changes
+ALTER TABLE "arrangements"
+    ADD COLUMN "ratings_dependant" BOOL NOT NULL DEFAULT FALSE;
+
+UPDATE "arrangements"
+SET "ratings_dependant" = TRUE
+WHERE convert_from("strategy", 'UTF8') LIKE '%"GroupByRating"%';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS "pictures_deleted_date_idx";
+DROP INDEX IF EXISTS "pictures_modified_at_idx";
+ALTER TABLE "pictures"
+    DROP COLUMN IF EXISTS "modified_at";
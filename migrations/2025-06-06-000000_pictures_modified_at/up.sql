@@ -0,0 +1,9 @@
+-- Pictures changes are listed from their modification and deletion dates, for clients synchronization.
+-- The modification date is the date of the last change of a picture or of its tags, ratings and groups.
+-- The edition date comes from the EXIF metadata, and doesn't change with the tags, ratings or groups.
+ALTER TABLE "pictures"
+    ADD COLUMN "modified_at" TIMESTAMP NOT NULL DEFAULT timezone('utc', now());
+UPDATE "pictures"
+SET "modified_at" = GREATEST("edition_date", "deleted_date");
+CREATE INDEX "pictures_modified_at_idx" ON "pictures" ("modified_at");
+CREATE INDEX "pictures_deleted_date_idx" ON "pictures" ("deleted_date");
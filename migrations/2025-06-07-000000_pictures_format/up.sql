@@ -0,0 +1,4 @@
+-- Format of the original picture, detected from its content at upload (jpeg, png, heif...).
+-- NULL for the pictures uploaded before, served as JPEG.
+ALTER TABLE "pictures"
+    ADD COLUMN "format" VARCHAR(16);
@@ -0,0 +1,3 @@
+-- Favorite flag of the pictures, set by their owner independently of ratings and tags
+ALTER TABLE "pictures"
+    ADD COLUMN "favorite" BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Sign in of the users without TOTP requires an email confirmation code, independently of tfa_login
+ALTER TABLE "users"
+    ADD COLUMN "email_tfa" BOOL NOT NULL DEFAULT FALSE;
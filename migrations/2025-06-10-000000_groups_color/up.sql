@@ -0,0 +1,3 @@
+-- RGB color of the groups, set when they are created by a grouping strategy
+ALTER TABLE "groups"
+    ADD COLUMN "color" BYTEA NULL;
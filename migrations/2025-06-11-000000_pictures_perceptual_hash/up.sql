@@ -0,0 +1,3 @@
+-- Perceptual hash of the pictures, computed from their small thumbnail at upload, to find near-duplicates
+ALTER TABLE "pictures"
+    ADD COLUMN "perceptual_hash" BIGINT NULL;
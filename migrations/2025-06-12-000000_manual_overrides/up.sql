@@ -0,0 +1,10 @@
+-- Pictures forced in or out of a group of an automatic arrangement, whatever its strategy decides
+CREATE TABLE "manual_overrides"
+(
+    "group_id"   INT4 NOT NULL,
+    "picture_id" INT8 NOT NULL,
+    "force_in"   BOOL NOT NULL,
+    PRIMARY KEY ("group_id", "picture_id"),
+    FOREIGN KEY ("group_id") REFERENCES "groups" ("id"),
+    FOREIGN KEY ("picture_id") REFERENCES "pictures" ("id")
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+UPDATE "shared_groups"
+SET "permissions" = (CASE WHEN "permissions" & 4 <> 0 THEN 1 ELSE 0 END)
+    | (CASE WHEN "permissions" & 2 <> 0 THEN 8 ELSE 0 END);
+COMMENT ON COLUMN "shared_groups"."permissions" IS NULL;
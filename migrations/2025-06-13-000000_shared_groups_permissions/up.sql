@@ -0,0 +1,8 @@
+-- Remap the share permissions to the View (1) / Edit tags (2) / Add pictures (4) bits checked on the recipients' actions.
+-- Every share grants View, "Add pictures" (bit 0) becomes Add pictures and "Edit picture" (bit 3) becomes Edit tags.
+-- "Share back", "Edit exif" and "Delete" (bits 1, 2 and 4) were never enforced and are dropped.
+UPDATE "shared_groups"
+SET "permissions" = 1
+    | (CASE WHEN "permissions" & 8 <> 0 THEN 2 ELSE 0 END)
+    | (CASE WHEN "permissions" & 1 <> 0 THEN 4 ELSE 0 END);
+COMMENT ON COLUMN "shared_groups"."permissions" IS 'Bits : View / Edit tags / Add pictures';
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "pictures_tags"
+    DROP COLUMN IF EXISTS "assignment_order";
@@ -0,0 +1,4 @@
+-- Order in which the tags have been assigned to the pictures, the compliance repair keeping the most recently assigned tag.
+-- The existing assignments are numbered in no particular order.
+ALTER TABLE "pictures_tags"
+    ADD COLUMN "assignment_order" BIGSERIAL NOT NULL;
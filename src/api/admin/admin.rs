@@ -0,0 +1,243 @@
+use crate::database::database::{DBConn, DBPool};
+use crate::database::group::sharing_consistency::SharingConsistencyReport;
+use crate::database::integrity_scan::IntegrityReport;
+use crate::database::migrations::{MigrationsStatus, MIGRATIONS};
+use crate::database::picture::picture::Picture;
+use crate::database::schema::UserStatus;
+use crate::database::user::user::{StorageRecomputeReport, User};
+use crate::utils::auth::AdminUser;
+use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorResponse, ErrorType};
+use crate::utils::s3::PictureStorer;
+use crate::utils::thumbnail::{decode_perceptual_hash, PictureThumbnail};
+use chrono::NaiveDateTime;
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_okapi::{openapi, JsonSchema};
+
+/// Maximum number of users returned in a page of the admin users list
+pub const ADMIN_USERS_MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(JsonSchema, Serialize, Debug)]
+pub struct AdminUserData {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) creation_date: NaiveDateTime,
+    pub(crate) status: UserStatus,
+    pub(crate) tfa_login: bool,
+    pub(crate) storage_count_ko: i64,
+    pub(crate) storage_limit_ko: i64,
+}
+impl From<User> for AdminUserData {
+    fn from(user: User) -> Self {
+        AdminUserData {
+            id: user.id,
+            name: user.name,
+            email: user.email,
+            creation_date: user.creation_date,
+            status: user.status,
+            tfa_login: user.tfa_login,
+            storage_count_ko: user.storage_count_ko,
+            storage_limit_ko: user.storage_limit_ko,
+        }
+    }
+}
+
+#[derive(JsonSchema, Serialize, Debug)]
+pub struct AdminUsersResponse {
+    pub(crate) users: Vec<AdminUserData>,
+    pub(crate) total: i64,
+    pub(crate) page: i64,
+    pub(crate) page_size: i64,
+}
+
+/// List the users, for admins only.
+/// Users can be filtered by status and by a case-insensitive fragment of their email.
+/// Pages start at 1, and hold 50 users by default (at most 200).
+#[openapi(tag = "Admin")]
+#[get("/admin/users?<status>&<email_contains>&<page>&<page_size>")]
+pub async fn admin_list_users(
+    db: &State<DBPool>,
+    _admin: AdminUser,
+    status: Option<UserStatus>,
+    email_contains: Option<String>,
+    page: Option<i64>,
+    page_size: Option<i64>,
+) -> Result<Json<AdminUsersResponse>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let page = page.unwrap_or(1);
+    let page_size = page_size.unwrap_or(50);
+    if page < 1 {
+        return ErrorType::InvalidInput("Page number must be greater than 0".to_string()).res_err_no_rollback();
+    }
+    if !(1..=ADMIN_USERS_MAX_PAGE_SIZE).contains(&page_size) {
+        return ErrorType::InvalidInput(format!("Page size must be between 1 and {}", ADMIN_USERS_MAX_PAGE_SIZE)).res_err_no_rollback();
+    }
+    let email_contains = email_contains.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    let (users, total) = User::admin_search(conn, status, email_contains, page, page_size)?;
+    Ok(Json(AdminUsersResponse {
+        users: users.into_iter().map(AdminUserData::from).collect(),
+        total,
+        page,
+        page_size,
+    }))
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct StorageLimitRequest {
+    limit_ko: i64,
+    /// Apply the limit even if it is below the current storage usage of the user
+    #[serde(default)]
+    force: bool,
+}
+
+/// Set the storage limit of a user, for admins only.
+/// A limit below the current storage usage of the user is rejected unless `force` is set.
+#[openapi(tag = "Admin")]
+#[post("/admin/users/<user_id>/storage-limit", data = "<data>")]
+pub async fn admin_set_storage_limit(
+    db: &State<DBPool>,
+    _admin: AdminUser,
+    user_id: i32,
+    data: Json<StorageLimitRequest>,
+) -> Result<Json<AdminUserData>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let user = User::set_storage_limit(conn, user_id, data.limit_ko, data.force)?;
+    Ok(Json(AdminUserData::from(user)))
+}
+
+/// Number of users whose storage count is recomputed in each transaction of `/admin/recompute-storage`
+const STORAGE_RECOMPUTE_BATCH_SIZE: i64 = 500;
+
+/// Recompute the storage count of all the users from the size of their pictures out of the trash (plus the counted copied shares),
+/// and correct the drifted ones, for admins only. Returns the adjusted users with the previous and corrected counts.
+#[openapi(tag = "Admin")]
+#[post("/admin/recompute-storage")]
+pub async fn admin_recompute_storage(db: &State<DBPool>, admin: AdminUser) -> Result<Json<StorageRecomputeReport>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let report = User::recompute_all_storage(conn, STORAGE_RECOMPUTE_BATCH_SIZE)?;
+    warn!(
+        "Admin {} recomputed the storage counts: {} of {} users adjusted",
+        admin.0.id, report.adjusted_users, report.scanned_users
+    );
+    Ok(Json(report))
+}
+
+/// Number of pictures loaded in each batch of `/admin/backfill-perceptual-hashes`
+const PERCEPTUAL_HASH_BACKFILL_BATCH_SIZE: i64 = 100;
+
+/// Result of [`backfill_perceptual_hashes`].
+#[derive(JsonSchema, Serialize, Debug, PartialEq, Default)]
+pub struct PerceptualHashBackfillReport {
+    pub hashed_pictures: usize,
+    /// Pictures whose small thumbnail couldn't be read or decoded, left without perceptual hash.
+    pub failed_picture_ids: Vec<i64>,
+}
+
+/// Compute the perceptual hash of the pictures that have none, from their small thumbnail, for admins only.
+/// The pictures uploaded before near-duplicates were detected have no hash, and are never reported as similar until then.
+/// Pictures whose thumbnail can't be read are skipped and reported, running it again retries them.
+#[openapi(tag = "Admin")]
+#[post("/admin/backfill-perceptual-hashes")]
+pub async fn admin_backfill_perceptual_hashes(
+    db: &State<DBPool>,
+    admin: AdminUser,
+    picture_storer: &State<PictureStorer>,
+) -> Result<Json<PerceptualHashBackfillReport>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let report = backfill_perceptual_hashes(conn, picture_storer, PERCEPTUAL_HASH_BACKFILL_BATCH_SIZE).await?;
+    warn!(
+        "Admin {} backfilled the perceptual hashes: {} pictures hashed, {} failed",
+        admin.0.id,
+        report.hashed_pictures,
+        report.failed_picture_ids.len()
+    );
+    Ok(Json(report))
+}
+
+/// Store the perceptual hash of all the pictures that have none, computed from their small thumbnail stored in S3.
+/// Pictures are loaded by batches of `batch_size`, each hash being stored as soon as it is computed.
+pub async fn backfill_perceptual_hashes(
+    conn: &mut DBConn,
+    picture_storer: &PictureStorer,
+    batch_size: i64,
+) -> Result<PerceptualHashBackfillReport, ErrorResponder> {
+    let mut report = PerceptualHashBackfillReport::default();
+    let mut after_id = 0;
+    loop {
+        let picture_ids = Picture::without_perceptual_hash(conn, after_id, batch_size)?;
+        let Some(last_id) = picture_ids.last() else {
+            break;
+        };
+        after_id = *last_id;
+        for picture_id in picture_ids {
+            match thumbnail_perceptual_hash(picture_storer, picture_id).await {
+                Ok(perceptual_hash) => {
+                    Picture::set_perceptual_hash(conn, picture_id, perceptual_hash)?;
+                    report.hashed_pictures += 1;
+                }
+                Err(e) => {
+                    warn!("Unable to hash picture {}: {:?}", picture_id, ErrorResponse::from(e));
+                    report.failed_picture_ids.push(picture_id);
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Perceptual hash of the small thumbnail of a picture, read from S3.
+async fn thumbnail_perceptual_hash(picture_storer: &PictureStorer, picture_id: i64) -> Result<i64, ErrorResponder> {
+    let thumbnail = picture_storer
+        .get_picture(PictureThumbnail::Small, picture_id)
+        .await?
+        .collect()
+        .await
+        .map_err(|e| ErrorType::S3Error(format!("Unable to read picture: {}", e)).res_no_rollback())?;
+    decode_perceptual_hash(&thumbnail.into_bytes())
+}
+
+/// Reset the two-factor authentication of a user who lost their TOTP device, for admins only (account recovery).
+/// All the TOTP secrets of the user are deleted and 2FA is disabled at sign in: the user can then sign in with their password only.
+#[openapi(tag = "Admin")]
+#[post("/admin/users/<user_id>/reset-totp")]
+pub async fn admin_reset_totp(db: &State<DBPool>, admin: AdminUser, user_id: i32) -> Result<Json<AdminUserData>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let (user, deleted_secrets) = err_transaction(conn, |conn| User::reset_totp(conn, user_id))?;
+    warn!(
+        "Admin {} reset the 2FA of user {} ({} TOTP secrets deleted)",
+        admin.0.id, user.id, deleted_secrets
+    );
+    Ok(Json(AdminUserData::from(user)))
+}
+
+/// List the applied database migrations and the pending ones, for admins only.
+/// Migrations are run at boot, then pending migrations mean the database is not in the state expected by the server.
+#[openapi(tag = "Admin")]
+#[get("/admin/migrations")]
+pub async fn admin_migrations_status(db: &State<DBPool>, _admin: AdminUser) -> Result<Json<MigrationsStatus>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(MigrationsStatus::from_harness(conn, MIGRATIONS)?))
+}
+
+/// Check that the pictures of the groups shared with every recipient were propagated to the groups of the recipient,
+/// and that no group of a recipient holds a picture they can't access, for admins only.
+/// Nothing is changed, the anomalies are only reported.
+#[openapi(tag = "Admin")]
+#[post("/admin/verify-sharing-consistency")]
+pub async fn admin_verify_sharing_consistency(db: &State<DBPool>, _admin: AdminUser) -> Result<Json<SharingConsistencyReport>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(SharingConsistencyReport::scan(conn)?))
+}
+
+/// Scan the database for rows of `groups_pictures`, `pictures_tags`, `shared_groups` and `ratings` referencing
+/// non-existent parents, and for users whose storage count disagrees with the size of their pictures, for admins only.
+/// Nothing is changed, the anomalies are only reported.
+#[openapi(tag = "Admin")]
+#[post("/admin/integrity-scan")]
+pub async fn admin_integrity_scan(db: &State<DBPool>, _admin: AdminUser) -> Result<Json<IntegrityReport>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(IntegrityReport::scan(conn)?))
+}
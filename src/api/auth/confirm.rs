@@ -4,6 +4,7 @@ use crate::database::schema::UserStatus;
 use crate::database::user::user::User;
 use crate::database::user::{auth_token::AuthToken, confirmation::Confirmation};
 use crate::utils::auth::{DeviceInfo, UserAuthInfo};
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
 use crate::utils::utils::get_frontend_host;
 use crate::utils::validation::validate_input;
@@ -17,8 +18,8 @@ pub struct ConfirmCodeData {
     action: ConfirmationAction,
     /// token sent to the browser when the action was initiated
     code_token: String,
-    /// 4-digit code emailed to the user
-    #[validate(range(min = 0, max = 9999, message = "Code must be a 4 digit number"))]
+    /// Code emailed to the user (at most 4 digits, see `CONFIRMATION_CODE_DIGITS`)
+    #[validate(range(min = 0, max = 9999, message = "Code must be a number of at most 4 digits"))]
     code: i16,
 }
 
@@ -63,8 +64,9 @@ pub fn auth_confirm_code(
         .map_err(|_| ErrorType::UnprocessableEntity("Code token should be a hex string".to_string()).res_no_rollback())?;
 
     err_transaction(conn, |conn| {
-        let redirect_url =
-            Confirmation::check_code_and_mark_as_used(conn, &user_id, &data.action, &code_token, &data.code, 15)?.unwrap_or(get_frontend_host());
+        let max_minutes = CONFIG.confirmation_max_minutes(&data.action);
+        let redirect_url = Confirmation::check_code_and_mark_as_used(conn, &user_id, &data.action, &code_token, &data.code, max_minutes)?
+            .unwrap_or(get_frontend_host());
         confirm_execute(conn, &data.action, user, redirect_url, &device_info)
     })
 }
@@ -86,7 +88,8 @@ pub fn auth_confirm_token(
     let token = hex::decode(&data.token).map_err(|_| ErrorType::UnprocessableEntity("token should be a hex string".to_string()).res_no_rollback())?;
 
     err_transaction(conn, |conn| {
-        let redirect_url = Confirmation::check_token_and_mark_as_used(conn, &user_id, &data.action, &token, 15)?.unwrap_or(get_frontend_host());
+        let max_minutes = CONFIG.confirmation_max_minutes(&data.action);
+        let redirect_url = Confirmation::check_token_and_mark_as_used(conn, &user_id, &data.action, &token, max_minutes)?.unwrap_or(get_frontend_host());
         confirm_execute(conn, &data.action, user, redirect_url, &device_info)
     })
 }
@@ -4,6 +4,7 @@ use crate::database::user::user::User;
 use crate::database::user::{auth_token::AuthToken, confirmation::Confirmation, totp_secret::TOTPSecret};
 use crate::mailing::mailer::send_rendered_email;
 use crate::utils::auth::DeviceInfo;
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
 use crate::utils::utils::{get_frontend_host, left_pad};
 use pwhash::bcrypt;
@@ -27,6 +28,8 @@ pub struct SigninResponse {
     pub name: String,
     pub email: String,
     pub auth_token: String,
+    /// The user never signed in from this device (same device string and IP address) before, a notification email has been sent
+    pub new_device: bool,
 }
 
 #[derive(JsonSchema, Serialize, Debug)]
@@ -37,6 +40,8 @@ pub struct SigninEmailResponse {
 
 /// Endpoint to sign in a user.
 /// If the user requires 2FA, it will either throw `TFARequired`, `TFARequiredOverEmail` or `InvalidTOTPCode`.
+/// Users with email 2FA enabled and without TOTP always get `TFARequiredOverEmail`, and must sign in with `/auth/signin/email`.
+/// If the user never signed in from this device before, they are warned by email.
 #[openapi(tag = "Authentication")]
 #[post("/auth/signin", data = "<data>")]
 pub fn auth_signin(data: Json<SigninData>, db: &rocket::State<DBPool>, device_info: DeviceInfo) -> Result<Json<SigninResponse>, ErrorResponder> {
@@ -45,21 +50,21 @@ pub fn auth_signin(data: Json<SigninData>, db: &rocket::State<DBPool>, device_in
     err_transaction(conn, |conn| {
         let user = check_user_password_and_status(conn, &data.email, &data.password)?;
 
-        if user.tfa_login {
-            if let Some(totp_code) = &data.totp_code {
-                if !TOTPSecret::check_user_totp(conn, &user.id, totp_code)? {
-                    return ErrorType::InvalidTOTPCode.res_err_no_rollback();
-                }
-            } else {
-                // 2FA Required, checking if TOTP is available
-                if TOTPSecret::has_user_totp(conn, &user.id)? {
-                    return ErrorType::TFARequired.res_err_no_rollback();
-                }
-                return ErrorType::TFARequiredOverEmail.res_err_no_rollback();
-            }
+        if user.tfa_login || user.email_tfa {
+            let totp_code_valid = match &data.totp_code {
+                Some(totp_code) if user.tfa_login => Some(TOTPSecret::check_user_totp(conn, &user.id, totp_code)?),
+                _ => None,
+            };
+            // 2FA Required without code, checking if TOTP is available
+            let has_totp = totp_code_valid.is_none() && TOTPSecret::has_user_totp(conn, &user.id)?;
+            check_second_factor(user.tfa_login, user.email_tfa, has_totp, totp_code_valid)?;
         }
 
+        let new_device = !AuthToken::has_known_device(conn, &user.id, &device_info)?;
         let auth_token = AuthToken::insert_token_for_user(conn, &user.id, &device_info, 0)?;
+        if new_device {
+            send_new_signin_email(&user, &device_info);
+        }
 
         Ok(Json(SigninResponse {
             status: user.status,
@@ -67,10 +72,34 @@ pub fn auth_signin(data: Json<SigninData>, db: &rocket::State<DBPool>, device_in
             name: user.name,
             email: user.email,
             auth_token: hex::encode(auth_token),
+            new_device,
         }))
     })
 }
 
+/// Check the second factor of a user signing in: nothing is required if 2FA is disabled, otherwise the TOTP code
+/// must be valid (`totp_code_valid` is `None` when no code was given), or a code is required, over email if the user has no TOTP.
+/// With `email_tfa`, a user without TOTP needs the code sent over email even if 2FA is disabled.
+pub fn check_second_factor(tfa_login: bool, email_tfa: bool, has_totp: bool, totp_code_valid: Option<bool>) -> Result<(), ErrorResponder> {
+    match (tfa_login, totp_code_valid) {
+        (false, _) if email_tfa && !has_totp => ErrorType::TFARequiredOverEmail.res_err_no_rollback(),
+        (false, _) | (true, Some(true)) => Ok(()),
+        (true, Some(false)) => ErrorType::InvalidTOTPCode.res_err_no_rollback(),
+        (true, None) if has_totp => ErrorType::TFARequired.res_err_no_rollback(),
+        (true, None) => ErrorType::TFARequiredOverEmail.res_err_no_rollback(),
+    }
+}
+
+/// Warn the user that their account has been signed in from a new device.
+fn send_new_signin_email(user: &User, device_info: &DeviceInfo) {
+    let subject = "New sign in to your account".to_string();
+    let mut context = tera::Context::new();
+    context.insert("name", &user.name);
+    context.insert("ip", &device_info.ip_address.map(|ip| ip.to_string()).unwrap_or("Unknown".to_string()));
+    context.insert("agent", &device_info.device_string);
+    send_rendered_email((user.name.clone(), user.email.clone()), subject, "new_signin".to_string(), context);
+}
+
 /// Login endpoint for users that require 2FA; sends a confirmation email.
 #[openapi(tag = "Authentication")]
 #[post("/auth/signin/email", data = "<data>")]
@@ -85,7 +114,7 @@ pub fn auth_signin_email(
 
         let (token, code_token, code) =
             Confirmation::insert_confirmation(conn, user.id, ConfirmationAction::Signin, &device_info, &data.redirect_url, 0)?;
-        let code_str = left_pad(&code.to_string(), '0', 4);
+        let code_str = left_pad(&code.to_string(), '0', CONFIG.confirmation_code_digits as usize);
 
         // Sending email
         let signin_url = format!("{}/signin?id={}&token={}", get_frontend_host(), user.id, hex::encode(&token));
@@ -96,6 +125,7 @@ pub fn auth_signin_email(
         context.insert("code", &code_str);
         context.insert("ip", &device_info.ip_address.map(|ip| ip.to_string()).unwrap_or("Unknown".to_string()));
         context.insert("agent", &device_info.device_string);
+        context.insert("expiry_minutes", &CONFIG.confirmation_max_minutes(&ConfirmationAction::Signin));
         send_rendered_email((user.name.clone(), data.email.clone()), subject, "confirm_signin".to_string(), context);
 
         Ok(Json(SigninEmailResponse {
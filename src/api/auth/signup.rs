@@ -7,8 +7,10 @@ use crate::database::database::DBPool;
 use crate::database::schema::ConfirmationAction;
 use crate::database::user::confirmation::Confirmation;
 use crate::database::user::user::User;
+use crate::grouping::default_arrangements::create_default_arrangements;
 use crate::mailing::mailer::send_rendered_email;
 use crate::utils::auth::DeviceInfo;
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{err_transaction, ErrorResponder};
 use crate::utils::utils::{get_frontend_host, left_pad};
 use crate::utils::validation::validate_input;
@@ -35,6 +37,7 @@ pub struct SignupResponse {
 
 /// Endpoint to register a new user account.
 /// A confirmation entry will be added to the database, and an email will be sent to the user.
+/// The default arrangements are created for new users (see [`create_default_arrangements`]).
 #[openapi(tag = "Authentication")]
 #[post("/auth/signup", data = "<data>")]
 pub fn auth_signup(data: Json<SignupData>, db: &rocket::State<DBPool>, device_info: DeviceInfo) -> Result<Json<SignupResponse>, ErrorResponder> {
@@ -44,11 +47,12 @@ pub fn auth_signup(data: Json<SignupData>, db: &rocket::State<DBPool>, device_in
     err_transaction(conn, |conn| {
         // Inserting user
         let uid = User::create_user(conn, &data.name, &data.email, &data.password)?;
+        create_default_arrangements(conn, uid)?;
 
         // Inserting confirmation
         let (confirm_token, confirm_code_token, confirm_code) =
             Confirmation::insert_confirmation(conn, uid, ConfirmationAction::Signup, &device_info, &data.redirect_url, 0)?;
-        let confirm_code_str = left_pad(&confirm_code.to_string(), '0', 4);
+        let confirm_code_str = left_pad(&confirm_code.to_string(), '0', CONFIG.confirmation_code_digits as usize);
 
         // Sending email
         let signup_url = format!("{}/signup?id={}&token={}", get_frontend_host(), uid, hex::encode(&confirm_token));
@@ -59,6 +63,7 @@ pub fn auth_signup(data: Json<SignupData>, db: &rocket::State<DBPool>, device_in
         context.insert("code", &confirm_code_str);
         context.insert("ip", &device_info.ip_address.map(|ip| ip.to_string()).unwrap_or("Unknown".to_string()));
         context.insert("agent", &device_info.device_string);
+        context.insert("expiry_minutes", &CONFIG.confirmation_max_minutes(&ConfirmationAction::Signup));
         send_rendered_email((data.name.clone(), data.email.clone()), subject, "confirm_signup".to_string(), context);
 
         Ok(Json(SignupResponse {
@@ -0,0 +1,92 @@
+use crate::api::groups::manual_groups::editable_manual_group;
+use crate::api::tags::{apply_picture_tags_edition, EditPictureTagsRequest};
+use crate::database::database::{DBConn, DBPool};
+use crate::database::group::arrangement::ArrangementDependencyType;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::Picture;
+use crate::database::user::user::User;
+use crate::grouping::grouping_delta::{grouping_transaction, GroupingDelta};
+use crate::grouping::grouping_process::{group_add_pictures, group_pictures, group_remove_pictures};
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use crate::utils::validation::validate_picture_batch;
+use itertools::Itertools;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::{openapi, JsonSchema};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CurateRequest {
+    #[serde(flatten)]
+    pub tags: EditPictureTagsRequest,
+    pub add_to_group_id: Option<i32>,
+    pub remove_from_group_id: Option<i32>,
+}
+
+/// Edit the tags of pictures and move them between manual groups in a single transaction, then regroup them once.
+/// Tags are edited as with `PATCH /picture_tags`. Groups must be manual, and editable by the user as with `/group/manual/pictures`,
+/// and the pictures must be accessible to the user.
+/// If any step fails, nothing is changed.
+#[openapi(tag = "Tags")]
+#[post("/curate", data = "<data>")]
+pub async fn curate_pictures(db: &State<DBPool>, user: User, data: Json<CurateRequest>) -> Result<(), ErrorResponder> {
+    validate_picture_batch(&data.tags.picture_ids)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    if data.tags.picture_ids.is_empty() {
+        return ErrorType::UnprocessableEntity("No picture ids to curate".to_string()).res_err();
+    }
+
+    curate(conn, user.id, &data)
+}
+
+/// Apply the curation in a single transaction, rolled back if any step fails.
+pub fn curate(conn: &mut DBConn, user_id: i32, request: &CurateRequest) -> Result<(), ErrorResponder> {
+    let picture_ids = request.tags.picture_ids.iter().cloned().unique().collect_vec();
+
+    grouping_transaction(conn, |conn, delta| {
+        apply_picture_tags_edition(conn, user_id, &request.tags)?;
+
+        if let Some(group_id) = request.add_to_group_id {
+            rollback_on_error(curate_group(conn, delta, user_id, group_id, &picture_ids, true))?;
+        }
+        if let Some(group_id) = request.remove_from_group_id {
+            rollback_on_error(curate_group(conn, delta, user_id, group_id, &picture_ids, false))?;
+        }
+
+        // Regroup the pictures once for both the tags and the groups changes
+        let dependency_type = ArrangementDependencyType {
+            groups_dependant: true,
+            tags_dependant: true,
+            exif_dependant: false,
+            ratings_dependant: false,
+        };
+        group_pictures(conn, delta, user_id, Some(&picture_ids), None, Some(&dependency_type), true)?;
+        Ok(())
+    })
+}
+
+/// Add or remove pictures of a manual group, checking the user can edit the group and access the pictures.
+fn curate_group(
+    conn: &mut DBConn,
+    delta: &mut GroupingDelta,
+    user_id: i32,
+    group_id: i32,
+    picture_ids: &Vec<i64>,
+    add: bool,
+) -> Result<(), ErrorResponder> {
+    let group = Group::from_id(conn, group_id)?;
+    let group = editable_manual_group(conn, user_id, group.id, group.arrangement_id)?;
+    Picture::require_all_accessible(picture_ids, &Picture::filter_user_accessible_pictures(conn, user_id, picture_ids)?)?;
+    if add {
+        group_add_pictures(conn, delta, group.id, picture_ids)?;
+        Ok(())
+    } else {
+        group_remove_pictures(conn, delta, group.id, picture_ids)
+    }
+}
+
+/// The tags have already been edited when the group steps run: any of their errors must roll the edition back,
+/// including the ones that usually don't need to (e.g. a non-manual group).
+pub(crate) fn rollback_on_error<T>(result: Result<T, ErrorResponder>) -> Result<T, ErrorResponder> {
+    result.map_err(|e| e.with_rollback(true))
+}
@@ -0,0 +1,39 @@
+use crate::database::user::user::User;
+use crate::grouping::grouping_delta::GroupingProgressChannels;
+use rocket::futures::stream::{BoxStream, StreamExt};
+use rocket::response::stream::{stream, Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Shutdown, State};
+use rocket_okapi::openapi;
+
+/// Server-sent events stream of the progress of the user's arrangement-wide grouping operations
+/// (regroup-all, arrangement creation, edition and re-enabling). Previews don't report their progress.
+/// Once the operation is committed, a `grouping_progress` event is sent for each arrangement that has been grouped, with the number
+/// of arrangements processed out of the total of the operation. Nothing is sent for rolled back operations.
+/// Events sent while the client lags behind are skipped.
+#[openapi(tag = "Events")]
+#[get("/events")]
+pub async fn grouping_events(
+    progress_channels: &State<GroupingProgressChannels>,
+    user: User,
+    mut shutdown: Shutdown,
+) -> EventStream<BoxStream<'static, Event>> {
+    let mut receiver = progress_channels.subscribe(user.id);
+    EventStream::from(
+        stream! {
+            loop {
+                let progress = select! {
+                    progress = receiver.recv() => match progress {
+                        Ok(progress) => progress,
+                        Err(RecvError::Closed) => break,
+                        Err(RecvError::Lagged(_)) => continue,
+                    },
+                    _ = &mut shutdown => break,
+                };
+                yield Event::json(&progress).event("grouping_progress");
+            }
+        }
+        .boxed(),
+    )
+}
@@ -1,4 +1,5 @@
-use crate::database::database::DBPool;
+use crate::api::tags::{add_tags_to_all_pictures, insert_tag_group_with_tags};
+use crate::database::database::{DBConn, DBPool};
 use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType};
 use crate::database::group::group::Group;
 use crate::database::group::link_share_group::LinkShareGroups;
@@ -6,7 +7,14 @@ use crate::database::group::shared_group::SharedGroup;
 use crate::database::hierarchy::hierarchy_arrangement::HierarchyArrangements;
 use crate::database::user::user::User;
 use crate::grouping::arrangement_strategy::{ArrangementStrategy, ArrangementStrategyRequest};
-use crate::grouping::grouping_process::{group_clear_pictures, group_pictures};
+use crate::grouping::arrangement_template::{ArrangementTemplate, TemplateReferences};
+use crate::grouping::dependency_graph::DependencyGraph;
+use crate::grouping::grouping_delta::{
+    grouping_transaction, grouping_transaction_reporting, lock_user_grouping, preview_transaction, GroupingDelta, GroupingPreview,
+    GroupingProgressChannels,
+};
+use crate::grouping::grouping_process::{group_clear_pictures, group_pictures, regroup_all};
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
 use diesel_derives::{Associations, Identifiable, Queryable, Selectable};
 use itertools::Itertools;
@@ -15,6 +23,7 @@ use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::State;
 use rocket_okapi::{openapi, JsonSchema};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct ArrangementRequest {
@@ -22,12 +31,46 @@ pub struct ArrangementRequest {
     name: String,
     strategy: Option<ArrangementStrategyRequest>,
 }
-#[derive(Serialize, JsonSchema)]
+#[derive(Deserialize, JsonSchema)]
+pub struct EditArrangementRequest {
+    #[serde(flatten)]
+    arrangement: ArrangementRequest,
+    /// Edition version of the arrangement the edition is based on, as returned when listing arrangements
+    edition_version: i32,
+}
+#[derive(Deserialize, JsonSchema)]
+pub struct MergeArrangementsRequest {
+    source_id: i32,
+    target_id: i32,
+}
+#[derive(Deserialize, JsonSchema)]
+pub struct ArrangementFromTemplateRequest {
+    template: ArrangementTemplate,
+    /// Create the tag groups of the template the user doesn't have, instead of failing
+    #[serde(default)]
+    create_missing_tag_groups: bool,
+}
+#[derive(Deserialize, JsonSchema)]
+pub struct ArrangementEnabledRequest {
+    enabled: bool,
+}
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
 pub struct ArrangementResponse {
     arrangement: ArrangementResponseArrangement,
     groups: Vec<Group>,
     to_be_deleted_groups: Vec<Group>,
 }
+impl ArrangementResponse {
+    /// Build the response from the arrangement and all its groups, separating the groups marked as to be deleted.
+    pub fn from_arrangement_and_groups(arrangement: Arrangement, groups: Vec<Group>) -> Result<Self, ErrorResponder> {
+        let (to_be_deleted_groups, groups) = groups.into_iter().partition(|g| g.to_be_deleted);
+        Ok(ArrangementResponse {
+            arrangement: ArrangementResponseArrangement::try_from(arrangement)?,
+            groups,
+            to_be_deleted_groups,
+        })
+    }
+}
 
 #[derive(Debug, PartialEq, Clone, JsonSchema, Serialize)]
 pub struct ArrangementResponseArrangement {
@@ -36,6 +79,8 @@ pub struct ArrangementResponseArrangement {
     pub name: String,
     pub strong_match_conversion: bool,
     pub strategy: Option<ArrangementStrategy>,
+    pub edition_version: i32,
+    pub enabled: bool,
 }
 impl TryFrom<Arrangement> for ArrangementResponseArrangement {
     type Error = ErrorResponder;
@@ -46,10 +91,32 @@ impl TryFrom<Arrangement> for ArrangementResponseArrangement {
             strategy: arrangement.get_strategy()?,
             name: arrangement.name,
             strong_match_conversion: arrangement.strong_match_conversion,
+            edition_version: arrangement.edition_version,
+            enabled: arrangement.enabled,
         })
     }
 }
 
+/// Groups of an arrangement regrouped by [`regroup_all_arrangements`], with their number of pictures.
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+pub struct RegroupedArrangement {
+    pub arrangement_id: i32,
+    /// Number of pictures of each group of the arrangement, by group id
+    pub pictures_counts: BTreeMap<i32, i64>,
+}
+impl RegroupedArrangement {
+    /// Build the counts of the groups of an arrangement from the counts of the non-empty groups.
+    pub fn from_counts(arrangement_id: i32, groups: &[Group], counts: &HashMap<i32, i64>) -> Self {
+        RegroupedArrangement {
+            arrangement_id,
+            pictures_counts: groups
+                .iter()
+                .map(|group| (group.id, counts.get(&group.id).copied().unwrap_or(0)))
+                .collect(),
+        }
+    }
+}
+
 /// List all user’s arrangements
 #[openapi(tag = "Arrangement")]
 #[get("/arrangement")]
@@ -59,103 +126,421 @@ pub async fn list_arrangements(db: &State<DBPool>, user: User) -> Result<Json<Ve
 
     let arrangements = arrangements_with_groups
         .into_iter()
-        .map(|(arrangement, groups)| {
-            Ok(ArrangementResponse {
-                arrangement: ArrangementResponseArrangement::try_from(arrangement)?,
-                groups: groups.iter().filter(|g| !g.to_be_deleted).cloned().collect_vec(),
-                to_be_deleted_groups: groups.into_iter().filter(|g| g.to_be_deleted).collect_vec(),
-            })
-        })
+        .map(|(arrangement, groups)| ArrangementResponse::from_arrangement_and_groups(arrangement, groups))
         .collect::<Result<Vec<_>, ErrorResponder>>()?;
 
     Ok(Json(arrangements))
 }
 
+/// Get the dependency graph of the user’s non-manual arrangements: an arrangement depends on another one
+/// when its filter includes groups of the other arrangement. Arrangements of a cycle are grouped in an arbitrary order.
+#[openapi(tag = "Arrangement")]
+#[get("/arrangement/dependency-graph")]
+pub async fn get_arrangements_dependency_graph(db: &State<DBPool>, user: User) -> Result<Json<DependencyGraph>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let arrangements = Arrangement::list_arrangements_and_groups(conn, user.id)?;
+    Ok(Json(DependencyGraph::new(&arrangements)))
+}
+
+/// Get a single user’s arrangement, with its groups
+#[openapi(tag = "Arrangement")]
+#[get("/arrangement/<arrangement_id>")]
+pub async fn get_arrangement(db: &State<DBPool>, user: User, arrangement_id: i32) -> Result<Json<ArrangementResponse>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user.id)?;
+    let groups = Group::from_arrangement_all(conn, arrangement.id)?;
+
+    Ok(Json(ArrangementResponse::from_arrangement_and_groups(arrangement, groups)?))
+}
+
 /// Create a new arrangement
 #[openapi(tag = "Arrangement")]
 #[post("/arrangement", data = "<data>")]
-pub async fn create_arrangement(db: &State<DBPool>, user: User, data: Json<ArrangementRequest>) -> Result<Json<ArrangementResponse>, ErrorResponder> {
+pub async fn create_arrangement(
+    db: &State<DBPool>,
+    progress_channels: &State<GroupingProgressChannels>,
+    user: User,
+    data: Json<ArrangementRequest>,
+) -> Result<Json<ArrangementResponse>, ErrorResponder> {
     let mut conn = &mut db.get().unwrap();
 
-    err_transaction(&mut conn, |conn| {
-        // Create the arrangement and persist it in the database
-        let mut arrangement = Arrangement::new(conn, user.id, data.name.clone(), data.strong_match_conversion, None)?;
-
-        // Create strategy (will eventually create groups, needs to be done after having created the arrangement)
-        let strategy = match &data.strategy {
-            Some(strategy_req) => Some(strategy_req.create(conn, arrangement.id)?),
-            None => None,
-        };
-
-        if strategy.is_some() {
-            // Save strategy in the arrangement (will also set the dependency types)
-            arrangement.set_strategy(conn, strategy.clone())?;
-            // Group all pictures according to the strategy
-            group_pictures(conn, user.id, None, Some(arrangement.id), None, false)?;
+    grouping_transaction_reporting(&mut conn, progress_channels, user.id, |conn, delta| {
+        // Holding the grouping lock, no concurrent request of the user can take the name before the arrangement is inserted
+        lock_user_grouping(conn, user.id)?;
+        Arrangement::check_name_available(conn, user.id, &data.name, None)?;
+        Ok(Json(insert_arrangement(
+            conn,
+            delta,
+            user.id,
+            data.name.clone(),
+            data.strong_match_conversion,
+            data.strategy.as_ref(),
+        )?))
+    })
+}
+
+/// Get the template of an arrangement: a portable version of the arrangement that can be recreated for another user,
+/// where tags, tag groups and groups are referenced by name. The referenced tag groups are included with their tags.
+#[openapi(tag = "Arrangement")]
+#[get("/arrangement/<arrangement_id>/template")]
+pub async fn get_arrangement_template(db: &State<DBPool>, user: User, arrangement_id: i32) -> Result<Json<ArrangementTemplate>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user.id)?;
+    let references = TemplateReferences::load(conn, user.id)?;
+    Ok(Json(ArrangementTemplate::export(&arrangement, &references)?))
+}
+
+/// Create an arrangement from a template (see [`get_arrangement_template`]), resolving its references by name among the user’s
+/// tags and groups. Missing tag groups are created with their tags if `create_missing_tag_groups` is set,
+/// otherwise any unresolved reference fails with `UnprocessableEntity`.
+#[openapi(tag = "Arrangement")]
+#[post("/arrangement/from-template", data = "<request>")]
+pub async fn create_arrangement_from_template(
+    db: &State<DBPool>,
+    progress_channels: &State<GroupingProgressChannels>,
+    user: User,
+    request: Json<ArrangementFromTemplateRequest>,
+) -> Result<Json<ArrangementResponse>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let template = &request.template;
+
+    grouping_transaction_reporting(conn, progress_channels, user.id, |conn, delta| {
+        lock_user_grouping(conn, user.id)?;
+        Arrangement::check_name_available(conn, user.id, &template.name, None)?;
+        let mut references = TemplateReferences::load(conn, user.id)?;
+        if request.create_missing_tag_groups {
+            let inserted = template
+                .missing_tag_groups(&references)
+                .into_iter()
+                .map(|tag_group| insert_tag_group_with_tags(conn, user.id, tag_group.to_tag_group_with_tags(user.id)))
+                .collect::<Result<Vec<_>, ErrorResponder>>()?;
+
+            let default_tag_ids = inserted
+                .iter()
+                .flat_map(|tgwt| tgwt.tags.iter())
+                .filter(|tag| tag.is_default)
+                .map(|tag| tag.id)
+                .collect_vec();
+            add_tags_to_all_pictures(conn, user.id, &default_tag_ids)?;
+            references.tag_groups.extend(inserted);
         }
+        let strategy = template.strategy_request(&references)?;
+
+        Ok(Json(insert_arrangement(
+            conn,
+            delta,
+            user.id,
+            template.name.clone(),
+            template.strong_match_conversion,
+            strategy.as_ref(),
+        )?))
+    })
+}
 
-        Ok(Json(ArrangementResponse {
-            groups: Group::from_arrangement(conn, arrangement.id, false)?,
-            arrangement: ArrangementResponseArrangement {
-                id: arrangement.id,
-                user_id: arrangement.user_id,
-                name: arrangement.name,
-                strong_match_conversion: arrangement.strong_match_conversion,
-                strategy,
-            },
-            to_be_deleted_groups: vec![],
-        }))
+/// Insert an arrangement with its strategy groups, and group all the user’s pictures according to the strategy.
+/// Refused if the user already has the maximum number of arrangements.
+/// Must run in a transaction: the user’s grouping lock is held until its end so that concurrent inserts can't exceed the limit.
+pub(crate) fn insert_arrangement(
+    conn: &mut DBConn,
+    delta: &mut GroupingDelta,
+    user_id: i32,
+    name: String,
+    strong_match_conversion: bool,
+    strategy_request: Option<&ArrangementStrategyRequest>,
+) -> Result<ArrangementResponse, ErrorResponder> {
+    lock_user_grouping(conn, user_id)?;
+    Arrangement::check_user_limit(Arrangement::count_for_user(conn, user_id)?, CONFIG.max_arrangements_per_user)?;
+    // Create the arrangement and persist it in the database
+    let mut arrangement = Arrangement::new(conn, user_id, name, strong_match_conversion, None)?;
+
+    // Create strategy (will eventually create groups, needs to be done after having created the arrangement)
+    let strategy = match strategy_request {
+        Some(strategy_req) => Some(strategy_req.create(conn, arrangement.id)?),
+        None => None,
+    };
+
+    if strategy.is_some() {
+        // Save strategy in the arrangement (will also set the dependency types)
+        arrangement.set_strategy(conn, strategy.clone())?;
+        // Group all pictures according to the strategy
+        group_pictures(conn, delta, user_id, None, Some(arrangement.id), None, false)?;
+    }
+
+    Ok(ArrangementResponse {
+        groups: Group::from_arrangement(conn, arrangement.id, false)?,
+        arrangement: ArrangementResponseArrangement {
+            id: arrangement.id,
+            user_id: arrangement.user_id,
+            name: arrangement.name,
+            strong_match_conversion: arrangement.strong_match_conversion,
+            strategy,
+            edition_version: arrangement.edition_version,
+            enabled: arrangement.enabled,
+        },
+        to_be_deleted_groups: vec![],
     })
 }
 
 /// Edit an arrangement
+/// The edition is based on the provided edition version of the arrangement: if the arrangement has been edited since then,
+/// a `Conflict` error is returned and the client must reload the arrangement before retrying.
 #[openapi(tag = "Arrangement")]
-#[patch("/arrangement/<arrangement_id>", data = "<request>")]
+#[patch("/arrangement/<arrangement_id>", data = "<edit_request>")]
 pub async fn edit_arrangement(
     db: &State<DBPool>,
+    progress_channels: &State<GroupingProgressChannels>,
     user: User,
     arrangement_id: i32,
-    request: Json<ArrangementRequest>,
+    edit_request: Json<EditArrangementRequest>,
 ) -> Result<Json<ArrangementResponse>, ErrorResponder> {
     let mut conn = &mut db.get().unwrap();
     let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user.id)?;
+    arrangement.check_edition_version(edit_request.edition_version)?;
+    let request = &edit_request.arrangement;
+
+    grouping_transaction_reporting(&mut conn, progress_channels, user.id, |conn, delta| {
+        lock_user_grouping(conn, user.id)?;
+        Arrangement::check_name_available(conn, user.id, &request.name, Some(arrangement.id))?;
+        let response = apply_arrangement_edit(
+            conn,
+            delta,
+            user.id,
+            &arrangement,
+            &request.name,
+            request.strong_match_conversion,
+            request.strategy.as_ref(),
+        )?;
+        Ok(Json(response))
+    })
+}
+
+/// Edit the arrangement (whose edition version has been checked) and its groups, then regroup all the pictures in it if it is not manual.
+pub fn apply_arrangement_edit(
+    conn: &mut DBConn,
+    delta: &mut GroupingDelta,
+    user_id: i32,
+    arrangement: &Arrangement,
+    name: &String,
+    strong_match_conversion: bool,
+    strategy_request: Option<&ArrangementStrategyRequest>,
+) -> Result<ArrangementResponse, ErrorResponder> {
+    // 1. Update the groups of the arrangement due to the strategy change (marks old groups as "to be deleted", and create the required new ones).
+    let new_strategy = edit_strategy(conn, arrangement, strategy_request)?;
+
+    // 2. Update the arrangement in the database
+    let arrangement = Arrangement::update(
+        conn,
+        arrangement.id,
+        arrangement.edition_version,
+        name,
+        strong_match_conversion,
+        &new_strategy,
+    )?;
 
-    err_transaction(&mut conn, |conn| {
-        // 1. Update the groups of the arrangement due to the strategy change (marks old groups as "to be deleted", and create the required new ones).
-        let new_strategy = match (arrangement.get_strategy()?, &request.strategy) {
-            (Some(old_strategy), Some(new_strategy_req)) => Some(new_strategy_req.edit(conn, arrangement.id, old_strategy)?),
-            (None, Some(new_strategy)) => {
-                Group::mark_all_as_to_be_deleted(conn, arrangement.id)?;
-                Some(new_strategy.create(conn, arrangement.id)?)
+    // 4. Check all pictures against this edited arrangement
+    if new_strategy.is_some() {
+        // Arrangement is not manual -> act like if the arrangement was just created
+        group_pictures(conn, delta, user_id, None, Some(arrangement.id), None, true)?;
+    }
+
+    let groups = Group::from_arrangement_all(conn, arrangement.id)?;
+    let not_to_be_deleted_groups = groups.iter().filter(|g| !g.to_be_deleted).cloned().collect_vec();
+    let to_be_deleted_groups = groups.iter().filter(|g| g.to_be_deleted).cloned().collect_vec();
+
+    Ok(ArrangementResponse {
+        arrangement: ArrangementResponseArrangement {
+            id: arrangement.id,
+            user_id: arrangement.user_id,
+            name: arrangement.name,
+            strong_match_conversion: arrangement.strong_match_conversion,
+            strategy: new_strategy,
+            edition_version: arrangement.edition_version,
+            enabled: arrangement.enabled,
+        },
+        groups: not_to_be_deleted_groups,
+        to_be_deleted_groups,
+    })
+}
+
+/// Preview the group membership changes of an edition of the arrangement strategy, without applying it.
+/// The edition and the grouping are run in a transaction that is rolled back, only the number of pictures
+/// that would be added to and removed from each group of the user is returned, the new groups being referenced by name.
+#[openapi(tag = "Arrangement")]
+#[post("/arrangement/<arrangement_id>/preview-edit", data = "<strategy>")]
+pub async fn preview_arrangement_edit(
+    db: &State<DBPool>,
+    user: User,
+    arrangement_id: i32,
+    strategy: Json<ArrangementStrategyRequest>,
+) -> Result<Json<GroupingPreview>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user.id)?;
+
+    Ok(Json(preview_strategy_edit(conn, user.id, &arrangement, &strategy)?))
+}
+
+/// Count the pictures the edition of the arrangement strategy would add to and remove from each group of the user,
+/// by applying it in a transaction that is rolled back.
+pub fn preview_strategy_edit(
+    conn: &mut DBConn,
+    user_id: i32,
+    arrangement: &Arrangement,
+    strategy: &ArrangementStrategyRequest,
+) -> Result<GroupingPreview, ErrorResponder> {
+    let ((group_ids, new_groups), delta) = preview_transaction(conn, |conn, delta| {
+        let group_ids = Group::from_user_id_all(conn, user_id)?
+            .into_iter()
+            .map(|group| group.id)
+            .collect::<HashSet<i32>>();
+        apply_arrangement_edit(
+            conn,
+            delta,
+            user_id,
+            arrangement,
+            &arrangement.name,
+            arrangement.strong_match_conversion,
+            Some(strategy),
+        )?;
+        // The groups created by the edit are rolled back with it, they are identified by their name
+        let new_groups = Group::from_user_id_all(conn, user_id)?
+            .into_iter()
+            .filter(|group| !group_ids.contains(&group.id))
+            .map(|group| (group.id, group.name))
+            .collect::<HashMap<i32, String>>();
+        Ok((group_ids, new_groups))
+    })?;
+    Ok(delta.preview(&group_ids, &new_groups))
+}
+
+/// Update the groups of the arrangement for its new strategy: old groups are marked as "to be deleted" and the required new ones are created.
+/// Returns the new strategy, `None` for a manual arrangement.
+fn edit_strategy(
+    conn: &mut DBConn,
+    arrangement: &Arrangement,
+    strategy_request: Option<&ArrangementStrategyRequest>,
+) -> Result<Option<ArrangementStrategy>, ErrorResponder> {
+    Ok(match (arrangement.get_strategy()?, strategy_request) {
+        (Some(old_strategy), Some(new_strategy_req)) => Some(new_strategy_req.edit(conn, arrangement.id, old_strategy)?),
+        (None, Some(new_strategy)) => {
+            Group::mark_all_as_to_be_deleted(conn, arrangement.id)?;
+            Some(new_strategy.create(conn, arrangement.id)?)
+        }
+        // When switching to manual arrangement. No need to mark old groups as "to be deleted", they will stay as the new manual groups.
+        (Some(_), None) | (None, None) => None,
+    })
+}
+
+/// Enable or disable an arrangement.
+/// A disabled arrangement is skipped by the grouping process: its groups are kept as they are but receive no new pictures.
+/// Enabling it again rebuilds its groups from all the pictures.
+#[openapi(tag = "Arrangement")]
+#[patch("/arrangement/<arrangement_id>/enabled", data = "<request>")]
+pub async fn set_arrangement_enabled(
+    db: &State<DBPool>,
+    progress_channels: &State<GroupingProgressChannels>,
+    user: User,
+    arrangement_id: i32,
+    request: Json<ArrangementEnabledRequest>,
+) -> Result<Json<ArrangementResponseArrangement>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user.id)?;
+
+    grouping_transaction_reporting(conn, progress_channels, user.id, |conn, delta| {
+        let arrangement = apply_arrangement_enabled(conn, delta, user.id, &arrangement, request.enabled)?;
+        Ok(Json(ArrangementResponseArrangement::try_from(arrangement)?))
+    })
+}
+
+/// Enable or disable the arrangement, regrouping all the pictures in it if it is enabled again and not manual (see [`set_arrangement_enabled`]).
+pub fn apply_arrangement_enabled(
+    conn: &mut DBConn,
+    delta: &mut GroupingDelta,
+    user_id: i32,
+    arrangement: &Arrangement,
+    enabled: bool,
+) -> Result<Arrangement, ErrorResponder> {
+    let was_enabled = arrangement.enabled;
+    let arrangement = Arrangement::set_enabled(conn, arrangement.id, enabled)?;
+
+    if arrangement.enabled && !was_enabled && arrangement.strategy.is_some() {
+        group_pictures(conn, delta, user_id, None, Some(arrangement.id), None, true)?;
+    }
+    Ok(arrangement)
+}
+
+/// Re-derive the dependency flags of all user’s arrangements from their strategy, fixing the ones that are out of sync.
+/// Returns the number of arrangements that have been corrected.
+#[openapi(tag = "Arrangement")]
+#[post("/arrangement/recompute-dependencies")]
+pub async fn recompute_arrangements_dependencies(db: &State<DBPool>, user: User) -> Result<Json<usize>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+
+    err_transaction(conn, |conn| {
+        let mut corrected = 0;
+        for mut arrangement in Arrangement::from_user_id(conn, user.id)? {
+            if arrangement.recompute_dependency_flags()? {
+                arrangement.update_dependency_flags(conn)?;
+                corrected += 1;
             }
-            // When switching to manual arrangement. No need to mark old groups as "to be deleted", they will stay as the new manual groups.
-            (Some(_), None) | (None, None) => None,
-        };
+        }
+        Ok(Json(corrected))
+    })
+}
 
-        // 2. Update the arrangement in the database
-        let arrangement = Arrangement::update(conn, arrangement.id, &request.name, request.strong_match_conversion, &new_strategy)?;
+/// Regroup all the pictures in all the user's enabled arrangements, manual arrangements excepted, in a single transaction:
+/// pictures are added to the groups they match and removed from the ones they no longer match.
+/// Returns the number of pictures of each group (the ones marked as to be deleted excepted) of the regrouped arrangements.
+#[openapi(tag = "Arrangement")]
+#[post("/regroup-all")]
+pub async fn regroup_all_arrangements(
+    db: &State<DBPool>,
+    progress_channels: &State<GroupingProgressChannels>,
+    user: User,
+) -> Result<Json<Vec<RegroupedArrangement>>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
 
-        // 4. Check all pictures against this edited arrangement
-        if new_strategy.is_some() {
-            // Arrangement is not manual -> act like if the arrangement was just created
-            group_pictures(conn, user.id, None, Some(arrangement.id), None, true)?;
+    grouping_transaction_reporting(conn, progress_channels, user.id, |conn, delta| {
+        let arrangement_ids = regroup_all(conn, delta, user.id)?;
+        let mut regrouped = Vec::with_capacity(arrangement_ids.len());
+        for arrangement_id in arrangement_ids {
+            let groups = Group::from_arrangement(conn, arrangement_id, false)?;
+            let group_ids = groups.iter().map(|group| group.id).collect_vec();
+            let counts = HashMap::from_iter(Group::pictures_counts(conn, &group_ids)?);
+            regrouped.push(RegroupedArrangement::from_counts(arrangement_id, &groups, &counts));
         }
+        Ok(Json(regrouped))
+    })
+}
 
-        let groups = Group::from_arrangement_all(conn, arrangement.id)?;
-        let not_to_be_deleted_groups = groups.iter().filter(|g| !g.to_be_deleted).cloned().collect_vec();
-        let to_be_deleted_groups = groups.iter().filter(|g| g.to_be_deleted).cloned().collect_vec();
-
-        Ok(Json(ArrangementResponse {
-            arrangement: ArrangementResponseArrangement {
-                id: arrangement.id,
-                user_id: arrangement.user_id,
-                name: arrangement.name,
-                strong_match_conversion: arrangement.strong_match_conversion,
-                strategy: new_strategy,
-            },
-            groups: not_to_be_deleted_groups,
-            to_be_deleted_groups,
-        }))
+/// Permanently delete the groups of the arrangement marked as to be deleted that no longer contain any picture.
+/// This is also done periodically by the maintenance task. Returns the ids of the deleted groups.
+#[openapi(tag = "Arrangement")]
+#[post("/arrangement/<arrangement_id>/flush-deleted-groups")]
+pub async fn flush_deleted_groups(db: &State<DBPool>, user: User, arrangement_id: i32) -> Result<Json<Vec<i32>>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user.id)?;
+
+    err_transaction(conn, |conn| Ok(Json(Group::delete_to_be_deleted_empty(conn, Some(arrangement.id))?)))
+}
+
+/// Merge a manual arrangement into another one: the groups of the source arrangement are moved to the target arrangement,
+/// then the source arrangement is deleted. Both arrangements must be manual, and the source must not be used in a hierarchy.
+/// The groups keep their pictures, shares and link shares.
+#[openapi(tag = "Arrangement")]
+#[post("/arrangement/merge", data = "<request>")]
+pub async fn merge_arrangements(
+    db: &State<DBPool>,
+    user: User,
+    request: Json<MergeArrangementsRequest>,
+) -> Result<Json<ArrangementResponse>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let source = Arrangement::from_id_and_user_id(conn, request.source_id, user.id)?;
+    let target = Arrangement::from_id_and_user_id(conn, request.target_id, user.id)?;
+
+    err_transaction(conn, |conn| {
+        source.merge_into(conn, &target)?;
+        let groups = Group::from_arrangement_all(conn, target.id)?;
+        Ok(Json(ArrangementResponse::from_arrangement_and_groups(target, groups)?))
     })
 }
 
@@ -188,8 +573,8 @@ pub async fn delete_arrangement(db: &State<DBPool>, user: User, arrangement_id:
     // 3. Remove pictures from groups of the arrangement (should be done carefully to remove the pictures from other users if needed)
     let group_ids = Group::from_arrangement_all(conn, arrangement.id)?.into_iter().map(|g| g.id).collect_vec();
 
-    err_transaction(&mut conn, |conn| {
-        group_ids.iter().try_for_each(|group_id| group_clear_pictures(conn, *group_id))?;
+    grouping_transaction(&mut conn, |conn, delta| {
+        group_ids.iter().try_for_each(|group_id| group_clear_pictures(conn, delta, *group_id))?;
 
         // 4. Delete the shared groups, link share groups, groups, and the arrangement itself
         SharedGroup::delete_by_group_ids(conn, &group_ids)?;
@@ -1,9 +1,14 @@
-use crate::database::database::DBPool;
+use crate::database::database::{DBConn, DBPool};
 use crate::database::group::arrangement::Arrangement;
 use crate::database::group::group::Group;
+use crate::database::group::shared_group::{SharePermissions, SharedGroup};
+use crate::database::picture::picture::Picture;
 use crate::database::user::user::User;
+use crate::grouping::grouping_delta::grouping_transaction;
 use crate::grouping::grouping_process::{group_add_pictures, group_remove_pictures};
 use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
+use crate::utils::validation::validate_picture_batch;
+use itertools::Itertools;
 use rocket::serde::{json::Json, Deserialize};
 use rocket::State;
 use rocket_okapi::{openapi, JsonSchema};
@@ -22,6 +27,40 @@ pub struct ModifyGroupPicturesRequest {
     picture_ids: Vec<i64>,
 }
 
+#[derive(Deserialize, JsonSchema, Debug)]
+pub struct AssignGroupsRequest {
+    pub picture_ids: Vec<i64>,
+    #[serde(default)]
+    pub add_to_group_ids: Vec<i32>,
+    #[serde(default)]
+    pub remove_from_group_ids: Vec<i32>,
+}
+impl AssignGroupsRequest {
+    /// Groups to edit, in the order they are edited, with true if the pictures are added to the group, false if they are removed.
+    /// A group can't be in both lists.
+    pub fn group_operations(&self) -> Result<Vec<(i32, bool)>, ErrorResponder> {
+        if self.picture_ids.is_empty() {
+            return ErrorType::UnprocessableEntity("No picture ids to assign".to_string()).res_err_no_rollback();
+        }
+        validate_picture_batch(&self.picture_ids)?;
+        let added: HashSet<&i32> = self.add_to_group_ids.iter().collect();
+        if let Some(group_id) = self.remove_from_group_ids.iter().find(|id| added.contains(id)) {
+            return ErrorType::InvalidInput(format!("Group {} can't be both added to and removed from", group_id)).res_err_no_rollback();
+        }
+        Ok(self
+            .add_to_group_ids
+            .iter()
+            .map(|id| (*id, true))
+            .chain(self.remove_from_group_ids.iter().map(|id| (*id, false)))
+            .unique()
+            .collect())
+    }
+    /// Pictures to assign, without duplicates, in the order of the request.
+    pub fn unique_picture_ids(&self) -> Vec<i64> {
+        self.picture_ids.iter().cloned().unique().collect()
+    }
+}
+
 /// Create a new manual group
 #[openapi(tag = "Groups")]
 #[post("/group/manual", data = "<request>")]
@@ -35,45 +74,98 @@ pub async fn create_manual_group(db: &State<DBPool>, user: User, request: Json<C
             return Err(ErrorType::GroupIsNotManual.res_no_rollback());
         }
 
-        let group = Group::insert(conn, request.arrangement_id, request.name.clone(), false)?;
+        let group = Group::insert(conn, request.arrangement_id, request.name.clone(), false, None)?;
         Ok(Json(group))
     })
 }
 
 /// Add pictures to a manual group
+/// The group can also be edited by the users it is shared with, if the share grants them the add-pictures permission.
 #[openapi(tag = "Groups")]
 #[post("/group/manual/pictures", data = "<request>")]
 pub async fn add_pictures_to_group(db: &State<DBPool>, user: User, request: Json<ModifyGroupPicturesRequest>) -> Result<(), ErrorResponder> {
+    validate_picture_batch(&request.picture_ids)?;
     let mut conn = &mut db.get().unwrap();
 
-    err_transaction(&mut conn, |conn| {
-        // Verify the arrangement is manual and owned by the user
-        let arrangement = Arrangement::from_id_and_user_id(conn, request.arrangement_id, user.id)?;
-        if arrangement.strategy.is_some() {
-            return Err(ErrorType::GroupIsNotManual.res_no_rollback());
-        }
-        // Get the group and verify it belongs to the arrangement
-        let group = Group::from_id_and_arrangement(conn, request.group_id, request.arrangement_id)?;
-        group_add_pictures(conn, group.id, &request.picture_ids)?;
+    grouping_transaction(&mut conn, |conn, delta| {
+        let group = editable_manual_group(conn, user.id, request.group_id, request.arrangement_id)?;
+        group_add_pictures(conn, delta, group.id, &request.picture_ids)?;
         Ok(())
     })
 }
 
 /// Remove pictures from a manual group
+/// The group can also be edited by the users it is shared with, if the share grants them the add-pictures permission.
 #[openapi(tag = "Groups")]
 #[delete("/group/manual/pictures", data = "<request>")]
 pub async fn remove_pictures_from_group(db: &State<DBPool>, user: User, request: Json<ModifyGroupPicturesRequest>) -> Result<(), ErrorResponder> {
+    validate_picture_batch(&request.picture_ids)?;
     let mut conn = &mut db.get().unwrap();
 
-    err_transaction(&mut conn, |conn| {
-        // Verify the arrangement is manual and owned by the user
-        let arrangement = Arrangement::from_id_and_user_id(conn, request.arrangement_id, user.id)?;
-        if arrangement.strategy.is_some() {
-            return Err(ErrorType::GroupIsNotManual.res_no_rollback());
+    grouping_transaction(&mut conn, |conn, delta| {
+        let group = editable_manual_group(conn, user.id, request.group_id, request.arrangement_id)?;
+        group_remove_pictures(conn, delta, group.id, &request.picture_ids)?;
+        Ok(())
+    })
+}
+
+/// Add pictures to several manual groups and remove them from others in a single transaction.
+/// All the groups must be manual and owned by the user, this is checked before editing any group.
+#[openapi(tag = "Groups")]
+#[post("/groups/assign", data = "<request>")]
+pub async fn assign_pictures_to_groups(db: &State<DBPool>, user: User, request: Json<AssignGroupsRequest>) -> Result<(), ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    assign_pictures(conn, user.id, &request)
+}
+
+/// Add the pictures to and remove them from the manual groups of the request, in a single transaction.
+pub fn assign_pictures(conn: &mut DBConn, user_id: i32, request: &AssignGroupsRequest) -> Result<(), ErrorResponder> {
+    let operations = request.group_operations()?;
+    let picture_ids = request.unique_picture_ids();
+
+    grouping_transaction(conn, |conn, delta| {
+        for (group_id, _) in operations.iter() {
+            owned_manual_group(conn, user_id, *group_id)?;
+        }
+        Picture::require_all_accessible(&picture_ids, &Picture::filter_user_accessible_pictures(conn, user_id, &picture_ids)?)?;
+        for (group_id, add) in operations {
+            if add {
+                group_add_pictures(conn, delta, group_id, &picture_ids)?;
+            } else {
+                group_remove_pictures(conn, delta, group_id, &picture_ids)?;
+            }
         }
-        // Get the group and verify it belongs to the arrangement
-        let group = Group::from_id_and_arrangement(conn, request.group_id, request.arrangement_id)?;
-        group_remove_pictures(conn, group.id, &request.picture_ids)?;
         Ok(())
     })
 }
+
+/// Get a group, verifying it is manual and owned by the user.
+fn owned_manual_group(conn: &mut DBConn, user_id: i32, group_id: i32) -> Result<Group, ErrorResponder> {
+    let group = Group::from_id(conn, group_id)?;
+    let arrangement = Arrangement::from_id_and_user_id(conn, group.arrangement_id, user_id)?;
+    if arrangement.strategy.is_some() {
+        return Err(ErrorType::GroupIsNotManual.res_no_rollback());
+    }
+    Ok(group)
+}
+
+/// Get a manual group, verifying it belongs to the arrangement.
+/// If the arrangement is not owned by the user, the group must be shared with the user with the add-pictures permission.
+pub(crate) fn editable_manual_group(conn: &mut DBConn, user_id: i32, group_id: i32, arrangement_id: i32) -> Result<Group, ErrorResponder> {
+    let arrangement = match Arrangement::from_id_and_user_id_opt(conn, arrangement_id, user_id)? {
+        Some(arrangement) => arrangement,
+        None => {
+            SharedGroup::require_permission(conn, user_id, group_id, SharePermissions::ADD_PICTURES)?;
+            Arrangement::get_arrangements_from_groups_ids(conn, vec![group_id])?
+                .into_iter()
+                .find(|arrangement| arrangement.id == arrangement_id)
+                .ok_or_else(|| ErrorType::ArrangementNotFound.res())?
+        }
+    };
+    // Verify the arrangement is manual
+    if arrangement.strategy.is_some() {
+        return Err(ErrorType::GroupIsNotManual.res_no_rollback());
+    }
+    // Get the group and verify it belongs to the arrangement
+    Group::from_id_and_arrangement(conn, group_id, arrangement_id)
+}
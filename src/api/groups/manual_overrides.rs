@@ -0,0 +1,78 @@
+use crate::database::database::{DBConn, DBPool};
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::manual_override::ManualOverride;
+use crate::database::picture::picture::Picture;
+use crate::database::user::user::User;
+use crate::grouping::grouping_delta::grouping_transaction;
+use crate::grouping::grouping_process::group_pictures;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use crate::utils::validation::validate_picture_batch;
+use itertools::Itertools;
+use rocket::serde::{json::Json, Deserialize};
+use rocket::State;
+use rocket_okapi::{openapi, JsonSchema};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SetManualOverridesRequest {
+    group_id: i32,
+    picture_ids: Vec<i64>,
+    /// True to keep the pictures in the group, false to keep them out of it.
+    force_in: bool,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ClearManualOverridesRequest {
+    group_id: i32,
+    picture_ids: Vec<i64>,
+}
+
+/// Force pictures in or out of a group of an automatic arrangement, whatever its strategy decides.
+/// The overrides survive the regroupings, until they are cleared. The arrangement is regrouped for these pictures.
+#[openapi(tag = "Groups")]
+#[post("/group/overrides", data = "<request>")]
+pub async fn set_manual_overrides(db: &State<DBPool>, user: User, request: Json<SetManualOverridesRequest>) -> Result<(), ErrorResponder> {
+    validate_picture_batch(&request.picture_ids)?;
+    let picture_ids = request.picture_ids.iter().cloned().unique().collect_vec();
+    let conn = &mut db.get().unwrap();
+
+    grouping_transaction(conn, |conn, delta| {
+        let group = overridable_group(conn, user.id, request.group_id)?;
+        if Picture::filter_user_accessible_pictures(conn, user.id, &picture_ids)?.len() != picture_ids.len() {
+            return ErrorType::Unauthorized.res_err_no_rollback();
+        }
+        ManualOverride::upsert(conn, group.id, &picture_ids, request.force_in)?;
+        group_pictures(conn, delta, user.id, Some(&picture_ids), Some(group.arrangement_id), None, true)
+    })
+}
+
+/// Clear the overrides of pictures in a group of an automatic arrangement,
+/// regrouping the arrangement for these pictures so that its strategy decides again.
+#[openapi(tag = "Groups")]
+#[delete("/group/overrides", data = "<request>")]
+pub async fn clear_manual_overrides(db: &State<DBPool>, user: User, request: Json<ClearManualOverridesRequest>) -> Result<(), ErrorResponder> {
+    validate_picture_batch(&request.picture_ids)?;
+    let conn = &mut db.get().unwrap();
+
+    grouping_transaction(conn, |conn, delta| {
+        let group = overridable_group(conn, user.id, request.group_id)?;
+        let picture_ids = ManualOverride::delete(conn, group.id, &request.picture_ids)?;
+        if picture_ids.is_empty() {
+            return Ok(());
+        }
+        group_pictures(conn, delta, user.id, Some(&picture_ids), Some(group.arrangement_id), None, true)
+    })
+}
+
+/// Get a group, verifying it is a group of an automatic arrangement owned by the user.
+fn overridable_group(conn: &mut DBConn, user_id: i32, group_id: i32) -> Result<Group, ErrorResponder> {
+    let group = Group::from_id(conn, group_id)?;
+    let arrangement = Arrangement::from_id_and_user_id(conn, group.arrangement_id, user_id)?;
+    if arrangement.strategy.is_none() {
+        return ErrorType::InvalidInput("Overrides only apply to the groups of automatic arrangements".to_string()).res_err_no_rollback();
+    }
+    if group.to_be_deleted {
+        return ErrorType::InvalidInput(format!("Group {} is to be deleted", group.id)).res_err_no_rollback();
+    }
+    Ok(group)
+}
@@ -0,0 +1,146 @@
+use crate::api::picture::ListPictureData;
+use crate::database::database::{DBConn, DBPool};
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::{OutgoingShare, SharePermissions, ShareRecipient, SharedGroup, SharerArrangements};
+use crate::database::picture::picture::Picture;
+use crate::database::picture::picture_tag::PictureTag;
+use crate::database::user::user::User;
+use crate::grouping::grouping_delta::{grouping_transaction, GroupingDelta};
+use crate::grouping::grouping_process::{group_pictures, ungroup_unaccessible_pictures, CopiedStorageSnapshot};
+use crate::utils::errors_catcher::ErrorResponder;
+use itertools::Itertools;
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_okapi::{openapi, JsonSchema};
+
+#[derive(Serialize, JsonSchema)]
+pub struct PendingSharesResponse {
+    count: usize,
+    group_ids: Vec<i32>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpdateSharePermissionsRequest {
+    permissions: i16,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SharedGroupPicturesResponse {
+    group_id: i32,
+    recipients: Vec<ShareRecipient>,
+    pictures: Vec<ListPictureData>,
+}
+
+/// Accept all the pending shares sent to the user.
+/// The pictures of the shared groups get the default tags of the user and are grouped in his arrangements.
+#[openapi(tag = "Shares")]
+#[post("/shares/pending/accept-all")]
+pub async fn accept_all_pending_shares(db: &State<DBPool>, user: User) -> Result<Json<PendingSharesResponse>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+
+    grouping_transaction(conn, |conn, delta| {
+        let group_ids = accept_all_pending(conn, delta, user.id)?;
+        Ok(Json(PendingSharesResponse {
+            count: group_ids.len(),
+            group_ids,
+        }))
+    })
+}
+
+/// Confirm all the pending shares sent to the user, and group their pictures (see [`accept_all_pending_shares`]).
+/// Returns the ids of the shared groups.
+pub(crate) fn accept_all_pending(conn: &mut DBConn, delta: &mut GroupingDelta, user_id: i32) -> Result<Vec<i32>, ErrorResponder> {
+    let shared_groups = SharedGroup::confirm_all_pending_for_user(conn, user_id)?;
+    let group_ids = shared_groups.iter().map(|shared_group| shared_group.group_id).unique().collect_vec();
+    let picture_ids = Group::pictures_from_group_ids(conn, &group_ids)?;
+
+    // Pictures of the copied shares now count toward the user storage, except the ones they own
+    // and the ones they already reached through another copied share.
+    CopiedStorageSnapshot::take(conn, &shared_groups, &picture_ids, &group_ids)?.update_storage(conn)?;
+
+    if !picture_ids.is_empty() {
+        PictureTag::add_default_tags_to_pictures_without_tags(conn, user_id, &picture_ids)?;
+        group_pictures(conn, delta, user_id, Some(&picture_ids), None, None, false)?;
+    }
+    Ok(group_ids)
+}
+
+/// Decline all the pending shares sent to the user.
+/// Pictures the user loses access to are removed from all his groups.
+#[openapi(tag = "Shares")]
+#[post("/shares/pending/decline-all")]
+pub async fn decline_all_pending_shares(db: &State<DBPool>, user: User) -> Result<Json<PendingSharesResponse>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+
+    grouping_transaction(conn, |conn, delta| {
+        let group_ids = decline_all_pending(conn, delta, user.id)?;
+        Ok(Json(PendingSharesResponse {
+            count: group_ids.len(),
+            group_ids,
+        }))
+    })
+}
+
+/// Delete all the pending shares sent to the user, and ungroup the pictures they lose access to (see [`decline_all_pending_shares`]).
+/// Returns the ids of the shared groups.
+pub(crate) fn decline_all_pending(conn: &mut DBConn, delta: &mut GroupingDelta, user_id: i32) -> Result<Vec<i32>, ErrorResponder> {
+    let group_ids = SharedGroup::delete_all_pending_for_user(conn, user_id)?
+        .into_iter()
+        .map(|shared_group| shared_group.group_id)
+        .unique()
+        .collect_vec();
+
+    let picture_ids = Group::pictures_from_group_ids(conn, &group_ids)?;
+    ungroup_unaccessible_pictures(conn, delta, user_id, &picture_ids)?;
+    Ok(group_ids)
+}
+
+/// List the shares of the groups of the user's arrangements, with their recipient and status.
+#[openapi(tag = "Shares")]
+#[get("/shares/outgoing")]
+pub async fn list_outgoing_shares(db: &State<DBPool>, user: User) -> Result<Json<Vec<OutgoingShare>>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    Ok(Json(SharedGroup::outgoing_for_owner(conn, user.id)?))
+}
+
+/// List the groups shared with the user (confirmed shares only), by sharer and by arrangement of the sharer.
+#[openapi(tag = "Shares")]
+#[get("/shared/arrangements")]
+pub async fn list_shared_arrangements(db: &State<DBPool>, user: User) -> Result<Json<Vec<SharerArrangements>>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let shares = SharedGroup::incoming_for_recipient(conn, user.id)?;
+    Ok(Json(SharerArrangements::from_shares(shares)))
+}
+
+/// List the pictures a group of the user's arrangements exposes through its shares, with the recipients of the shares (pending ones included).
+#[openapi(tag = "Shares")]
+#[get("/group/<group_id>/shared-pictures")]
+pub async fn list_group_shared_pictures(db: &State<DBPool>, user: User, group_id: i32) -> Result<Json<SharedGroupPicturesResponse>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let group = Group::from_id_and_user_id(conn, group_id, user.id)?;
+    Ok(Json(SharedGroupPicturesResponse {
+        group_id: group.id,
+        recipients: SharedGroup::recipients(conn, group.id)?,
+        pictures: Picture::from_group(conn, group.id)?,
+    }))
+}
+
+/// Change the permissions of the share of a group of the user's arrangements with another user.
+/// The permissions must be known bit flags including `VIEW`, otherwise `InvalidInput` is returned.
+/// They are checked on each action of the recipient, so the change applies to their next requests.
+#[openapi(tag = "Shares")]
+#[patch("/group/share/<group_id>/<user_id>", data = "<request>")]
+pub async fn update_share_permissions(
+    db: &State<DBPool>,
+    user: User,
+    group_id: i32,
+    user_id: i32,
+    request: Json<UpdateSharePermissionsRequest>,
+) -> Result<(), ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    let permissions = SharePermissions::from_request_bits(request.permissions)?;
+    let group = Group::from_id_and_user_id(conn, group_id, user.id)?;
+    SharedGroup::update_permissions(conn, user_id, group.id, permissions)?;
+    Ok(())
+}
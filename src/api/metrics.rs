@@ -0,0 +1,11 @@
+use crate::utils::metrics::Metrics;
+use rocket::State;
+use rocket_okapi::openapi;
+
+/// Traffic metrics of the server (requests count, latency and size by route), in the Prometheus text format.
+/// Unauthenticated, to be scraped by the monitoring stack: it should not be exposed publicly by the reverse proxy.
+#[openapi(tag = "Metrics")]
+#[get("/metrics")]
+pub async fn get_metrics(metrics: &State<Metrics>) -> String {
+    metrics.render()
+}
@@ -1,19 +1,37 @@
 use crate::api::query_pictures::{PictureFilter, PictureSort, PicturesQuery};
 use crate::database::database::{DBConn, DBPool};
-use crate::database::picture::picture::{MixedPictureDetails, Picture, PictureDetails};
+use crate::database::group::arrangement::ArrangementDependencyType;
+use crate::database::picture::picture::{
+    ExifField, ExifHistogramBucket, MixedPictureDetails, Picture, PictureAccess, PictureDetails, PictureDetailsFields, PictureDetailsSelection,
+    PictureVisibility, PicturesTotalSize,
+};
 use crate::database::picture::picture_tag::PictureTag;
+use crate::database::picture::rating::Rating;
+use crate::database::tag::tag_group::{TagGroup, TagGroupViolation, TagGroupWithTags};
 use crate::database::user::user::User;
+use crate::grouping::grouping_delta::grouping_transaction;
 use crate::grouping::grouping_process::group_pictures;
-use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorResponse, ErrorType};
+use crate::utils::byte_range::ByteRange;
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorResponse, ErrorType};
+use crate::utils::exif::strip_private_metadata;
+use crate::utils::multipart::{MultipartMixed, MultipartPart, MultipartPartStream};
+use crate::utils::picture_format::check_picture_format;
 use crate::utils::s3::PictureStorer;
-use crate::utils::thumbnail::{generate_blurhash, generate_thumbnail, PictureThumbnail, ORIGINAL_TEMP_DIR, THUMBS_TEMP_DIR};
+use crate::utils::thumbnail::{generate_blurhash, generate_perceptual_hash, generate_thumbnail, PictureThumbnail};
+use crate::utils::validation::{validate_picture_batch, validate_picture_comment, validate_picture_rating, validation_error_to_responder};
+use crate::utils::zip::{unique_entry_names, ZipArchive, ZipByteStream, ZipEncoder};
 use aws_smithy_types::byte_stream::ByteStream;
 use chrono::NaiveDateTime;
 use diesel::dsl::update;
 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use itertools::Itertools;
 use rand::random;
 use rocket::form::Form;
 use rocket::fs::TempFile;
+use rocket::futures::StreamExt;
+use rocket::http::Status;
+use rocket::response::stream::stream;
 use rocket::response::Responder;
 use rocket::serde::json::Json;
 use rocket::serde::Serialize;
@@ -40,6 +58,8 @@ pub struct UploadPictureResponse {
     pub(crate) name: String,
     pub(crate) picture: Picture,
     pub(crate) thumbnail_error: Option<ErrorResponse>,
+    /// Pictures of the user that are near-duplicates of the uploaded picture, most similar first. They don't prevent the upload.
+    pub(crate) similar_picture_ids: Vec<i64>,
 }
 
 #[derive(FromForm, Debug)]
@@ -58,29 +78,46 @@ impl JsonSchema for UploadPictureData<'_> {
 }
 
 /// Upload a picture using multipart form upload
+/// With `strict_thumbnails` (defaults to `STRICT_THUMBNAILS`), a thumbnail failure rolls back the whole upload and is returned as an error,
+/// otherwise the picture is kept and the failure is reported in `thumbnail_error`.
 /// TODO : Implement chunked upload
 #[openapi(tag = "Picture")]
-#[post("/picture", data = "<upload>")]
+#[post("/picture?<strict_thumbnails>", data = "<upload>")]
 pub async fn add_picture(
     mut upload: Form<UploadPictureData<'_>>,
+    strict_thumbnails: Option<bool>,
     db: &State<DBPool>,
     picture_storer: &State<PictureStorer>,
     user: User,
 ) -> Result<Json<UploadPictureResponse>, ErrorResponder> {
     let conn: &mut DBConn = &mut db.get().unwrap();
+    let strict_thumbnails = strict_thumbnails.unwrap_or(CONFIG.strict_thumbnails);
     let file_name = upload.file.name().unwrap_or("unknown.jpg").to_string();
 
     let file_name_ascii = file_name.chars().filter(|c| c.is_ascii()).collect::<String>();
     let temp_file_name = format!("{}-{}", random::<u16>(), file_name_ascii);
 
-    let res = {
-        // Saving the file
-        if let Err(e) = upload.file.persist_to(Path::new(ORIGINAL_TEMP_DIR).join(temp_file_name.clone())).await {
-            error!("{:?}", e);
-            return ErrorType::InternalError(format!("Unable to save file to {}", ORIGINAL_TEMP_DIR)).res_err();
-        }
-        let path = upload.file.path().unwrap();
+    // Saving the file
+    if let Err(e) = upload.file.persist_to(CONFIG.original_temp_dir().join(temp_file_name)).await {
+        error!("{:?}", e);
+        return ErrorType::InternalError(format!("Unable to save file to {}", CONFIG.original_temp_dir().display())).res_err();
+    }
+    let path = upload.file.path().unwrap().to_path_buf();
 
+    upload_picture(conn, picture_storer, &user, file_name, &path, strict_thumbnails).await
+}
+
+/// Store the uploaded file saved at `path` as a new picture of the user (see [`add_picture`]).
+/// The temporary files of the upload, the saved file and its thumbnails, are removed once done, whether the upload succeeded or not.
+pub async fn upload_picture(
+    conn: &mut DBConn,
+    picture_storer: &PictureStorer,
+    user: &User,
+    file_name: String,
+    path: &Path,
+    strict_thumbnails: bool,
+) -> Result<Json<UploadPictureResponse>, ErrorResponder> {
+    let res: Result<Json<UploadPictureResponse>, ErrorResponder> = async {
         // Calculate file size (Rounding up)
         let file_size_o = path
             .metadata()
@@ -97,6 +134,9 @@ pub async fn add_picture(
             return ErrorType::InvalidInput(format!("File size is too big: {} Ko", file_size_ko)).res_err();
         }
 
+        // Check the file is actually a picture, before trying to read it
+        let format = check_picture_format(path, &CONFIG.supported_picture_formats)?;
+
         // Read EXIF metadata
         let meta = rexiv2::Metadata::new_from_path(path).ok();
 
@@ -120,34 +160,55 @@ pub async fn add_picture(
                                 blurhash = Some(tiny_thumb);
                             }
                             Err(e) => {
-                                thumbnail_error = Some(ErrorResponse::from(e));
+                                thumbnail_error = Some(e);
                                 break;
                             }
                         }
                     }
                 }
                 Err(e) => {
-                    thumbnail_error = Some(ErrorResponse::from(e));
+                    thumbnail_error = Some(e);
                     break;
                 }
             }
         }
+        let mut thumbnail_error = thumbnail_failure(strict_thumbnails, thumbnail_error)?;
+        // Perceptual hash of the small thumbnail, to find the near-duplicates of the picture
+        let perceptual_hash = thumbnails.get(&(PictureThumbnail::Small as usize)).and_then(|thumbnail_path| {
+            generate_perceptual_hash(thumbnail_path)
+                .map_err(|e| warn!("{:?}", ErrorResponse::from(e)))
+                .ok()
+        });
 
         // Database operations
-        let picture = err_transaction(conn, |conn| {
-            let picture = Picture::insert(conn, user.id, file_name.clone(), meta, file_size_ko, blurhash)?;
+        let picture = grouping_transaction(conn, |conn, delta| {
+            let picture = Picture::insert(conn, user.id, file_name.clone(), meta, file_size_ko, blurhash, format.clone())?;
+            if let Some(perceptual_hash) = perceptual_hash {
+                Picture::set_perceptual_hash(conn, picture.id, perceptual_hash)?;
+            }
             let pictures = vec![picture.id];
             // Adding default tags
             PictureTag::add_default_tags(conn, user.id, &pictures)?;
             // Grouping pictures
-            group_pictures(conn, user.id, Some(&pictures), None, None, false).map_err(|e| e.with_rollback(true))?;
+            group_pictures(conn, delta, user.id, Some(&pictures), None, None, false).map_err(|e| e.with_rollback(true))?;
 
             // Upload file to S3
             task::block_in_place(|| {
                 tokio::runtime::Handle::current().block_on(async {
                     picture_storer
                         .store_picture_from_file(PictureThumbnail::Original as usize, picture.id, &path)
-                        .await
+                        .await?;
+                    // In strict mode, thumbnails are stored before committing, and a failure deletes the stored files
+                    if strict_thumbnails {
+                        for (thumbnail_type, thumbnail_path) in thumbnails.iter() {
+                            let res = picture_storer.store_picture_from_file(*thumbnail_type, picture.id, thumbnail_path).await;
+                            if let Err(e) = res {
+                                picture_storer.delete_picture(picture.id).await;
+                                return Err(e.with_rollback(true));
+                            }
+                        }
+                    }
+                    Ok(())
                 })
             })?;
 
@@ -155,37 +216,72 @@ pub async fn add_picture(
         })?;
 
         // Uploading thumbnails to S3
-        for (thumbnail_type, thumbnail_path) in thumbnails {
-            let res = picture_storer.store_picture_from_file(thumbnail_type, picture.id, &thumbnail_path).await;
-            if let Err(e) = res {
-                thumbnail_error = Some(ErrorResponse::from(e));
-                break;
+        if !strict_thumbnails {
+            for (thumbnail_type, thumbnail_path) in thumbnails {
+                let res = picture_storer.store_picture_from_file(thumbnail_type, picture.id, &thumbnail_path).await;
+                if let Err(e) = res {
+                    thumbnail_error = Some(ErrorResponse::from(e));
+                    break;
+                }
             }
         }
 
+        // Near-duplicates are only reported, failing to find them doesn't fail the upload
+        let similar_picture_ids = perceptual_hash
+            .map_or(Ok(vec![]), |hash| Picture::similar_pictures(conn, user.id, picture.id, hash))
+            .unwrap_or_else(|e| {
+                warn!("{:?}", ErrorResponse::from(e));
+                vec![]
+            });
+
         Ok(Json(UploadPictureResponse {
             name: file_name,
             picture,
             thumbnail_error,
+            similar_picture_ids,
         }))
-    };
+    }
+    .await;
 
-    // Cleaning up files
-    let _ = std::fs::remove_file(Path::new(ORIGINAL_TEMP_DIR).join(temp_file_name.clone()));
-    let _ = std::fs::remove_file(Path::new(THUMBS_TEMP_DIR).join(temp_file_name));
+    // Cleaning up files, including when the upload failed
+    let _ = std::fs::remove_file(path);
+    if let Some(temp_file_name) = path.file_name() {
+        let _ = std::fs::remove_file(CONFIG.thumbs_temp_dir().join(temp_file_name));
+    }
     res
 }
 
+/// Handle a thumbnail generation or storage failure of an upload: in strict mode, the failure is returned as an error that rolls back
+/// the upload, otherwise it is kept to be reported in the response.
+pub fn thumbnail_failure(strict: bool, error: Option<ErrorResponder>) -> Result<Option<ErrorResponse>, ErrorResponder> {
+    match error {
+        Some(e) if strict => Err(e.with_rollback(true)),
+        error => Ok(error.map(ErrorResponse::from)),
+    }
+}
+
 pub struct PictureStream {
-    picture_id: i64,
-    picture_stream: ByteStream,
+    pub picture_id: i64,
+    pub format: PictureThumbnail,
+    /// Format of the original picture, setting the content type of the original (see [`Picture::format`])
+    pub original_format: Option<String>,
+    pub picture_stream: ByteStream,
+    /// Whether the `Range` header is supported for this picture format
+    pub accept_ranges: bool,
+    /// `Content-Range` of the stream if only a range of the picture is streamed
+    pub content_range: Option<String>,
 }
 impl<'a> Responder<'a, 'a> for PictureStream {
     fn respond_to(self, _: &Request) -> response::Result<'a> {
-        Response::build()
-            .header(rocket::http::ContentType::JPEG)
-            .streamed_body(self.picture_stream.into_async_read())
-            .ok()
+        let mut response = Response::build();
+        response.header(self.format.content_type_with_original(self.original_format.as_deref()));
+        if self.accept_ranges {
+            response.raw_header("Accept-Ranges", "bytes");
+        }
+        if let Some(content_range) = self.content_range {
+            response.status(Status::PartialContent).raw_header("Content-Range", content_range);
+        }
+        response.streamed_body(self.picture_stream.into_async_read()).ok()
     }
 }
 impl OpenApiResponderInner for PictureStream {
@@ -198,14 +294,21 @@ impl OpenApiResponderInner for PictureStream {
 /// If the user is logged in, the picture is only accessible if owned by the user or in a shared group with the user,
 /// If the user is not logged in, the picture is only accessible if it is in a publicly shared group.
 /// Otherwise, Unauthorized is returned
+/// The original format supports the `Range` header (a single byte range), responding with 206 Partial Content,
+/// or with 416 Range Not Satisfiable and a `Content-Range: bytes */<size>` header if the range starts after the end of the picture.
+/// With `strip_exif=true`, the location and personal information tags of the original are removed before streaming it
+/// (the stored picture is left unchanged, and the `Range` header is ignored). Thumbnails have no EXIF metadata.
 /// TODO: Implement S3 secret URL or picture secret token and remove the access check from this endpoint.
+/// Ranked after the static `/picture/<picture_id>/access` route.
 #[openapi(tag = "Picture")]
-#[get("/picture/<picture_id>/<format>")]
+#[get("/picture/<picture_id>/<format>?<strip_exif>", rank = 2)]
 pub async fn get_picture(
     db: &State<DBPool>,
     format: PictureThumbnail,
     picture_id: i64,
+    strip_exif: Option<bool>,
     user: Option<User>,
+    range: Option<ByteRange>,
     picture_storer: &State<PictureStorer>,
 ) -> Result<PictureStream, ErrorResponder> {
     let conn: &mut DBConn = &mut db.get().unwrap();
@@ -218,9 +321,279 @@ pub async fn get_picture(
     if !access_allowed {
         return Err(ErrorType::Unauthorized.res_no_rollback());
     }
+    let original_format = match format {
+        PictureThumbnail::Original => Picture::get_format(conn, picture_id)?,
+        _ => None,
+    };
+
+    if strip_exif.unwrap_or(false) && format == PictureThumbnail::Original {
+        return stream_stripped_picture(picture_storer, picture_id, original_format).await;
+    }
+
+    stream_picture(picture_storer, picture_id, format, original_format, range).await
+}
 
+/// Stream the original of a picture without its location and personal information tags, once its access has been checked
+/// (see [`get_picture`]). The whole picture is loaded to be stripped, so ranges are not supported.
+pub async fn stream_stripped_picture(
+    picture_storer: &PictureStorer,
+    picture_id: i64,
+    original_format: Option<String>,
+) -> Result<PictureStream, ErrorResponder> {
+    let picture = picture_storer
+        .get_picture(PictureThumbnail::Original, picture_id)
+        .await?
+        .collect()
+        .await
+        .map_err(|e| ErrorType::S3Error(format!("Unable to read picture: {}", e)).res_no_rollback())?;
+    let stripped = strip_private_metadata(&picture.into_bytes())?;
+    Ok(PictureStream {
+        picture_id,
+        format: PictureThumbnail::Original,
+        original_format,
+        picture_stream: ByteStream::from(stripped),
+        accept_ranges: false,
+        content_range: None,
+    })
+}
+
+/// Stream a picture, or a byte range of its original, from S3 once its access has been checked (see [`get_picture`]).
+pub async fn stream_picture(
+    picture_storer: &PictureStorer,
+    picture_id: i64,
+    format: PictureThumbnail,
+    original_format: Option<String>,
+    range: Option<ByteRange>,
+) -> Result<PictureStream, ErrorResponder> {
+    let accept_ranges = format == PictureThumbnail::Original;
+    if let (true, Some(range)) = (accept_ranges, range) {
+        let (picture_stream, content_range) = picture_storer.get_picture_range(format, picture_id, range).await?;
+        return Ok(PictureStream {
+            picture_id,
+            format,
+            original_format,
+            picture_stream,
+            accept_ranges,
+            content_range,
+        });
+    }
     let picture_stream = picture_storer.get_picture(format, picture_id).await?;
-    Ok(PictureStream { picture_id, picture_stream })
+    Ok(PictureStream {
+        picture_id,
+        format,
+        original_format,
+        picture_stream,
+        accept_ranges,
+        content_range: None,
+    })
+}
+
+/// Check whether a picture exists and is accessible, without streaming it.
+/// For anonymous callers, only the `publicly_shared` flag is meaningful.
+/// A picture that does not exist is reported as inaccessible.
+#[openapi(tag = "Picture")]
+#[get("/picture/<picture_id>/access")]
+pub async fn get_picture_access(db: &State<DBPool>, picture_id: i64, user: Option<User>) -> Result<Json<PictureAccess>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::get_picture_access(conn, picture_id, user.map(|user| user.id))?))
+}
+
+/// List everyone who can see a picture: its owner, the users it is shared with (and through which group),
+/// and the prefixes of the public links giving access to it. Only the owner of the picture can audit it.
+#[openapi(tag = "Picture")]
+#[get("/picture/<picture_id>/visibility")]
+pub async fn get_picture_visibility(db: &State<DBPool>, user: User, picture_id: i64) -> Result<Json<PictureVisibility>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::visibility_audit(conn, picture_id, user.id)?))
+}
+
+/// Maximum number of thumbnails that can be fetched in one batch
+pub const THUMBNAILS_BATCH_MAX: usize = 200;
+/// Number of thumbnails fetched concurrently from S3 for one batch
+const THUMBNAILS_BATCH_CONCURRENCY: usize = 8;
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct ThumbnailsBatchRequest {
+    pub picture_ids: Vec<i64>,
+    pub format: PictureThumbnail,
+}
+
+/// Get the thumbnails of several pictures in a single `multipart/mixed` response, fetched concurrently and streamed as they arrive.
+/// Each part is the JPEG thumbnail of a picture, identified by its `Content-ID` header (the picture id).
+/// Pictures the user can't access are skipped, others are returned in the requested order.
+/// A thumbnail that can't be fetched is returned as an `application/json` part holding the error, the other parts are still returned.
+#[openapi(tag = "Picture")]
+#[post("/pictures/thumbnails/batch", data = "<data>")]
+pub async fn get_thumbnails_batch(
+    db: &State<DBPool>,
+    user: User,
+    data: Json<ThumbnailsBatchRequest>,
+    picture_storer: &State<PictureStorer>,
+) -> Result<MultipartMixed, ErrorResponder> {
+    if data.format == PictureThumbnail::Original {
+        return ErrorType::InvalidInput("Only thumbnails can be fetched in batch".to_string()).res_err();
+    }
+    if data.picture_ids.len() > THUMBNAILS_BATCH_MAX {
+        return ErrorType::InvalidInput(format!("At most {} thumbnails can be fetched in batch", THUMBNAILS_BATCH_MAX)).res_err();
+    }
+    let picture_ids = {
+        let conn: &mut DBConn = &mut db.get().unwrap();
+        let accessible_ids = Picture::filter_user_accessible_pictures(conn, user.id, &data.picture_ids)?;
+        batch_picture_ids(&data.picture_ids, &accessible_ids)
+    };
+
+    Ok(MultipartMixed::new(thumbnails_batch_stream(
+        picture_storer.inner().clone(),
+        picture_ids,
+        data.format,
+    )))
+}
+
+/// Stream the thumbnails parts of the pictures, in the order of `picture_ids`, fetched from S3 with a bounded concurrency.
+/// Only the thumbnails being fetched or sent are held in memory.
+pub fn thumbnails_batch_stream(picture_storer: PictureStorer, picture_ids: Vec<i64>, format: PictureThumbnail) -> MultipartPartStream {
+    Box::pin(
+        rocket::futures::stream::iter(picture_ids)
+            .map(move |picture_id| {
+                let picture_storer = picture_storer.clone();
+                async move {
+                    let thumbnail = async {
+                        picture_storer
+                            .get_picture(format, picture_id)
+                            .await?
+                            .collect()
+                            .await
+                            .map_err(|_e| ErrorType::S3Error(String::from("Unable to retrieve object")).res())
+                    }
+                    .await;
+                    match thumbnail {
+                        Ok(thumbnail) => thumbnail_part(picture_id, thumbnail.to_vec()),
+                        Err(e) => {
+                            error!("Unable to fetch thumbnail of picture {} for a batch: {:?}", picture_id, e);
+                            thumbnail_error_part(picture_id, e)
+                        }
+                    }
+                }
+            })
+            .buffered(THUMBNAILS_BATCH_CONCURRENCY),
+    )
+}
+
+/// Ids of the requested pictures to return in a batch: the accessible ones, in the requested order and without duplicates.
+pub fn batch_picture_ids(requested_ids: &[i64], accessible_ids: &[i64]) -> Vec<i64> {
+    requested_ids.iter().filter(|id| accessible_ids.contains(id)).unique().copied().collect()
+}
+/// Multipart part reporting that the thumbnail of a picture couldn't be fetched, with the JSON error as body.
+pub fn thumbnail_error_part(picture_id: i64, error: ErrorResponder) -> MultipartPart {
+    MultipartPart {
+        headers: vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("Content-ID".to_string(), picture_id.to_string()),
+        ],
+        body: serde_json::to_vec(&ErrorResponse::from(error)).unwrap_or_default(),
+    }
+}
+/// Multipart part of the JPEG thumbnail of a picture.
+pub fn thumbnail_part(picture_id: i64, thumbnail: Vec<u8>) -> MultipartPart {
+    MultipartPart {
+        headers: vec![
+            ("Content-Type".to_string(), "image/jpeg".to_string()),
+            ("Content-ID".to_string(), picture_id.to_string()),
+        ],
+        body: thumbnail,
+    }
+}
+
+/// Maximum number of pictures that can be downloaded in one ZIP archive
+pub const ZIP_DOWNLOAD_MAX_PICTURES: usize = 1000;
+/// Maximum total size of the pictures downloaded in one ZIP archive (2 GiB)
+pub const ZIP_DOWNLOAD_MAX_SIZE_KO: i64 = 2 * 1024 * 1024;
+/// Number of originals fetched concurrently from S3 while the archive is streamed
+const ZIP_DOWNLOAD_CONCURRENCY: usize = 4;
+
+/// Download the originals of several pictures as a ZIP archive, streamed as it is built.
+/// Entries are named after the pictures names, with a ` (n)` suffix for duplicates.
+/// Pictures the user can't access are skipped, others are archived in the requested order.
+/// If an original can't be fetched while streaming, the archive is cut short and is invalid.
+#[openapi(tag = "Picture")]
+#[post("/pictures/download-zip", data = "<picture_ids>")]
+pub async fn download_pictures_zip(
+    db: &State<DBPool>,
+    user: User,
+    picture_ids: Json<Vec<i64>>,
+    picture_storer: &State<PictureStorer>,
+) -> Result<ZipArchive, ErrorResponder> {
+    if picture_ids.len() > ZIP_DOWNLOAD_MAX_PICTURES {
+        return ErrorType::InvalidInput(format!("At most {} pictures can be downloaded at once", ZIP_DOWNLOAD_MAX_PICTURES)).res_err();
+    }
+    let entries = {
+        let conn: &mut DBConn = &mut db.get().unwrap();
+        let accessible_ids = Picture::filter_user_accessible_pictures(conn, user.id, &picture_ids)?;
+        let picture_ids = batch_picture_ids(&picture_ids, &accessible_ids);
+        let size_ko = Picture::sum_size_ko(conn, &picture_ids)?;
+        if size_ko > ZIP_DOWNLOAD_MAX_SIZE_KO {
+            return ErrorType::InvalidInput(format!(
+                "At most {} Ko can be downloaded at once, got {} Ko",
+                ZIP_DOWNLOAD_MAX_SIZE_KO, size_ko
+            ))
+            .res_err();
+        }
+        let names = Picture::from_ids(conn, &picture_ids)?
+            .into_iter()
+            .map(|picture| (picture.id, picture.name))
+            .collect();
+        zip_entries(&picture_ids, names)
+    };
+
+    Ok(ZipArchive {
+        file_name: "pictures.zip".to_string(),
+        stream: pictures_zip_stream(picture_storer.inner().clone(), entries),
+    })
+}
+
+/// Entries of the ZIP archive of the pictures (picture id, entry name), in the order of `picture_ids`, from the pictures names.
+pub fn zip_entries(picture_ids: &[i64], names: HashMap<i64, String>) -> Vec<(i64, String)> {
+    let mut names = names;
+    let picture_ids = picture_ids.iter().filter(|id| names.contains_key(id)).copied().collect_vec();
+    let names = picture_ids.iter().map(|id| names.remove(id).unwrap()).collect_vec();
+    picture_ids.into_iter().zip(unique_entry_names(&names)).collect()
+}
+/// Stream the ZIP archive of the originals, fetched from S3 with a bounded concurrency.
+fn pictures_zip_stream(picture_storer: PictureStorer, entries: Vec<(i64, String)>) -> ZipByteStream {
+    Box::pin(stream! {
+        let mut encoder = ZipEncoder::new();
+        let mut originals = rocket::futures::stream::iter(entries)
+            .map(|(picture_id, name)| {
+                let picture_storer = picture_storer.clone();
+                async move { (picture_id, name, picture_storer.get_picture(PictureThumbnail::Original, picture_id).await) }
+            })
+            .buffered(ZIP_DOWNLOAD_CONCURRENCY);
+
+        while let Some((picture_id, name, original)) = originals.next().await {
+            let mut original = match original {
+                Ok(original) => original,
+                Err(e) => {
+                    error!("Unable to fetch picture {} for a ZIP download: {:?}", picture_id, e);
+                    return;
+                }
+            };
+            yield encoder.start_entry(&name);
+            while let Some(chunk) = original.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        encoder.add_data(&chunk);
+                        yield chunk.to_vec();
+                    }
+                    Err(e) => {
+                        error!("Unable to read picture {} for a ZIP download: {:?}", picture_id, e);
+                        return;
+                    }
+                }
+            }
+            yield encoder.end_entry();
+        }
+        yield encoder.finish();
+    })
 }
 
 #[derive(JsonSchema, Serialize, Debug)]
@@ -229,15 +602,90 @@ pub struct ListPictureData {
     pub(crate) name: String,
     pub(crate) width: i16,
     pub(crate) height: i16,
+    pub(crate) size_ko: i32,
     pub(crate) creation_date: NaiveDateTime,
     pub(crate) edition_date: NaiveDateTime,
     pub(crate) blurhash: Option<String>,
+    /// Groups of the user's arrangements containing the picture, only when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) group_ids: Option<Vec<i32>>,
+}
+impl ListPictureData {
+    /// Set the group ids of each picture from the (picture_id, group_id) memberships, pictures without membership getting an empty list.
+    pub fn attach_group_ids(pictures: &mut [ListPictureData], memberships: &[(i64, i32)]) {
+        let mut group_ids: HashMap<i64, Vec<i32>> = HashMap::new();
+        for (picture_id, group_id) in memberships.iter() {
+            group_ids.entry(*picture_id).or_default().push(*group_id);
+        }
+        for picture in pictures.iter_mut() {
+            picture.group_ids = Some(group_ids.remove(&picture.id).unwrap_or_default());
+        }
+    }
+    /// Sort the pictures in the order of `picture_ids`, as they are loaded in an arbitrary order.
+    /// Each id gives a single entry, its first occurrence being kept. The ids without a picture give `None` if `keep_missing`
+    /// is set, and are left out otherwise.
+    pub fn in_requested_order(pictures: Vec<ListPictureData>, picture_ids: &[i64], keep_missing: bool) -> Vec<Option<ListPictureData>> {
+        let mut positions: HashMap<i64, usize> = HashMap::with_capacity(picture_ids.len());
+        for picture_id in picture_ids.iter() {
+            let position = positions.len();
+            positions.entry(*picture_id).or_insert(position);
+        }
+        let mut ordered: Vec<Option<ListPictureData>> = (0..positions.len()).map(|_| None).collect();
+        for picture in pictures {
+            if let Some(position) = positions.get(&picture.id) {
+                ordered[*position] = Some(picture);
+            }
+        }
+        if !keep_missing {
+            ordered.retain(Option::is_some);
+        }
+        ordered
+    }
+}
+/// Columns selected to build a [`ListPictureData`]: id, name, width, height, size_ko, creation_date, edition_date, blurhash
+pub type ListPictureRow = (i64, String, i16, i16, i32, NaiveDateTime, NaiveDateTime, Option<String>);
+impl From<ListPictureRow> for ListPictureData {
+    fn from((id, name, width, height, size_ko, creation_date, edition_date, blurhash): ListPictureRow) -> Self {
+        ListPictureData {
+            id,
+            name,
+            width,
+            height,
+            size_ko,
+            creation_date,
+            edition_date,
+            blurhash,
+            group_ids: None,
+        }
+    }
 }
 
 #[derive(JsonSchema, Deserialize, Debug)]
 pub struct PicturesDetailsQuery {
     picture_ids: Vec<i64>,
 }
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct PicturesByIdsQuery {
+    picture_ids: Vec<i64>,
+    /// Return `null` for the requested pictures that don't exist or can't be accessed, instead of leaving them out
+    #[serde(default)]
+    keep_missing: bool,
+}
+/// Get the pictures of a list, in the order of the list. Only the pictures the user can access are returned,
+/// the other ones being left out or returned as `null` (see `keep_missing`).
+#[openapi(tag = "Picture")]
+#[post("/pictures/by-ids", data = "<data>")]
+pub async fn get_pictures_by_ids(
+    db: &State<DBPool>,
+    user: User,
+    data: Json<PicturesByIdsQuery>,
+) -> Result<Json<Vec<Option<ListPictureData>>>, ErrorResponder> {
+    validate_picture_batch(&data.picture_ids)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let pictures = Picture::from_ids_accessible(conn, user.id, &data.picture_ids)?;
+    Ok(Json(ListPictureData::in_requested_order(pictures, &data.picture_ids, data.keep_missing)))
+}
+
 /// Get pictures details as a MixedPictureDetails object.
 /// It includes the common fields containing the common data, and mixed data as None (serialized as nothing)
 /// Common and mixed tags are also calculated, and statistics about ratings.
@@ -248,16 +696,229 @@ pub async fn get_pictures_details(
     user: User,
     data: Json<PicturesDetailsQuery>,
 ) -> Result<Json<MixedPictureDetails>, ErrorResponder> {
+    validate_picture_batch(&data.picture_ids)?;
     let conn: &mut DBConn = &mut db.get().unwrap();
     Ok(Json(Picture::get_mixed_picture_details(conn, user.id, &data.picture_ids)?))
 }
 
-/// Get picture details, includes tags and ratings
+/// Get picture details, includes tags and ratings.
+/// `fields` is a comma separated list of the sections to return among `picture`, `tags` and `ratings`, all of them by default.
+/// The sections that are not requested are not computed, and are omitted from the response.
 #[openapi(tag = "Picture")]
-#[get("/picture_details/<picture_id>")]
-pub async fn get_picture_details(db: &State<DBPool>, user: User, picture_id: i64) -> Result<Json<PictureDetails>, ErrorResponder> {
+#[get("/picture_details/<picture_id>?<fields>")]
+pub async fn get_picture_details(
+    db: &State<DBPool>,
+    user: User,
+    picture_id: i64,
+    fields: Option<String>,
+) -> Result<Json<PictureDetailsSelection>, ErrorResponder> {
     let conn: &mut DBConn = &mut db.get().unwrap();
+    let fields = PictureDetailsFields::parse(fields.as_deref())?;
 
-    let picture = Picture::get_picture_details(conn, user.id, picture_id)?;
+    let picture = Picture::get_picture_details_selection(conn, user.id, picture_id, &fields)?;
     Ok(Json(picture))
 }
+
+/// List the constraints of the user's tag groups that the tags of a picture violate: required tag groups
+/// of which the picture has no tag, and non-multiple tag groups of which it has several tags. The picture is compliant if none is returned.
+#[openapi(tag = "Picture")]
+#[get("/picture/<picture_id>/tag-compliance")]
+pub async fn get_picture_tag_compliance(db: &State<DBPool>, user: User, picture_id: i64) -> Result<Json<Vec<TagGroupViolation>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    if !Picture::can_user_access_picture(conn, picture_id, user.id)? {
+        return ErrorType::PictureNotFound.res_err_no_rollback();
+    }
+    let tag_groups = TagGroup::list_all_tags_as_tag_group_with_tags(conn, user.id)?;
+    let tag_ids = PictureTag::get_picture_tags(conn, picture_id, user.id)?;
+    Ok(Json(TagGroupWithTags::violations(&tag_groups, &tag_ids)))
+}
+
+/// Get the details of several pictures individually, each including its tags and ratings.
+/// Pictures the user can't access are skipped, others are returned in the requested order.
+#[openapi(tag = "Picture")]
+#[post("/pictures/details/list", data = "<picture_ids>")]
+pub async fn list_pictures_details(db: &State<DBPool>, user: User, picture_ids: Json<Vec<i64>>) -> Result<Json<Vec<PictureDetails>>, ErrorResponder> {
+    validate_picture_batch(&picture_ids)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::get_many_picture_details(conn, user.id, &picture_ids)?))
+}
+
+/// Get the total size of a selection of pictures, and the number of pictures counted.
+/// Pictures the user can't access are not counted.
+#[openapi(tag = "Picture")]
+#[post("/pictures/total-size", data = "<picture_ids>")]
+pub async fn get_pictures_total_size(db: &State<DBPool>, user: User, picture_ids: Json<Vec<i64>>) -> Result<Json<PicturesTotalSize>, ErrorResponder> {
+    validate_picture_batch(&picture_ids)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::total_size_for(conn, user.id, &picture_ids)?))
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct ExifStatsRequest {
+    picture_ids: Vec<i64>,
+    field: ExifField,
+}
+/// Get the histogram of the values of an EXIF field (e.g. the most used focal lengths) over a selection of pictures,
+/// as `{ value, count }` buckets, most frequent first. Pictures without value are counted in the `null` bucket.
+/// Pictures the user can't access are not counted.
+/// Does not change any state, but using post to have a request body.
+#[openapi(tag = "Picture")]
+#[post("/pictures/exif-stats", data = "<data>")]
+pub async fn get_pictures_exif_stats(
+    db: &State<DBPool>,
+    user: User,
+    data: Json<ExifStatsRequest>,
+) -> Result<Json<Vec<ExifHistogramBucket>>, ErrorResponder> {
+    validate_picture_batch(&data.picture_ids)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::exif_histogram(conn, user.id, &data.picture_ids, data.field)?))
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct EditPictureCommentRequest {
+    comment: String,
+}
+/// Edit the comment of a picture, only allowed to the owner of the picture: the pictures of other users are reported as not found.
+/// The picture is not regrouped as no arrangement strategy depends on comments.
+#[openapi(tag = "Picture")]
+#[put("/picture/<picture_id>/comment", data = "<data>")]
+pub async fn edit_picture_comment(
+    db: &State<DBPool>,
+    user: User,
+    picture_id: i64,
+    data: Json<EditPictureCommentRequest>,
+) -> Result<Json<Picture>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    validate_picture_comment(&data.comment).map_err(|e| validation_error_to_responder("comment", e))?;
+    Ok(Json(Picture::update_comment(conn, picture_id, user.id, &data.comment)?))
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct SetPictureFavoriteRequest {
+    favorite: bool,
+}
+/// Flag or unflag a picture as favorite, only allowed to the owner of the picture.
+/// The picture is not regrouped as no arrangement strategy depends on the favorite flag.
+#[openapi(tag = "Picture")]
+#[put("/picture/<picture_id>/favorite", data = "<data>")]
+pub async fn set_picture_favorite(
+    db: &State<DBPool>,
+    user: User,
+    picture_id: i64,
+    data: Json<SetPictureFavoriteRequest>,
+) -> Result<Json<Picture>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+
+    let picture = Picture::from_ids(conn, &vec![picture_id])?
+        .pop()
+        .ok_or_else(|| ErrorType::PictureNotFound.res_no_rollback())?;
+    if picture.owner_id != user.id {
+        return ErrorType::Unauthorized.res_err_no_rollback();
+    }
+
+    Ok(Json(Picture::set_favorite(conn, picture_id, user.id, data.favorite)?))
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct RatePictureRequest {
+    rating: i16,
+}
+/// Rate a picture from 0 to 5 stars, replacing the previous rating of the user.
+/// The picture must be accessible to the user. It is regrouped in the arrangements depending on ratings.
+#[openapi(tag = "Picture")]
+#[put("/picture/<picture_id>/rating", data = "<data>")]
+pub async fn rate_picture(db: &State<DBPool>, user: User, picture_id: i64, data: Json<RatePictureRequest>) -> Result<Json<Rating>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    validate_picture_rating(data.rating).map_err(|e| validation_error_to_responder("rating", e))?;
+    check_picture_accessible(conn, user.id, picture_id)?;
+
+    grouping_transaction(conn, |conn, delta| {
+        let rating = Rating::set(conn, user.id, picture_id, data.rating)?;
+        group_pictures(
+            conn,
+            delta,
+            user.id,
+            Some(&vec![picture_id]),
+            None,
+            Some(&ArrangementDependencyType::new_ratings_dependant()),
+            true,
+        )?;
+        Ok(Json(rating))
+    })
+}
+
+/// Remove the rating of the user from a picture, regrouping it in the arrangements depending on ratings.
+#[openapi(tag = "Picture")]
+#[delete("/picture/<picture_id>/rating")]
+pub async fn remove_picture_rating(db: &State<DBPool>, user: User, picture_id: i64) -> Result<(), ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    check_picture_accessible(conn, user.id, picture_id)?;
+
+    grouping_transaction(conn, |conn, delta| {
+        Rating::delete(conn, user.id, picture_id)?;
+        group_pictures(
+            conn,
+            delta,
+            user.id,
+            Some(&vec![picture_id]),
+            None,
+            Some(&ArrangementDependencyType::new_ratings_dependant()),
+            true,
+        )
+    })
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct RatePicturesRequest {
+    picture_ids: Vec<i64>,
+    /// Rating from 0 to 5 stars, or null to remove the ratings
+    rating: Option<i16>,
+}
+/// Rate several pictures at once, replacing the previous ratings of the user, or remove their ratings if `rating` is null.
+/// Pictures the user can't access are skipped. The rated pictures are regrouped once in the arrangements depending on ratings.
+/// Returns the ids of the rated pictures.
+#[openapi(tag = "Picture")]
+#[post("/pictures/ratings", data = "<data>")]
+pub async fn rate_pictures(db: &State<DBPool>, user: User, data: Json<RatePicturesRequest>) -> Result<Json<Vec<i64>>, ErrorResponder> {
+    validate_picture_batch(&data.picture_ids)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    if let Some(rating) = data.rating {
+        validate_picture_rating(rating).map_err(|e| validation_error_to_responder("rating", e))?;
+    }
+    rate_pictures_batch(conn, user.id, &data.picture_ids, data.rating).map(Json)
+}
+
+/// Set (or remove if None) the rating of the accessible pictures among `picture_ids` and regroup them, in a single transaction.
+/// Returns the ids of the rated pictures.
+pub fn rate_pictures_batch(conn: &mut DBConn, user_id: i32, picture_ids: &[i64], rating: Option<i16>) -> Result<Vec<i64>, ErrorResponder> {
+    let accessible_ids = Picture::filter_user_accessible_pictures(conn, user_id, picture_ids)?;
+    let picture_ids = batch_picture_ids(picture_ids, &accessible_ids);
+
+    grouping_transaction(conn, |conn, delta| {
+        match rating {
+            Some(rating) => {
+                Rating::set_batch(conn, user_id, &picture_ids, rating)?;
+            }
+            None => {
+                Rating::remove_batch(conn, user_id, &picture_ids)?;
+            }
+        }
+        group_pictures(
+            conn,
+            delta,
+            user_id,
+            Some(&picture_ids),
+            None,
+            Some(&ArrangementDependencyType::new_ratings_dependant()),
+            true,
+        )?;
+        Ok(picture_ids)
+    })
+}
+
+fn check_picture_accessible(conn: &mut DBConn, user_id: i32, picture_id: i64) -> Result<(), ErrorResponder> {
+    if Picture::filter_user_accessible_pictures(conn, user_id, &[picture_id])?.is_empty() {
+        return ErrorType::PictureNotFound.res_err_no_rollback();
+    }
+    Ok(())
+}
@@ -1,12 +1,18 @@
 use crate::api::picture::ListPictureData;
 use crate::database::database::{DBConn, DBPool};
-use crate::database::picture::picture::Picture;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::{CalendarDay, Picture, PictureChange, PictureNeighbors, TagFacet};
 use crate::database::schema::*;
 use crate::database::user::user::User;
+use crate::grouping::grouping_delta::grouping_transaction;
+use crate::grouping::grouping_process::group_pictures;
+use crate::grouping::strategy_filtering::StrategyFiltering;
 use crate::rocket::futures::StreamExt;
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use crate::utils::s3::PictureStorer;
-use crate::utils::thumbnail::{generate_blurhash, PictureThumbnail, THUMBS_TEMP_DIR};
+use crate::utils::thumbnail::{generate_blurhash, PictureThumbnail};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use diesel::dsl::{exists, not, Filter};
 use diesel::query_dsl::methods;
 use diesel::QueryDsl;
@@ -18,19 +24,64 @@ use rocket::State;
 use rocket_okapi::{openapi, JsonSchema};
 use std::cmp::Ordering;
 use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PicturesQuery {
     pub filters: Vec<PictureFilter>, // Applies an AND between filters
+    /// Optional filtering tree (like arrangement filters), applied with an AND with the other filters.
+    #[serde(default)]
+    pub filter_tree: Option<StrategyFiltering>,
     pub sorts: Vec<PictureSort>,
     pub page: i32,
+    /// Number of pictures per page, defaults to `DEFAULT_PAGE_SIZE` and is capped to `MAX_PAGE_SIZE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<i64>,
+    /// Keyset pagination: the page is made of the pictures following this one instead of the page `page`.
+    /// Only valid when sorted by descending creation date only (see [`PicturesQuery::check_keyset_sorts`]).
+    /// Set from the `after_creation_date` and `after_id` query parameters.
+    #[serde(skip)]
+    pub after: Option<PictureCursor>,
 }
 impl PicturesQuery {
     pub fn from_page(page: i32) -> Self {
         PicturesQuery {
             filters: vec![],
+            filter_tree: None,
             sorts: vec![],
             page,
+            page_size: None,
+            after: None,
+        }
+    }
+    /// Keyset pagination ([`PicturesQuery::after`]) only follows the creation date, most recent first:
+    /// any other sort is rejected rather than silently ignored.
+    pub fn check_keyset_sorts(&self) -> Result<(), ErrorResponder> {
+        if self.after.is_some() && self.sorts != [PictureSort::CreationDate { ascend: false }] {
+            return ErrorType::InvalidInput("after_creation_date and after_id require sorting by descending creation date".to_string())
+                .res_err_no_rollback();
+        }
+        Ok(())
+    }
+}
+/// Position of a picture in the pictures sorted by creation date, most recent first, then by id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PictureCursor {
+    pub creation_date: NaiveDateTime,
+    pub id: i64,
+}
+impl PictureCursor {
+    /// Cursor of the `after_creation_date` and `after_id` query parameters, that must be both set or both unset.
+    /// The date is formatted like the dates of the pictures lists (e.g. `2024-05-01T12:30:00.250`).
+    pub fn parse(creation_date: Option<&str>, id: Option<i64>) -> Result<Option<Self>, ErrorResponder> {
+        match (creation_date, id) {
+            (None, None) => Ok(None),
+            (Some(creation_date), Some(id)) => {
+                let creation_date = NaiveDateTime::from_str(creation_date)
+                    .map_err(|_| ErrorType::InvalidInput(format!("Invalid after_creation_date: {}", creation_date)).res_no_rollback())?;
+                Ok(Some(PictureCursor { creation_date, id }))
+            }
+            _ => ErrorType::InvalidInput("after_creation_date and after_id must be set together".to_string()).res_err_no_rollback(),
         }
     }
 }
@@ -38,11 +89,17 @@ impl PicturesQuery {
 #[serde(tag = "type")]
 pub enum PictureFilter {
     Arrangement { invert: bool, ids: Vec<i32> }, // user must be the owner
-    Group { invert: bool, ids: Vec<i32> },       // can be a shared group
+    Group { invert: bool, ids: Vec<i32> },       // can be a shared group, inverted to exclude the pictures in any of the groups
     Deleted { invert: bool },
+    Copied { invert: bool },                  // Pictures copied from another user's picture
+    Favorite { invert: bool },                // Pictures flagged as favorite by their owner
     Owned { invert: bool },                   // Only pictures owned by the user
+    Author { invert: bool, ids: Vec<i32> },   // Pictures authored by one of the users, independently of the owner
     TagGroup { invert: bool, ids: Vec<i32> }, // user must be the owner
     Tag { invert: bool, ids: Vec<i32> },      // user must be the owner
+    Orientation { invert: bool, values: Vec<PictureOrientation> },
+    AspectRatio { min: Option<f64>, max: Option<f64> }, // Bounds of width / height, e.g. { max: 1.0 } for portrait pictures
+    SizeKo { invert: bool, min: Option<i32>, max: Option<i32> }, // Bounds of the picture size in kB, both included
 }
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
@@ -51,13 +108,166 @@ pub enum PictureSort {
     EditionDate { ascend: bool },
 }
 
+/// Additional data to include in a pictures list, from the comma separated `include` query parameter.
+#[derive(Debug, Default, PartialEq)]
+pub struct ListInclude {
+    /// Groups of the user's arrangements containing each picture
+    pub groups: bool,
+}
+impl ListInclude {
+    pub fn parse(include: Option<&str>) -> Result<Self, ErrorResponder> {
+        let mut list_include = ListInclude::default();
+        for value in include.unwrap_or_default().split(',').map(str::trim).filter(|value| !value.is_empty()) {
+            match value {
+                "groups" => list_include.groups = true,
+                _ => return ErrorType::InvalidInput(format!("Unknown include value: {}", value)).res_err_no_rollback(),
+            }
+        }
+        Ok(list_include)
+    }
+}
+
 /// Query pictures using custom query filters and sorting parameters.
+/// With `include=groups`, each picture lists the groups of the user's arrangements containing it.
+/// With `after_creation_date` and `after_id` (the ones of the last picture of the previous page), the pictures are paginated
+/// by keyset instead of by page number, ignoring the page of the query. The query must then be sorted by creation date only,
+/// descending, as the first page was.
+/// Unlike page numbers, keysets are stable when pictures are added or removed while iterating. The first page is the page 1
+/// of the query sorted by creation date, most recent first.
 /// Does not change any state, but using post to have a request body.
 #[openapi(tag = "Picture")]
-#[post("/query_pictures", data = "<query>")]
-pub async fn query_pictures(db: &State<DBPool>, user: User, query: Json<PicturesQuery>) -> Result<Json<Vec<ListPictureData>>, ErrorResponder> {
+#[post("/query_pictures?<include>&<after_creation_date>&<after_id>", data = "<query>")]
+pub async fn query_pictures(
+    db: &State<DBPool>,
+    user: User,
+    query: Json<PicturesQuery>,
+    include: Option<String>,
+    after_creation_date: Option<String>,
+    after_id: Option<i64>,
+) -> Result<Json<Vec<ListPictureData>>, ErrorResponder> {
     let conn: &mut DBConn = &mut db.get().unwrap();
-    let pictures = Picture::query(conn, user.id, query.into_inner(), 100)?;
+    let include = ListInclude::parse(include.as_deref())?;
+    let page_size = CONFIG.page_size(query.page_size);
+    let mut query = query.into_inner();
+    query.after = PictureCursor::parse(after_creation_date.as_deref(), after_id)?;
+    query.check_keyset_sorts()?;
+    let mut pictures = Picture::query(conn, user.id, query, page_size)?;
 
+    if include.groups && !pictures.is_empty() {
+        let picture_ids: Vec<i64> = pictures.iter().map(|picture| picture.id).collect();
+        let memberships = Group::user_groups_of_pictures(conn, user.id, &picture_ids)?;
+        ListPictureData::attach_group_ids(&mut pictures, &memberships);
+    }
     Ok(Json(pictures))
 }
+
+/// Count, for each tag of the user, the pictures matching the query that have this tag, to refine a search by tag.
+/// The sorting and the page of the query are ignored: all the matching pictures the user can access are counted.
+/// Does not change any state, but using post to have a request body.
+#[openapi(tag = "Picture")]
+#[post("/pictures/facets", data = "<query>")]
+pub async fn query_pictures_tag_facets(db: &State<DBPool>, user: User, query: Json<PicturesQuery>) -> Result<Json<Vec<TagFacet>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::tag_facets_for_query(conn, user.id, query.into_inner())?))
+}
+
+/// List the ids of all the pictures matching the query that the user can access, by ascending id, for bulk operations on the results.
+/// The sorting and the page of the query are ignored. Returns `UnprocessableEntity` when more than `MAX_QUERY_IDS` pictures match.
+/// Does not change any state, but using post to have a request body.
+#[openapi(tag = "Picture")]
+#[post("/pictures/ids", data = "<query>")]
+pub async fn query_pictures_ids(db: &State<DBPool>, user: User, query: Json<PicturesQuery>) -> Result<Json<Vec<i64>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::query_ids(conn, user.id, query.into_inner(), CONFIG.max_query_ids)?))
+}
+
+/// Restore from the trash all the pictures owned by the user matching the query, and regroup them once.
+/// Only the pictures in the trash are restored, as with a `Deleted` filter. The sorting and the page of the query are ignored.
+/// The pictures are restored by batches of `MAX_BATCH_PICTURES`, in a single transaction. Returns the ids of the restored pictures.
+#[openapi(tag = "Picture")]
+#[post("/pictures/restore-by-query", data = "<query>")]
+pub async fn restore_pictures_by_query(db: &State<DBPool>, user: User, query: Json<PicturesQuery>) -> Result<Json<Vec<i64>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(restore_and_group_by_query(conn, user.id, &query, CONFIG.max_batch_pictures as i64)?))
+}
+
+/// Restore the pictures of the user matching the query (see [`Picture::restore_by_query`]) and group them again, in a single transaction.
+pub fn restore_and_group_by_query(conn: &mut DBConn, user_id: i32, query: &PicturesQuery, batch_size: i64) -> Result<Vec<i64>, ErrorResponder> {
+    grouping_transaction(conn, |conn, delta| {
+        let picture_ids = Picture::restore_by_query(conn, user_id, query, batch_size)?;
+        if !picture_ids.is_empty() {
+            group_pictures(conn, delta, user_id, Some(&picture_ids), None, None, false)?;
+        }
+        Ok(picture_ids)
+    })
+}
+
+/// Previous and next pictures of a picture in the results of a pictures query, with the same filters and sorts as `/query_pictures`,
+/// for the navigation between pictures without fetching the pages. The page of the query is ignored.
+/// The picture must match the query, and its neighbors are `null` at the ends of the results.
+#[openapi(tag = "Picture")]
+#[post("/picture/<picture_id>/neighbors", data = "<query>")]
+pub async fn get_picture_neighbors(
+    db: &State<DBPool>,
+    user: User,
+    picture_id: i64,
+    query: Json<PicturesQuery>,
+) -> Result<Json<PictureNeighbors>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(Picture::neighbors_in_query(conn, user.id, picture_id, query.into_inner())?))
+}
+
+/// Parse the `from` and `to` days of a calendar (e.g. `2024-05-01`), `from` being before or equal to `to`.
+pub fn parse_calendar_range(from: &str, to: &str) -> Result<(NaiveDate, NaiveDate), ErrorResponder> {
+    let from_date = NaiveDate::from_str(from).map_err(|_| ErrorType::InvalidInput(format!("Invalid from date: {}", from)).res_no_rollback())?;
+    let to_date = NaiveDate::from_str(to).map_err(|_| ErrorType::InvalidInput(format!("Invalid to date: {}", to)).res_no_rollback())?;
+    if from_date > to_date {
+        return ErrorType::InvalidInput("from must be before or equal to to".to_string()).res_err_no_rollback();
+    }
+    Ok((from_date, to_date))
+}
+
+/// Count the pictures the user can access (trash excepted) per day of creation, for calendar views.
+/// `from` and `to` are days (e.g. `2024-05-01`), both included. Days without pictures are not listed.
+#[openapi(tag = "Picture")]
+#[get("/pictures/calendar?<from>&<to>")]
+pub async fn query_pictures_calendar(db: &State<DBPool>, user: User, from: String, to: String) -> Result<Json<Vec<CalendarDay>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let (from, to) = parse_calendar_range(&from, &to)?;
+    Ok(Json(Picture::calendar_counts(conn, user.id, from, to)?))
+}
+
+/// List the pictures owned by the user that are not in any group, whatever the arrangement, most recent first.
+/// It reflects the actual group membership: pictures caught by a manual group or an "Other" group are not listed.
+#[openapi(tag = "Picture")]
+#[get("/pictures/ungrouped?<page>")]
+pub async fn query_ungrouped_pictures(db: &State<DBPool>, user: User, page: Option<i32>) -> Result<Json<Vec<ListPictureData>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let page = page.unwrap_or(1);
+    if page < 1 {
+        return ErrorType::InvalidInput("Page number must be greater than 0".to_string()).res_err_no_rollback();
+    }
+    Ok(Json(Picture::query_ungrouped(conn, user.id, page, CONFIG.page_size(None))?))
+}
+
+/// List the changes of the pictures the user can access since a UNIX timestamp (in seconds), for clients synchronizing their copy:
+/// pictures modified after `since`, themselves or their tags, ratings or groups,
+/// and pictures moved to the trash after `since`, flagged as deleted so that clients remove them.
+#[openapi(tag = "Picture")]
+#[get("/pictures/changes?<since>&<page>")]
+pub async fn query_pictures_changes(
+    db: &State<DBPool>,
+    user: User,
+    since: i64,
+    page: Option<i32>,
+) -> Result<Json<Vec<PictureChange>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let page = page.unwrap_or(1);
+    if page < 1 {
+        return ErrorType::InvalidInput("Page number must be greater than 0".to_string()).res_err_no_rollback();
+    }
+    let since = DateTime::from_timestamp(since, 0)
+        .ok_or_else(|| ErrorType::InvalidInput("Invalid timestamp".to_string()).res_no_rollback())?
+        .naive_utc();
+    Ok(Json(Picture::changes_since(conn, user.id, since, page, CONFIG.page_size(None))?))
+}
@@ -0,0 +1,75 @@
+use crate::api::picture::ListPictureData;
+use crate::api::query_pictures::PicturesQuery;
+use crate::database::database::{DBConn, DBPool};
+use crate::database::picture::picture::Picture;
+use crate::database::picture::saved_search::{SavedSearch, SavedSearchDetails};
+use crate::database::user::user::User;
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use crate::utils::validation::validate_input;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::{openapi, JsonSchema};
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, JsonSchema, Validate)]
+pub struct SavedSearchRequest {
+    #[validate(length(min = 1, max = 32, message = "Name must be between 1 and 32 characters"))]
+    pub name: String,
+    pub query: PicturesQuery,
+}
+
+/// Save a named pictures query, to run it again later.
+/// The page of the query is ignored when running it.
+#[openapi(tag = "Picture")]
+#[post("/saved-searches", data = "<data>")]
+pub async fn create_saved_search(db: &State<DBPool>, user: User, data: Json<SavedSearchRequest>) -> Result<Json<SavedSearchDetails>, ErrorResponder> {
+    validate_input(&data)?;
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let data = data.into_inner();
+    Ok(Json(SavedSearch::insert(conn, user.id, data.name, &data.query)?.to_details()?))
+}
+
+/// List the saved searches of the user.
+#[openapi(tag = "Picture")]
+#[get("/saved-searches")]
+pub async fn list_saved_searches(db: &State<DBPool>, user: User) -> Result<Json<Vec<SavedSearchDetails>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let saved_searches = SavedSearch::from_user_id(conn, user.id)?;
+    Ok(Json(saved_searches.iter().map(SavedSearch::to_details).collect::<Result<Vec<_>, _>>()?))
+}
+
+/// Delete a saved search of the user.
+#[openapi(tag = "Picture")]
+#[delete("/saved-searches/<saved_search_id>")]
+pub async fn delete_saved_search(db: &State<DBPool>, user: User, saved_search_id: i32) -> Result<(), ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let saved_search = SavedSearch::from_id_and_user_id(conn, saved_search_id, user.id)?;
+    SavedSearch::delete(conn, saved_search.id)
+}
+
+/// Run a saved search, returning the requested page of results as with `/query_pictures`.
+#[openapi(tag = "Picture")]
+#[get("/saved-searches/<saved_search_id>/results?<page>")]
+pub async fn query_saved_search(
+    db: &State<DBPool>,
+    user: User,
+    saved_search_id: i32,
+    page: Option<i32>,
+) -> Result<Json<Vec<ListPictureData>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let page = page.unwrap_or(1);
+    if page < 1 {
+        return ErrorType::InvalidInput("Page number must be greater than 0".to_string()).res_err_no_rollback();
+    }
+    run_saved_search(conn, user.id, saved_search_id, page).map(Json)
+}
+
+/// Run the saved search of the user, returning the given page of results.
+pub fn run_saved_search(conn: &mut DBConn, user_id: i32, saved_search_id: i32, page: i32) -> Result<Vec<ListPictureData>, ErrorResponder> {
+    let saved_search = SavedSearch::from_id_and_user_id(conn, saved_search_id, user_id)?;
+    let query = saved_search.query_for_page(page)?;
+    let page_size = CONFIG.page_size(query.page_size);
+    Picture::query(conn, user_id, query, page_size)
+}
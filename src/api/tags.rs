@@ -1,18 +1,23 @@
 use crate::api::query_pictures::PicturesQuery;
 use crate::database::database::{DBConn, DBPool};
-use crate::database::group::arrangement::ArrangementDependencyType;
+use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType, ArrangementDetails};
+use crate::database::group::shared_group::{SharePermissions, SharedGroup};
 use crate::database::picture::picture::Picture;
 use crate::database::picture::picture_tag::PictureTag;
 use crate::database::tag::tag::Tag;
-use crate::database::tag::tag_group::{TagGroup, TagGroupWithTags};
+use crate::database::tag::tag_group::{TagGroup, TagGroupRepairReport, TagGroupWithTags};
 use crate::database::user::user::User;
+use crate::grouping::grouping_delta::{grouping_transaction, lock_user_grouping};
 use crate::grouping::grouping_process::group_pictures;
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
+use crate::utils::validation::validate_picture_batch;
 use itertools::Itertools;
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::State;
 use rocket_okapi::{openapi, JsonSchema};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct AllTagsResponse {
@@ -43,50 +48,102 @@ pub async fn create_tag_group(data: Json<TagGroupWithTags>, db: &State<DBPool>,
     let mut conn: &mut DBConn = &mut db.get().unwrap();
 
     err_transaction(&mut conn, |conn| {
-        // Insert the group and tags
-        let mut to_insert_tag_group = data.tag_group.clone();
-        to_insert_tag_group.user_id = user.id;
-        let inserted_tag_group = TagGroup::insert(conn, to_insert_tag_group)?;
-        let inserted_tag_group_id = inserted_tag_group.id.unwrap();
-        let mut inserted_tags = Vec::new();
-
-        for mut tag in data.into_inner().tags {
-            tag.tag_group_id = inserted_tag_group_id;
-            inserted_tags.push(Tag::insert(conn, tag)?);
-        }
+        let inserted = insert_tag_group_with_tags(conn, user.id, data.into_inner())?;
 
-        let default_tag_ids = inserted_tags.iter().filter(|tag| tag.is_default).map(|tag| tag.id).collect_vec();
+        // Add all default tags to all pictures
+        let default_tag_ids = inserted.tags.iter().filter(|tag| tag.is_default).map(|tag| tag.id).collect_vec();
+        add_tags_to_all_pictures(conn, user.id, &default_tag_ids)?;
 
-        // Check requirements (on inserted_tags to have the correct ids):
-        //  - If the group is required, there must be at least one default tag.
-        //  - If the group is not multiple, there can't be more than one default tag.
-        if inserted_tag_group.required && default_tag_ids.len() == 0 {
-            return ErrorType::UnprocessableEntity("Required tag group must have at least one default tag".to_string()).res_err();
-        }
-        if !inserted_tag_group.multiple && default_tag_ids.len() > 1 {
-            return ErrorType::UnprocessableEntity("Multiple tag group can't have more than one default tag".to_string()).res_err();
-        }
+        Ok(Json(inserted))
+    })
+}
 
-        // Add all default tags to all pictures
-        let mut query = PicturesQuery::from_page(1);
-        let mut pictures = Picture::query(conn, user.id, query.clone(), 1000)?;
-        while pictures.len() > 0 {
-            let ids = pictures.into_iter().map(|picture| picture.id).collect_vec();
-            PictureTag::add_pictures_batch(conn, &default_tag_ids, &ids)?;
-            query.page += 1;
-            if ids.len() < 1000 {
-                break;
-            }
-            pictures = Picture::query(conn, user.id, query.clone(), 1000)?;
-        }
+/// Creates multiple tag groups with their tags at once.
+/// All the tag groups are created or none of them is: one invalid tag group rejects the whole batch.
+#[openapi(tag = "Tags")]
+#[post("/tag_groups/batch", data = "<data>")]
+pub async fn create_tag_groups_batch(
+    data: Json<Vec<TagGroupWithTags>>,
+    db: &State<DBPool>,
+    user: User,
+) -> Result<Json<Vec<TagGroupWithTags>>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
 
-        Ok(Json(TagGroupWithTags {
-            tag_group: inserted_tag_group,
-            tags: inserted_tags,
-        }))
+    err_transaction(conn, |conn| Ok(Json(insert_tag_groups_batch(conn, user.id, data.into_inner())?)))
+}
+
+/// Insert tag groups of the user with their tags, then add the default tags of all the groups to all the pictures at once.
+/// Must run in a transaction: an invalid tag group fails with a rollback error after the previous ones were inserted.
+pub(crate) fn insert_tag_groups_batch(
+    conn: &mut DBConn,
+    user_id: i32,
+    tag_groups: Vec<TagGroupWithTags>,
+) -> Result<Vec<TagGroupWithTags>, ErrorResponder> {
+    let inserted = tag_groups
+        .into_iter()
+        .map(|tag_group_with_tags| insert_tag_group_with_tags(conn, user_id, tag_group_with_tags))
+        .collect::<Result<Vec<_>, ErrorResponder>>()?;
+
+    let default_tag_ids = inserted
+        .iter()
+        .flat_map(|tgwt| tgwt.tags.iter())
+        .filter(|tag| tag.is_default)
+        .map(|tag| tag.id)
+        .collect_vec();
+    add_tags_to_all_pictures(conn, user_id, &default_tag_ids)?;
+
+    Ok(inserted)
+}
+
+/// Insert a tag group of the user with its tags, positioned in the provided order,
+/// checking the default tags requirements and the maximum number of tag groups of the user.
+/// Must run in a transaction: the user’s grouping lock is held until its end so that concurrent inserts can't exceed the limit.
+pub(crate) fn insert_tag_group_with_tags(
+    conn: &mut DBConn,
+    user_id: i32,
+    tag_group_with_tags: TagGroupWithTags,
+) -> Result<TagGroupWithTags, ErrorResponder> {
+    tag_group_with_tags.check_default_tags()?;
+    lock_user_grouping(conn, user_id)?;
+    TagGroup::check_user_limit(TagGroup::count_for_user(conn, user_id)?, CONFIG.max_tag_groups_per_user)?;
+
+    let mut to_insert_tag_group = tag_group_with_tags.tag_group;
+    to_insert_tag_group.user_id = user_id;
+    let inserted_tag_group = TagGroup::insert(conn, to_insert_tag_group)?;
+    let inserted_tag_group_id = inserted_tag_group.id.unwrap();
+    let mut inserted_tags = Vec::new();
+
+    for (position, mut tag) in tag_group_with_tags.tags.into_iter().enumerate() {
+        tag.tag_group_id = inserted_tag_group_id;
+        tag.position = position as i32;
+        inserted_tags.push(Tag::insert(conn, tag)?);
+    }
+
+    Ok(TagGroupWithTags {
+        tag_group: inserted_tag_group,
+        tags: inserted_tags,
     })
 }
 
+/// Add tags to all the pictures of the user, by batches of 1000 pictures.
+pub(crate) fn add_tags_to_all_pictures(conn: &mut DBConn, user_id: i32, tag_ids: &Vec<i32>) -> Result<(), ErrorResponder> {
+    if tag_ids.is_empty() {
+        return Ok(());
+    }
+    let mut query = PicturesQuery::from_page(1);
+    let mut pictures = Picture::query(conn, user_id, query.clone(), 1000)?;
+    while !pictures.is_empty() {
+        let ids = pictures.into_iter().map(|picture| picture.id).collect_vec();
+        PictureTag::add_pictures_batch(conn, tag_ids, &ids)?;
+        query.page += 1;
+        if ids.len() < 1000 {
+            break;
+        }
+        pictures = Picture::query(conn, user_id, query.clone(), 1000)?;
+    }
+    Ok(())
+}
+
 /// Patch a tag group and its tags (create, edit, delete)
 #[openapi(tag = "Tags")]
 #[patch("/tag_group", data = "<data>")]
@@ -127,27 +184,22 @@ pub async fn patch_tag_group(data: Json<PatchTagGroupRequest>, db: &State<DBPool
             updated_or_new_tags.push(Tag::patch(conn, tag)?);
         }
 
-        // 4. Create new tags
-        for mut tag in data.new_tags.clone() {
+        // 4. Create new tags, placed after the existing ones
+        let next_position = old_tag_group_tags.iter().map(|tag| tag.position + 1).max().unwrap_or(0);
+        for (index, mut tag) in data.new_tags.clone().into_iter().enumerate() {
             tag.tag_group_id = updated_tag_group.id.unwrap();
+            tag.position = next_position + index as i32;
             updated_or_new_tags.push(Tag::insert(conn, tag)?);
         }
 
-        // 5. Check requirements for the updated tag group:
-        //  - If the group is required, there must be at least one default tag.
-        //  - If the group is not multiple, there can't be more than one default tag.
+        // 5. Check that the default tags satisfy the requirements of the updated tag group
         let default_tag_ids = updated_or_new_tags
             .iter()
             .chain(unedited_tags.iter())
             .filter(|tag| tag.is_default)
             .map(|tag| tag.id)
             .collect_vec();
-        if data.edited_tag_group.required && default_tag_ids.len() == 0 {
-            return ErrorType::UnprocessableEntity("Required tag group must have at least one default tag".to_string()).res_err();
-        }
-        if !data.edited_tag_group.multiple && default_tag_ids.len() > 1 {
-            return ErrorType::UnprocessableEntity("Multiple tag group can't have more than one default tag".to_string()).res_err();
-        }
+        data.edited_tag_group.check_default_tag_ids(&default_tag_ids)?;
 
         // 6. If the group is required, add all the default tag to all pictures that don't have any tag from this tag group
         if updated_tag_group.required {
@@ -156,6 +208,7 @@ pub async fn patch_tag_group(data: Json<PatchTagGroupRequest>, db: &State<DBPool
 
         // 7. Gather all Tags: all old tags that are not deleted or edited, and all updated/new tags
         let mut all_tags = updated_or_new_tags.iter().chain(unedited_tags.iter()).cloned().collect::<Vec<Tag>>();
+        all_tags.sort_by_key(|tag| (tag.position, tag.id));
 
         // 7. Update arrangements strategies if needed
         // TODO: update arrangements that depends on this tag group.
@@ -168,14 +221,19 @@ pub async fn patch_tag_group(data: Json<PatchTagGroupRequest>, db: &State<DBPool
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct IDOnly {
+pub struct DeleteTagGroupRequest {
     pub id: i32,
+    /// Delete the tag group even if enabled arrangements depend on it, disabling them
+    #[serde(default)]
+    pub force: bool,
 }
 
-/// Delete an existing tag group
+/// Delete an existing tag group.
+/// If enabled arrangements group by this tag group or filter on its tags, the deletion is refused with `UnprocessableEntity`,
+/// unless `force` is set: these arrangements are then disabled, keeping their groups as they are.
 #[openapi(tag = "Tags")]
 #[delete("/tag_group", data = "<data>")]
-pub async fn delete_tag_group(data: Json<IDOnly>, db: &State<DBPool>, user: User) -> Result<(), ErrorResponder> {
+pub async fn delete_tag_group(data: Json<DeleteTagGroupRequest>, db: &State<DBPool>, user: User) -> Result<(), ErrorResponder> {
     let mut conn: &mut DBConn = &mut db.get().unwrap();
 
     // Check that the user is the owner of the tag group
@@ -185,17 +243,182 @@ pub async fn delete_tag_group(data: Json<IDOnly>, db: &State<DBPool>, user: User
     }
 
     err_transaction(&mut conn, |conn| {
+        // Check the arrangements depending on the tag group
+        let tag_ids = Tag::list_tags(conn, data.id)?.into_iter().map(|tag| tag.id).collect_vec();
+        let arrangements = Arrangement::list_arrangements_and_groups(conn, user.id)?;
+        let dependant_arrangements = ArrangementDetails::depending_on_tag_group(&arrangements, data.id, &tag_ids);
+        TagGroup::check_deletable(&dependant_arrangements, data.force)?;
+        for arrangement in dependant_arrangements.iter() {
+            Arrangement::set_enabled(conn, arrangement.id, false)?;
+        }
+
         let deleted = TagGroup::delete(conn, data.id)?;
         if deleted == 0 {
             return ErrorType::InternalError("Tag group has not been deleted".to_string()).res_err();
         }
 
-        // TODO: apply deletion to all pictures and strategies
+        // TODO: apply deletion to all pictures
 
         Ok(())
     })
 }
 
+/// Reorder the tags of a tag group.
+/// The body is the ordered list of the tag group's tag ids, it must contain each of its tags exactly once.
+/// Returns the tags sorted by their new position.
+#[openapi(tag = "Tags")]
+#[put("/tag_group/<tag_group_id>/tag-order", data = "<data>")]
+pub async fn reorder_tags(tag_group_id: i32, data: Json<Vec<i32>>, db: &State<DBPool>, user: User) -> Result<Json<Vec<Tag>>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+
+    // Check that the user is the owner of the tag group
+    let tag_group = TagGroup::from_id(conn, tag_group_id)?;
+    if tag_group.user_id != user.id {
+        return ErrorType::Unauthorized.res_err();
+    }
+
+    err_transaction(conn, |conn| Ok(Json(Tag::reorder(conn, tag_group_id, &data)?)))
+}
+
+/// Remove a tag from all the pictures, without deleting the tag.
+/// If the tag belongs to a required tag group, pictures left without any tag of the group get its default tag back,
+/// and the default tag itself can't be removed.
+/// Returns the number of pictures the tag has been removed from.
+#[openapi(tag = "Tags")]
+#[delete("/tag/<tag_id>/assignments")]
+pub async fn clear_tag_assignments(tag_id: i32, db: &State<DBPool>, user: User) -> Result<Json<usize>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+    clear_tag(conn, user.id, tag_id).map(Json)
+}
+
+/// Unassign the tag from all the pictures and regroup them, in a single transaction.
+pub fn clear_tag(conn: &mut DBConn, user_id: i32, tag_id: i32) -> Result<usize, ErrorResponder> {
+    // Check that the user is the owner of the tag
+    let tag = Tag::from_id(conn, tag_id)?;
+    let tag_group = TagGroup::from_id(conn, tag.tag_group_id)?;
+    if tag_group.user_id != user_id {
+        return ErrorType::Unauthorized.res_err();
+    }
+    tag.check_assignments_removable(&tag_group)?;
+
+    grouping_transaction(conn, |conn, delta| {
+        let picture_ids = PictureTag::remove_all_pictures(conn, tag.id)?;
+        if picture_ids.is_empty() {
+            return Ok(0);
+        }
+
+        // Add the default tag back to the pictures that no longer have any tag of a required tag group
+        if tag_group.required {
+            let default_tag = Tag::list_tags(conn, tag.tag_group_id)?
+                .into_iter()
+                .find(|tag| tag.is_default)
+                .ok_or_else(|| ErrorType::InternalError("There is a required tag group without any default tag".to_string()).res())?;
+            TagGroup::add_default_tag_to_pictures_without_tag_from_list(conn, default_tag.id, tag.tag_group_id, &picture_ids)?;
+        }
+
+        // Regroup the pictures
+        group_pictures(
+            conn,
+            delta,
+            user_id,
+            Some(&picture_ids),
+            None,
+            Some(&ArrangementDependencyType::new_tags_dependant()),
+            true,
+        )?;
+
+        Ok(picture_ids.len())
+    })
+}
+
+/// Add the default tags of each tag group to all the pictures that have no tag of the group,
+/// e.g. after pictures have been imported outside the normal upload path.
+/// Returns the number of tag assignments added; running it again adds nothing.
+#[openapi(tag = "Tags")]
+#[post("/tags/apply-defaults")]
+pub async fn apply_default_tags(db: &State<DBPool>, user: User) -> Result<Json<usize>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+
+    grouping_transaction(conn, |conn, delta| {
+        let mut added = 0;
+        let mut query = PicturesQuery::from_page(1);
+        let mut pictures = Picture::query(conn, user.id, query.clone(), 1000)?;
+        while !pictures.is_empty() {
+            let ids = pictures.into_iter().map(|picture| picture.id).collect_vec();
+            let batch_added = PictureTag::add_default_tags_to_pictures_without_tags(conn, user.id, &ids)?;
+            if batch_added > 0 {
+                group_pictures(
+                    conn,
+                    delta,
+                    user.id,
+                    Some(&ids),
+                    None,
+                    Some(&ArrangementDependencyType::new_tags_dependant()),
+                    true,
+                )?;
+                added += batch_added;
+            }
+            query.page += 1;
+            if ids.len() < 1000 {
+                break;
+            }
+            pictures = Picture::query(conn, user.id, query.clone(), 1000)?;
+        }
+        Ok(Json(added))
+    })
+}
+
+/// Make the tags of all the pictures comply with the constraints of the user's tag groups, then regroup the changed pictures:
+/// pictures without any tag of a required tag group get its default tags, and pictures having several tags of
+/// a non-multiple tag group keep only one of them, the default tag if they have it, otherwise the most recently assigned one.
+/// Pictures missing a tag of a required tag group without default tags are left as they are, and reported as unresolved.
+/// Returns the changes made for each tag group, tag groups without any change nor unresolved picture being left out.
+#[openapi(tag = "Tags")]
+#[post("/tags/repair-compliance")]
+pub async fn repair_tags_compliance(db: &State<DBPool>, user: User) -> Result<Json<Vec<TagGroupRepairReport>>, ErrorResponder> {
+    let conn = &mut db.get().unwrap();
+
+    grouping_transaction(conn, |conn, delta| {
+        let tag_groups = TagGroup::list_all_tags_as_tag_group_with_tags(conn, user.id)?;
+        let mut reports: BTreeMap<i32, TagGroupRepairReport> = BTreeMap::new();
+        let mut query = PicturesQuery::from_page(1);
+        let mut pictures = Picture::query(conn, user.id, query.clone(), 1000)?;
+        while !pictures.is_empty() {
+            let ids = pictures.into_iter().map(|picture| picture.id).collect_vec();
+            let pictures_tags = PictureTag::get_pictures_tags(conn, user.id, &ids)?;
+            let repairs = tag_groups
+                .iter()
+                .filter_map(|tag_group| tag_group.repair(&ids, &pictures_tags))
+                .collect_vec();
+            for repair in repairs.iter() {
+                repair.apply(conn)?;
+                reports.entry(repair.tag_group_id).or_default().add(repair);
+            }
+            // Unresolved pictures are left as they are, there is nothing to regroup for them
+            if repairs
+                .iter()
+                .any(|repair| !repair.defaulted_picture_ids.is_empty() || !repair.removed_tags.is_empty())
+            {
+                group_pictures(
+                    conn,
+                    delta,
+                    user.id,
+                    Some(&ids),
+                    None,
+                    Some(&ArrangementDependencyType::new_tags_dependant()),
+                    true,
+                )?;
+            }
+            query.page += 1;
+            if ids.len() < 1000 {
+                break;
+            }
+            pictures = Picture::query(conn, user.id, query.clone(), 1000)?;
+        }
+        Ok(Json(reports.into_values().collect()))
+    })
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct EditPictureTagsRequest {
     pub picture_ids: Vec<i64>,
@@ -204,24 +427,48 @@ pub struct EditPictureTagsRequest {
 }
 
 /// Edit tags of a list of pictures
-/// The user can edit tags of pictures he does not own as long as the tag is his own.
+/// The user can edit tags of pictures he does not own as long as the tag is his own,
+/// and the pictures are in a group shared with the user granting the edit-tags permission.
 /// If the tag is not multiple, any picture already having a tag of the same tag group will lose the old tag in favor of the new one.
 /// If the tag is required, the picture will be tagged with the default tag of the tag group.
 #[openapi(tag = "Tags")]
 #[patch("/picture_tags", data = "<data>")]
 pub async fn edit_picture_tags(db: &State<DBPool>, user: User, data: Json<EditPictureTagsRequest>) -> Result<Json<Vec<i32>>, ErrorResponder> {
+    validate_picture_batch(&data.picture_ids)?;
     let mut conn: &mut DBConn = &mut db.get().unwrap();
     if data.picture_ids.len() == 0 {
         return ErrorType::UnprocessableEntity("No picture ids on which to edit tags".to_string()).res_err();
     }
 
+    grouping_transaction(&mut conn, |conn, delta| {
+        apply_picture_tags_edition(conn, user.id, &data)?;
+
+        // Regroup the pictures
+        group_pictures(
+            conn,
+            delta,
+            user.id,
+            Some(&data.picture_ids),
+            None,
+            Some(&ArrangementDependencyType::new_tags_dependant()),
+            true,
+        )?;
+
+        Ok(Json(PictureTag::get_picture_tags(conn, data.picture_ids[0], user.id)?))
+    })
+}
+
+/// Apply a tags edition to pictures, without regrouping them (see [`edit_picture_tags`]).
+/// Must be run in a transaction, as the edition is checked along the way.
+pub fn apply_picture_tags_edition(conn: &mut DBConn, user_id: i32, data: &EditPictureTagsRequest) -> Result<(), ErrorResponder> {
     // Grouping tags by tag group, checking at the same time that tags exists and belong to the user
     let add_tags = Tag::from_ids(conn, data.add_tag_ids.clone())?;
     let remove_tags = Tag::from_ids(conn, data.remove_tag_ids.clone())?;
     if add_tags.len() != data.add_tag_ids.len() || remove_tags.len() != data.remove_tag_ids.len() {
         return ErrorType::TagNotFound.res_err();
     }
-    let user_tag_groups = TagGroup::list_tag_groups(conn, user.id)?;
+    let user_tag_groups = TagGroup::list_tag_groups(conn, user_id)?;
+    SharedGroup::require_pictures_permission(conn, user_id, &data.picture_ids, SharePermissions::EDIT_TAGS)?;
 
     let mut more_than_one_add_tag = false;
 
@@ -273,42 +520,29 @@ pub async fn edit_picture_tags(db: &State<DBPool>, user: User, data: Json<EditPi
         return ErrorType::TagNotFound.res_err();
     }
 
-    err_transaction(&mut conn, |conn| {
-        // Remove tags
-        PictureTag::remove_pictures_batch(conn, &data.remove_tag_ids, &data.picture_ids)?;
+    // Remove tags
+    PictureTag::remove_pictures_batch(conn, &data.remove_tag_ids, &data.picture_ids)?;
 
-        // Remove all tags for multiple tag groups before adding new tags
-        for tgwt in add_tgwt {
-            if !tgwt.tag_group.multiple {
-                tgwt.tag_group.remove_pictures(conn, &data.picture_ids)?;
-            }
+    // Remove all tags for multiple tag groups before adding new tags
+    for tgwt in add_tgwt {
+        if !tgwt.tag_group.multiple {
+            tgwt.tag_group.remove_pictures(conn, &data.picture_ids)?;
         }
-        // Add tags
-        PictureTag::add_pictures_batch(conn, &data.add_tag_ids, &data.picture_ids)?;
-
-        // Add default tags for required tag groups
-        for tgwt in remove_tgwt {
-            if tgwt.tag_group.required {
-                // Get the default tag of the group
-                let default_tag = Tag::list_tags(conn, tgwt.tag_group.id.unwrap())?
-                    .into_iter()
-                    .find(|tag| tag.is_default)
-                    .ok_or_else(|| ErrorType::InternalError("There is a required tag group without any default tag".to_string()).res())?;
-                // Add the default tag to the pictures
-                TagGroup::add_default_tag_to_pictures_without_tag_from_list(conn, default_tag.id, tgwt.tag_group.id.unwrap(), &data.picture_ids)?;
-            }
+    }
+    // Add tags
+    PictureTag::add_pictures_batch(conn, &data.add_tag_ids, &data.picture_ids)?;
+
+    // Add default tags for required tag groups
+    for tgwt in remove_tgwt {
+        if tgwt.tag_group.required {
+            // Get the default tag of the group
+            let default_tag = Tag::list_tags(conn, tgwt.tag_group.id.unwrap())?
+                .into_iter()
+                .find(|tag| tag.is_default)
+                .ok_or_else(|| ErrorType::InternalError("There is a required tag group without any default tag".to_string()).res())?;
+            // Add the default tag to the pictures
+            TagGroup::add_default_tag_to_pictures_without_tag_from_list(conn, default_tag.id, tgwt.tag_group.id.unwrap(), &data.picture_ids)?;
         }
-
-        // Regroup the pictures
-        group_pictures(
-            conn,
-            user.id,
-            Some(&data.picture_ids),
-            None,
-            Some(&ArrangementDependencyType::new_tags_dependant()),
-            true,
-        )?;
-
-        Ok(Json(PictureTag::get_picture_tags(conn, data.picture_ids[0], user.id)?))
-    })
+    }
+    Ok(())
 }
@@ -0,0 +1,27 @@
+use crate::api::groups::arrangement::ArrangementResponse;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tests::test_database::{arrangement, group};
+
+#[test]
+pub fn test_single_arrangement_matches_list_entry() {
+    let arrangements = vec![arrangement(1), arrangement(2)];
+    let to_be_deleted = Group {
+        to_be_deleted: true,
+        ..group(21, 2)
+    };
+    let user_groups = vec![group(10, 1), group(20, 2), to_be_deleted, group(22, 2)];
+
+    // Response as built by `list_arrangements`, from all the user’s groups
+    let listed = Arrangement::attach_groups(arrangements, &user_groups)
+        .into_iter()
+        .map(|(arrangement, groups)| ArrangementResponse::from_arrangement_and_groups(arrangement, groups).unwrap())
+        .collect::<Vec<_>>();
+
+    // Response as built by `get_arrangement`, from the groups of the arrangement only
+    let arrangement_groups = user_groups.iter().filter(|g| g.arrangement_id == 2).cloned().collect();
+    let single = ArrangementResponse::from_arrangement_and_groups(arrangement(2), arrangement_groups).unwrap();
+
+    assert_eq!(listed[1], single);
+    assert_ne!(listed[0], single);
+}
@@ -0,0 +1,95 @@
+use crate::api::curate::{curate, rollback_on_error, CurateRequest};
+use crate::api::tags::EditPictureTagsRequest;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::Picture;
+use crate::database::schema::*;
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_tags, insert_user, test_connection};
+use crate::grouping::strategy_filtering::FilterType;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorType, ErrorTypeKind};
+use diesel::prelude::*;
+
+#[test]
+pub fn test_group_step_failure_rolls_back_tags_edition() {
+    // A non-manual group is usually reported without rollback, as nothing has been changed yet
+    let result: Result<(), _> = Err(ErrorType::GroupIsNotManual.res_no_rollback());
+    let error = rollback_on_error(result).unwrap_err();
+    assert!(error.do_rollback());
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::GroupIsNotManual));
+
+    assert_eq!(rollback_on_error(Ok::<_, _>(3)).unwrap(), 3);
+}
+
+#[test]
+pub fn test_curate_request_deserialization() {
+    let request: CurateRequest =
+        serde_json::from_str(r#"{"picture_ids": [1, 2], "add_tag_ids": [5], "remove_tag_ids": [], "add_to_group_id": 8}"#).unwrap();
+    assert_eq!(request.tags.picture_ids, vec![1, 2]);
+    assert_eq!(request.tags.add_tag_ids, vec![5]);
+    assert!(request.tags.remove_tag_ids.is_empty());
+    assert_eq!(request.add_to_group_id, Some(8));
+    assert_eq!(request.remove_from_group_id, None);
+}
+
+#[test]
+pub fn test_inaccessible_pictures_roll_back_the_curation() {
+    // Duplicated ids are accessible as long as each of them is
+    assert!(Picture::require_all_accessible(&[1, 2, 1], &[2, 1]).is_ok());
+
+    // The group steps run after the tags edition: an inaccessible picture must undo it
+    let error = rollback_on_error(Picture::require_all_accessible(&[1, 3, 1], &[1])).unwrap_err();
+    assert!(error.do_rollback());
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::Unauthorized));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_failing_group_step_rolls_back_the_tags_and_the_groups() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "curate_rollback");
+    let tag_ids = insert_tags(conn, user_id, 2);
+    let picture_id = insert_picture(conn, user_id, &[]);
+    let arrangement = Arrangement::new(conn, user_id, "Manual".to_string(), false, None).unwrap();
+    let manual_group = Group::insert(conn, arrangement.id, "Manual group".to_string(), false, None).unwrap();
+    let filter_group_id = insert_filter_arrangement(
+        conn,
+        user_id,
+        "Tagged".to_string(),
+        FilterType::IncludeTags(vec![tag_ids[1]]).to_strategy(),
+    );
+
+    // The picture is tagged and added to the manual group before the removal from a non-manual group fails
+    let request = CurateRequest {
+        tags: EditPictureTagsRequest {
+            picture_ids: vec![picture_id],
+            add_tag_ids: vec![tag_ids[0]],
+            remove_tag_ids: vec![],
+        },
+        add_to_group_id: Some(manual_group.id),
+        remove_from_group_id: Some(filter_group_id),
+    };
+    let error = curate(conn, user_id, &request).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::GroupIsNotManual));
+
+    let picture_tag_ids: Vec<i32> = pictures_tags::table
+        .filter(pictures_tags::picture_id.eq(picture_id))
+        .select(pictures_tags::tag_id)
+        .load(conn)
+        .unwrap();
+    assert!(picture_tag_ids.is_empty());
+    assert!(Group::pictures_from_group_ids(conn, &vec![manual_group.id]).unwrap().is_empty());
+
+    // Without the failing step, both are applied
+    let request = CurateRequest {
+        remove_from_group_id: None,
+        ..request
+    };
+    curate(conn, user_id, &request).unwrap();
+    let picture_tag_ids: Vec<i32> = pictures_tags::table
+        .filter(pictures_tags::picture_id.eq(picture_id))
+        .select(pictures_tags::tag_id)
+        .load(conn)
+        .unwrap();
+    assert_eq!(picture_tag_ids, vec![tag_ids[0]]);
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![manual_group.id]).unwrap(), vec![picture_id]);
+}
@@ -0,0 +1,98 @@
+use crate::api::groups::manual_groups::{assign_pictures, AssignGroupsRequest};
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::Picture;
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_tags, insert_user, test_connection};
+use crate::grouping::strategy_filtering::FilterType;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+
+#[test]
+pub fn test_assign_to_two_groups_and_remove_from_a_third() {
+    let request: AssignGroupsRequest =
+        serde_json::from_str(r#"{"picture_ids": [7], "add_to_group_ids": [1, 2], "remove_from_group_ids": [3]}"#).unwrap();
+    assert_eq!(request.picture_ids, vec![7]);
+    assert_eq!(request.group_operations().unwrap(), vec![(1, true), (2, true), (3, false)]);
+}
+
+#[test]
+pub fn test_assign_request_validation() {
+    // Lists default to empty, duplicated groups are edited once
+    let request: AssignGroupsRequest = serde_json::from_str(r#"{"picture_ids": [7], "add_to_group_ids": [1, 1]}"#).unwrap();
+    assert!(request.remove_from_group_ids.is_empty());
+    assert_eq!(request.group_operations().unwrap(), vec![(1, true)]);
+
+    // A group can't be both added to and removed from
+    let request: AssignGroupsRequest =
+        serde_json::from_str(r#"{"picture_ids": [7], "add_to_group_ids": [1, 2], "remove_from_group_ids": [2]}"#).unwrap();
+    let error = request.group_operations().unwrap_err();
+    assert!(!error.do_rollback());
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::InvalidInput));
+
+    // At least one picture is required
+    let request: AssignGroupsRequest = serde_json::from_str(r#"{"picture_ids": [], "add_to_group_ids": [1]}"#).unwrap();
+    assert!(matches!(
+        ErrorResponse::from(request.group_operations().unwrap_err()).error_type,
+        ErrorTypeKind::UnprocessableEntity
+    ));
+}
+
+#[test]
+pub fn test_assign_duplicated_pictures() {
+    let request: AssignGroupsRequest = serde_json::from_str(r#"{"picture_ids": [7, 8, 7], "add_to_group_ids": [1]}"#).unwrap();
+    assert_eq!(request.unique_picture_ids(), vec![7, 8]);
+
+    // Both pictures are accessible: the duplicate must not make the access check fail
+    assert!(Picture::require_all_accessible(&request.unique_picture_ids(), &[8, 7]).is_ok());
+    // One of them is not: nothing is assigned
+    let error = Picture::require_all_accessible(&request.unique_picture_ids(), &[7]).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::Unauthorized));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_assign_edits_all_the_groups_or_none() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "assign");
+    let other_user_id = insert_user(conn, "assign_other");
+    let picture_ids = vec![insert_picture(conn, user_id, &[]), insert_picture(conn, user_id, &[])];
+    let arrangement = Arrangement::new(conn, user_id, "Manual".to_string(), false, None).unwrap();
+    let group_ids = ["First", "Second", "Third"].map(|name| Group::insert(conn, arrangement.id, name.to_string(), false, None).unwrap().id);
+    Group::add_pictures(conn, group_ids[2], &vec![picture_ids[0]]).unwrap();
+    let sorted_pictures = |conn: &mut DBConn, group_id| {
+        let mut picture_ids = Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap();
+        picture_ids.sort();
+        picture_ids
+    };
+
+    let request = AssignGroupsRequest {
+        picture_ids: vec![picture_ids[0], picture_ids[1], picture_ids[0]],
+        add_to_group_ids: vec![group_ids[0], group_ids[1]],
+        remove_from_group_ids: vec![group_ids[2]],
+    };
+    assign_pictures(conn, user_id, &request).unwrap();
+    assert_eq!(sorted_pictures(conn, group_ids[0]), picture_ids);
+    assert_eq!(sorted_pictures(conn, group_ids[1]), picture_ids);
+    assert!(sorted_pictures(conn, group_ids[2]).is_empty());
+
+    // A picture the user can't access, or a group that is not manual: none of the groups is edited
+    let other_picture_id = insert_picture(conn, other_user_id, &[]);
+    let request = AssignGroupsRequest {
+        picture_ids: vec![picture_ids[0], other_picture_id],
+        add_to_group_ids: vec![group_ids[2]],
+        remove_from_group_ids: vec![],
+    };
+    let error = assign_pictures(conn, user_id, &request).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::Unauthorized));
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let filter_group_id = insert_filter_arrangement(conn, user_id, "Tagged".to_string(), FilterType::IncludeTags(tag_ids).to_strategy());
+    let request = AssignGroupsRequest {
+        picture_ids: vec![picture_ids[0]],
+        add_to_group_ids: vec![group_ids[2], filter_group_id],
+        remove_from_group_ids: vec![group_ids[0]],
+    };
+    let error = assign_pictures(conn, user_id, &request).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::GroupIsNotManual));
+    assert!(sorted_pictures(conn, group_ids[2]).is_empty());
+    assert_eq!(sorted_pictures(conn, group_ids[0]), picture_ids);
+}
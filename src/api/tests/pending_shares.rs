@@ -0,0 +1,91 @@
+use crate::api::groups::shares::{accept_all_pending, decline_all_pending};
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::SharedGroup;
+use crate::database::picture::picture_tag::PictureTag;
+use crate::database::schema::{shared_groups, tags};
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_share, insert_tags, insert_user, test_connection};
+use crate::grouping::grouping_delta::grouping_transaction;
+use crate::grouping::strategy_filtering::FilterType;
+use diesel::prelude::*;
+
+/// Shares of the user, by group id, with whether they are confirmed.
+fn shares(conn: &mut DBConn, user_id: i32) -> Vec<(i32, bool)> {
+    shared_groups::table
+        .filter(shared_groups::user_id.eq(user_id))
+        .order(shared_groups::group_id.asc())
+        .select((shared_groups::group_id, shared_groups::confirmed))
+        .load(conn)
+        .unwrap()
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_accepted_pending_shares_group_their_pictures() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "sharer");
+    let recipient_id = insert_user(conn, "recipient");
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let picture_ids = [insert_picture(conn, owner_id, &[]), insert_picture(conn, owner_id, &[])];
+    let mut group_ids = vec![];
+    for picture_id in picture_ids {
+        let group = Group::insert(conn, arrangement.id, format!("Group {}", picture_id), false, None).unwrap();
+        Group::add_pictures(conn, group.id, &vec![picture_id]).unwrap();
+        insert_share(conn, recipient_id, group.id, false);
+        group_ids.push(group.id);
+    }
+    // The recipient groups the pictures having their default tag
+    let default_tag_id = insert_tags(conn, recipient_id, 1)[0];
+    diesel::update(tags::table.find(default_tag_id))
+        .set(tags::is_default.eq(true))
+        .execute(conn)
+        .unwrap();
+    let recipient_group_id = insert_filter_arrangement(
+        conn,
+        recipient_id,
+        "Default".to_string(),
+        FilterType::IncludeTags(vec![default_tag_id]).to_strategy(),
+    );
+
+    assert_eq!(SharedGroup::pending_for_user(conn, recipient_id).unwrap().len(), 2);
+
+    let mut accepted_group_ids = grouping_transaction(conn, |conn, delta| accept_all_pending(conn, delta, recipient_id)).unwrap();
+    accepted_group_ids.sort();
+    assert_eq!(accepted_group_ids, group_ids);
+    assert_eq!(shares(conn, recipient_id), vec![(group_ids[0], true), (group_ids[1], true)]);
+    assert!(SharedGroup::pending_for_user(conn, recipient_id).unwrap().is_empty());
+
+    // The shared pictures get the default tag of the recipient, and are grouped in their arrangement
+    for picture_id in picture_ids {
+        assert_eq!(
+            PictureTag::get_picture_tags(conn, picture_id, recipient_id).unwrap(),
+            vec![default_tag_id]
+        );
+    }
+    let mut grouped_picture_ids = Group::pictures_from_group_ids(conn, &vec![recipient_group_id]).unwrap();
+    grouped_picture_ids.sort();
+    assert_eq!(grouped_picture_ids, picture_ids);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_declined_pending_shares_are_removed() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "sharer");
+    let recipient_id = insert_user(conn, "recipient");
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let confirmed_group = Group::insert(conn, arrangement.id, "Confirmed".to_string(), false, None).unwrap();
+    let pending_group = Group::insert(conn, arrangement.id, "Pending".to_string(), false, None).unwrap();
+    let other_pending_group = Group::insert(conn, arrangement.id, "Other pending".to_string(), false, None).unwrap();
+    insert_share(conn, recipient_id, confirmed_group.id, true);
+    insert_share(conn, recipient_id, pending_group.id, false);
+    insert_share(conn, recipient_id, other_pending_group.id, false);
+
+    let mut declined_group_ids = grouping_transaction(conn, |conn, delta| decline_all_pending(conn, delta, recipient_id)).unwrap();
+    declined_group_ids.sort();
+    assert_eq!(declined_group_ids, vec![pending_group.id, other_pending_group.id]);
+    // Only the confirmed share is left
+    assert_eq!(shares(conn, recipient_id), vec![(confirmed_group.id, true)]);
+    assert!(SharedGroup::pending_for_user(conn, recipient_id).unwrap().is_empty());
+}
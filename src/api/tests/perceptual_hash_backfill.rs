@@ -0,0 +1,58 @@
+use crate::api::admin::admin::backfill_perceptual_hashes;
+use crate::database::picture::picture::Picture;
+use crate::database::schema::pictures;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::utils::tests::s3_mock::picture_storer_serving;
+use crate::utils::thumbnail::perceptual_hash;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use std::io::Cursor;
+
+/// Small thumbnail served by the mocked S3 for every picture.
+fn thumbnail() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| Rgb([(x * 4) as u8, (y * 5) as u8, 128])))
+}
+
+#[test]
+pub fn test_pictures_without_perceptual_hash_are_listed_by_batches() {
+    let sql = debug_query::<Pg, _>(&Picture::without_perceptual_hash_statement(42, 100)).to_string();
+    assert!(sql.contains("WHERE ((\"pictures\".\"perceptual_hash\" IS NULL) AND (\"pictures\".\"id\" > $1))"));
+    assert!(sql.contains("ORDER BY \"pictures\".\"id\" ASC LIMIT $2"));
+}
+
+#[rocket::async_test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub async fn test_backfill_hashes_the_pictures_without_hash() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "backfill");
+    let picture_ids = (0..3).map(|_| insert_picture(conn, user_id, &[])).collect::<Vec<_>>();
+    // Pictures uploaded since the hashes are computed keep their hash
+    Picture::set_perceptual_hash(conn, picture_ids[1], 7).unwrap();
+
+    let mut encoded = Cursor::new(Vec::new());
+    thumbnail().write_to(&mut encoded, ImageFormat::Png).unwrap();
+    let picture_storer = picture_storer_serving(Box::leak(encoded.into_inner().into_boxed_slice()));
+    let report = backfill_perceptual_hashes(conn, &picture_storer, 1).await.unwrap();
+    assert_eq!(report.hashed_pictures, 2);
+    assert!(report.failed_picture_ids.is_empty());
+
+    let hashes: Vec<Option<i64>> = pictures::table
+        .filter(pictures::id.eq_any(&picture_ids))
+        .order(pictures::id.asc())
+        .select(pictures::perceptual_hash)
+        .load(conn)
+        .unwrap();
+    let hash = perceptual_hash(&thumbnail());
+    assert_eq!(hashes, vec![Some(hash), Some(7), Some(hash)]);
+
+    // A thumbnail that can't be decoded is reported, and the picture left without hash to be retried
+    let picture_id = insert_picture(conn, user_id, &[]);
+    let report = backfill_perceptual_hashes(conn, &picture_storer_serving(b"not an image"), 1)
+        .await
+        .unwrap();
+    assert_eq!(report.hashed_pictures, 0);
+    assert_eq!(report.failed_picture_ids, vec![picture_id]);
+    assert_eq!(Picture::without_perceptual_hash(conn, 0, 10).unwrap(), vec![picture_id]);
+}
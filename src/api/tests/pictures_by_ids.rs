@@ -0,0 +1,56 @@
+use crate::api::picture::ListPictureData;
+use crate::database::picture::picture::Picture;
+use chrono::NaiveDateTime;
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+fn list_picture(id: i64) -> ListPictureData {
+    ListPictureData {
+        id,
+        name: format!("picture-{}.jpg", id),
+        width: 100,
+        height: 100,
+        size_ko: 10,
+        creation_date: NaiveDateTime::default(),
+        edition_date: NaiveDateTime::default(),
+        blurhash: None,
+        group_ids: None,
+    }
+}
+fn ids(pictures: &[Option<ListPictureData>]) -> Vec<Option<i64>> {
+    pictures.iter().map(|picture| picture.as_ref().map(|picture| picture.id)).collect()
+}
+
+#[test]
+pub fn test_pictures_are_returned_in_requested_order() {
+    // Pictures are loaded in database order
+    let loaded = vec![list_picture(1), list_picture(4), list_picture(7), list_picture(9)];
+    let requested = [9, 1, 7, 4];
+
+    let ordered = ListPictureData::in_requested_order(loaded, &requested, false);
+    assert_eq!(ids(&ordered), vec![Some(9), Some(1), Some(7), Some(4)]);
+}
+
+#[test]
+pub fn test_missing_pictures_are_omitted_or_nulled() {
+    // Picture 5 does not exist or can't be accessed, picture 3 is requested twice
+    let requested = [3, 5, 1, 3];
+    let omitted = ListPictureData::in_requested_order(vec![list_picture(1), list_picture(3)], &requested, false);
+    assert_eq!(ids(&omitted), vec![Some(3), Some(1)]);
+
+    let nulled = ListPictureData::in_requested_order(vec![list_picture(1), list_picture(3)], &requested, true);
+    assert_eq!(ids(&nulled), vec![Some(3), None, Some(1)]);
+    assert_eq!(serde_json::to_value(&nulled).unwrap()[1], serde_json::Value::Null);
+}
+
+#[test]
+pub fn test_pictures_by_ids_statement_checks_access() {
+    let statement = Picture::from_ids_accessible_statement(2, &[9, 1]);
+    let sql = debug_query::<Pg, _>(&statement).to_string();
+    assert!(sql.contains("\"pictures\".\"id\" = ANY($1)"));
+    assert!(sql.contains("EXISTS (SELECT"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(sql.ends_with("binds: [[9, 1], 2, 2, false]"));
+    // Groups pending deletion are hidden from the recipients
+    assert!(sql.contains("\"groups\".\"to_be_deleted\" = $4"));
+}
@@ -0,0 +1,116 @@
+use crate::api::picture::{batch_picture_ids, zip_entries};
+use crate::utils::zip::{unique_entry_names, ZipEncoder};
+use std::collections::HashMap;
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Read the entries of a stored ZIP archive from its central directory: (name, data)
+fn read_archive(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let end = archive.len() - 22;
+    assert_eq!(read_u32(archive, end), 0x06054b50);
+    let count = read_u16(archive, end + 10) as usize;
+    let mut offset = read_u32(archive, end + 16) as usize;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        assert_eq!(read_u32(archive, offset), 0x02014b50);
+        let crc = read_u32(archive, offset + 16);
+        let size = read_u32(archive, offset + 24) as usize;
+        let name_length = read_u16(archive, offset + 28) as usize;
+        let local_offset = read_u32(archive, offset + 42) as usize;
+        let name = String::from_utf8(archive[offset + 46..offset + 46 + name_length].to_vec()).unwrap();
+
+        assert_eq!(read_u32(archive, local_offset), 0x04034b50);
+        let data_offset = local_offset + 30 + read_u16(archive, local_offset + 26) as usize;
+        let data = archive[data_offset..data_offset + size].to_vec();
+        assert_eq!(crc32fast::hash(&data), crc);
+        assert_eq!(read_u32(archive, data_offset + size), 0x08074b50);
+
+        entries.push((name, data));
+        offset += 46 + name_length;
+    }
+    entries
+}
+
+#[test]
+pub fn test_archive_contains_one_entry_per_accessible_picture() {
+    // Picture 3 is not accessible, picture 1 is requested twice, pictures 1 and 4 have the same name
+    let picture_ids = batch_picture_ids(&[2, 1, 3, 1, 4], &[1, 2, 4]);
+    let names = HashMap::from([
+        (1, "IMG_0001.jpg".to_string()),
+        (2, "beach.png".to_string()),
+        (4, "IMG_0001.jpg".to_string()),
+    ]);
+    let entries = zip_entries(&picture_ids, names);
+    assert_eq!(
+        entries,
+        vec![
+            (2, "beach.png".to_string()),
+            (1, "IMG_0001.jpg".to_string()),
+            (4, "IMG_0001 (1).jpg".to_string())
+        ]
+    );
+
+    // Archive streamed in several chunks per picture
+    let mut encoder = ZipEncoder::new();
+    let mut archive = Vec::new();
+    for (picture_id, name) in entries.iter() {
+        archive.extend(encoder.start_entry(name));
+        for chunk in [vec![0xff, 0xd8], vec![*picture_id as u8; 3]] {
+            encoder.add_data(&chunk);
+            archive.extend(chunk);
+        }
+        archive.extend(encoder.end_entry());
+    }
+    archive.extend(encoder.finish());
+
+    let archived = read_archive(&archive);
+    assert_eq!(archived.len(), 3);
+    assert_eq!(archived[0], ("beach.png".to_string(), vec![0xff, 0xd8, 2, 2, 2]));
+    assert_eq!(archived[1], ("IMG_0001.jpg".to_string(), vec![0xff, 0xd8, 1, 1, 1]));
+    assert_eq!(archived[2], ("IMG_0001 (1).jpg".to_string(), vec![0xff, 0xd8, 4, 4, 4]));
+}
+
+#[test]
+pub fn test_empty_archive() {
+    let archive = ZipEncoder::new().finish();
+    assert_eq!(archive.len(), 22);
+    assert!(read_archive(&archive).is_empty());
+}
+
+#[test]
+pub fn test_unique_entry_names() {
+    let names = [
+        "a.jpg",
+        "a.jpg",
+        "a.jpg",
+        "a (1).jpg",
+        "dir/b.png",
+        "",
+        "README",
+        "README",
+        ".hidden",
+        ".hidden",
+    ]
+    .map(String::from);
+    assert_eq!(
+        unique_entry_names(&names),
+        vec![
+            "a.jpg",
+            "a (1).jpg",
+            "a (2).jpg",
+            "a (1) (1).jpg",
+            "dir_b.png",
+            "picture",
+            "README",
+            "README (1)",
+            ".hidden",
+            ".hidden (1)"
+        ]
+    );
+}
@@ -0,0 +1,66 @@
+use crate::api::groups::arrangement::RegroupedArrangement;
+use crate::database::database::DBConn;
+use crate::database::group::group::Group;
+use crate::database::tests::test_database::{group, insert_filter_arrangement, insert_picture, insert_tags, insert_user, test_connection};
+use crate::grouping::grouping_delta::{lock_user_grouping_statement, GroupingDelta};
+use crate::grouping::grouping_process::regroup_all;
+use crate::grouping::strategy_filtering::FilterType;
+use diesel::debug_query;
+use diesel::dsl::sql;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use std::collections::{BTreeMap, HashMap};
+
+#[test]
+pub fn test_regroup_all_takes_the_user_grouping_lock() {
+    let sql = debug_query::<Pg, _>(&lock_user_grouping_statement(7)).to_string();
+    // Transaction-level lock, released on commit or rollback, keyed on the grouping class and the user
+    assert_eq!(sql, "SELECT pg_advisory_xact_lock($1, $2) -- binds: [1, 7]");
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_regroup_all_holds_the_user_grouping_lock_until_the_end_of_the_transaction() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "regroup_all_lock");
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let picture_id = insert_picture(conn, user_id, &[tag_ids[0]]);
+    let group_id = insert_filter_arrangement(conn, user_id, "Tagged".to_string(), FilterType::IncludeTags(tag_ids).to_strategy());
+
+    assert_eq!(regroup_all(conn, &mut GroupingDelta::new(), user_id).unwrap().len(), 1);
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap(), vec![picture_id]);
+
+    // Another grouping operation of the user can't take the lock until the regrouping transaction ends,
+    // while the lock of another user is free
+    let try_lock = |conn: &mut DBConn, user_id: i32| {
+        diesel::select(sql::<Bool>(&format!("pg_try_advisory_xact_lock(1, {})", user_id)))
+            .get_result::<bool>(conn)
+            .unwrap()
+    };
+    let other_conn = &mut test_connection();
+    assert!(!try_lock(other_conn, user_id));
+    assert!(try_lock(other_conn, user_id + 1));
+}
+
+#[test]
+pub fn test_regrouped_arrangements_counts() {
+    let sql = debug_query::<Pg, _>(&Group::pictures_counts_statement(&[3, 4, 5])).to_string();
+    assert!(sql.starts_with("SELECT \"groups_pictures\".\"group_id\", COUNT(*) FROM \"groups_pictures\""));
+    assert!(sql.contains("GROUP BY \"groups_pictures\".\"group_id\" ORDER BY \"groups_pictures\".\"group_id\""));
+    assert!(sql.ends_with("binds: [[3, 4, 5]]"));
+
+    // After the regrouping, groups 3 and 4 of the arrangement 1 hold pictures, the group 5 no longer matches any picture
+    let counts = HashMap::from([(3, 12), (4, 2)]);
+    let regrouped = RegroupedArrangement::from_counts(1, &[group(3, 1), group(4, 1), group(5, 1)], &counts);
+    assert_eq!(
+        regrouped,
+        RegroupedArrangement {
+            arrangement_id: 1,
+            pictures_counts: BTreeMap::from([(3, 12), (4, 2), (5, 0)]),
+        }
+    );
+    let json = serde_json::to_value(&regrouped).unwrap();
+    assert_eq!(json["pictures_counts"]["3"], 12);
+    assert_eq!(json["pictures_counts"]["5"], 0);
+}
@@ -0,0 +1,52 @@
+use crate::api::picture::{thumbnail_failure, upload_picture};
+use crate::database::tests::test_database::{insert_user, test_connection};
+use crate::database::user::user::User;
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorType, ErrorTypeKind};
+use crate::utils::tests::s3_mock::picture_storer_serving;
+use crate::utils::thumbnail::create_temp_directories;
+
+#[test]
+pub fn test_strict_thumbnail_failure_rolls_back_upload() {
+    let error = ErrorType::UnableToCreateThumbnail(String::from("Unable to resize")).res_no_rollback();
+    let error = thumbnail_failure(true, Some(error)).unwrap_err();
+
+    // The error is returned from the upload transaction, that is rolled back
+    assert!(error.do_rollback());
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnableToCreateThumbnail));
+    assert!(thumbnail_failure(true, None).unwrap().is_none());
+}
+
+#[test]
+pub fn test_thumbnail_failure_is_reported_without_strict_mode() {
+    let error = ErrorType::S3Error(String::from("Unable to store object")).res_no_rollback();
+    let error = thumbnail_failure(false, Some(error)).unwrap().expect("thumbnail error reported");
+
+    assert!(matches!(error.error_type, ErrorTypeKind::S3Error));
+    assert!(!error.rollback);
+    assert!(thumbnail_failure(false, None).unwrap().is_none());
+}
+
+#[rocket::async_test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub async fn test_strict_thumbnail_failure_removes_the_temporary_files() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "strict_thumbnails");
+    let mut user = User::from_id(conn, &user_id).unwrap();
+    user.storage_limit_ko = 1000;
+    create_temp_directories();
+
+    // A JPEG header followed by nothing: accepted as a picture, but no thumbnail can be generated from it
+    let temp_file_name = format!("{}-broken.jpg", std::process::id());
+    let path = CONFIG.original_temp_dir().join(&temp_file_name);
+    std::fs::write(&path, [0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F', 0]).unwrap();
+
+    let picture_storer = picture_storer_serving(b"");
+    let error = upload_picture(conn, &picture_storer, &user, "broken.jpg".to_string(), &path, true)
+        .await
+        .unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnableToCreateThumbnail));
+    // The upload failed, its temporary files are removed all the same
+    assert!(!path.exists());
+    assert!(!CONFIG.thumbs_temp_dir().join(&temp_file_name).exists());
+}
@@ -0,0 +1,61 @@
+use crate::api::tags::insert_tag_groups_batch;
+use crate::database::picture::picture_tag::PictureTag;
+use crate::database::tag::tag::Tag;
+use crate::database::tag::tag_group::{TagGroup, TagGroupWithTags};
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::utils::errors_catcher::{err_transaction, ErrorResponse, ErrorTypeKind};
+
+fn tag_group_with_tags(name: &str, required: bool, default_tags: &[bool]) -> TagGroupWithTags {
+    TagGroupWithTags {
+        tag_group: TagGroup {
+            id: None,
+            user_id: 0,
+            name: name.to_string(),
+            multiple: true,
+            required,
+        },
+        tags: default_tags
+            .iter()
+            .enumerate()
+            .map(|(i, is_default)| Tag {
+                id: 0,
+                tag_group_id: 0,
+                name: format!("{} {}", name, i),
+                color: vec![0, 0, 0],
+                is_default: *is_default,
+                position: 0,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_invalid_tag_group_rolls_back_the_whole_batch() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "tags_batch");
+    let picture_id = insert_picture(conn, user_id, &[]);
+
+    // The first tag groups are inserted, with their default tag, before the required one without default tag fails
+    let batch = vec![
+        tag_group_with_tags("Places", false, &[true, false]),
+        tag_group_with_tags("People", false, &[false]),
+        tag_group_with_tags("Rating", true, &[false, false]),
+    ];
+    let error = err_transaction(conn, |conn| insert_tag_groups_batch(conn, user_id, batch)).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnprocessableEntity));
+    assert!(TagGroup::list_tag_groups(conn, user_id).unwrap().is_empty());
+    assert!(PictureTag::get_picture_tags(conn, picture_id, user_id).unwrap().is_empty());
+
+    // Without the invalid tag group, the batch is inserted and the default tag added to the picture
+    let batch = vec![
+        tag_group_with_tags("Places", false, &[true, false]),
+        tag_group_with_tags("People", false, &[false]),
+    ];
+    let inserted = err_transaction(conn, |conn| insert_tag_groups_batch(conn, user_id, batch)).unwrap();
+    assert_eq!(TagGroup::list_tag_groups(conn, user_id).unwrap().len(), 2);
+    assert_eq!(
+        PictureTag::get_picture_tags(conn, picture_id, user_id).unwrap(),
+        vec![inserted[0].tags[0].id]
+    );
+}
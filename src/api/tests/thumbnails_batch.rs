@@ -0,0 +1,75 @@
+use crate::api::picture::{batch_picture_ids, thumbnail_error_part, thumbnail_part, thumbnails_batch_stream};
+use crate::utils::errors_catcher::ErrorType;
+use crate::utils::multipart::{MultipartMixed, MultipartPart};
+use crate::utils::tests::s3_mock::picture_storer_serving;
+use crate::utils::thumbnail::PictureThumbnail;
+use rocket::futures::{stream, StreamExt};
+
+/// Boundary and encoded body of a multipart made of `parts`.
+async fn encode(parts: Vec<MultipartPart>) -> (String, Vec<u8>) {
+    let multipart = MultipartMixed::new(Box::pin(stream::iter(parts)));
+    assert!(multipart.content_type().starts_with("multipart/mixed; boundary=archypix-"));
+    let boundary = multipart.boundary.clone();
+    (boundary, multipart.into_byte_stream().concat().await)
+}
+
+#[rocket::async_test]
+pub async fn test_batch_contains_one_part_per_accessible_picture() {
+    // Picture 3 is not accessible, picture 1 is requested twice
+    let picture_ids = batch_picture_ids(&[2, 1, 3, 1], &[1, 2]);
+    assert_eq!(picture_ids, vec![2, 1]);
+
+    let (boundary, body) = encode(picture_ids.iter().map(|id| thumbnail_part(*id, vec![0xff, 0xd8, *id as u8])).collect()).await;
+    let delimiter = format!("--{}", boundary);
+    let parts = String::from_utf8_lossy(&body)
+        .split(&delimiter)
+        .filter(|part| part.starts_with("\r\n"))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    assert_eq!(parts.len(), 2);
+    assert!(parts[0].contains("Content-ID: 2\r\n"));
+    assert!(parts[1].contains("Content-ID: 1\r\n"));
+    assert!(parts.iter().all(|part| part.contains("Content-Type: image/jpeg\r\n")));
+    assert!(body.ends_with(format!("{}--\r\n", delimiter).as_bytes()));
+}
+
+#[rocket::async_test]
+pub async fn test_empty_batch() {
+    assert!(batch_picture_ids(&[1, 2], &[]).is_empty());
+    let (boundary, body) = encode(vec![]).await;
+    assert_eq!(body, format!("--{}--\r\n", boundary).into_bytes());
+}
+
+#[rocket::async_test]
+pub async fn test_failed_thumbnail_is_reported_in_its_part() {
+    // Thumbnail 2 can't be fetched: the other thumbnails are still returned, with an error part in place of 2
+    let parts = vec![
+        thumbnail_part(1, vec![0xff, 0xd8]),
+        thumbnail_error_part(2, ErrorType::S3Error(String::from("Unable to retrieve object")).res()),
+        thumbnail_part(3, vec![0xff, 0xd8]),
+    ];
+    assert_eq!(parts[1].headers[0], ("Content-Type".to_string(), "application/json".to_string()));
+    assert_eq!(parts[1].headers[1], ("Content-ID".to_string(), "2".to_string()));
+    let error: serde_json::Value = serde_json::from_slice(&parts[1].body).unwrap();
+    assert_eq!(error["error_type"], "S3Error");
+    assert_eq!(error["message"], "S3 error: Unable to retrieve object");
+
+    let (_, body) = encode(parts).await;
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("Content-ID: 1\r\n") && body.contains("Content-ID: 3\r\n"));
+}
+
+#[rocket::async_test]
+pub async fn test_thumbnails_are_streamed_in_the_requested_order() {
+    let picture_storer = picture_storer_serving(&[0xff, 0xd8, 0xff, 0xd9]);
+    let parts = thumbnails_batch_stream(picture_storer, vec![3, 1, 2], PictureThumbnail::Small)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        parts,
+        vec![3, 1, 2]
+            .into_iter()
+            .map(|id| thumbnail_part(id, vec![0xff, 0xd8, 0xff, 0xd9]))
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,43 @@
+use crate::api::user::UserProfileResponse;
+use crate::database::database::DBConn;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::database::user::totp_secret::TOTPSecret;
+use crate::database::user::user::User;
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_profile_has_totp_once_a_secret_is_set_up() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "profile");
+    insert_picture(conn, user_id, &[]);
+    let profile = |conn: &mut DBConn| {
+        let user = User::from_id(conn, &user_id).unwrap();
+        UserProfileResponse::from_user(conn, user).unwrap()
+    };
+
+    let before = profile(conn);
+    assert!(!before.has_totp);
+    assert_eq!(before.picture_count, 1);
+    assert_eq!(before.friend_count, 0);
+
+    TOTPSecret::insert_secret_for_user(conn, &user_id, &vec![1; 20]).unwrap();
+    assert!(profile(conn).has_totp);
+
+    // Reset by an admin
+    TOTPSecret::delete_for_user(conn, &user_id).unwrap();
+    assert!(!profile(conn).has_totp);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_update_name_renames_only_the_user() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "renamed");
+    let other_user_id = insert_user(conn, "other");
+
+    let user = User::update_name(conn, user_id, "Jane Doe").unwrap();
+    assert_eq!(user.id, user_id);
+    assert_eq!(user.name, "Jane Doe");
+    assert_eq!(User::from_id(conn, &user_id).unwrap(), user);
+    assert_eq!(User::from_id(conn, &other_user_id).unwrap().name, "other");
+}
@@ -0,0 +1,30 @@
+use crate::api::user::PublicUserProfile;
+use crate::database::group::shared_group::SharedGroup;
+use crate::database::tests::test_database::user;
+use crate::database::user::friend::Friends;
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+#[test]
+pub fn test_public_profile_of_a_friend_leaks_no_sensitive_field() {
+    let profile = serde_json::to_value(PublicUserProfile::new(&user(7), true, 2)).unwrap();
+    assert_eq!(
+        profile,
+        serde_json::json!({ "id": 7, "name": "User 7", "is_friend": true, "shared_group_count": 2 })
+    );
+}
+
+#[test]
+pub fn test_friendship_is_checked_in_both_orders() {
+    let sql = debug_query::<Pg, _>(&Friends::are_friends_statement(1, 7)).to_string();
+    assert!(sql.starts_with("SELECT EXISTS (SELECT"));
+    assert!(sql.contains("(\"friends\".\"user_id_1\" = $1) AND (\"friends\".\"user_id_2\" = $2)"));
+    assert!(sql.contains("(\"friends\".\"user_id_1\" = $3) AND (\"friends\".\"user_id_2\" = $4)"));
+    assert!(sql.ends_with("binds: [1, 7, 7, 1]"));
+
+    // Shared groups are counted in both directions too
+    let sql = debug_query::<Pg, _>(&SharedGroup::count_between_statement(1, 7)).to_string();
+    assert!(sql.contains("(\"shared_groups\".\"user_id\" = $1) AND (\"arrangements\".\"user_id\" = $2)"));
+    assert!(sql.contains("(\"shared_groups\".\"user_id\" = $3) AND (\"arrangements\".\"user_id\" = $4)"));
+    assert!(sql.ends_with("binds: [1, 7, 7, 1, true, false]"));
+}
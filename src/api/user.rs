@@ -0,0 +1,137 @@
+use crate::database::database::{DBConn, DBPool};
+use crate::database::group::shared_group::SharedGroup;
+use crate::database::picture::picture::Picture;
+use crate::database::schema::UserStatus;
+use crate::database::user::friend::Friends;
+use crate::database::user::totp_secret::TOTPSecret;
+use crate::database::user::user::User;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use crate::utils::validation::{validate_user_name, validation_error_to_responder};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_okapi::{openapi, JsonSchema};
+
+#[derive(JsonSchema, Serialize, Debug)]
+pub struct UserProfileResponse {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) status: UserStatus,
+    pub(crate) tfa_login: bool,
+    pub(crate) email_tfa: bool,
+    pub(crate) has_totp: bool,
+    pub(crate) storage_used_ko: i64,
+    pub(crate) storage_limit_ko: i64,
+    pub(crate) friend_count: i64,
+    pub(crate) picture_count: i64,
+}
+impl UserProfileResponse {
+    /// Aggregates the profile of the user with his TOTP status, friends and pictures counts.
+    pub fn from_user(conn: &mut DBConn, user: User) -> Result<Self, ErrorResponder> {
+        Ok(UserProfileResponse {
+            has_totp: TOTPSecret::has_user_totp(conn, &user.id)?,
+            friend_count: Friends::count_for_user(conn, user.id)?,
+            picture_count: Picture::count_owned(conn, user.id)?,
+            id: user.id,
+            name: user.name,
+            email: user.email,
+            status: user.status,
+            tfa_login: user.tfa_login,
+            email_tfa: user.email_tfa,
+            storage_used_ko: user.storage_count_ko,
+            storage_limit_ko: user.storage_limit_ko,
+        })
+    }
+}
+
+/// Profile of a user as seen by other users: only non-sensitive fields, never the email nor the status.
+#[derive(JsonSchema, Serialize, Debug)]
+pub struct PublicUserProfile {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    /// The user is a friend of the authenticated user
+    pub(crate) is_friend: bool,
+    /// Number of groups shared between the user and the authenticated user, in either direction
+    pub(crate) shared_group_count: i64,
+}
+impl PublicUserProfile {
+    pub fn new(user: &User, is_friend: bool, shared_group_count: i64) -> Self {
+        PublicUserProfile {
+            id: user.id,
+            name: user.name.clone(),
+            is_friend,
+            shared_group_count,
+        }
+    }
+    /// Profile of the user as seen by the viewer, with their friendship and the groups they share.
+    pub fn from_user(conn: &mut DBConn, viewer_id: i32, user: &User) -> Result<Self, ErrorResponder> {
+        let is_friend = Friends::are_friends(conn, viewer_id, user.id)?;
+        let shared_group_count = SharedGroup::count_between(conn, viewer_id, user.id)?;
+        Ok(PublicUserProfile::new(user, is_friend, shared_group_count))
+    }
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct UpdateUserProfileRequest {
+    /// New display name, surrounding whitespace is trimmed
+    name: String,
+}
+
+#[derive(JsonSchema, Deserialize, Debug)]
+pub struct UpdateUserSecurityRequest {
+    /// Require a code sent by email to sign in when the user has no TOTP
+    email_tfa: bool,
+}
+
+/// Get the full profile of the authenticated user.
+#[openapi(tag = "User")]
+#[get("/user/me")]
+pub async fn get_user_profile(db: &State<DBPool>, user: User) -> Result<Json<UserProfileResponse>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    Ok(Json(UserProfileResponse::from_user(conn, user)?))
+}
+
+/// Get the public profile of another user, to look them up by id.
+/// Users that have not confirmed their account yet are not found.
+#[openapi(tag = "User")]
+#[get("/user/<user_id>/public")]
+pub async fn get_user_public_profile(db: &State<DBPool>, user: User, user_id: i32) -> Result<Json<PublicUserProfile>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let other_user = User::from_id(conn, &user_id)?;
+    if other_user.status == UserStatus::Unconfirmed {
+        return ErrorType::UserNotFound.res_err_no_rollback();
+    }
+    Ok(Json(PublicUserProfile::from_user(conn, user.id, &other_user)?))
+}
+
+/// Update the display name of the authenticated user and return the updated profile.
+/// The name is trimmed and must be a valid username (see [`validate_user_name`]), otherwise `InvalidInput` is returned.
+#[openapi(tag = "User")]
+#[patch("/user/me", data = "<request>")]
+pub async fn patch_user_profile(
+    db: &State<DBPool>,
+    user: User,
+    request: Json<UpdateUserProfileRequest>,
+) -> Result<Json<UserProfileResponse>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let name = request.name.trim();
+    validate_user_name(name).map_err(|e| validation_error_to_responder("name", e))?;
+
+    let user = User::update_name(conn, user.id, name)?;
+    Ok(Json(UserProfileResponse::from_user(conn, user)?))
+}
+
+/// Enable or disable the email confirmation of the sign in of the authenticated user, and return the updated profile.
+/// When enabled and the user has no TOTP, signing in requires the code sent by `/auth/signin/email`.
+#[openapi(tag = "User")]
+#[patch("/user/me/security", data = "<request>")]
+pub async fn patch_user_security(
+    db: &State<DBPool>,
+    user: User,
+    request: Json<UpdateUserSecurityRequest>,
+) -> Result<Json<UserProfileResponse>, ErrorResponder> {
+    let conn: &mut DBConn = &mut db.get().unwrap();
+    let user = User::set_email_tfa(conn, user.id, request.email_tfa)?;
+    Ok(Json(UserProfileResponse::from_user(conn, user)?))
+}
@@ -1,9 +1,11 @@
 use crate::database::database::DBConn;
 use crate::database::group::group::Group;
+use crate::database::hierarchy::hierarchy_arrangement::HierarchyArrangements;
 use crate::database::schema::*;
 use crate::database::user::user::User;
 use crate::grouping::arrangement_strategy::ArrangementStrategy;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::r2d2::PooledConnection;
 use diesel::{Associations, Identifiable, Queryable, Selectable};
@@ -24,6 +26,9 @@ pub struct Arrangement {
     pub groups_dependant: bool,
     pub tags_dependant: bool,
     pub exif_dependant: bool,
+    pub ratings_dependant: bool,
+    pub edition_version: i32, // Incremented on each edition, used to detect concurrent editions
+    pub enabled: bool,        // Disabled arrangements are skipped by the grouping process
 }
 
 impl Arrangement {
@@ -34,63 +39,134 @@ impl Arrangement {
         strong_match_conversion: bool,
         strategy: Option<ArrangementStrategy>,
     ) -> Result<Arrangement, ErrorResponder> {
-        let strategy_bytes = serde_json::to_vec(&strategy).map_err(|e| ErrorType::InternalError(e.to_string()).res_no_rollback())?;
+        let strategy_bytes = Self::strategy_to_binary(&strategy)?;
         let dependency_type = ArrangementDependencyType::from(&strategy);
 
         diesel::insert_into(arrangements::table)
             .values((
                 arrangements::user_id.eq(user_id),
                 arrangements::name.eq(&name),
-                arrangements::strategy.eq(Some(strategy_bytes)),
+                arrangements::strategy.eq(strategy_bytes),
                 arrangements::strong_match_conversion.eq(strong_match_conversion),
                 arrangements::groups_dependant.eq(dependency_type.groups_dependant),
                 arrangements::tags_dependant.eq(dependency_type.tags_dependant),
                 arrangements::exif_dependant.eq(dependency_type.exif_dependant),
+                arrangements::ratings_dependant.eq(dependency_type.ratings_dependant),
             ))
             .get_result(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
 
+    /// Check that a user having `count` arrangements can create a new one, given the per-user `limit`.
+    pub fn check_user_limit(count: i64, limit: i64) -> Result<(), ErrorResponder> {
+        if count >= limit {
+            return ErrorType::UnprocessableEntity(format!("Users can't have more than {} arrangements", limit)).res_err();
+        }
+        Ok(())
+    }
+    pub fn count_for_user(conn: &mut DBConn, user_id: i32) -> Result<i64, ErrorResponder> {
+        arrangements::table
+            .filter(arrangements::user_id.eq(user_id))
+            .count()
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+
+    /// Update the arrangement only if its edition version is still `edition_version`, incrementing it.
+    /// Returns a `Conflict` error if the arrangement has been edited in the meantime.
     pub fn update(
         conn: &mut DBConn,
         id: i32,
+        edition_version: i32,
         name: &String,
         strong_match_conversion: bool,
         strategy: &Option<ArrangementStrategy>,
     ) -> Result<Arrangement, ErrorResponder> {
         let dependency_type = ArrangementDependencyType::from(strategy);
 
+        diesel::update(
+            arrangements::table
+                .filter(arrangements::id.eq(id))
+                .filter(arrangements::edition_version.eq(edition_version)),
+        )
+        .set((
+            arrangements::name.eq(name),
+            arrangements::strategy.eq(Self::strategy_to_binary(strategy)?),
+            arrangements::strong_match_conversion.eq(&strong_match_conversion),
+            arrangements::groups_dependant.eq(dependency_type.groups_dependant),
+            arrangements::tags_dependant.eq(dependency_type.tags_dependant),
+            arrangements::exif_dependant.eq(dependency_type.exif_dependant),
+            arrangements::ratings_dependant.eq(dependency_type.ratings_dependant),
+            arrangements::edition_version.eq(arrangements::edition_version + 1),
+        ))
+        .returning(Arrangement::as_returning())
+        .get_result(conn)
+        .optional()
+        .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?
+        .ok_or_else(Self::edition_conflict)
+    }
+    /// Check that the arrangement has not been edited since the client fetched the version `edition_version`.
+    pub fn check_edition_version(&self, edition_version: i32) -> Result<(), ErrorResponder> {
+        if self.edition_version != edition_version {
+            return Err(Self::edition_conflict());
+        }
+        Ok(())
+    }
+    /// Enable or disable the arrangement. The groups of a disabled arrangement are not updated by the grouping process.
+    pub fn set_enabled(conn: &mut DBConn, id: i32, enabled: bool) -> Result<Arrangement, ErrorResponder> {
         diesel::update(arrangements::table.filter(arrangements::id.eq(id)))
-            .set((
-                arrangements::name.eq(name),
-                arrangements::strategy.eq(Self::strategy_to_binary(strategy)?),
-                arrangements::strong_match_conversion.eq(&strong_match_conversion),
-                arrangements::groups_dependant.eq(dependency_type.groups_dependant),
-                arrangements::tags_dependant.eq(dependency_type.tags_dependant),
-                arrangements::exif_dependant.eq(dependency_type.exif_dependant),
-            ))
+            .set(arrangements::enabled.eq(enabled))
             .returning(Arrangement::as_returning())
             .get_result(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
+    /// Check that no other arrangement of the user is named `name` (trimmed, case-insensitive).
+    /// `arrangement_id` is the id of the arrangement being renamed, if any.
+    pub fn check_name_available(conn: &mut DBConn, user_id: i32, name: &str, arrangement_id: Option<i32>) -> Result<(), ErrorResponder> {
+        Self::check_name_available_among(&Self::from_user_id(conn, user_id)?, name, arrangement_id)
+    }
+    /// Check that none of the arrangements, except the one of id `arrangement_id`, is named `name` (trimmed, case-insensitive).
+    pub fn check_name_available_among(arrangements: &[Arrangement], name: &str, arrangement_id: Option<i32>) -> Result<(), ErrorResponder> {
+        let name = name.trim().to_lowercase();
+        if arrangements
+            .iter()
+            .any(|arrangement| Some(arrangement.id) != arrangement_id && arrangement.name.trim().to_lowercase() == name)
+        {
+            return ErrorType::InvalidInput("An arrangement with the same name already exists".to_string()).res_err_no_rollback();
+        }
+        Ok(())
+    }
+    fn edition_conflict() -> ErrorResponder {
+        ErrorType::Conflict("The arrangement has been edited in the meantime, reload it and retry".to_string()).res()
+    }
 
     pub fn from_user_id(conn: &mut DBConn, user_id: i32) -> Result<Vec<Arrangement>, ErrorResponder> {
-        arrangements::table
-            .filter(arrangements::user_id.eq(user_id))
+        Self::from_user_id_statement(user_id)
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
+    /// Arrangements of the user, ordered by id so that listings are stable between requests.
+    pub fn from_user_id_statement(user_id: i32) -> arrangements::BoxedQuery<'static, Pg> {
+        arrangements::table
+            .filter(arrangements::user_id.eq(user_id))
+            .order_by(arrangements::id)
+            .into_boxed()
+    }
     pub fn from_user_id_with_groups(conn: &mut DBConn, user_id: i32) -> Result<Vec<(Arrangement, Vec<Group>)>, ErrorResponder> {
         let arrangements = Self::from_user_id(conn, user_id)?;
         let groups = Group::from_user_id_all(conn, user_id)?;
 
-        Ok(arrangements
+        Ok(Self::attach_groups(arrangements, &groups))
+    }
+    /// Pair each arrangement with its groups, taken from `groups`.
+    pub fn attach_groups(arrangements: Vec<Arrangement>, groups: &[Group]) -> Vec<(Arrangement, Vec<Group>)> {
+        arrangements
             .into_iter()
             .map(|arrangement| {
                 let arrangement_groups = groups.iter().filter(|group| group.arrangement_id == arrangement.id).cloned().collect();
                 (arrangement, arrangement_groups)
             })
-            .collect_vec())
+            .collect_vec()
     }
     pub fn from_id_and_user_id(conn: &mut DBConn, arrangement_id: i32, user_id: i32) -> Result<Arrangement, ErrorResponder> {
         Self::from_id_and_user_id_opt(conn, arrangement_id, user_id)?.ok_or_else(|| ErrorType::ArrangementNotFound.res())
@@ -119,6 +195,7 @@ impl Arrangement {
         self.groups_dependant = dependency_type.groups_dependant;
         self.tags_dependant = dependency_type.tags_dependant;
         self.exif_dependant = dependency_type.exif_dependant;
+        self.ratings_dependant = dependency_type.ratings_dependant;
 
         diesel::update(arrangements::table.filter(arrangements::id.eq(self.id)))
             .set((
@@ -126,6 +203,33 @@ impl Arrangement {
                 arrangements::groups_dependant.eq(self.groups_dependant),
                 arrangements::tags_dependant.eq(self.tags_dependant),
                 arrangements::exif_dependant.eq(self.exif_dependant),
+                arrangements::ratings_dependant.eq(self.ratings_dependant),
+            ))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(())
+    }
+    /// Re-derive the dependency flags from the strategy.
+    /// Returns true if the flags were out of sync with the strategy and have been corrected (not persisted).
+    pub fn recompute_dependency_flags(&mut self) -> Result<bool, ErrorResponder> {
+        let dependency_type = ArrangementDependencyType::from(&self.get_strategy()?);
+        if ArrangementDependencyType::from(&*self) == dependency_type {
+            return Ok(false);
+        }
+        self.groups_dependant = dependency_type.groups_dependant;
+        self.tags_dependant = dependency_type.tags_dependant;
+        self.exif_dependant = dependency_type.exif_dependant;
+        self.ratings_dependant = dependency_type.ratings_dependant;
+        Ok(true)
+    }
+    /// Persist the dependency flags of the arrangement.
+    pub fn update_dependency_flags(&self, conn: &mut DBConn) -> Result<(), ErrorResponder> {
+        diesel::update(arrangements::table.filter(arrangements::id.eq(self.id)))
+            .set((
+                arrangements::groups_dependant.eq(self.groups_dependant),
+                arrangements::tags_dependant.eq(self.tags_dependant),
+                arrangements::exif_dependant.eq(self.exif_dependant),
+                arrangements::ratings_dependant.eq(self.ratings_dependant),
             ))
             .execute(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
@@ -142,8 +246,7 @@ impl Arrangement {
 
     /// List all user’s arrangements
     pub fn list_arrangements(conn: &mut DBConn, user_id: i32) -> Result<Vec<Arrangement>, ErrorResponder> {
-        arrangements::table
-            .filter(arrangements::user_id.eq(user_id))
+        Self::from_user_id_statement(user_id)
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
@@ -168,7 +271,7 @@ impl Arrangement {
             return Ok(vec![]);
         }
 
-        for i in 0..arrangements.len() - 1 {
+        for i in 0..arrangements.len() {
             let cloned_arrangements = arrangements.clone();
             arrangements[i].set_dependant_arrangements_auto(&cloned_arrangements);
         }
@@ -184,6 +287,31 @@ impl Arrangement {
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?)
     }
 
+    /// Check that the arrangement `self` can be merged into `target`: both must be distinct manual arrangements,
+    /// and `self`, being deleted by the merge, must not be used in a hierarchy.
+    pub fn check_mergeable_into(&self, target: &Arrangement, used_in_hierarchy: bool) -> Result<(), ErrorResponder> {
+        if self.id == target.id {
+            return ErrorType::UnprocessableEntity("Can’t merge an arrangement into itself".to_string()).res_err();
+        }
+        if self.strategy.is_some() || target.strategy.is_some() {
+            return ErrorType::UnprocessableEntity("Only manual arrangements can be merged".to_string()).res_err();
+        }
+        if used_in_hierarchy {
+            return ErrorType::UnprocessableEntity("Can’t merge this arrangement because it is used in a hierarchy".to_string()).res_err();
+        }
+        Ok(())
+    }
+    /// Move all the groups of this manual arrangement to `target`, then delete this arrangement.
+    /// The groups keep their ids: their pictures, shares, link shares and the arrangements depending on them are left unchanged.
+    /// Returns the ids of the moved groups.
+    pub fn merge_into(&self, conn: &mut DBConn, target: &Arrangement) -> Result<Vec<i32>, ErrorResponder> {
+        let used_in_hierarchy = !HierarchyArrangements::from_arrangement_id(conn, self.id)?.is_empty();
+        self.check_mergeable_into(target, used_in_hierarchy)?;
+        let group_ids = Group::move_to_arrangement(conn, self.id, target.id)?;
+        Self::delete(conn, self.id)?;
+        Ok(group_ids)
+    }
+
     /// Delete the arrangement with the given id, without taking care of the dependencies (hierarchies, shared groups, strategies...)
     pub fn delete(conn: &mut DBConn, arrangement_id: i32) -> Result<(), ErrorResponder> {
         diesel::delete(arrangements::table.filter(arrangements::id.eq(arrangement_id)))
@@ -200,6 +328,14 @@ pub struct ArrangementDetails {
     pub dependant_arrangements: Vec<i32>, // Ids of the arrangements on which this arrangement depends (got with set_dependant_arrangements_auto fetching the groups’s arrangements)
 }
 impl ArrangementDetails {
+    /// Enabled arrangements whose strategy depends on the tag group (see [`ArrangementStrategy::depends_on_tag_group`]).
+    pub fn depending_on_tag_group(arrangements: &[ArrangementDetails], tag_group_id: i32, tag_ids: &[i32]) -> Vec<Arrangement> {
+        arrangements
+            .iter()
+            .filter(|details| details.arrangement.enabled && details.strategy.depends_on_tag_group(tag_group_id, tag_ids))
+            .map(|details| details.arrangement.clone())
+            .collect()
+    }
     pub fn set_dependant_arrangements_auto(&mut self, all_arrangements_details: &Vec<ArrangementDetails>) {
         self.dependant_arrangements = all_arrangements_details
             .iter()
@@ -219,11 +355,12 @@ impl PartialEq for ArrangementDetails {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ArrangementDependencyType {
     pub groups_dependant: bool,
     pub tags_dependant: bool,
     pub exif_dependant: bool,
+    pub ratings_dependant: bool,
 }
 
 impl ArrangementDependencyType {
@@ -232,6 +369,7 @@ impl ArrangementDependencyType {
             groups_dependant: true,
             tags_dependant: false,
             exif_dependant: false,
+            ratings_dependant: false,
         }
     }
     pub fn new_tags_dependant() -> Self {
@@ -239,6 +377,7 @@ impl ArrangementDependencyType {
             groups_dependant: false,
             tags_dependant: true,
             exif_dependant: false,
+            ratings_dependant: false,
         }
     }
     pub fn new_exif_dependant() -> Self {
@@ -246,6 +385,15 @@ impl ArrangementDependencyType {
             groups_dependant: false,
             tags_dependant: false,
             exif_dependant: true,
+            ratings_dependant: false,
+        }
+    }
+    pub fn new_ratings_dependant() -> Self {
+        Self {
+            groups_dependant: false,
+            tags_dependant: false,
+            exif_dependant: false,
+            ratings_dependant: true,
         }
     }
     pub fn new_none() -> Self {
@@ -253,6 +401,7 @@ impl ArrangementDependencyType {
             groups_dependant: false,
             tags_dependant: false,
             exif_dependant: false,
+            ratings_dependant: false,
         }
     }
     /// Returns true if at least one of the dependencies of this type matches one of the provided.
@@ -260,6 +409,7 @@ impl ArrangementDependencyType {
         (self.groups_dependant && other.groups_dependant)
             || (self.tags_dependant && other.tags_dependant)
             || (self.exif_dependant && other.exif_dependant)
+            || (self.ratings_dependant && other.ratings_dependant)
     }
 }
 
@@ -270,6 +420,7 @@ impl From<&Option<ArrangementStrategy>> for ArrangementDependencyType {
                 groups_dependant: strategy.is_groups_dependant(),
                 tags_dependant: strategy.is_tags_dependant(),
                 exif_dependant: strategy.is_exif_dependant(),
+                ratings_dependant: strategy.is_ratings_dependant(),
             }
         } else {
             Self::new_none()
@@ -282,6 +433,7 @@ impl From<&Arrangement> for ArrangementDependencyType {
             groups_dependant: a.groups_dependant,
             tags_dependant: a.tags_dependant,
             exif_dependant: a.exif_dependant,
+            ratings_dependant: a.ratings_dependant,
         }
     }
 }
@@ -292,6 +444,7 @@ impl From<&ArrangementDetails> for ArrangementDependencyType {
             groups_dependant: ad.arrangement.groups_dependant,
             tags_dependant: ad.arrangement.tags_dependant,
             exif_dependant: ad.arrangement.exif_dependant,
+            ratings_dependant: ad.arrangement.ratings_dependant,
         }
     }
 }
@@ -1,8 +1,17 @@
 use crate::database::database::DBConn;
 use crate::database::group::arrangement::Arrangement;
+use crate::database::group::link_share_group::LinkShareGroups;
+use crate::database::group::manual_override::ManualOverride;
+use crate::database::group::shared_group::SharedGroup;
+use crate::database::hierarchy::hierarchy_arrangement::HierarchyArrangements;
+use crate::database::picture::picture::Picture;
 use crate::database::schema::*;
+use crate::utils::color::{hex_color_option, palette_color};
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
 use diesel::{Associations, Identifiable, Queryable, Selectable};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -17,18 +26,45 @@ pub struct Group {
     pub share_match_conversion: bool,
     pub name: String,
     pub to_be_deleted: bool,
+    /// RGB color, (de)serialized as a `#RRGGBB` hex string. Set for the groups created by a grouping strategy.
+    #[serde(default, with = "hex_color_option")]
+    #[schemars(with = "Option<String>")]
+    pub color: Option<Vec<u8>>,
 }
 
 impl Group {
-    pub fn insert(conn: &mut DBConn, arrangement_id: i32, name: String, share_match_conversion: bool) -> Result<Group, ErrorResponder> {
+    pub fn insert(
+        conn: &mut DBConn,
+        arrangement_id: i32,
+        name: String,
+        share_match_conversion: bool,
+        color: Option<Vec<u8>>,
+    ) -> Result<Group, ErrorResponder> {
+        Self::insert_statement(arrangement_id, name, share_match_conversion, color)
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    /// The inserted group is returned by the `INSERT` itself (`RETURNING`), its id being the one of the inserted row.
+    pub fn insert_statement(
+        arrangement_id: i32,
+        name: String,
+        share_match_conversion: bool,
+        color: Option<Vec<u8>>,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, Group> {
         diesel::insert_into(groups::table)
             .values((
                 groups::arrangement_id.eq(arrangement_id),
                 groups::name.eq(name),
                 groups::share_match_conversion.eq(share_match_conversion),
+                groups::color.eq(color),
             ))
-            .get_result(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .returning(Group::as_returning())
+    }
+
+    /// Insert a group created by a grouping strategy, colored from its name (see [`palette_color`]).
+    pub fn insert_colored(conn: &mut DBConn, arrangement_id: i32, name: String) -> Result<Group, ErrorResponder> {
+        let color = palette_color(&name);
+        Self::insert(conn, arrangement_id, name, false, Some(color))
     }
 
     pub fn from_id(conn: &mut DBConn, group_id: i32) -> Result<Group, ErrorResponder> {
@@ -60,12 +96,68 @@ impl Group {
             .first(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
-    /// Retrieves all groups for a given user, including those marked for deletion.
+    /// Retrieves a group of the user's arrangements, or a [`ErrorType::NotFound`] error.
+    pub fn from_id_and_user_id(conn: &mut DBConn, group_id: i32, user_id: i32) -> Result<Group, ErrorResponder> {
+        groups::table
+            .inner_join(arrangements::table.on(groups::arrangement_id.eq(arrangements::id)))
+            .filter(groups::id.eq(group_id))
+            .filter(arrangements::user_id.eq(user_id))
+            .select(Group::as_select())
+            .first(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?
+            .ok_or_else(|| ErrorType::NotFound(String::from("Group not found")).res())
+    }
+    /// Retrieves all groups for a given user, including those marked for deletion, ordered by id.
     pub fn from_user_id_all(conn: &mut DBConn, user_id: i32) -> Result<Vec<Group>, ErrorResponder> {
         groups::table
             .inner_join(arrangements::table.on(groups::arrangement_id.eq(arrangements::id)))
             .filter(arrangements::user_id.eq(user_id))
             .select(Group::as_select())
+            .order_by(groups::id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+
+    /// Counts the pictures of each of the groups, as (group_id, count) tuples ordered by group id. Empty groups are left out.
+    pub fn pictures_counts(conn: &mut DBConn, group_ids: &[i32]) -> Result<Vec<(i32, i64)>, ErrorResponder> {
+        Self::pictures_counts_statement(group_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to count groups pictures".to_string(), e).res())
+    }
+    pub fn pictures_counts_statement(group_ids: &[i32]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i64)> {
+        groups_pictures::table
+            .filter(groups_pictures::group_id.eq_any(group_ids.to_vec()))
+            .group_by(groups_pictures::group_id)
+            .select((groups_pictures::group_id, diesel::dsl::count_star()))
+            .order_by(groups_pictures::group_id)
+    }
+
+    /// Retrieves the memberships of the pictures in the groups of the user's arrangements, as (picture_id, group_id) tuples, in a single query.
+    pub fn user_groups_of_pictures(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<(i64, i32)>, ErrorResponder> {
+        Self::user_groups_of_pictures_statement(user_id, picture_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures groups".to_string(), e).res())
+    }
+    /// Build the statement of [`Group::user_groups_of_pictures`], ordered by picture and group ids.
+    pub fn user_groups_of_pictures_statement(
+        user_id: i32,
+        picture_ids: &[i64],
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i64, i32)> {
+        groups_pictures::table
+            .inner_join(groups::table.inner_join(arrangements::table))
+            .filter(arrangements::user_id.eq(user_id))
+            .filter(groups_pictures::picture_id.eq_any(picture_ids.to_vec()))
+            .select((groups_pictures::picture_id, groups_pictures::group_id))
+            .order_by((groups_pictures::picture_id, groups_pictures::group_id))
+    }
+
+    /// Retrieves the ids of all pictures contained in at least one of the groups.
+    pub fn pictures_from_group_ids(conn: &mut DBConn, group_ids: &Vec<i32>) -> Result<Vec<i64>, ErrorResponder> {
+        groups_pictures::table
+            .filter(groups_pictures::group_id.eq_any(group_ids))
+            .select(groups_pictures::picture_id)
+            .distinct()
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
@@ -83,37 +175,68 @@ impl Group {
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
 
-    // Adds a picture to the group and returns the vec of added picture ids (the ones that were not already in the group)
+    /// Add the pictures to the group, ignoring the ones already in it.
+    /// Returns the ids of the pictures that were actually added.
     pub fn add_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i64>) -> Result<Vec<i64>, ErrorResponder> {
+        let added_picture_ids = Self::add_pictures_statement(group_id, picture_ids)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Picture::touch(conn, &added_picture_ids)?;
+        Ok(added_picture_ids)
+    }
+    pub fn add_pictures_statement(group_id: i32, picture_ids: &[i64]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
         let values: Vec<_> = picture_ids
-            .into_iter()
+            .iter()
             .map(|pic_id| (groups_pictures::group_id.eq(group_id), groups_pictures::picture_id.eq(*pic_id)))
             .collect();
 
         diesel::insert_into(groups_pictures::table)
-            .values(&values)
+            .values(values)
             .on_conflict_do_nothing()
             .returning(groups_pictures::picture_id)
-            .get_results(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
 
     pub fn remove_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i64>) -> Result<Vec<i64>, ErrorResponder> {
-        diesel::delete(groups_pictures::table)
+        let removed_picture_ids = diesel::delete(groups_pictures::table)
             .filter(groups_pictures::group_id.eq(group_id))
             .filter(groups_pictures::picture_id.eq_any(picture_ids))
             .returning(groups_pictures::picture_id)
             .get_results(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Picture::touch(conn, &removed_picture_ids)?;
+        Ok(removed_picture_ids)
     }
     pub fn clear_and_get_pictures(conn: &mut DBConn, group_id: i32) -> Result<Vec<i64>, ErrorResponder> {
-        diesel::delete(groups_pictures::table)
+        let removed_picture_ids = diesel::delete(groups_pictures::table)
             .filter(groups_pictures::group_id.eq(group_id))
             .returning(groups_pictures::picture_id)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Picture::touch(conn, &removed_picture_ids)?;
+        Ok(removed_picture_ids)
+    }
+    /// Move all the groups of an arrangement to another arrangement. The groups keep their ids, and so their pictures, shares and links.
+    /// Returns the ids of the moved groups.
+    pub fn move_to_arrangement(conn: &mut DBConn, from_arrangement_id: i32, to_arrangement_id: i32) -> Result<Vec<i32>, ErrorResponder> {
+        Self::move_to_arrangement_statement(from_arrangement_id, to_arrangement_id)
             .get_results(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
+    pub fn move_to_arrangement_statement(
+        from_arrangement_id: i32,
+        to_arrangement_id: i32,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i32> {
+        diesel::update(groups::table.filter(groups::arrangement_id.eq(from_arrangement_id)))
+            .set(groups::arrangement_id.eq(to_arrangement_id))
+            .returning(groups::id)
+    }
     pub fn delete_by_arrangement_id(conn: &mut DBConn, arrangement_id: i32) -> Result<(), ErrorResponder> {
+        diesel::delete(
+            manual_overrides::table
+                .filter(manual_overrides::group_id.eq_any(groups::table.filter(groups::arrangement_id.eq(arrangement_id)).select(groups::id))),
+        )
+        .execute(conn)
+        .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
         diesel::delete(groups::table.filter(groups::arrangement_id.eq(arrangement_id)))
             .execute(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
@@ -127,4 +250,45 @@ impl Group {
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
         Ok(())
     }
+    /// Permanently deletes the groups marked as to be deleted that no longer contain any picture,
+    /// in the given arrangement or in all arrangements. Shares, link shares and manual overrides of the groups are deleted first.
+    /// Returns the ids of the deleted groups.
+    pub fn delete_to_be_deleted_empty(conn: &mut DBConn, arrangement_id: Option<i32>) -> Result<Vec<i32>, ErrorResponder> {
+        let mut query = groups::table.filter(groups::to_be_deleted.eq(true)).into_boxed();
+        if let Some(arrangement_id) = arrangement_id {
+            query = query.filter(groups::arrangement_id.eq(arrangement_id));
+        }
+        let to_be_deleted_groups: Vec<Group> = query.load(conn).map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        if to_be_deleted_groups.is_empty() {
+            return Ok(vec![]);
+        }
+        let populated_group_ids: Vec<i32> = groups_pictures::table
+            .filter(groups_pictures::group_id.eq_any(to_be_deleted_groups.iter().map(|group| group.id)))
+            .select(groups_pictures::group_id)
+            .distinct()
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+
+        let group_ids = Self::to_be_deleted_empty_ids(&to_be_deleted_groups, &populated_group_ids);
+        if group_ids.is_empty() {
+            return Ok(group_ids);
+        }
+        SharedGroup::delete_by_group_ids(conn, &group_ids)?;
+        SharedGroup::clear_match_conversion_group_ids(conn, &group_ids)?;
+        LinkShareGroups::delete_by_group_ids(conn, &group_ids)?;
+        ManualOverride::delete_by_group_ids(conn, &group_ids)?;
+        HierarchyArrangements::clear_parent_group_ids(conn, &group_ids)?;
+        diesel::delete(groups::table.filter(groups::id.eq_any(&group_ids)))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(group_ids)
+    }
+    /// Ids of the groups marked as to be deleted that are not in `populated_group_ids` (groups still containing pictures).
+    pub fn to_be_deleted_empty_ids(groups: &[Group], populated_group_ids: &[i32]) -> Vec<i32> {
+        groups
+            .iter()
+            .filter(|group| group.to_be_deleted && !populated_group_ids.contains(&group.id))
+            .map(|group| group.id)
+            .collect()
+    }
 }
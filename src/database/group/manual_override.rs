@@ -0,0 +1,149 @@
+use crate::database::database::DBConn;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::Picture;
+use crate::database::schema::*;
+use crate::grouping::strategy_grouping::UngroupRecord;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::dsl::exists;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use diesel::upsert::excluded;
+use diesel::{Associations, Identifiable, Queryable, Selectable};
+use std::collections::{BTreeMap, HashSet};
+
+/// Picture forced in (`force_in`) or out of a group of an automatic arrangement, whatever its strategy decides.
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone)]
+#[diesel(primary_key(group_id, picture_id))]
+#[diesel(belongs_to(Group))]
+#[diesel(belongs_to(Picture))]
+#[diesel(table_name = manual_overrides)]
+pub struct ManualOverride {
+    pub group_id: i32,
+    pub picture_id: i64,
+    pub force_in: bool,
+}
+
+/// Group memberships forced by the manual overrides of an arrangement (see [`ManualOverride::forced_memberships`]).
+#[derive(Debug, Default, PartialEq)]
+pub struct ForcedMemberships {
+    /// group_id -> picture_ids to add
+    pub add: BTreeMap<i32, Vec<i64>>,
+    /// group_id -> picture_ids to remove
+    pub remove: BTreeMap<i32, Vec<i64>>,
+}
+impl ForcedMemberships {
+    pub fn picture_ids(&self) -> HashSet<i64> {
+        self.add.values().chain(self.remove.values()).flatten().cloned().collect()
+    }
+    /// Pictures of the list that are not forced out of the group, the ones the strategy may add to it.
+    pub fn without_forced_out<'a>(&self, group_id: i32, picture_ids: impl IntoIterator<Item = &'a i64>) -> Vec<i64> {
+        let forced_out = self.remove.get(&group_id);
+        picture_ids
+            .into_iter()
+            .filter(|picture_id| forced_out.map_or(true, |forced_out| !forced_out.contains(picture_id)))
+            .cloned()
+            .collect()
+    }
+    /// Take the forced in pictures out of the ungroup record, so they stay in their group even if the strategy doesn't match them.
+    pub fn keep_forced_in(&self, ungroup_record: &mut UngroupRecord) {
+        for (group_id, forced_in) in self.add.iter() {
+            if let Some(picture_ids) = ungroup_record.map.get_mut(group_id) {
+                forced_in.iter().for_each(|picture_id| {
+                    picture_ids.remove(picture_id);
+                });
+            }
+        }
+    }
+}
+
+impl ManualOverride {
+    /// Force the pictures in or out of the group, replacing their previous override in this group.
+    pub fn upsert(conn: &mut DBConn, group_id: i32, picture_ids: &[i64], force_in: bool) -> Result<(), ErrorResponder> {
+        let values: Vec<_> = picture_ids
+            .iter()
+            .map(|picture_id| {
+                (
+                    manual_overrides::group_id.eq(group_id),
+                    manual_overrides::picture_id.eq(*picture_id),
+                    manual_overrides::force_in.eq(force_in),
+                )
+            })
+            .collect();
+        diesel::insert_into(manual_overrides::table)
+            .values(values)
+            .on_conflict((manual_overrides::group_id, manual_overrides::picture_id))
+            .do_update()
+            .set(manual_overrides::force_in.eq(excluded(manual_overrides::force_in)))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(())
+    }
+    /// Clear the overrides of the pictures in the group. Returns the ids of the pictures that had one.
+    pub fn delete(conn: &mut DBConn, group_id: i32, picture_ids: &[i64]) -> Result<Vec<i64>, ErrorResponder> {
+        diesel::delete(manual_overrides::table)
+            .filter(manual_overrides::group_id.eq(group_id))
+            .filter(manual_overrides::picture_id.eq_any(picture_ids))
+            .returning(manual_overrides::picture_id)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn delete_by_group_ids(conn: &mut DBConn, group_ids: &Vec<i32>) -> Result<(), ErrorResponder> {
+        diesel::delete(manual_overrides::table.filter(manual_overrides::group_id.eq_any(group_ids)))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(())
+    }
+
+    /// Overrides of the groups, restricted to the given pictures if any.
+    /// Only the pictures the user can still access are kept: pictures in the trash are removed from all groups,
+    /// and a picture that is no longer shared with the user must not be put back in their groups.
+    pub fn from_group_ids(
+        conn: &mut DBConn,
+        user_id: i32,
+        group_ids: &[i32],
+        picture_ids: Option<&Vec<i64>>,
+    ) -> Result<Vec<ManualOverride>, ErrorResponder> {
+        Self::from_group_ids_statement(user_id, group_ids, picture_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn from_group_ids_statement(
+        user_id: i32,
+        group_ids: &[i32],
+        picture_ids: Option<&Vec<i64>>,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, ManualOverride> {
+        let accessible_picture_ids = pictures::table
+            .filter(pictures::deleted_date.is_null())
+            .filter(
+                pictures::owner_id.eq(user_id).or(exists(
+                    groups_pictures::table
+                        .inner_join(shared_groups::table.on(shared_groups::group_id.eq(groups_pictures::group_id)))
+                        .filter(groups_pictures::picture_id.eq(pictures::id))
+                        .filter(shared_groups::user_id.eq(user_id)),
+                )),
+            )
+            .select(pictures::id);
+        let mut query = manual_overrides::table
+            .filter(manual_overrides::group_id.eq_any(group_ids.to_vec()))
+            .filter(manual_overrides::picture_id.eq_any(accessible_picture_ids))
+            .select(ManualOverride::as_select())
+            .into_boxed();
+        if let Some(picture_ids) = picture_ids {
+            query = query.filter(manual_overrides::picture_id.eq_any(picture_ids.clone()));
+        }
+        query
+    }
+
+    /// Split the overrides into the memberships to force. They are loaded before the strategy groups the pictures,
+    /// so that the strategy never adds a forced out picture (see [`ForcedMemberships::without_forced_out`]).
+    pub fn forced_memberships(overrides: &[ManualOverride]) -> ForcedMemberships {
+        let mut forced = ForcedMemberships::default();
+        for manual_override in overrides {
+            let memberships = if manual_override.force_in { &mut forced.add } else { &mut forced.remove };
+            memberships.entry(manual_override.group_id).or_default().push(manual_override.picture_id);
+        }
+        forced
+    }
+}
@@ -3,9 +3,63 @@ use crate::database::group::group::Group;
 use crate::database::schema::*;
 use crate::database::user::user::User;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use diesel::BoolExpressionMethods;
 use diesel::ExpressionMethods;
+use diesel::JoinOnDsl;
 use diesel::QueryDsl;
-use diesel::{Associations, Identifiable, Queryable, RunQueryDsl, Selectable};
+use diesel::{Associations, Identifiable, OptionalExtension, Queryable, RunQueryDsl, Selectable};
+use rocket_okapi::JsonSchema;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ops::BitOr;
+
+/// Permissions granted to the recipient of a shared group, stored as bit flags in `shared_groups.permissions`.
+/// The shares stored with the initial layout of the column are remapped by the `shared_groups_permissions` migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharePermissions(i16);
+
+impl SharePermissions {
+    /// See the pictures of the group
+    pub const VIEW: Self = Self(1);
+    /// Edit the tags of the pictures of the group
+    pub const EDIT_TAGS: Self = Self(1 << 1);
+    /// Add and remove pictures of the group, for manual groups
+    pub const ADD_PICTURES: Self = Self(1 << 2);
+    /// All the known permissions
+    pub const ALL: Self = Self(Self::VIEW.0 | Self::EDIT_TAGS.0 | Self::ADD_PICTURES.0);
+
+    pub fn from_bits(bits: i16) -> Self {
+        Self(bits)
+    }
+    pub fn bits(self) -> i16 {
+        self.0
+    }
+    /// Whether all the permissions of `other` are granted.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// Permissions from bits received from a client.
+    /// Returns InvalidInput if a bit is not a known permission, or if `VIEW` is not granted.
+    pub fn from_request_bits(bits: i16) -> Result<Self, ErrorResponder> {
+        let permissions = Self(bits);
+        if !Self::ALL.contains(permissions) {
+            return ErrorType::InvalidInput(format!("Unknown share permissions: {}", bits & !Self::ALL.0)).res_err();
+        }
+        if !permissions.contains(Self::VIEW) {
+            return ErrorType::InvalidInput(String::from("Share permissions must include VIEW")).res_err();
+        }
+        Ok(permissions)
+    }
+}
+impl BitOr for SharePermissions {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
 #[diesel(primary_key(user_id, group_id))]
@@ -21,7 +75,188 @@ pub struct SharedGroup {
     pub confirmed: bool,
 }
 
+/// Share of a group sent by its owner, with the names of the group and of its arrangement.
+#[derive(Queryable, Serialize, JsonSchema, Debug, PartialEq)]
+pub struct OutgoingShare {
+    pub recipient_id: i32,
+    pub group_id: i32,
+    pub group_name: String,
+    pub arrangement_id: i32,
+    pub arrangement_name: String,
+    pub permissions: i16,
+    pub copied: bool,
+    pub confirmed: bool,
+}
+
+/// Recipient of a share of a group, with the permissions and status of the share.
+#[derive(Queryable, Serialize, JsonSchema, Debug, PartialEq)]
+pub struct ShareRecipient {
+    pub user_id: i32,
+    pub user_name: String,
+    pub permissions: i16,
+    pub copied: bool,
+    pub confirmed: bool,
+}
+
+/// Confirmed share of a group received by a user, with the sharer, and the names of the group and of its arrangement.
+#[derive(Queryable, Debug, PartialEq, Clone)]
+pub struct IncomingShare {
+    pub sharer_id: i32,
+    pub sharer_name: String,
+    pub arrangement_id: i32,
+    pub arrangement_name: String,
+    pub group_id: i32,
+    pub group_name: String,
+    pub permissions: i16,
+}
+
+/// Group shared with the user, within a [`SharedArrangement`].
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct SharedArrangementGroup {
+    pub group_id: i32,
+    pub group_name: String,
+    pub permissions: i16,
+}
+/// Arrangement of a sharer, with only the groups shared with the user.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct SharedArrangement {
+    pub arrangement_id: i32,
+    pub arrangement_name: String,
+    pub groups: Vec<SharedArrangementGroup>,
+}
+/// Arrangements of which a sharer shared groups with the user.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct SharerArrangements {
+    pub sharer_id: i32,
+    pub sharer_name: String,
+    pub arrangements: Vec<SharedArrangement>,
+}
+
+impl SharerArrangements {
+    /// Group the shares by sharer then by arrangement, the shares being sorted by sharer, arrangement and group.
+    pub fn from_shares(shares: Vec<IncomingShare>) -> Vec<SharerArrangements> {
+        let mut sharers: Vec<SharerArrangements> = Vec::new();
+        for share in shares {
+            if sharers.last().map(|sharer| sharer.sharer_id) != Some(share.sharer_id) {
+                sharers.push(SharerArrangements {
+                    sharer_id: share.sharer_id,
+                    sharer_name: share.sharer_name,
+                    arrangements: vec![],
+                });
+            }
+            let arrangements = &mut sharers.last_mut().unwrap().arrangements;
+            if arrangements.last().map(|arrangement| arrangement.arrangement_id) != Some(share.arrangement_id) {
+                arrangements.push(SharedArrangement {
+                    arrangement_id: share.arrangement_id,
+                    arrangement_name: share.arrangement_name,
+                    groups: vec![],
+                });
+            }
+            arrangements.last_mut().unwrap().groups.push(SharedArrangementGroup {
+                group_id: share.group_id,
+                group_name: share.group_name,
+                permissions: share.permissions,
+            });
+        }
+        sharers
+    }
+}
+
 impl SharedGroup {
+    pub fn permissions(&self) -> SharePermissions {
+        SharePermissions::from_bits(self.permissions)
+    }
+    /// Whether the pictures of this share count toward the storage usage of the recipient:
+    /// only confirmed copied shares count, and only when `count_copied_shares` (see `COPIED_SHARES_COUNT_STORAGE`) is enabled.
+    pub fn counts_toward_recipient_storage(&self, count_copied_shares: bool) -> bool {
+        count_copied_shares && self.copied && self.confirmed
+    }
+    /// Returns Unauthorized if this share does not grant the required permissions.
+    pub fn check_permission(&self, required: SharePermissions) -> Result<(), ErrorResponder> {
+        if !self.permissions().contains(required) {
+            return ErrorType::Unauthorized.res_err();
+        }
+        Ok(())
+    }
+    /// Returns Unauthorized if the group is not shared with the user, or without the required permissions.
+    pub fn require_permission(conn: &mut DBConn, user_id: i32, group_id: i32, required: SharePermissions) -> Result<SharedGroup, ErrorResponder> {
+        let shared_group: SharedGroup = shared_groups::table
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::group_id.eq(group_id))
+            .first(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?
+            .ok_or_else(|| ErrorType::Unauthorized.res())?;
+        shared_group.check_permission(required)?;
+        Ok(shared_group)
+    }
+    /// Returns Unauthorized if any of the pictures not owned by the user is not in a group shared with the user granting the required permissions.
+    pub fn require_pictures_permission(
+        conn: &mut DBConn,
+        user_id: i32,
+        picture_ids: &[i64],
+        required: SharePermissions,
+    ) -> Result<(), ErrorResponder> {
+        let owned_picture_ids: Vec<i64> = pictures::table
+            .filter(pictures::id.eq_any(picture_ids))
+            .filter(pictures::owner_id.eq(user_id))
+            .select(pictures::id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        let shared_pictures_permissions: Vec<(i64, i16)> = groups_pictures::table
+            .inner_join(shared_groups::table.on(shared_groups::group_id.eq(groups_pictures::group_id)))
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(groups_pictures::picture_id.eq_any(picture_ids))
+            .select((groups_pictures::picture_id, shared_groups::permissions))
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+
+        if !Self::pictures_lacking_permission(picture_ids, &owned_picture_ids, &shared_pictures_permissions, required).is_empty() {
+            return ErrorType::Unauthorized.res_err();
+        }
+        Ok(())
+    }
+    /// Pictures that are neither owned nor in a shared group granting the required permissions.
+    /// `shared_pictures_permissions` are the (picture_id, permissions) of the shared groups containing the pictures.
+    pub fn pictures_lacking_permission(
+        picture_ids: &[i64],
+        owned_picture_ids: &[i64],
+        shared_pictures_permissions: &[(i64, i16)],
+        required: SharePermissions,
+    ) -> Vec<i64> {
+        let allowed: HashSet<i64> = owned_picture_ids
+            .iter()
+            .copied()
+            .chain(
+                shared_pictures_permissions
+                    .iter()
+                    .filter(|(_, permissions)| SharePermissions::from_bits(*permissions).contains(required))
+                    .map(|(picture_id, _)| *picture_id),
+            )
+            .collect();
+        picture_ids.iter().filter(|picture_id| !allowed.contains(picture_id)).copied().collect()
+    }
+
+    /// Replace the permissions of the share of the group with the user, and return the updated share.
+    /// Returns NotFound if the group is not shared with the user.
+    pub fn update_permissions(conn: &mut DBConn, user_id: i32, group_id: i32, permissions: SharePermissions) -> Result<SharedGroup, ErrorResponder> {
+        SharedGroup::update_permissions_statement(user_id, group_id, permissions)
+            .get_result(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?
+            .ok_or_else(|| ErrorType::NotFound(String::from("Share not found")).res())
+    }
+    pub fn update_permissions_statement(
+        user_id: i32,
+        group_id: i32,
+        permissions: SharePermissions,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, SharedGroup> {
+        diesel::update(shared_groups::table)
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::group_id.eq(group_id))
+            .set(shared_groups::permissions.eq(permissions.bits()))
+    }
+
     pub fn from_group_id(conn: &mut DBConn, group_id: i32) -> Result<Vec<SharedGroup>, ErrorResponder> {
         shared_groups::table
             .filter(shared_groups::group_id.eq(group_id))
@@ -29,10 +264,128 @@ impl SharedGroup {
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
 
+    /// Retrieves all the shares sent to the user that are not confirmed yet.
+    pub fn pending_for_user(conn: &mut DBConn, user_id: i32) -> Result<Vec<SharedGroup>, ErrorResponder> {
+        shared_groups::table
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::confirmed.eq(false))
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+
+    /// Confirms all the pending shares of the user and returns the confirmed shares.
+    pub fn confirm_all_pending_for_user(conn: &mut DBConn, user_id: i32) -> Result<Vec<SharedGroup>, ErrorResponder> {
+        diesel::update(shared_groups::table)
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::confirmed.eq(false))
+            .set(shared_groups::confirmed.eq(true))
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+
+    /// Deletes all the pending shares of the user and returns the deleted shares.
+    pub fn delete_all_pending_for_user(conn: &mut DBConn, user_id: i32) -> Result<Vec<SharedGroup>, ErrorResponder> {
+        diesel::delete(shared_groups::table)
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::confirmed.eq(false))
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+
     pub fn delete_by_group_ids(conn: &mut DBConn, group_ids: &Vec<i32>) -> Result<(), ErrorResponder> {
         diesel::delete(shared_groups::table.filter(shared_groups::group_id.eq_any(group_ids)))
             .execute(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
         Ok(())
     }
+    /// Unset the match conversion group of the shares converted to one of the groups.
+    pub fn clear_match_conversion_group_ids(conn: &mut DBConn, group_ids: &Vec<i32>) -> Result<(), ErrorResponder> {
+        diesel::update(shared_groups::table.filter(shared_groups::match_conversion_group_id.eq_any(group_ids)))
+            .set(shared_groups::match_conversion_group_id.eq(None::<i32>))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(())
+    }
+
+    /// Retrieves all the shares of the groups of the owner's arrangements, ordered by arrangement, group and recipient.
+    pub fn outgoing_for_owner(conn: &mut DBConn, owner_id: i32) -> Result<Vec<OutgoingShare>, ErrorResponder> {
+        SharedGroup::outgoing_for_owner_statement(owner_id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn outgoing_for_owner_statement(owner_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, OutgoingShare> {
+        shared_groups::table
+            .inner_join(groups::table.inner_join(arrangements::table))
+            .filter(arrangements::user_id.eq(owner_id))
+            .select((
+                shared_groups::user_id,
+                shared_groups::group_id,
+                groups::name,
+                arrangements::id,
+                arrangements::name,
+                shared_groups::permissions,
+                shared_groups::copied,
+                shared_groups::confirmed,
+            ))
+            .order_by((arrangements::id, groups::id, shared_groups::user_id))
+    }
+    /// Confirmed shares received by the user, sorted by sharer, arrangement and group. Groups pending deletion are skipped.
+    pub fn incoming_for_recipient(conn: &mut DBConn, user_id: i32) -> Result<Vec<IncomingShare>, ErrorResponder> {
+        SharedGroup::incoming_for_recipient_statement(user_id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn incoming_for_recipient_statement(user_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, IncomingShare> {
+        shared_groups::table
+            .inner_join(groups::table.inner_join(arrangements::table.inner_join(users::table)))
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::confirmed.eq(true))
+            .filter(groups::to_be_deleted.eq(false))
+            .select((
+                users::id,
+                users::name,
+                arrangements::id,
+                arrangements::name,
+                groups::id,
+                groups::name,
+                shared_groups::permissions,
+            ))
+            .order_by((users::id, arrangements::id, groups::id))
+    }
+    /// Number of groups shared between the two users, in either direction. Pending shares and groups pending deletion are not counted.
+    pub fn count_between(conn: &mut DBConn, user_id: i32, other_user_id: i32) -> Result<i64, ErrorResponder> {
+        SharedGroup::count_between_statement(user_id, other_user_id)
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn count_between_statement(user_id: i32, other_user_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        shared_groups::table
+            .inner_join(groups::table.inner_join(arrangements::table))
+            .filter(
+                (shared_groups::user_id.eq(user_id).and(arrangements::user_id.eq(other_user_id)))
+                    .or(shared_groups::user_id.eq(other_user_id).and(arrangements::user_id.eq(user_id))),
+            )
+            .filter(shared_groups::confirmed.eq(true))
+            .filter(groups::to_be_deleted.eq(false))
+            .count()
+    }
+    /// Recipients of the shares of a group, pending shares included, ordered by user id.
+    pub fn recipients(conn: &mut DBConn, group_id: i32) -> Result<Vec<ShareRecipient>, ErrorResponder> {
+        SharedGroup::recipients_statement(group_id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn recipients_statement(group_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, ShareRecipient> {
+        shared_groups::table
+            .inner_join(users::table)
+            .filter(shared_groups::group_id.eq(group_id))
+            .select((
+                shared_groups::user_id,
+                users::name,
+                shared_groups::permissions,
+                shared_groups::copied,
+                shared_groups::confirmed,
+            ))
+            .order_by(shared_groups::user_id)
+    }
 }
@@ -0,0 +1,142 @@
+use crate::database::database::DBConn;
+use crate::database::group::group::Group;
+use crate::database::group::group_picture::GroupPicture;
+use crate::database::picture::picture::Picture;
+use crate::database::schema::*;
+use crate::grouping::grouping_delta::{preview_transaction, PictureGroupsEvent};
+use crate::grouping::grouping_process::group_pictures;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use itertools::Itertools;
+use rocket_okapi::JsonSchema;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Discrepancy between the pictures a recipient of shared groups can access and the group memberships.
+#[derive(JsonSchema, Serialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type")]
+pub enum SharingAnomaly {
+    /// A picture of a group shared with the user matches the group `group_id` of the user but is not in it:
+    /// it was not propagated to the user when it was added to the shared group.
+    /// The pictures that no arrangement of the user matches are not in the user's groups, and are not reported.
+    SharedPictureNotGrouped { user_id: i32, group_id: i32, picture_id: i64 },
+    /// A group of the user still holds a picture the user can no longer access (stale membership).
+    UnaccessiblePictureGrouped { user_id: i32, group_id: i32, picture_id: i64 },
+}
+
+/// Result of a scan of the shared groups recipients (see [`SharingConsistencyReport::scan`]).
+#[derive(JsonSchema, Serialize, Debug, PartialEq)]
+pub struct SharingConsistencyReport {
+    pub recipients_count: usize,
+    pub anomalies: Vec<SharingAnomaly>,
+}
+
+impl SharingConsistencyReport {
+    /// Check, for every recipient of a shared group, that the pictures of the groups shared with them are in the groups of the recipient
+    /// they match, and that their own groups hold no picture they can't access. Read-only: the pictures the groups should hold
+    /// are found by grouping the shared pictures in a transaction that is rolled back.
+    pub fn scan(conn: &mut DBConn) -> Result<SharingConsistencyReport, ErrorResponder> {
+        let recipients: Vec<i32> = shared_groups::table
+            .select(shared_groups::user_id)
+            .distinct()
+            .order_by(shared_groups::user_id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+
+        let mut anomalies = Vec::new();
+        for user_id in recipients.iter() {
+            let shared_pictures: Vec<GroupPicture> = Self::shared_pictures_statement(*user_id)
+                .load(conn)
+                .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+            let group_ids = Group::from_user_id_all(conn, *user_id)?.into_iter().map(|group| group.id).collect_vec();
+            let grouped_pictures: Vec<GroupPicture> = groups_pictures::table
+                .filter(groups_pictures::group_id.eq_any(&group_ids))
+                .select(GroupPicture::as_select())
+                .load(conn)
+                .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+
+            let picture_ids = grouped_pictures
+                .iter()
+                .map(|group_picture| group_picture.picture_id)
+                .unique()
+                .collect_vec();
+            let accessible_pictures = HashSet::from_iter(Picture::filter_user_accessible_pictures(conn, *user_id, &picture_ids)?);
+
+            let shared_picture_ids = shared_pictures
+                .iter()
+                .map(|group_picture| group_picture.picture_id)
+                .unique()
+                .collect_vec();
+            let (_, delta) = preview_transaction(conn, |conn, delta| {
+                group_pictures(conn, delta, *user_id, Some(&shared_picture_ids), None, None, false)
+            })?;
+            let missing_pictures = Self::missing_memberships(&delta.events(), &shared_picture_ids, &group_ids);
+            anomalies.extend(Self::find_anomalies(*user_id, &missing_pictures, &grouped_pictures, &accessible_pictures));
+        }
+        Ok(SharingConsistencyReport {
+            recipients_count: recipients.len(),
+            anomalies,
+        })
+    }
+
+    /// Build the statement listing the memberships of the groups whose share the user accepted, that are not pending deletion,
+    /// leaving out the pictures in the trash.
+    pub fn shared_pictures_statement(user_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, GroupPicture> {
+        groups_pictures::table
+            .inner_join(shared_groups::table.on(shared_groups::group_id.eq(groups_pictures::group_id)))
+            .inner_join(groups::table.on(groups::id.eq(groups_pictures::group_id)))
+            .inner_join(pictures::table.on(pictures::id.eq(groups_pictures::picture_id)))
+            .filter(shared_groups::user_id.eq(user_id))
+            .filter(shared_groups::confirmed.eq(true))
+            .filter(groups::to_be_deleted.eq(false))
+            .filter(pictures::deleted_date.is_null())
+            .select(GroupPicture::as_select())
+    }
+
+    /// Memberships of the shared pictures in the groups of the user (`group_ids`) that are missing,
+    /// from the net changes of grouping the shared pictures in the user's arrangements.
+    /// The changes of the groups of the other users, to which the grouping propagates, are left out.
+    pub fn missing_memberships(events: &[PictureGroupsEvent], shared_picture_ids: &[i64], group_ids: &[i32]) -> Vec<GroupPicture> {
+        events
+            .iter()
+            .filter(|event| shared_picture_ids.contains(&event.picture_id))
+            .flat_map(|event| {
+                event
+                    .added_groups
+                    .iter()
+                    .filter(|group_id| group_ids.contains(group_id))
+                    .map(|group_id| GroupPicture {
+                        group_id: *group_id,
+                        picture_id: event.picture_id,
+                    })
+            })
+            .collect()
+    }
+
+    /// Anomalies of a user, from the missing memberships of the pictures shared with them (see [`SharingConsistencyReport::missing_memberships`]),
+    /// the memberships of their own groups, and the pictures of their own groups they can access.
+    pub fn find_anomalies(
+        user_id: i32,
+        missing_pictures: &[GroupPicture],
+        grouped_pictures: &[GroupPicture],
+        accessible_pictures: &HashSet<i64>,
+    ) -> Vec<SharingAnomaly> {
+        let not_grouped = missing_pictures.iter().map(|group_picture| SharingAnomaly::SharedPictureNotGrouped {
+            user_id,
+            group_id: group_picture.group_id,
+            picture_id: group_picture.picture_id,
+        });
+        let stale = grouped_pictures
+            .iter()
+            .filter(|group_picture| !accessible_pictures.contains(&group_picture.picture_id))
+            .map(|group_picture| SharingAnomaly::UnaccessiblePictureGrouped {
+                user_id,
+                group_id: group_picture.group_id,
+                picture_id: group_picture.picture_id,
+            });
+        not_grouped.chain(stale).collect()
+    }
+}
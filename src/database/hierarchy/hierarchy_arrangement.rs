@@ -26,4 +26,12 @@ impl HierarchyArrangements {
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
+    /// Unset the parent group of the hierarchy arrangements whose parent is one of the groups.
+    pub fn clear_parent_group_ids(conn: &mut DBConn, group_ids: &Vec<i32>) -> Result<(), ErrorResponder> {
+        diesel::update(hierarchies_arrangements::table.filter(hierarchies_arrangements::parent_group_id.eq_any(group_ids)))
+            .set(hierarchies_arrangements::parent_group_id.eq(None::<i32>))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(())
+    }
 }
@@ -0,0 +1,184 @@
+use crate::database::database::DBConn;
+use crate::database::schema::*;
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::dsl::{exists, not};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use rocket_okapi::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Row referencing a parent that doesn't exist, or storage count that disagrees with the stored pictures.
+#[derive(JsonSchema, Serialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type")]
+pub enum IntegrityAnomaly {
+    /// A `groups_pictures` row references a missing group or picture.
+    DanglingGroupPicture { group_id: i32, picture_id: i64 },
+    /// A `pictures_tags` row references a missing picture or tag.
+    DanglingPictureTag { picture_id: i64, tag_id: i32 },
+    /// A `shared_groups` row references a missing user, group or match conversion group.
+    DanglingSharedGroup { user_id: i32, group_id: i32 },
+    /// A `ratings` row references a missing user or picture.
+    DanglingRating { user_id: i32, picture_id: i64 },
+    /// The storage count of the user differs from the total size of the pictures counted in their storage
+    /// (see [`IntegrityReport::pictures_sizes_ko`]).
+    StorageCountMismatch {
+        user_id: i32,
+        storage_count_ko: i64,
+        pictures_size_ko: i64,
+    },
+}
+
+/// Result of a scan of the whole database (see [`IntegrityReport::scan`]).
+#[derive(JsonSchema, Serialize, Debug, PartialEq)]
+pub struct IntegrityReport {
+    pub anomalies: Vec<IntegrityAnomaly>,
+}
+
+impl IntegrityReport {
+    /// Look for rows referencing non-existent parents and for storage counts that disagree with the stored pictures. Read-only.
+    pub fn scan(conn: &mut DBConn) -> Result<IntegrityReport, ErrorResponder> {
+        let mut anomalies = Vec::new();
+        let groups_pictures: Vec<(i32, i64)> = Self::dangling_groups_pictures_statement()
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        anomalies.extend(
+            groups_pictures
+                .into_iter()
+                .map(|(group_id, picture_id)| IntegrityAnomaly::DanglingGroupPicture { group_id, picture_id }),
+        );
+        let pictures_tags: Vec<(i64, i32)> = Self::dangling_pictures_tags_statement()
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        anomalies.extend(
+            pictures_tags
+                .into_iter()
+                .map(|(picture_id, tag_id)| IntegrityAnomaly::DanglingPictureTag { picture_id, tag_id }),
+        );
+        let shared_groups: Vec<(i32, i32)> = Self::dangling_shared_groups_statement()
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        anomalies.extend(
+            shared_groups
+                .into_iter()
+                .map(|(user_id, group_id)| IntegrityAnomaly::DanglingSharedGroup { user_id, group_id }),
+        );
+        let ratings: Vec<(i32, i64)> = Self::dangling_ratings_statement()
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        anomalies.extend(
+            ratings
+                .into_iter()
+                .map(|(user_id, picture_id)| IntegrityAnomaly::DanglingRating { user_id, picture_id }),
+        );
+
+        let users: Vec<(i32, i64)> = users::table
+            .select((users::id, users::storage_count_ko))
+            .order_by(users::id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        let user_ids: Vec<i32> = users.iter().map(|(user_id, _)| *user_id).collect();
+        let pictures_sizes_ko = Self::pictures_sizes_ko(conn, &user_ids)?;
+        anomalies.extend(Self::storage_anomalies(&users, &pictures_sizes_ko));
+
+        Ok(IntegrityReport { anomalies })
+    }
+
+    /// Size in Ko of the pictures counted in the storage of each of the users: the pictures they own that are not in the trash,
+    /// plus the pictures of the confirmed copied shares they received when these count toward their storage.
+    pub fn pictures_sizes_ko(conn: &mut DBConn, user_ids: &[i32]) -> Result<HashMap<i32, i64>, ErrorResponder> {
+        let owned: Vec<(i32, Option<i64>)> = Self::owned_pictures_size_statement(user_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        let mut pictures_sizes_ko: HashMap<i32, i64> = owned.into_iter().map(|(user_id, size)| (user_id, size.unwrap_or(0))).collect();
+        if CONFIG.copied_shares_count_storage {
+            let copied: Vec<(i32, i64, i32)> = Self::copied_shares_pictures_statement(user_ids)
+                .load(conn)
+                .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+            for (user_id, _, size_ko) in copied {
+                *pictures_sizes_ko.entry(user_id).or_default() += size_ko as i64;
+            }
+        }
+        Ok(pictures_sizes_ko)
+    }
+
+    /// Users whose storage count, from the `(user_id, storage_count_ko)` rows, differs from the size of their pictures.
+    pub fn storage_anomalies(users: &[(i32, i64)], pictures_sizes_ko: &HashMap<i32, i64>) -> Vec<IntegrityAnomaly> {
+        users
+            .iter()
+            .filter_map(|(user_id, storage_count_ko)| {
+                let pictures_size_ko = pictures_sizes_ko.get(user_id).cloned().unwrap_or(0);
+                (pictures_size_ko != *storage_count_ko).then_some(IntegrityAnomaly::StorageCountMismatch {
+                    user_id: *user_id,
+                    storage_count_ko: *storage_count_ko,
+                    pictures_size_ko,
+                })
+            })
+            .collect()
+    }
+
+    pub fn dangling_groups_pictures_statement() -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i64)> {
+        groups_pictures::table
+            .filter(
+                not(exists(groups::table.filter(groups::id.eq(groups_pictures::group_id))))
+                    .or(not(exists(pictures::table.filter(pictures::id.eq(groups_pictures::picture_id))))),
+            )
+            .select((groups_pictures::group_id, groups_pictures::picture_id))
+            .order_by((groups_pictures::group_id, groups_pictures::picture_id))
+    }
+    pub fn dangling_pictures_tags_statement() -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i64, i32)> {
+        pictures_tags::table
+            .filter(
+                not(exists(pictures::table.filter(pictures::id.eq(pictures_tags::picture_id))))
+                    .or(not(exists(tags::table.filter(tags::id.eq(pictures_tags::tag_id))))),
+            )
+            .select((pictures_tags::picture_id, pictures_tags::tag_id))
+            .order_by((pictures_tags::picture_id, pictures_tags::tag_id))
+    }
+    pub fn dangling_shared_groups_statement() -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i32)> {
+        shared_groups::table
+            .filter(
+                not(exists(users::table.filter(users::id.eq(shared_groups::user_id))))
+                    .or(not(exists(groups::table.filter(groups::id.eq(shared_groups::group_id)))))
+                    .or(shared_groups::match_conversion_group_id.is_not_null().and(not(exists(
+                        groups::table.filter(groups::id.nullable().eq(shared_groups::match_conversion_group_id)),
+                    )))),
+            )
+            .select((shared_groups::user_id, shared_groups::group_id))
+            .order_by((shared_groups::user_id, shared_groups::group_id))
+    }
+    pub fn dangling_ratings_statement() -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i64)> {
+        ratings::table
+            .filter(
+                not(exists(users::table.filter(users::id.eq(ratings::user_id))))
+                    .or(not(exists(pictures::table.filter(pictures::id.eq(ratings::picture_id))))),
+            )
+            .select((ratings::user_id, ratings::picture_id))
+            .order_by((ratings::user_id, ratings::picture_id))
+    }
+    /// Total size of the pictures owned by each of the users out of the trash, as `(user_id, size_ko)` rows.
+    pub fn owned_pictures_size_statement(user_ids: &[i32]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, Option<i64>)> {
+        pictures::table
+            .filter(pictures::owner_id.eq_any(user_ids.to_vec()))
+            .filter(pictures::deleted_date.is_null())
+            .group_by(pictures::owner_id)
+            .select((pictures::owner_id, diesel::dsl::sum(pictures::size_ko)))
+    }
+    /// Pictures out of the trash the users access through confirmed copied shares, that they don't own,
+    /// as `(user_id, picture_id, size_ko)` rows.
+    pub fn copied_shares_pictures_statement(user_ids: &[i32]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i64, i32)> {
+        groups_pictures::table
+            .inner_join(shared_groups::table.on(shared_groups::group_id.eq(groups_pictures::group_id)))
+            .inner_join(pictures::table.on(pictures::id.eq(groups_pictures::picture_id)))
+            .filter(shared_groups::user_id.eq_any(user_ids.to_vec()))
+            .filter(shared_groups::copied.eq(true))
+            .filter(shared_groups::confirmed.eq(true))
+            .filter(pictures::owner_id.ne(shared_groups::user_id))
+            .filter(pictures::deleted_date.is_null())
+            .select((shared_groups::user_id, pictures::id, pictures::size_ko))
+            .distinct()
+    }
+}
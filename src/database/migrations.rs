@@ -0,0 +1,57 @@
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::migration::MigrationSource;
+use diesel::pg::Pg;
+use diesel::PgConnection;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// State of the database migrations: the applied versions and the ones still pending, sorted in ascending order.
+#[derive(JsonSchema, Serialize, Debug, PartialEq)]
+pub struct MigrationsStatus {
+    pub applied_versions: Vec<String>,
+    pub pending_versions: Vec<String>,
+    pub has_pending: bool,
+}
+
+impl MigrationsStatus {
+    /// Compare the migrations applied by the harness with the migrations of the source.
+    pub fn from_harness<H, S>(harness: &mut H, source: S) -> Result<MigrationsStatus, ErrorResponder>
+    where
+        H: MigrationHarness<Pg>,
+        S: MigrationSource<Pg>,
+    {
+        let mut applied_versions: Vec<String> = harness
+            .applied_migrations()
+            .map_err(|e| ErrorType::InternalError(format!("Failed to list the applied migrations: {}", e)).res())?
+            .iter()
+            .map(|version| version.to_string())
+            .collect();
+        applied_versions.sort();
+        let pending_versions: Vec<String> = harness
+            .pending_migrations(source)
+            .map_err(|e| ErrorType::InternalError(format!("Failed to list the pending migrations: {}", e)).res())?
+            .iter()
+            .map(|migration| migration.name().version().to_string())
+            .collect();
+        Ok(MigrationsStatus {
+            applied_versions,
+            has_pending: !pending_versions.is_empty(),
+            pending_versions,
+        })
+    }
+}
+
+/// Run the pending migrations at boot.
+/// If a migration fails, the server can't run on the database: the error is logged and the process exits.
+pub fn run_boot_migrations(conn: &mut PgConnection) {
+    match conn.run_pending_migrations(MIGRATIONS) {
+        Ok(versions) => info!("Applied {} pending migrations: {:?}", versions.len(), versions),
+        Err(e) => {
+            error!("Failed to run the database migrations, the server can't start: {}", e);
+            ::std::process::exit(1);
+        }
+    }
+}
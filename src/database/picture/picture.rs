@@ -1,4 +1,4 @@
-use crate::api::picture::ListPictureData;
+use crate::api::picture::{ListPictureData, ListPictureRow};
 use crate::api::query_pictures::{PictureFilter, PictureSort, PicturesQuery};
 use crate::database::database::DBConn;
 use crate::database::picture::picture_tag::PictureTag;
@@ -7,23 +7,66 @@ use crate::database::schema::PictureOrientation;
 use crate::database::schema::*;
 use crate::database::tag::tag::Tag;
 use crate::database::user::user::User;
+use crate::grouping::strategy_filtering::StrategyFiltering;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use bigdecimal::BigDecimal;
-use chrono::NaiveDateTime;
-use diesel::dsl::{exists, insert_into, not, Filter, Nullable};
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use diesel::dsl::{count_star, exists, insert_into, not, AsSelect, Filter, Nullable, SqlTypeOf};
 use diesel::helper_types::{IntoBoxed, LeftJoin, LeftJoinOn, LeftJoinQuerySource, Or};
 use diesel::internal::table_macro::{BoxedSelectStatement, FromClause, Join, JoinOn, LeftOuter, SelectStatement};
+use diesel::pg::Pg;
 use diesel::query_builder::QueryFragment;
 use diesel::query_dsl::InternalJoinDsl;
+use diesel::query_dsl::LoadQuery;
 use diesel::sql_types::{BigInt, Binary, Bool, Decimal, Integer, SmallInt, Text, TinyInt, VarChar, Varchar};
 use diesel::QueryDsl;
-use diesel::{Associations, Identifiable, Queryable, RunQueryDsl, Selectable};
+use diesel::{Associations, BoxableExpression, Identifiable, Queryable, RunQueryDsl, Selectable};
 use diesel::{BoolExpressionMethods, ExpressionMethods};
 use diesel::{JoinOnDsl, NullableExpressionMethods, OptionalExtension, SelectableHelper};
 use diesel_derives::Insertable;
 use rocket::serde::json::Json;
 use rocket_okapi::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Boxed statement selecting pictures, that can be filtered, sorted and paginated further
+pub type PicturesStatement = pictures::BoxedQuery<'static, Pg, SqlTypeOf<AsSelect<Picture, Pg>>>;
+type PicturesPredicate = Box<dyn BoxableExpression<pictures::table, Pg, SqlType = Bool>>;
+
+/// Column ordering the pictures of a query (see [`Picture::query_order`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PictureOrderColumn {
+    CreationDate,
+    EditionDate,
+    Id,
+}
+impl PictureOrderColumn {
+    /// Pictures whose value of the column is greater than the one of `picture`, or lower if not `greater`.
+    fn compare(self, picture: &Picture, greater: bool) -> PicturesPredicate {
+        match (self, greater) {
+            (PictureOrderColumn::CreationDate, true) => Box::new(pictures::dsl::creation_date.gt(picture.creation_date)),
+            (PictureOrderColumn::CreationDate, false) => Box::new(pictures::dsl::creation_date.lt(picture.creation_date)),
+            (PictureOrderColumn::EditionDate, true) => Box::new(pictures::dsl::edition_date.gt(picture.edition_date)),
+            (PictureOrderColumn::EditionDate, false) => Box::new(pictures::dsl::edition_date.lt(picture.edition_date)),
+            (PictureOrderColumn::Id, true) => Box::new(pictures::dsl::id.gt(picture.id)),
+            (PictureOrderColumn::Id, false) => Box::new(pictures::dsl::id.lt(picture.id)),
+        }
+    }
+    fn equal(self, picture: &Picture) -> PicturesPredicate {
+        match self {
+            PictureOrderColumn::CreationDate => Box::new(pictures::dsl::creation_date.eq(picture.creation_date)),
+            PictureOrderColumn::EditionDate => Box::new(pictures::dsl::edition_date.eq(picture.edition_date)),
+            PictureOrderColumn::Id => Box::new(pictures::dsl::id.eq(picture.id)),
+        }
+    }
+}
+
+/// Pictures right before and after a picture in the results of a query (see [`Picture::neighbors_in_query`]).
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+pub struct PictureNeighbors {
+    pub previous_id: Option<i64>,
+    pub next_id: Option<i64>,
+}
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Insertable, JsonSchema, Serialize, Debug, PartialEq, Clone)]
 #[diesel(primary_key(id))]
@@ -58,6 +101,11 @@ pub struct Picture {
     pub f_number: Option<BigDecimal>,
     pub size_ko: i32,
     pub blurhash: Option<String>,
+    /// Format of the original picture, detected at upload (see [`crate::utils::picture_format::sniff_picture_format`]).
+    /// None for the pictures uploaded before it was stored, served as JPEG.
+    pub format: Option<String>,
+    /// Favorite flag, set by the owner of the picture
+    pub favorite: bool,
 }
 #[derive(Debug, PartialEq, JsonSchema, Serialize)]
 pub struct PictureDetails {
@@ -65,6 +113,80 @@ pub struct PictureDetails {
     pub tags_ids: Vec<i32>,
     pub ratings: Vec<Rating>,
 }
+/// Sections of [`PictureDetails`] to compute, from the comma separated `fields` query parameter.
+#[derive(Debug, PartialEq)]
+pub struct PictureDetailsFields {
+    pub picture: bool,
+    pub tags: bool,
+    pub ratings: bool,
+}
+impl Default for PictureDetailsFields {
+    fn default() -> Self {
+        PictureDetailsFields {
+            picture: true,
+            tags: true,
+            ratings: true,
+        }
+    }
+}
+impl PictureDetailsFields {
+    /// All the sections are computed when no field is given.
+    pub fn parse(fields: Option<&str>) -> Result<Self, ErrorResponder> {
+        let values: Vec<&str> = fields
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect();
+        if values.is_empty() {
+            return Ok(PictureDetailsFields::default());
+        }
+        let mut details_fields = PictureDetailsFields {
+            picture: false,
+            tags: false,
+            ratings: false,
+        };
+        for value in values {
+            match value {
+                "picture" => details_fields.picture = true,
+                "tags" => details_fields.tags = true,
+                "ratings" => details_fields.ratings = true,
+                _ => return ErrorType::InvalidInput(format!("Unknown picture details field: {}", value)).res_err_no_rollback(),
+            }
+        }
+        Ok(details_fields)
+    }
+}
+/// [`PictureDetails`] with only the requested sections (see [`PictureDetailsFields`]), the other ones are not serialized.
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct PictureDetailsSelection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture: Option<Picture>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags_ids: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ratings: Option<Vec<Rating>>,
+}
+
+/// Queries of the sections of the details of a picture (see [`Picture::get_picture_details_selection`]).
+pub trait PictureDetailsSource {
+    /// The picture if the user can access it.
+    fn accessible_picture(&mut self, user_id: i32, picture_id: i64) -> Result<Option<Picture>, ErrorResponder>;
+    fn tags(&mut self, user_id: i32, picture_id: i64) -> Result<Vec<i32>, ErrorResponder>;
+    fn ratings_including_friends(&mut self, user_id: i32, picture_id: i64) -> Result<Vec<Rating>, ErrorResponder>;
+}
+impl PictureDetailsSource for DBConn {
+    fn accessible_picture(&mut self, user_id: i32, picture_id: i64) -> Result<Option<Picture>, ErrorResponder> {
+        Ok(Picture::get_pictures_details(self, user_id, vec![picture_id])?.pop())
+    }
+    fn tags(&mut self, user_id: i32, picture_id: i64) -> Result<Vec<i32>, ErrorResponder> {
+        PictureTag::get_picture_tags(self, picture_id, user_id)
+    }
+    fn ratings_including_friends(&mut self, user_id: i32, picture_id: i64) -> Result<Vec<Rating>, ErrorResponder> {
+        Rating::from_picture_id_including_friends(self, picture_id, user_id)
+    }
+}
+
 /// The first Option is None if value is mixed
 #[derive(Debug, PartialEq, JsonSchema, Serialize)]
 pub struct MixedPicture {
@@ -81,6 +203,8 @@ pub struct MixedPicture {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub copied: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub favorite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub creation_date: Option<NaiveDateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub edition_date: Option<NaiveDateTime>,
@@ -122,26 +246,546 @@ pub struct MixedPictureDetails {
     pub rating_users: Vec<i32>,             // List of friends user IDs that rated the picture
 }
 
+/// Total size of a selection of pictures, and the number of pictures counted.
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct PicturesTotalSize {
+    pub total_size_ko: i64,
+    pub count: i64,
+}
+/// Built from the `(SUM(size_ko), COUNT(*))` row, the sum being NULL when no picture is counted.
+impl From<(Option<i64>, i64)> for PicturesTotalSize {
+    fn from((total_size_ko, count): (Option<i64>, i64)) -> Self {
+        PicturesTotalSize {
+            total_size_ko: total_size_ko.unwrap_or(0),
+            count,
+        }
+    }
+}
+
+/// EXIF column over which a histogram of pictures can be computed (see [`Picture::exif_histogram`]).
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Deserialize, Serialize)]
+pub enum ExifField {
+    Altitude,
+    Orientation,
+    Width,
+    Height,
+    CameraBrand,
+    CameraModel,
+    FocalLength,
+    ExposureTime,
+    IsoSpeed,
+    FNumber,
+}
+impl ExifField {
+    /// SQL expression of the field value as text, NULL when the picture has no value.
+    /// Exposure times are formatted as `num/den`.
+    pub fn text_expression(&self) -> &'static str {
+        match self {
+            ExifField::Altitude => "\"pictures\".\"altitude\"::text",
+            ExifField::Orientation => "\"pictures\".\"orientation\"::text",
+            ExifField::Width => "\"pictures\".\"width\"::text",
+            ExifField::Height => "\"pictures\".\"height\"::text",
+            ExifField::CameraBrand => "\"pictures\".\"camera_brand\"::text",
+            ExifField::CameraModel => "\"pictures\".\"camera_model\"::text",
+            ExifField::FocalLength => "\"pictures\".\"focal_length\"::text",
+            ExifField::ExposureTime => "(\"pictures\".\"exposure_time_num\" || '/' || \"pictures\".\"exposure_time_den\")",
+            ExifField::IsoSpeed => "\"pictures\".\"iso_speed\"::text",
+            ExifField::FNumber => "\"pictures\".\"f_number\"::text",
+        }
+    }
+}
+/// Number of pictures having a value of an EXIF field, the pictures without value being counted in the `None` bucket.
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct ExifHistogramBucket {
+    pub value: Option<String>,
+    pub count: i64,
+}
+impl ExifHistogramBucket {
+    /// Buckets from the `(value, COUNT(*))` rows, most frequent values first, then by value, the `None` bucket last among equals.
+    pub fn from_rows(rows: Vec<(Option<String>, i64)>) -> Vec<ExifHistogramBucket> {
+        let mut buckets: Vec<ExifHistogramBucket> = rows.into_iter().map(|(value, count)| ExifHistogramBucket { value, count }).collect();
+        buckets.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.value.is_none().cmp(&b.value.is_none()))
+                .then_with(|| a.value.cmp(&b.value))
+        });
+        buckets
+    }
+}
+
+/// Number of pictures having a tag, among a set of pictures.
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct TagFacet {
+    pub tag_id: i32,
+    pub count: i64,
+}
+/// Built from the `(tag_id, COUNT(*))` row.
+impl From<(i32, i64)> for TagFacet {
+    fn from((tag_id, count): (i32, i64)) -> Self {
+        TagFacet { tag_id, count }
+    }
+}
+
+/// Number of pictures created on a day (see [`Picture::calendar_counts`]).
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct CalendarDay {
+    pub date: NaiveDate,
+    pub count: i64,
+}
+/// Built from the `(DATE(creation_date), COUNT(*))` row.
+impl From<(NaiveDate, i64)> for CalendarDay {
+    fn from((date, count): (NaiveDate, i64)) -> Self {
+        CalendarDay { date, count }
+    }
+}
+
+/// Change of a picture, for clients synchronizing their copy of the pictures (see [`Picture::changes_since`]).
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct PictureChange {
+    pub picture_id: i64,
+    /// Last change of the picture, its tags, ratings or groups
+    pub modified_at: NaiveDateTime,
+    /// The picture is in the trash, and must be removed by the client
+    pub deleted: bool,
+}
+/// Built from the `(id, modified_at, deleted_date)` row.
+impl From<(i64, NaiveDateTime, Option<NaiveDateTime>)> for PictureChange {
+    fn from((picture_id, modified_at, deleted_date): (i64, NaiveDateTime, Option<NaiveDateTime>)) -> Self {
+        PictureChange {
+            picture_id,
+            modified_at,
+            deleted: deleted_date.is_some(),
+        }
+    }
+}
+
+/// Number of bytes of a link share token disclosed in a visibility audit, enough to recognize the link without being able to use it.
+pub const LINK_TOKEN_PREFIX_LEN: usize = 4;
+
+/// User who can see a picture through a group shared with them.
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct PictureShare {
+    pub user_id: i32,
+    pub via_group_id: i32,
+    /// The user accepted the share
+    pub confirmed: bool,
+}
+/// Built from the `(user_id, group_id, confirmed)` row of `shared_groups`.
+impl From<(i32, i32, bool)> for PictureShare {
+    fn from((user_id, via_group_id, confirmed): (i32, i32, bool)) -> Self {
+        PictureShare {
+            user_id,
+            via_group_id,
+            confirmed,
+        }
+    }
+}
+/// Everyone who can see a picture (see [`Picture::visibility_audit`]).
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct PictureVisibility {
+    pub owner_id: i32,
+    pub shared_with: Vec<PictureShare>,
+    /// Hex prefixes of the tokens of the public links giving access to the picture
+    pub public_links: Vec<String>,
+}
+impl PictureVisibility {
+    pub fn new(owner_id: i32, shares: Vec<(i32, i32, bool)>, link_tokens: Vec<Vec<u8>>) -> Self {
+        PictureVisibility {
+            owner_id,
+            shared_with: shares.into_iter().map(PictureShare::from).collect(),
+            public_links: link_tokens
+                .iter()
+                .map(|token| hex::encode(&token[..token.len().min(LINK_TOKEN_PREFIX_LEN)]))
+                .collect(),
+        }
+    }
+}
+
+/// Access of the caller to a picture, as checked by the picture streaming endpoint.
+#[derive(Debug, PartialEq, JsonSchema, Serialize)]
+pub struct PictureAccess {
+    pub accessible: bool,      // Whether the picture can be fetched by the caller
+    pub owned: bool,           // Whether the caller owns the picture, always false for anonymous callers
+    pub publicly_shared: bool, // Whether the picture is in a publicly shared group
+}
+impl PictureAccess {
+    /// Access of a logged-in user: only owned pictures or pictures in groups shared with the user are accessible.
+    pub fn for_user(owned: bool, shared_with_user: bool, publicly_shared: bool) -> Self {
+        Self {
+            accessible: owned || shared_with_user,
+            owned,
+            publicly_shared,
+        }
+    }
+    /// Access of an anonymous caller: only publicly shared pictures are accessible.
+    pub fn anonymous(publicly_shared: bool) -> Self {
+        Self {
+            accessible: publicly_shared,
+            owned: false,
+            publicly_shared,
+        }
+    }
+}
+
+/// Maximum Hamming distance between the perceptual hashes of two pictures for them to be reported as near-duplicates
+pub const SIMILAR_PICTURES_MAX_DISTANCE: i32 = 6;
+/// Maximum number of near-duplicates reported for a picture
+pub const SIMILAR_PICTURES_MAX: i64 = 20;
+
 impl Picture {
     /// Get a list of pictures based on the query. This function guaranties that the user has the right to access the requested pictures.
     pub fn query(conn: &mut DBConn, user_id: i32, query: PicturesQuery, page_size: i64) -> Result<Vec<ListPictureData>, ErrorResponder> {
+        Self::load_list_data(conn, Self::query_statement(user_id, query, page_size))
+    }
+
+    /// Get the ids of all the pictures matching the query that the user can access, by ascending id.
+    /// The sorting and the page of the query are ignored.
+    /// Returns UnprocessableEntity if more than `max_ids` pictures match, rather than an incomplete set.
+    pub fn query_ids(conn: &mut DBConn, user_id: i32, query: PicturesQuery, max_ids: i64) -> Result<Vec<i64>, ErrorResponder> {
+        let picture_ids = Self::query_ids_statement(user_id, query, max_ids + 1)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the pictures ids".to_string(), e).res())?;
+        Self::check_query_ids_count(picture_ids, max_ids)
+    }
+    /// Build the statement selecting the ids of the pictures of the query, at most `limit` (see [`Picture::query_ids`]).
+    pub fn query_ids_statement(user_id: i32, query: PicturesQuery, limit: i64) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        Self::filtered_statement(user_id, query.filters, query.filter_tree)
+            .select(pictures::dsl::id)
+            .order(pictures::dsl::id.asc())
+            .limit(limit)
+    }
+    /// Returns UnprocessableEntity if there are more than `max_ids` ids.
+    pub fn check_query_ids_count(picture_ids: Vec<i64>, max_ids: i64) -> Result<Vec<i64>, ErrorResponder> {
+        if picture_ids.len() as i64 > max_ids {
+            return ErrorType::UnprocessableEntity(format!("More than {} pictures match the query", max_ids)).res_err();
+        }
+        Ok(picture_ids)
+    }
+
+    /// Restore from the trash all the pictures owned by the user matching the query, by batches of `batch_size` pictures,
+    /// and add their size back to the storage usage of the user. The sorting and the page of the query are ignored.
+    /// Returns the ids of the restored pictures, by ascending id. Regrouping them is left to the caller.
+    pub fn restore_by_query(conn: &mut DBConn, user_id: i32, query: &PicturesQuery, batch_size: i64) -> Result<Vec<i64>, ErrorResponder> {
+        let mut restored_ids = Vec::new();
+        loop {
+            let after_id = restored_ids.last().copied().unwrap_or(0);
+            let mut batch: Vec<(i64, i32)> = Self::restore_batch_statement(user_id, query.clone(), after_id, batch_size)
+                .load(conn)
+                .map_err(|e| ErrorType::DatabaseError("Failed to restore pictures".to_string(), e).res())?;
+            batch.sort_unstable();
+            User::add_storage_count(conn, user_id, batch.iter().map(|(_, size_ko)| *size_ko as i64).sum())?;
+            let is_last_batch = (batch.len() as i64) < batch_size;
+            restored_ids.extend(batch.into_iter().map(|(picture_id, _)| picture_id));
+            if is_last_batch {
+                return Ok(restored_ids);
+            }
+        }
+    }
+    /// Build the statement restoring the next batch of at most `limit` pictures of the query, owned by the user, in the trash,
+    /// and with an id greater than `after_id` (see [`Picture::restore_by_query`]). Returns the `(id, size_ko)` of the restored pictures.
+    pub fn restore_batch_statement(
+        user_id: i32,
+        query: PicturesQuery,
+        after_id: i64,
+        limit: i64,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i64, i32)> {
+        let picture_ids = Self::filtered_statement(user_id, query.filters, query.filter_tree)
+            .filter(pictures::dsl::owner_id.eq(user_id))
+            .filter(pictures::dsl::deleted_date.is_not_null())
+            .filter(pictures::dsl::id.gt(after_id))
+            .select(pictures::dsl::id)
+            .order(pictures::dsl::id.asc())
+            .limit(limit);
+        diesel::update(pictures::table.filter(pictures::dsl::id.eq_any(picture_ids)))
+            .set((
+                pictures::dsl::deleted_date.eq(None::<NaiveDateTime>),
+                pictures::dsl::modified_at.eq(Utc::now().naive_utc()),
+            ))
+            .returning((pictures::dsl::id, pictures::dsl::size_ko))
+    }
+
+    /// Mark the pictures as modified, so that the clients synchronizing from [`Picture::changes_since`] fetch them again.
+    /// Called on every change of their tags, ratings or groups, the changes of the pictures themselves setting it directly.
+    pub fn touch(conn: &mut DBConn, picture_ids: &[i64]) -> Result<(), ErrorResponder> {
+        if picture_ids.is_empty() {
+            return Ok(());
+        }
+        diesel::update(pictures::table.filter(pictures::dsl::id.eq_any(picture_ids)))
+            .set(pictures::dsl::modified_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to update the pictures modification date".to_string(), e).res())?;
+        Ok(())
+    }
+
+    /// Store the perceptual hash of a picture (see [`crate::utils::thumbnail::perceptual_hash`]).
+    pub fn set_perceptual_hash(conn: &mut DBConn, picture_id: i64, perceptual_hash: i64) -> Result<(), ErrorResponder> {
+        diesel::update(pictures::table.filter(pictures::dsl::id.eq(picture_id)))
+            .set(pictures::dsl::perceptual_hash.eq(perceptual_hash))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to store the perceptual hash".to_string(), e).res())?;
+        Ok(())
+    }
+    /// Get the ids of at most `limit` pictures without perceptual hash, with an id greater than `after_id`, by ascending id.
+    /// The pictures uploaded before the perceptual hashes were computed have none (see [`crate::api::admin::admin::backfill_perceptual_hashes`]).
+    pub fn without_perceptual_hash(conn: &mut DBConn, after_id: i64, limit: i64) -> Result<Vec<i64>, ErrorResponder> {
+        Self::without_perceptual_hash_statement(after_id, limit)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the pictures without perceptual hash".to_string(), e).res())
+    }
+    pub fn without_perceptual_hash_statement(after_id: i64, limit: i64) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        pictures::table
+            .filter(pictures::dsl::perceptual_hash.is_null())
+            .filter(pictures::dsl::id.gt(after_id))
+            .select(pictures::dsl::id)
+            .order(pictures::dsl::id.asc())
+            .limit(limit)
+    }
+    /// Get the pictures of the user, out of the trash, that are near-duplicates of a picture from its perceptual hash
+    /// (see [`crate::utils::thumbnail::perceptual_hash`]), most similar first. The picture itself is excluded.
+    pub fn similar_pictures(conn: &mut DBConn, user_id: i32, picture_id: i64, perceptual_hash: i64) -> Result<Vec<i64>, ErrorResponder> {
+        Self::similar_pictures_statement(user_id, picture_id, perceptual_hash)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the similar pictures".to_string(), e).res())
+    }
+    /// Build the statement selecting at most [`SIMILAR_PICTURES_MAX`] near-duplicates of a picture (see [`Picture::similar_pictures`]).
+    /// The Hamming distance between the hashes is the number of `1` bits of their XOR.
+    pub fn similar_pictures_statement(
+        user_id: i32,
+        picture_id: i64,
+        perceptual_hash: i64,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        let distance = || {
+            diesel::dsl::sql::<Integer>("length(replace(((\"pictures\".\"perceptual_hash\" # ")
+                .bind::<BigInt, _>(perceptual_hash)
+                .sql(")::bit(64))::text, '0', ''))")
+        };
+        pictures::table
+            .filter(pictures::dsl::owner_id.eq(user_id))
+            .filter(pictures::dsl::id.ne(picture_id))
+            .filter(pictures::dsl::deleted_date.is_null())
+            .filter(pictures::dsl::perceptual_hash.is_not_null())
+            .filter(distance().le(SIMILAR_PICTURES_MAX_DISTANCE))
+            .select(pictures::dsl::id)
+            .order((distance().asc(), pictures::dsl::id.asc()))
+            .limit(SIMILAR_PICTURES_MAX)
+    }
+
+    /// Get a page of the pictures owned by the user that are not in any of their groups, whatever the arrangement.
+    /// This reflects the actual group membership: pictures caught by a manual group or an "Other" group are not listed.
+    /// Pictures in the trash are not listed.
+    pub fn query_ungrouped(conn: &mut DBConn, user_id: i32, page: i32, page_size: i64) -> Result<Vec<ListPictureData>, ErrorResponder> {
+        Self::load_list_data(conn, Self::query_ungrouped_statement(user_id, page, page_size))
+    }
+    /// Build the boxed statement selecting a page of the user's ungrouped pictures, most recent first (see [`Picture::query_ungrouped`]).
+    pub fn query_ungrouped_statement(user_id: i32, page: i32, page_size: i64) -> PicturesStatement {
+        assert_ne!(page, 0, "Page number must be greater than 0");
+        pictures::table
+            .filter(pictures::dsl::owner_id.eq(user_id))
+            .filter(pictures::dsl::deleted_date.is_null())
+            // Only the groups of the user's arrangements: a picture grouped by a recipient it is shared with is still ungrouped
+            .filter(not(exists(
+                groups_pictures::table
+                    .inner_join(groups::table.on(groups::dsl::id.eq(groups_pictures::dsl::group_id)))
+                    .inner_join(arrangements::table.on(arrangements::dsl::id.eq(groups::dsl::arrangement_id)))
+                    .filter(groups_pictures::dsl::picture_id.eq(pictures::dsl::id))
+                    .filter(arrangements::dsl::user_id.eq(user_id))
+                    .select(groups_pictures::dsl::picture_id),
+            )))
+            .select(Picture::as_select())
+            .order((pictures::dsl::creation_date.desc(), pictures::dsl::id.desc()))
+            .limit(page_size)
+            .offset((page - 1) as i64 * page_size)
+            .into_boxed()
+    }
+
+    /// Get the pictures of a group, most recent first. The access to the group must be checked by the caller.
+    pub fn from_group(conn: &mut DBConn, group_id: i32) -> Result<Vec<ListPictureData>, ErrorResponder> {
+        Self::load_list_data(conn, Self::from_group_statement(group_id))
+    }
+    /// Build the boxed statement selecting the pictures of a group, most recent first (see [`Picture::from_group`]).
+    pub fn from_group_statement(group_id: i32) -> PicturesStatement {
+        pictures::table
+            .filter(exists(
+                groups_pictures::table
+                    .filter(groups_pictures::dsl::picture_id.eq(pictures::dsl::id))
+                    .filter(groups_pictures::dsl::group_id.eq(group_id)),
+            ))
+            .select(Picture::as_select())
+            .order((pictures::dsl::creation_date.desc(), pictures::dsl::id.desc()))
+            .into_boxed()
+    }
+
+    /// Get the pictures of the list that the user can access, in no particular order
+    /// (see [`ListPictureData::in_requested_order`] to get them in the order of the list).
+    pub fn from_ids_accessible(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<ListPictureData>, ErrorResponder> {
+        Self::load_list_data(conn, Self::from_ids_accessible_statement(user_id, picture_ids))
+    }
+    /// Build the boxed statement selecting the pictures of the list that the user can access (see [`Picture::from_ids_accessible`]).
+    /// Visibility is checked with a subquery, so that a picture shared in several groups is selected once.
+    pub fn from_ids_accessible_statement(user_id: i32, picture_ids: &[i64]) -> PicturesStatement {
+        pictures::table
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
+            .filter(
+                pictures::dsl::owner_id.eq(user_id).or(Self::shared_with_predicate(user_id)),
+            )
+            .select(Picture::as_select())
+            .into_boxed()
+    }
+
+    /// Predicate matching the pictures of a group shared with the user.
+    /// Groups pending deletion are skipped, so that recipients don't see them while they are being removed.
+    fn shared_with_predicate(user_id: i32) -> PicturesPredicate {
+        Box::new(exists(
+            groups_pictures::table
+                .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
+                .inner_join(groups::table.on(groups::dsl::id.eq(groups_pictures::dsl::group_id)))
+                .filter(groups_pictures::dsl::picture_id.eq(pictures::dsl::id))
+                .filter(shared_groups::dsl::user_id.eq(user_id))
+                .filter(groups::dsl::to_be_deleted.eq(false)),
+        ))
+    }
+    /// Predicate matching the pictures of a group shared with the user, including the groups pending deletion.
+    /// Used by the access checks, so that grouping propagation still sees a group until it is actually deleted.
+    fn shared_access_predicate(user_id: i32) -> PicturesPredicate {
+        Box::new(exists(
+            groups_pictures::table
+                .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
+                .filter(groups_pictures::dsl::picture_id.eq(pictures::dsl::id))
+                .filter(shared_groups::dsl::user_id.eq(user_id)),
+        ))
+    }
+
+    /// Fetch the pictures of the statement as [`ListPictureData`]
+    fn load_list_data(conn: &mut DBConn, dsl_query: PicturesStatement) -> Result<Vec<ListPictureData>, ErrorResponder> {
+        let pictures: Vec<ListPictureData> = dsl_query
+            .select((
+                pictures::id,
+                pictures::name,
+                pictures::width,
+                pictures::height,
+                pictures::size_ko,
+                pictures::creation_date,
+                pictures::edition_date,
+                pictures::blurhash,
+            ))
+            .distinct()
+            .load::<ListPictureRow>(conn)
+            .map(|vec| vec.into_iter().map(ListPictureData::from).collect())
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures".to_string(), e).res())?;
+
+        Ok(pictures)
+    }
+
+    /// Build the boxed statement selecting the pictures matching the query, restricted to the pictures the user can access.
+    /// With a keyset ([`PicturesQuery::after`]), the page is made of the pictures following it, the page being ignored.
+    /// The sorts of the query must be checked with [`PicturesQuery::check_keyset_sorts`].
+    pub fn query_statement(user_id: i32, query: PicturesQuery, page_size: i64) -> PicturesStatement {
+        let mut dsl_query = Self::filtered_statement(user_id, query.filters, query.filter_tree);
+
+        // Applying keyset pagination, in the order of the first page: creation date descending, then id ascending
+        if let Some(after) = query.after {
+            dsl_query = dsl_query.filter(
+                pictures::dsl::creation_date
+                    .lt(after.creation_date)
+                    .or(pictures::dsl::creation_date.eq(after.creation_date).and(pictures::dsl::id.gt(after.id))),
+            );
+            return Self::ordered_statement(dsl_query, &Self::query_order(&query.sorts), false).limit(page_size);
+        }
         assert_ne!(query.page, 0, "Page number must be greater than 0");
 
+        // Applying sorting
+        dsl_query = Self::ordered_statement(dsl_query, &Self::query_order(&query.sorts), false);
+
+        // Applying pagination
+        dsl_query = dsl_query.limit(page_size).offset((query.page - 1) as i64 * page_size);
+
+        dsl_query
+    }
+
+    /// Columns ordering the pictures of a query, with whether they are ascending: the sorts of the query in order,
+    /// then the id so that pictures with the same sort values keep a stable order.
+    pub fn query_order(sorts: &[PictureSort]) -> Vec<(PictureOrderColumn, bool)> {
+        sorts
+            .iter()
+            .map(|sort| match sort {
+                PictureSort::CreationDate { ascend } => (PictureOrderColumn::CreationDate, *ascend),
+                PictureSort::EditionDate { ascend } => (PictureOrderColumn::EditionDate, *ascend),
+            })
+            .chain([(PictureOrderColumn::Id, true)])
+            .collect()
+    }
+    /// Order the statement by the columns of `order`, or in the opposite direction if `reverse` is set.
+    fn ordered_statement(mut dsl_query: PicturesStatement, order: &[(PictureOrderColumn, bool)], reverse: bool) -> PicturesStatement {
+        for (column, ascend) in order.iter() {
+            dsl_query = match (column, *ascend != reverse) {
+                (PictureOrderColumn::CreationDate, true) => dsl_query.then_order_by(pictures::dsl::creation_date.asc()),
+                (PictureOrderColumn::CreationDate, false) => dsl_query.then_order_by(pictures::dsl::creation_date.desc()),
+                (PictureOrderColumn::EditionDate, true) => dsl_query.then_order_by(pictures::dsl::edition_date.asc()),
+                (PictureOrderColumn::EditionDate, false) => dsl_query.then_order_by(pictures::dsl::edition_date.desc()),
+                (PictureOrderColumn::Id, true) => dsl_query.then_order_by(pictures::dsl::id.asc()),
+                (PictureOrderColumn::Id, false) => dsl_query.then_order_by(pictures::dsl::id.desc()),
+            };
+        }
+        dsl_query
+    }
+
+    /// Pictures right before and after the picture in the results of the query, with the same filters and sorts as [`Picture::query`].
+    /// Only the two neighbors are loaded, with the picture sort values as keysets. The page of the query is ignored.
+    pub fn neighbors_in_query(conn: &mut DBConn, user_id: i32, picture_id: i64, query: PicturesQuery) -> Result<PictureNeighbors, ErrorResponder> {
+        let picture = Self::filtered_statement(user_id, query.filters.clone(), query.filter_tree.clone())
+            .filter(pictures::dsl::id.eq(picture_id))
+            .first::<Picture>(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError("Failed to get picture".to_string(), e).res())?
+            .ok_or_else(|| ErrorType::NotFound("Picture not found in the query results".to_string()).res_no_rollback())?;
+
+        let mut neighbor_id = |reverse: bool| {
+            Self::neighbor_statement(user_id, &query, &picture, reverse)
+                .select(pictures::dsl::id)
+                .first::<i64>(conn)
+                .optional()
+                .map_err(|e| ErrorType::DatabaseError("Failed to get picture neighbors".to_string(), e).res())
+        };
+        Ok(PictureNeighbors {
+            previous_id: neighbor_id(true)?,
+            next_id: neighbor_id(false)?,
+        })
+    }
+    /// Build the boxed statement selecting the picture following `picture` in the results of the query,
+    /// or the one preceding it if `reverse` is set.
+    pub fn neighbor_statement(user_id: i32, query: &PicturesQuery, picture: &Picture, reverse: bool) -> PicturesStatement {
+        let order = Self::query_order(&query.sorts);
+        // (k0, k1...) after (v0, v1...): k0 after v0, or k0 = v0 and k1 after v1...
+        let mut after: Option<PicturesPredicate> = None;
+        for i in (0..order.len()).rev() {
+            let (column, ascend) = order[i];
+            let mut predicate = column.compare(picture, ascend != reverse);
+            if let Some(after) = after {
+                predicate = Box::new(predicate.or(column.equal(picture).and(after)));
+            }
+            after = Some(predicate);
+        }
+        let dsl_query = Self::filtered_statement(user_id, query.filters.clone(), query.filter_tree.clone()).filter(after.unwrap());
+        Self::ordered_statement(dsl_query, &order, reverse).limit(1)
+    }
+
+    /// Build the boxed statement selecting all the pictures matching the filters, restricted to the pictures the user can access.
+    /// No sorting or pagination is applied.
+    fn filtered_statement(user_id: i32, filters: Vec<PictureFilter>, filter_tree: Option<StrategyFiltering>) -> PicturesStatement {
         // Initial request that returns all the pictures the user can see
+        // (Visibility is checked with a subquery to keep a query on the pictures table only, allowing filtering tree predicates)
         let mut dsl_query = pictures::table
-            .left_join(groups_pictures::table.on(groups_pictures::dsl::picture_id.eq(pictures::dsl::id)))
-            .left_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
             .filter(
                 pictures::dsl::owner_id
                     .eq(user_id) // Owned picture
-                    .or(shared_groups::dsl::user_id.eq(user_id)), // Shared picture
+                    .or(Self::shared_with_predicate(user_id)), // Shared picture
             )
             .select(Picture::as_select())
-            .distinct()
             .into_boxed();
 
         // Applying filters
-        for filter in query.filters {
+        for filter in filters {
             dsl_query = match filter.clone() {
                 PictureFilter::Owned { invert } => {
                     if !invert {
@@ -150,7 +794,16 @@ impl Picture {
                         dsl_query.filter(not(pictures::dsl::owner_id.eq(user_id)))
                     }
                 }
+                PictureFilter::Author { invert, ids } => {
+                    if !invert {
+                        dsl_query.filter(pictures::dsl::author_id.eq_any(ids))
+                    } else {
+                        dsl_query.filter(not(pictures::dsl::author_id.eq_any(ids)))
+                    }
+                }
                 PictureFilter::Deleted { invert } => dsl_query.filter(pictures::dsl::deleted_date.is_null().eq(invert)),
+                PictureFilter::Copied { invert } => dsl_query.filter(pictures::dsl::copied.eq(!invert)),
+                PictureFilter::Favorite { invert } => dsl_query.filter(pictures::dsl::favorite.eq(!invert)),
                 PictureFilter::Arrangement { invert, ids } => {
                     let gp_alias = diesel::alias!(groups_pictures as gp_alias);
                     let subquery = exists(
@@ -203,130 +856,349 @@ impl Picture {
                         dsl_query.filter(not(subquery))
                     }
                 }
-            }
-        }
-
-        // Applying sorting
-        for sort in query.sorts {
-            dsl_query = match sort {
-                PictureSort::CreationDate { ascend } => {
-                    if ascend {
-                        dsl_query.order(pictures::dsl::creation_date.asc())
+                PictureFilter::Orientation { invert, values } => {
+                    if !invert {
+                        dsl_query.filter(pictures::dsl::orientation.eq_any(values))
                     } else {
-                        dsl_query.order(pictures::dsl::creation_date.desc())
+                        dsl_query.filter(not(pictures::dsl::orientation.eq_any(values)))
                     }
                 }
-                PictureSort::EditionDate { ascend } => {
-                    if ascend {
-                        dsl_query.order(pictures::dsl::edition_date.asc())
-                    } else {
-                        dsl_query.order(pictures::dsl::edition_date.desc())
+                PictureFilter::AspectRatio { min, max } => {
+                    // Comparing width with ratio * height, to avoid dividing by a zero height
+                    if let Some(min) = min {
+                        dsl_query = dsl_query.filter(float8(pictures::dsl::width).ge(float8(pictures::dsl::height) * min));
+                    }
+                    if let Some(max) = max {
+                        dsl_query = dsl_query.filter(float8(pictures::dsl::width).le(float8(pictures::dsl::height) * max));
                     }
+                    dsl_query
                 }
+                PictureFilter::SizeKo { invert, min, max } => match (min, max) {
+                    (Some(min), Some(max)) if !invert => dsl_query.filter(pictures::dsl::size_ko.between(min, max)),
+                    (Some(min), Some(max)) => dsl_query.filter(not(pictures::dsl::size_ko.between(min, max))),
+                    (Some(min), None) if !invert => dsl_query.filter(pictures::dsl::size_ko.ge(min)),
+                    (Some(min), None) => dsl_query.filter(pictures::dsl::size_ko.lt(min)),
+                    (None, Some(max)) if !invert => dsl_query.filter(pictures::dsl::size_ko.le(max)),
+                    (None, Some(max)) => dsl_query.filter(pictures::dsl::size_ko.gt(max)),
+                    (None, None) => dsl_query,
+                },
             }
         }
 
-        // Applying pagination
-        dsl_query = dsl_query.limit(page_size).offset((query.page - 1) as i64 * page_size);
+        // Applying the filtering tree
+        if let Some(filter_tree) = filter_tree {
+            dsl_query = dsl_query.filter(filter_tree.as_diesel_predicate());
+        }
 
-        // Fetching the pictures
-        let pictures: Vec<ListPictureData> = dsl_query
-            .select((
-                pictures::id,
-                pictures::name,
-                pictures::width,
-                pictures::height,
-                pictures::creation_date,
-                pictures::edition_date,
-                pictures::blurhash,
-            ))
-            .distinct()
-            .load::<(i64, String, i16, i16, NaiveDateTime, NaiveDateTime, Option<String>)>(conn)
-            .map(|vec| {
-                vec.into_iter()
-                    .map(|(id, name, width, height, creation_date, edition_date, blurhash)| ListPictureData {
-                        id,
-                        name,
-                        width,
-                        height,
-                        creation_date,
-                        edition_date,
-                        blurhash,
-                    })
-                    .collect()
-            })
-            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures".to_string(), e).res())?;
+        dsl_query
+    }
 
-        Ok(pictures)
+    /// Get a page of the pictures the user can access that were modified (see [`Picture::touch`]) or moved to the trash after `since`, ordered by id.
+    pub fn changes_since(
+        conn: &mut DBConn,
+        user_id: i32,
+        since: NaiveDateTime,
+        page: i32,
+        page_size: i64,
+    ) -> Result<Vec<PictureChange>, ErrorResponder> {
+        Self::changes_since_statement(user_id, since, page, page_size)
+            .select((pictures::dsl::id, pictures::dsl::modified_at, pictures::dsl::deleted_date))
+            .load::<(i64, NaiveDateTime, Option<NaiveDateTime>)>(conn)
+            .map(|rows| rows.into_iter().map(PictureChange::from).collect())
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the pictures changes".to_string(), e).res())
+    }
+    /// Build the boxed statement selecting a page of the pictures changed after `since` (see [`Picture::changes_since`]).
+    pub fn changes_since_statement(user_id: i32, since: NaiveDateTime, page: i32, page_size: i64) -> PicturesStatement {
+        assert_ne!(page, 0, "Page number must be greater than 0");
+        Self::filtered_statement(user_id, vec![], None)
+            .filter(
+                pictures::dsl::modified_at.gt(since).or(pictures::dsl::deleted_date
+                    .is_not_null()
+                    .and(pictures::dsl::deleted_date.assume_not_null().gt(since))),
+            )
+            .order(pictures::dsl::id.asc())
+            .limit(page_size)
+            .offset((page - 1) as i64 * page_size)
     }
 
-    /// Returns Ok(true) if the user is the owner of the picture or the picture is in a group shared with the user
-    pub fn can_user_access_picture(conn: &mut DBConn, picture_id: i64, user_id: i32) -> Result<bool, ErrorResponder> {
-        let owned_count = pictures::table
+    /// Count, for each tag of the user, the pictures matching the query that have this tag. The sorting and the page of the query are ignored.
+    /// This function guaranties that only the pictures the user has the right to access are counted.
+    pub fn tag_facets_for_query(conn: &mut DBConn, user_id: i32, query: PicturesQuery) -> Result<Vec<TagFacet>, ErrorResponder> {
+        Self::tag_facets_statement(user_id, query)
+            .load::<(i32, i64)>(conn)
+            .map(|rows| rows.into_iter().map(TagFacet::from).collect())
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the tag facets".to_string(), e).res())
+    }
+    /// Build the statement counting the pictures of the query per tag of the user (see [`Picture::tag_facets_for_query`]), ordered by tag id.
+    pub fn tag_facets_statement(user_id: i32, query: PicturesQuery) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i64)> {
+        let picture_ids = Self::filtered_statement(user_id, query.filters, query.filter_tree).select(pictures::dsl::id);
+        pictures_tags::table
+            .inner_join(tags::table.on(tags::dsl::id.eq(pictures_tags::dsl::tag_id)))
+            .inner_join(tag_groups::table.on(tag_groups::dsl::id.eq(tags::dsl::tag_group_id)))
+            .filter(tag_groups::dsl::user_id.eq(user_id))
+            .filter(pictures_tags::dsl::picture_id.eq_any(picture_ids))
+            .group_by(pictures_tags::dsl::tag_id)
+            .select((pictures_tags::dsl::tag_id, count_star()))
+            .order(pictures_tags::dsl::tag_id)
+    }
+
+    /// Format of the original picture, None if it was uploaded before the format was stored. The access must be checked by the caller.
+    pub fn get_format(conn: &mut DBConn, picture_id: i64) -> Result<Option<String>, ErrorResponder> {
+        pictures::table
             .filter(pictures::dsl::id.eq(picture_id))
-            .filter(pictures::dsl::owner_id.eq(user_id))
-            .count()
+            .select(pictures::dsl::format)
+            .first(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError("Failed to get picture".to_string(), e).res())?
+            .ok_or_else(|| ErrorType::PictureNotFound.res())
+    }
+    /// Returns Ok(true) if the user is the owner of the picture, whether it is in the trash or not.
+    pub fn is_picture_owned_by(conn: &mut DBConn, picture_id: i64, user_id: i32) -> Result<bool, ErrorResponder> {
+        let owned_count = Self::is_picture_owned_by_statement(picture_id, user_id)
             .get_result::<i64>(conn)
             .map_err(|e| ErrorType::DatabaseError("Failed to get picture".to_string(), e).res())?;
 
-        if owned_count > 0 {
-            return Ok(true);
+        Ok(owned_count > 0)
+    }
+    /// Build the statement counting the pictures with this id owned by the user (see [`Picture::is_picture_owned_by`]).
+    pub fn is_picture_owned_by_statement(picture_id: i64, user_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        pictures::table
+            .filter(pictures::dsl::id.eq(picture_id))
+            .filter(pictures::dsl::owner_id.eq(user_id))
+            .count()
+    }
+    /// List everyone who can see the picture: its owner, the users of the groups containing it that are shared with them,
+    /// and the public links of these groups. Only the owner of the picture can audit it.
+    pub fn visibility_audit(conn: &mut DBConn, picture_id: i64, user_id: i32) -> Result<PictureVisibility, ErrorResponder> {
+        if !Picture::is_picture_owned_by(conn, picture_id, user_id)? {
+            return ErrorType::PictureNotFound.res_err_no_rollback();
         }
-
-        let shared_count = groups_pictures::table
+        let shares = Self::visibility_shares_statement(picture_id)
+            .load::<(i32, i32, bool)>(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get picture shares".to_string(), e).res())?;
+        let link_tokens = Self::visibility_links_statement(picture_id)
+            .load::<Vec<u8>>(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get picture public links".to_string(), e).res())?;
+        Ok(PictureVisibility::new(user_id, shares, link_tokens))
+    }
+    /// Build the statement listing the `(user_id, group_id, confirmed)` shares of the groups containing the picture.
+    pub fn visibility_shares_statement(picture_id: i64) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (i32, i32, bool)> {
+        groups_pictures::table
             .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
-            .filter(shared_groups::dsl::user_id.eq(user_id))
             .filter(groups_pictures::dsl::picture_id.eq(picture_id))
-            .count()
-            .get_result::<i64>(conn)
-            .map_err(|e| ErrorType::DatabaseError("Failed to get picture".to_string(), e).res())?;
-
-        Ok(shared_count > 0)
+            .select((shared_groups::dsl::user_id, shared_groups::dsl::group_id, shared_groups::dsl::confirmed))
+            .order((shared_groups::dsl::user_id, shared_groups::dsl::group_id))
     }
-    pub fn filter_user_accessible_pictures(conn: &mut DBConn, user_id: i32, picture_ids: &Vec<i64>) -> Result<Vec<i64>, ErrorResponder> {
-        pictures::table
-            // Join with shared pictures
-            .left_join(
-                groups_pictures::table
-                    .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
-                    .on(groups_pictures::dsl::picture_id.eq(pictures::dsl::id)),
-            )
-            // Filter allowed pictures
-            .filter(shared_groups::dsl::user_id.eq(user_id).or(pictures::dsl::owner_id.eq(user_id)))
-            // Filter requested pictures
-            .filter(pictures::dsl::id.eq_any(picture_ids))
-            .select(pictures::dsl::id)
-            .distinct()
+    /// Build the statement listing the tokens of the public links of the groups containing the picture.
+    pub fn visibility_links_statement(picture_id: i64) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, Vec<u8>> {
+        groups_pictures::table
+            .inner_join(link_share_groups::table.on(link_share_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
+            .filter(groups_pictures::dsl::picture_id.eq(picture_id))
+            .select(link_share_groups::dsl::token)
+            .order(link_share_groups::dsl::token)
+    }
+    /// Returns Ok(true) if the user is the owner of the picture or the picture is in a group shared with the user
+    pub fn can_user_access_picture(conn: &mut DBConn, picture_id: i64, user_id: i32) -> Result<bool, ErrorResponder> {
+        Ok(!Self::filter_user_accessible_pictures(conn, user_id, &[picture_id])?.is_empty())
+    }
+    pub fn filter_user_accessible_pictures(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<i64>, ErrorResponder> {
+        Self::filter_user_accessible_pictures_statement(user_id, picture_ids)
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError("Failed to get accessible pictures".to_string(), e).res())
     }
-    pub fn filter_user_unaccessible_pictures(conn: &mut DBConn, user_id: i32, picture_ids: &Vec<i64>) -> Result<Vec<i64>, ErrorResponder> {
+    /// Build the statement selecting the ids of the pictures of the list that the user owns or that are shared with them.
+    pub fn filter_user_accessible_pictures_statement(
+        user_id: i32,
+        picture_ids: &[i64],
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
         pictures::table
-            // Join with shared pictures
-            .left_join(
-                groups_pictures::table
-                    .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
-                    .on(groups_pictures::dsl::picture_id.eq(pictures::dsl::id)),
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
+            .filter(pictures::dsl::owner_id.eq(user_id).or(Self::shared_access_predicate(user_id)))
+            .select(pictures::dsl::id)
+    }
+    /// Pictures of the list that the user can't access, in the order of the list.
+    pub fn filter_user_unaccessible_pictures(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<i64>, ErrorResponder> {
+        let accessible_picture_ids = Self::filter_user_accessible_pictures(conn, user_id, picture_ids)?;
+        Ok(Self::unaccessible_among(picture_ids, &accessible_picture_ids))
+    }
+    /// Pictures of the list that are not in `accessible_picture_ids`, in the order of the list.
+    pub fn unaccessible_among(picture_ids: &[i64], accessible_picture_ids: &[i64]) -> Vec<i64> {
+        let accessible_picture_ids: HashSet<&i64> = accessible_picture_ids.iter().collect();
+        picture_ids.iter().filter(|id| !accessible_picture_ids.contains(id)).cloned().collect()
+    }
+    /// Fails with Unauthorized, without rollback, if some pictures of the list are not in `accessible_picture_ids`.
+    /// Duplicated ids in the list are allowed.
+    pub fn require_all_accessible(picture_ids: &[i64], accessible_picture_ids: &[i64]) -> Result<(), ErrorResponder> {
+        if !Self::unaccessible_among(picture_ids, accessible_picture_ids).is_empty() {
+            return ErrorType::Unauthorized.res_err_no_rollback();
+        }
+        Ok(())
+    }
+    /// Total size of the pictures of the list that the user can access, computed by the database without loading the pictures.
+    pub fn total_size_for(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<PicturesTotalSize, ErrorResponder> {
+        Self::total_size_for_statement(user_id, picture_ids)
+            .first::<(Option<i64>, i64)>(conn)
+            .map(PicturesTotalSize::from)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the pictures total size".to_string(), e).res())
+    }
+    /// Build the statement summing the size of the accessible pictures of the list (see [`Picture::total_size_for`]).
+    /// Visibility is checked with a subquery, so that a picture shared in several groups is counted once.
+    pub fn total_size_for_statement(
+        user_id: i32,
+        picture_ids: &[i64],
+    ) -> pictures::BoxedQuery<'static, Pg, (diesel::sql_types::Nullable<BigInt>, BigInt)> {
+        pictures::table
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
+            .filter(
+                pictures::dsl::owner_id.eq(user_id).or(Self::shared_with_predicate(user_id)),
+            )
+            .select((diesel::dsl::sum(pictures::dsl::size_ko), count_star()))
+            .into_boxed()
+    }
+    /// Histogram of the values of an EXIF field over the pictures of the list that the user can access, computed by the database.
+    pub fn exif_histogram(
+        conn: &mut DBConn,
+        user_id: i32,
+        picture_ids: &[i64],
+        field: ExifField,
+    ) -> Result<Vec<ExifHistogramBucket>, ErrorResponder> {
+        Self::exif_histogram_statement(user_id, picture_ids, field)
+            .load::<(Option<String>, i64)>(conn)
+            .map(ExifHistogramBucket::from_rows)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the EXIF histogram".to_string(), e).res())
+    }
+    /// Build the statement counting the accessible pictures of the list per value of the field (see [`Picture::exif_histogram`]).
+    /// Visibility is checked with a subquery, so that a picture shared in several groups is counted once.
+    pub fn exif_histogram_statement(
+        user_id: i32,
+        picture_ids: &[i64],
+        field: ExifField,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (Option<String>, i64)> {
+        let value = diesel::dsl::sql::<diesel::sql_types::Nullable<Text>>(field.text_expression());
+        pictures::table
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
+            .filter(
+                pictures::dsl::owner_id.eq(user_id).or(Self::shared_with_predicate(user_id)),
             )
-            // Filter disallowed pictures
-            .filter(not(shared_groups::dsl::user_id.eq(user_id).and(pictures::dsl::owner_id.eq(user_id))))
-            // Filter requested pictures
+            .group_by(value.clone())
+            .select((value, count_star()))
+    }
+    /// Count the pictures the user can access per day of creation, from `from` to `to` included, by ascending date.
+    /// Days without pictures are not listed. Pictures in the trash are not counted.
+    pub fn calendar_counts(conn: &mut DBConn, user_id: i32, from: NaiveDate, to: NaiveDate) -> Result<Vec<CalendarDay>, ErrorResponder> {
+        Self::calendar_counts_statement(user_id, from, to)
+            .load::<(NaiveDate, i64)>(conn)
+            .map(|rows| rows.into_iter().map(CalendarDay::from).collect())
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the calendar counts".to_string(), e).res())
+    }
+    /// Build the statement counting the accessible pictures per day of creation (see [`Picture::calendar_counts`]).
+    /// Visibility is checked with a subquery, so that a picture shared in several groups is counted once.
+    pub fn calendar_counts_statement(
+        user_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, (NaiveDate, i64)> {
+        let start = from.and_time(NaiveTime::MIN);
+        let end = to.checked_add_days(Days::new(1)).unwrap_or(NaiveDate::MAX).and_time(NaiveTime::MIN);
+        let day = diesel::dsl::sql::<diesel::sql_types::Date>("DATE(\"pictures\".\"creation_date\")");
+        pictures::table
+            .filter(pictures::dsl::creation_date.ge(start))
+            .filter(pictures::dsl::creation_date.lt(end))
+            .filter(pictures::dsl::deleted_date.is_null())
+            .filter(pictures::dsl::owner_id.eq(user_id).or(Self::shared_with_predicate(user_id)))
+            .group_by(day.clone())
+            .select((day.clone(), count_star()))
+            .order(day)
+    }
+    /// Get the pictures from their ids, without any access check
+    pub fn from_ids(conn: &mut DBConn, picture_ids: &Vec<i64>) -> Result<Vec<Picture>, ErrorResponder> {
+        pictures::table
             .filter(pictures::dsl::id.eq_any(picture_ids))
+            .select(Picture::as_select())
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures".to_string(), e).res())
+    }
+    /// Sum of the size of the pictures (in Ko), without any access check
+    pub fn sum_size_ko(conn: &mut DBConn, picture_ids: &[i64]) -> Result<i64, ErrorResponder> {
+        if picture_ids.is_empty() {
+            return Ok(0);
+        }
+        pictures::table
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
+            .select(diesel::dsl::sum(pictures::dsl::size_ko))
+            .first::<Option<i64>>(conn)
+            .map(|size| size.unwrap_or(0))
+            .map_err(|e| ErrorType::DatabaseError("Failed to sum the pictures size".to_string(), e).res())
+    }
+    /// Pictures of the list counted in the storage of the user through their confirmed copied shares:
+    /// pictures out of the trash, that they don't own, in a group shared with them by a copied share (see [`IntegrityReport::pictures_sizes_ko`]).
+    /// The shares of `excluded_group_ids` are ignored.
+    ///
+    /// [`IntegrityReport::pictures_sizes_ko`]: crate::database::integrity_scan::IntegrityReport::pictures_sizes_ko
+    pub fn filter_copied_storage_pictures(
+        conn: &mut DBConn,
+        user_id: i32,
+        picture_ids: &[i64],
+        excluded_group_ids: &[i32],
+    ) -> Result<Vec<i64>, ErrorResponder> {
+        if picture_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        Self::copied_storage_pictures_statement(user_id, picture_ids, excluded_group_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get the pictures of the copied shares".to_string(), e).res())
+    }
+    pub fn copied_storage_pictures_statement(
+        user_id: i32,
+        picture_ids: &[i64],
+        excluded_group_ids: &[i32],
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        groups_pictures::table
+            .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
+            .inner_join(pictures::table.on(pictures::dsl::id.eq(groups_pictures::dsl::picture_id)))
+            .filter(shared_groups::dsl::user_id.eq(user_id))
+            .filter(shared_groups::dsl::copied.eq(true))
+            .filter(shared_groups::dsl::confirmed.eq(true))
+            .filter(shared_groups::dsl::group_id.ne_all(excluded_group_ids.to_vec()))
+            .filter(pictures::dsl::owner_id.ne(user_id))
+            .filter(pictures::dsl::deleted_date.is_null())
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
             .select(pictures::dsl::id)
             .distinct()
-            .load(conn)
-            .map_err(|e| ErrorType::DatabaseError("Failed to get accessible pictures".to_string(), e).res())
     }
-    pub fn is_picture_publicly_shared(conn: &mut DBConn, picture_id: i64) -> Result<bool, ErrorResponder> {
-        let shared_count = groups_pictures::table
-            .inner_join(link_share_groups::table.on(link_share_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
-            .filter(groups_pictures::dsl::picture_id.eq(picture_id))
+    /// Counts the pictures owned by the user that are not in the trash
+    pub fn count_owned(conn: &mut DBConn, user_id: i32) -> Result<i64, ErrorResponder> {
+        pictures::table
+            .filter(pictures::dsl::owner_id.eq(user_id))
+            .filter(pictures::dsl::deleted_date.is_null())
             .count()
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to count pictures".to_string(), e).res())
+    }
+    pub fn is_picture_publicly_shared(conn: &mut DBConn, picture_id: i64) -> Result<bool, ErrorResponder> {
+        let shared_count = Self::is_picture_publicly_shared_statement(picture_id)
             .get_result::<i64>(conn)
             .map_err(|e| ErrorType::DatabaseError("Failed to get picture".to_string(), e).res())?;
 
         Ok(shared_count > 0)
     }
+    /// Build the statement counting the public links of the groups containing the picture (see [`Picture::is_picture_publicly_shared`]).
+    pub fn is_picture_publicly_shared_statement(picture_id: i64) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        groups_pictures::table
+            .inner_join(link_share_groups::table.on(link_share_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
+            .filter(groups_pictures::dsl::picture_id.eq(picture_id))
+            .count()
+    }
+    /// Get the access of the user (or of an anonymous caller if None) to a picture, without fetching the picture itself.
+    pub fn get_picture_access(conn: &mut DBConn, picture_id: i64, user_id: Option<i32>) -> Result<PictureAccess, ErrorResponder> {
+        let publicly_shared = Picture::is_picture_publicly_shared(conn, picture_id)?;
+        let Some(user_id) = user_id else {
+            return Ok(PictureAccess::anonymous(publicly_shared));
+        };
+        let owned = Picture::is_picture_owned_by(conn, picture_id, user_id)?;
+        let shared_with_user = !owned && Picture::can_user_access_picture(conn, picture_id, user_id)?;
+        Ok(PictureAccess::for_user(owned, shared_with_user, publicly_shared))
+    }
 
     pub fn insert(
         conn: &mut DBConn,
@@ -335,6 +1207,7 @@ impl Picture {
         metadata: Option<rexiv2::Metadata>,
         size_ko: i32,
         blurhash: Option<String>,
+        format: String,
     ) -> Result<Picture, ErrorResponder> {
         let mut p = Picture::from(metadata);
         p.owner_id = user_id;
@@ -342,6 +1215,7 @@ impl Picture {
         p.name = name;
         p.size_ko = size_ko;
         p.blurhash = blurhash;
+        p.format = Some(format);
 
         insert_into(pictures::table)
             .values((
@@ -368,38 +1242,141 @@ impl Picture {
                 pictures::dsl::f_number.eq(p.f_number),
                 pictures::dsl::size_ko.eq(p.size_ko),
                 pictures::dsl::blurhash.eq(p.blurhash),
+                pictures::dsl::format.eq(p.format),
+                pictures::dsl::favorite.eq(p.favorite),
             ))
+            .returning(Picture::as_returning())
             .get_result(conn)
             .map_err(|e| ErrorType::DatabaseError("Failed to insert picture".to_string(), e).res())
     }
 
+    /// Update the comment of a picture owned by the user, also updating its edition date.
+    /// Returns `PictureNotFound` if the picture does not exist or is not owned by the user.
+    pub fn update_comment(conn: &mut DBConn, picture_id: i64, user_id: i32, comment: &str) -> Result<Picture, ErrorResponder> {
+        let edition_date = Utc::now().naive_utc();
+        diesel::update(
+            pictures::table
+                .filter(pictures::dsl::id.eq(picture_id))
+                .filter(pictures::dsl::owner_id.eq(user_id)),
+        )
+        .set((
+            pictures::dsl::comment.eq(comment),
+            pictures::dsl::edition_date.eq(edition_date),
+            pictures::dsl::modified_at.eq(edition_date),
+        ))
+        .returning(Picture::as_returning())
+        .get_result(conn)
+        .optional()
+        .map_err(|e| ErrorType::DatabaseError("Failed to update picture comment".to_string(), e).res())?
+        .ok_or_else(|| ErrorType::PictureNotFound.res())
+    }
+
+    /// Set or unset the favorite flag of a picture owned by the user, also updating its edition date.
+    /// Returns `PictureNotFound` if the picture does not exist or is not owned by the user.
+    pub fn set_favorite(conn: &mut DBConn, picture_id: i64, user_id: i32, favorite: bool) -> Result<Picture, ErrorResponder> {
+        Self::set_favorite_statement(picture_id, user_id, favorite, Utc::now().naive_utc())
+            .get_result(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError("Failed to update picture favorite".to_string(), e).res())?
+            .ok_or_else(|| ErrorType::PictureNotFound.res())
+    }
+    pub fn set_favorite_statement(
+        picture_id: i64,
+        user_id: i32,
+        favorite: bool,
+        edition_date: NaiveDateTime,
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, Picture> {
+        diesel::update(
+            pictures::table
+                .filter(pictures::dsl::id.eq(picture_id))
+                .filter(pictures::dsl::owner_id.eq(user_id)),
+        )
+        .set((
+            pictures::dsl::favorite.eq(favorite),
+            pictures::dsl::edition_date.eq(edition_date),
+            pictures::dsl::modified_at.eq(edition_date),
+        ))
+        .returning(Picture::as_returning())
+    }
+
     pub fn get_pictures_details(conn: &mut DBConn, user_id: i32, picture_ids: Vec<i64>) -> Result<Vec<Picture>, ErrorResponder> {
-        let pictures: Vec<Picture> = pictures::table
-            // Join with shared pictures
-            .left_join(
-                groups_pictures::table
-                    .inner_join(shared_groups::table.on(shared_groups::dsl::group_id.eq(groups_pictures::dsl::group_id)))
-                    .on(groups_pictures::dsl::picture_id.eq(pictures::dsl::id)),
-            )
-            // Filter allowed pictures
-            .filter(shared_groups::dsl::user_id.eq(user_id).or(pictures::dsl::owner_id.eq(user_id)))
-            // Filter requested pictures
-            .filter(pictures::dsl::id.eq_any(picture_ids))
-            .select(Picture::as_select())
-            .distinct()
+        Self::get_pictures_details_statement(user_id, &picture_ids)
             .load(conn)
-            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures details".to_string(), e).res())?;
-
-        Ok(pictures)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures details".to_string(), e).res())
+    }
+    /// Build the statement selecting the pictures of the list that the user owns or that are shared with them.
+    pub fn get_pictures_details_statement(user_id: i32, picture_ids: &[i64]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, Picture> {
+        pictures::table
+            .filter(pictures::dsl::id.eq_any(picture_ids.to_vec()))
+            .filter(pictures::dsl::owner_id.eq(user_id).or(Self::shared_access_predicate(user_id)))
+            .select(Picture::as_select())
     }
 
-    pub fn get_picture_details(conn: &mut DBConn, user_id: i32, picture_id: i64) -> Result<PictureDetails, ErrorResponder> {
-        let picture = Self::get_pictures_details(conn, user_id, vec![picture_id])?
-            .pop()
+    /// Get the requested sections of the details of a picture. The picture is always loaded, to check its access,
+    /// but the tags and ratings (including the ones of the friends) are only queried when requested.
+    pub fn get_picture_details_selection(
+        source: &mut impl PictureDetailsSource,
+        user_id: i32,
+        picture_id: i64,
+        fields: &PictureDetailsFields,
+    ) -> Result<PictureDetailsSelection, ErrorResponder> {
+        let picture = source
+            .accessible_picture(user_id, picture_id)?
             .ok_or_else(|| ErrorType::PictureNotFound.res())?;
-        let ratings = Rating::from_picture_id_including_friends(conn, picture_id, user_id)?;
-        let tags_ids = PictureTag::get_picture_tags(conn, picture_id, user_id)?;
-        Ok(PictureDetails { picture, tags_ids, ratings })
+        let tags_ids = if fields.tags { Some(source.tags(user_id, picture_id)?) } else { None };
+        let ratings = if fields.ratings {
+            Some(source.ratings_including_friends(user_id, picture_id)?)
+        } else {
+            None
+        };
+        Ok(PictureDetailsSelection {
+            picture: fields.picture.then_some(picture),
+            tags_ids,
+            ratings,
+        })
+    }
+
+    /// Get the details of each of the accessible pictures, including their tags and ratings.
+    /// Tags and ratings are fetched with one query each for all the pictures.
+    /// Pictures are returned in the order of `picture_ids`, inaccessible pictures are skipped.
+    pub fn get_many_picture_details(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<PictureDetails>, ErrorResponder> {
+        let pictures = Self::get_pictures_details(conn, user_id, picture_ids.to_vec())?;
+        let accessible_ids = pictures.iter().map(|p| p.id).collect::<Vec<i64>>();
+        let tags = PictureTag::get_pictures_tags(conn, user_id, &accessible_ids)?;
+        let ratings = Rating::from_picture_ids_including_friends(conn, user_id, &accessible_ids)?;
+        Ok(Self::assemble_pictures_details(picture_ids, pictures, tags, ratings))
+    }
+    /// Dispatch the (picture_id, tag_id) tuples and the ratings to their pictures, following the order of `picture_ids`.
+    pub fn assemble_pictures_details(
+        picture_ids: &[i64],
+        pictures: Vec<Picture>,
+        tags: Vec<(i64, i32)>,
+        ratings: Vec<Rating>,
+    ) -> Vec<PictureDetails> {
+        let mut details: HashMap<i64, PictureDetails> = pictures
+            .into_iter()
+            .map(|picture| {
+                (
+                    picture.id,
+                    PictureDetails {
+                        picture,
+                        tags_ids: vec![],
+                        ratings: vec![],
+                    },
+                )
+            })
+            .collect();
+        for (picture_id, tag_id) in tags {
+            if let Some(d) = details.get_mut(&picture_id) {
+                d.tags_ids.push(tag_id);
+            }
+        }
+        for rating in ratings {
+            if let Some(d) = details.get_mut(&rating.picture_id) {
+                d.ratings.push(rating);
+            }
+        }
+        picture_ids.iter().filter_map(|id| details.remove(id)).collect()
     }
 
     /// Get mixed picture details from a vector of picture IDs
@@ -442,6 +1419,7 @@ impl Picture {
                 author_id: None,
                 deleted_date: None,
                 copied: None,
+                favorite: None,
                 creation_date: None,
                 edition_date: None,
                 latitude: None,
@@ -483,6 +1461,7 @@ impl Picture {
             author_id: check_same!(author_id),
             deleted_date: check_same!(deleted_date),
             copied: check_same!(copied),
+            favorite: check_same!(favorite),
             creation_date: check_same!(creation_date),
             edition_date: check_same!(edition_date),
             latitude: check_same!(latitude),
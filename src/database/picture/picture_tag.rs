@@ -2,9 +2,11 @@ use crate::database::database::DBConn;
 use crate::database::picture::picture::Picture;
 use crate::database::schema::*;
 use crate::database::tag::tag::Tag;
-use crate::database::tag::tag_group::TagGroup;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use diesel::dsl::{exists, not};
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
 use diesel::{Associations, ExpressionMethods, Identifiable, JoinOnDsl, QueryDsl, Queryable, RunQueryDsl, Selectable};
 use itertools::Itertools;
 use std::collections::HashMap;
@@ -42,17 +44,32 @@ impl PictureTag {
             .map_err(|e| ErrorType::DatabaseError("Failed to get picture tags".to_string(), e).res())
     }
 
+    /// Get the tags of the user on each of the pictures, as (picture_id, tag_id) tuples in the order they were assigned, in a single query
+    pub fn get_pictures_tags(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<(i64, i32)>, ErrorResponder> {
+        pictures_tags::table
+            .filter(pictures_tags::picture_id.eq_any(picture_ids))
+            // Check that the tag is owned by the owner
+            .inner_join(tags::table.on(tags::id.eq(pictures_tags::tag_id)))
+            .inner_join(tag_groups::table.on(tag_groups::id.eq(tags::tag_group_id)))
+            .filter(tag_groups::user_id.eq(user_id))
+            .select((pictures_tags::picture_id, pictures_tags::tag_id))
+            .order_by(pictures_tags::assignment_order)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures tags".to_string(), e).res())
+    }
+
     pub fn add_pictures(conn: &mut DBConn, tag_id: i32, picture_ids: &Vec<i64>) -> Result<usize, ErrorResponder> {
         let values: Vec<_> = picture_ids
             .into_iter()
             .map(|pic_id| (pictures_tags::tag_id.eq(tag_id), pictures_tags::picture_id.eq(pic_id)))
             .collect();
 
-        diesel::insert_into(pictures_tags::table)
+        let added = diesel::insert_into(pictures_tags::table)
             .values(&values)
             .on_conflict_do_nothing()
             .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Self::touch_if_changed(conn, added, picture_ids)
     }
     pub fn add_pictures_batch(conn: &mut DBConn, tag_ids: &Vec<i32>, picture_ids: &Vec<i64>) -> Result<usize, ErrorResponder> {
         let values: Vec<_> = tag_ids
@@ -64,25 +81,45 @@ impl PictureTag {
             })
             .collect();
 
-        diesel::insert_into(pictures_tags::table)
+        let added = diesel::insert_into(pictures_tags::table)
             .values(&values)
             .on_conflict_do_nothing()
             .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Self::touch_if_changed(conn, added, picture_ids)
     }
     pub fn remove_pictures(conn: &mut DBConn, tag_id: i32, picture_ids: &Vec<i64>) -> Result<usize, ErrorResponder> {
-        diesel::delete(pictures_tags::table)
+        let removed = diesel::delete(pictures_tags::table)
             .filter(pictures_tags::tag_id.eq(tag_id))
             .filter(pictures_tags::picture_id.eq_any(picture_ids))
             .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Self::touch_if_changed(conn, removed, picture_ids)
+    }
+    /// Remove the tag from all the pictures, returning the ids of the pictures that had it
+    pub fn remove_all_pictures(conn: &mut DBConn, tag_id: i32) -> Result<Vec<i64>, ErrorResponder> {
+        let picture_ids = diesel::delete(pictures_tags::table)
+            .filter(pictures_tags::tag_id.eq(tag_id))
+            .returning(pictures_tags::picture_id)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Picture::touch(conn, &picture_ids)?;
+        Ok(picture_ids)
     }
     pub fn remove_pictures_batch(conn: &mut DBConn, tag_ids: &Vec<i32>, picture_ids: &Vec<i64>) -> Result<usize, ErrorResponder> {
-        diesel::delete(pictures_tags::table)
+        let removed = diesel::delete(pictures_tags::table)
             .filter(pictures_tags::tag_id.eq_any(tag_ids))
             .filter(pictures_tags::picture_id.eq_any(picture_ids))
             .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Self::touch_if_changed(conn, removed, picture_ids)
+    }
+    /// Mark the pictures as modified (see [`Picture::touch`]) if some of their tags changed, returning the number of changed assignments.
+    fn touch_if_changed(conn: &mut DBConn, changed: usize, picture_ids: &[i64]) -> Result<usize, ErrorResponder> {
+        if changed > 0 {
+            Picture::touch(conn, picture_ids)?;
+        }
+        Ok(changed)
     }
 
     /// Add all the users’ default tags to a list of pictures.
@@ -99,26 +136,39 @@ impl PictureTag {
     }
 
     /// For every tag group of the user, add the defaults tags of the tag group only to provided pictures that have not any tag of this tag group.
-    pub fn add_default_tags_to_pictures_without_tags(conn: &mut DBConn, user_id: i32, picture_ids: &Vec<i64>) -> Result<(), ErrorResponder> {
-        TagGroup::list_all_tags_as_tag_group_with_tags(conn, user_id)?
-            .iter()
-            .try_for_each(|tgwt| {
-                let pictures_without_tag = pictures::table
-                    .filter(pictures::id.eq_any(picture_ids))
-                    .filter(not(exists(
-                        pictures_tags::table
-                            .inner_join(tags::table.on(tags::id.eq(pictures_tags::tag_id)))
-                            .filter(pictures_tags::picture_id.eq(pictures::id))
-                            .filter(tags::tag_group_id.eq(tgwt.tag_group.id.unwrap())),
-                    )))
-                    .select(pictures::id)
-                    .load::<i64>(conn)
-                    .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+    /// Returns the number of tag assignments added. Running it again on the same pictures adds nothing.
+    pub fn add_default_tags_to_pictures_without_tags(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<usize, ErrorResponder> {
+        let tagged_picture_ids: Vec<i64> = Self::add_default_tags_to_pictures_without_tags_statement(user_id, picture_ids)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to add default tags".to_string(), e).res())?;
+        Picture::touch(conn, &tagged_picture_ids.iter().cloned().unique().collect_vec())?;
+        Ok(tagged_picture_ids.len())
+    }
+    /// Build the single `INSERT … SELECT … WHERE NOT EXISTS` statement adding the default tags of every tag group of the user
+    /// to the pictures that have no tag of this tag group. Returns the picture id of each added assignment.
+    pub fn add_default_tags_to_pictures_without_tags_statement(
+        user_id: i32,
+        picture_ids: &[i64],
+    ) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        let group_tags = diesel::alias!(tags as group_tags);
+        let missing_default_tags = tags::table
+            .inner_join(tag_groups::table.on(tag_groups::id.eq(tags::tag_group_id)))
+            .inner_join(pictures::table.on(pictures::id.eq_any(picture_ids.to_vec())))
+            .filter(tag_groups::user_id.eq(user_id))
+            .filter(tags::is_default.eq(true))
+            .filter(not(exists(
+                pictures_tags::table
+                    .inner_join(group_tags.on(group_tags.field(tags::id).eq(pictures_tags::tag_id)))
+                    .filter(pictures_tags::picture_id.eq(pictures::id))
+                    .filter(group_tags.field(tags::tag_group_id).eq(tags::tag_group_id)),
+            )))
+            .select((pictures::id, tags::id));
 
-                let default_tags = tgwt.tags.iter().filter(|t| t.is_default).map(|t| t.id).collect_vec();
-                Self::add_pictures_batch(conn, &default_tags, &pictures_without_tag)?;
-                Ok(())
-            })
+        diesel::insert_into(pictures_tags::table)
+            .values(missing_default_tags)
+            .into_columns((pictures_tags::picture_id, pictures_tags::tag_id))
+            .on_conflict_do_nothing()
+            .returning(pictures_tags::picture_id)
     }
 
     /// Get common and mixed tags from an array of pictures
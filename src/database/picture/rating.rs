@@ -3,8 +3,11 @@ use crate::database::picture::picture::Picture;
 use crate::database::schema::*;
 use crate::database::user::user::User;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
 use diesel::query_dsl::InternalJoinDsl;
+use diesel::query_dsl::LoadQuery;
 use diesel::{Associations, Identifiable, Queryable, Selectable};
 use diesel::{BoolExpressionMethods, ExpressionMethods, OptionalExtension, RunQueryDsl};
 use diesel::{JoinOnDsl, NullableExpressionMethods, SelectableHelper};
@@ -23,7 +26,81 @@ pub struct Rating {
     pub rating: i16,
 }
 
+/// Highest rating value, ratings going from 0 to `RATING_MAX` stars.
+pub const RATING_MAX: i16 = 5;
+
 impl Rating {
+    /// Set the rating of the user for the picture, replacing the previous one.
+    pub fn set(conn: &mut DBConn, user_id: i32, picture_id: i64, rating: i16) -> Result<Rating, ErrorResponder> {
+        Picture::touch(conn, &[picture_id])?;
+        diesel::insert_into(ratings::table)
+            .values((
+                ratings::user_id.eq(user_id),
+                ratings::picture_id.eq(picture_id),
+                ratings::rating.eq(rating),
+            ))
+            .on_conflict((ratings::user_id, ratings::picture_id))
+            .do_update()
+            .set(ratings::rating.eq(rating))
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    /// Remove the rating of the user for the picture, if any.
+    pub fn delete(conn: &mut DBConn, user_id: i32, picture_id: i64) -> Result<(), ErrorResponder> {
+        diesel::delete(
+            ratings::table
+                .filter(ratings::user_id.eq(user_id))
+                .filter(ratings::picture_id.eq(picture_id)),
+        )
+        .execute(conn)
+        .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Picture::touch(conn, &[picture_id])
+    }
+    /// Set the same rating of the user for all the pictures, replacing the previous ones, in a single query.
+    pub fn set_batch(conn: &mut DBConn, user_id: i32, picture_ids: &[i64], rating: i16) -> Result<Vec<Rating>, ErrorResponder> {
+        if picture_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        Picture::touch(conn, picture_ids)?;
+        Self::set_batch_statement(user_id, picture_ids, rating)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn set_batch_statement(user_id: i32, picture_ids: &[i64], rating: i16) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, Rating> {
+        let values = picture_ids
+            .iter()
+            .map(|picture_id| {
+                (
+                    ratings::user_id.eq(user_id),
+                    ratings::picture_id.eq(*picture_id),
+                    ratings::rating.eq(rating),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(ratings::table)
+            .values(values)
+            .on_conflict((ratings::user_id, ratings::picture_id))
+            .do_update()
+            .set(ratings::rating.eq(rating))
+            .returning(Rating::as_returning())
+    }
+    /// Remove the ratings of the user for all the pictures, returning the ids of the pictures that were rated.
+    pub fn remove_batch(conn: &mut DBConn, user_id: i32, picture_ids: &[i64]) -> Result<Vec<i64>, ErrorResponder> {
+        let removed_picture_ids = Self::remove_batch_statement(user_id, picture_ids)
+            .get_results(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Picture::touch(conn, &removed_picture_ids)?;
+        Ok(removed_picture_ids)
+    }
+    pub fn remove_batch_statement(user_id: i32, picture_ids: &[i64]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+        diesel::delete(
+            ratings::table
+                .filter(ratings::user_id.eq(user_id))
+                .filter(ratings::picture_id.eq_any(picture_ids.to_vec())),
+        )
+        .returning(ratings::picture_id)
+    }
+
     pub fn from_picture_id(conn: &mut DBConn, picture_id: i64, user_id: i32) -> Result<Option<Rating>, ErrorResponder> {
         ratings::table
             .filter(ratings::dsl::picture_id.eq(picture_id))
@@ -51,6 +128,21 @@ impl Rating {
             .map_err(|e| ErrorType::DatabaseError("Failed to get ratings".to_string(), e).res())
     }
 
+    /// Gets the ratings given by the owner of the arrangement to a slice of pictures
+    pub fn from_picture_ids_of_arrangement_owner(conn: &mut DBConn, arrangement_id: i32, picture_ids: &[i64]) -> Result<Vec<Rating>, ErrorResponder> {
+        ratings::table
+            .filter(ratings::dsl::picture_id.eq_any(picture_ids))
+            .filter(
+                ratings::dsl::user_id.eq_any(
+                    arrangements::table
+                        .filter(arrangements::dsl::id.eq(arrangement_id))
+                        .select(arrangements::dsl::user_id),
+                ),
+            )
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get ratings".to_string(), e).res())
+    }
+
     /// Get rating statistics for a slice of pictures.
     /// Returned tuple contains: (average_user_rating, average_global_rating, friends user ids that have ratings for at least one picture)
     pub fn get_mixed_pictures_ratings(
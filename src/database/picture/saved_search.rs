@@ -0,0 +1,86 @@
+use crate::api::query_pictures::PicturesQuery;
+use crate::database::database::DBConn;
+use crate::database::schema::*;
+use crate::database::user::user::User;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::prelude::*;
+use diesel::{Associations, Identifiable, Queryable, Selectable};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Named [`PicturesQuery`] saved by a user, the query being stored as JSON.
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone)]
+#[diesel(primary_key(id))]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = saved_searches)]
+pub struct SavedSearch {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub query: String,
+}
+
+/// Saved search with its deserialized query
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct SavedSearchDetails {
+    pub id: i32,
+    pub name: String,
+    pub query: PicturesQuery,
+}
+
+impl SavedSearch {
+    pub fn insert(conn: &mut DBConn, user_id: i32, name: String, query: &PicturesQuery) -> Result<SavedSearch, ErrorResponder> {
+        diesel::insert_into(saved_searches::table)
+            .values((
+                saved_searches::user_id.eq(user_id),
+                saved_searches::name.eq(name),
+                saved_searches::query.eq(Self::query_to_json(query)?),
+            ))
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    /// Saved searches of the user, ordered by id
+    pub fn from_user_id(conn: &mut DBConn, user_id: i32) -> Result<Vec<SavedSearch>, ErrorResponder> {
+        saved_searches::table
+            .filter(saved_searches::user_id.eq(user_id))
+            .order_by(saved_searches::id)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn from_id_and_user_id(conn: &mut DBConn, id: i32, user_id: i32) -> Result<SavedSearch, ErrorResponder> {
+        saved_searches::table
+            .filter(saved_searches::id.eq(id))
+            .filter(saved_searches::user_id.eq(user_id))
+            .first(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?
+            .ok_or_else(|| ErrorType::NotFound(format!("saved search {}", id)).res())
+    }
+    pub fn delete(conn: &mut DBConn, id: i32) -> Result<(), ErrorResponder> {
+        diesel::delete(saved_searches::table.filter(saved_searches::id.eq(id)))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        Ok(())
+    }
+
+    /// Deserialize the stored query and return it
+    pub fn get_query(&self) -> Result<PicturesQuery, ErrorResponder> {
+        serde_json::from_str(&self.query).map_err(|e| ErrorType::InternalError(e.to_string()).res())
+    }
+    /// Stored query, to fetch the given page of results
+    pub fn query_for_page(&self, page: i32) -> Result<PicturesQuery, ErrorResponder> {
+        let mut query = self.get_query()?;
+        query.page = page;
+        Ok(query)
+    }
+    pub fn query_to_json(query: &PicturesQuery) -> Result<String, ErrorResponder> {
+        serde_json::to_string(query).map_err(|e| ErrorType::InternalError(e.to_string()).res())
+    }
+    pub fn to_details(&self) -> Result<SavedSearchDetails, ErrorResponder> {
+        Ok(SavedSearchDetails {
+            id: self.id,
+            name: self.name.clone(),
+            query: self.get_query()?,
+        })
+    }
+}
@@ -4,7 +4,7 @@ use diesel_derives::define_sql_function;
 use rocket_okapi::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(JsonSchema, Debug, PartialEq, Serialize, diesel_derive_enum::DbEnum)]
+#[derive(JsonSchema, Debug, PartialEq, Clone, Serialize, diesel_derive_enum::DbEnum, rocket::FromFormField)]
 #[DbValueStyle = "snake_case"]
 pub enum UserStatus {
     Unconfirmed,
@@ -26,6 +26,7 @@ table! {
         tfa_login -> Bool,
         storage_count_ko -> Int8,
         storage_limit_ko -> Int8,
+        email_tfa -> Bool,
     }
 }
 
@@ -102,6 +103,7 @@ table! {
 }
 joinable!(tag_groups -> users (user_id));
 allow_tables_to_appear_in_same_query!(tag_groups, users);
+allow_tables_to_appear_in_same_query!(tag_groups, pictures);
 
 table! {
     tags (id) {
@@ -110,6 +112,7 @@ table! {
         name -> Varchar,
         color -> Binary,
         is_default -> Bool,
+        position -> Int4,
     }
 }
 joinable!(tags -> tag_groups (tag_group_id));
@@ -161,8 +164,16 @@ table! {
         f_number -> Nullable<Decimal>,
         size_ko -> Int4,
         blurhash -> Nullable<Varchar>,
+        format -> Nullable<Varchar>,
+        favorite -> Bool,
+        perceptual_hash -> Nullable<Int8>,
+        modified_at -> Timestamp,
     }
 }
+define_sql_function! {
+    /// Converts a smallint to double precision, allowing to compute ratios.
+    fn float8(x: diesel::sql_types::SmallInt) -> diesel::sql_types::Double;
+}
 joinable!(pictures -> users (owner_id));
 //joinable!(pictures -> users (author_id));
 allow_tables_to_appear_in_same_query!(pictures, users);
@@ -171,6 +182,7 @@ table! {
     pictures_tags (picture_id, tag_id) {
         picture_id -> Int8,
         tag_id -> Int4,
+        assignment_order -> Int8,
     }
 }
 joinable!(pictures_tags -> pictures (picture_id));
@@ -192,10 +204,25 @@ table! {
         groups_dependant -> Bool,
         tags_dependant -> Bool,
         exif_dependant -> Bool,
+        ratings_dependant -> Bool,
+        edition_version -> Int4,
+        enabled -> Bool,
     }
 }
 joinable!(arrangements -> users (user_id));
 allow_tables_to_appear_in_same_query!(arrangements, users);
+allow_tables_to_appear_in_same_query!(arrangements, pictures);
+
+table! {
+    saved_searches (id) {
+        id -> Serial,
+        user_id -> Int4,
+        name -> Varchar,
+        query -> Text,
+    }
+}
+joinable!(saved_searches -> users (user_id));
+allow_tables_to_appear_in_same_query!(saved_searches, users);
 
 table! {
     groups (id) {
@@ -204,11 +231,13 @@ table! {
         share_match_conversion -> Bool,
         name -> Varchar,
         to_be_deleted -> Bool,
+        color -> Nullable<Binary>,
     }
 }
 joinable!(groups -> arrangements (arrangement_id));
 allow_tables_to_appear_in_same_query!(groups, arrangements);
 allow_tables_to_appear_in_same_query!(groups, pictures);
+allow_tables_to_appear_in_same_query!(groups, users);
 
 table! {
     groups_pictures (group_id, picture_id) {
@@ -220,6 +249,19 @@ joinable!(groups_pictures -> groups (group_id));
 joinable!(groups_pictures -> pictures (picture_id));
 allow_tables_to_appear_in_same_query!(groups_pictures, groups);
 allow_tables_to_appear_in_same_query!(groups_pictures, pictures);
+allow_tables_to_appear_in_same_query!(groups_pictures, arrangements);
+
+table! {
+    manual_overrides (group_id, picture_id) {
+        group_id -> Int4,
+        picture_id -> Int8,
+        force_in -> Bool,
+    }
+}
+joinable!(manual_overrides -> groups (group_id));
+joinable!(manual_overrides -> pictures (picture_id));
+allow_tables_to_appear_in_same_query!(manual_overrides, groups);
+allow_tables_to_appear_in_same_query!(manual_overrides, pictures);
 
 table! {
     link_share_groups (token) {
@@ -237,6 +279,7 @@ table! {
     shared_groups (user_id, group_id) {
         user_id -> Int4,
         group_id -> Int4,
+        // Bits : View / Edit tags / Add pictures (see SharePermissions)
         permissions -> Int2,
         match_conversion_group_id -> Nullable<Int4>,
         copied -> Bool,
@@ -247,6 +290,7 @@ joinable!(shared_groups -> groups (group_id));
 joinable!(shared_groups -> users (user_id));
 //joinable!(shared_groups -> groups (match_conversion_group_id));
 allow_tables_to_appear_in_same_query!(shared_groups, groups);
+allow_tables_to_appear_in_same_query!(shared_groups, arrangements);
 allow_tables_to_appear_in_same_query!(shared_groups, groups_pictures);
 allow_tables_to_appear_in_same_query!(shared_groups, pictures);
 allow_tables_to_appear_in_same_query!(shared_groups, users);
@@ -307,3 +351,4 @@ joinable!(ratings -> pictures (picture_id));
 allow_tables_to_appear_in_same_query!(ratings, users);
 allow_tables_to_appear_in_same_query!(ratings, pictures);
 allow_tables_to_appear_in_same_query!(ratings, friends);
+allow_tables_to_appear_in_same_query!(ratings, arrangements);
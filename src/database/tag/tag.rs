@@ -1,11 +1,15 @@
 use crate::database::database::DBConn;
+use crate::database::picture::picture_tag::PictureTag;
 use crate::database::schema::*;
 use crate::database::tag::tag_group::TagGroup;
+use crate::utils::color::hex_color;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use diesel::query_dsl::InternalJoinDsl;
 use diesel::{
-    Associations, ExpressionMethods, Identifiable, Insertable, JoinOnDsl, OptionalExtension, QueryDsl, Queryable, RunQueryDsl, Selectable, Table,
+    Associations, ExpressionMethods, Identifiable, Insertable, JoinOnDsl, OptionalExtension, QueryDsl, Queryable, RunQueryDsl, Selectable,
+    SelectableHelper, Table,
 };
+use itertools::Itertools;
 use rocket::yansi::Paint;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,38 +22,95 @@ pub struct Tag {
     pub id: i32,
     pub tag_group_id: i32,
     pub name: String,
+    /// RGB color, (de)serialized as a `#RRGGBB` hex string
+    #[serde(with = "hex_color")]
+    #[schemars(with = "String")]
     pub color: Vec<u8>,
     pub is_default: bool,
+    /// Position of the tag within its tag group, tags are listed by ascending position.
+    #[serde(default)]
+    pub position: i32,
 }
 
 impl Tag {
     pub fn insert(conn: &mut DBConn, mut tag: Tag) -> Result<Tag, ErrorResponder> {
+        Self::validate_color(&tag.color)?;
         diesel::insert_into(tags::table)
             .values((
                 tags::tag_group_id.eq(tag.tag_group_id),
                 tags::name.eq(&tag.name.clone()),
                 tags::color.eq(tag.color.clone()),
                 tags::is_default.eq(tag.is_default),
+                tags::position.eq(tag.position),
             ))
             .get_result(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
-    // Edit a tag name, color, and default
+    // Edit a tag name, color, and default, returning the tag as stored
     pub fn patch(conn: &mut DBConn, tag: Tag) -> Result<Tag, ErrorResponder> {
-        let _ = diesel::update(tags::table.find(tag.id))
+        Self::validate_color(&tag.color)?;
+        diesel::update(tags::table.find(tag.id))
             .set((tags::name.eq(&tag.name), tags::color.eq(&tag.color), tags::is_default.eq(tag.is_default)))
-            .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
-        Ok(tag)
+            .returning(Tag::as_returning())
+            .get_result(conn)
+            .optional()
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?
+            .ok_or_else(|| ErrorType::TagNotFound.res())
     }
 
-    /// List all TagGroup's tags
+    /// Check that the color is a 3 bytes RGB color, otherwise returns `InvalidInput`.
+    pub fn validate_color(color: &[u8]) -> Result<(), ErrorResponder> {
+        if color.len() != 3 {
+            return ErrorType::InvalidInput(format!("Tag color must be 3 bytes (RGB), got {} bytes", color.len())).res_err();
+        }
+        Ok(())
+    }
+    /// List all TagGroup's tags, sorted by position
     pub fn list_tags(conn: &mut DBConn, tag_group_id: i32) -> Result<Vec<Tag>, ErrorResponder> {
         tags::table
             .filter(tags::tag_group_id.eq(tag_group_id))
+            .order_by((tags::position, tags::id))
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
+    /// Reorder the tags of a tag group following `tag_ids`, that must contain exactly all the tag group's tag ids.
+    /// Returns the tags sorted by their new position.
+    pub fn reorder(conn: &mut DBConn, tag_group_id: i32, tag_ids: &[i32]) -> Result<Vec<Tag>, ErrorResponder> {
+        let tags = Self::apply_order(Self::list_tags(conn, tag_group_id)?, tag_ids)?;
+        for tag in &tags {
+            diesel::update(tags::table.find(tag.id))
+                .set(tags::position.eq(tag.position))
+                .execute(conn)
+                .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        }
+        Ok(tags)
+    }
+    /// Set the position of each tag to its index in `tag_ids`, and return the tags sorted by position.
+    /// Fails with `InvalidInput` if `tag_ids` is not exactly the set of ids of `tags` (unknown, missing or duplicated ids).
+    pub fn apply_order(tags: Vec<Tag>, tag_ids: &[i32]) -> Result<Vec<Tag>, ErrorResponder> {
+        if tag_ids.len() != tags.len() || tag_ids.iter().unique().count() != tag_ids.len() {
+            return ErrorType::InvalidInput("Tag order must contain each tag of the tag group exactly once".to_string()).res_err_no_rollback();
+        }
+        let mut tags = tags;
+        for tag in tags.iter_mut() {
+            tag.position = tag_ids
+                .iter()
+                .position(|id| *id == tag.id)
+                .ok_or_else(|| ErrorType::InvalidInput(format!("Tag {} is missing from the tag order", tag.id)).res_no_rollback())?
+                as i32;
+        }
+        tags.sort_by_key(|tag| tag.position);
+        Ok(tags)
+    }
+    /// Check that the tag can be unassigned from all pictures at once:
+    /// the default tag of a required tag group can't, as it is the one pictures fall back to.
+    pub fn check_assignments_removable(&self, tag_group: &TagGroup) -> Result<(), ErrorResponder> {
+        if tag_group.required && self.is_default {
+            return ErrorType::UnprocessableEntity("The default tag of a required tag group can't be removed from all pictures".to_string())
+                .res_err_no_rollback();
+        }
+        Ok(())
+    }
     pub fn from_id_with_tag_group(conn: &mut DBConn, tag_id: i32) -> Result<(Tag, TagGroup), ErrorResponder> {
         tags::table
             .inner_join(tag_groups::table.on(tags::tag_group_id.eq(tag_groups::id)))
@@ -76,9 +137,7 @@ impl Tag {
     }
     pub fn delete(conn: &mut DBConn, id: i32) -> Result<usize, ErrorResponder> {
         // Delete all pictures with this tag
-        diesel::delete(pictures_tags::table.filter(pictures_tags::tag_id.eq(id)))
-            .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())?;
+        PictureTag::remove_all_pictures(conn, id)?;
 
         diesel::delete(tags::table.filter(tags::id.eq(id)))
             .execute(conn)
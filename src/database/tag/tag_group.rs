@@ -1,14 +1,18 @@
 use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
 use crate::database::picture::picture_tag::PictureTag;
 use crate::database::schema::*;
 use crate::database::tag::tag::Tag;
 use crate::database::user::user::User;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use diesel::dsl::{exists, not};
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
 use diesel::{Associations, Identifiable, Queryable, RunQueryDsl, Selectable};
 use diesel::{BoolExpressionMethods, JoinOnDsl};
 use diesel::{EqAll, QueryDsl};
-use diesel::{ExpressionMethods, OptionalExtension};
+use diesel::{ExpressionMethods, OptionalExtension, SelectableHelper};
 use itertools::Itertools;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -27,23 +31,209 @@ pub struct TagGroup {
     pub required: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub struct TagGroupWithTags {
     pub tag_group: TagGroup,
     pub tags: Vec<Tag>,
 }
 
+/// Constraint of a tag group that the tags of a picture (or the default tags) don't satisfy.
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum TagGroupViolation {
+    /// The tag group is required, but none of its tags is set.
+    MissingRequiredTag { tag_group_id: i32 },
+    /// The tag group is not multiple, but several of its tags are set.
+    MultipleTags { tag_group_id: i32, tag_ids: Vec<i32> },
+}
+
+/// Changes to the tags of pictures making them comply with the constraints of a tag group (see [`TagGroupWithTags::repair`]).
+#[derive(Debug, PartialEq)]
+pub struct TagGroupRepair {
+    pub tag_group_id: i32,
+    /// Default tags of the tag group, to add to the pictures of `defaulted_picture_ids`
+    pub default_tag_ids: Vec<i32>,
+    /// Pictures without any tag of the required tag group
+    pub defaulted_picture_ids: Vec<i64>,
+    /// Pictures without any tag of the required tag group, left as they are as the tag group has no default tag
+    pub unresolved_picture_ids: Vec<i64>,
+    /// (picture_id, tag_id) assignments to remove, from pictures having several tags of the non-multiple tag group
+    pub removed_tags: Vec<(i64, i32)>,
+}
+impl TagGroupRepair {
+    pub fn apply(&self, conn: &mut DBConn) -> Result<(), ErrorResponder> {
+        if !self.defaulted_picture_ids.is_empty() {
+            PictureTag::add_pictures_batch(conn, &self.default_tag_ids, &self.defaulted_picture_ids)?;
+        }
+        for (tag_id, removed) in self.removed_tags.iter().into_group_map_by(|(_, tag_id)| *tag_id) {
+            let picture_ids = removed.into_iter().map(|(picture_id, _)| *picture_id).collect_vec();
+            PictureTag::remove_pictures(conn, tag_id, &picture_ids)?;
+        }
+        Ok(())
+    }
+}
+
+/// Changes made to the tags of a tag group by a compliance repair.
+#[derive(Debug, Default, PartialEq, Serialize, JsonSchema)]
+pub struct TagGroupRepairReport {
+    pub tag_group_id: i32,
+    /// Number of pictures that got the default tags of the required tag group
+    pub defaulted_pictures: usize,
+    /// Number of pictures still missing a tag of the required tag group, as it has no default tag
+    pub unresolved_pictures: usize,
+    /// Number of tags removed from pictures having several tags of the non-multiple tag group
+    pub removed_tags: usize,
+}
+impl TagGroupRepairReport {
+    pub fn add(&mut self, repair: &TagGroupRepair) {
+        self.tag_group_id = repair.tag_group_id;
+        self.defaulted_pictures += repair.defaulted_picture_ids.len();
+        self.unresolved_pictures += repair.unresolved_picture_ids.len();
+        self.removed_tags += repair.removed_tags.len();
+    }
+}
+
+impl TagGroupWithTags {
+    /// Check the default tags of a new tag group (see [`TagGroup::check_default_tag_ids`]).
+    pub fn check_default_tags(&self) -> Result<(), ErrorResponder> {
+        let default_tag_ids = self.tags.iter().filter(|tag| tag.is_default).map(|tag| tag.id).collect_vec();
+        self.tag_group.check_default_tag_ids(&default_tag_ids)
+    }
+    /// Constraints of the tag groups violated by the tags of a picture, `tag_ids` being all the tags of the picture.
+    pub fn violations(tag_groups: &[TagGroupWithTags], tag_ids: &[i32]) -> Vec<TagGroupViolation> {
+        tag_groups
+            .iter()
+            .sorted_by_key(|tag_group| tag_group.tag_group.id)
+            .filter_map(|tag_group| {
+                let group_tag_ids = tag_group
+                    .tags
+                    .iter()
+                    .map(|tag| tag.id)
+                    .filter(|tag_id| tag_ids.contains(tag_id))
+                    .collect_vec();
+                tag_group.tag_group.violation(group_tag_ids)
+            })
+            .collect()
+    }
+    /// Changes making the pictures comply with the constraints of the tag group, from their (picture_id, tag_id) assignments
+    /// in the order they were assigned: pictures missing a tag of a required group get its default tags, and pictures having
+    /// several tags of a non-multiple group keep only one of them, the default tag if they have it, otherwise the most recently assigned one.
+    /// Pictures missing a tag of a required group without default tags can't be repaired, they are reported as unresolved.
+    /// Returns `None` if all the pictures already comply.
+    pub fn repair(&self, picture_ids: &[i64], pictures_tags: &[(i64, i32)]) -> Option<TagGroupRepair> {
+        let mut repair = TagGroupRepair {
+            tag_group_id: self.tag_group.id.unwrap_or_default(),
+            default_tag_ids: self.tags.iter().filter(|tag| tag.is_default).map(|tag| tag.id).collect(),
+            defaulted_picture_ids: vec![],
+            unresolved_picture_ids: vec![],
+            removed_tags: vec![],
+        };
+        for picture_id in picture_ids.iter() {
+            let group_tag_ids = pictures_tags
+                .iter()
+                .filter(|(tag_picture_id, tag_id)| tag_picture_id == picture_id && self.tags.iter().any(|tag| tag.id == *tag_id))
+                .map(|(_, tag_id)| *tag_id)
+                .collect_vec();
+            match self.tag_group.violation(group_tag_ids) {
+                Some(TagGroupViolation::MissingRequiredTag { .. }) if repair.default_tag_ids.is_empty() => {
+                    repair.unresolved_picture_ids.push(*picture_id)
+                }
+                Some(TagGroupViolation::MissingRequiredTag { .. }) => repair.defaulted_picture_ids.push(*picture_id),
+                Some(TagGroupViolation::MultipleTags { tag_ids, .. }) => {
+                    let kept_tag_id = tag_ids
+                        .iter()
+                        .find(|tag_id| repair.default_tag_ids.contains(tag_id))
+                        .or_else(|| tag_ids.last())
+                        .cloned();
+                    repair.removed_tags.extend(
+                        tag_ids
+                            .into_iter()
+                            .filter(|tag_id| Some(*tag_id) != kept_tag_id)
+                            .map(|tag_id| (*picture_id, tag_id)),
+                    );
+                }
+                None => {}
+            }
+        }
+        if repair.defaulted_picture_ids.is_empty() && repair.unresolved_picture_ids.is_empty() && repair.removed_tags.is_empty() {
+            return None;
+        }
+        Some(repair)
+    }
+}
+
 impl TagGroup {
-    pub fn insert(conn: &mut DBConn, mut tag_group: TagGroup) -> Result<TagGroup, ErrorResponder> {
+    /// Check that the tag group can be deleted, given the enabled arrangements depending on it:
+    /// the deletion would break them, so it is refused unless `force` is set (the arrangements then being disabled).
+    pub fn check_deletable(dependant_arrangements: &[Arrangement], force: bool) -> Result<(), ErrorResponder> {
+        if dependant_arrangements.is_empty() || force {
+            return Ok(());
+        }
+        ErrorType::UnprocessableEntity(format!(
+            "Tag group is used by the arrangements {}, use force to delete it anyway and disable them",
+            dependant_arrangements
+                .iter()
+                .map(|arrangement| format!("\"{}\"", arrangement.name))
+                .join(", ")
+        ))
+        .res_err_no_rollback()
+    }
+    /// Check that a user having `count` tag groups can create a new one, given the per-user `limit`.
+    pub fn check_user_limit(count: i64, limit: i64) -> Result<(), ErrorResponder> {
+        if count >= limit {
+            return ErrorType::UnprocessableEntity(format!("Users can't have more than {} tag groups", limit)).res_err();
+        }
+        Ok(())
+    }
+    pub fn count_for_user(conn: &mut DBConn, user_id: i32) -> Result<i64, ErrorResponder> {
+        tag_groups::table
+            .filter(tag_groups::user_id.eq(user_id))
+            .count()
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    /// Constraint of the tag group violated when exactly the tags of `tag_ids`, of this group, are set:
+    ///  - If the group is required, there must be at least one tag.
+    ///  - If the group is not multiple, there can't be more than one tag.
+    pub fn violation(&self, tag_ids: Vec<i32>) -> Option<TagGroupViolation> {
+        let tag_group_id = self.id.unwrap_or_default();
+        if self.required && tag_ids.is_empty() {
+            return Some(TagGroupViolation::MissingRequiredTag { tag_group_id });
+        }
+        if !self.multiple && tag_ids.len() > 1 {
+            return Some(TagGroupViolation::MultipleTags { tag_group_id, tag_ids });
+        }
+        None
+    }
+    /// Check the default tags of the tag group, which must satisfy its constraints (see [`TagGroup::violation`]).
+    pub fn check_default_tag_ids(&self, default_tag_ids: &[i32]) -> Result<(), ErrorResponder> {
+        match self.violation(default_tag_ids.to_vec()) {
+            Some(TagGroupViolation::MissingRequiredTag { .. }) => {
+                ErrorType::UnprocessableEntity("Required tag group must have at least one default tag".to_string()).res_err()
+            }
+            Some(TagGroupViolation::MultipleTags { .. }) => {
+                ErrorType::UnprocessableEntity("Multiple tag group can't have more than one default tag".to_string()).res_err()
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn insert(conn: &mut DBConn, tag_group: TagGroup) -> Result<TagGroup, ErrorResponder> {
+        Self::insert_statement(tag_group)
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    /// The inserted tag group is returned by the `INSERT` itself (`RETURNING`), with the id of the inserted row.
+    /// The id of `tag_group` is ignored.
+    pub fn insert_statement(tag_group: TagGroup) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, TagGroup> {
         diesel::insert_into(tag_groups::table)
             .values((
                 tag_groups::user_id.eq(tag_group.user_id),
-                tag_groups::name.eq(&tag_group.name.clone()),
+                tag_groups::name.eq(tag_group.name),
                 tag_groups::multiple.eq(tag_group.multiple),
                 tag_groups::required.eq(tag_group.required),
             ))
-            .get_result(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+            .returning(TagGroup::as_returning())
     }
     // Edit a tag group name, multiple, and required, works only if the user owns the tag group
     pub fn patch(conn: &mut DBConn, tag_group: TagGroup, user_id: i32) -> Result<TagGroup, ErrorResponder> {
@@ -70,6 +260,7 @@ impl TagGroup {
         tag_groups::table
             .inner_join(tags::table)
             .filter(tag_groups::user_id.eq(user_id))
+            .order_by((tag_groups::id, tags::position, tags::id))
             .load(conn)
             .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
     }
@@ -162,10 +353,6 @@ impl TagGroup {
     /// Remove tags of this tag group from pictures
     pub fn remove_pictures(&self, conn: &mut DBConn, picture_ids: &Vec<i64>) -> Result<usize, ErrorResponder> {
         let tag_ids = Tag::list_tags(conn, self.id.unwrap())?.iter().map(|tag| tag.id).collect::<Vec<i32>>();
-        diesel::delete(pictures_tags::table)
-            .filter(pictures_tags::tag_id.eq_any(tag_ids))
-            .filter(pictures_tags::picture_id.eq_any(picture_ids))
-            .execute(conn)
-            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+        PictureTag::remove_pictures_batch(conn, &tag_ids, picture_ids)
     }
 }
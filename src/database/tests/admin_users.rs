@@ -0,0 +1,228 @@
+use crate::api::auth::signin::check_second_factor;
+use crate::database::database::DBConn;
+use crate::database::integrity_scan::IntegrityReport;
+use crate::database::schema::*;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection, user};
+use crate::database::user::totp_secret::TOTPSecret;
+use crate::database::user::user::{StorageCorrection, User};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use crate::utils::utils::like_contains_pattern;
+use chrono::Utc;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+#[test]
+pub fn test_like_contains_pattern() {
+    assert_eq!(like_contains_pattern("doe@"), "%doe@%");
+    assert_eq!(like_contains_pattern(""), "%%");
+    // LIKE wildcards are matched literally
+    assert_eq!(like_contains_pattern("a_b%c"), "%a\\_b\\%c%");
+    assert_eq!(like_contains_pattern("a\\b"), "%a\\\\b%");
+}
+
+#[test]
+pub fn test_admin_search_by_status() {
+    let sql = debug_query::<Pg, _>(&User::admin_search_statement(Some(UserStatus::Banned), None)).to_string();
+    assert!(sql.contains("WHERE (\"users\".\"status\" = $1)"));
+    assert!(sql.contains("Banned"));
+    assert!(!sql.contains("ILIKE"));
+}
+
+#[test]
+pub fn test_admin_search_by_email_fragment() {
+    let sql = debug_query::<Pg, _>(&User::admin_search_statement(None, Some("Doe@Example"))).to_string();
+    assert!(sql.contains("WHERE (\"users\".\"email\" ILIKE $1)"));
+    assert!(sql.contains("\"%Doe@Example%\""));
+    assert!(!sql.contains("\"users\".\"status\" ="));
+
+    // Both filters combined
+    let sql = debug_query::<Pg, _>(&User::admin_search_statement(Some(UserStatus::Normal), Some("doe"))).to_string();
+    assert!(sql.contains("(\"users\".\"status\" = $1) AND (\"users\".\"email\" ILIKE $2)"));
+}
+
+#[test]
+pub fn test_admin_search_without_filters() {
+    let sql = debug_query::<Pg, _>(&User::admin_search_statement(None, None)).to_string();
+    assert!(!sql.contains("WHERE"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_admin_search_matches_the_email_fragment_literally_and_the_status() {
+    let conn = &mut test_connection();
+    let underscore_id = insert_user(conn, "a_b.admin_search");
+    let any_char_id = insert_user(conn, "axb.admin_search");
+    let percent_id = insert_user(conn, "a%b.admin_search");
+    diesel::update(users::table.find(percent_id))
+        .set(users::status.eq(UserStatus::Banned))
+        .execute(conn)
+        .unwrap();
+    let search = |conn: &mut DBConn, status: Option<UserStatus>, email_contains: &str| {
+        let (users, total) = User::admin_search(conn, status, Some(email_contains), 1, 100).unwrap();
+        assert_eq!(total, users.len() as i64);
+        users.iter().map(|user| user.id).collect::<Vec<_>>()
+    };
+
+    // Case-insensitive, `_` and `%` only match themselves
+    assert_eq!(search(conn, None, "B.ADMIN_SEARCH@"), vec![underscore_id, any_char_id, percent_id]);
+    assert_eq!(search(conn, None, "a_b.admin_search"), vec![underscore_id]);
+    assert_eq!(search(conn, None, "a%b.admin_search"), vec![percent_id]);
+    // The status filter is combined with the email fragment, new users being unconfirmed
+    assert_eq!(search(conn, Some(UserStatus::Banned), "b.admin_search@"), vec![percent_id]);
+    assert_eq!(
+        search(conn, Some(UserStatus::Unconfirmed), "b.admin_search@"),
+        vec![underscore_id, any_char_id]
+    );
+}
+
+fn user_with_storage(storage_count_ko: i64, storage_limit_ko: i64) -> User {
+    User {
+        storage_count_ko,
+        storage_limit_ko,
+        ..user(1)
+    }
+}
+
+#[test]
+pub fn test_check_storage_limit() {
+    let user = user_with_storage(500, 1000);
+    assert!(user.check_storage_limit(2000, false).is_ok());
+    assert!(user.check_storage_limit(500, false).is_ok());
+    assert!(user.check_storage_limit(0, true).is_ok());
+
+    // Negative limit, even with force
+    let error = ErrorResponse::from(user.check_storage_limit(-1, true).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+}
+
+#[test]
+pub fn test_storage_limit_below_usage_requires_force() {
+    let user = user_with_storage(500, 1000);
+    let error = ErrorResponse::from(user.check_storage_limit(499, false).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+    assert!(user.check_storage_limit(499, true).is_ok());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_reset_totp_allows_signin_without_code() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "reset_totp");
+    diesel::update(users::table.find(user_id))
+        .set(users::tfa_login.eq(true))
+        .execute(conn)
+        .unwrap();
+    TOTPSecret::insert_secret_for_user(conn, &user_id, &vec![1; 20]).unwrap();
+    // A user with 2FA and a TOTP secret needs a code
+    let error = ErrorResponse::from(check_second_factor(true, false, true, None).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::TFARequired));
+
+    let (user, deleted_secrets) = User::reset_totp(conn, user_id).unwrap();
+    assert_eq!(deleted_secrets, 1);
+    assert!(!user.tfa_login);
+    let remaining_secrets: i64 = totp_secrets::table
+        .filter(totp_secrets::user_id.eq(user_id))
+        .count()
+        .get_result(conn)
+        .unwrap();
+    assert_eq!(remaining_secrets, 0);
+
+    // Once reset, no code is required anymore
+    let user = User::from_id(conn, &user_id).unwrap();
+    assert!(!user.tfa_login);
+    assert!(check_second_factor(user.tfa_login, user.email_tfa, false, None).is_ok());
+}
+
+#[test]
+pub fn test_email_tfa_requires_email_confirmation() {
+    let mut user = user_with_storage(0, 1000);
+    // Without 2FA, no code is required
+    assert!(check_second_factor(user.tfa_login, user.email_tfa, false, None).is_ok());
+
+    // Once email 2FA is enabled, the next sign in of a user without TOTP goes through the email confirmation
+    user.email_tfa = true;
+    let error = ErrorResponse::from(check_second_factor(user.tfa_login, user.email_tfa, false, None).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::TFARequiredOverEmail));
+
+    // Users with a TOTP secret are not affected unless 2FA is enabled
+    assert!(check_second_factor(user.tfa_login, user.email_tfa, true, None).is_ok());
+    user.tfa_login = true;
+    let error = ErrorResponse::from(check_second_factor(user.tfa_login, user.email_tfa, true, None).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::TFARequired));
+    assert!(check_second_factor(user.tfa_login, user.email_tfa, true, Some(true)).is_ok());
+}
+
+#[test]
+pub fn test_recompute_storage_corrects_drifted_counters() {
+    // User 1 is up to date, user 2 never had their uploads counted, user 3 has no pictures left but a leftover count
+    let users = [(1, 300), (2, 0), (3, 50)];
+    let pictures_sizes_ko = HashMap::from([(1, 300), (2, 1200)]);
+    assert_eq!(
+        User::storage_corrections(&users, &pictures_sizes_ko),
+        vec![
+            StorageCorrection {
+                user_id: 2,
+                previous_ko: 0,
+                corrected_ko: 1200,
+                delta_ko: 1200,
+            },
+            StorageCorrection {
+                user_id: 3,
+                previous_ko: 50,
+                corrected_ko: 0,
+                delta_ko: -50,
+            },
+        ]
+    );
+
+    let sql = debug_query::<Pg, _>(&User::set_storage_count_statement(2, 1200)).to_string();
+    assert_eq!(
+        sql,
+        "UPDATE \"users\" SET \"storage_count_ko\" = $1 WHERE (\"users\".\"id\" = $2) -- binds: [1200, 2]"
+    );
+    // Pictures in the trash don't count
+    let sql = debug_query::<Pg, _>(&IntegrityReport::owned_pictures_size_statement(&[2])).to_string();
+    assert!(sql.contains("\"pictures\".\"owner_id\" = ANY($1)) AND (\"pictures\".\"deleted_date\" IS NULL)"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_recompute_all_storage_corrects_a_drifted_counter() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "drifted_storage");
+    let picture_ids = [insert_picture(conn, user_id, &[]), insert_picture(conn, user_id, &[])];
+    diesel::update(pictures::table.find(picture_ids[0]))
+        .set(pictures::size_ko.eq(300))
+        .execute(conn)
+        .unwrap();
+    // A picture in the trash doesn't count
+    let trashed_id = insert_picture(conn, user_id, &[]);
+    diesel::update(pictures::table.find(trashed_id))
+        .set(pictures::deleted_date.eq(Some(Utc::now().naive_utc())))
+        .execute(conn)
+        .unwrap();
+    User::set_storage_count_statement(user_id, 50).execute(conn).unwrap();
+
+    let report = User::recompute_all_storage(conn, 2).unwrap();
+    assert!(report.scanned_users >= 1);
+    assert_eq!(
+        report
+            .corrections
+            .iter()
+            .filter(|correction| correction.user_id == user_id)
+            .collect::<Vec<_>>(),
+        vec![&StorageCorrection {
+            user_id,
+            previous_ko: 50,
+            corrected_ko: 301,
+            delta_ko: 251,
+        }]
+    );
+    assert_eq!(User::from_id(conn, &user_id).unwrap().storage_count_ko, 301);
+
+    // Once corrected, the counter is left as is
+    let report = User::recompute_all_storage(conn, 2).unwrap();
+    assert!(!report.corrections.iter().any(|correction| correction.user_id == user_id));
+}
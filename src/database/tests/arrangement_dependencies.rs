@@ -0,0 +1,116 @@
+use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType, ArrangementDetails};
+use crate::database::tag::tag_group::TagGroup;
+use crate::grouping::arrangement_strategy::ArrangementStrategy;
+use crate::grouping::group_by_filter::FilterGrouping;
+use crate::grouping::group_by_tag::TagGrouping;
+use crate::grouping::strategy_filtering::FilterType;
+use crate::grouping::strategy_grouping::StrategyGrouping;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use std::collections::BTreeMap;
+
+fn tags_grouping_arrangement() -> Arrangement {
+    let strategy = Some(ArrangementStrategy {
+        filter: FilterType::IncludeGroups(vec![1]).to_strategy(),
+        groupings: StrategyGrouping::GroupByTags(TagGrouping {
+            tag_group_id: 1,
+            tag_id_to_group_id: BTreeMap::new(),
+            other_group_id: None,
+            group_names_format: "{}".to_string(),
+        }),
+        preserve_unicity: true,
+    });
+    let dependency_type = ArrangementDependencyType::from(&strategy);
+    Arrangement {
+        id: 1,
+        user_id: 1,
+        name: "By tag".to_string(),
+        strong_match_conversion: false,
+        strategy: Arrangement::strategy_to_binary(&strategy).unwrap(),
+        groups_dependant: dependency_type.groups_dependant,
+        tags_dependant: dependency_type.tags_dependant,
+        exif_dependant: dependency_type.exif_dependant,
+        ratings_dependant: dependency_type.ratings_dependant,
+        edition_version: 0,
+        enabled: true,
+    }
+}
+
+#[test]
+pub fn test_synced_dependency_flags_are_kept() {
+    let mut arrangement = tags_grouping_arrangement();
+    assert!(arrangement.groups_dependant);
+    assert!(arrangement.tags_dependant);
+    assert!(!arrangement.recompute_dependency_flags().unwrap());
+}
+
+#[test]
+pub fn test_desynced_dependency_flags_are_fixed() {
+    let mut arrangement = tags_grouping_arrangement();
+    arrangement.tags_dependant = false;
+    arrangement.exif_dependant = true;
+
+    assert!(arrangement.recompute_dependency_flags().unwrap());
+    assert!(arrangement.groups_dependant);
+    assert!(arrangement.tags_dependant);
+    assert!(!arrangement.exif_dependant);
+    // Already fixed
+    assert!(!arrangement.recompute_dependency_flags().unwrap());
+}
+
+#[test]
+pub fn test_manual_arrangement_has_no_dependency() {
+    let mut arrangement = tags_grouping_arrangement();
+    arrangement.strategy = None;
+
+    assert!(arrangement.recompute_dependency_flags().unwrap());
+    assert_eq!(ArrangementDependencyType::from(&arrangement), ArrangementDependencyType::new_none());
+}
+
+fn details(arrangement: Arrangement) -> ArrangementDetails {
+    let strategy = arrangement.get_strategy().unwrap().unwrap();
+    ArrangementDetails {
+        dependant_groups: strategy.get_dependant_groups(),
+        arrangement,
+        strategy,
+        dependant_arrangements: vec![],
+    }
+}
+
+#[test]
+pub fn test_tag_group_deletion_is_refused_when_referenced() {
+    // Grouping by the tag group 1
+    let grouping = details(tags_grouping_arrangement());
+    // Filtering on the tag 12 of the tag group 2
+    let mut filtering = tags_grouping_arrangement();
+    filtering.id = 2;
+    filtering.name = "Filtered".to_string();
+    let strategy = Some(ArrangementStrategy {
+        filter: FilterType::IncludeTags(vec![12]).to_strategy(),
+        groupings: StrategyGrouping::GroupByFilter(FilterGrouping {
+            filters: vec![],
+            other_group_id: None,
+        }),
+        preserve_unicity: true,
+    });
+    filtering.strategy = Arrangement::strategy_to_binary(&strategy).unwrap();
+    let filtering = details(filtering);
+    let arrangements = vec![grouping, filtering];
+
+    let dependants = ArrangementDetails::depending_on_tag_group(&arrangements, 1, &[10, 11]);
+    assert_eq!(dependants.iter().map(|a| a.id).collect::<Vec<_>>(), vec![1]);
+    let error = ErrorResponse::from(TagGroup::check_deletable(&dependants, false).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+    assert!(error.message.contains("\"By tag\""));
+    // Forced, the arrangements get disabled instead
+    assert!(TagGroup::check_deletable(&dependants, true).is_ok());
+
+    let dependants = ArrangementDetails::depending_on_tag_group(&arrangements, 2, &[12, 13]);
+    assert_eq!(dependants.iter().map(|a| a.id).collect::<Vec<_>>(), vec![2]);
+
+    // Unreferenced tag group, or disabled arrangements
+    let dependants = ArrangementDetails::depending_on_tag_group(&arrangements, 3, &[14]);
+    assert!(TagGroup::check_deletable(&dependants, false).is_ok());
+    let mut disabled = arrangements.clone();
+    disabled.iter_mut().for_each(|details| details.arrangement.enabled = false);
+    assert!(ArrangementDetails::depending_on_tag_group(&disabled, 1, &[10, 11]).is_empty());
+}
@@ -0,0 +1,128 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tests::test_database::{arrangement, insert_picture, insert_user, test_connection};
+use crate::utils::errors_catcher::{ErrorResponder, ErrorResponse, ErrorTypeKind};
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+#[test]
+pub fn test_check_edition_version() {
+    let arrangement = Arrangement {
+        edition_version: 3,
+        ..arrangement(1)
+    };
+    assert!(arrangement.check_edition_version(3).is_ok());
+}
+
+#[test]
+pub fn test_stale_edition_version_is_rejected() {
+    let arrangement = Arrangement {
+        edition_version: 3,
+        ..arrangement(1)
+    };
+    let error = arrangement.check_edition_version(2).unwrap_err();
+    assert!(matches!(error, ErrorResponder::Conflict(_)));
+    let response = ErrorResponse::from(error);
+    assert!(matches!(response.error_type, ErrorTypeKind::Conflict));
+
+    // A version from the future is also rejected
+    let error = arrangement.check_edition_version(4).unwrap_err();
+    assert!(matches!(error, ErrorResponder::Conflict(_)));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_update_is_applied_only_at_the_current_edition_version() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "edition");
+    let arrangement = Arrangement::new(conn, user_id, "Original".to_string(), false, None).unwrap();
+    assert_eq!(arrangement.edition_version, 0);
+
+    let updated = Arrangement::update(conn, arrangement.id, 0, &"Edited".to_string(), true, &None).unwrap();
+    assert_eq!(updated.name, "Edited");
+    assert_eq!(updated.edition_version, 1);
+
+    // An edition based on the previous version is rejected, and leaves the arrangement untouched
+    let error = Arrangement::update(conn, arrangement.id, 0, &"Stale".to_string(), false, &None).unwrap_err();
+    assert!(matches!(error, ErrorResponder::Conflict(_)));
+    assert_eq!(Arrangement::from_id_and_user_id(conn, arrangement.id, user_id).unwrap(), updated);
+}
+
+#[test]
+pub fn test_duplicate_arrangement_name_is_rejected() {
+    let other = Arrangement {
+        name: "By Camera".to_string(),
+        ..arrangement(2)
+    };
+    let arrangements = vec![arrangement(1), other];
+
+    // Creation
+    let error = ErrorResponse::from(Arrangement::check_name_available_among(&arrangements, "  by camera ", None).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    assert!(Arrangement::check_name_available_among(&arrangements, "By Month", None).is_ok());
+
+    // Renaming arrangement 1
+    assert!(Arrangement::check_name_available_among(&arrangements, "BY CAMERA", Some(1)).is_err());
+    assert!(Arrangement::check_name_available_among(&arrangements, "By Month", Some(1)).is_ok());
+    // Keeping its own name, or changing its case
+    assert!(Arrangement::check_name_available_among(&arrangements, "arrangement 1", Some(1)).is_ok());
+}
+
+#[test]
+pub fn test_only_manual_arrangements_are_merged() {
+    let source = arrangement(2);
+    let target = arrangement(1);
+    assert!(source.check_mergeable_into(&target, false).is_ok());
+
+    let automatic = Arrangement {
+        strategy: Some(vec![]),
+        ..arrangement(1)
+    };
+    for (source, target, used_in_hierarchy) in [
+        (&source, &automatic, false),
+        (&automatic, &source, false),
+        (&source, &source, false),
+        (&source, &target, true),
+    ] {
+        let error = source.check_mergeable_into(target, used_in_hierarchy).unwrap_err();
+        assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnprocessableEntity));
+    }
+}
+
+#[test]
+pub fn test_merge_moves_the_source_groups_to_the_target() {
+    let sql = debug_query::<Pg, _>(&Group::move_to_arrangement_statement(2, 1)).to_string();
+    assert!(sql.starts_with("UPDATE \"groups\" SET \"arrangement_id\" = $1 WHERE (\"groups\".\"arrangement_id\" = $2) RETURNING \"groups\".\"id\""));
+    assert!(sql.ends_with("binds: [1, 2]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_merged_source_arrangement_is_emptied_and_removed() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "merge");
+    let picture_id = insert_picture(conn, user_id, &[]);
+    let source = Arrangement::new(conn, user_id, "Source".to_string(), false, None).unwrap();
+    let target = Arrangement::new(conn, user_id, "Target".to_string(), false, None).unwrap();
+    let target_group = Group::insert(conn, target.id, "Target group".to_string(), false, None).unwrap();
+    let source_group = Group::insert(conn, source.id, "Source group".to_string(), false, None).unwrap();
+    let empty_source_group = Group::insert(conn, source.id, "Empty source group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, source_group.id, &vec![picture_id]).unwrap();
+
+    let mut moved_group_ids = source.merge_into(conn, &target).unwrap();
+    moved_group_ids.sort();
+    assert_eq!(moved_group_ids, vec![source_group.id, empty_source_group.id]);
+
+    // The source arrangement has no group left, and is removed
+    assert!(Group::from_arrangement_all(conn, source.id).unwrap().is_empty());
+    assert_eq!(Arrangement::from_id_and_user_id_opt(conn, source.id, user_id).unwrap(), None);
+    // The target gained the source groups, with their pictures
+    let mut target_group_ids = Group::from_arrangement_all(conn, target.id)
+        .unwrap()
+        .iter()
+        .map(|group| group.id)
+        .collect::<Vec<_>>();
+    target_group_ids.sort();
+    assert_eq!(target_group_ids, vec![target_group.id, source_group.id, empty_source_group.id]);
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![source_group.id]).unwrap(), vec![picture_id]);
+}
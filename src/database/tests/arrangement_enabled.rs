@@ -0,0 +1,73 @@
+use crate::api::groups::arrangement::apply_arrangement_enabled;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_tags, insert_user, test_connection};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::{group_pictures, select_arrangements_to_group};
+use crate::grouping::strategy_filtering::FilterType;
+use crate::grouping::tests::arrangement_sort_algorithms::create_arrangement_with_dependant_arrangements;
+
+#[test]
+pub fn test_select_arrangements_to_group_skips_disabled() {
+    let mut arrangements = vec![
+        create_arrangement_with_dependant_arrangements(1, vec![]),
+        create_arrangement_with_dependant_arrangements(2, vec![1]),
+        create_arrangement_with_dependant_arrangements(3, vec![]),
+    ];
+    arrangements[1].arrangement.enabled = false;
+
+    // The disabled arrangement receives no new pictures
+    let mut selected: Vec<i32> = select_arrangements_to_group(arrangements.clone(), 0, None, None)
+        .unwrap()
+        .iter()
+        .map(|a| a.arrangement.id)
+        .collect();
+    selected.sort();
+    assert_eq!(selected, vec![1, 3]);
+    assert!(select_arrangements_to_group(arrangements.clone(), 0, Some(2), None).unwrap().is_empty());
+
+    // Once enabled again, it is grouped again
+    arrangements[1].arrangement.enabled = true;
+    let selected: Vec<i32> = select_arrangements_to_group(arrangements.clone(), 0, None, None)
+        .unwrap()
+        .iter()
+        .map(|a| a.arrangement.id)
+        .collect();
+    assert_eq!(selected.len(), 3);
+    // Arrangement 2 depends on arrangement 1
+    assert!(selected.iter().position(|id| *id == 1) < selected.iter().position(|id| *id == 2));
+    let selected: Vec<i32> = select_arrangements_to_group(arrangements, 0, Some(2), None)
+        .unwrap()
+        .iter()
+        .map(|a| a.arrangement.id)
+        .collect();
+    assert_eq!(selected, vec![2]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_disabled_arrangement_receives_no_new_memberships_until_enabled() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "disabled_arrangement");
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let group_id = insert_filter_arrangement(
+        conn,
+        user_id,
+        "Tagged".to_string(),
+        FilterType::IncludeTags(vec![tag_ids[0]]).to_strategy(),
+    );
+    let arrangement_id = Group::from_id(conn, group_id).unwrap().arrangement_id;
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user_id).unwrap();
+    let arrangement = apply_arrangement_enabled(conn, &mut GroupingDelta::new(), user_id, &arrangement, false).unwrap();
+    assert!(!arrangement.enabled);
+
+    // The picture matches the strategy, but the disabled arrangement is skipped
+    let picture_id = insert_picture(conn, user_id, &[tag_ids[0]]);
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    assert!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap().is_empty());
+
+    // Enabling it again rebuilds its groups
+    let arrangement = apply_arrangement_enabled(conn, &mut GroupingDelta::new(), user_id, &arrangement, true).unwrap();
+    assert!(arrangement.enabled);
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap(), vec![picture_id]);
+}
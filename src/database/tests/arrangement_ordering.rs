@@ -0,0 +1,55 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::schema::arrangements;
+use crate::database::tests::test_database::{arrangement, group, insert_user, test_connection};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+#[test]
+pub fn test_arrangements_are_listed_by_id() {
+    let sql = debug_query::<Pg, _>(&Arrangement::from_user_id_statement(1)).to_string();
+    assert!(sql.contains("WHERE (\"arrangements\".\"user_id\" = $1) ORDER BY \"arrangements\".\"id\""));
+}
+
+#[test]
+pub fn test_attached_groups_keep_the_listing_order() {
+    let groups = vec![group(1, 2), group(2, 1), group(3, 2)];
+    let listed = Arrangement::attach_groups(vec![arrangement(1), arrangement(2)], &groups);
+    let ids = listed
+        .iter()
+        .map(|(arrangement, groups)| (arrangement.id, groups.iter().map(|group| group.id).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![(1, vec![2]), (2, vec![1, 3])]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_arrangements_and_groups_are_loaded_by_id_after_updates() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "arrangement_ordering");
+    let arrangement_ids = ["B", "C", "A"].map(|name| Arrangement::new(conn, user_id, name.to_string(), false, None).unwrap().id);
+    let group_ids = ["B", "C", "A"].map(|name| Group::insert(conn, arrangement_ids[1], name.to_string(), false, None).unwrap().id);
+
+    // Updated rows are stored again at the end of the table, their position in a sequential scan changes
+    diesel::update(arrangements::table.find(arrangement_ids[0]))
+        .set(arrangements::name.eq("D"))
+        .execute(conn)
+        .unwrap();
+    Group::rename(conn, group_ids[0], "D".to_string()).unwrap();
+
+    let listed = Arrangement::from_user_id_with_groups(conn, user_id).unwrap();
+    let ids = listed
+        .iter()
+        .map(|(arrangement, groups)| (arrangement.id, groups.iter().map(|group| group.id).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        vec![
+            (arrangement_ids[0], vec![]),
+            (arrangement_ids[1], group_ids.to_vec()),
+            (arrangement_ids[2], vec![])
+        ]
+    );
+    assert_eq!(listed[0].0.name, "D");
+}
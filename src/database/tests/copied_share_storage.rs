@@ -0,0 +1,115 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::{SharePermissions, SharedGroup};
+use crate::database::picture::picture::Picture;
+use crate::database::schema::*;
+use crate::database::tests::test_database::{insert_picture, insert_share, insert_user, shared_group, test_connection};
+use crate::database::user::user::User;
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::{group_add_pictures, group_remove_pictures, storage_changes};
+use crate::utils::config::Config;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use std::collections::HashSet;
+
+#[test]
+pub fn test_copied_share_counts_toward_recipient_storage() {
+    let share = SharedGroup {
+        copied: true,
+        ..shared_group(1, SharePermissions::VIEW)
+    };
+    assert!(share.counts_toward_recipient_storage(true));
+
+    // Picture 5 entered the storage of the recipient, picture 6 left it, picture 7 stays in it
+    let (charged, refunded) = storage_changes(&HashSet::from([6, 7]), &HashSet::from([5, 7]));
+    assert_eq!(charged, vec![5]);
+    assert_eq!(refunded, vec![6]);
+}
+
+#[test]
+pub fn test_other_shares_do_not_count_toward_recipient_storage() {
+    // Not copied, or still pending
+    for (copied, confirmed) in [(false, true), (true, false), (false, false)] {
+        let share = SharedGroup {
+            copied,
+            confirmed,
+            ..shared_group(1, SharePermissions::VIEW)
+        };
+        assert!(!share.counts_toward_recipient_storage(true));
+    }
+    // Policy disabled
+    let share = SharedGroup {
+        copied: true,
+        ..shared_group(1, SharePermissions::VIEW)
+    };
+    assert!(!share.counts_toward_recipient_storage(false));
+}
+
+#[test]
+pub fn test_picture_of_two_copied_shares_is_counted_once() {
+    // Picture 5 is in the groups 1 and 2, both shared with the recipient by a confirmed copied share
+    let sql = debug_query::<Pg, _>(&Picture::copied_storage_pictures_statement(2, &[5], &[])).to_string();
+    assert!(sql.starts_with("SELECT DISTINCT \"pictures\".\"id\""));
+    assert!(sql.contains("(\"shared_groups\".\"copied\" = $2)"));
+    assert!(sql.contains("(\"shared_groups\".\"confirmed\" = $3)"));
+    assert!(sql.contains("(\"pictures\".\"owner_id\" != $5)"));
+    assert!(sql.contains("(\"pictures\".\"deleted_date\" IS NULL)"));
+
+    // Added to the group 2 while already reached through the group 1 (not charged again),
+    // or removed from the group 1 while still reached through the group 2 (not refunded)
+    let (charged, refunded) = storage_changes(&HashSet::from([5]), &HashSet::from([5]));
+    assert!(charged.is_empty() && refunded.is_empty());
+    // Removed from the group 2 too: refunded once
+    let (charged, refunded) = storage_changes(&HashSet::from([5]), &HashSet::new());
+    assert!(charged.is_empty());
+    assert_eq!(refunded, vec![5]);
+
+    // When accepting the share of the group 2, the picture reached before is computed without it
+    let sql = debug_query::<Pg, _>(&Picture::copied_storage_pictures_statement(2, &[5], &[2])).to_string();
+    assert!(sql.contains("(\"shared_groups\".\"group_id\" != ALL($4))"));
+}
+
+#[test]
+pub fn test_copied_shares_count_storage_by_default() {
+    assert!(Config::default().copied_shares_count_storage);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_recipient_storage_follows_the_pictures_of_copied_shares() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "copied_share_owner");
+    let recipient_id = insert_user(conn, "copied_share_recipient");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+    diesel::update(pictures::table.find(picture_id))
+        .set(pictures::size_ko.eq(100))
+        .execute(conn)
+        .unwrap();
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let mut group_ids = vec![];
+    for name in ["First", "Second"] {
+        let group = Group::insert(conn, arrangement.id, name.to_string(), false, None).unwrap();
+        insert_share(conn, recipient_id, group.id, true);
+        group_ids.push(group.id);
+    }
+    diesel::update(shared_groups::table.filter(shared_groups::user_id.eq(recipient_id)))
+        .set(shared_groups::copied.eq(true))
+        .execute(conn)
+        .unwrap();
+    let initial_ko = User::from_id(conn, &recipient_id).unwrap().storage_count_ko;
+    let storage_ko = |conn: &mut DBConn| User::from_id(conn, &recipient_id).unwrap().storage_count_ko - initial_ko;
+
+    // Charged once when added to the first group, not again when added to the second one
+    group_add_pictures(conn, &mut GroupingDelta::new(), group_ids[0], &vec![picture_id]).unwrap();
+    assert_eq!(storage_ko(conn), 100);
+    group_add_pictures(conn, &mut GroupingDelta::new(), group_ids[1], &vec![picture_id]).unwrap();
+    assert_eq!(storage_ko(conn), 100);
+
+    // Still reached through the second group when removed from the first one, refunded once removed from both
+    group_remove_pictures(conn, &mut GroupingDelta::new(), group_ids[0], &vec![picture_id]).unwrap();
+    assert_eq!(storage_ko(conn), 100);
+    group_remove_pictures(conn, &mut GroupingDelta::new(), group_ids[1], &vec![picture_id]).unwrap();
+    assert_eq!(storage_ko(conn), 0);
+}
@@ -0,0 +1,202 @@
+use crate::database::database::DBConn;
+use crate::database::picture::picture_tag::PictureTag;
+use crate::database::schema::*;
+use crate::database::tag::tag::Tag;
+use crate::database::tag::tag_group::{TagGroup, TagGroupRepair, TagGroupRepairReport, TagGroupViolation, TagGroupWithTags};
+use crate::database::tests::test_database::{insert_picture, insert_tags, insert_user, tag, test_connection};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+fn default_tag(id: i32, tag_group_id: i32) -> Tag {
+    Tag {
+        is_default: true,
+        ..tag(id, tag_group_id)
+    }
+}
+fn tag_group_with_tags(id: i32, tags: Vec<Tag>) -> TagGroupWithTags {
+    TagGroupWithTags {
+        tag_group: TagGroup {
+            id: Some(id),
+            user_id: 1,
+            name: format!("Group {}", id),
+            multiple: true,
+            required: false,
+        },
+        tags,
+    }
+}
+
+#[test]
+pub fn test_missing_default_tags_are_added_in_a_single_statement() {
+    let sql = debug_query::<Pg, _>(&PictureTag::add_default_tags_to_pictures_without_tags_statement(1, &[1, 2])).to_string();
+    assert!(sql.starts_with("INSERT INTO \"pictures_tags\" (\"picture_id\", \"tag_id\") SELECT \"pictures\".\"id\", \"tags\".\"id\" FROM"));
+    assert!(sql.contains("NOT (EXISTS (SELECT"));
+    assert!(sql.contains("(\"group_tags\".\"tag_group_id\" = \"tags\".\"tag_group_id\")"));
+    assert!(sql.contains("ON CONFLICT DO NOTHING RETURNING \"pictures_tags\".\"picture_id\""));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_missing_default_tags_are_added_once() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "default_tags");
+    let first_tag_ids = insert_tags(conn, user_id, 1);
+    let second_tag_ids = insert_tags(conn, user_id, 2);
+    let other_user_id = insert_user(conn, "default_tags_other");
+    let other_user_tag_ids = insert_tags(conn, other_user_id, 1);
+    diesel::update(tags::table.filter(tags::id.eq_any([first_tag_ids[0], second_tag_ids[0], other_user_tag_ids[0]])))
+        .set(tags::is_default.eq(true))
+        .execute(conn)
+        .unwrap();
+    // The first picture has a tag of the second tag group, the other one has no tag
+    let tagged_picture_id = insert_picture(conn, user_id, &[second_tag_ids[1]]);
+    let untagged_picture_id = insert_picture(conn, user_id, &[]);
+    let picture_ids = [tagged_picture_id, untagged_picture_id];
+    let sorted_tags = |conn: &mut DBConn, picture_id: i64| {
+        let mut tag_ids = PictureTag::get_picture_tags(conn, picture_id, user_id).unwrap();
+        tag_ids.sort();
+        tag_ids
+    };
+
+    assert_eq!(
+        PictureTag::add_default_tags_to_pictures_without_tags(conn, user_id, &picture_ids).unwrap(),
+        3
+    );
+    assert_eq!(sorted_tags(conn, tagged_picture_id), vec![first_tag_ids[0], second_tag_ids[1]]);
+    assert_eq!(sorted_tags(conn, untagged_picture_id), vec![first_tag_ids[0], second_tag_ids[0]]);
+
+    // A second run changes nothing
+    assert_eq!(
+        PictureTag::add_default_tags_to_pictures_without_tags(conn, user_id, &picture_ids).unwrap(),
+        0
+    );
+    assert_eq!(sorted_tags(conn, tagged_picture_id), vec![first_tag_ids[0], second_tag_ids[1]]);
+    assert_eq!(sorted_tags(conn, untagged_picture_id), vec![first_tag_ids[0], second_tag_ids[0]]);
+}
+
+#[test]
+pub fn test_invalid_tag_group_in_batch_rolls_back() {
+    let mut required = tag_group_with_tags(1, vec![tag(10, 1)]);
+    required.tag_group.required = true;
+    let mut single = tag_group_with_tags(2, vec![default_tag(20, 2), default_tag(21, 2)]);
+    single.tag_group.multiple = false;
+    let valid = tag_group_with_tags(3, vec![default_tag(30, 3), default_tag(31, 3)]);
+
+    assert!(valid.check_default_tags().is_ok());
+    for invalid in [required, single] {
+        let error = invalid.check_default_tags().unwrap_err();
+        // The whole batch transaction must be rolled back, including the tag groups already inserted
+        assert!(error.do_rollback());
+        assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnprocessableEntity));
+    }
+}
+
+#[test]
+pub fn test_picture_missing_a_required_tag_is_not_compliant() {
+    let mut required = tag_group_with_tags(1, vec![default_tag(10, 1), tag(11, 1)]);
+    required.tag_group.required = true;
+    let mut single = tag_group_with_tags(2, vec![tag(20, 2), tag(21, 2)]);
+    single.tag_group.multiple = false;
+    let optional = tag_group_with_tags(3, vec![tag(30, 3), tag(31, 3)]);
+    let tag_groups = vec![single, required, optional];
+
+    assert_eq!(
+        TagGroupWithTags::violations(&tag_groups, &[20, 30, 31]),
+        vec![TagGroupViolation::MissingRequiredTag { tag_group_id: 1 }]
+    );
+    assert_eq!(
+        TagGroupWithTags::violations(&tag_groups, &[11, 20, 21]),
+        vec![TagGroupViolation::MultipleTags {
+            tag_group_id: 2,
+            tag_ids: vec![20, 21],
+        }]
+    );
+    assert!(TagGroupWithTags::violations(&tag_groups, &[10, 11, 21]).is_empty());
+}
+
+#[test]
+pub fn test_repair_keeps_a_single_tag_in_non_multiple_group() {
+    let mut single = tag_group_with_tags(1, vec![tag(10, 1), default_tag(11, 1), tag(12, 1)]);
+    single.tag_group.multiple = false;
+    let picture_ids = [1, 2, 3];
+    // Picture 1 has the default tag among others, picture 2 has two non-default tags, tag 10 assigned last, picture 3 complies
+    let mut pictures_tags = vec![(1, 10), (1, 11), (1, 12), (2, 12), (2, 10), (3, 10)];
+
+    let repair = single.repair(&picture_ids, &pictures_tags).unwrap();
+    assert_eq!(
+        repair,
+        TagGroupRepair {
+            tag_group_id: 1,
+            default_tag_ids: vec![11],
+            defaulted_picture_ids: vec![],
+            unresolved_picture_ids: vec![],
+            removed_tags: vec![(1, 10), (1, 12), (2, 12)],
+        }
+    );
+
+    // Once repaired, each picture has a single tag of the group: the default one, or the most recently assigned one
+    pictures_tags.retain(|assignment| !repair.removed_tags.contains(assignment));
+    assert_eq!(pictures_tags, vec![(1, 11), (2, 10), (3, 10)]);
+    assert_eq!(single.repair(&picture_ids, &pictures_tags), None);
+}
+
+#[test]
+pub fn test_repair_adds_defaults_of_required_group() {
+    let mut required = tag_group_with_tags(2, vec![default_tag(20, 2), tag(21, 2)]);
+    required.tag_group.required = true;
+    let pictures_tags = vec![(1, 21), (2, 30)];
+
+    let repair = required.repair(&[1, 2, 3], &pictures_tags).unwrap();
+    assert_eq!(repair.defaulted_picture_ids, vec![2, 3]);
+    assert_eq!(repair.default_tag_ids, vec![20]);
+
+    let mut report = TagGroupRepairReport::default();
+    report.add(&repair);
+    report.add(&repair);
+    assert_eq!(
+        report,
+        TagGroupRepairReport {
+            tag_group_id: 2,
+            defaulted_pictures: 4,
+            unresolved_pictures: 0,
+            removed_tags: 0,
+        }
+    );
+}
+
+#[test]
+pub fn test_repair_reports_required_group_without_default_as_unresolved() {
+    let mut required = tag_group_with_tags(2, vec![tag(20, 2), tag(21, 2)]);
+    required.tag_group.required = true;
+    let pictures_tags = vec![(1, 21)];
+
+    // Picture 2 misses a tag of the group, which has no default tag to give it
+    let repair = required.repair(&[1, 2], &pictures_tags).unwrap();
+    assert_eq!(repair.defaulted_picture_ids, Vec::<i64>::new());
+    assert_eq!(repair.unresolved_picture_ids, vec![2]);
+
+    let mut report = TagGroupRepairReport::default();
+    report.add(&repair);
+    assert_eq!(report.defaulted_pictures, 0);
+    assert_eq!(report.unresolved_pictures, 1);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_repair_keeps_the_most_recently_assigned_tag() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "repair");
+    let tag_ids = insert_tags(conn, user_id, 2);
+    // The tag with the highest id is assigned first
+    let picture_id = insert_picture(conn, user_id, &[tag_ids[1]]);
+    PictureTag::add_pictures_batch(conn, &vec![tag_ids[0]], &vec![picture_id]).unwrap();
+    let mut single = TagGroup::list_all_tags_as_tag_group_with_tags(conn, user_id).unwrap().remove(0);
+    single.tag_group.multiple = false;
+
+    let pictures_tags = PictureTag::get_pictures_tags(conn, user_id, &[picture_id]).unwrap();
+    assert_eq!(pictures_tags, vec![(picture_id, tag_ids[1]), (picture_id, tag_ids[0])]);
+    single.repair(&[picture_id], &pictures_tags).unwrap().apply(conn).unwrap();
+    assert_eq!(PictureTag::get_picture_tags(conn, picture_id, user_id).unwrap(), vec![tag_ids[0]]);
+}
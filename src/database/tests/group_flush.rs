@@ -0,0 +1,86 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::schema::*;
+use crate::database::tests::test_database::{group, insert_picture, insert_share, insert_user, test_connection};
+use diesel::prelude::*;
+
+#[test]
+pub fn test_only_empty_to_be_deleted_groups_are_flushed() {
+    let to_be_deleted = |id: i32| Group {
+        to_be_deleted: true,
+        ..group(id, 1)
+    };
+    let groups = vec![to_be_deleted(1), to_be_deleted(2), group(3, 1)];
+
+    // Group 2 still contains pictures, group 3 is not marked as to be deleted
+    let populated_group_ids = vec![2, 3];
+    assert_eq!(Group::to_be_deleted_empty_ids(&groups, &populated_group_ids), vec![1]);
+
+    // Once group 2 is emptied, it is flushed as well
+    let populated_group_ids = vec![3];
+    assert_eq!(Group::to_be_deleted_empty_ids(&groups, &populated_group_ids), vec![1, 2]);
+
+    // Empty groups not marked as to be deleted are kept
+    assert!(Group::to_be_deleted_empty_ids(&[group(3, 1)], &[]).is_empty());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_flagged_groups_are_deleted_once_empty() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "group_flush");
+    let recipient_id = insert_user(conn, "group_flush_recipient");
+    let picture_id = insert_picture(conn, user_id, &[]);
+    let arrangement = Arrangement::new(conn, user_id, "Flushed".to_string(), false, None).unwrap();
+    let other_arrangement = Arrangement::new(conn, user_id, "Other".to_string(), false, None).unwrap();
+    let insert_group = |conn: &mut DBConn, arrangement_id: i32, to_be_deleted: bool| {
+        let group_id = Group::insert(conn, arrangement_id, "Group".to_string(), false, None).unwrap().id;
+        if to_be_deleted {
+            Group::mark_as_to_be_deleted(conn, group_id).unwrap();
+        }
+        group_id
+    };
+    let empty_group_id = insert_group(conn, arrangement.id, true);
+    let populated_group_id = insert_group(conn, arrangement.id, true);
+    let kept_group_id = insert_group(conn, arrangement.id, false);
+    let other_group_id = insert_group(conn, other_arrangement.id, true);
+    Group::add_pictures(conn, populated_group_id, &vec![picture_id]).unwrap();
+    // The shares and public links of the group are deleted with it
+    insert_share(conn, recipient_id, empty_group_id, true);
+    diesel::insert_into(link_share_groups::table)
+        .values((
+            link_share_groups::token.eq(vec![0xde, 0xad, 0xbe, 0xef]),
+            link_share_groups::group_id.eq(empty_group_id),
+            link_share_groups::permissions.eq(1),
+        ))
+        .execute(conn)
+        .unwrap();
+    let group_exists = |conn: &mut DBConn, group_id: i32| groups::table.find(group_id).count().get_result::<i64>(conn).unwrap() == 1;
+
+    assert_eq!(
+        Group::delete_to_be_deleted_empty(conn, Some(arrangement.id)).unwrap(),
+        vec![empty_group_id]
+    );
+    assert!(!group_exists(conn, empty_group_id));
+    assert!(group_exists(conn, populated_group_id) && group_exists(conn, kept_group_id) && group_exists(conn, other_group_id));
+    let shares: i64 = shared_groups::table
+        .filter(shared_groups::group_id.eq(empty_group_id))
+        .count()
+        .get_result(conn)
+        .unwrap();
+    assert_eq!(shares, 0);
+
+    // Once emptied, the populated group is deleted as well
+    Group::remove_pictures(conn, populated_group_id, &vec![picture_id]).unwrap();
+    assert_eq!(
+        Group::delete_to_be_deleted_empty(conn, Some(arrangement.id)).unwrap(),
+        vec![populated_group_id]
+    );
+    assert!(Group::delete_to_be_deleted_empty(conn, Some(arrangement.id)).unwrap().is_empty());
+    assert!(group_exists(conn, kept_group_id));
+
+    // Without arrangement, the groups of all the arrangements are deleted
+    assert!(Group::delete_to_be_deleted_empty(conn, None).unwrap().contains(&other_group_id));
+    assert!(!group_exists(conn, other_group_id));
+}
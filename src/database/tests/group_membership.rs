@@ -0,0 +1,52 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::{gained_access_pictures, group_add_pictures};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use std::collections::HashSet;
+
+#[test]
+pub fn test_adding_pictures_to_a_group_ignores_present_ones() {
+    let sql = debug_query::<Pg, _>(&Group::add_pictures_statement(4, &[1, 2])).to_string();
+    // Memberships already present are skipped, and only the inserted rows are returned
+    assert!(sql.starts_with("INSERT INTO \"groups_pictures\" (\"group_id\", \"picture_id\") VALUES ($1, $2), ($3, $4)"));
+    assert!(sql.contains("ON CONFLICT DO NOTHING RETURNING \"groups_pictures\".\"picture_id\""));
+    assert!(sql.ends_with("binds: [4, 1, 4, 2]"));
+}
+
+#[test]
+pub fn test_re_adding_a_picture_grants_no_access() {
+    let accessible_pictures = HashSet::from([3]);
+    // Re-adding pictures already in the group inserts nothing, so no recipient gains access
+    assert!(gained_access_pictures(&[], &accessible_pictures).is_empty());
+    // Pictures the recipient could already access through another group are not propagated again
+    assert_eq!(gained_access_pictures(&[2, 3, 1], &accessible_pictures), vec![1, 2]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_re_adding_pictures_returns_and_records_nothing() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "re_add");
+    let picture_ids = vec![insert_picture(conn, user_id, &[]), insert_picture(conn, user_id, &[])];
+    let arrangement = Arrangement::new(conn, user_id, "Manual".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Group".to_string(), false, None).unwrap();
+
+    let mut delta = GroupingDelta::new();
+    assert_eq!(group_add_pictures(conn, &mut delta, group.id, &picture_ids).unwrap(), picture_ids);
+    assert_eq!(delta.events().len(), 2);
+
+    // Pictures already in the group are neither returned nor recorded
+    let mut delta = GroupingDelta::new();
+    assert!(group_add_pictures(conn, &mut delta, group.id, &picture_ids).unwrap().is_empty());
+    assert!(delta.events().is_empty());
+    let new_picture_id = insert_picture(conn, user_id, &[]);
+    assert_eq!(
+        group_add_pictures(conn, &mut delta, group.id, &vec![picture_ids[0], new_picture_id]).unwrap(),
+        vec![new_picture_id]
+    );
+    assert_eq!(delta.events().len(), 1);
+    assert_eq!(delta.events()[0].picture_id, new_picture_id);
+}
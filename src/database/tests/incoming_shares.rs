@@ -0,0 +1,70 @@
+use crate::database::group::shared_group::{IncomingShare, SharedArrangement, SharedArrangementGroup, SharedGroup, SharerArrangements};
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+fn share(sharer_id: i32, arrangement_id: i32, group_id: i32) -> IncomingShare {
+    IncomingShare {
+        sharer_id,
+        sharer_name: format!("User {}", sharer_id),
+        arrangement_id,
+        arrangement_name: format!("Arrangement {}", arrangement_id),
+        group_id,
+        group_name: format!("Group {}", group_id),
+        permissions: 1,
+    }
+}
+fn group(group_id: i32) -> SharedArrangementGroup {
+    SharedArrangementGroup {
+        group_id,
+        group_name: format!("Group {}", group_id),
+        permissions: 1,
+    }
+}
+
+#[test]
+pub fn test_incoming_shares_are_confirmed_shares_of_the_recipient() {
+    let sql = debug_query::<Pg, _>(&SharedGroup::incoming_for_recipient_statement(7)).to_string();
+    // The sharer is the owner of the arrangement of the shared group
+    assert!(sql
+        .contains("INNER JOIN (\"groups\" INNER JOIN (\"arrangements\" INNER JOIN \"users\" ON (\"arrangements\".\"user_id\" = \"users\".\"id\"))"));
+    assert!(sql.contains("WHERE (((\"shared_groups\".\"user_id\" = $1) AND (\"shared_groups\".\"confirmed\" = $2))"));
+    // A group pending deletion is not listed to the recipient
+    assert!(sql.contains("AND (\"groups\".\"to_be_deleted\" = $3))"));
+    assert!(sql.ends_with("binds: [7, true, false]"));
+}
+
+#[test]
+pub fn test_shares_are_listed_by_sharer_and_arrangement() {
+    let shares = vec![share(2, 10, 100), share(2, 10, 101), share(2, 11, 110), share(3, 20, 200)];
+
+    assert_eq!(
+        SharerArrangements::from_shares(shares),
+        vec![
+            SharerArrangements {
+                sharer_id: 2,
+                sharer_name: "User 2".to_string(),
+                arrangements: vec![
+                    SharedArrangement {
+                        arrangement_id: 10,
+                        arrangement_name: "Arrangement 10".to_string(),
+                        groups: vec![group(100), group(101)],
+                    },
+                    SharedArrangement {
+                        arrangement_id: 11,
+                        arrangement_name: "Arrangement 11".to_string(),
+                        groups: vec![group(110)],
+                    },
+                ],
+            },
+            SharerArrangements {
+                sharer_id: 3,
+                sharer_name: "User 3".to_string(),
+                arrangements: vec![SharedArrangement {
+                    arrangement_id: 20,
+                    arrangement_name: "Arrangement 20".to_string(),
+                    groups: vec![group(200)],
+                }],
+            },
+        ]
+    );
+}
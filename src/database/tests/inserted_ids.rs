@@ -0,0 +1,62 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tag::tag_group::TagGroup;
+use crate::database::tests::test_database::{insert_user, test_connection};
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+#[test]
+pub fn test_tag_group_insert_returns_the_inserted_row() {
+    let tag_group = TagGroup {
+        id: Some(42),
+        user_id: 1,
+        name: "Places".to_string(),
+        multiple: true,
+        required: false,
+    };
+    let sql = debug_query::<Pg, _>(&TagGroup::insert_statement(tag_group)).to_string();
+
+    // The id is assigned by the database and read back from the same statement.
+    assert!(sql.starts_with("INSERT INTO \"tag_groups\" (\"user_id\", \"name\", \"multiple\", \"required\") VALUES ($1, $2, $3, $4)"));
+    assert!(sql.contains(" RETURNING \"tag_groups\".\"id\", "));
+    assert!(!sql.contains("42"));
+}
+
+#[test]
+pub fn test_group_insert_returns_the_inserted_row() {
+    let sql = debug_query::<Pg, _>(&Group::insert_statement(3, "Summer".to_string(), false, None)).to_string();
+
+    assert!(sql.starts_with("INSERT INTO \"groups\" (\"arrangement_id\", \"name\", \"share_match_conversion\", \"color\") VALUES ($1, $2, $3, $4)"));
+    assert!(sql.contains(" RETURNING \"groups\".\"id\", "));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_inserts_return_the_ids_of_the_inserted_rows() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "inserted_ids");
+    let tag_group = |name: &str| TagGroup {
+        id: Some(42),
+        user_id,
+        name: name.to_string(),
+        multiple: true,
+        required: false,
+    };
+
+    // Several inserts in a row: each returned row is the one stored under its id
+    let places = TagGroup::insert(conn, tag_group("Places")).unwrap();
+    let people = TagGroup::insert(conn, tag_group("People")).unwrap();
+    assert_ne!(places.id, Some(42));
+    assert_ne!(places.id, people.id);
+    assert_eq!(TagGroup::from_id(conn, places.id.unwrap()).unwrap(), places);
+    assert_eq!(TagGroup::from_id(conn, people.id.unwrap()).unwrap(), people);
+    assert_eq!(people.name, "People");
+
+    let arrangement = Arrangement::new(conn, user_id, "Seasons".to_string(), false, None).unwrap();
+    let summer = Group::insert(conn, arrangement.id, "Summer".to_string(), false, None).unwrap();
+    let winter = Group::insert(conn, arrangement.id, "Winter".to_string(), true, Some(vec![0, 0, 255])).unwrap();
+    assert_ne!(summer.id, winter.id);
+    assert_eq!(Group::from_id(conn, summer.id).unwrap(), summer);
+    assert_eq!(Group::from_id(conn, winter.id).unwrap(), winter);
+    assert_eq!((winter.name.as_str(), winter.share_match_conversion), ("Winter", true));
+}
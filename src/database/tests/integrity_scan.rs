@@ -0,0 +1,73 @@
+use crate::database::integrity_scan::{IntegrityAnomaly, IntegrityReport};
+use crate::database::schema::*;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+#[test]
+pub fn test_dangling_group_picture_is_detected() {
+    // A groups_pictures row whose group was deleted without removing its pictures matches one of the NOT EXISTS conditions
+    let sql = debug_query::<Pg, _>(&IntegrityReport::dangling_groups_pictures_statement()).to_string();
+    assert!(sql.starts_with("SELECT \"groups_pictures\".\"group_id\", \"groups_pictures\".\"picture_id\" FROM \"groups_pictures\""));
+    assert!(sql.contains("NOT (EXISTS (SELECT \"groups\".\"id\""));
+    assert!(sql.contains("WHERE (\"groups\".\"id\" = \"groups_pictures\".\"group_id\")"));
+    assert!(sql.contains("WHERE (\"pictures\".\"id\" = \"groups_pictures\".\"picture_id\")"));
+
+    let sql = debug_query::<Pg, _>(&IntegrityReport::dangling_pictures_tags_statement()).to_string();
+    assert!(sql.contains("WHERE (\"tags\".\"id\" = \"pictures_tags\".\"tag_id\")"));
+    let sql = debug_query::<Pg, _>(&IntegrityReport::dangling_ratings_statement()).to_string();
+    assert!(sql.contains("WHERE (\"users\".\"id\" = \"ratings\".\"user_id\")"));
+    // The match conversion group is optional
+    let sql = debug_query::<Pg, _>(&IntegrityReport::dangling_shared_groups_statement()).to_string();
+    assert!(sql.contains("\"shared_groups\".\"match_conversion_group_id\" IS NOT NULL"));
+
+    let json = serde_json::to_value(IntegrityAnomaly::DanglingGroupPicture { group_id: 4, picture_id: 9 }).unwrap();
+    assert_eq!(json, serde_json::json!({"type": "DanglingGroupPicture", "group_id": 4, "picture_id": 9}));
+}
+
+#[test]
+pub fn test_storage_count_mismatch_is_detected() {
+    // User 1 matches its pictures, user 2 lost track of a 300 ko upload, user 3 has no picture
+    let users = [(1, 1200), (2, 500), (3, 0)];
+    let pictures_sizes_ko = HashMap::from([(1, 1200), (2, 800)]);
+    assert_eq!(
+        IntegrityReport::storage_anomalies(&users, &pictures_sizes_ko),
+        vec![IntegrityAnomaly::StorageCountMismatch {
+            user_id: 2,
+            storage_count_ko: 500,
+            pictures_size_ko: 800,
+        }]
+    );
+
+    let sql = debug_query::<Pg, _>(&IntegrityReport::owned_pictures_size_statement(&[1, 2])).to_string();
+    assert!(sql.contains("GROUP BY \"pictures\".\"owner_id\""));
+    let sql = debug_query::<Pg, _>(&IntegrityReport::copied_shares_pictures_statement(&[1, 2])).to_string();
+    assert!(sql.starts_with("SELECT DISTINCT"));
+    assert!(sql.contains("\"pictures\".\"owner_id\" != \"shared_groups\".\"user_id\""));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_scan_reports_dangling_rows() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "integrity");
+    let picture_id = insert_picture(conn, user_id, &[]);
+
+    // The foreign keys are checked by triggers, disabled while inserting rows whose group and tag don't exist
+    diesel::sql_query("SET LOCAL session_replication_role = replica").execute(conn).unwrap();
+    diesel::insert_into(groups_pictures::table)
+        .values((groups_pictures::group_id.eq(-1), groups_pictures::picture_id.eq(picture_id)))
+        .execute(conn)
+        .unwrap();
+    diesel::insert_into(pictures_tags::table)
+        .values((pictures_tags::picture_id.eq(picture_id), pictures_tags::tag_id.eq(-2)))
+        .execute(conn)
+        .unwrap();
+    diesel::sql_query("SET LOCAL session_replication_role = DEFAULT").execute(conn).unwrap();
+
+    let anomalies = IntegrityReport::scan(conn).unwrap().anomalies;
+    assert!(anomalies.contains(&IntegrityAnomaly::DanglingGroupPicture { group_id: -1, picture_id }));
+    assert!(anomalies.contains(&IntegrityAnomaly::DanglingPictureTag { picture_id, tag_id: -2 }));
+}
@@ -0,0 +1,33 @@
+use crate::database::user::auth_token::AuthToken;
+use crate::utils::auth::DeviceInfo;
+use ipnet::IpNet;
+
+fn device(device_string: &str, ip_address: Option<&str>) -> DeviceInfo {
+    DeviceInfo {
+        device_string: device_string.to_string(),
+        ip_address: ip_address.map(|ip| ip.parse::<IpNet>().unwrap()),
+    }
+}
+
+#[test]
+pub fn test_first_time_device_is_new() {
+    let known = vec![(Some("Firefox on Linux".to_string()), Some("192.168.1.10/32".parse().unwrap()))];
+
+    // No device yet
+    assert!(!AuthToken::is_known_device(&[], &device("Firefox on Linux", Some("192.168.1.10/32"))));
+    // Another browser, or the same browser from another IP address
+    assert!(!AuthToken::is_known_device(&known, &device("Safari on iOS", Some("192.168.1.10/32"))));
+    assert!(!AuthToken::is_known_device(&known, &device("Firefox on Linux", Some("203.0.113.7/32"))));
+    assert!(!AuthToken::is_known_device(&known, &device("Firefox on Linux", None)));
+}
+
+#[test]
+pub fn test_existing_device_is_known() {
+    let known = vec![
+        (None, None),
+        (Some("Safari on iOS".to_string()), None),
+        (Some("Firefox on Linux".to_string()), Some("192.168.1.10/32".parse().unwrap())),
+    ];
+    assert!(AuthToken::is_known_device(&known, &device("Firefox on Linux", Some("192.168.1.10/32"))));
+    assert!(AuthToken::is_known_device(&known, &device("Safari on iOS", None)));
+}
@@ -0,0 +1,81 @@
+use crate::database::migrations::{MigrationsStatus, MIGRATIONS};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use diesel::migration::{Migration, MigrationSource, MigrationVersion};
+use diesel::pg::Pg;
+use diesel_migrations::MigrationHarness;
+
+/// Harness recording the applied migrations in memory, without running them.
+#[derive(Default)]
+struct InMemoryHarness {
+    applied: Vec<MigrationVersion<'static>>,
+    fail: bool,
+}
+impl MigrationHarness<Pg> for InMemoryHarness {
+    fn run_migration(&mut self, migration: &dyn Migration<Pg>) -> diesel::migration::Result<MigrationVersion<'static>> {
+        let version = migration.name().version().as_owned();
+        self.applied.push(version.as_owned());
+        Ok(version)
+    }
+    fn revert_migration(&mut self, migration: &dyn Migration<Pg>) -> diesel::migration::Result<MigrationVersion<'static>> {
+        let version = migration.name().version().as_owned();
+        self.applied.retain(|applied| *applied != version);
+        Ok(version)
+    }
+    fn applied_migrations(&mut self) -> diesel::migration::Result<Vec<MigrationVersion<'static>>> {
+        if self.fail {
+            return Err("database unavailable".into());
+        }
+        Ok(self.applied.iter().map(|version| version.as_owned()).collect())
+    }
+}
+
+fn all_versions() -> Vec<String> {
+    let mut versions: Vec<String> = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+        .unwrap()
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect();
+    versions.sort();
+    versions
+}
+
+#[test]
+pub fn test_fresh_database_has_all_migrations_pending() {
+    let status = MigrationsStatus::from_harness(&mut InMemoryHarness::default(), MIGRATIONS).unwrap();
+    assert!(status.applied_versions.is_empty());
+    assert_eq!(status.pending_versions, all_versions());
+    assert!(status.has_pending);
+}
+
+#[test]
+pub fn test_partially_migrated_database() {
+    let mut harness = InMemoryHarness::default();
+    harness.run_next_migration(MIGRATIONS).unwrap();
+
+    let versions = all_versions();
+    let status = MigrationsStatus::from_harness(&mut harness, MIGRATIONS).unwrap();
+    assert_eq!(status.applied_versions, versions[..1]);
+    assert_eq!(status.pending_versions, versions[1..]);
+    assert!(status.has_pending);
+}
+
+#[test]
+pub fn test_migrated_database_has_no_pending_migration() {
+    let mut harness = InMemoryHarness::default();
+    harness.run_pending_migrations(MIGRATIONS).unwrap();
+
+    let status = MigrationsStatus::from_harness(&mut harness, MIGRATIONS).unwrap();
+    assert_eq!(status.applied_versions, all_versions());
+    assert!(status.pending_versions.is_empty());
+    assert!(!status.has_pending);
+}
+
+#[test]
+pub fn test_harness_error_is_internal_error() {
+    let mut harness = InMemoryHarness {
+        fail: true,
+        ..Default::default()
+    };
+    let error = MigrationsStatus::from_harness(&mut harness, MIGRATIONS).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::InternalError));
+}
@@ -0,0 +1,104 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::{OutgoingShare, SharePermissions, SharedGroup};
+use crate::database::picture::picture::Picture;
+use crate::database::schema::shared_groups;
+use crate::database::tests::test_database::{insert_share, insert_user, test_connection};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+#[test]
+pub fn test_outgoing_shares_are_filtered_on_the_owner() {
+    let sql = debug_query::<Pg, _>(&SharedGroup::outgoing_for_owner_statement(7)).to_string();
+    // Shares of the groups of the owner's arrangements
+    assert!(sql.contains("INNER JOIN (\"groups\" INNER JOIN \"arrangements\""));
+    assert!(sql.contains("WHERE (\"arrangements\".\"user_id\" = $1)"));
+    assert!(sql.ends_with("binds: [7]"));
+    // Recipient and status columns, in the OutgoingShare fields order
+    assert!(sql.starts_with(
+        "SELECT \"shared_groups\".\"user_id\", \"shared_groups\".\"group_id\", \"groups\".\"name\", \"arrangements\".\"id\", \
+         \"arrangements\".\"name\", \"shared_groups\".\"permissions\", \"shared_groups\".\"copied\", \"shared_groups\".\"confirmed\" "
+    ));
+}
+
+#[test]
+pub fn test_outgoing_share_serialization() {
+    let share = OutgoingShare {
+        recipient_id: 2,
+        group_id: 10,
+        group_name: "Holidays".to_string(),
+        arrangement_id: 3,
+        arrangement_name: "Events".to_string(),
+        permissions: 1,
+        copied: false,
+        confirmed: true,
+    };
+    let json = serde_json::to_value(&share).unwrap();
+    assert_eq!(json["recipient_id"], 2);
+    assert_eq!(json["group_name"], "Holidays");
+    assert_eq!(json["arrangement_name"], "Events");
+    assert_eq!(json["confirmed"], true);
+    assert_eq!(json["copied"], false);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_outgoing_shares_list_the_shares_of_the_owner_with_their_status() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "outgoing_owner");
+    let confirmed_recipient_id = insert_user(conn, "outgoing_confirmed");
+    let pending_recipient_id = insert_user(conn, "outgoing_pending");
+    let arrangement = Arrangement::new(conn, owner_id, "Events".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Holidays".to_string(), false, None).unwrap();
+    insert_share(conn, pending_recipient_id, group.id, false);
+    insert_share(conn, confirmed_recipient_id, group.id, true);
+    SharedGroup::update_permissions(conn, confirmed_recipient_id, group.id, SharePermissions::VIEW).unwrap();
+    diesel::update(shared_groups::table.find((confirmed_recipient_id, group.id)))
+        .set(shared_groups::copied.eq(true))
+        .execute(conn)
+        .unwrap();
+    // A share received by the owner is not one of their outgoing shares
+    let other_arrangement = Arrangement::new(conn, confirmed_recipient_id, "Received".to_string(), false, None).unwrap();
+    let other_group = Group::insert(conn, other_arrangement.id, "Received group".to_string(), false, None).unwrap();
+    insert_share(conn, owner_id, other_group.id, true);
+
+    let share = |recipient_id: i32, permissions: SharePermissions, copied: bool, confirmed: bool| OutgoingShare {
+        recipient_id,
+        group_id: group.id,
+        group_name: "Holidays".to_string(),
+        arrangement_id: arrangement.id,
+        arrangement_name: "Events".to_string(),
+        permissions: permissions.bits(),
+        copied,
+        confirmed,
+    };
+    assert_eq!(
+        SharedGroup::outgoing_for_owner(conn, owner_id).unwrap(),
+        vec![
+            share(confirmed_recipient_id, SharePermissions::VIEW, true, true),
+            share(pending_recipient_id, SharePermissions::ALL, false, false),
+        ]
+    );
+}
+
+#[test]
+pub fn test_group_shared_pictures_and_recipients() {
+    // Pictures of the group, through its memberships
+    let sql = debug_query::<Pg, _>(&Picture::from_group_statement(10)).to_string();
+    assert!(sql.contains(
+        "WHERE EXISTS (SELECT \"groups_pictures\".\"group_id\", \"groups_pictures\".\"picture_id\" FROM \"groups_pictures\" \
+         WHERE ((\"groups_pictures\".\"picture_id\" = \"pictures\".\"id\") AND (\"groups_pictures\".\"group_id\" = $1)))"
+    ));
+    assert!(sql.contains("ORDER BY \"pictures\".\"creation_date\" DESC, \"pictures\".\"id\" DESC"));
+    assert!(sql.ends_with("binds: [10]"));
+
+    // Recipients of the shares of the same group, with their name
+    let sql = debug_query::<Pg, _>(&SharedGroup::recipients_statement(10)).to_string();
+    assert!(sql.starts_with(
+        "SELECT \"shared_groups\".\"user_id\", \"users\".\"name\", \"shared_groups\".\"permissions\", \
+         \"shared_groups\".\"copied\", \"shared_groups\".\"confirmed\" FROM (\"shared_groups\" INNER JOIN \"users\""
+    ));
+    assert!(sql.contains("WHERE (\"shared_groups\".\"group_id\" = $1) ORDER BY \"shared_groups\".\"user_id\""));
+    assert!(sql.ends_with("binds: [10]"));
+}
@@ -0,0 +1,165 @@
+use crate::api::query_pictures::PicturesQuery;
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::SharedGroup;
+use crate::database::picture::picture::{Picture, PictureAccess, PictureShare, PictureVisibility};
+use crate::database::schema::link_share_groups;
+use crate::database::tests::test_database::{insert_picture, insert_share, insert_user, test_connection};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+#[test]
+pub fn test_picture_access_agrees_with_the_streaming_check() {
+    // The picture endpoint lets logged-in users fetch their own pictures and the ones shared with them,
+    // and anonymous callers fetch the publicly shared ones. The probe must never disagree with it.
+    for owned in [false, true] {
+        for shared_with_user in [false, true] {
+            for publicly_shared in [false, true] {
+                let access = PictureAccess::for_user(owned, shared_with_user, publicly_shared);
+                assert_eq!(access.accessible, owned || shared_with_user);
+                assert_eq!((access.owned, access.publicly_shared), (owned, publicly_shared));
+            }
+        }
+        let access = PictureAccess::anonymous(owned);
+        assert_eq!(access.accessible, owned);
+        assert!(!access.owned);
+    }
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_picture_access_of_owned_shared_and_unaccessible_pictures() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "access_owner");
+    let recipient_id = insert_user(conn, "access_recipient");
+    let stranger_id = insert_user(conn, "access_stranger");
+    let shared_picture_id = insert_picture(conn, owner_id, &[]);
+    let private_picture_id = insert_picture(conn, owner_id, &[]);
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![shared_picture_id]).unwrap();
+    insert_share(conn, recipient_id, group.id, true);
+    // Accessible, owned, publicly shared
+    let access = |conn: &mut DBConn, picture_id: i64, user_id: Option<i32>| {
+        let access = Picture::get_picture_access(conn, picture_id, user_id).unwrap();
+        (access.accessible, access.owned, access.publicly_shared)
+    };
+
+    assert_eq!(access(conn, private_picture_id, Some(owner_id)), (true, true, false));
+    assert_eq!(access(conn, shared_picture_id, Some(owner_id)), (true, true, false));
+    assert_eq!(access(conn, shared_picture_id, Some(recipient_id)), (true, false, false));
+    assert_eq!(access(conn, private_picture_id, Some(recipient_id)), (false, false, false));
+    assert_eq!(access(conn, shared_picture_id, Some(stranger_id)), (false, false, false));
+    assert_eq!(access(conn, shared_picture_id, None), (false, false, false));
+
+    // With a public link on the group, only anonymous callers gain access to the picture
+    diesel::insert_into(link_share_groups::table)
+        .values((
+            link_share_groups::token.eq(vec![0xde, 0xad, 0xbe, 0xef]),
+            link_share_groups::group_id.eq(group.id),
+            link_share_groups::permissions.eq(1),
+        ))
+        .execute(conn)
+        .unwrap();
+    assert_eq!(access(conn, shared_picture_id, Some(stranger_id)), (false, false, true));
+    assert_eq!(access(conn, shared_picture_id, None), (true, false, true));
+    assert_eq!(access(conn, private_picture_id, None), (false, false, false));
+}
+
+#[test]
+pub fn test_picture_access_queries() {
+    let sql = debug_query::<Pg, _>(&Picture::is_picture_owned_by_statement(9, 2)).to_string();
+    assert!(sql.starts_with("SELECT COUNT(*) FROM \"pictures\" WHERE ((\"pictures\".\"id\" = $1) AND (\"pictures\".\"owner_id\" = $2))"));
+    assert!(sql.ends_with("binds: [9, 2]"));
+
+    // Only the public links of the groups containing the picture count, not the shares with users
+    let sql = debug_query::<Pg, _>(&Picture::is_picture_publicly_shared_statement(9)).to_string();
+    assert!(sql.contains("INNER JOIN \"link_share_groups\" ON (\"link_share_groups\".\"group_id\" = \"groups_pictures\".\"group_id\")"));
+    assert!(sql.contains("WHERE (\"groups_pictures\".\"picture_id\" = $1)"));
+    assert!(!sql.contains("shared_groups"));
+}
+
+#[test]
+pub fn test_visibility_audit_lists_shares_and_public_links() {
+    let sql = debug_query::<Pg, _>(&Picture::visibility_shares_statement(42)).to_string();
+    assert!(sql.contains("INNER JOIN \"shared_groups\" ON (\"shared_groups\".\"group_id\" = \"groups_pictures\".\"group_id\")"));
+    assert!(sql.contains("\"groups_pictures\".\"picture_id\" = $1"));
+    let sql = debug_query::<Pg, _>(&Picture::visibility_links_statement(42)).to_string();
+    assert!(sql.contains("INNER JOIN \"link_share_groups\" ON (\"link_share_groups\".\"group_id\" = \"groups_pictures\".\"group_id\")"));
+    assert!(sql.contains("SELECT \"link_share_groups\".\"token\""));
+
+    // Shared with user 2 through two groups, with user 3 not confirmed yet, and one public link
+    let visibility = PictureVisibility::new(
+        1,
+        vec![(2, 10, true), (2, 11, true), (3, 10, false)],
+        vec![vec![0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04]],
+    );
+    assert_eq!(visibility.owner_id, 1);
+    assert_eq!(
+        visibility.shared_with,
+        vec![
+            PictureShare {
+                user_id: 2,
+                via_group_id: 10,
+                confirmed: true
+            },
+            PictureShare {
+                user_id: 2,
+                via_group_id: 11,
+                confirmed: true
+            },
+            PictureShare {
+                user_id: 3,
+                via_group_id: 10,
+                confirmed: false
+            },
+        ]
+    );
+    // Only a prefix of the token is disclosed
+    assert_eq!(visibility.public_links, vec!["deadbeef".to_string()]);
+
+    let private = PictureVisibility::new(1, vec![], vec![]);
+    assert!(private.shared_with.is_empty() && private.public_links.is_empty());
+}
+
+#[test]
+pub fn test_unaccessible_pictures_are_the_ones_not_accessible() {
+    // The removal of pictures 1 and 3 from a shared group must be propagated to the recipient, who still accesses 2
+    assert_eq!(Picture::unaccessible_among(&[1, 2, 3], &[2]), vec![1, 3]);
+    assert_eq!(Picture::unaccessible_among(&[1, 2, 3], &[3, 1, 2]), Vec::<i64>::new());
+    assert_eq!(Picture::unaccessible_among(&[4, 5], &[]), vec![4, 5]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_recipient_no_longer_sees_a_group_pending_deletion() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "pending_deletion_owner");
+    let recipient_id = insert_user(conn, "pending_deletion_recipient");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![picture_id]).unwrap();
+    insert_share(conn, recipient_id, group.id, true);
+    let listed_ids = |conn: &mut DBConn| -> Vec<i64> {
+        Picture::query(conn, recipient_id, PicturesQuery::from_page(1), 100)
+            .unwrap()
+            .iter()
+            .map(|picture| picture.id)
+            .collect()
+    };
+    assert_eq!(listed_ids(conn), vec![picture_id]);
+    assert_eq!(SharedGroup::incoming_for_recipient(conn, recipient_id).unwrap().len(), 1);
+
+    Group::mark_as_to_be_deleted(conn, group.id).unwrap();
+    // The group and its pictures are hidden from the recipient
+    assert!(listed_ids(conn).is_empty());
+    assert!(SharedGroup::incoming_for_recipient(conn, recipient_id).unwrap().is_empty());
+    // The access checks of the grouping propagation still see the group until it is actually deleted
+    assert_eq!(
+        Picture::filter_user_accessible_pictures(conn, recipient_id, &[picture_id]).unwrap(),
+        vec![picture_id]
+    );
+}
@@ -0,0 +1,222 @@
+use crate::api::query_pictures::{PictureFilter, PicturesQuery};
+use crate::database::database::DBConn;
+use crate::database::picture::picture::{Picture, PictureDetailsFields, PictureDetailsSelection, PictureDetailsSource};
+use crate::database::picture::rating::Rating;
+use crate::database::tests::test_database::{count_queries, insert_picture, insert_tags, insert_user, picture, test_connection};
+use crate::utils::errors_catcher::{ErrorResponder, ErrorResponse, ErrorTypeKind};
+use chrono::NaiveDateTime;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use std::sync::atomic::Ordering;
+
+fn rating(user_id: i32, picture_id: i64, rating: i16) -> Rating {
+    Rating { user_id, picture_id, rating }
+}
+
+#[test]
+pub fn test_assemble_pictures_details() {
+    let pictures = vec![picture(1), picture(2), picture(3)];
+    let tags = vec![(1, 10), (3, 10), (1, 11), (3, 12)];
+    let ratings = vec![rating(1, 2, 4), rating(2, 2, 5), rating(1, 3, 1)];
+
+    let details = Picture::assemble_pictures_details(&[3, 1, 2], pictures, tags, ratings);
+
+    // Requested order is kept
+    assert_eq!(details.iter().map(|d| d.picture.id).collect::<Vec<i64>>(), vec![3, 1, 2]);
+    // Tags and ratings are dispatched to their own picture
+    assert_eq!(details[0].tags_ids, vec![10, 12]);
+    assert_eq!(details[0].ratings, vec![rating(1, 3, 1)]);
+    assert_eq!(details[1].tags_ids, vec![10, 11]);
+    assert!(details[1].ratings.is_empty());
+    assert!(details[2].tags_ids.is_empty());
+    assert_eq!(details[2].ratings, vec![rating(1, 2, 4), rating(2, 2, 5)]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_many_picture_details_are_loaded_in_a_constant_number_of_queries() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "many_details");
+    let tag_ids = insert_tags(conn, user_id, 2);
+    let picture_ids = (0..6).map(|_| insert_picture(conn, user_id, &tag_ids)).collect::<Vec<_>>();
+    for (i, picture_id) in picture_ids.iter().enumerate() {
+        Rating::set(conn, user_id, *picture_id, (i % 5) as i16 + 1).unwrap();
+    }
+    let details_selects = |conn: &mut DBConn, picture_ids: &[i64]| {
+        let selects = count_queries(conn, "SELECT");
+        let details = Picture::get_many_picture_details(conn, user_id, picture_ids).unwrap();
+        (details, selects.load(Ordering::SeqCst))
+    };
+
+    let (details, selects) = details_selects(conn, &picture_ids[..2]);
+    assert_eq!(details.len(), 2);
+    let (details, all_selects) = details_selects(conn, &picture_ids);
+    assert_eq!(all_selects, selects);
+    assert_eq!(details.iter().map(|details| details.picture.id).collect::<Vec<_>>(), picture_ids);
+    for (i, details) in details.iter().enumerate() {
+        let mut tags_ids = details.tags_ids.clone();
+        tags_ids.sort();
+        assert_eq!(tags_ids, tag_ids);
+        assert_eq!(details.ratings, vec![rating(user_id, picture_ids[i], (i % 5) as i16 + 1)]);
+    }
+}
+
+#[test]
+pub fn test_assemble_pictures_details_skips_inaccessible_pictures() {
+    // Picture 2 is requested but not accessible: it is not part of the fetched pictures
+    let details = Picture::assemble_pictures_details(&[1, 2], vec![picture(1)], vec![(1, 10)], vec![]);
+
+    assert_eq!(details.len(), 1);
+    assert_eq!(details[0].picture.id, 1);
+    assert_eq!(details[0].tags_ids, vec![10]);
+}
+
+#[test]
+pub fn test_picture_details_include_favorite() {
+    let mut favorite = picture(2);
+    favorite.favorite = true;
+    let details = Picture::assemble_pictures_details(&[1, 2], vec![picture(1), favorite], vec![], vec![]);
+
+    let json = serde_json::to_value(&details).unwrap();
+    assert_eq!(json[0]["picture"]["favorite"], false);
+    assert_eq!(json[1]["picture"]["favorite"], true);
+}
+
+#[test]
+pub fn test_set_favorite_statement_updates_only_the_owner_picture() {
+    let date = NaiveDateTime::parse_from_str("2024-05-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let sql = debug_query::<Pg, _>(&Picture::set_favorite_statement(7, 3, true, date)).to_string();
+    // Only the picture of the owner is updated, its edition date too so that clients syncing the changes get it
+    assert!(sql.starts_with("UPDATE \"pictures\" SET \"favorite\" = $1, \"edition_date\" = $2"));
+    assert!(sql.contains("WHERE ((\"pictures\".\"id\" = $3) AND (\"pictures\".\"owner_id\" = $4))"));
+    assert!(sql.contains("RETURNING \"pictures\".\"id\""));
+    assert!(sql.contains("\"pictures\".\"favorite\""));
+    assert!(sql.ends_with("binds: [true, 2024-05-01T12:00:00, 7, 3]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_comment_is_only_edited_by_the_owner() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "comment_owner");
+    let other_user_id = insert_user(conn, "comment_other");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+
+    let picture = Picture::update_comment(conn, picture_id, owner_id, "Sunset over the lake").unwrap();
+    assert_eq!(picture.comment, "Sunset over the lake");
+    assert!(picture.edition_date > NaiveDateTime::default());
+    assert_eq!(Picture::from_ids(conn, &vec![picture_id]).unwrap()[0].comment, "Sunset over the lake");
+
+    // Another user can't edit it
+    let error = Picture::update_comment(conn, picture_id, other_user_id, "Mine now").unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::PictureNotFound));
+    assert_eq!(Picture::from_ids(conn, &vec![picture_id]).unwrap()[0].comment, "Sunset over the lake");
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_set_favorite_is_persisted_for_the_owner() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "favorite_owner");
+    let other_user_id = insert_user(conn, "favorite_other");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+    let other_picture_id = insert_picture(conn, owner_id, &[]);
+    let favorite_ids = |conn: &mut DBConn| {
+        let mut query = PicturesQuery::from_page(1);
+        query.filters = vec![PictureFilter::Favorite { invert: false }];
+        Picture::query_ids(conn, owner_id, query, 100).unwrap()
+    };
+    assert!(favorite_ids(conn).is_empty());
+
+    let picture = Picture::set_favorite(conn, picture_id, owner_id, true).unwrap();
+    assert!(picture.favorite);
+    assert!(Picture::from_ids(conn, &vec![picture_id]).unwrap()[0].favorite);
+
+    // Another user can't flag it, nor unflag it
+    let error = Picture::set_favorite(conn, other_picture_id, other_user_id, true).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::PictureNotFound));
+    let error = Picture::set_favorite(conn, picture_id, other_user_id, false).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::PictureNotFound));
+    assert!(!Picture::from_ids(conn, &vec![other_picture_id]).unwrap()[0].favorite);
+
+    // Only the flagged picture is selected by the favorite filter
+    assert_eq!(favorite_ids(conn), vec![picture_id]);
+}
+
+#[test]
+pub fn test_picture_details_fields_selection() {
+    // Everything by default, for backward compatibility
+    assert_eq!(PictureDetailsFields::parse(None).unwrap(), PictureDetailsFields::default());
+    assert_eq!(PictureDetailsFields::parse(Some("")).unwrap(), PictureDetailsFields::default());
+    assert_eq!(
+        PictureDetailsFields::parse(Some("picture, ratings")).unwrap(),
+        PictureDetailsFields {
+            picture: true,
+            tags: false,
+            ratings: true,
+        }
+    );
+    let error = ErrorResponse::from(PictureDetailsFields::parse(Some("tags,groups")).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+
+    // Only the tags: the ratings (and friends ratings) query is skipped, and the other sections are omitted
+    let fields = PictureDetailsFields::parse(Some("tags")).unwrap();
+    assert!(fields.tags);
+    assert!(!fields.ratings);
+    assert!(!fields.picture);
+    let selection = PictureDetailsSelection {
+        picture: None,
+        tags_ids: Some(vec![10, 12]),
+        ratings: None,
+    };
+    assert_eq!(serde_json::to_value(&selection).unwrap(), serde_json::json!({ "tags_ids": [10, 12] }));
+}
+
+/// Picture 1 with tags 10 and 12, rated by the user and a friend, recording the queries run.
+#[derive(Default)]
+struct RecordingSource {
+    queries: Vec<&'static str>,
+}
+impl PictureDetailsSource for RecordingSource {
+    fn accessible_picture(&mut self, _user_id: i32, picture_id: i64) -> Result<Option<Picture>, ErrorResponder> {
+        self.queries.push("picture");
+        Ok((picture_id == 1).then(|| picture(1)))
+    }
+    fn tags(&mut self, _user_id: i32, _picture_id: i64) -> Result<Vec<i32>, ErrorResponder> {
+        self.queries.push("tags");
+        Ok(vec![10, 12])
+    }
+    fn ratings_including_friends(&mut self, user_id: i32, picture_id: i64) -> Result<Vec<Rating>, ErrorResponder> {
+        self.queries.push("ratings");
+        Ok(vec![rating(user_id, picture_id, 4), rating(2, picture_id, 5)])
+    }
+}
+
+#[test]
+pub fn test_picture_details_selection_only_queries_the_requested_fields() {
+    // Only the tags: the ratings query is skipped, the picture is still loaded to check the access
+    let mut source = RecordingSource::default();
+    let fields = PictureDetailsFields::parse(Some("tags")).unwrap();
+    let selection = Picture::get_picture_details_selection(&mut source, 1, 1, &fields).unwrap();
+    assert_eq!(source.queries, vec!["picture", "tags"]);
+    assert_eq!(
+        selection,
+        PictureDetailsSelection {
+            picture: None,
+            tags_ids: Some(vec![10, 12]),
+            ratings: None,
+        }
+    );
+
+    let mut source = RecordingSource::default();
+    let selection = Picture::get_picture_details_selection(&mut source, 1, 1, &PictureDetailsFields::default()).unwrap();
+    assert_eq!(source.queries, vec!["picture", "tags", "ratings"]);
+    assert_eq!(selection.picture.map(|p| p.id), Some(1));
+    assert_eq!(selection.ratings.unwrap().len(), 2);
+
+    // An inaccessible picture is not found, and nothing else is queried
+    let mut source = RecordingSource::default();
+    let error = Picture::get_picture_details_selection(&mut source, 1, 2, &fields).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::PictureNotFound));
+    assert_eq!(source.queries, vec!["picture"]);
+}
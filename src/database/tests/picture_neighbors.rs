@@ -0,0 +1,94 @@
+use crate::api::query_pictures::{PictureFilter, PictureSort, PicturesQuery};
+use crate::database::database::DBConn;
+use crate::database::picture::picture::{Picture, PictureNeighbors, PictureOrderColumn};
+use crate::database::tests::test_database::{insert_picture_created_at, insert_user, picture, test_connection};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use chrono::NaiveDate;
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+fn sorted_query(sorts: Vec<PictureSort>) -> PicturesQuery {
+    let mut query = PicturesQuery::from_page(1);
+    query.filters = vec![PictureFilter::Favorite { invert: false }];
+    query.sorts = sorts;
+    query
+}
+
+#[test]
+pub fn test_query_order_ends_with_the_id() {
+    let order = Picture::query_order(&[PictureSort::CreationDate { ascend: false }, PictureSort::EditionDate { ascend: true }]);
+    assert_eq!(
+        order,
+        vec![
+            (PictureOrderColumn::CreationDate, false),
+            (PictureOrderColumn::EditionDate, true),
+            (PictureOrderColumn::Id, true),
+        ]
+    );
+    assert_eq!(Picture::query_order(&[]), vec![(PictureOrderColumn::Id, true)]);
+}
+
+#[test]
+pub fn test_neighbors_follow_the_query_order() {
+    let query = sorted_query(vec![PictureSort::CreationDate { ascend: false }]);
+    let query_sql = debug_query::<Pg, _>(&Picture::query_statement(1, query.clone(), 100)).to_string();
+    assert!(query_sql.contains("ORDER BY \"pictures\".\"creation_date\" DESC, \"pictures\".\"id\" ASC LIMIT"));
+
+    // Next picture: the first one after the picture in the query order, with the same filters
+    let next_sql = debug_query::<Pg, _>(&Picture::neighbor_statement(1, &query, &picture(7), false)).to_string();
+    assert!(next_sql.contains("\"pictures\".\"favorite\" = $"));
+    assert!(next_sql.contains("((\"pictures\".\"creation_date\" < $5) OR ((\"pictures\".\"creation_date\" = $6) AND (\"pictures\".\"id\" > $7)))"));
+    assert!(next_sql.contains("ORDER BY \"pictures\".\"creation_date\" DESC, \"pictures\".\"id\" ASC LIMIT $8"));
+    assert!(next_sql.ends_with("7, 1]"));
+
+    // Previous picture: the first one before the picture, in the reverse order
+    let previous_sql = debug_query::<Pg, _>(&Picture::neighbor_statement(1, &query, &picture(7), true)).to_string();
+    assert!(
+        previous_sql.contains("((\"pictures\".\"creation_date\" > $5) OR ((\"pictures\".\"creation_date\" = $6) AND (\"pictures\".\"id\" < $7)))")
+    );
+    assert!(previous_sql.contains("ORDER BY \"pictures\".\"creation_date\" ASC, \"pictures\".\"id\" DESC LIMIT $8"));
+}
+
+#[test]
+pub fn test_neighbors_compare_all_the_sorts() {
+    let query = sorted_query(vec![
+        PictureSort::EditionDate { ascend: true },
+        PictureSort::CreationDate { ascend: false },
+    ]);
+    let next_sql = debug_query::<Pg, _>(&Picture::neighbor_statement(1, &query, &picture(7), false)).to_string();
+    assert!(next_sql.contains(
+        "((\"pictures\".\"edition_date\" > $5) OR ((\"pictures\".\"edition_date\" = $6) AND ((\"pictures\".\"creation_date\" < $7) \
+         OR ((\"pictures\".\"creation_date\" = $8) AND (\"pictures\".\"id\" > $9)))))"
+    ));
+    assert!(next_sql.contains("ORDER BY \"pictures\".\"edition_date\" ASC, \"pictures\".\"creation_date\" DESC, \"pictures\".\"id\" ASC LIMIT $10"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_neighbors_in_query_are_the_adjacent_results() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "neighbors");
+    let other_user_id = insert_user(conn, "neighbors_other");
+    let day = |day: u32| NaiveDate::from_ymd_opt(2025, 6, day).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let oldest_id = insert_picture_created_at(conn, user_id, day(1));
+    let first_id = insert_picture_created_at(conn, user_id, day(2));
+    let second_id = insert_picture_created_at(conn, user_id, day(2));
+    let newest_id = insert_picture_created_at(conn, user_id, day(4));
+    // Not visible to the user
+    insert_picture_created_at(conn, other_user_id, day(3));
+
+    // Most recent first, the pictures of the same date by ascending id
+    let mut query = PicturesQuery::from_page(1);
+    query.sorts = vec![PictureSort::CreationDate { ascend: false }];
+    let neighbors = |conn: &mut DBConn, picture_id| Picture::neighbors_in_query(conn, user_id, picture_id, query.clone()).unwrap();
+    let ids = |neighbors: PictureNeighbors| (neighbors.previous_id, neighbors.next_id);
+    assert_eq!(ids(neighbors(conn, newest_id)), (None, Some(first_id)));
+    assert_eq!(ids(neighbors(conn, first_id)), (Some(newest_id), Some(second_id)));
+    assert_eq!(ids(neighbors(conn, second_id)), (Some(first_id), Some(oldest_id)));
+    assert_eq!(ids(neighbors(conn, oldest_id)), (Some(second_id), None));
+
+    // The picture must match the query
+    query.filters = vec![PictureFilter::Favorite { invert: false }];
+    let error = ErrorResponse::from(Picture::neighbors_in_query(conn, user_id, first_id, query).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::NotFound));
+}
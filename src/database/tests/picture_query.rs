@@ -0,0 +1,896 @@
+use crate::api::picture::ListPictureData;
+use crate::api::query_pictures::{
+    parse_calendar_range, restore_and_group_by_query, ListInclude, PictureCursor, PictureFilter, PictureSort, PicturesQuery,
+};
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::{Picture, PictureChange, TagFacet};
+use crate::database::picture::picture_tag::PictureTag;
+use crate::database::schema::pictures;
+use crate::database::schema::PictureOrientation;
+use crate::database::tests::test_database::{
+    count_queries, insert_filter_arrangement, insert_picture, insert_picture_created_at, insert_share, insert_tags, insert_user, test_connection,
+};
+use crate::database::user::user::User;
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::group_pictures;
+use crate::grouping::strategy_filtering::{FilterType, StrategyFiltering};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+fn query_sql(filters: Vec<PictureFilter>) -> String {
+    let mut query = PicturesQuery::from_page(1);
+    query.filters = filters;
+    debug_query::<Pg, _>(&Picture::query_statement(1, query, 100)).to_string()
+}
+
+/// Replace the numbered placeholders (`$3`) by `$`, so that fragments can be asserted whatever the binds before them.
+pub fn without_bind_numbers(sql: &str) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut in_placeholder = false;
+    for c in sql.chars() {
+        if in_placeholder && c.is_ascii_digit() {
+            continue;
+        }
+        in_placeholder = c == '$';
+        result.push(c);
+    }
+    result
+}
+
+#[test]
+pub fn test_author_filter() {
+    let sql = query_sql(vec![PictureFilter::Author { invert: false, ids: vec![2, 3] }]);
+    // Filtering on the author, while keeping the visibility check on the owner
+    assert!(sql.contains("\"pictures\".\"author_id\" = ANY($"));
+    assert!(sql.contains("\"pictures\".\"owner_id\" = $"));
+    assert!(sql.contains("[2, 3]"));
+
+    let sql = query_sql(vec![PictureFilter::Author { invert: true, ids: vec![2] }]);
+    assert!(sql.contains("NOT ((\"pictures\".\"author_id\" = ANY($"));
+}
+
+#[test]
+pub fn test_author_filter_composes_with_other_filters() {
+    let sql = query_sql(vec![
+        PictureFilter::Owned { invert: true },
+        PictureFilter::Author { invert: false, ids: vec![1] },
+    ]);
+    assert!(sql.contains("\"pictures\".\"author_id\" = ANY($"));
+    assert!(sql.contains("NOT ((\"pictures\".\"owner_id\" = $"));
+}
+
+#[test]
+pub fn test_author_filter_deserialization() {
+    let filter: PictureFilter = serde_json::from_str(r#"{"type": "Author", "invert": false, "ids": [4, 5]}"#).unwrap();
+    assert_eq!(filter, PictureFilter::Author { invert: false, ids: vec![4, 5] });
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_author_filter_selects_the_accessible_pictures_of_the_authors() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "author_filter");
+    let other_user_id = insert_user(conn, "author_filter_other");
+    let author_id = insert_user(conn, "author_filter_author");
+    let insert_authored_picture = |conn: &mut DBConn, owner_id: i32, author_id: i32| {
+        let picture_id = insert_picture(conn, owner_id, &[]);
+        diesel::update(pictures::table.find(picture_id))
+            .set(pictures::author_id.eq(author_id))
+            .execute(conn)
+            .unwrap();
+        picture_id
+    };
+    let own_picture_id = insert_authored_picture(conn, user_id, user_id);
+    let copied_picture_id = insert_authored_picture(conn, user_id, author_id);
+    let shared_picture_id = insert_authored_picture(conn, other_user_id, author_id);
+    let arrangement = Arrangement::new(conn, other_user_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![shared_picture_id]).unwrap();
+    insert_share(conn, user_id, group.id, true);
+    // Neither owned by the user nor shared with them
+    insert_authored_picture(conn, other_user_id, author_id);
+    let query_ids = |conn: &mut DBConn, filters: Vec<PictureFilter>| {
+        let mut query = PicturesQuery::from_page(1);
+        query.filters = filters;
+        Picture::query_ids(conn, user_id, query, 100).unwrap()
+    };
+
+    let author_filter = |invert: bool| PictureFilter::Author {
+        invert,
+        ids: vec![author_id],
+    };
+    assert_eq!(query_ids(conn, vec![author_filter(false)]), vec![copied_picture_id, shared_picture_id]);
+    assert_eq!(query_ids(conn, vec![author_filter(true)]), vec![own_picture_id]);
+    assert_eq!(
+        query_ids(conn, vec![PictureFilter::Owned { invert: false }, author_filter(false)]),
+        vec![copied_picture_id]
+    );
+}
+
+#[test]
+pub fn test_ungrouped_pictures_query() {
+    let sql = debug_query::<Pg, _>(&Picture::query_ungrouped_statement(1, 2, 100)).to_string();
+    // Only owned pictures that are not in the trash
+    assert!(sql.contains("\"pictures\".\"owner_id\" = $1"));
+    assert!(sql.contains("\"pictures\".\"deleted_date\" IS NULL"));
+    // Pictures with at least one membership in a group of the user's arrangements are excluded,
+    // the groups of the recipients the pictures are shared with don't count
+    assert!(sql.contains("NOT (EXISTS (SELECT \"groups_pictures\".\"picture_id\" FROM ((\"groups_pictures\""));
+    assert!(sql.contains("INNER JOIN \"arrangements\" ON (\"arrangements\".\"id\" = \"groups\".\"arrangement_id\"))"));
+    assert!(sql.contains("WHERE ((\"groups_pictures\".\"picture_id\" = \"pictures\".\"id\") AND (\"arrangements\".\"user_id\" = $2))))"));
+    assert!(!sql.contains("shared_groups"));
+    assert!(sql.contains("LIMIT $3 OFFSET $4"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_ungrouped_pictures_are_the_owned_ones_outside_the_user_groups() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "ungrouped");
+    let other_user_id = insert_user(conn, "ungrouped_other");
+    let date = |day: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let grouped_picture_id = insert_picture_created_at(conn, user_id, date(1));
+    let ungrouped_picture_id = insert_picture_created_at(conn, user_id, date(2));
+    let recipient_grouped_picture_id = insert_picture_created_at(conn, user_id, date(3));
+    let trashed_picture_id = insert_picture_created_at(conn, user_id, date(4));
+    insert_picture_created_at(conn, other_user_id, date(5));
+    diesel::update(pictures::table.find(trashed_picture_id))
+        .set(pictures::deleted_date.eq(Some(Utc::now().naive_utc())))
+        .execute(conn)
+        .unwrap();
+    let arrangement = Arrangement::new(conn, user_id, "Manual".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![grouped_picture_id]).unwrap();
+    // In a group of another user only, e.g. of a recipient the picture is shared with
+    let other_arrangement = Arrangement::new(conn, other_user_id, "Manual".to_string(), false, None).unwrap();
+    let other_group = Group::insert(conn, other_arrangement.id, "Group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, other_group.id, &vec![recipient_grouped_picture_id]).unwrap();
+    let ungrouped_ids = |conn: &mut DBConn, page: i32, page_size: i64| -> Vec<i64> {
+        Picture::query_ungrouped(conn, user_id, page, page_size)
+            .unwrap()
+            .iter()
+            .map(|picture| picture.id)
+            .collect()
+    };
+
+    assert_eq!(ungrouped_ids(conn, 1, 100), vec![recipient_grouped_picture_id, ungrouped_picture_id]);
+    assert_eq!(ungrouped_ids(conn, 2, 1), vec![ungrouped_picture_id]);
+
+    // Once grouped, the picture is not listed anymore
+    Group::add_pictures(conn, group.id, &vec![ungrouped_picture_id]).unwrap();
+    assert_eq!(ungrouped_ids(conn, 1, 100), vec![recipient_grouped_picture_id]);
+}
+
+#[test]
+pub fn test_orientation_filter() {
+    let sql = query_sql(vec![PictureFilter::Orientation {
+        invert: false,
+        values: vec![PictureOrientation::Rotate90, PictureOrientation::Rotate270],
+    }]);
+    assert!(sql.contains("\"pictures\".\"orientation\" = ANY($"));
+    assert!(sql.contains("[Rotate90, Rotate270]"));
+
+    let sql = query_sql(vec![PictureFilter::Orientation {
+        invert: true,
+        values: vec![PictureOrientation::Normal],
+    }]);
+    assert!(sql.contains("NOT ((\"pictures\".\"orientation\" = ANY($"));
+
+    let filter: PictureFilter = serde_json::from_str(r#"{"type": "Orientation", "invert": false, "values": ["Normal", "Rotate180"]}"#).unwrap();
+    assert_eq!(
+        filter,
+        PictureFilter::Orientation {
+            invert: false,
+            values: vec![PictureOrientation::Normal, PictureOrientation::Rotate180]
+        }
+    );
+}
+
+#[test]
+pub fn test_aspect_ratio_filter() {
+    // Portrait pictures
+    let sql = query_sql(vec![PictureFilter::AspectRatio { min: None, max: Some(1.0) }]);
+    assert!(sql.contains("float8(\"pictures\".\"width\") <= (float8(\"pictures\".\"height\") * $"));
+    assert!(!sql.contains(">= (float8("));
+
+    let sql = query_sql(vec![PictureFilter::AspectRatio {
+        min: Some(1.5),
+        max: Some(2.0),
+    }]);
+    assert!(sql.contains("float8(\"pictures\".\"width\") >= (float8(\"pictures\".\"height\") * $"));
+    assert!(sql.contains("float8(\"pictures\".\"width\") <= (float8(\"pictures\".\"height\") * $"));
+    assert!(sql.contains("1.5, 2.0"));
+}
+
+#[test]
+pub fn test_size_filter() {
+    let sql = query_sql(vec![PictureFilter::SizeKo {
+        invert: false,
+        min: Some(1000),
+        max: Some(5000),
+    }]);
+    assert!(sql.contains("\"pictures\".\"size_ko\" BETWEEN $"));
+    assert!(sql.contains("1000, 5000"));
+
+    // Open-ended bounds
+    let sql = query_sql(vec![PictureFilter::SizeKo {
+        invert: false,
+        min: Some(1000),
+        max: None,
+    }]);
+    assert!(sql.contains("\"pictures\".\"size_ko\" >= $"));
+    assert!(!sql.contains("\"pictures\".\"size_ko\" <= $"));
+    let sql = query_sql(vec![PictureFilter::SizeKo {
+        invert: false,
+        min: None,
+        max: Some(5000),
+    }]);
+    assert!(sql.contains("\"pictures\".\"size_ko\" <= $"));
+    assert!(!sql.contains("\"pictures\".\"size_ko\" >= $"));
+    let sql = query_sql(vec![PictureFilter::SizeKo {
+        invert: false,
+        min: None,
+        max: None,
+    }]);
+    assert!(!sql.contains("size_ko\" "));
+
+    // Inverted, composed with another filter
+    let sql = query_sql(vec![
+        PictureFilter::Owned { invert: false },
+        PictureFilter::SizeKo {
+            invert: true,
+            min: Some(1000),
+            max: Some(5000),
+        },
+    ]);
+    assert!(sql.contains("NOT ((\"pictures\".\"size_ko\" BETWEEN $"));
+    assert!(sql.contains("\"pictures\".\"owner_id\" = $"));
+    let sql = query_sql(vec![PictureFilter::SizeKo {
+        invert: true,
+        min: Some(1000),
+        max: None,
+    }]);
+    assert!(sql.contains("\"pictures\".\"size_ko\" < $"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_size_filter_returns_the_pictures_in_the_range() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "size_filter");
+    let sizes = [500, 1000, 3000, 5000, 8000];
+    let picture_ids = sizes.map(|size| {
+        let picture_id = insert_picture(conn, user_id, &[]);
+        diesel::update(pictures::table.find(picture_id))
+            .set(pictures::size_ko.eq(size))
+            .execute(conn)
+            .unwrap();
+        picture_id
+    });
+    let query_sizes = |conn: &mut DBConn, filters: Vec<PictureFilter>| {
+        let mut query = PicturesQuery::from_page(1);
+        query.filters = filters;
+        Picture::query_ids(conn, user_id, query, 100)
+            .unwrap()
+            .iter()
+            .map(|picture_id| sizes[picture_ids.iter().position(|id| id == picture_id).unwrap()])
+            .collect::<Vec<_>>()
+    };
+    let size_ko = |invert: bool, min: Option<i32>, max: Option<i32>| PictureFilter::SizeKo { invert, min, max };
+
+    // Both bounds are included
+    assert_eq!(query_sizes(conn, vec![size_ko(false, Some(1000), Some(5000))]), vec![1000, 3000, 5000]);
+    assert_eq!(query_sizes(conn, vec![size_ko(false, Some(3000), None)]), vec![3000, 5000, 8000]);
+    assert_eq!(query_sizes(conn, vec![size_ko(false, None, Some(1000))]), vec![500, 1000]);
+    assert_eq!(query_sizes(conn, vec![size_ko(false, None, None)]), sizes.to_vec());
+
+    // Inverted, the bounds are excluded
+    assert_eq!(query_sizes(conn, vec![size_ko(true, Some(1000), Some(5000))]), vec![500, 8000]);
+    assert_eq!(query_sizes(conn, vec![size_ko(true, Some(1000), None)]), vec![500]);
+    assert_eq!(query_sizes(conn, vec![size_ko(true, None, Some(5000))]), vec![8000]);
+
+    // Composed with another filter
+    diesel::update(pictures::table.filter(pictures::id.eq_any(vec![picture_ids[0], picture_ids[3]])))
+        .set(pictures::favorite.eq(true))
+        .execute(conn)
+        .unwrap();
+    assert_eq!(
+        query_sizes(conn, vec![PictureFilter::Favorite { invert: false }, size_ko(false, Some(1000), None)]),
+        vec![5000]
+    );
+}
+
+#[test]
+pub fn test_groups_pending_deletion_are_hidden_from_recipients() {
+    let sql = query_sql(vec![]);
+    // Shared pictures are visible through the groups shared with the user, unless the group is being deleted
+    assert!(sql.contains(
+        "INNER JOIN \"groups\" ON (\"groups\".\"id\" = \"groups_pictures\".\"group_id\")) \
+         WHERE (((\"groups_pictures\".\"picture_id\" = \"pictures\".\"id\") AND (\"shared_groups\".\"user_id\" = $2)) \
+         AND (\"groups\".\"to_be_deleted\" = $3))"
+    ));
+    assert!(sql.ends_with("binds: [1, 1, false, 100, 0]"));
+}
+
+#[test]
+pub fn test_copied_filter() {
+    let copied_sql = query_sql(vec![PictureFilter::Copied { invert: false }]);
+    assert!(copied_sql.contains("\"pictures\".\"copied\" = $"));
+    assert!(copied_sql.ends_with("[1, 1, false, true, 100, 0]"));
+
+    // Originals only
+    let original_sql = query_sql(vec![PictureFilter::Copied { invert: true }]);
+    assert!(original_sql.contains("\"pictures\".\"copied\" = $"));
+    assert!(original_sql.ends_with("[1, 1, false, false, 100, 0]"));
+
+    let filter: PictureFilter = serde_json::from_str(r#"{"type": "Copied", "invert": true}"#).unwrap();
+    assert_eq!(filter, PictureFilter::Copied { invert: true });
+}
+
+#[test]
+pub fn test_favorite_filter() {
+    let favorite_sql = query_sql(vec![PictureFilter::Favorite { invert: false }]);
+    assert!(favorite_sql.contains("\"pictures\".\"favorite\" = $"));
+    assert!(favorite_sql.ends_with("[1, 1, false, true, 100, 0]"));
+
+    // Pictures that are not favorites
+    let other_sql = query_sql(vec![PictureFilter::Favorite { invert: true }]);
+    assert!(other_sql.ends_with("[1, 1, false, false, 100, 0]"));
+
+    let filter: PictureFilter = serde_json::from_str(r#"{"type": "Favorite", "invert": false}"#).unwrap();
+    assert_eq!(filter, PictureFilter::Favorite { invert: false });
+}
+
+#[test]
+pub fn test_list_picture_data_has_size() {
+    let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let data = ListPictureData::from((7, "IMG_0007.jpg".to_string(), 4000, 3000, 2048, date, date, None));
+    assert_eq!(data.size_ko, 2048);
+    assert_eq!(data.width, 4000);
+    assert_eq!(data.height, 3000);
+
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["size_ko"], 2048);
+}
+
+#[test]
+pub fn test_tag_facets_count_the_filtered_pictures() {
+    let mut query = PicturesQuery::from_page(3);
+    query.filters = vec![PictureFilter::Author { invert: false, ids: vec![2] }];
+    let sql = debug_query::<Pg, _>(&Picture::tag_facets_statement(1, query)).to_string();
+
+    // Pictures are counted per tag, in a single grouped query
+    assert!(sql.contains("SELECT \"pictures_tags\".\"tag_id\", COUNT(*)"));
+    assert!(sql.contains("GROUP BY \"pictures_tags\".\"tag_id\""));
+    // Only the tags of the user are counted
+    assert!(sql.contains("\"tag_groups\".\"user_id\" = $1"));
+    // Over the pictures of the query, with the visibility check and the filters, but regardless of the page
+    assert!(sql.contains("\"pictures_tags\".\"picture_id\" = ANY(SELECT \"pictures\".\"id\" FROM \"pictures\""));
+    assert!(sql.contains("\"pictures\".\"author_id\" = ANY($"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(!sql.contains("LIMIT"));
+    assert!(!sql.contains("OFFSET"));
+
+    assert_eq!(TagFacet::from((4, 12)), TagFacet { tag_id: 4, count: 12 });
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_tag_facets_count_the_pictures_of_the_query_per_tag() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "tag_facets");
+    let other_user_id = insert_user(conn, "tag_facets_other");
+    let tag_ids = insert_tags(conn, user_id, 3);
+    let other_tag_ids = insert_tags(conn, other_user_id, 1);
+    let (a, b) = (tag_ids[0], tag_ids[1]);
+    let both = insert_picture(conn, user_id, &[a, b]);
+    insert_picture(conn, user_id, &[a]);
+    let only_b = insert_picture(conn, user_id, &[b]);
+    insert_picture(conn, user_id, &[]);
+    // A shared picture, tagged by its owner and by the recipient
+    let shared_picture_id = insert_picture(conn, other_user_id, &[other_tag_ids[0], a]);
+    let arrangement = Arrangement::new(conn, other_user_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![shared_picture_id]).unwrap();
+    insert_share(conn, user_id, group.id, true);
+    // A picture of another user, not shared
+    insert_picture(conn, other_user_id, &[a]);
+
+    // All the pictures of the query are counted, regardless of the page, and only the tags of the user are listed
+    let mut query = PicturesQuery::from_page(3);
+    query.page_size = Some(1);
+    assert_eq!(
+        Picture::tag_facets_for_query(conn, user_id, query.clone()).unwrap(),
+        vec![TagFacet { tag_id: a, count: 3 }, TagFacet { tag_id: b, count: 2 }]
+    );
+
+    // Only the pictures matching the filters are counted
+    diesel::update(pictures::table.filter(pictures::id.eq_any(vec![both, only_b])))
+        .set(pictures::favorite.eq(true))
+        .execute(conn)
+        .unwrap();
+    query.filters = vec![PictureFilter::Favorite { invert: false }];
+    assert_eq!(
+        Picture::tag_facets_for_query(conn, user_id, query).unwrap(),
+        vec![TagFacet { tag_id: a, count: 1 }, TagFacet { tag_id: b, count: 2 }]
+    );
+}
+
+#[test]
+pub fn test_changes_since_lists_edited_and_deleted_pictures() {
+    let since = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let sql = debug_query::<Pg, _>(&Picture::changes_since_statement(1, since, 2, 50)).to_string();
+
+    // Pictures modified after the timestamp, or moved to the trash after it, including the trashed ones.
+    // The edition date comes from the EXIF metadata, and doesn't change with the tags, ratings or groups
+    assert!(sql.contains("((\"pictures\".\"modified_at\" > $"));
+    assert!(!sql.contains("\"pictures\".\"edition_date\" > $"));
+    assert!(sql.contains("OR ((\"pictures\".\"deleted_date\" IS NOT NULL) AND (\"pictures\".\"deleted_date\" > $"));
+    assert!(!sql.contains("\"pictures\".\"deleted_date\" IS NULL"));
+    // Restricted to the pictures the user can access, paginated
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(sql.contains("ORDER BY \"pictures\".\"id\" ASC LIMIT $"));
+    assert!(sql.contains("2024-05-01T12:00:00"));
+
+    // A modified picture and a picture deleted after the timestamp, as returned to the client
+    let modified = PictureChange::from((3, since + Duration::hours(1), None));
+    let deleted = PictureChange::from((4, since - Duration::days(3), Some(since + Duration::minutes(5))));
+    assert!(!modified.deleted && modified.modified_at > since);
+    assert!(deleted.deleted);
+    let json = serde_json::to_value(&deleted).unwrap();
+    assert_eq!(json["picture_id"], 4);
+    assert_eq!(json["deleted"], true);
+}
+
+#[test]
+pub fn test_picture_changes_set_the_modification_date() {
+    let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let favorite_sql = debug_query::<Pg, _>(&Picture::set_favorite_statement(3, 1, true, date)).to_string();
+    // Both dates are set to the same UTC timestamp
+    assert!(favorite_sql.contains("\"edition_date\" = $2, \"modified_at\" = $3"));
+    assert!(favorite_sql.ends_with("binds: [true, 2024-05-01T12:00:00, 2024-05-01T12:00:00, 3, 1]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_changes_feed_lists_the_pictures_changed_since_the_last_synchronization() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "changes_feed");
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let favorite_picture_id = insert_picture(conn, user_id, &[]);
+    let tagged_picture_id = insert_picture(conn, user_id, &[]);
+    let unchanged_picture_id = insert_picture(conn, user_id, &[]);
+    let picture_ids = [favorite_picture_id, tagged_picture_id, unchanged_picture_id];
+    let synchronization_date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    diesel::update(pictures::table.filter(pictures::id.eq_any(picture_ids)))
+        .set(pictures::modified_at.eq(synchronization_date - Duration::days(1)))
+        .execute(conn)
+        .unwrap();
+    assert!(Picture::changes_since(conn, user_id, synchronization_date, 1, 50).unwrap().is_empty());
+
+    // A change of the picture itself, and a change of its tags
+    let before_changes = Utc::now().naive_utc() - Duration::seconds(1);
+    Picture::set_favorite(conn, favorite_picture_id, user_id, true).unwrap();
+    PictureTag::add_pictures(conn, tag_ids[0], &vec![tagged_picture_id]).unwrap();
+
+    let changes = Picture::changes_since(conn, user_id, synchronization_date, 1, 50).unwrap();
+    assert_eq!(
+        changes.iter().map(|change| change.picture_id).collect::<Vec<_>>(),
+        vec![favorite_picture_id, tagged_picture_id]
+    );
+    // Dated in UTC, whatever the time zone of the database server
+    let after_changes = Utc::now().naive_utc() + Duration::seconds(1);
+    assert!(changes
+        .iter()
+        .all(|change| !change.deleted && change.modified_at > before_changes && change.modified_at < after_changes));
+}
+
+#[test]
+pub fn test_group_ids_are_included_only_when_requested() {
+    let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let list = || {
+        vec![
+            ListPictureData::from((7, "IMG_0007.jpg".to_string(), 4000, 3000, 2048, date, date, None)),
+            ListPictureData::from((8, "IMG_0008.jpg".to_string(), 4000, 3000, 2048, date, date, None)),
+        ]
+    };
+
+    // Without the parameter, the list is unchanged
+    assert_eq!(ListInclude::parse(None).unwrap(), ListInclude { groups: false });
+    assert_eq!(ListInclude::parse(Some("")).unwrap(), ListInclude { groups: false });
+    let json = serde_json::to_value(&list()).unwrap();
+    assert!(json[0].get("group_ids").is_none());
+
+    // With it, every picture gets its groups, possibly none
+    assert_eq!(ListInclude::parse(Some("groups")).unwrap(), ListInclude { groups: true });
+    let mut pictures = list();
+    ListPictureData::attach_group_ids(&mut pictures, &[(7, 3), (7, 12), (9, 4)]);
+    let json = serde_json::to_value(&pictures).unwrap();
+    assert_eq!(json[0]["group_ids"], serde_json::json!([3, 12]));
+    assert_eq!(json[1]["group_ids"], serde_json::json!([]));
+
+    let error = ErrorResponse::from(ListInclude::parse(Some("groups,tags")).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+}
+
+#[test]
+pub fn test_pictures_groups_are_fetched_in_one_query() {
+    let sql = debug_query::<Pg, _>(&Group::user_groups_of_pictures_statement(1, &[7, 8])).to_string();
+    assert!(sql.contains("SELECT \"groups_pictures\".\"picture_id\", \"groups_pictures\".\"group_id\" FROM (\"groups_pictures\""));
+    // Only the groups of the user's arrangements, for the listed pictures
+    assert!(sql.contains("\"arrangements\".\"user_id\" = $1"));
+    assert!(sql.contains("\"groups_pictures\".\"picture_id\" = ANY($2)"));
+    assert!(sql.contains("[7, 8]"));
+}
+
+#[test]
+pub fn test_inverted_group_filter_excludes_pictures_in_any_group() {
+    let sql = query_sql(vec![PictureFilter::Group {
+        invert: true,
+        ids: vec![3, 4, 5],
+    }]);
+    // A single membership in any of the groups is enough to exclude a picture: NOT EXISTS over all the ids,
+    // rather than one NOT EXISTS per group or a membership outside of a group
+    assert!(sql.contains(
+        "NOT (EXISTS (SELECT \"gp_alias\".\"group_id\", \"gp_alias\".\"picture_id\" FROM \"groups_pictures\" AS \"gp_alias\" \
+         WHERE ((\"gp_alias\".\"picture_id\" = \"pictures\".\"id\") AND (\"gp_alias\".\"group_id\" = ANY($4)))))"
+    ));
+    assert_eq!(sql.matches("EXISTS (SELECT \"gp_alias\"").count(), 1);
+    assert!(sql.ends_with("binds: [1, 1, false, [3, 4, 5], 100, 0]"));
+
+    let sql = query_sql(vec![PictureFilter::Group {
+        invert: false,
+        ids: vec![3, 4, 5],
+    }]);
+    assert!(!sql.contains("NOT (EXISTS (SELECT \"gp_alias\""));
+    assert!(sql.contains("AND EXISTS (SELECT \"gp_alias\".\"group_id\""));
+}
+
+#[test]
+pub fn test_keyset_pagination_statement() {
+    let mut query = PicturesQuery::from_page(3);
+    query.sorts = vec![PictureSort::CreationDate { ascend: false }];
+    query.after = Some(PictureCursor {
+        creation_date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+        id: 42,
+    });
+    assert!(query.check_keyset_sorts().is_ok());
+    let sql = debug_query::<Pg, _>(&Picture::query_statement(1, query, 50)).to_string();
+
+    // The page follows the cursor, whatever the page number
+    assert!(sql.contains("((\"pictures\".\"creation_date\" < $4) OR ((\"pictures\".\"creation_date\" = $5) AND (\"pictures\".\"id\" > $6)))"));
+    assert!(sql.contains("ORDER BY \"pictures\".\"creation_date\" DESC, \"pictures\".\"id\" ASC LIMIT $7"));
+    assert!(!sql.contains("OFFSET"));
+    assert!(sql.ends_with("binds: [1, 1, false, 2024-05-01T12:00:00, 2024-05-01T12:00:00, 42, 50]"));
+}
+
+#[test]
+pub fn test_keyset_pagination_rejects_other_sorts() {
+    let mut query = PicturesQuery::from_page(1);
+    query.after = Some(PictureCursor {
+        creation_date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+        id: 42,
+    });
+    for sorts in [
+        vec![],
+        vec![PictureSort::EditionDate { ascend: true }],
+        vec![PictureSort::CreationDate { ascend: true }],
+        vec![PictureSort::CreationDate { ascend: false }, PictureSort::EditionDate { ascend: false }],
+    ] {
+        query.sorts = sorts;
+        let error = ErrorResponse::from(query.check_keyset_sorts().unwrap_err());
+        assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    }
+    query.sorts = vec![PictureSort::CreationDate { ascend: false }];
+    assert!(query.check_keyset_sorts().is_ok());
+    // Without keyset, any sort is fine
+    query.after = None;
+    assert!(query.check_keyset_sorts().is_ok());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_keyset_pages_are_stable_under_inserts() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "keyset");
+    let date = |day: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    // Pictures (day, name), some sharing a creation date
+    let mut ids = HashMap::new();
+    for (day, name) in [(1, 1), (2, 2), (2, 3), (2, 4), (3, 5), (4, 6), (4, 7), (5, 8)] {
+        ids.insert(insert_picture_created_at(conn, user_id, date(day)), name);
+    }
+    let page = |conn: &mut DBConn, after: Option<PictureCursor>| {
+        let mut query = PicturesQuery::from_page(1);
+        query.sorts = vec![PictureSort::CreationDate { ascend: false }];
+        query.after = after;
+        query.check_keyset_sorts().unwrap();
+        Picture::query(conn, user_id, query, 3).unwrap()
+    };
+    let cursor = |picture: &ListPictureData| PictureCursor {
+        creation_date: picture.creation_date,
+        id: picture.id,
+    };
+
+    let mut seen = page(conn, None);
+    // A picture is inserted mid-iteration, more recent than the next pages: it must not shift them
+    ids.insert(insert_picture_created_at(conn, user_id, date(6)), 9);
+    // and another one, older, sharing a creation date with the following pages: it is listed once
+    ids.insert(insert_picture_created_at(conn, user_id, date(2)), 10);
+
+    let mut after = seen.last().map(cursor);
+    loop {
+        let next = page(conn, after);
+        if next.is_empty() {
+            break;
+        }
+        after = next.last().map(cursor);
+        seen.extend(next);
+    }
+    let names: Vec<i32> = seen.iter().map(|picture| ids[&picture.id]).collect();
+    assert_eq!(names, vec![8, 6, 7, 5, 2, 3, 4, 10, 1]);
+}
+
+#[test]
+pub fn test_picture_cursor_parsing() {
+    assert_eq!(PictureCursor::parse(None, None).unwrap(), None);
+    let cursor = PictureCursor::parse(Some("2024-05-01T12:30:00.250"), Some(7)).unwrap().unwrap();
+    assert_eq!(cursor.id, 7);
+    assert_eq!(cursor.creation_date.to_string(), "2024-05-01 12:30:00.250");
+
+    let error = ErrorResponse::from(PictureCursor::parse(Some("yesterday"), Some(7)).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    let error = ErrorResponse::from(PictureCursor::parse(None, Some(7)).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+}
+
+#[test]
+pub fn test_query_ids_selects_all_the_matching_ids() {
+    let mut query = PicturesQuery::from_page(3);
+    query.page_size = Some(10);
+    query.sorts = vec![PictureSort::EditionDate { ascend: true }];
+    query.filters = vec![PictureFilter::Favorite { invert: false }];
+    let sql = debug_query::<Pg, _>(&Picture::query_ids_statement(1, query, 1001)).to_string();
+
+    // Only the ids, with the same filters and visibility check as the listing, but neither the sorts nor the page
+    assert!(sql.starts_with("SELECT \"pictures\".\"id\" FROM \"pictures\" WHERE"));
+    assert!(sql.contains("\"pictures\".\"favorite\" = $"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(sql.contains("ORDER BY \"pictures\".\"id\" ASC LIMIT $"));
+    assert!(!sql.contains("OFFSET"));
+    assert!(!sql.contains("edition_date"));
+    assert!(sql.ends_with("binds: [1, 1, false, true, 1001]"));
+
+    // The complete set is returned up to the cap, a larger set is refused
+    assert_eq!(Picture::check_query_ids_count(vec![1, 4, 9], 3).unwrap(), vec![1, 4, 9]);
+    let error = ErrorResponse::from(Picture::check_query_ids_count(vec![1, 4, 9, 12], 3).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_query_ids_lists_the_matching_accessible_pictures() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "query_ids");
+    let other_user_id = insert_user(conn, "query_ids_other");
+    let picture_ids = (0..4).map(|_| insert_picture(conn, user_id, &[])).collect::<Vec<_>>();
+    let other_picture_id = insert_picture(conn, other_user_id, &[]);
+    let shared_picture_id = insert_picture(conn, other_user_id, &[]);
+    let arrangement = Arrangement::new(conn, other_user_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![shared_picture_id]).unwrap();
+    insert_share(conn, user_id, group.id, true);
+    diesel::update(pictures::table.filter(pictures::id.eq_any(vec![picture_ids[1], picture_ids[3], other_picture_id, shared_picture_id])))
+        .set(pictures::favorite.eq(true))
+        .execute(conn)
+        .unwrap();
+
+    // All the favorite pictures the user can access, whatever the page and the sorts
+    let mut query = PicturesQuery::from_page(2);
+    query.page_size = Some(1);
+    query.sorts = vec![PictureSort::CreationDate { ascend: false }];
+    query.filters = vec![PictureFilter::Favorite { invert: false }];
+    assert_eq!(
+        Picture::query_ids(conn, user_id, query.clone(), 3).unwrap(),
+        vec![picture_ids[1], picture_ids[3], shared_picture_id]
+    );
+
+    // A larger set than the cap is refused
+    let error = ErrorResponse::from(Picture::query_ids(conn, user_id, query, 2).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+}
+
+#[test]
+pub fn test_calendar_counts_statement() {
+    let from = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 5, 31).unwrap();
+    let sql = debug_query::<Pg, _>(&Picture::calendar_counts_statement(1, from, to)).to_string();
+
+    // Both days are included: the range ends at the start of the day after `to`
+    assert!(sql.contains("\"pictures\".\"creation_date\" >= $1"));
+    assert!(sql.contains("\"pictures\".\"creation_date\" < $2"));
+    assert!(sql.contains("\"pictures\".\"deleted_date\" IS NULL"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(sql.contains("GROUP BY DATE(\"pictures\".\"creation_date\") ORDER BY DATE(\"pictures\".\"creation_date\")"));
+    assert!(sql.ends_with("binds: [2024-05-01T00:00:00, 2024-06-01T00:00:00, 1, 1, false]"));
+
+    assert_eq!(parse_calendar_range("2024-05-01", "2024-05-01").unwrap(), (from, from));
+    for (from, to) in [("2024-05-31", "2024-05-01"), ("2024-13-01", "2024-05-01"), ("2024-05-01", "yesterday")] {
+        let error = ErrorResponse::from(parse_calendar_range(from, to).unwrap_err());
+        assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    }
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_calendar_counts_pictures_per_day() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "calendar");
+    let other_user_id = insert_user(conn, "calendar_other");
+    let at = |day: &str, time: &str| NaiveDateTime::parse_from_str(&format!("{} {}", day, time), "%Y-%m-%d %H:%M:%S").unwrap();
+    insert_picture_created_at(conn, user_id, at("2024-05-01", "00:00:00"));
+    insert_picture_created_at(conn, user_id, at("2024-05-01", "18:30:00"));
+    insert_picture_created_at(conn, user_id, at("2024-05-15", "23:59:59"));
+    insert_picture_created_at(conn, user_id, at("2024-05-31", "08:00:00"));
+    // Out of the range, in the trash, or not accessible
+    insert_picture_created_at(conn, user_id, at("2024-04-30", "23:59:59"));
+    insert_picture_created_at(conn, user_id, at("2024-06-01", "00:00:00"));
+    let trashed_id = insert_picture_created_at(conn, user_id, at("2024-05-15", "12:00:00"));
+    diesel::update(pictures::table.find(trashed_id))
+        .set(pictures::deleted_date.eq(Some(NaiveDateTime::default())))
+        .execute(conn)
+        .unwrap();
+    insert_picture_created_at(conn, other_user_id, at("2024-05-01", "12:00:00"));
+    // Shared with the user through two groups, counted once
+    let shared_id = insert_picture_created_at(conn, other_user_id, at("2024-05-31", "12:00:00"));
+    let arrangement = Arrangement::new(conn, other_user_id, "Shared".to_string(), false, None).unwrap();
+    for name in ["First", "Second"] {
+        let group = Group::insert(conn, arrangement.id, name.to_string(), false, None).unwrap();
+        Group::add_pictures(conn, group.id, &vec![shared_id]).unwrap();
+        insert_share(conn, user_id, group.id, true);
+    }
+
+    let from = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 5, 31).unwrap();
+    let counts = Picture::calendar_counts(conn, user_id, from, to)
+        .unwrap()
+        .into_iter()
+        .map(|day| (day.date.to_string(), day.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        counts,
+        vec![
+            ("2024-05-01".to_string(), 2),
+            ("2024-05-15".to_string(), 1),
+            ("2024-05-31".to_string(), 2)
+        ]
+    );
+}
+
+#[test]
+pub fn test_restore_by_query_restores_the_owned_deleted_pictures() {
+    let mut query = PicturesQuery::from_page(1);
+    query.filters = vec![PictureFilter::Favorite { invert: false }];
+    let sql = debug_query::<Pg, _>(&Picture::restore_batch_statement(1, query, 0, 1000)).to_string();
+    let sql = without_bind_numbers(&sql);
+
+    // The matching pictures of the user in the trash, after the previous batch, are taken out of the trash,
+    // and come back in the changes of the clients that removed them
+    assert!(sql.starts_with(
+        "UPDATE \"pictures\" SET \"deleted_date\" = $, \"modified_at\" = $ WHERE (\"pictures\".\"id\" = ANY(SELECT \"pictures\".\"id\""
+    ));
+    assert!(sql.contains("\"pictures\".\"favorite\" = $"));
+    assert!(sql.contains("(\"pictures\".\"owner_id\" = $)) AND (\"pictures\".\"deleted_date\" IS NOT NULL)) AND (\"pictures\".\"id\" > $)"));
+    assert!(sql.contains("ORDER BY \"pictures\".\"id\" ASC LIMIT $"));
+    // Their sizes are returned to count them back in the storage of the user
+    assert!(sql.contains("RETURNING \"pictures\".\"id\", \"pictures\".\"size_ko\""));
+    // The batch starts after the id 0 and holds at most 1000 pictures
+    assert!(sql.ends_with(", 0, 1000]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_restore_by_query_pages_through_the_trash() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "restore");
+    let other_user_id = insert_user(conn, "restore_other");
+    let trash = |conn: &mut DBConn, picture_ids: &[i64]| {
+        diesel::update(pictures::table.filter(pictures::id.eq_any(picture_ids)))
+            .set(pictures::deleted_date.eq(Some(NaiveDateTime::default())))
+            .execute(conn)
+            .unwrap();
+    };
+    let trashed_ids = (0..5).map(|_| insert_picture(conn, user_id, &[])).collect::<Vec<_>>();
+    trash(conn, &trashed_ids);
+    insert_picture(conn, user_id, &[]);
+    let other_trashed_id = insert_picture(conn, other_user_id, &[]);
+    trash(conn, &[other_trashed_id]);
+    let storage_count_ko = User::from_id(conn, &user_id).unwrap().storage_count_ko;
+
+    // Batches of 2, 2 and 1 pictures, the last one being shorter than the batch size
+    let batches = count_queries(conn, "UPDATE \"pictures\" SET \"deleted_date\"");
+    let restored_ids = Picture::restore_by_query(conn, user_id, &PicturesQuery::from_page(1), 2).unwrap();
+    assert_eq!(restored_ids, trashed_ids);
+    assert_eq!(batches.load(Ordering::SeqCst), 3);
+    assert_eq!(User::from_id(conn, &user_id).unwrap().storage_count_ko, storage_count_ko + 5);
+
+    // With as many pictures as a whole number of batches, an empty batch ends the loop
+    trash(conn, &trashed_ids[..4]);
+    let batches = count_queries(conn, "UPDATE \"pictures\" SET \"deleted_date\"");
+    assert_eq!(
+        Picture::restore_by_query(conn, user_id, &PicturesQuery::from_page(1), 2).unwrap(),
+        trashed_ids[..4]
+    );
+    assert_eq!(batches.load(Ordering::SeqCst), 3);
+
+    // The pictures of the other user are left in the trash
+    let other_deleted_date: Option<NaiveDateTime> = pictures::table.find(other_trashed_id).select(pictures::deleted_date).first(conn).unwrap();
+    assert!(other_deleted_date.is_some());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_restored_pictures_are_grouped_again() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "restore_group");
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let picture_id = insert_picture(conn, user_id, &tag_ids);
+    let group_id = insert_filter_arrangement(conn, user_id, "Tagged".to_string(), FilterType::IncludeTags(tag_ids).to_strategy());
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+
+    // In the trash, the picture leaves its group
+    diesel::update(pictures::table.find(picture_id))
+        .set(pictures::deleted_date.eq(Some(NaiveDateTime::default())))
+        .execute(conn)
+        .unwrap();
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    assert!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap().is_empty());
+
+    assert_eq!(
+        restore_and_group_by_query(conn, user_id, &PicturesQuery::from_page(1), 2).unwrap(),
+        vec![picture_id]
+    );
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap(), vec![picture_id]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_filter_tree_or_of_two_tags_returns_the_union() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "filter_tree");
+    let tag_ids = insert_tags(conn, user_id, 3);
+    let (a, b, c) = (tag_ids[0], tag_ids[1], tag_ids[2]);
+    let only_a = insert_picture(conn, user_id, &[a]);
+    let only_b = insert_picture(conn, user_id, &[b]);
+    insert_picture(conn, user_id, &[c]);
+    let both = insert_picture(conn, user_id, &[a, b]);
+    insert_picture(conn, user_id, &[]);
+
+    let mut query = PicturesQuery::from_page(1);
+    query.filter_tree = Some(StrategyFiltering::Or(Box::new(vec![
+        FilterType::IncludeTags(vec![a]).to_strategy(),
+        FilterType::IncludeTags(vec![b]).to_strategy(),
+    ])));
+    let picture_ids: Vec<i64> = Picture::query(conn, user_id, query.clone(), 100)
+        .unwrap()
+        .iter()
+        .map(|picture| picture.id)
+        .collect();
+    // Each picture once, even when it has both tags
+    assert_eq!(picture_ids, vec![only_a, only_b, both]);
+
+    // The tree is combined with the flat filters
+    query.filters = vec![PictureFilter::Tag { invert: true, ids: vec![a] }];
+    let picture_ids: Vec<i64> = Picture::query(conn, user_id, query, 100)
+        .unwrap()
+        .iter()
+        .map(|picture| picture.id)
+        .collect();
+    assert_eq!(picture_ids, vec![only_b]);
+}
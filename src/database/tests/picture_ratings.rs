@@ -0,0 +1,56 @@
+use crate::api::picture::{batch_picture_ids, rate_pictures_batch};
+use crate::database::database::DBConn;
+use crate::database::picture::rating::Rating;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+#[test]
+pub fn test_batch_rating_sets_all_accessible_pictures() {
+    // Picture 3 is not accessible, picture 1 is listed twice.
+    let picture_ids = batch_picture_ids(&[1, 2, 3, 1, 4], &[4, 2, 1]);
+    assert_eq!(picture_ids, vec![1, 2, 4]);
+
+    let sql = debug_query::<Pg, _>(&Rating::set_batch_statement(7, &picture_ids, 4)).to_string();
+    assert!(sql.starts_with(
+        "INSERT INTO \"ratings\" (\"user_id\", \"picture_id\", \"rating\") VALUES ($1, $2, $3), ($4, $5, $6), ($7, $8, $9) \
+         ON CONFLICT (\"user_id\", \"picture_id\") DO UPDATE SET \"rating\" = $10 RETURNING"
+    ));
+    assert!(sql.ends_with("binds: [7, 1, 4, 7, 2, 4, 7, 4, 4, 4]"));
+}
+
+#[test]
+pub fn test_batch_rating_null_clears_the_ratings() {
+    let sql = debug_query::<Pg, _>(&Rating::remove_batch_statement(7, &[1, 2, 4])).to_string();
+    assert!(sql.starts_with(
+        "DELETE FROM \"ratings\" WHERE ((\"ratings\".\"user_id\" = $1) AND (\"ratings\".\"picture_id\" = ANY($2))) RETURNING \"ratings\".\"picture_id\""
+    ));
+    assert!(sql.ends_with("binds: [7, [1, 2, 4]]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_batch_rating_rates_the_accessible_pictures_and_null_clears_them() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "batch_rating");
+    let other_user_id = insert_user(conn, "batch_rating_other");
+    let picture_ids = vec![insert_picture(conn, user_id, &[]), insert_picture(conn, user_id, &[])];
+    let other_picture_id = insert_picture(conn, other_user_id, &[]);
+    Rating::set(conn, user_id, picture_ids[0], 2).unwrap();
+    Rating::set(conn, other_user_id, other_picture_id, 1).unwrap();
+    let rating =
+        |conn: &mut DBConn, user_id: i32, picture_id: i64| Rating::from_picture_id(conn, picture_id, user_id).unwrap().map(|rating| rating.rating);
+
+    // The previous rating is replaced, the picture of the other user is skipped
+    let requested_ids = [picture_ids[0], picture_ids[1], other_picture_id, picture_ids[0]];
+    assert_eq!(rate_pictures_batch(conn, user_id, &requested_ids, Some(4)).unwrap(), picture_ids);
+    assert_eq!(rating(conn, user_id, picture_ids[0]), Some(4));
+    assert_eq!(rating(conn, user_id, picture_ids[1]), Some(4));
+    assert_eq!(rating(conn, user_id, other_picture_id), None);
+    assert_eq!(rating(conn, other_user_id, other_picture_id), Some(1));
+
+    assert_eq!(rate_pictures_batch(conn, user_id, &requested_ids, None).unwrap(), picture_ids);
+    assert_eq!(rating(conn, user_id, picture_ids[0]), None);
+    assert_eq!(rating(conn, user_id, picture_ids[1]), None);
+    assert_eq!(rating(conn, other_user_id, other_picture_id), Some(1));
+}
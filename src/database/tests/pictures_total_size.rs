@@ -0,0 +1,154 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::picture::picture::{ExifField, ExifHistogramBucket, Picture, PicturesTotalSize};
+use crate::database::schema::pictures;
+use crate::database::tests::test_database::{insert_picture, insert_share, insert_user, test_connection};
+use bigdecimal::BigDecimal;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+#[test]
+pub fn test_total_size_statement() {
+    let sql = debug_query::<Pg, _>(&Picture::total_size_for_statement(3, &[1, 2, 5])).to_string();
+    assert!(sql.starts_with("SELECT sum(\"pictures\".\"size_ko\"), COUNT(*) FROM \"pictures\""));
+    assert!(sql.contains("\"pictures\".\"id\" = ANY($"));
+    // Only the pictures owned by the user or shared with them are counted, each once
+    assert!(sql.contains("\"pictures\".\"owner_id\" = $"));
+    assert!(sql.contains("EXISTS (SELECT"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(!sql.contains("LEFT OUTER JOIN"));
+    assert!(sql.contains("[1, 2, 5], 3"));
+}
+
+#[test]
+pub fn test_total_size_from_row() {
+    assert_eq!(
+        PicturesTotalSize::from((Some(1024 + 2048 + 512), 3)),
+        PicturesTotalSize {
+            total_size_ko: 3584,
+            count: 3
+        }
+    );
+    // No accessible picture: SUM is NULL
+    assert_eq!(PicturesTotalSize::from((None, 0)), PicturesTotalSize { total_size_ko: 0, count: 0 });
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_total_size_sums_the_accessible_pictures_once() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "total_size");
+    let other_user_id = insert_user(conn, "total_size_other");
+    let insert_sized_picture = |conn: &mut DBConn, owner_id: i32, size_ko: i32| {
+        let picture_id = insert_picture(conn, owner_id, &[]);
+        diesel::update(pictures::table.find(picture_id))
+            .set(pictures::size_ko.eq(size_ko))
+            .execute(conn)
+            .unwrap();
+        picture_id
+    };
+    let owned_picture_ids = [insert_sized_picture(conn, user_id, 100), insert_sized_picture(conn, user_id, 200)];
+    let shared_picture_id = insert_sized_picture(conn, other_user_id, 400);
+    let other_picture_id = insert_sized_picture(conn, other_user_id, 800);
+    // The shared picture is in two groups shared with the user
+    let arrangement = Arrangement::new(conn, other_user_id, "Shared".to_string(), false, None).unwrap();
+    for name in ["First", "Second"] {
+        let group = Group::insert(conn, arrangement.id, name.to_string(), false, None).unwrap();
+        Group::add_pictures(conn, group.id, &vec![shared_picture_id]).unwrap();
+        insert_share(conn, user_id, group.id, true);
+    }
+
+    let picture_ids = [
+        owned_picture_ids[0],
+        owned_picture_ids[1],
+        shared_picture_id,
+        other_picture_id,
+        other_picture_id + 1000,
+    ];
+    assert_eq!(
+        Picture::total_size_for(conn, user_id, &picture_ids).unwrap(),
+        PicturesTotalSize {
+            total_size_ko: 700,
+            count: 3
+        }
+    );
+    assert_eq!(
+        Picture::total_size_for(conn, user_id, &[other_picture_id]).unwrap(),
+        PicturesTotalSize { total_size_ko: 0, count: 0 }
+    );
+}
+
+#[test]
+pub fn test_exif_histogram_statement() {
+    let sql = debug_query::<Pg, _>(&Picture::exif_histogram_statement(3, &[1, 2, 5], ExifField::FocalLength)).to_string();
+    assert!(sql.starts_with("SELECT \"pictures\".\"focal_length\"::text, COUNT(*) FROM \"pictures\""));
+    assert!(sql.contains("GROUP BY \"pictures\".\"focal_length\"::text"));
+    // Only the accessible pictures of the list are counted, each once
+    assert!(sql.contains("\"pictures\".\"id\" = ANY($"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $"));
+    assert!(!sql.contains("LEFT OUTER JOIN"));
+
+    let sql = debug_query::<Pg, _>(&Picture::exif_histogram_statement(3, &[1], ExifField::ExposureTime)).to_string();
+    assert!(sql.contains("GROUP BY (\"pictures\".\"exposure_time_num\" || '/' || \"pictures\".\"exposure_time_den\")"));
+}
+
+#[test]
+pub fn test_exif_histogram_counts_per_focal_length() {
+    // Seeded pictures: three at 50mm, one at 35mm, two at 85mm and two without focal length
+    let rows = vec![
+        (Some("85.00".to_string()), 2),
+        (None, 2),
+        (Some("35.00".to_string()), 1),
+        (Some("50.00".to_string()), 3),
+    ];
+    let buckets = ExifHistogramBucket::from_rows(rows);
+    let counts: Vec<(Option<&str>, i64)> = buckets.iter().map(|bucket| (bucket.value.as_deref(), bucket.count)).collect();
+    assert_eq!(counts, vec![(Some("50.00"), 3), (Some("85.00"), 2), (None, 2), (Some("35.00"), 1)]);
+
+    let json = serde_json::to_value(&buckets[2]).unwrap();
+    assert_eq!(json, serde_json::json!({"value": null, "count": 2}));
+    let field: ExifField = serde_json::from_str("\"FocalLength\"").unwrap();
+    assert_eq!(field, ExifField::FocalLength);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_exif_histogram_counts_the_accessible_pictures_of_the_list() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "exif_histogram");
+    let other_user_id = insert_user(conn, "exif_histogram_other");
+    let insert_focal_picture = |conn: &mut DBConn, owner_id: i32, focal_length: Option<i32>| {
+        let picture_id = insert_picture(conn, owner_id, &[]);
+        diesel::update(pictures::table.find(picture_id))
+            .set(pictures::focal_length.eq(focal_length.map(BigDecimal::from)))
+            .execute(conn)
+            .unwrap();
+        picture_id
+    };
+    let mut picture_ids = [Some(50), Some(50), Some(50), Some(35), None, None]
+        .map(|focal_length| insert_focal_picture(conn, user_id, focal_length))
+        .to_vec();
+    let shared_picture_id = insert_focal_picture(conn, other_user_id, Some(85));
+    let arrangement = Arrangement::new(conn, other_user_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![shared_picture_id]).unwrap();
+    insert_share(conn, user_id, group.id, true);
+    picture_ids.push(shared_picture_id);
+    // Neither a picture of another user that is not shared, nor a picture out of the list is counted
+    picture_ids.push(insert_focal_picture(conn, other_user_id, Some(85)));
+    insert_focal_picture(conn, user_id, Some(50));
+
+    let buckets = Picture::exif_histogram(conn, user_id, &picture_ids, ExifField::FocalLength).unwrap();
+    let counts: Vec<(Option<&str>, i64)> = buckets.iter().map(|bucket| (bucket.value.as_deref(), bucket.count)).collect();
+    assert_eq!(counts, vec![(Some("50"), 3), (None, 2), (Some("35"), 1), (Some("85"), 1)]);
+
+    diesel::update(pictures::table.filter(pictures::id.eq_any(&picture_ids[..2])))
+        .set((pictures::exposure_time_num.eq(1), pictures::exposure_time_den.eq(250)))
+        .execute(conn)
+        .unwrap();
+    let buckets = Picture::exif_histogram(conn, user_id, &picture_ids[..3], ExifField::ExposureTime).unwrap();
+    let counts: Vec<(Option<&str>, i64)> = buckets.iter().map(|bucket| (bucket.value.as_deref(), bucket.count)).collect();
+    assert_eq!(counts, vec![(Some("1/250"), 2), (None, 1)]);
+}
@@ -0,0 +1,105 @@
+use crate::api::query_pictures::{PictureFilter, PictureSort, PicturesQuery};
+use crate::api::saved_searches::run_saved_search;
+use crate::database::picture::picture::Picture;
+use crate::database::picture::saved_search::{SavedSearch, SavedSearchDetails};
+use crate::database::schema::*;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::utils::errors_catcher::{ErrorResponder, ErrorResponse, ErrorTypeKind};
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+fn pictures_query(page: i32) -> PicturesQuery {
+    let mut query = PicturesQuery::from_page(page);
+    query.filters = vec![
+        PictureFilter::Owned { invert: false },
+        PictureFilter::SizeKo {
+            invert: false,
+            min: Some(1000),
+            max: None,
+        },
+    ];
+    query.sorts = vec![PictureSort::CreationDate { ascend: false }];
+    query
+}
+
+#[test]
+pub fn test_saved_query_round_trip() {
+    let query = pictures_query(1);
+    let saved_search = SavedSearch {
+        id: 4,
+        user_id: 1,
+        name: "Large files".to_string(),
+        query: SavedSearch::query_to_json(&query).unwrap(),
+    };
+    assert_eq!(saved_search.get_query().unwrap(), query);
+    assert_eq!(
+        saved_search.to_details().unwrap(),
+        SavedSearchDetails {
+            id: 4,
+            name: "Large files".to_string(),
+            query,
+        }
+    );
+}
+
+#[test]
+pub fn test_saved_query_runs_like_inline_query() {
+    let saved_search = SavedSearch {
+        id: 4,
+        user_id: 1,
+        name: "Large files".to_string(),
+        query: SavedSearch::query_to_json(&pictures_query(1)).unwrap(),
+    };
+    for page in [1, 3] {
+        let saved = debug_query::<Pg, _>(&Picture::query_statement(1, saved_search.query_for_page(page).unwrap(), 100)).to_string();
+        let inline = debug_query::<Pg, _>(&Picture::query_statement(1, pictures_query(page), 100)).to_string();
+        assert_eq!(saved, inline);
+    }
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_saved_search_is_saved_listed_run_and_deleted_by_its_owner_only() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "saved_search_owner");
+    let other_user_id = insert_user(conn, "saved_search_other");
+    let picture_ids = [(); 2].map(|_| insert_picture(conn, user_id, &[]));
+    diesel::update(pictures::table.find(picture_ids[1]))
+        .set(pictures::size_ko.eq(2000))
+        .execute(conn)
+        .unwrap();
+
+    let saved_search = SavedSearch::insert(conn, user_id, "Large files".to_string(), &pictures_query(3)).unwrap();
+    let saved_searches = SavedSearch::from_user_id(conn, user_id).unwrap();
+    assert_eq!(saved_searches, vec![saved_search.clone()]);
+    assert_eq!(
+        saved_searches[0].to_details().unwrap(),
+        SavedSearchDetails {
+            id: saved_search.id,
+            name: "Large files".to_string(),
+            query: pictures_query(3),
+        }
+    );
+
+    // The stored page is ignored: only the large picture matches on the first page
+    let results: Vec<i64> = run_saved_search(conn, user_id, saved_search.id, 1)
+        .unwrap()
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(results, vec![picture_ids[1]]);
+
+    // Another user can neither list, run nor delete it
+    let not_found = |result: Result<(), ErrorResponder>| matches!(ErrorResponse::from(result.unwrap_err()).error_type, ErrorTypeKind::NotFound);
+    assert!(SavedSearch::from_user_id(conn, other_user_id).unwrap().is_empty());
+    assert!(not_found(run_saved_search(conn, other_user_id, saved_search.id, 1).map(|_| ())));
+    assert!(not_found(
+        SavedSearch::from_id_and_user_id(conn, saved_search.id, other_user_id).map(|_| ())
+    ));
+
+    // The owner deletes it
+    SavedSearch::delete(conn, SavedSearch::from_id_and_user_id(conn, saved_search.id, user_id).unwrap().id).unwrap();
+    assert!(SavedSearch::from_user_id(conn, user_id).unwrap().is_empty());
+    assert!(not_found(run_saved_search(conn, user_id, saved_search.id, 1).map(|_| ())));
+}
@@ -0,0 +1,131 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::{SharePermissions, SharedGroup};
+use crate::database::tests::test_database::{insert_picture, insert_share, insert_user, shared_group, test_connection};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use diesel::debug_query;
+use diesel::pg::Pg;
+
+#[test]
+pub fn test_read_only_share_is_rejected() {
+    let share = shared_group(10, SharePermissions::VIEW);
+    assert!(share.check_permission(SharePermissions::VIEW).is_ok());
+    for required in [SharePermissions::EDIT_TAGS, SharePermissions::ADD_PICTURES] {
+        let error = ErrorResponse::from(share.check_permission(required).unwrap_err());
+        assert!(matches!(error.error_type, ErrorTypeKind::Unauthorized));
+    }
+
+    // Picture 1 is owned, picture 2 is only in a read-only shared group
+    let lacking = SharedGroup::pictures_lacking_permission(&[1, 2], &[1], &[(2, SharePermissions::VIEW.bits())], SharePermissions::EDIT_TAGS);
+    assert_eq!(lacking, vec![2]);
+    // Picture 3 is not accessible at all
+    let lacking = SharedGroup::pictures_lacking_permission(&[1, 3], &[1], &[], SharePermissions::EDIT_TAGS);
+    assert_eq!(lacking, vec![3]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_read_only_recipient_is_rejected() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "read_only_owner");
+    let recipient_id = insert_user(conn, "read_only_recipient");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![picture_id]).unwrap();
+    insert_share(conn, recipient_id, group.id, true);
+    SharedGroup::update_permissions(conn, recipient_id, group.id, SharePermissions::VIEW).unwrap();
+
+    // The recipient can see the pictures of the group, but neither edit their tags nor add pictures to it
+    assert!(SharedGroup::require_permission(conn, recipient_id, group.id, SharePermissions::VIEW).is_ok());
+    assert!(SharedGroup::require_pictures_permission(conn, recipient_id, &[picture_id], SharePermissions::VIEW).is_ok());
+    for required in [SharePermissions::EDIT_TAGS, SharePermissions::ADD_PICTURES] {
+        let error = ErrorResponse::from(SharedGroup::require_permission(conn, recipient_id, group.id, required).unwrap_err());
+        assert!(matches!(error.error_type, ErrorTypeKind::Unauthorized));
+    }
+    let error =
+        ErrorResponse::from(SharedGroup::require_pictures_permission(conn, recipient_id, &[picture_id], SharePermissions::EDIT_TAGS).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::Unauthorized));
+    // The owner doesn't need any share
+    assert!(SharedGroup::require_pictures_permission(conn, owner_id, &[picture_id], SharePermissions::EDIT_TAGS).is_ok());
+}
+
+#[test]
+pub fn test_edit_share_is_allowed() {
+    let share = shared_group(10, SharePermissions::VIEW | SharePermissions::EDIT_TAGS);
+    assert!(share.check_permission(SharePermissions::EDIT_TAGS).is_ok());
+    assert!(share.check_permission(SharePermissions::VIEW | SharePermissions::EDIT_TAGS).is_ok());
+    assert!(share.check_permission(SharePermissions::ADD_PICTURES).is_err());
+
+    // Picture 2 is in a read-only group and in a group granting the edition of tags
+    let shared_pictures_permissions = [(2, SharePermissions::VIEW.bits()), (2, share.permissions)];
+    let lacking = SharedGroup::pictures_lacking_permission(&[1, 2], &[1], &shared_pictures_permissions, SharePermissions::EDIT_TAGS);
+    assert!(lacking.is_empty());
+}
+
+#[test]
+pub fn test_requested_permissions_are_validated() {
+    let permissions = SharePermissions::from_request_bits((SharePermissions::VIEW | SharePermissions::ADD_PICTURES).bits()).unwrap();
+    assert!(permissions.contains(SharePermissions::ADD_PICTURES));
+    assert_eq!(
+        SharePermissions::from_request_bits(SharePermissions::ALL.bits()).unwrap(),
+        SharePermissions::ALL
+    );
+
+    // Unknown bit, and permissions without VIEW
+    for bits in [SharePermissions::VIEW.bits() | 1 << 5, SharePermissions::EDIT_TAGS.bits(), -1] {
+        let error = ErrorResponse::from(SharePermissions::from_request_bits(bits).unwrap_err());
+        assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    }
+}
+
+#[test]
+pub fn test_lowered_permissions_apply_to_the_recipient() {
+    let mut share = shared_group(10, SharePermissions::VIEW | SharePermissions::EDIT_TAGS);
+    assert!(share.check_permission(SharePermissions::EDIT_TAGS).is_ok());
+
+    let permissions = SharePermissions::from_request_bits(SharePermissions::VIEW.bits()).unwrap();
+    let sql = debug_query::<Pg, _>(&SharedGroup::update_permissions_statement(share.user_id, share.group_id, permissions)).to_string();
+    assert!(sql.starts_with("UPDATE \"shared_groups\" SET \"permissions\" = $1"));
+    assert!(sql.contains("WHERE ((\"shared_groups\".\"user_id\" = $2) AND (\"shared_groups\".\"group_id\" = $3))"));
+    assert!(sql.ends_with("binds: [1, 2, 10]"));
+
+    // The updated share no longer allows the recipient to edit the tags of the pictures of the group
+    share.permissions = permissions.bits();
+    let error = ErrorResponse::from(share.check_permission(SharePermissions::EDIT_TAGS).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::Unauthorized));
+    let lacking = SharedGroup::pictures_lacking_permission(&[5], &[], &[(5, share.permissions)], SharePermissions::EDIT_TAGS);
+    assert_eq!(lacking, vec![5]);
+    assert!(share.check_permission(SharePermissions::VIEW).is_ok());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_updated_permissions_change_the_actions_of_the_recipient() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "permissions_owner");
+    let recipient_id = insert_user(conn, "permissions_recipient");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, group.id, &vec![picture_id]).unwrap();
+    insert_share(conn, recipient_id, group.id, true);
+    assert!(SharedGroup::require_pictures_permission(conn, recipient_id, &[picture_id], SharePermissions::EDIT_TAGS).is_ok());
+
+    // Lowered to the view only: the recipient can no longer edit the tags
+    let share = SharedGroup::update_permissions(conn, recipient_id, group.id, SharePermissions::VIEW).unwrap();
+    assert_eq!(share.permissions, SharePermissions::VIEW.bits());
+    let error =
+        ErrorResponse::from(SharedGroup::require_pictures_permission(conn, recipient_id, &[picture_id], SharePermissions::EDIT_TAGS).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::Unauthorized));
+    assert!(SharedGroup::require_pictures_permission(conn, recipient_id, &[picture_id], SharePermissions::VIEW).is_ok());
+
+    // Raised again: the recipient can add pictures, but still not edit the tags
+    SharedGroup::update_permissions(conn, recipient_id, group.id, SharePermissions::VIEW | SharePermissions::ADD_PICTURES).unwrap();
+    assert!(SharedGroup::require_permission(conn, recipient_id, group.id, SharePermissions::ADD_PICTURES).is_ok());
+    assert!(SharedGroup::require_permission(conn, recipient_id, group.id, SharePermissions::EDIT_TAGS).is_err());
+
+    // A share that doesn't exist is not created
+    let error = ErrorResponse::from(SharedGroup::update_permissions(conn, owner_id, group.id, SharePermissions::VIEW).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::NotFound));
+}
@@ -0,0 +1,155 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::group_picture::GroupPicture;
+use crate::database::group::sharing_consistency::{SharingAnomaly, SharingConsistencyReport};
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_share, insert_tags, insert_user, test_connection};
+use crate::grouping::grouping_delta::{GroupingDelta, PictureGroupsEvent};
+use crate::grouping::grouping_process::group_pictures;
+use crate::grouping::strategy_filtering::FilterType;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use std::collections::HashSet;
+
+fn group_picture(group_id: i32, picture_id: i64) -> GroupPicture {
+    GroupPicture { group_id, picture_id }
+}
+
+#[test]
+pub fn test_consistent_sharing_has_no_anomaly() {
+    // Pictures 1 and 2 are shared with user 2, and already grouped in its group 20: the grouping adds them nowhere
+    let grouped = vec![group_picture(20, 1), group_picture(20, 2)];
+    let accessible = HashSet::from([1, 2]);
+    assert!(SharingConsistencyReport::find_anomalies(2, &[], &grouped, &accessible).is_empty());
+}
+
+#[test]
+pub fn test_stale_membership_is_flagged() {
+    // Picture 3 was removed from the shared group, but is still in the group 20 of the recipient
+    let grouped = vec![group_picture(20, 1), group_picture(20, 3)];
+    let accessible = HashSet::from([1]);
+    assert_eq!(
+        SharingConsistencyReport::find_anomalies(2, &[], &grouped, &accessible),
+        vec![SharingAnomaly::UnaccessiblePictureGrouped {
+            user_id: 2,
+            group_id: 20,
+            picture_id: 3
+        }]
+    );
+}
+
+#[test]
+pub fn test_ungrouped_shared_picture_is_flagged() {
+    // Picture 4 was added to a shared group and matches the group 21 of the recipient, but was never propagated to it
+    let missing = vec![group_picture(21, 4)];
+    let grouped = vec![group_picture(20, 1)];
+    let accessible = HashSet::from([1]);
+    let anomalies = SharingConsistencyReport::find_anomalies(2, &missing, &grouped, &accessible);
+    assert_eq!(
+        anomalies,
+        vec![SharingAnomaly::SharedPictureNotGrouped {
+            user_id: 2,
+            group_id: 21,
+            picture_id: 4
+        }]
+    );
+    let json = serde_json::to_value(&anomalies[0]).unwrap();
+    assert_eq!(json["type"], "SharedPictureNotGrouped");
+    assert_eq!(json["picture_id"], 4);
+}
+
+#[test]
+pub fn test_missing_memberships_are_the_ones_of_the_shared_pictures_in_the_recipient_groups() {
+    let events = vec![
+        PictureGroupsEvent {
+            picture_id: 4,
+            added_groups: vec![21, 30],
+            removed_groups: vec![],
+        },
+        // Picture 5 is not shared with the recipient, group 30 is a group of another user the grouping propagates to
+        PictureGroupsEvent {
+            picture_id: 5,
+            added_groups: vec![21],
+            removed_groups: vec![],
+        },
+        PictureGroupsEvent {
+            picture_id: 6,
+            added_groups: vec![],
+            removed_groups: vec![20],
+        },
+    ];
+    assert_eq!(
+        SharingConsistencyReport::missing_memberships(&events, &[4, 6], &[20, 21]),
+        vec![group_picture(21, 4)]
+    );
+}
+
+#[test]
+pub fn test_shared_pictures_are_the_ones_of_accepted_shares() {
+    let sql = debug_query::<Pg, _>(&SharingConsistencyReport::shared_pictures_statement(2)).to_string();
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $1"));
+    // Pending shares, groups being deleted and pictures in the trash are not expected in the groups of the recipient
+    assert!(sql.contains("\"shared_groups\".\"confirmed\" = $2"));
+    assert!(sql.contains("\"groups\".\"to_be_deleted\" = $3"));
+    assert!(sql.contains("\"pictures\".\"deleted_date\" IS NULL"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_scan_reports_only_the_shared_pictures_missing_from_the_groups_they_match() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "consistency_owner");
+    let recipient_id = insert_user(conn, "consistency_recipient");
+    let tag_ids = insert_tags(conn, recipient_id, 1);
+    let matching_id = insert_picture(conn, owner_id, &tag_ids);
+    let not_matching_id = insert_picture(conn, owner_id, &[]);
+    let unshared_id = insert_picture(conn, owner_id, &tag_ids);
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let shared_group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, shared_group.id, &vec![matching_id, not_matching_id, unshared_id]).unwrap();
+    insert_share(conn, recipient_id, shared_group.id, true);
+    let group_id = insert_filter_arrangement(
+        conn,
+        recipient_id,
+        "Tagged".to_string(),
+        FilterType::IncludeTags(tag_ids.clone()).to_strategy(),
+    );
+    group_pictures(conn, &mut GroupingDelta::new(), recipient_id, None, None, None, true).unwrap();
+
+    // The picture that doesn't match the filter of the recipient is in none of its groups, as expected
+    let recipient_anomalies = |report: SharingConsistencyReport| {
+        report
+            .anomalies
+            .into_iter()
+            .filter(|anomaly| match anomaly {
+                SharingAnomaly::SharedPictureNotGrouped { user_id, .. } | SharingAnomaly::UnaccessiblePictureGrouped { user_id, .. } => {
+                    *user_id == recipient_id
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    assert!(recipient_anomalies(SharingConsistencyReport::scan(conn).unwrap()).is_empty());
+
+    // A picture is unshared and another one shared without propagating the changes to the recipient
+    Group::remove_pictures(conn, shared_group.id, &vec![unshared_id]).unwrap();
+    let added_id = insert_picture(conn, owner_id, &tag_ids);
+    Group::add_pictures(conn, shared_group.id, &vec![added_id]).unwrap();
+    assert_eq!(
+        recipient_anomalies(SharingConsistencyReport::scan(conn).unwrap()),
+        vec![
+            SharingAnomaly::SharedPictureNotGrouped {
+                user_id: recipient_id,
+                group_id,
+                picture_id: added_id
+            },
+            SharingAnomaly::UnaccessiblePictureGrouped {
+                user_id: recipient_id,
+                group_id,
+                picture_id: unshared_id
+            },
+        ]
+    );
+    // The scan is read-only
+    let mut grouped_picture_ids = Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap();
+    grouped_picture_ids.sort();
+    assert_eq!(grouped_picture_ids, vec![matching_id, unshared_id]);
+}
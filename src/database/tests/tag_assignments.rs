@@ -0,0 +1,113 @@
+use crate::api::tags::clear_tag;
+use crate::database::group::group::Group;
+use crate::database::picture::picture_tag::PictureTag;
+use crate::database::tag::tag::Tag;
+use crate::database::tag::tag_group::TagGroup;
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_user, tag, test_connection};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::group_pictures;
+use crate::grouping::strategy_filtering::FilterType;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+
+fn tag_group(required: bool) -> TagGroup {
+    TagGroup {
+        id: Some(1),
+        user_id: 1,
+        name: "Group".to_string(),
+        multiple: false,
+        required,
+    }
+}
+
+#[test]
+pub fn test_check_assignments_removable() {
+    let default_tag = Tag {
+        is_default: true,
+        ..tag(1, 1)
+    };
+    assert!(tag(1, 1).check_assignments_removable(&tag_group(false)).is_ok());
+    assert!(default_tag.check_assignments_removable(&tag_group(false)).is_ok());
+    // Pictures left without tag of a required group fall back to the default tag
+    assert!(tag(1, 1).check_assignments_removable(&tag_group(true)).is_ok());
+}
+
+#[test]
+pub fn test_required_group_default_tag_is_not_removable() {
+    let default_tag = Tag {
+        is_default: true,
+        ..tag(1, 1)
+    };
+    let error = ErrorResponse::from(default_tag.check_assignments_removable(&tag_group(true)).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+    assert!(!error.rollback);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_cleared_tag_falls_back_to_the_default_tag_and_pictures_are_regrouped() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "clear_tag");
+    let other_user_id = insert_user(conn, "clear_tag_other");
+    let tag_group_id = TagGroup::insert(conn, TagGroup { user_id, ..tag_group(true) }).unwrap().id.unwrap();
+    let default_tag_id = Tag::insert(
+        conn,
+        Tag {
+            tag_group_id,
+            is_default: true,
+            ..tag(1, 1)
+        },
+    )
+    .unwrap()
+    .id;
+    let tag_id = Tag::insert(
+        conn,
+        Tag {
+            tag_group_id,
+            name: "Retired".to_string(),
+            ..tag(2, 1)
+        },
+    )
+    .unwrap()
+    .id;
+    let mut tagged_picture_ids = vec![insert_picture(conn, user_id, &[tag_id]), insert_picture(conn, user_id, &[tag_id])];
+    tagged_picture_ids.sort();
+    let default_picture_id = insert_picture(conn, user_id, &[default_tag_id]);
+    let retired_group_id = insert_filter_arrangement(conn, user_id, "Retired".to_string(), FilterType::IncludeTags(vec![tag_id]).to_strategy());
+    let default_group_id = insert_filter_arrangement(
+        conn,
+        user_id,
+        "Default".to_string(),
+        FilterType::IncludeTags(vec![default_tag_id]).to_strategy(),
+    );
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    assert_eq!(
+        Group::pictures_from_group_ids(conn, &vec![default_group_id]).unwrap(),
+        vec![default_picture_id]
+    );
+
+    // Only the owner of the tag can clear it, and not the default tag of the required group
+    let error = clear_tag(conn, other_user_id, tag_id).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::Unauthorized));
+    let error = clear_tag(conn, user_id, default_tag_id).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnprocessableEntity));
+    assert_eq!(
+        PictureTag::filter_pictures_from_tag(conn, default_tag_id, &vec![default_picture_id]).unwrap(),
+        vec![default_picture_id]
+    );
+
+    // The pictures lose the tag, get the default tag back, and move from one group to the other
+    assert_eq!(clear_tag(conn, user_id, tag_id).unwrap(), 2);
+    assert!(PictureTag::filter_pictures_from_tag(conn, tag_id, &tagged_picture_ids)
+        .unwrap()
+        .is_empty());
+    let mut defaulted_picture_ids = PictureTag::filter_pictures_from_tag(conn, default_tag_id, &tagged_picture_ids).unwrap();
+    defaulted_picture_ids.sort();
+    assert_eq!(defaulted_picture_ids, tagged_picture_ids);
+    assert!(Group::pictures_from_group_ids(conn, &vec![retired_group_id]).unwrap().is_empty());
+    let mut default_group_picture_ids = Group::pictures_from_group_ids(conn, &vec![default_group_id]).unwrap();
+    default_group_picture_ids.sort();
+    assert_eq!(default_group_picture_ids, [tagged_picture_ids, vec![default_picture_id]].concat());
+
+    // Nothing left to clear
+    assert_eq!(clear_tag(conn, user_id, tag_id).unwrap(), 0);
+}
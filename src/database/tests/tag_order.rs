@@ -0,0 +1,51 @@
+use crate::database::tag::tag::Tag;
+use crate::database::tests::test_database::{insert_tags, insert_user, tag, test_connection};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+
+#[test]
+pub fn test_apply_order() {
+    let tags = vec![tag(1, 1), tag(2, 1), tag(3, 1)];
+
+    let ordered = Tag::apply_order(tags, &[3, 1, 2]).unwrap();
+    assert_eq!(ordered.iter().map(|t| t.id).collect::<Vec<i32>>(), vec![3, 1, 2]);
+    assert_eq!(ordered.iter().map(|t| t.position).collect::<Vec<i32>>(), vec![0, 1, 2]);
+}
+
+#[test]
+pub fn test_apply_order_invalid_ids() {
+    let tags = vec![tag(1, 1), tag(2, 1), tag(3, 1)];
+
+    // Unknown id
+    let error = ErrorResponse::from(Tag::apply_order(tags.clone(), &[1, 2, 4]).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    // Missing id
+    let error = ErrorResponse::from(Tag::apply_order(tags.clone(), &[1, 2]).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    // Duplicated id
+    let error = ErrorResponse::from(Tag::apply_order(tags.clone(), &[1, 2, 2]).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    // Extra id
+    let error = ErrorResponse::from(Tag::apply_order(tags, &[1, 2, 3, 4]).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_patch_returns_the_stored_tag() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "tag_patch");
+    let tag_ids = insert_tags(conn, user_id, 2);
+    let tag_group_id = Tag::from_id(conn, tag_ids[0]).unwrap().tag_group_id;
+    Tag::reorder(conn, tag_group_id, &[tag_ids[1], tag_ids[0]]).unwrap();
+
+    // The edition doesn't carry the position of the tag, the stored one is returned
+    let mut edited = tag(tag_ids[0], tag_group_id);
+    edited.name = "Renamed".to_string();
+    edited.is_default = true;
+    let patched = Tag::patch(conn, edited).unwrap();
+    assert_eq!(patched, Tag::from_id(conn, tag_ids[0]).unwrap());
+    assert_eq!((patched.name.as_str(), patched.is_default, patched.position), ("Renamed", true, 1));
+
+    let error = ErrorResponse::from(Tag::patch(conn, tag(-1, 1)).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::TagNotFound));
+}
@@ -0,0 +1,255 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::shared_group::{SharePermissions, SharedGroup};
+use crate::database::migrations::MIGRATIONS;
+use crate::database::picture::picture::Picture;
+use crate::database::schema::*;
+use crate::database::tag::tag::Tag;
+use crate::database::user::user::User;
+use crate::grouping::arrangement_strategy::ArrangementStrategy;
+use crate::grouping::group_by_filter::FilterGrouping;
+use crate::grouping::strategy_filtering::StrategyFiltering;
+use crate::grouping::strategy_grouping::StrategyGrouping;
+use chrono::NaiveDateTime;
+use diesel::connection::InstrumentationEvent;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel_migrations::MigrationHarness;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Once};
+
+static MIGRATE: Once = Once::new();
+
+/// Connection to the database of `TEST_DATABASE_URL`, migrated, in a transaction that is never committed.
+/// The tests using it are ignored by default, run them with `cargo test -- --ignored`.
+pub fn test_connection() -> DBConn {
+    let url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(ConnectionManager::<PgConnection>::new(url))
+        .expect("Could not build connection pool to the test database");
+    let mut conn = pool.get().unwrap();
+    MIGRATE.call_once(|| {
+        conn.run_pending_migrations(MIGRATIONS).expect("Failed to migrate the test database");
+    });
+    conn.begin_test_transaction().unwrap();
+    conn
+}
+
+/// Count the queries run on the connection whose SQL starts with `prefix`.
+pub fn count_queries(conn: &mut DBConn, prefix: &'static str) -> Arc<AtomicUsize> {
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = count.clone();
+    conn.set_instrumentation(move |event: InstrumentationEvent<'_>| {
+        if let InstrumentationEvent::StartQuery { query, .. } = event {
+            if query.to_string().starts_with(prefix) {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+    count
+}
+
+pub fn insert_user(conn: &mut DBConn, name: &str) -> i32 {
+    diesel::insert_into(users::table)
+        .values((
+            users::name.eq(name),
+            users::email.eq(format!("{}@archypix.test", name)),
+            users::password_hash.eq("x".repeat(60)),
+        ))
+        .returning(users::id)
+        .get_result(conn)
+        .unwrap()
+}
+
+/// Insert a tag group of `count` tags, returning the ids of the tags.
+pub fn insert_tags(conn: &mut DBConn, user_id: i32, count: usize) -> Vec<i32> {
+    let tag_group_id: i32 = diesel::insert_into(tag_groups::table)
+        .values((
+            tag_groups::user_id.eq(user_id),
+            tag_groups::name.eq("Tags"),
+            tag_groups::multiple.eq(true),
+            tag_groups::required.eq(false),
+        ))
+        .returning(tag_groups::id)
+        .get_result(conn)
+        .unwrap();
+    (0..count)
+        .map(|i| {
+            diesel::insert_into(tags::table)
+                .values((
+                    tags::tag_group_id.eq(tag_group_id),
+                    tags::name.eq(format!("Tag {}", i)),
+                    tags::color.eq(vec![0u8, 0, 0]),
+                    tags::is_default.eq(false),
+                ))
+                .returning(tags::id)
+                .get_result(conn)
+                .unwrap()
+        })
+        .collect()
+}
+
+/// Insert a picture owned by the user, with the tags.
+pub fn insert_picture(conn: &mut DBConn, user_id: i32, tag_ids: &[i32]) -> i64 {
+    let picture_id = insert_picture_created_at(conn, user_id, NaiveDateTime::default());
+    let values = tag_ids
+        .iter()
+        .map(|tag_id| (pictures_tags::picture_id.eq(picture_id), pictures_tags::tag_id.eq(*tag_id)))
+        .collect::<Vec<_>>();
+    diesel::insert_into(pictures_tags::table).values(values).execute(conn).unwrap();
+    picture_id
+}
+
+/// Insert a picture owned by the user, without tags.
+pub fn insert_picture_created_at(conn: &mut DBConn, user_id: i32, creation_date: NaiveDateTime) -> i64 {
+    diesel::insert_into(pictures::table)
+        .values((
+            pictures::name.eq("Picture"),
+            pictures::comment.eq(""),
+            pictures::owner_id.eq(user_id),
+            pictures::author_id.eq(user_id),
+            pictures::copied.eq(false),
+            pictures::creation_date.eq(creation_date),
+            pictures::edition_date.eq(NaiveDateTime::default()),
+            pictures::width.eq(1),
+            pictures::height.eq(1),
+            pictures::size_ko.eq(1),
+        ))
+        .returning(pictures::id)
+        .get_result(conn)
+        .unwrap()
+}
+
+/// Arrangement grouping the pictures of the filter in a single group, returning the id of the group.
+pub fn insert_filter_arrangement(conn: &mut DBConn, user_id: i32, name: String, filter: StrategyFiltering) -> i32 {
+    let mut arrangement = Arrangement::new(conn, user_id, name.clone(), false, None).unwrap();
+    let group = Group::insert(conn, arrangement.id, name, false, None).unwrap();
+    let strategy = ArrangementStrategy {
+        filter: filter.clone(),
+        groupings: StrategyGrouping::GroupByFilter(FilterGrouping {
+            filters: vec![(group.id, filter)],
+            other_group_id: None,
+        }),
+        preserve_unicity: false,
+    };
+    arrangement.set_strategy(conn, Some(strategy)).unwrap();
+    group.id
+}
+
+/// Share the group with the user, with all the permissions, confirmed or pending.
+pub fn insert_share(conn: &mut DBConn, user_id: i32, group_id: i32, confirmed: bool) {
+    diesel::insert_into(shared_groups::table)
+        .values((
+            shared_groups::user_id.eq(user_id),
+            shared_groups::group_id.eq(group_id),
+            shared_groups::permissions.eq(SharePermissions::ALL.bits()),
+            shared_groups::copied.eq(false),
+            shared_groups::confirmed.eq(confirmed),
+        ))
+        .execute(conn)
+        .unwrap();
+}
+
+/// Picture of user 1 that is not in the database, for the tests of statements and of the data built from pictures.
+pub fn picture(id: i64) -> Picture {
+    Picture {
+        id,
+        name: format!("Picture {}", id),
+        comment: String::new(),
+        owner_id: 1,
+        author_id: 1,
+        deleted_date: None,
+        copied: false,
+        creation_date: NaiveDateTime::default(),
+        edition_date: NaiveDateTime::default(),
+        latitude: None,
+        longitude: None,
+        altitude: None,
+        orientation: PictureOrientation::Unspecified,
+        width: 0,
+        height: 0,
+        camera_brand: None,
+        camera_model: None,
+        focal_length: None,
+        exposure_time_num: None,
+        exposure_time_den: None,
+        iso_speed: None,
+        f_number: None,
+        size_ko: 1,
+        blurhash: None,
+        format: None,
+        favorite: false,
+    }
+}
+
+/// Arrangement of user 1 without strategy, that is not in the database.
+pub fn arrangement(id: i32) -> Arrangement {
+    Arrangement {
+        id,
+        user_id: 1,
+        name: format!("Arrangement {}", id),
+        strong_match_conversion: false,
+        strategy: None,
+        groups_dependant: false,
+        tags_dependant: false,
+        exif_dependant: false,
+        ratings_dependant: false,
+        edition_version: 0,
+        enabled: true,
+    }
+}
+
+/// Group of the arrangement that is not in the database.
+pub fn group(id: i32, arrangement_id: i32) -> Group {
+    Group {
+        id,
+        arrangement_id,
+        share_match_conversion: false,
+        name: format!("Group {}", id),
+        to_be_deleted: false,
+        color: None,
+    }
+}
+
+/// Tag of the tag group that is not in the database.
+pub fn tag(id: i32, tag_group_id: i32) -> Tag {
+    Tag {
+        id,
+        tag_group_id,
+        name: format!("Tag {}", id),
+        color: vec![0, 0, 0],
+        is_default: false,
+        position: 0,
+    }
+}
+
+/// Confirmed share of the group with user 2, that is not in the database.
+pub fn shared_group(group_id: i32, permissions: SharePermissions) -> SharedGroup {
+    SharedGroup {
+        user_id: 2,
+        group_id,
+        permissions: permissions.bits(),
+        match_conversion_group_id: None,
+        copied: false,
+        confirmed: true,
+    }
+}
+
+/// User that is not in the database.
+pub fn user(id: i32) -> User {
+    User {
+        id,
+        name: format!("User {}", id),
+        email: format!("user{}@archypix.test", id),
+        password_hash: "x".repeat(60),
+        creation_date: NaiveDateTime::default(),
+        status: UserStatus::Normal,
+        tfa_login: false,
+        storage_count_ko: 0,
+        storage_limit_ko: 0,
+        email_tfa: false,
+    }
+}
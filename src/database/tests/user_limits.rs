@@ -0,0 +1,98 @@
+use crate::api::groups::arrangement::insert_arrangement;
+use crate::api::tags::insert_tag_group_with_tags;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::schema::*;
+use crate::database::tag::tag_group::{TagGroup, TagGroupWithTags};
+use crate::database::tests::test_database::{insert_user, test_connection};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::utils::config::{Config, CONFIG};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use diesel::prelude::*;
+
+#[test]
+pub fn test_arrangement_creation_is_refused_at_the_limit() {
+    // Just under the limit: the last arrangement can still be created
+    assert!(Arrangement::check_user_limit(9, 10).is_ok());
+
+    let error = ErrorResponse::from(Arrangement::check_user_limit(10, 10).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+    assert!(Arrangement::check_user_limit(12, 10).is_err());
+}
+
+#[test]
+pub fn test_tag_group_creation_is_refused_at_the_limit() {
+    assert!(TagGroup::check_user_limit(0, 1).is_ok());
+    assert!(TagGroup::check_user_limit(4, 5).is_ok());
+
+    let error = ErrorResponse::from(TagGroup::check_user_limit(5, 5).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+}
+
+#[test]
+pub fn test_user_limits_must_be_positive() {
+    let config = Config::default();
+    assert!(config.max_arrangements_per_user > 0 && config.max_tag_groups_per_user > 0);
+    assert!(config.validate().is_ok());
+
+    let config = Config {
+        max_tag_groups_per_user: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_arrangement_insert_is_refused_at_the_limit() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "arrangements_limit");
+    let arrangements = (0..CONFIG.max_arrangements_per_user)
+        .map(|i| {
+            (
+                arrangements::user_id.eq(user_id),
+                arrangements::name.eq(format!("Arrangement {}", i)),
+                arrangements::strong_match_conversion.eq(false),
+                arrangements::groups_dependant.eq(false),
+                arrangements::tags_dependant.eq(false),
+                arrangements::exif_dependant.eq(false),
+            )
+        })
+        .collect::<Vec<_>>();
+    diesel::insert_into(arrangements::table).values(&arrangements).execute(conn).unwrap();
+
+    let error = insert_arrangement(conn, &mut GroupingDelta::new(), user_id, "One too many".to_string(), false, None).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnprocessableEntity));
+    assert_eq!(Arrangement::count_for_user(conn, user_id).unwrap(), CONFIG.max_arrangements_per_user);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_tag_group_insert_is_refused_at_the_limit() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "tag_groups_limit");
+    let tag_groups = (0..CONFIG.max_tag_groups_per_user)
+        .map(|i| {
+            (
+                tag_groups::user_id.eq(user_id),
+                tag_groups::name.eq(format!("Tag group {}", i)),
+                tag_groups::multiple.eq(true),
+                tag_groups::required.eq(false),
+            )
+        })
+        .collect::<Vec<_>>();
+    diesel::insert_into(tag_groups::table).values(&tag_groups).execute(conn).unwrap();
+
+    let tag_group_with_tags = TagGroupWithTags {
+        tag_group: TagGroup {
+            id: None,
+            user_id,
+            name: "One too many".to_string(),
+            multiple: true,
+            required: false,
+        },
+        tags: vec![],
+    };
+    let error = insert_tag_group_with_tags(conn, user_id, tag_group_with_tags).unwrap_err();
+    assert!(matches!(ErrorResponse::from(error).error_type, ErrorTypeKind::UnprocessableEntity));
+    assert_eq!(TagGroup::count_for_user(conn, user_id).unwrap(), CONFIG.max_tag_groups_per_user);
+}
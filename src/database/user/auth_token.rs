@@ -6,8 +6,8 @@ use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use crate::utils::utils::random_token;
 use chrono::{Local, NaiveDateTime, TimeDelta, Utc};
 use diesel::delete;
-use diesel::ExpressionMethods;
 use diesel::{insert_into, update, Identifiable, Insertable, Queryable, RunQueryDsl, Selectable};
+use diesel::{ExpressionMethods, QueryDsl};
 use ipnet::IpNet;
 use rocket::Request;
 
@@ -50,6 +50,21 @@ impl AuthToken {
                 ErrorType::DatabaseError("Failed to insert auth token".to_string(), e).res_err()
             })
     }
+    /// Returns true if the user already signed in from this device, i.e. has an auth token with the same device string and IP address.
+    pub fn has_known_device(conn: &mut DBConn, user_id: &i32, device_info: &DeviceInfo) -> Result<bool, ErrorResponder> {
+        let devices: Vec<(Option<String>, Option<IpNet>)> = auth_tokens::table
+            .filter(auth_tokens::dsl::user_id.eq(user_id))
+            .select((auth_tokens::dsl::device_string, auth_tokens::dsl::ip_address))
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get auth tokens devices".to_string(), e).res())?;
+        Ok(Self::is_known_device(&devices, device_info))
+    }
+    /// Returns true if one of the `(device_string, ip_address)` devices is the device of `device_info`.
+    pub fn is_known_device(devices: &[(Option<String>, Option<IpNet>)], device_info: &DeviceInfo) -> bool {
+        devices.iter().any(|(device_string, ip_address)| {
+            device_string.as_deref() == Some(device_info.device_string.as_str()) && *ip_address == device_info.ip_address
+        })
+    }
     pub fn update_last_use_date(&self, conn: &mut DBConn) -> Result<(), ErrorResponder> {
         // Working in UTC time.
         let current_naive = Utc::now().naive_utc();
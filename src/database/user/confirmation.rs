@@ -2,6 +2,7 @@ use crate::database::database::DBConn;
 use crate::database::schema::*;
 use crate::database::utils::is_error_duplicate_key;
 use crate::utils::auth::DeviceInfo;
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use crate::utils::utils::{random_code, random_token};
 use chrono::{Duration, NaiveDateTime, Utc};
@@ -39,7 +40,7 @@ impl Confirmation {
     ) -> Result<(Vec<u8>, Vec<u8>, i16), ErrorResponder> {
         let token = random_token(16);
         let code_token = random_token(16);
-        let code = random_code(4) as i16;
+        let code = random_code(CONFIG.confirmation_code_digits) as i16;
 
         insert_into(confirmations::table)
             .values((
@@ -83,9 +84,7 @@ impl Confirmation {
             if confirmation.used {
                 return ErrorType::ConfirmationAlreadyUsed.res_err_no_rollback();
             }
-            if confirmation.date < Utc::now().naive_utc() - Duration::minutes(max_minutes) {
-                return ErrorType::ConfirmationExpired.res_err_no_rollback();
-            }
+            confirmation.check_not_expired(Utc::now().naive_utc(), max_minutes)?;
             if confirmation.code_trials >= 3 {
                 return ErrorType::ConfirmationTooManyAttempts.res_err_no_rollback();
             }
@@ -124,14 +123,19 @@ impl Confirmation {
             if confirmation.used {
                 return ErrorType::ConfirmationAlreadyUsed.res_err_no_rollback();
             }
-            if confirmation.date < Utc::now().naive_utc() - Duration::minutes(max_minutes) {
-                return ErrorType::ConfirmationExpired.res_err_no_rollback();
-            }
+            confirmation.check_not_expired(Utc::now().naive_utc(), max_minutes)?;
             confirmation.mark_as_used(conn)?;
             return Ok(confirmation.redirect_url);
         }
         ErrorType::ConfirmationNotFound.res_err_no_rollback()
     }
+    /// Throw `ConfirmationExpired` if the confirmation was created more than `max_minutes` before `now`.
+    pub fn check_not_expired(&self, now: NaiveDateTime, max_minutes: i64) -> Result<(), ErrorResponder> {
+        if self.date < now - Duration::minutes(max_minutes) {
+            return ErrorType::ConfirmationExpired.res_err_no_rollback();
+        }
+        Ok(())
+    }
     pub fn mark_as_used(&self, conn: &mut DBConn) -> Result<(), ErrorResponder> {
         update(confirmations::table)
             .filter(confirmations::dsl::user_id.eq(&self.user_id))
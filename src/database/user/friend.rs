@@ -1,6 +1,12 @@
+use crate::database::database::DBConn;
 use crate::database::schema::*;
 use crate::database::user::user::User;
-use diesel::{Associations, Identifiable, Queryable, Selectable};
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::dsl::exists;
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use diesel::{Associations, BoolExpressionMethods, ExpressionMethods, Identifiable, QueryDsl, Queryable, RunQueryDsl, Selectable};
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
 #[diesel(primary_key(user_id_1, user_id_2))]
@@ -10,3 +16,28 @@ pub struct Friends {
     pub user_id_1: i32,
     pub user_id_2: i32,
 }
+
+impl Friends {
+    /// Counts the friends of the user (friendships are stored once, in any order)
+    pub fn count_for_user(conn: &mut DBConn, user_id: i32) -> Result<i64, ErrorResponder> {
+        friends::table
+            .filter(friends::user_id_1.eq(user_id).or(friends::user_id_2.eq(user_id)))
+            .count()
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to count friends".to_string(), e).res())
+    }
+    /// Whether the two users are friends, whatever the order the friendship is stored in
+    pub fn are_friends(conn: &mut DBConn, user_id: i32, other_user_id: i32) -> Result<bool, ErrorResponder> {
+        Friends::are_friends_statement(user_id, other_user_id)
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to check friendship".to_string(), e).res())
+    }
+    pub fn are_friends_statement(user_id: i32, other_user_id: i32) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, bool> {
+        diesel::select(exists(
+            friends::table.filter(
+                (friends::user_id_1.eq(user_id).and(friends::user_id_2.eq(other_user_id)))
+                    .or(friends::user_id_1.eq(other_user_id).and(friends::user_id_2.eq(user_id))),
+            ),
+        ))
+    }
+}
@@ -41,6 +41,12 @@ impl TOTPSecret {
             .load::<TOTPSecret>(conn)
             .map_err(|e| ErrorType::DatabaseError("Failed to get user TOTP secrets".to_string(), e).res())
     }
+    /// Delete all the TOTP secrets of the user, returning the number of deleted secrets.
+    pub fn delete_for_user(conn: &mut DBConn, user_id: &i32) -> Result<usize, ErrorResponder> {
+        diesel::delete(totp_secrets::table.filter(totp_secrets::dsl::user_id.eq(user_id)))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to delete user TOTP secrets".to_string(), e).res())
+    }
     pub fn check_user_totp(conn: &mut DBConn, user_id: &i32, code: &str) -> Result<bool, ErrorResponder> {
         let secrets = TOTPSecret::get_user_totp_secrets(conn, user_id)?;
         for secret in secrets {
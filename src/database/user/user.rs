@@ -1,13 +1,22 @@
 use crate::database::database::DBConn;
+use crate::database::integrity_scan::IntegrityReport;
 use crate::database::schema::*;
-use crate::database::user::{auth_token::AuthToken, confirmation::Confirmation};
-use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use crate::database::user::{auth_token::AuthToken, confirmation::Confirmation, totp_secret::TOTPSecret};
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
+use crate::utils::utils::like_contains_pattern;
 use chrono::NaiveDateTime;
-use diesel::QueryDsl;
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::methods::ExecuteDsl;
 use diesel::{insert_into, update, Identifiable, Insertable, OptionalExtension, Queryable, RunQueryDsl, Selectable};
 use diesel::{ExpressionMethods, SelectableHelper};
+use diesel::{PgTextExpressionMethods, QueryDsl};
 use pwhash::bcrypt;
 use rocket::Request;
+use rocket_okapi::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
 
 #[derive(Queryable, Selectable, Identifiable, Insertable, Debug, PartialEq)]
 #[diesel(primary_key(id))]
@@ -22,6 +31,26 @@ pub struct User {
     pub tfa_login: bool,
     pub storage_count_ko: i64,
     pub storage_limit_ko: i64,
+    /// Sign in requires a code sent by email when the user has no TOTP, even if `tfa_login` is disabled
+    pub email_tfa: bool,
+}
+
+/// Correction of the storage count of a user, see [`User::recompute_all_storage`].
+#[derive(JsonSchema, Serialize, Debug, PartialEq)]
+pub struct StorageCorrection {
+    pub user_id: i32,
+    pub previous_ko: i64,
+    pub corrected_ko: i64,
+    /// `corrected_ko - previous_ko`
+    pub delta_ko: i64,
+}
+
+/// Result of [`User::recompute_all_storage`].
+#[derive(JsonSchema, Serialize, Debug, PartialEq, Default)]
+pub struct StorageRecomputeReport {
+    pub scanned_users: usize,
+    pub adjusted_users: usize,
+    pub corrections: Vec<StorageCorrection>,
 }
 
 impl User {
@@ -89,6 +118,7 @@ impl User {
                 users::dsl::name.eq::<String>(name.to_string()),
                 users::dsl::email.eq(email.to_string()),
                 users::dsl::password_hash.eq(bcrypt::hash(password).unwrap()),
+                users::dsl::email_tfa.eq(CONFIG.email_tfa_default),
             ))
             .get_result::<User>(conn)
             .map_err(|e| ErrorType::DatabaseError("Failed to insert user".to_string(), e).res())
@@ -107,6 +137,169 @@ impl User {
         Ok(())
     }
 
+    pub fn update_name(conn: &mut DBConn, user_id: i32, name: &str) -> Result<User, ErrorResponder> {
+        update(users::table)
+            .filter(users::dsl::id.eq(user_id))
+            .set(users::dsl::name.eq(name))
+            .returning(User::as_returning())
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to update user name".to_string(), e).res())
+    }
+
+    /// Enable or disable the email confirmation of the sign in of the user when they have no TOTP, returning the updated user.
+    pub fn set_email_tfa(conn: &mut DBConn, user_id: i32, email_tfa: bool) -> Result<User, ErrorResponder> {
+        update(users::table)
+            .filter(users::dsl::id.eq(user_id))
+            .set(users::dsl::email_tfa.eq(email_tfa))
+            .returning(User::as_returning())
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to update user email 2FA".to_string(), e).res())
+    }
+
+    /// Set the storage limit of the user, returning the updated user.
+    /// The limit can't be set below the current storage usage of the user unless `force` is true (see [`User::check_storage_limit`]).
+    pub fn set_storage_limit(conn: &mut DBConn, user_id: i32, limit_ko: i64, force: bool) -> Result<User, ErrorResponder> {
+        User::from_id(conn, &user_id)?.check_storage_limit(limit_ko, force)?;
+        update(users::table)
+            .filter(users::dsl::id.eq(user_id))
+            .set(users::dsl::storage_limit_ko.eq(limit_ko))
+            .returning(User::as_returning())
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to update user storage limit".to_string(), e).res())
+    }
+    /// Check that the storage limit can be applied to the user:
+    /// it must not be negative, and not lower than the current storage usage unless `force` is true.
+    pub fn check_storage_limit(&self, limit_ko: i64, force: bool) -> Result<(), ErrorResponder> {
+        if limit_ko < 0 {
+            return ErrorType::InvalidInput("Storage limit can't be negative".to_string()).res_err_no_rollback();
+        }
+        if limit_ko < self.storage_count_ko && !force {
+            return ErrorType::UnprocessableEntity(format!(
+                "Storage limit ({} Ko) is below the current storage usage of the user ({} Ko), use force to apply it anyway",
+                limit_ko, self.storage_count_ko
+            ))
+            .res_err_no_rollback();
+        }
+        Ok(())
+    }
+
+    /// Reset the two-factor authentication of the user, for account recovery:
+    /// deletes all its TOTP secrets and disables 2FA at sign in. Returns the updated user and the number of deleted secrets.
+    pub fn reset_totp(conn: &mut DBConn, user_id: i32) -> Result<(User, usize), ErrorResponder> {
+        User::from_id(conn, &user_id)?;
+        let deleted_secrets = TOTPSecret::delete_for_user(conn, &user_id)?;
+        let user = update(users::table)
+            .filter(users::dsl::id.eq(user_id))
+            .set(users::dsl::tfa_login.eq(false))
+            .returning(User::as_returning())
+            .get_result(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to disable user 2FA".to_string(), e).res())?;
+        Ok((user, deleted_secrets))
+    }
+
+    /// Add `delta_ko` (can be negative) to the storage usage of the user.
+    pub fn add_storage_count(conn: &mut DBConn, user_id: i32, delta_ko: i64) -> Result<(), ErrorResponder> {
+        if delta_ko == 0 {
+            return Ok(());
+        }
+        update(users::table)
+            .filter(users::dsl::id.eq(user_id))
+            .set(users::dsl::storage_count_ko.eq(users::dsl::storage_count_ko + delta_ko))
+            .execute(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to update user storage usage".to_string(), e).res())?;
+        Ok(())
+    }
+
+    /// Recompute the storage count of all the users from the size of the pictures counted in their storage
+    /// (see [`IntegrityReport::pictures_sizes_ko`]), and correct the ones that drifted.
+    /// Users are processed by batches of `batch_size`, each in its own transaction, to avoid locking all the users at once.
+    pub fn recompute_all_storage(conn: &mut DBConn, batch_size: i64) -> Result<StorageRecomputeReport, ErrorResponder> {
+        let mut report = StorageRecomputeReport::default();
+        let mut last_user_id = 0;
+        loop {
+            let (users, corrections) = err_transaction(conn, |conn| {
+                let users: Vec<(i32, i64)> = users::table
+                    .filter(users::dsl::id.gt(last_user_id))
+                    .order(users::dsl::id.asc())
+                    .limit(batch_size)
+                    .select((users::dsl::id, users::dsl::storage_count_ko))
+                    .for_update()
+                    .load(conn)
+                    .map_err(|e| ErrorType::DatabaseError("Failed to get users storage counts".to_string(), e).res())?;
+                let user_ids: Vec<i32> = users.iter().map(|(user_id, _)| *user_id).collect();
+                let corrections = Self::storage_corrections(&users, &IntegrityReport::pictures_sizes_ko(conn, &user_ids)?);
+                for correction in corrections.iter() {
+                    Self::set_storage_count_statement(correction.user_id, correction.corrected_ko)
+                        .execute(conn)
+                        .map_err(|e| ErrorType::DatabaseError("Failed to update user storage usage".to_string(), e).res())?;
+                }
+                Ok((users, corrections))
+            })?;
+            let Some((user_id, _)) = users.last() else {
+                break;
+            };
+            last_user_id = *user_id;
+            report.scanned_users += users.len();
+            report.adjusted_users += corrections.len();
+            report.corrections.extend(corrections);
+        }
+        Ok(report)
+    }
+    /// Corrections of the storage counts, from the `(user_id, storage_count_ko)` rows, that differ from the size of the pictures of the users.
+    pub fn storage_corrections(users: &[(i32, i64)], pictures_sizes_ko: &HashMap<i32, i64>) -> Vec<StorageCorrection> {
+        users
+            .iter()
+            .filter_map(|(user_id, storage_count_ko)| {
+                let corrected_ko = pictures_sizes_ko.get(user_id).cloned().unwrap_or(0);
+                (corrected_ko != *storage_count_ko).then_some(StorageCorrection {
+                    user_id: *user_id,
+                    previous_ko: *storage_count_ko,
+                    corrected_ko,
+                    delta_ko: corrected_ko - *storage_count_ko,
+                })
+            })
+            .collect()
+    }
+    pub fn set_storage_count_statement(user_id: i32, storage_count_ko: i64) -> impl QueryFragment<Pg> + ExecuteDsl<DBConn> + RunQueryDsl<DBConn> {
+        update(users::table)
+            .filter(users::dsl::id.eq(user_id))
+            .set(users::dsl::storage_count_ko.eq(storage_count_ko))
+    }
+
+    /// Search the users for the admin users list, optionally filtering by status and by a case-insensitive email fragment.
+    /// Returns the requested page of users sorted by id, and the total number of matching users.
+    pub fn admin_search(
+        conn: &mut DBConn,
+        status: Option<UserStatus>,
+        email_contains: Option<&str>,
+        page: i64,
+        page_size: i64,
+    ) -> Result<(Vec<User>, i64), ErrorResponder> {
+        let total = Self::admin_search_statement(status.clone(), email_contains)
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to count users".to_string(), e).res())?;
+        let users = Self::admin_search_statement(status, email_contains)
+            .order(users::dsl::id.asc())
+            .limit(page_size)
+            .offset((page - 1) * page_size)
+            .select(User::as_select())
+            .load::<User>(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to search users".to_string(), e).res())?;
+        Ok((users, total))
+    }
+    /// Build the boxed statement selecting the users matching the admin search filters.
+    pub fn admin_search_statement(status: Option<UserStatus>, email_contains: Option<&str>) -> users::BoxedQuery<'static, Pg> {
+        let mut query = users::table.into_boxed();
+        if let Some(status) = status {
+            query = query.filter(users::dsl::status.eq(status));
+        }
+        if let Some(email_contains) = email_contains {
+            query = query.filter(users::dsl::email.ilike(like_contains_pattern(email_contains)));
+        }
+        query
+    }
+
     pub fn get_id_from_headers(request: &Request<'_>) -> Option<i32> {
         request.headers().get_one("X-User-Id").map(|s| s.parse::<i32>().ok()).flatten()
     }
@@ -1,5 +1,6 @@
 use crate::database::database::DBConn;
 use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType};
+use crate::database::picture::picture::Picture;
 use crate::database::schema::PictureOrientation;
 use crate::grouping::strategy_filtering::StrategyFiltering;
 use crate::grouping::strategy_grouping::{StrategyGrouping, StrategyGroupingRequest};
@@ -8,6 +9,7 @@ use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Write};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ArrangementStrategy {
@@ -27,6 +29,24 @@ impl ArrangementStrategy {
         dependant_groups.extend(self.groupings.get_dependant_groups());
         dependant_groups
     }
+    /// Get the tags ids on which the strategy filters depend.
+    pub fn get_dependant_tags(&self) -> Vec<i32> {
+        let mut dependant_tags = self.filter.get_dependant_tags();
+        if let StrategyGrouping::GroupByFilter(filter_grouping) = &self.groupings {
+            for (_, filter) in filter_grouping.filters.iter() {
+                dependant_tags.extend(filter.get_dependant_tags());
+            }
+        }
+        dependant_tags
+    }
+    /// Whether the strategy groups by the tag group, or filters on one of its tags (`tag_ids`).
+    pub fn depends_on_tag_group(&self, tag_group_id: i32, tag_ids: &[i32]) -> bool {
+        if !self.is_tags_dependant() {
+            return false;
+        }
+        matches!(&self.groupings, StrategyGrouping::GroupByTags(tag_grouping) if tag_grouping.tag_group_id == tag_group_id)
+            || self.get_dependant_tags().iter().any(|tag_id| tag_ids.contains(tag_id))
+    }
     pub fn is_groups_dependant(&self) -> bool {
         self.filter.is_groups_dependant() || self.groupings.is_groups_dependant()
     }
@@ -36,12 +56,17 @@ impl ArrangementStrategy {
     pub fn is_exif_dependant(&self) -> bool {
         self.filter.is_exif_dependant() || self.groupings.is_exif_dependant()
     }
+    pub fn is_ratings_dependant(&self) -> bool {
+        // Filters can't depend on ratings
+        self.groupings.is_ratings_dependant()
+    }
 
     pub fn get_dependency_type(&self) -> ArrangementDependencyType {
         ArrangementDependencyType {
             groups_dependant: self.is_groups_dependant(),
             tags_dependant: self.is_tags_dependant(),
             exif_dependant: self.is_exif_dependant(),
+            ratings_dependant: self.is_ratings_dependant(),
         }
     }
 }
@@ -66,6 +91,105 @@ pub enum ExifDataTypeValue {
     FNumber(Vec<BigDecimal>),
 }
 
+impl ExifDataTypeValue {
+    /// Number of values
+    pub fn len(&self) -> usize {
+        match self {
+            ExifDataTypeValue::CreationDate(v) | ExifDataTypeValue::EditionDate(v) => v.len(),
+            ExifDataTypeValue::Latitude(v) | ExifDataTypeValue::Longitude(v) | ExifDataTypeValue::FocalLength(v) | ExifDataTypeValue::FNumber(v) => {
+                v.len()
+            }
+            ExifDataTypeValue::Altitude(v) | ExifDataTypeValue::Width(v) | ExifDataTypeValue::Height(v) => v.len(),
+            ExifDataTypeValue::Orientation(v) => v.len(),
+            ExifDataTypeValue::CameraBrand(v) | ExifDataTypeValue::CameraModel(v) => v.len(),
+            ExifDataTypeValue::ExposureTime(v) => v.len(),
+            ExifDataTypeValue::IsoSpeed(v) => v.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value of the picture for this data type, as a single value of the same variant.
+    /// Returns None if the picture has no value for this data type.
+    pub fn picture_value(&self, picture: &Picture) -> Option<ExifDataTypeValue> {
+        Some(match self {
+            ExifDataTypeValue::CreationDate(_) => ExifDataTypeValue::CreationDate(vec![picture.creation_date]),
+            ExifDataTypeValue::EditionDate(_) => ExifDataTypeValue::EditionDate(vec![picture.edition_date]),
+            ExifDataTypeValue::Latitude(_) => ExifDataTypeValue::Latitude(vec![picture.latitude.clone()?]),
+            ExifDataTypeValue::Longitude(_) => ExifDataTypeValue::Longitude(vec![picture.longitude.clone()?]),
+            ExifDataTypeValue::Altitude(_) => ExifDataTypeValue::Altitude(vec![picture.altitude?]),
+            ExifDataTypeValue::Orientation(_) => ExifDataTypeValue::Orientation(vec![picture.orientation.clone()]),
+            ExifDataTypeValue::Width(_) => ExifDataTypeValue::Width(vec![picture.width]),
+            ExifDataTypeValue::Height(_) => ExifDataTypeValue::Height(vec![picture.height]),
+            ExifDataTypeValue::CameraBrand(_) => ExifDataTypeValue::CameraBrand(vec![picture.camera_brand.clone()?]),
+            ExifDataTypeValue::CameraModel(_) => ExifDataTypeValue::CameraModel(vec![picture.camera_model.clone()?]),
+            ExifDataTypeValue::FocalLength(_) => ExifDataTypeValue::FocalLength(vec![picture.focal_length.clone()?]),
+            ExifDataTypeValue::ExposureTime(_) => ExifDataTypeValue::ExposureTime(vec![(picture.exposure_time_num?, picture.exposure_time_den?)]),
+            ExifDataTypeValue::IsoSpeed(_) => ExifDataTypeValue::IsoSpeed(vec![picture.iso_speed?]),
+            ExifDataTypeValue::FNumber(_) => ExifDataTypeValue::FNumber(vec![picture.f_number.clone()?]),
+        })
+    }
+
+    /// Appends the values of `other` if it is of the same variant. Returns false otherwise.
+    pub fn append(&mut self, other: ExifDataTypeValue) -> bool {
+        match (self, other) {
+            (ExifDataTypeValue::CreationDate(v), ExifDataTypeValue::CreationDate(o)) => v.extend(o),
+            (ExifDataTypeValue::EditionDate(v), ExifDataTypeValue::EditionDate(o)) => v.extend(o),
+            (ExifDataTypeValue::Latitude(v), ExifDataTypeValue::Latitude(o)) => v.extend(o),
+            (ExifDataTypeValue::Longitude(v), ExifDataTypeValue::Longitude(o)) => v.extend(o),
+            (ExifDataTypeValue::Altitude(v), ExifDataTypeValue::Altitude(o)) => v.extend(o),
+            (ExifDataTypeValue::Orientation(v), ExifDataTypeValue::Orientation(o)) => v.extend(o),
+            (ExifDataTypeValue::Width(v), ExifDataTypeValue::Width(o)) => v.extend(o),
+            (ExifDataTypeValue::Height(v), ExifDataTypeValue::Height(o)) => v.extend(o),
+            (ExifDataTypeValue::CameraBrand(v), ExifDataTypeValue::CameraBrand(o)) => v.extend(o),
+            (ExifDataTypeValue::CameraModel(v), ExifDataTypeValue::CameraModel(o)) => v.extend(o),
+            (ExifDataTypeValue::FocalLength(v), ExifDataTypeValue::FocalLength(o)) => v.extend(o),
+            (ExifDataTypeValue::ExposureTime(v), ExifDataTypeValue::ExposureTime(o)) => v.extend(o),
+            (ExifDataTypeValue::IsoSpeed(v), ExifDataTypeValue::IsoSpeed(o)) => v.extend(o),
+            (ExifDataTypeValue::FNumber(v), ExifDataTypeValue::FNumber(o)) => v.extend(o),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Formats the value at the given index.
+    /// Dates are formatted with the chrono `format` (e.g. "%Y-%m"), other values replace the "{}" placeholders of `format`.
+    pub fn format_value(&self, index: usize, format: &str) -> Option<String> {
+        Some(match self {
+            ExifDataTypeValue::CreationDate(v) | ExifDataTypeValue::EditionDate(v) => format_date(v.get(index)?, format),
+            ExifDataTypeValue::Latitude(v) | ExifDataTypeValue::Longitude(v) | ExifDataTypeValue::FocalLength(v) | ExifDataTypeValue::FNumber(v) => {
+                format_display(v.get(index)?.normalized(), format)
+            }
+            ExifDataTypeValue::Altitude(v) | ExifDataTypeValue::Width(v) | ExifDataTypeValue::Height(v) => format_display(v.get(index)?, format),
+            ExifDataTypeValue::Orientation(v) => format_display(format!("{:?}", v.get(index)?), format),
+            ExifDataTypeValue::CameraBrand(v) | ExifDataTypeValue::CameraModel(v) => format_display(v.get(index)?, format),
+            ExifDataTypeValue::ExposureTime(v) => {
+                let (num, den) = v.get(index)?;
+                format_display(format!("{}/{}", num, den), format)
+            }
+            ExifDataTypeValue::IsoSpeed(v) => format_display(v.get(index)?, format),
+        })
+    }
+}
+
+/// Formats a date with a chrono format string, falling back to the default representation if the format is empty or invalid.
+fn format_date(date: &NaiveDateTime, format: &str) -> String {
+    let mut formatted = String::new();
+    if format.is_empty() || write!(formatted, "{}", date.format(format)).is_err() {
+        return date.to_string();
+    }
+    formatted
+}
+/// Replaces the "{}" placeholders of the format with the value, or returns the value alone if there is no placeholder.
+fn format_display<T: Display>(value: T, format: &str) -> String {
+    if format.contains("{}") {
+        format.replace("{}", &value.to_string())
+    } else {
+        value.to_string()
+    }
+}
+
 // Requests
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
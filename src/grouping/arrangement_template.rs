@@ -0,0 +1,413 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tag::tag::Tag;
+use crate::database::tag::tag_group::{TagGroup, TagGroupWithTags};
+use crate::grouping::arrangement_strategy::{ArrangementStrategy, ArrangementStrategyRequest, ExifDataTypeValue};
+use crate::grouping::group_by_exif_value::ExifValuesGroupingRequest;
+use crate::grouping::group_by_filter::{FilterGroupingRequest, FilterGroupingValueRequest};
+use crate::grouping::group_by_rating::RatingGroupingRequest;
+use crate::grouping::group_by_tag::TagGroupingRequest;
+use crate::grouping::strategy_filtering::{FilterType, StrategyFiltering};
+use crate::grouping::strategy_grouping::{StrategyGrouping, StrategyGroupingRequest};
+use crate::utils::color::hex_color;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Tag referenced by its name and the name of its tag group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct TagReference {
+    pub tag_group: String,
+    pub tag: String,
+}
+/// Group referenced by its name and the name of its arrangement.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct GroupReference {
+    pub arrangement: String,
+    pub group: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateTag {
+    pub name: String,
+    /// RGB color, (de)serialized as a `#RRGGBB` hex string
+    #[serde(with = "hex_color")]
+    #[schemars(with = "String")]
+    pub color: Vec<u8>,
+    pub is_default: bool,
+}
+/// Tag group referenced by a template, with its tags sorted by position. It can be created when importing the template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateTagGroup {
+    pub name: String,
+    pub multiple: bool,
+    pub required: bool,
+    pub tags: Vec<TemplateTag>,
+}
+impl TemplateTagGroup {
+    /// Tag group and tags to insert for the user.
+    pub fn to_tag_group_with_tags(&self, user_id: i32) -> TagGroupWithTags {
+        TagGroupWithTags {
+            tag_group: TagGroup {
+                id: None,
+                user_id,
+                name: self.name.clone(),
+                multiple: self.multiple,
+                required: self.required,
+            },
+            tags: self
+                .tags
+                .iter()
+                .enumerate()
+                .map(|(position, tag)| Tag {
+                    id: 0,
+                    tag_group_id: 0,
+                    name: tag.name.clone(),
+                    color: tag.color.clone(),
+                    is_default: tag.is_default,
+                    position: position as i32,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// [`StrategyFiltering`] referencing tags and groups by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum TemplateFiltering {
+    Or(Vec<TemplateFiltering>),
+    And(Vec<TemplateFiltering>),
+    Not(Box<TemplateFiltering>),
+    Filter(TemplateFilterType),
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum TemplateFilterType {
+    IncludeTags(Vec<TagReference>),
+    IncludeGroups(Vec<GroupReference>),
+    ExifEqualTo(ExifDataTypeValue),
+    ExifInInterval(ExifDataTypeValue),
+}
+/// Group of a filter grouping, created with the arrangement.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateFilterGroup {
+    pub name: String,
+    pub filter: TemplateFiltering,
+}
+/// [`StrategyGrouping`] without the groups of the arrangement, that are created on import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum TemplateGrouping {
+    GroupByFilter(Vec<TemplateFilterGroup>),
+    GroupByTags { tag_group: String, group_names_format: String },
+    GroupByExifValues(ExifValuesGroupingRequest),
+    GroupByRating,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ArrangementStrategyTemplate {
+    pub filter: TemplateFiltering,
+    pub groupings: TemplateGrouping,
+    pub preserve_unicity: bool,
+}
+
+/// Portable arrangement, that can be recreated for another user: tags, tag groups and groups are referenced by name instead of id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ArrangementTemplate {
+    pub name: String,
+    pub strong_match_conversion: bool,
+    /// `None` for a manual arrangement
+    pub strategy: Option<ArrangementStrategyTemplate>,
+    /// Tag groups referenced by the strategy
+    pub tag_groups: Vec<TemplateTagGroup>,
+}
+
+/// Tag groups, tags and groups of a user, resolving the references of a template from ids to names and back.
+#[derive(Debug, Default, Clone)]
+pub struct TemplateReferences {
+    pub tag_groups: Vec<TagGroupWithTags>,
+    /// Groups (the ones marked as to be deleted excepted), with the name of their arrangement
+    pub groups: Vec<(String, Group)>,
+}
+
+impl TemplateReferences {
+    pub fn load(conn: &mut DBConn, user_id: i32) -> Result<Self, ErrorResponder> {
+        let tags = TagGroup::list_all_tags(conn, user_id)?;
+        let tag_groups = TagGroup::list_tag_groups(conn, user_id)?
+            .into_iter()
+            .map(|tag_group| TagGroupWithTags {
+                tags: tags
+                    .iter()
+                    .filter(|(group, _)| group.id == tag_group.id)
+                    .map(|(_, tag)| tag.clone())
+                    .collect(),
+                tag_group,
+            })
+            .collect();
+        let groups = Arrangement::from_user_id_with_groups(conn, user_id)?
+            .into_iter()
+            .flat_map(|(arrangement, groups)| {
+                groups
+                    .into_iter()
+                    .filter(|group| !group.to_be_deleted)
+                    .map(move |group| (arrangement.name.clone(), group))
+            })
+            .collect();
+        Ok(TemplateReferences { tag_groups, groups })
+    }
+
+    fn tag_group(&self, tag_group_id: i32) -> Result<&TagGroupWithTags, ErrorResponder> {
+        self.tag_groups
+            .iter()
+            .find(|tgwt| tgwt.tag_group.id == Some(tag_group_id))
+            .ok_or_else(|| ErrorType::UnprocessableEntity(format!("Tag group {} not found", tag_group_id)).res())
+    }
+    fn tag_group_id(&self, name: &str) -> Result<i32, ErrorResponder> {
+        self.tag_groups
+            .iter()
+            .find(|tgwt| tgwt.tag_group.name == name)
+            .and_then(|tgwt| tgwt.tag_group.id)
+            .ok_or_else(|| ErrorType::UnprocessableEntity(format!("Tag group \"{}\" not found", name)).res())
+    }
+    fn tag_reference(&self, tag_id: i32) -> Result<(i32, TagReference), ErrorResponder> {
+        self.tag_groups
+            .iter()
+            .find_map(|tgwt| {
+                let tag = tgwt.tags.iter().find(|tag| tag.id == tag_id)?;
+                Some((
+                    tag.tag_group_id,
+                    TagReference {
+                        tag_group: tgwt.tag_group.name.clone(),
+                        tag: tag.name.clone(),
+                    },
+                ))
+            })
+            .ok_or_else(|| ErrorType::UnprocessableEntity(format!("Tag {} not found", tag_id)).res())
+    }
+    fn tag_id(&self, reference: &TagReference) -> Result<i32, ErrorResponder> {
+        self.tag_groups
+            .iter()
+            .filter(|tgwt| tgwt.tag_group.name == reference.tag_group)
+            .flat_map(|tgwt| tgwt.tags.iter())
+            .find(|tag| tag.name == reference.tag)
+            .map(|tag| tag.id)
+            .ok_or_else(|| {
+                ErrorType::UnprocessableEntity(format!(
+                    "Tag \"{}\" of the tag group \"{}\" not found",
+                    reference.tag, reference.tag_group
+                ))
+                .res()
+            })
+    }
+    fn group_reference(&self, group_id: i32) -> Result<GroupReference, ErrorResponder> {
+        self.groups
+            .iter()
+            .find(|(_, group)| group.id == group_id)
+            .map(|(arrangement, group)| GroupReference {
+                arrangement: arrangement.clone(),
+                group: group.name.clone(),
+            })
+            .ok_or_else(|| ErrorType::UnprocessableEntity(format!("Group {} not found", group_id)).res())
+    }
+    fn group_id(&self, reference: &GroupReference) -> Result<i32, ErrorResponder> {
+        self.groups
+            .iter()
+            .find(|(arrangement, group)| *arrangement == reference.arrangement && group.name == reference.group)
+            .map(|(_, group)| group.id)
+            .ok_or_else(|| {
+                ErrorType::UnprocessableEntity(format!(
+                    "Group \"{}\" of the arrangement \"{}\" not found",
+                    reference.group, reference.arrangement
+                ))
+                .res()
+            })
+    }
+}
+
+impl ArrangementTemplate {
+    /// Build the template of an arrangement of the user owning `references`.
+    /// Fails with `UnprocessableEntity` for groupings that can't be recreated from a request (EXIF intervals, locations).
+    pub fn export(arrangement: &Arrangement, references: &TemplateReferences) -> Result<Self, ErrorResponder> {
+        let mut tag_group_ids = Vec::new();
+        let strategy = match arrangement.get_strategy()? {
+            Some(strategy) => Some(ArrangementStrategyTemplate::export(&strategy, references, &mut tag_group_ids)?),
+            None => None,
+        };
+        let tag_groups = tag_group_ids
+            .into_iter()
+            .unique()
+            .map(|tag_group_id| {
+                let tgwt = references.tag_group(tag_group_id)?;
+                Ok(TemplateTagGroup {
+                    name: tgwt.tag_group.name.clone(),
+                    multiple: tgwt.tag_group.multiple,
+                    required: tgwt.tag_group.required,
+                    tags: tgwt
+                        .tags
+                        .iter()
+                        .sorted_by_key(|tag| (tag.position, tag.id))
+                        .map(|tag| TemplateTag {
+                            name: tag.name.clone(),
+                            color: tag.color.clone(),
+                            is_default: tag.is_default,
+                        })
+                        .collect(),
+                })
+            })
+            .collect::<Result<Vec<_>, ErrorResponder>>()?;
+        Ok(ArrangementTemplate {
+            name: arrangement.name.clone(),
+            strong_match_conversion: arrangement.strong_match_conversion,
+            strategy,
+            tag_groups,
+        })
+    }
+
+    /// Tag groups of the template that the user owning `references` doesn't have (by name).
+    pub fn missing_tag_groups(&self, references: &TemplateReferences) -> Vec<&TemplateTagGroup> {
+        self.tag_groups
+            .iter()
+            .filter(|tag_group| !references.tag_groups.iter().any(|tgwt| tgwt.tag_group.name == tag_group.name))
+            .collect()
+    }
+
+    /// Strategy request recreating the template strategy for the user owning `references`, resolving the references by name.
+    /// Returns `None` for a manual arrangement. Fails with `UnprocessableEntity` if a reference can't be resolved.
+    pub fn strategy_request(&self, references: &TemplateReferences) -> Result<Option<ArrangementStrategyRequest>, ErrorResponder> {
+        let Some(strategy) = &self.strategy else {
+            return Ok(None);
+        };
+        Ok(Some(ArrangementStrategyRequest {
+            filter: strategy.filter.resolve(references)?,
+            groupings: match &strategy.groupings {
+                TemplateGrouping::GroupByFilter(groups) => StrategyGroupingRequest::GroupByFilter(FilterGroupingRequest {
+                    filters: groups
+                        .iter()
+                        .map(|group| {
+                            Ok(FilterGroupingValueRequest {
+                                id: 0,
+                                name: group.name.clone(),
+                                filter: group.filter.resolve(references)?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, ErrorResponder>>()?,
+                }),
+                TemplateGrouping::GroupByTags {
+                    tag_group,
+                    group_names_format,
+                } => StrategyGroupingRequest::GroupByTags(TagGroupingRequest {
+                    tag_group_id: references.tag_group_id(tag_group)?,
+                    group_names_format: group_names_format.clone(),
+                }),
+                TemplateGrouping::GroupByExifValues(request) => StrategyGroupingRequest::GroupByExifValues(request.clone()),
+                TemplateGrouping::GroupByRating => StrategyGroupingRequest::GroupByRating(RatingGroupingRequest {}),
+            },
+            preserve_unicity: strategy.preserve_unicity,
+        }))
+    }
+}
+
+impl ArrangementStrategyTemplate {
+    /// Template of the strategy, pushing the ids of the referenced tag groups to `tag_group_ids`.
+    fn export(strategy: &ArrangementStrategy, references: &TemplateReferences, tag_group_ids: &mut Vec<i32>) -> Result<Self, ErrorResponder> {
+        let groupings = match &strategy.groupings {
+            StrategyGrouping::GroupByFilter(grouping) => TemplateGrouping::GroupByFilter(
+                grouping
+                    .filters
+                    .iter()
+                    .map(|(group_id, filter)| {
+                        Ok(TemplateFilterGroup {
+                            name: references.group_reference(*group_id)?.group,
+                            filter: TemplateFiltering::export(filter, references, tag_group_ids)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ErrorResponder>>()?,
+            ),
+            StrategyGrouping::GroupByTags(grouping) => {
+                tag_group_ids.push(grouping.tag_group_id);
+                TemplateGrouping::GroupByTags {
+                    tag_group: references.tag_group(grouping.tag_group_id)?.tag_group.name.clone(),
+                    group_names_format: grouping.group_names_format.clone(),
+                }
+            }
+            StrategyGrouping::GroupByExifValues(grouping) => TemplateGrouping::GroupByExifValues(ExifValuesGroupingRequest {
+                data_type: grouping.data_type.clone(),
+                group_names_format: grouping.group_names_format.clone(),
+            }),
+            StrategyGrouping::GroupByRating(_) => TemplateGrouping::GroupByRating,
+            StrategyGrouping::GroupByExifInterval(_) | StrategyGrouping::GroupByLocation(_) => {
+                return ErrorType::UnprocessableEntity("This arrangement grouping can't be exported as a template".to_string()).res_err();
+            }
+        };
+        Ok(ArrangementStrategyTemplate {
+            filter: TemplateFiltering::export(&strategy.filter, references, tag_group_ids)?,
+            groupings,
+            preserve_unicity: strategy.preserve_unicity,
+        })
+    }
+}
+
+impl TemplateFiltering {
+    fn export(filter: &StrategyFiltering, references: &TemplateReferences, tag_group_ids: &mut Vec<i32>) -> Result<Self, ErrorResponder> {
+        let export_all = |filters: &Vec<StrategyFiltering>, tag_group_ids: &mut Vec<i32>| {
+            filters
+                .iter()
+                .map(|filter| Self::export(filter, references, tag_group_ids))
+                .collect::<Result<Vec<_>, ErrorResponder>>()
+        };
+        Ok(match filter {
+            StrategyFiltering::Or(filters) => TemplateFiltering::Or(export_all(filters, tag_group_ids)?),
+            StrategyFiltering::And(filters) => TemplateFiltering::And(export_all(filters, tag_group_ids)?),
+            StrategyFiltering::Not(filter) => TemplateFiltering::Not(Box::new(Self::export(filter, references, tag_group_ids)?)),
+            StrategyFiltering::Filter(filter_type) => TemplateFiltering::Filter(match filter_type {
+                FilterType::IncludeTags(tag_ids) => TemplateFilterType::IncludeTags(
+                    tag_ids
+                        .iter()
+                        .map(|tag_id| {
+                            let (tag_group_id, reference) = references.tag_reference(*tag_id)?;
+                            tag_group_ids.push(tag_group_id);
+                            Ok(reference)
+                        })
+                        .collect::<Result<Vec<_>, ErrorResponder>>()?,
+                ),
+                FilterType::IncludeGroups(group_ids) => {
+                    TemplateFilterType::IncludeGroups(group_ids.iter().map(|group_id| references.group_reference(*group_id)).collect::<Result<
+                        Vec<_>,
+                        ErrorResponder,
+                    >>(
+                    )?)
+                }
+                FilterType::ExifEqualTo(exif) => TemplateFilterType::ExifEqualTo(exif.clone()),
+                FilterType::ExifInInterval(exif) => TemplateFilterType::ExifInInterval(exif.clone()),
+            }),
+        })
+    }
+
+    fn resolve(&self, references: &TemplateReferences) -> Result<StrategyFiltering, ErrorResponder> {
+        let resolve_all = |filters: &Vec<TemplateFiltering>| {
+            filters
+                .iter()
+                .map(|filter| filter.resolve(references))
+                .collect::<Result<Vec<_>, ErrorResponder>>()
+        };
+        Ok(match self {
+            TemplateFiltering::Or(filters) => StrategyFiltering::Or(Box::new(resolve_all(filters)?)),
+            TemplateFiltering::And(filters) => StrategyFiltering::And(Box::new(resolve_all(filters)?)),
+            TemplateFiltering::Not(filter) => StrategyFiltering::Not(Box::new(filter.resolve(references)?)),
+            TemplateFiltering::Filter(filter_type) => StrategyFiltering::Filter(match filter_type {
+                TemplateFilterType::IncludeTags(tags) => FilterType::IncludeTags(tags.iter().map(|tag| references.tag_id(tag)).collect::<Result<
+                    Vec<_>,
+                    ErrorResponder,
+                >>(
+                )?),
+                TemplateFilterType::IncludeGroups(groups) => FilterType::IncludeGroups(
+                    groups
+                        .iter()
+                        .map(|group| references.group_id(group))
+                        .collect::<Result<Vec<_>, ErrorResponder>>()?,
+                ),
+                TemplateFilterType::ExifEqualTo(exif) => FilterType::ExifEqualTo(exif.clone()),
+                TemplateFilterType::ExifInInterval(exif) => FilterType::ExifInInterval(exif.clone()),
+            }),
+        })
+    }
+}
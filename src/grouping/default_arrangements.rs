@@ -0,0 +1,53 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::grouping::arrangement_strategy::{ArrangementStrategyRequest, ExifDataTypeValue};
+use crate::grouping::group_by_exif_value::ExifValuesGroupingRequest;
+use crate::grouping::strategy_filtering::StrategyFiltering;
+use crate::grouping::strategy_grouping::StrategyGroupingRequest;
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::ErrorResponder;
+
+/// Arrangements created for new users: name and strategy request.
+/// - "By Month" groups pictures by creation date, values are compared by month.
+/// - "By Camera" groups pictures by camera model.
+pub fn default_arrangements_requests() -> Vec<(String, ArrangementStrategyRequest)> {
+    let all_pictures = StrategyFiltering::And(Box::default());
+    vec![
+        (
+            "By Month".to_string(),
+            ArrangementStrategyRequest {
+                filter: all_pictures.clone(),
+                groupings: StrategyGroupingRequest::GroupByExifValues(ExifValuesGroupingRequest {
+                    data_type: ExifDataTypeValue::CreationDate(vec![]),
+                    group_names_format: "%Y-%m".to_string(),
+                }),
+                preserve_unicity: true,
+            },
+        ),
+        (
+            "By Camera".to_string(),
+            ArrangementStrategyRequest {
+                filter: all_pictures,
+                groupings: StrategyGroupingRequest::GroupByExifValues(ExifValuesGroupingRequest {
+                    data_type: ExifDataTypeValue::CameraModel(vec![]),
+                    group_names_format: "{}".to_string(),
+                }),
+                preserve_unicity: true,
+            },
+        ),
+    ]
+}
+
+/// Creates the default arrangements for a user that has no arrangement yet, if enabled (`DEFAULT_ARRANGEMENTS`).
+/// The user is expected to have no pictures, then no grouping is done.
+pub fn create_default_arrangements(conn: &mut DBConn, user_id: i32) -> Result<(), ErrorResponder> {
+    if !CONFIG.default_arrangements || !Arrangement::from_user_id(conn, user_id)?.is_empty() {
+        return Ok(());
+    }
+    for (name, strategy_request) in default_arrangements_requests() {
+        let mut arrangement = Arrangement::new(conn, user_id, name, false, None)?;
+        let strategy = strategy_request.create(conn, arrangement.id)?;
+        arrangement.set_strategy(conn, Some(strategy))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use crate::database::group::arrangement::ArrangementDetails;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct DependencyGraphNode {
+    pub arrangement_id: i32,
+    pub name: String,
+}
+/// The arrangement `arrangement_id` depends on the groups of the arrangement `depends_on`.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct DependencyGraphEdge {
+    pub arrangement_id: i32,
+    pub depends_on: i32,
+}
+
+/// Dependencies between the non-manual arrangements of a user, as used to sort them topologically before grouping.
+/// Arrangements of a cycle can't be sorted: they are grouped in an arbitrary order.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct DependencyGraph {
+    pub nodes: Vec<DependencyGraphNode>,
+    pub edges: Vec<DependencyGraphEdge>,
+    pub has_cycle: bool,
+}
+
+impl DependencyGraph {
+    /// Build the graph from the arrangements and their dependant arrangements, sorted by arrangement id.
+    pub fn new(arrangements: &[ArrangementDetails]) -> DependencyGraph {
+        let dependencies: BTreeMap<i32, Vec<i32>> = arrangements
+            .iter()
+            .map(|details| {
+                let mut depends_on = details.dependant_arrangements.clone();
+                depends_on.sort();
+                depends_on.dedup();
+                (details.arrangement.id, depends_on)
+            })
+            .collect();
+
+        let mut nodes = arrangements
+            .iter()
+            .map(|details| DependencyGraphNode {
+                arrangement_id: details.arrangement.id,
+                name: details.arrangement.name.clone(),
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| node.arrangement_id);
+        let edges = dependencies
+            .iter()
+            .flat_map(|(arrangement_id, depends_on)| {
+                depends_on.iter().map(|depends_on| DependencyGraphEdge {
+                    arrangement_id: *arrangement_id,
+                    depends_on: *depends_on,
+                })
+            })
+            .collect();
+
+        DependencyGraph {
+            nodes,
+            edges,
+            has_cycle: Self::has_cycle(&dependencies),
+        }
+    }
+
+    fn has_cycle(dependencies: &BTreeMap<i32, Vec<i32>>) -> bool {
+        // Depth-first search, a cycle being found when reaching an arrangement of the current path
+        fn visit(node_id: i32, dependencies: &BTreeMap<i32, Vec<i32>>, visited: &mut HashSet<i32>, path: &mut HashSet<i32>) -> bool {
+            if path.contains(&node_id) {
+                return true;
+            }
+            if !visited.insert(node_id) {
+                return false;
+            }
+            path.insert(node_id);
+            let found = dependencies
+                .get(&node_id)
+                .is_some_and(|depends_on| depends_on.iter().any(|dep| visit(*dep, dependencies, visited, path)));
+            path.remove(&node_id);
+            found
+        }
+        let mut visited = HashSet::new();
+        dependencies
+            .keys()
+            .any(|node_id| visit(*node_id, dependencies, &mut visited, &mut HashSet::new()))
+    }
+}
@@ -0,0 +1,194 @@
+use crate::database::database::DBConn;
+use crate::database::schema::*;
+use crate::grouping::strategy_filtering::{KnownPicture, StrategyFiltering};
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use std::collections::{HashMap, HashSet};
+
+/// How the pictures matching a filter are obtained (see [`FilterCache::plan`]).
+#[derive(Debug, PartialEq)]
+pub enum FilterPlan {
+    /// The matching pictures are known without querying the database.
+    Known(HashSet<i64>),
+    /// The pictures known to match, and the pictures that must still be filtered by a query (`None` for all the pictures).
+    Query(HashSet<i64>, Option<Vec<i64>>),
+}
+
+/// Results of the arrangements filters within a single grouping process.
+/// When grouping a given list of pictures, their tags and groups are loaded once so that most filters are evaluated
+/// without any query (e.g. a tag filter on a picture that has none of the tags), and the results of identical filters are reused.
+/// The groups whose content changes during the process must be reported with [`FilterCache::groups_changed`].
+#[derive(Debug, Default)]
+pub struct FilterCache {
+    /// Known pictures, when only a given list of pictures is grouped
+    pictures: Option<HashMap<i64, KnownPicture>>,
+    results: Vec<(StrategyFiltering, HashSet<i64>)>,
+    changed_group_ids: HashSet<i32>,
+    /// Number of filter queries run on the database
+    pub queries_count: usize,
+}
+
+impl FilterCache {
+    pub fn new(pictures: Option<HashMap<i64, KnownPicture>>) -> Self {
+        FilterCache {
+            pictures,
+            ..Default::default()
+        }
+    }
+
+    /// Load the tags and groups of the pictures of `picture_ids`, if provided. Pictures that don't exist are left out.
+    pub fn load(conn: &mut DBConn, picture_ids: Option<&Vec<i64>>) -> Result<Self, ErrorResponder> {
+        let Some(picture_ids) = picture_ids else {
+            return Ok(Self::new(None));
+        };
+        let existing: Vec<(i64, bool)> = pictures::table
+            .filter(pictures::id.eq_any(picture_ids))
+            .select((pictures::id, pictures::deleted_date.is_not_null()))
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures".to_string(), e).res())?;
+        let existing_ids = existing.iter().map(|(picture_id, _)| *picture_id).collect::<Vec<_>>();
+        let tags: Vec<(i64, i32)> = pictures_tags::table
+            .filter(pictures_tags::picture_id.eq_any(&existing_ids))
+            .select((pictures_tags::picture_id, pictures_tags::tag_id))
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures tags".to_string(), e).res())?;
+        let groups: Vec<(i64, i32)> = groups_pictures::table
+            .filter(groups_pictures::picture_id.eq_any(&existing_ids))
+            .select((groups_pictures::picture_id, groups_pictures::group_id))
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get pictures groups".to_string(), e).res())?;
+
+        let mut pictures: HashMap<i64, KnownPicture> = existing
+            .into_iter()
+            .map(|(id, deleted)| {
+                (
+                    id,
+                    KnownPicture {
+                        deleted,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        for (picture_id, tag_id) in tags {
+            pictures.entry(picture_id).or_default().tag_ids.insert(tag_id);
+        }
+        for (picture_id, group_id) in groups {
+            pictures.entry(picture_id).or_default().group_ids.insert(group_id);
+        }
+        Ok(Self::new(Some(pictures)))
+    }
+
+    /// Find out how to get the pictures matching `filter`: from a previous identical filter, from the known pictures,
+    /// or with a query on the pictures that can't be evaluated.
+    pub fn plan(&self, filter: &StrategyFiltering) -> FilterPlan {
+        if let Some((_, result)) = self.results.iter().find(|(cached, _)| cached == filter) {
+            return FilterPlan::Known(result.clone());
+        }
+        let Some(pictures) = &self.pictures else {
+            return FilterPlan::Query(HashSet::new(), None);
+        };
+        let mut matching = HashSet::new();
+        let mut unknown = Vec::new();
+        for (picture_id, picture) in pictures.iter().filter(|(_, picture)| !picture.deleted) {
+            match filter.evaluate(picture) {
+                Some(true) => {
+                    matching.insert(*picture_id);
+                }
+                Some(false) => {}
+                None => unknown.push(*picture_id),
+            }
+        }
+        if unknown.is_empty() {
+            FilterPlan::Known(matching)
+        } else {
+            unknown.sort();
+            FilterPlan::Query(matching, Some(unknown))
+        }
+    }
+
+    /// Keep the result of `filter`, unless it depends on groups whose content changed.
+    pub fn record(&mut self, filter: &StrategyFiltering, result: &HashSet<i64>) {
+        if filter
+            .get_dependant_groups()
+            .iter()
+            .any(|group_id| self.changed_group_ids.contains(group_id))
+        {
+            return;
+        }
+        if !self.results.iter().any(|(cached, _)| cached == filter) {
+            self.results.push((filter.clone(), result.clone()));
+        }
+    }
+
+    /// Pictures matching `filter`, querying the database only for what can't be known otherwise.
+    pub fn filter_pictures(&mut self, conn: &mut DBConn, filter: &StrategyFiltering) -> Result<HashSet<i64>, ErrorResponder> {
+        let result = match self.plan(filter) {
+            FilterPlan::Known(result) => result,
+            FilterPlan::Query(mut matching, picture_ids) => {
+                self.queries_count += 1;
+                matching.extend(filter.filter_pictures(conn, picture_ids.as_ref())?);
+                matching
+            }
+        };
+        self.record(filter, &result);
+        Ok(result)
+    }
+
+    /// Pictures in the trash that are in any of the groups of `group_ids`, to be ungrouped as they match no filter.
+    pub fn deleted_pictures(&self, conn: &mut DBConn, group_ids: &[i32]) -> Result<HashSet<i64>, ErrorResponder> {
+        if let Some(deleted_ids) = self.known_deleted_pictures(group_ids) {
+            return Ok(deleted_ids);
+        }
+        let deleted_ids: Vec<i64> = deleted_pictures_statement(group_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError("Failed to get deleted pictures".to_string(), e).res())?;
+        Ok(deleted_ids.into_iter().collect())
+    }
+    /// Known pictures in the trash that are in any of the groups of `group_ids`, `None` if the pictures are not known.
+    pub fn known_deleted_pictures(&self, group_ids: &[i32]) -> Option<HashSet<i64>> {
+        let pictures = self.pictures.as_ref()?;
+        Some(
+            pictures
+                .iter()
+                .filter(|(_, picture)| {
+                    picture.deleted
+                        && group_ids
+                            .iter()
+                            .any(|group_id| picture.group_ids.contains(group_id) || picture.changed_group_ids.contains(group_id))
+                })
+                .map(|(picture_id, _)| *picture_id)
+                .collect(),
+        )
+    }
+
+    /// Report that pictures of `picture_ids` may have been added to or removed from the groups of `group_ids`.
+    /// The results of the filters depending on these groups are dropped.
+    pub fn groups_changed(&mut self, group_ids: &[i32], picture_ids: &HashSet<i64>) {
+        if picture_ids.is_empty() {
+            return;
+        }
+        self.changed_group_ids.extend(group_ids.iter().cloned());
+        if let Some(pictures) = &mut self.pictures {
+            for picture_id in picture_ids.iter() {
+                if let Some(picture) = pictures.get_mut(picture_id) {
+                    picture.changed_group_ids.extend(group_ids.iter().cloned());
+                }
+            }
+        }
+        self.results
+            .retain(|(filter, _)| !filter.get_dependant_groups().iter().any(|group_id| group_ids.contains(group_id)));
+    }
+}
+
+pub fn deleted_pictures_statement(group_ids: &[i32]) -> impl QueryFragment<Pg> + for<'a> LoadQuery<'a, DBConn, i64> {
+    groups_pictures::table
+        .inner_join(pictures::table.on(pictures::id.eq(groups_pictures::picture_id)))
+        .filter(groups_pictures::group_id.eq_any(group_ids.to_vec()))
+        .filter(pictures::deleted_date.is_not_null())
+        .select(groups_pictures::picture_id)
+        .distinct()
+}
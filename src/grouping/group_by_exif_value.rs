@@ -1,7 +1,26 @@
+use crate::database::database::DBConn;
+use crate::database::group::group::Group;
+use crate::database::group::manual_override::ForcedMemberships;
+use crate::database::picture::picture::Picture;
 use crate::grouping::arrangement_strategy::ExifDataTypeValue;
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::group_add_pictures;
+use crate::grouping::strategy_grouping::{StrategyGroupingTrait, UngroupRecord};
+use crate::utils::errors_catcher::ErrorResponder;
+use itertools::Itertools;
 use rocket::serde::{Deserialize, Serialize};
 use rocket_okapi::JsonSchema;
+use std::collections::{HashMap, HashSet};
+use std::mem::discriminant;
 
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExifValuesGroupingRequest {
+    pub data_type: ExifDataTypeValue, // Type of the grouped data, the values (can be empty) are used to pre-create groups
+    pub group_names_format: String,   // Datetime format for dates, or a format with a "{}" placeholder for other values
+}
+
+/// Groups pictures having the same formatted value in a group.
+/// Values are compared after being formatted with `group_names_format`, allowing for instance to group dates by month ("%Y-%m").
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ExifValuesGrouping {
     pub data_type: ExifDataTypeValue, // data vec contains the values for each group
@@ -9,3 +28,183 @@ pub struct ExifValuesGrouping {
     pub group_names_format: String,
     pub other_group_id: Option<i32>,
 }
+
+impl ExifValuesGrouping {
+    /// Map of the formatted values to their group id
+    fn get_formatted_values(&self) -> HashMap<String, i32> {
+        self.values_to_group_id
+            .iter()
+            .enumerate()
+            .filter_map(|(i, group_id)| self.data_type.format_value(i, &self.group_names_format).map(|key| (key, *group_id)))
+            .collect()
+    }
+    /// Returns the group of the value (a single value of the same variant as `data_type`), creating it if needed.
+    fn get_or_create_value_group(
+        &mut self,
+        conn: &mut DBConn,
+        arrangement_id: i32,
+        formatted_values: &mut HashMap<String, i32>,
+        value: ExifDataTypeValue,
+    ) -> Result<Option<(i32, bool)>, ErrorResponder> {
+        let Some(key) = value.format_value(0, &self.group_names_format) else {
+            return Ok(None);
+        };
+        if let Some(id) = formatted_values.get(&key) {
+            return Ok(Some((*id, false)));
+        }
+        if !self.data_type.append(value) {
+            return Ok(None);
+        }
+        let id = Group::insert_colored(conn, arrangement_id, key.clone())?.id;
+        self.values_to_group_id.push(id);
+        formatted_values.insert(key, id);
+        Ok(Some((id, true)))
+    }
+    fn get_or_create_other_group(&mut self, conn: &mut DBConn, arrangement_id: i32) -> Result<(i32, bool), ErrorResponder> {
+        if let Some(id) = self.other_group_id {
+            Ok((id, false))
+        } else {
+            let id = Group::insert_colored(conn, arrangement_id, "Other".to_string())?.id;
+            self.other_group_id = Some(id);
+            Ok((id, true))
+        }
+    }
+    /// Empty copy of the data type (same variant, no values)
+    fn empty_data_type(data_type: &ExifDataTypeValue) -> ExifDataTypeValue {
+        let mut empty = data_type.clone();
+        match &mut empty {
+            ExifDataTypeValue::CreationDate(v) | ExifDataTypeValue::EditionDate(v) => v.clear(),
+            ExifDataTypeValue::Latitude(v) | ExifDataTypeValue::Longitude(v) | ExifDataTypeValue::FocalLength(v) | ExifDataTypeValue::FNumber(v) => {
+                v.clear()
+            }
+            ExifDataTypeValue::Altitude(v) | ExifDataTypeValue::Width(v) | ExifDataTypeValue::Height(v) => v.clear(),
+            ExifDataTypeValue::Orientation(v) => v.clear(),
+            ExifDataTypeValue::CameraBrand(v) | ExifDataTypeValue::CameraModel(v) => v.clear(),
+            ExifDataTypeValue::ExposureTime(v) => v.clear(),
+            ExifDataTypeValue::IsoSpeed(v) => v.clear(),
+        }
+        empty
+    }
+    /// Split a multi-values data type into single values of the same variant
+    fn split_values(data_type: &ExifDataTypeValue) -> Vec<ExifDataTypeValue> {
+        (0..data_type.len())
+            .map(|i| {
+                let mut value = Self::empty_data_type(data_type);
+                match (&mut value, data_type) {
+                    (ExifDataTypeValue::CreationDate(v), ExifDataTypeValue::CreationDate(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::EditionDate(v), ExifDataTypeValue::EditionDate(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::Latitude(v), ExifDataTypeValue::Latitude(o)) => v.push(o[i].clone()),
+                    (ExifDataTypeValue::Longitude(v), ExifDataTypeValue::Longitude(o)) => v.push(o[i].clone()),
+                    (ExifDataTypeValue::Altitude(v), ExifDataTypeValue::Altitude(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::Orientation(v), ExifDataTypeValue::Orientation(o)) => v.push(o[i].clone()),
+                    (ExifDataTypeValue::Width(v), ExifDataTypeValue::Width(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::Height(v), ExifDataTypeValue::Height(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::CameraBrand(v), ExifDataTypeValue::CameraBrand(o)) => v.push(o[i].clone()),
+                    (ExifDataTypeValue::CameraModel(v), ExifDataTypeValue::CameraModel(o)) => v.push(o[i].clone()),
+                    (ExifDataTypeValue::FocalLength(v), ExifDataTypeValue::FocalLength(o)) => v.push(o[i].clone()),
+                    (ExifDataTypeValue::ExposureTime(v), ExifDataTypeValue::ExposureTime(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::IsoSpeed(v), ExifDataTypeValue::IsoSpeed(o)) => v.push(o[i]),
+                    (ExifDataTypeValue::FNumber(v), ExifDataTypeValue::FNumber(o)) => v.push(o[i].clone()),
+                    _ => {}
+                }
+                value
+            })
+            .collect()
+    }
+}
+
+impl StrategyGroupingTrait for ExifValuesGrouping {
+    type Request = ExifValuesGroupingRequest;
+
+    fn get_groups(&self) -> Vec<i32> {
+        let mut groups = self.values_to_group_id.clone();
+        if let Some(id) = self.other_group_id {
+            groups.push(id);
+        }
+        groups
+    }
+
+    fn group_pictures(
+        &mut self,
+        conn: &mut DBConn,
+        delta: &mut GroupingDelta,
+        arrangement_id: i32,
+        _preserve_unicity: bool, // A picture has a single value, then it always belongs to a single group
+        ungroup_record: &mut UngroupRecord,
+        forced: &ForcedMemberships,
+        picture_ids: &HashSet<i64>,
+    ) -> Result<bool, ErrorResponder> {
+        let mut update_strategy = false;
+        let mut formatted_values = self.get_formatted_values();
+        let mut groups_pictures: HashMap<i32, Vec<i64>> = HashMap::new();
+        let mut other_pictures = Vec::new();
+
+        let pictures = Picture::from_ids(conn, &picture_ids.iter().cloned().collect_vec())?;
+        for picture in pictures {
+            let group = match self.data_type.picture_value(&picture) {
+                Some(value) => self.get_or_create_value_group(conn, arrangement_id, &mut formatted_values, value)?,
+                None => None,
+            };
+            match group {
+                Some((group_id, created)) => {
+                    update_strategy |= created;
+                    groups_pictures.entry(group_id).or_default().push(picture.id);
+                }
+                None => other_pictures.push(picture.id),
+            }
+        }
+        if !other_pictures.is_empty() {
+            let (other_group_id, created) = self.get_or_create_other_group(conn, arrangement_id)?;
+            update_strategy |= created;
+            groups_pictures.insert(other_group_id, other_pictures);
+        }
+
+        for (group_id, pictures) in groups_pictures.iter() {
+            group_add_pictures(conn, delta, *group_id, &forced.without_forced_out(*group_id, pictures))?;
+        }
+
+        if ungroup_record.enable {
+            for group_id in self.get_groups() {
+                let grouped: HashSet<i64> = groups_pictures.get(&group_id).map(|p| p.iter().cloned().collect()).unwrap_or_default();
+                ungroup_record.add(group_id, picture_ids.difference(&grouped).cloned().collect());
+            }
+        }
+        Ok(update_strategy)
+    }
+
+    fn create(conn: &mut DBConn, arrangement_id: i32, request: &Self::Request) -> Result<Box<Self>, ErrorResponder> {
+        let mut grouping = ExifValuesGrouping {
+            data_type: Self::empty_data_type(&request.data_type),
+            values_to_group_id: vec![],
+            group_names_format: request.group_names_format.clone(),
+            other_group_id: None,
+        };
+        // Pre-create the groups of the requested values
+        let mut formatted_values = grouping.get_formatted_values();
+        for value in Self::split_values(&request.data_type) {
+            grouping.get_or_create_value_group(conn, arrangement_id, &mut formatted_values, value)?;
+        }
+        Ok(Box::new(grouping))
+    }
+
+    fn edit(&mut self, conn: &mut DBConn, arrangement_id: i32, request: &Self::Request) -> Result<(), ErrorResponder> {
+        if discriminant(&self.data_type) != discriminant(&request.data_type) || self.group_names_format != request.group_names_format {
+            // Values are not comparable anymore: recreate all the groups.
+            self.delete(conn, arrangement_id)?;
+            *self = *Self::create(conn, arrangement_id, request)?;
+        } else {
+            let mut formatted_values = self.get_formatted_values();
+            for value in Self::split_values(&request.data_type) {
+                self.get_or_create_value_group(conn, arrangement_id, &mut formatted_values, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&self, conn: &mut DBConn, _arrangement_id: i32) -> Result<(), ErrorResponder> {
+        for group_id in self.get_groups() {
+            Group::mark_as_to_be_deleted(conn, group_id)?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use crate::database::database::DBConn;
 use crate::database::group::group::Group;
+use crate::database::group::manual_override::ForcedMemberships;
+use crate::grouping::grouping_delta::GroupingDelta;
 use crate::grouping::grouping_process::group_add_pictures;
 use crate::grouping::strategy_filtering::StrategyFiltering;
 use crate::grouping::strategy_grouping::{StrategyGroupingTrait, UngroupRecord};
@@ -33,7 +35,7 @@ impl FilterGrouping {
         if let Some(id) = self.other_group_id {
             Ok((id, false))
         } else {
-            let id = Group::insert(conn, arrangement_id, "Other".to_string(), false)?.id;
+            let id = Group::insert_colored(conn, arrangement_id, "Other".to_string())?.id;
             self.other_group_id = Some(id);
             Ok((id, true))
         }
@@ -62,9 +64,11 @@ impl StrategyGroupingTrait for FilterGrouping {
     fn group_pictures(
         &mut self,
         conn: &mut DBConn,
+        delta: &mut GroupingDelta,
         arrangement_id: i32,
         preserve_unicity: bool,
         ungroup_record: &mut UngroupRecord,
+        forced: &ForcedMemberships,
         picture_ids: &HashSet<i64>,
     ) -> Result<bool, ErrorResponder> {
         let mut update_strategy = false;
@@ -80,7 +84,7 @@ impl StrategyGroupingTrait for FilterGrouping {
             );
             remaining_pictures_ids = remaining_pictures_ids.difference(&group_pictures).cloned().collect();
 
-            group_add_pictures(conn, *group_id, &group_pictures.iter().cloned().collect_vec())?;
+            group_add_pictures(conn, delta, *group_id, &forced.without_forced_out(*group_id, &group_pictures))?;
             if ungroup_record.enable {
                 let ungroup_pictures = picture_ids.difference(&group_pictures).cloned().collect();
                 ungroup_record.add(*group_id, ungroup_pictures);
@@ -89,7 +93,12 @@ impl StrategyGroupingTrait for FilterGrouping {
         if remaining_pictures_ids.len() != 0 {
             let (other_group_id, group_created) = self.get_or_create_other_group(conn, arrangement_id)?;
             update_strategy = group_created;
-            group_add_pictures(conn, other_group_id, &remaining_pictures_ids.iter().cloned().collect_vec())?;
+            group_add_pictures(
+                conn,
+                delta,
+                other_group_id,
+                &forced.without_forced_out(other_group_id, &remaining_pictures_ids),
+            )?;
         }
         // If the other group is not just created, and there is an other group, remove the other group pictures.
         if ungroup_record.enable && !update_strategy && self.other_group_id.is_some() {
@@ -105,7 +114,7 @@ impl StrategyGroupingTrait for FilterGrouping {
             .filters
             .iter()
             .map(|value| {
-                let group = Group::insert(conn, arrangement_id, value.name.clone(), false)?;
+                let group = Group::insert_colored(conn, arrangement_id, value.name.clone())?;
                 Ok((group.id, value.filter.clone()))
             })
             .collect::<Result<Vec<(i32, StrategyFiltering)>, ErrorResponder>>()?;
@@ -137,7 +146,7 @@ impl StrategyGroupingTrait for FilterGrouping {
         // Create new groups (with id <= 0 or unmatched)
         request.filters.iter_mut().try_for_each(|value| {
             if value.id <= 0 || !self.filters.iter().any(|f| f.0 == value.id) {
-                let group = Group::insert(conn, arrangement_id, value.name.clone(), false)?;
+                let group = Group::insert_colored(conn, arrangement_id, value.name.clone())?;
                 self.filters.push((group.id, value.filter.clone()));
                 value.id = group.id;
             }
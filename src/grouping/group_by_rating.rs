@@ -0,0 +1,109 @@
+use crate::database::database::DBConn;
+use crate::database::group::group::Group;
+use crate::database::group::manual_override::ForcedMemberships;
+use crate::database::picture::rating::{Rating, RATING_MAX};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::group_add_pictures;
+use crate::grouping::strategy_grouping::{StrategyGroupingTrait, UngroupRecord};
+use crate::utils::errors_catcher::ErrorResponder;
+use itertools::Itertools;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_okapi::JsonSchema;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RatingGroupingRequest {}
+
+/// Groups pictures by the rating given by the owner of the arrangement, with a group per rating value and a group for unrated pictures.
+/// All the groups are created with the strategy.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RatingGrouping {
+    pub rating_to_group_id: BTreeMap<i16, i32>,
+    pub unrated_group_id: i32,
+}
+
+impl RatingGrouping {
+    pub fn format_group_name(rating: i16) -> String {
+        if rating == 1 {
+            "1 star".to_string()
+        } else {
+            format!("{} stars", rating)
+        }
+    }
+    /// Group of each picture of `picture_ids` (group_id -> picture ids), from the ratings of the arrangement owner.
+    /// Pictures without rating, or with a rating out of range, belong to the unrated group.
+    pub fn assign_groups(&self, picture_ids: &HashSet<i64>, ratings: &[Rating]) -> BTreeMap<i32, Vec<i64>> {
+        let mut groups_pictures: BTreeMap<i32, Vec<i64>> = BTreeMap::new();
+        for picture_id in picture_ids.iter().sorted() {
+            let group_id = ratings
+                .iter()
+                .find(|rating| rating.picture_id == *picture_id)
+                .and_then(|rating| self.rating_to_group_id.get(&rating.rating))
+                .unwrap_or(&self.unrated_group_id);
+            groups_pictures.entry(*group_id).or_default().push(*picture_id);
+        }
+        groups_pictures
+    }
+}
+
+impl StrategyGroupingTrait for RatingGrouping {
+    type Request = RatingGroupingRequest;
+
+    fn get_groups(&self) -> Vec<i32> {
+        let mut groups: Vec<i32> = self.rating_to_group_id.values().cloned().collect();
+        groups.push(self.unrated_group_id);
+        groups
+    }
+
+    fn group_pictures(
+        &mut self,
+        conn: &mut DBConn,
+        delta: &mut GroupingDelta,
+        arrangement_id: i32,
+        _preserve_unicity: bool, // A picture has a single rating by the user, then it always belongs to a single group
+        ungroup_record: &mut UngroupRecord,
+        forced: &ForcedMemberships,
+        picture_ids: &HashSet<i64>,
+    ) -> Result<bool, ErrorResponder> {
+        let ratings = Rating::from_picture_ids_of_arrangement_owner(conn, arrangement_id, &picture_ids.iter().cloned().collect_vec())?;
+        let groups_pictures = self.assign_groups(picture_ids, &ratings);
+
+        for (group_id, pictures) in groups_pictures.iter() {
+            group_add_pictures(conn, delta, *group_id, &forced.without_forced_out(*group_id, pictures))?;
+        }
+
+        if ungroup_record.enable {
+            for group_id in self.get_groups() {
+                let grouped: HashSet<i64> = groups_pictures.get(&group_id).map(|p| p.iter().cloned().collect()).unwrap_or_default();
+                ungroup_record.add(group_id, picture_ids.difference(&grouped).cloned().collect());
+            }
+        }
+        // Groups are all created with the strategy
+        Ok(false)
+    }
+
+    fn create(conn: &mut DBConn, arrangement_id: i32, _request: &Self::Request) -> Result<Box<Self>, ErrorResponder> {
+        let mut rating_to_group_id = BTreeMap::new();
+        for rating in (0..=RATING_MAX).rev() {
+            let id = Group::insert_colored(conn, arrangement_id, Self::format_group_name(rating))?.id;
+            rating_to_group_id.insert(rating, id);
+        }
+        let unrated_group_id = Group::insert_colored(conn, arrangement_id, "Unrated".to_string())?.id;
+        Ok(Box::new(RatingGrouping {
+            rating_to_group_id,
+            unrated_group_id,
+        }))
+    }
+
+    fn edit(&mut self, _conn: &mut DBConn, _arrangement_id: i32, _request: &Self::Request) -> Result<(), ErrorResponder> {
+        // Nothing can be edited: the groups stay the same.
+        Ok(())
+    }
+
+    fn delete(&self, conn: &mut DBConn, _arrangement_id: i32) -> Result<(), ErrorResponder> {
+        for group_id in self.get_groups() {
+            Group::mark_as_to_be_deleted(conn, group_id)?;
+        }
+        Ok(())
+    }
+}
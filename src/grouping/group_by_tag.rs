@@ -1,8 +1,10 @@
 use crate::database::database::DBConn;
 use crate::database::group::arrangement::ArrangementDetails;
 use crate::database::group::group::Group;
+use crate::database::group::manual_override::ForcedMemberships;
 use crate::database::picture::picture_tag::PictureTag;
 use crate::database::tag::tag::Tag;
+use crate::grouping::grouping_delta::GroupingDelta;
 use crate::grouping::grouping_process::{group_add_pictures, group_remove_pictures};
 use crate::grouping::strategy_filtering::{FilterType, StrategyFiltering};
 use crate::grouping::strategy_grouping::{StrategyGroupingTrait, UngroupRecord};
@@ -29,7 +31,7 @@ impl TagGrouping {
         if let Some(id) = self.tag_id_to_group_id.get(&tag.id) {
             Ok((*id, false))
         } else {
-            let id = Group::insert(conn, arrangement_id, self.format_group_name(&tag), false)?.id;
+            let id = Group::insert(conn, arrangement_id, self.format_group_name(tag), false, Some(tag.color.clone()))?.id;
             self.other_group_id = Some(id);
             Ok((id, true))
         }
@@ -38,7 +40,7 @@ impl TagGrouping {
         if let Some(id) = self.other_group_id {
             Ok((id, false))
         } else {
-            let id = Group::insert(conn, arrangement_id, "Other".to_string(), false)?.id;
+            let id = Group::insert_colored(conn, arrangement_id, "Other".to_string())?.id;
             self.other_group_id = Some(id);
             Ok((id, true))
         }
@@ -61,9 +63,11 @@ impl StrategyGroupingTrait for TagGrouping {
     fn group_pictures(
         &mut self,
         conn: &mut DBConn,
+        delta: &mut GroupingDelta,
         arrangement_id: i32,
         preserve_unicity: bool,
         ungroup_record: &mut UngroupRecord,
+        forced: &ForcedMemberships,
         picture_ids: &HashSet<i64>,
     ) -> Result<bool, ErrorResponder> {
         let mut update_strategy = false;
@@ -81,7 +85,7 @@ impl StrategyGroupingTrait for TagGrouping {
                 let (group_id, group_created) = self.get_or_create_tag_group(conn, &tag, arrangement_id)?;
                 update_strategy |= group_created;
                 remaining_pictures_ids.retain(|&x| group_pictures.contains(&x));
-                group_add_pictures(conn, group_id, &group_pictures.iter().cloned().collect_vec())?;
+                group_add_pictures(conn, delta, group_id, &forced.without_forced_out(group_id, &group_pictures))?;
             }
 
             if ungroup_record.enable {
@@ -94,7 +98,12 @@ impl StrategyGroupingTrait for TagGrouping {
         if remaining_pictures_ids.len() != 0 {
             let (other_group_id, group_created) = self.get_or_create_other_group(conn, arrangement_id)?;
             update_strategy = group_created;
-            group_add_pictures(conn, other_group_id, &remaining_pictures_ids.iter().cloned().collect_vec())?;
+            group_add_pictures(
+                conn,
+                delta,
+                other_group_id,
+                &forced.without_forced_out(other_group_id, &remaining_pictures_ids),
+            )?;
         }
         // If the other group is not just created, and there is an other group, remove the other group pictures.
         if ungroup_record.enable && !update_strategy && self.other_group_id.is_some() {
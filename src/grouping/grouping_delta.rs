@@ -0,0 +1,312 @@
+use crate::database::database::DBConn;
+use crate::utils::errors_catcher::{err_transaction, ErrorResponder, ErrorType};
+use diesel::connection::{Connection, TransactionManager};
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::methods::ExecuteDsl;
+use diesel::sql_types::Integer;
+use diesel::RunQueryDsl;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Class of the Postgres advisory locks of the grouping operations, the second lock key being the user id.
+const GROUPING_LOCK_CLASS: i32 = 1;
+
+lazy_static! {
+    /// Channel on which the net group membership changes of each grouping operation are published.
+    pub static ref GROUPING_EVENTS: broadcast::Sender<Vec<PictureGroupsEvent>> = broadcast::channel(64).0;
+}
+
+/// Progress of a grouping operation: `processed` out of `total` arrangements have been grouped, the last one being `arrangement_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct GroupingProgress {
+    pub arrangement_id: i32,
+    pub processed: usize,
+    pub total: usize,
+}
+
+/// Per-user broadcast channels of [`GroupingProgress`] events, managed by Rocket.
+/// A channel is created on the first subscription of the user, and dropped once it has no subscriber left.
+#[derive(Debug, Default)]
+pub struct GroupingProgressChannels {
+    channels: Mutex<HashMap<i32, broadcast::Sender<GroupingProgress>>>, // user_id -> channel
+}
+
+impl GroupingProgressChannels {
+    pub fn subscribe(&self, user_id: i32) -> broadcast::Receiver<GroupingProgress> {
+        let mut channels = self.channels.lock().unwrap();
+        channels.entry(user_id).or_insert_with(|| broadcast::channel(64).0).subscribe()
+    }
+    /// Publish the progress to the subscribers of the user, if any.
+    pub fn send(&self, user_id: i32, progress: GroupingProgress) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(&user_id) {
+            if sender.send(progress).is_err() {
+                // No subscriber left
+                channels.remove(&user_id);
+            }
+        }
+    }
+}
+
+/// Records a [`GroupingProgress`] event each time an arrangement has been grouped (see [`GroupingDelta::take_progress`]),
+/// or nothing for the operations whose progress is not reported.
+/// The events are published once the grouping operation is committed (see [`grouping_transaction_reporting`]).
+pub struct GroupingProgressReporter {
+    enabled: bool,
+    user_id: i32,
+    processed: usize,
+    total: usize,
+    events: Vec<GroupingProgress>,
+}
+
+impl GroupingProgressReporter {
+    /// Reporter of the progress of a grouping operation of the user over `total` arrangements.
+    pub fn new(user_id: i32, total: usize) -> Self {
+        GroupingProgressReporter {
+            enabled: true,
+            user_id,
+            processed: 0,
+            total,
+            events: vec![],
+        }
+    }
+    /// Reporter recording nothing.
+    pub fn disabled(user_id: i32) -> Self {
+        GroupingProgressReporter {
+            enabled: false,
+            ..Self::new(user_id, 0)
+        }
+    }
+    pub fn arrangement_grouped(&mut self, arrangement_id: i32) {
+        if !self.enabled {
+            return;
+        }
+        self.processed += 1;
+        self.events.push(GroupingProgress {
+            arrangement_id,
+            processed: self.processed,
+            total: self.total,
+        });
+    }
+}
+
+/// Net change of the groups of a picture after a grouping operation.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PictureGroupsEvent {
+    pub picture_id: i64,
+    pub added_groups: Vec<i32>,
+    pub removed_groups: Vec<i32>,
+}
+
+/// Number of pictures that an operation would add to and remove from each group.
+/// Existing groups are referenced by id. The groups the operation would create don't exist once it is rolled back,
+/// they are referenced by name.
+#[derive(Debug, Default, PartialEq, Serialize, JsonSchema)]
+pub struct GroupingPreview {
+    pub would_add: BTreeMap<i32, usize>,
+    pub would_remove: BTreeMap<i32, usize>,
+    /// Pictures that would be added to each new group, by group name
+    pub new_groups_would_add: BTreeMap<String, usize>,
+}
+
+/// Records the group membership changes made during a grouping operation.
+/// A picture added then removed from the same group (or the opposite) cancels out, only net changes are kept.
+#[derive(Debug, Default)]
+pub struct GroupingDelta {
+    map: HashMap<i64, BTreeMap<i32, bool>>, // picture_id -> group_id -> true if added, false if removed
+    /// User whose grouping progress is reported, set by the endpoint that triggered the operation (see [`GroupingDelta::report_progress`]).
+    progress_user_id: Option<i32>,
+    /// Progress events of the user, to be published once the operation is committed
+    progress: Vec<GroupingProgress>,
+}
+
+impl GroupingDelta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Record pictures that have been added to a group (the ones that were not already in it).
+    pub fn record_added(&mut self, group_id: i32, picture_ids: &[i64]) {
+        self.record(group_id, picture_ids, true);
+    }
+    /// Record pictures that have been removed from a group (the ones that were in it).
+    pub fn record_removed(&mut self, group_id: i32, picture_ids: &[i64]) {
+        self.record(group_id, picture_ids, false);
+    }
+    fn record(&mut self, group_id: i32, picture_ids: &[i64], added: bool) {
+        for picture_id in picture_ids {
+            let groups = self.map.entry(*picture_id).or_default();
+            match groups.get(&group_id) {
+                // Opposite change: the picture is back to its initial state for this group
+                Some(previous) if *previous != added => {
+                    groups.remove(&group_id);
+                }
+                _ => {
+                    groups.insert(group_id, added);
+                }
+            }
+        }
+    }
+
+    /// Report the progress of the grouping of the user's arrangements (see [`grouping_transaction_reporting`]).
+    /// Previews and the propagation to the recipients of shared groups don't report theirs.
+    pub fn report_progress(&mut self, user_id: i32) {
+        self.progress_user_id = Some(user_id);
+    }
+    /// Reporter of the progress of a grouping of `total` arrangements of the user, recording it only if the progress of the user is reported.
+    /// The progress is taken until [`GroupingDelta::restore_progress`], so that the groupings nested in this one,
+    /// propagating its changes to the recipients of shared groups, don't report theirs.
+    pub fn take_progress(&mut self, user_id: i32, total: usize) -> GroupingProgressReporter {
+        if self.progress_user_id != Some(user_id) {
+            return GroupingProgressReporter::disabled(user_id);
+        }
+        self.progress_user_id = None;
+        GroupingProgressReporter::new(user_id, total)
+    }
+    /// Give back the reporter taken by [`GroupingDelta::take_progress`], keeping its events,
+    /// so that the next groupings of the operation report their progress again.
+    pub fn restore_progress(&mut self, progress: GroupingProgressReporter) {
+        if progress.enabled {
+            self.progress_user_id = Some(progress.user_id);
+            self.progress.extend(progress.events);
+        }
+    }
+    /// Progress events recorded so far, in the order the arrangements have been grouped.
+    pub fn progress(&self) -> &[GroupingProgress] {
+        &self.progress
+    }
+
+    /// Net changes, one event per picture whose groups changed, sorted by picture id.
+    pub fn events(&self) -> Vec<PictureGroupsEvent> {
+        self.map
+            .iter()
+            .filter(|(_, groups)| !groups.is_empty())
+            .sorted_by_key(|(picture_id, _)| **picture_id)
+            .map(|(picture_id, groups)| PictureGroupsEvent {
+                picture_id: *picture_id,
+                added_groups: groups.iter().filter(|(_, added)| **added).map(|(group_id, _)| *group_id).collect(),
+                removed_groups: groups.iter().filter(|(_, added)| !**added).map(|(group_id, _)| *group_id).collect(),
+            })
+            .collect()
+    }
+
+    /// Net changes counted per group, keeping only the groups of `group_ids` that existed before the operation,
+    /// and the groups of `new_groups` (group id -> name) that it created.
+    pub fn preview(&self, group_ids: &HashSet<i32>, new_groups: &HashMap<i32, String>) -> GroupingPreview {
+        let mut preview = GroupingPreview::default();
+        for groups in self.map.values() {
+            for (group_id, added) in groups.iter() {
+                if group_ids.contains(group_id) {
+                    let counts = if *added { &mut preview.would_add } else { &mut preview.would_remove };
+                    *counts.entry(*group_id).or_default() += 1;
+                } else if let (Some(name), true) = (new_groups.get(group_id), added) {
+                    *preview.new_groups_would_add.entry(name.clone()).or_default() += 1;
+                }
+            }
+        }
+        preview
+    }
+
+    /// Publish the recorded progress to the subscribers of the reported user. Must be called once the grouping operation is committed.
+    pub fn publish_progress(&mut self, channels: &GroupingProgressChannels, user_id: i32) {
+        for progress in self.progress.drain(..) {
+            channels.send(user_id, progress);
+        }
+    }
+
+    /// Publish the net changes on `events_channel` ([`GROUPING_EVENTS`] outside of tests). Must be called once the grouping operation is committed.
+    pub fn emit(self, events_channel: &broadcast::Sender<Vec<PictureGroupsEvent>>) {
+        let events = self.events();
+        if events.is_empty() {
+            return;
+        }
+        debug!("Emitting group membership changes of {} pictures", events.len());
+        // Sending only fails when there is no subscriber
+        let _ = events_channel.send(events);
+    }
+}
+
+/// Run a grouping operation in a transaction, then emit its net group membership changes on [`GROUPING_EVENTS`]
+/// if the transaction has been committed.
+pub fn grouping_transaction<T, F>(conn: &mut DBConn, f: F) -> Result<T, ErrorResponder>
+where
+    F: FnOnce(&mut DBConn, &mut GroupingDelta) -> Result<T, ErrorResponder>,
+{
+    grouping_transaction_emitting(conn, &GROUPING_EVENTS, None, f)
+}
+
+/// Same as [`grouping_transaction`], also publishing the progress of the grouping of the user's arrangements on `progress_channels`
+/// once the transaction has been committed: one event per grouped arrangement.
+pub fn grouping_transaction_reporting<T, F>(
+    conn: &mut DBConn,
+    progress_channels: &GroupingProgressChannels,
+    user_id: i32,
+    f: F,
+) -> Result<T, ErrorResponder>
+where
+    F: FnOnce(&mut DBConn, &mut GroupingDelta) -> Result<T, ErrorResponder>,
+{
+    grouping_transaction_emitting(conn, &GROUPING_EVENTS, Some((progress_channels, user_id)), f)
+}
+
+/// Same as [`grouping_transaction`], emitting the net group membership changes on `events_channel`,
+/// and the progress of the user on the given channels if any (see [`grouping_transaction_reporting`]).
+pub fn grouping_transaction_emitting<T, F>(
+    conn: &mut DBConn,
+    events_channel: &broadcast::Sender<Vec<PictureGroupsEvent>>,
+    progress: Option<(&GroupingProgressChannels, i32)>,
+    f: F,
+) -> Result<T, ErrorResponder>
+where
+    F: FnOnce(&mut DBConn, &mut GroupingDelta) -> Result<T, ErrorResponder>,
+{
+    let mut delta = GroupingDelta::new();
+    if let Some((_, user_id)) = progress {
+        delta.report_progress(user_id);
+    }
+    let result = err_transaction(conn, |conn| f(conn, &mut delta));
+    match &result {
+        Err(err) if err.do_rollback() => {}
+        _ => {
+            if let Some((progress_channels, user_id)) = progress {
+                delta.publish_progress(progress_channels, user_id);
+            }
+            delta.emit(events_channel)
+        }
+    }
+    result
+}
+
+/// Run a grouping operation in a transaction that is always rolled back, returning its result and its net group membership changes.
+/// Nothing is persisted nor emitted.
+pub fn preview_transaction<T, F>(conn: &mut DBConn, f: F) -> Result<(T, GroupingDelta), ErrorResponder>
+where
+    F: FnOnce(&mut DBConn, &mut GroupingDelta) -> Result<T, ErrorResponder>,
+{
+    let mut delta = GroupingDelta::new();
+    <DBConn as Connection>::TransactionManager::begin_transaction(conn)
+        .map_err(|e| ErrorType::DatabaseError("Failed to begin transaction".to_string(), e).res())?;
+    let result = f(conn, &mut delta);
+    <DBConn as Connection>::TransactionManager::rollback_transaction(conn)
+        .map_err(|e| ErrorType::DatabaseError("Failed to rollback transaction".to_string(), e).res())?;
+    result.map(|result| (result, delta))
+}
+
+/// Take the grouping lock of the user until the end of the current transaction, waiting for any other grouping operation holding it.
+/// Operations that must not interleave with another grouping of the same user (e.g. a full regrouping) take this lock.
+pub fn lock_user_grouping(conn: &mut DBConn, user_id: i32) -> Result<(), ErrorResponder> {
+    lock_user_grouping_statement(user_id)
+        .execute(conn)
+        .map(|_| ())
+        .map_err(|e| ErrorType::DatabaseError("Failed to lock the user grouping".to_string(), e).res())
+}
+pub fn lock_user_grouping_statement(user_id: i32) -> impl QueryFragment<Pg> + ExecuteDsl<DBConn> + RunQueryDsl<DBConn> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1, $2)")
+        .bind::<Integer, _>(GROUPING_LOCK_CLASS)
+        .bind::<Integer, _>(user_id)
+}
@@ -1,13 +1,18 @@
 use crate::database::database::DBConn;
 use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType, ArrangementDetails};
 use crate::database::group::group::Group;
+use crate::database::group::manual_override::{ForcedMemberships, ManualOverride};
 use crate::database::group::shared_group::SharedGroup;
 use crate::database::picture::picture::Picture;
 use crate::database::picture::picture_tag::PictureTag;
 use crate::database::tag::tag::Tag;
+use crate::database::user::user::User;
+use crate::grouping::filter_cache::FilterCache;
+use crate::grouping::grouping_delta::{lock_user_grouping, GroupingDelta};
 use crate::grouping::strategy_filtering::FilterType;
 use crate::grouping::strategy_grouping::{StrategyGrouping, StrategyGroupingTrait, UngroupRecord};
 use crate::grouping::topological_sorts::{topological_sort, topological_sort_filtered, topological_sort_from};
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use itertools::Itertools;
 use rocket::yansi::Paint;
@@ -40,13 +45,58 @@ use std::collections::{HashMap, HashSet};
 // | Edit   arrangement | All      | Edited + Dependants
 // | Add/Edit  pictures | Edited   | All
 
+/// Select the arrangements in which pictures must be grouped, sorted in topological order.
+/// Disabled arrangements are skipped: they keep their groups as they are until they are enabled again.
+/// See [`group_pictures`] for the filters.
+pub fn select_arrangements_to_group(
+    mut arrangements: Vec<ArrangementDetails>,
+    user_id: i32,
+    arrangement_id_filter: Option<i32>,
+    dependency_type_filter: Option<&ArrangementDependencyType>,
+) -> Result<Vec<ArrangementDetails>, ErrorResponder> {
+    if arrangement_id_filter.is_some() && dependency_type_filter.is_some() {
+        return Err(ErrorType::InvalidInput("Cannot filter by arrangement id and dependency type at the same time".to_string()).res());
+    }
+
+    Ok(if let Some(arrangement_id) = arrangement_id_filter {
+        let origin_arrangement = arrangements
+            .iter()
+            .find(|arrangement| arrangement.arrangement.id == arrangement_id)
+            .ok_or(
+                ErrorType::InvalidInput(format!("Arrangement of ID {} is not an arrangement of the user {}", arrangement_id, user_id).to_string())
+                    .res(),
+            )?
+            .clone();
+        if !origin_arrangement.arrangement.enabled {
+            return Ok(vec![]);
+        }
+
+        arrangements.retain(|arrangement| {
+            arrangement.arrangement.enabled && (arrangement.arrangement.groups_dependant || arrangement_id == arrangement.arrangement.id)
+        });
+        topological_sort_from(arrangements, &origin_arrangement)
+    } else {
+        arrangements.retain(|arrangement| arrangement.arrangement.enabled);
+        if let Some(dependency_type) = dependency_type_filter {
+            topological_sort_filtered(arrangements, dependency_type)
+        } else {
+            topological_sort(arrangements)
+        }
+    })
+}
+
 /// Group pictures into arrangements’ groups.
 /// If `do_ungroup` is true, pictures that do not match the arrangement filter will be ungrouped(, but `picture_ids_filter` must be provided not true at the moment due to arrangement editing.).
 /// If `arrangement_id_filter` is provided, only pictures from this arrangement will be grouped.
 /// If `dependency_type_filter` is provided, only pictures from arrangements of this dependency type or its dependant arrangements will be grouped.
 /// `arrangement_id_filter` and `dependency_type_filter` cannot be used at the same time.
+/// The manual overrides of the groups win over the strategy (see [`ManualOverride`]): they are loaded before it runs,
+/// so that a forced out picture is never added to its group, and forced in pictures are added and kept once it is done.
+/// Group membership changes are recorded in `delta`, to be emitted once the whole operation is done.
+/// The progress is recorded after each arrangement if the endpoint asked for it (see [`GroupingDelta::report_progress`]), to be published once committed.
 pub fn group_pictures(
     conn: &mut DBConn,
+    delta: &mut GroupingDelta,
     user_id: i32,
     picture_ids_filter: Option<&Vec<i64>>,
     arrangement_id_filter: Option<i32>,
@@ -60,40 +110,24 @@ pub fn group_pictures(
     );
 
     // Fetch all not manual arrangements and the list of their group ids
-    let mut arrangements = Arrangement::list_arrangements_and_groups(conn, user_id)?;
+    let arrangements = Arrangement::list_arrangements_and_groups(conn, user_id)?;
 
-    if arrangement_id_filter.is_some() && dependency_type_filter.is_some() {
-        return Err(ErrorType::InvalidInput("Cannot filter by arrangement id and dependency type at the same time".to_string()).res());
-    }
     if do_ungroup && picture_ids_filter.is_none() {
         // No optimization developed for when editing arrangements. Editing arrangements work like if all pictures were edited for now.
         //return Err(ErrorType::InvalidInput("Cannot ungroup without a list of picture ids".to_string()).res());
     }
 
     // Filter arrangements if needed
-    arrangements = if let Some(arrangement_id) = arrangement_id_filter {
-        let origin_arrangement = arrangements
-            .iter()
-            .find(|arrangement| arrangement.arrangement.id == arrangement_id)
-            .ok_or(
-                ErrorType::InvalidInput(format!("Arrangement of ID {} is not an arrangement of the user {}", arrangement_id, user_id).to_string())
-                    .res(),
-            )?
-            .clone();
-
-        arrangements.retain(|arrangement| arrangement.arrangement.groups_dependant || arrangement_id == arrangement.arrangement.id);
-        topological_sort_from(arrangements, &origin_arrangement)
-    } else if let Some(dependency_type) = dependency_type_filter {
-        topological_sort_filtered(arrangements, dependency_type)
-    } else {
-        topological_sort(arrangements)
-    };
+    let mut arrangements = select_arrangements_to_group(arrangements, user_id, arrangement_id_filter, dependency_type_filter)?;
 
     let mut ungroup_record = UngroupRecord::new(do_ungroup);
+    // Evaluate the filters from the pictures tags and groups when possible, and reuse the results of identical filters
+    let mut filter_cache = FilterCache::load(conn, picture_ids_filter)?;
+    let mut progress = delta.take_progress(user_id, arrangements.len());
 
     for arrangement in arrangements.iter_mut() {
         // Keep only pictures that match this arrangement
-        let pictures_ids: HashSet<i64> = HashSet::from_iter(arrangement.strategy.filter.filter_pictures(conn, picture_ids_filter)?.into_iter());
+        let pictures_ids = filter_cache.filter_pictures(conn, &arrangement.strategy.filter)?;
 
         info!(
             "Grouping {} pictures into arrangement {} of user {}",
@@ -103,26 +137,43 @@ pub fn group_pictures(
         );
         debug!("  Pictures ids: {:?}", pictures_ids);
 
+        // Manual overrides win over the strategy: forced out pictures are not added, forced in pictures are kept or added
+        let override_group_ids = arrangement.strategy.groupings.get_groups();
+        let forced = if override_group_ids.is_empty() {
+            ForcedMemberships::default()
+        } else {
+            ManualOverride::forced_memberships(&ManualOverride::from_group_ids(conn, user_id, &override_group_ids, picture_ids_filter)?)
+        };
+
         // Add pictures to groups
         let mut update_strategy = false;
         let a_id = arrangement.arrangement.id;
         let preserve_unicity = arrangement.strategy.preserve_unicity;
         match &mut arrangement.strategy.groupings {
             StrategyGrouping::GroupByFilter(filter_grouping) => {
-                update_strategy |= filter_grouping.group_pictures(conn, a_id, preserve_unicity, &mut ungroup_record, &pictures_ids)?;
+                update_strategy |=
+                    filter_grouping.group_pictures(conn, delta, a_id, preserve_unicity, &mut ungroup_record, &forced, &pictures_ids)?;
             }
             StrategyGrouping::GroupByTags(tag_grouping) => {
-                update_strategy |= tag_grouping.group_pictures(conn, a_id, preserve_unicity, &mut ungroup_record, &pictures_ids)?;
+                update_strategy |= tag_grouping.group_pictures(conn, delta, a_id, preserve_unicity, &mut ungroup_record, &forced, &pictures_ids)?;
+            }
+            StrategyGrouping::GroupByExifValues(exif_grouping) => {
+                update_strategy |= exif_grouping.group_pictures(conn, delta, a_id, preserve_unicity, &mut ungroup_record, &forced, &pictures_ids)?;
             }
-            StrategyGrouping::GroupByExifValues(e) => {}
             StrategyGrouping::GroupByExifInterval(e) => {}
             StrategyGrouping::GroupByLocation(l) => {}
+            StrategyGrouping::GroupByRating(rating_grouping) => {
+                update_strategy |=
+                    rating_grouping.group_pictures(conn, delta, a_id, preserve_unicity, &mut ungroup_record, &forced, &pictures_ids)?;
+            }
         }
 
         if update_strategy {
             let strategy = arrangement.strategy.clone();
             arrangement.arrangement.set_strategy(conn, Some(strategy))?;
         }
+        let group_ids = arrangement.strategy.groupings.get_groups();
+        filter_cache.groups_changed(&group_ids, &pictures_ids);
 
         if do_ungroup {
             info!(
@@ -130,41 +181,80 @@ pub fn group_pictures(
                 arrangement.arrangement.id, user_id
             );
             // Pictures that do not match the arrangement filter, but that are in a group of this arrangement.
-            let group_ids = arrangement.strategy.groupings.get_groups().clone();
-            let ungroup_pictures_ids = arrangement
+            let ungroup_filter = arrangement
                 .strategy
                 .filter
                 .clone()
                 .not()
-                .and(FilterType::IncludeGroups(group_ids.clone()).to_strategy())
-                .filter_pictures(conn, picture_ids_filter)?;
+                .and(FilterType::IncludeGroups(group_ids.clone()).to_strategy());
+            let mut ungroup_pictures_ids_set = filter_cache.filter_pictures(conn, &ungroup_filter)?;
+            // Pictures in the trash match no filter, not even the ungroup one
+            ungroup_pictures_ids_set.extend(filter_cache.deleted_pictures(conn, &group_ids)?);
+            filter_cache.groups_changed(&group_ids, &ungroup_pictures_ids_set);
 
-            let ungroup_pictures_ids_set = HashSet::from_iter(ungroup_pictures_ids.into_iter());
-            group_ids.into_iter().for_each(|group_id| {
-                ungroup_record.add(group_id, ungroup_pictures_ids_set.clone());
+            group_ids.iter().for_each(|group_id| {
+                ungroup_record.add(*group_id, ungroup_pictures_ids_set.clone());
             });
+        }
+
+        forced.keep_forced_in(&mut ungroup_record);
+        filter_cache.groups_changed(&group_ids, &forced.picture_ids());
 
+        if do_ungroup {
             // Ungroup all records
             ungroup_record
                 .map
                 .into_iter()
-                .try_for_each(|(group_id, picture_ids)| group_remove_pictures(conn, group_id, &picture_ids.into_iter().collect_vec()))?;
+                .try_for_each(|(group_id, picture_ids)| group_remove_pictures(conn, delta, group_id, &picture_ids.into_iter().collect_vec()))?;
             ungroup_record = UngroupRecord::new(do_ungroup);
         }
+        for (group_id, picture_ids) in forced.add {
+            group_add_pictures(conn, delta, group_id, &picture_ids)?;
+        }
+        // Forced out pictures the group held before the override
+        for (group_id, picture_ids) in forced.remove {
+            group_remove_pictures(conn, delta, group_id, &picture_ids)?;
+        }
+        progress.arrangement_grouped(arrangement.arrangement.id);
     }
+    delta.restore_progress(progress);
+    debug!(
+        "Grouped pictures into {} arrangements with {} filter queries",
+        arrangements.len(),
+        filter_cache.queries_count
+    );
 
     Ok(())
 }
 
+/// Recompute the groups of all the enabled arrangements of the user, manual arrangements excepted:
+/// all the pictures are grouped again in topological order, and the pictures that no longer match a group are removed from it.
+/// Only the memberships that actually change are touched, so the recipients of shared groups only lose the pictures that left them.
+/// Takes the user grouping lock, so that two regroupings of the same user don't interleave. Returns the ids of the regrouped arrangements.
+pub fn regroup_all(conn: &mut DBConn, delta: &mut GroupingDelta, user_id: i32) -> Result<Vec<i32>, ErrorResponder> {
+    lock_user_grouping(conn, user_id)?;
+    let arrangement_ids = Arrangement::list_arrangements_and_groups(conn, user_id)?
+        .into_iter()
+        .filter(|arrangement| arrangement.arrangement.enabled)
+        .map(|arrangement| arrangement.arrangement.id)
+        .collect_vec();
+    info!("Regrouping the {} arrangements of user {}", arrangement_ids.len(), user_id);
+
+    group_pictures(conn, delta, user_id, None, None, None, true)?;
+    Ok(arrangement_ids)
+}
+
 /// Add pictures to a group and then check for each user to which the group is shared:
 /// - For the pictures the user gained access to:
 ///   - Add the defaults tags to these pictures.
 ///   - Group them in his context.
-/// - If share match conversion is enabled, apply it to all pictures.
-pub fn group_add_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i64>) -> Result<(), ErrorResponder> {
+/// - If share match conversion is enabled, apply it to the added pictures.
+///
+/// Pictures already in the group are ignored. Returns the ids of the pictures that were actually added.
+pub fn group_add_pictures(conn: &mut DBConn, delta: &mut GroupingDelta, group_id: i32, picture_ids: &Vec<i64>) -> Result<Vec<i64>, ErrorResponder> {
     debug!("  Adding {} pictures to group {}, (ids: {:?})", picture_ids.len(), group_id, picture_ids);
     if picture_ids.len() == 0 {
-        return Ok(());
+        return Ok(vec![]);
     }
     let shared_groups = SharedGroup::from_group_id(conn, group_id)?;
 
@@ -178,16 +268,22 @@ pub fn group_add_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i6
         }
         users_accessible_pictures.insert(shared_group.user_id, accessible_pictures);
     }
+    let storage_snapshot = CopiedStorageSnapshot::take(conn, &shared_groups, picture_ids, &[])?;
 
-    let added_pictures: HashSet<i64> = HashSet::from_iter(Group::add_pictures(conn, group_id, picture_ids)?);
+    let added_picture_ids = Group::add_pictures(conn, group_id, picture_ids)?;
+    if added_picture_ids.is_empty() {
+        // Share match conversion only applies to the added pictures: with none, there is nothing left to do.
+        return Ok(added_picture_ids);
+    }
+    delta.record_added(group_id, &added_picture_ids);
+    storage_snapshot.update_storage(conn)?;
 
     for shared_group in shared_groups {
         let empty_hashset = HashSet::new();
         let accessible_pictures = users_accessible_pictures.get(&shared_group.user_id).unwrap_or(&empty_hashset);
 
         // Group new pictures on which the user gained access to.
-        let gained_access_pictures = added_pictures.difference(accessible_pictures);
-        let gained_access_pictures = Vec::from_iter(gained_access_pictures.into_iter().cloned());
+        let gained_access_pictures = gained_access_pictures(&added_picture_ids, accessible_pictures);
 
         // Even if the picture is newly accessible, it can already have tags from the time it was accessible.
         // Then, we are adding defaults tags only to pictures that have no tag from the group.
@@ -198,7 +294,7 @@ pub fn group_add_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i6
             gained_access_pictures.len(),
             shared_group.user_id
         );
-        group_pictures(conn, shared_group.user_id, Some(&gained_access_pictures), None, None, false)?;
+        group_pictures(conn, delta, shared_group.user_id, Some(&gained_access_pictures), None, None, false)?;
 
         // Applying share match conversion if enabled.
         if let Some(smc_group_id) = shared_group.match_conversion_group_id {
@@ -206,11 +302,22 @@ pub fn group_add_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i6
         }
     }
 
-    Ok(())
+    Ok(added_picture_ids)
+}
+
+/// Pictures added to a group a recipient couldn't access before, sorted by id.
+pub fn gained_access_pictures(added_picture_ids: &[i64], accessible_pictures: &HashSet<i64>) -> Vec<i64> {
+    added_picture_ids
+        .iter()
+        .filter(|picture_id| !accessible_pictures.contains(picture_id))
+        .cloned()
+        .sorted()
+        .dedup()
+        .collect()
 }
 
 /// Remove the pictures from the group, and remove them from all groups of users who lost access to them.
-pub fn group_remove_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec<i64>) -> Result<(), ErrorResponder> {
+pub fn group_remove_pictures(conn: &mut DBConn, delta: &mut GroupingDelta, group_id: i32, picture_ids: &Vec<i64>) -> Result<(), ErrorResponder> {
     debug!(
         "  Removing {} pictures from group {}, (ids: {:?})",
         picture_ids.len(),
@@ -220,27 +327,40 @@ pub fn group_remove_pictures(conn: &mut DBConn, group_id: i32, picture_ids: &Vec
     if picture_ids.len() == 0 {
         return Ok(());
     }
+    let shared_groups = SharedGroup::from_group_id(conn, group_id)?;
+    let storage_snapshot = CopiedStorageSnapshot::take(conn, &shared_groups, picture_ids, &[])?;
     let removed_pictures = Group::remove_pictures(conn, group_id, &picture_ids)?;
     if removed_pictures.len() == 0 {
         return Ok(());
     }
-    group_manage_removed_pictures(conn, group_id, removed_pictures)
+    delta.record_removed(group_id, &removed_pictures);
+    storage_snapshot.update_storage(conn)?;
+    group_manage_removed_pictures(conn, delta, &shared_groups, removed_pictures)
 }
 
 /// Remove all the pictures of the group, and remove them from all groups of users who lost access to them.
-pub fn group_clear_pictures(conn: &mut DBConn, group_id: i32) -> Result<(), ErrorResponder> {
+pub fn group_clear_pictures(conn: &mut DBConn, delta: &mut GroupingDelta, group_id: i32) -> Result<(), ErrorResponder> {
     debug!("  Removing all pictures from group {}", group_id);
+    let shared_groups = SharedGroup::from_group_id(conn, group_id)?;
+    let picture_ids = Group::pictures_from_group_ids(conn, &vec![group_id])?;
+    let storage_snapshot = CopiedStorageSnapshot::take(conn, &shared_groups, &picture_ids, &[])?;
     let removed_pictures = Group::clear_and_get_pictures(conn, group_id)?;
     if removed_pictures.len() == 0 {
         return Ok(());
     }
-    group_manage_removed_pictures(conn, group_id, removed_pictures)
+    delta.record_removed(group_id, &removed_pictures);
+    storage_snapshot.update_storage(conn)?;
+    group_manage_removed_pictures(conn, delta, &shared_groups, removed_pictures)
 }
 /// Propagate the removal of the pictures to all groups of users who lost access to them.
-fn group_manage_removed_pictures(conn: &mut DBConn, group_id: i32, removed_pictures: Vec<i64>) -> Result<(), ErrorResponder> {
-    let shared_groups = SharedGroup::from_group_id(conn, group_id)?;
+fn group_manage_removed_pictures(
+    conn: &mut DBConn,
+    delta: &mut GroupingDelta,
+    shared_groups: &[SharedGroup],
+    removed_pictures: Vec<i64>,
+) -> Result<(), ErrorResponder> {
     for shared_group in shared_groups.iter() {
-        let unaccessible_pictures = Picture::filter_user_accessible_pictures(conn, shared_group.user_id, &removed_pictures)?;
+        let unaccessible_pictures = Picture::filter_user_unaccessible_pictures(conn, shared_group.user_id, &removed_pictures)?;
 
         debug!(
             "  Propagating {} removed pictures to user {}",
@@ -250,7 +370,70 @@ fn group_manage_removed_pictures(conn: &mut DBConn, group_id: i32, removed_pictu
         // Delete pictures from user groups
         Group::from_user_id_all(conn, shared_group.user_id)?
             .into_iter()
-            .try_for_each(|group| group_remove_pictures(conn, group.id, &unaccessible_pictures))?;
+            .try_for_each(|group| group_remove_pictures(conn, delta, group.id, &unaccessible_pictures))?;
     }
     Ok(())
 }
+
+/// Pictures counted in the storage of the recipients of copied shares, taken before a change of the pictures of the shared groups.
+/// A picture reachable through several copied shares of a recipient is counted once (see [`IntegrityReport::pictures_sizes_ko`]),
+/// so once the change is done, recipients are only charged for the pictures entering their storage and refunded for the ones leaving it.
+///
+/// [`IntegrityReport::pictures_sizes_ko`]: crate::database::integrity_scan::IntegrityReport::pictures_sizes_ko
+pub struct CopiedStorageSnapshot {
+    picture_ids: Vec<i64>,
+    before: HashMap<i32, HashSet<i64>>, // recipient user_id -> pictures counted in their storage
+}
+
+impl CopiedStorageSnapshot {
+    /// Snapshot of the pictures of the list counted in the storage of the recipients of the shares that count toward it
+    /// (see `COPIED_SHARES_COUNT_STORAGE`). The shares of `excluded_group_ids` are ignored, as if they were not confirmed yet.
+    pub fn take(conn: &mut DBConn, shared_groups: &[SharedGroup], picture_ids: &[i64], excluded_group_ids: &[i32]) -> Result<Self, ErrorResponder> {
+        let mut before = HashMap::new();
+        let recipient_ids = shared_groups
+            .iter()
+            .filter(|shared_group| shared_group.counts_toward_recipient_storage(CONFIG.copied_shares_count_storage))
+            .map(|shared_group| shared_group.user_id)
+            .unique()
+            .collect_vec();
+        for user_id in recipient_ids {
+            let counted = Picture::filter_copied_storage_pictures(conn, user_id, picture_ids, excluded_group_ids)?;
+            before.insert(user_id, HashSet::from_iter(counted));
+        }
+        Ok(CopiedStorageSnapshot {
+            picture_ids: picture_ids.to_vec(),
+            before,
+        })
+    }
+
+    /// Charge or refund the recipients for the pictures that entered or left their storage since the snapshot.
+    pub fn update_storage(self, conn: &mut DBConn) -> Result<(), ErrorResponder> {
+        for (user_id, before) in self.before {
+            let after = HashSet::from_iter(Picture::filter_copied_storage_pictures(conn, user_id, &self.picture_ids, &[])?);
+            let (charged, refunded) = storage_changes(&before, &after);
+            let delta_ko = Picture::sum_size_ko(conn, &charged)? - Picture::sum_size_ko(conn, &refunded)?;
+            User::add_storage_count(conn, user_id, delta_ko)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pictures entering (first) and leaving (second) a storage, from the pictures counted in it before and after a change, sorted by id.
+pub fn storage_changes(before: &HashSet<i64>, after: &HashSet<i64>) -> (Vec<i64>, Vec<i64>) {
+    (
+        after.difference(before).cloned().sorted().collect(),
+        before.difference(after).cloned().sorted().collect(),
+    )
+}
+
+/// Remove the pictures the user can no longer access from all his groups.
+pub fn ungroup_unaccessible_pictures(conn: &mut DBConn, delta: &mut GroupingDelta, user_id: i32, picture_ids: &[i64]) -> Result<(), ErrorResponder> {
+    let unaccessible_pictures = Picture::filter_user_unaccessible_pictures(conn, user_id, picture_ids)?;
+    if unaccessible_pictures.is_empty() {
+        return Ok(());
+    }
+    debug!("  Ungrouping {} pictures user {} lost access to", unaccessible_pictures.len(), user_id);
+    Group::from_user_id_all(conn, user_id)?
+        .into_iter()
+        .try_for_each(|group| group_remove_pictures(conn, delta, group.id, &unaccessible_pictures))
+}
@@ -1,11 +1,12 @@
 use crate::database::database::DBConn;
-use crate::database::schema::{pictures_tags, PictureOrientation};
+use crate::database::schema::{pictures, pictures_tags, PictureOrientation};
 use crate::grouping::arrangement_strategy::ExifDataTypeValue;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use diesel::dsl::{exists, not};
+use diesel::internal::table_macro::{BoxedSelectStatement, FromClause};
 use diesel::pg::Pg;
 use diesel::prelude::*;
-use diesel::sql_types::Bool;
+use diesel::sql_types::{BigInt, Bool};
 use diesel::QueryDsl;
 use diesel::{BoxableExpression, ExpressionMethods};
 use diesel::{Queryable, Selectable};
@@ -21,19 +22,34 @@ pub enum StrategyFiltering {
     Filter(FilterType),
 }
 
+/// Tags and groups of a picture, known before grouping it (see [`StrategyFiltering::evaluate`]).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KnownPicture {
+    pub tag_ids: HashSet<i32>,
+    pub group_ids: HashSet<i32>,
+    /// Groups the picture may have been added to or removed from since `group_ids` was loaded
+    pub changed_group_ids: HashSet<i32>,
+    /// The picture is in the trash: it matches no filter
+    pub deleted: bool,
+}
+
 type BoxedExpr = Box<dyn BoxableExpression<crate::database::schema::pictures::table, Pg, SqlType = Bool>>;
 impl StrategyFiltering {
+    /// Pictures matching the filter, among `picture_ids` if provided. Pictures in the trash never match.
     pub fn filter_pictures(&self, conn: &mut DBConn, picture_ids: Option<&Vec<i64>>) -> Result<Vec<i64>, ErrorResponder> {
-        use crate::database::schema::*;
+        self.filter_pictures_statement(picture_ids)
+            .load(conn)
+            .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+    }
+    pub fn filter_pictures_statement(
+        &self,
+        picture_ids: Option<&Vec<i64>>,
+    ) -> BoxedSelectStatement<'static, BigInt, FromClause<pictures::table>, Pg> {
+        let mut statement = pictures::table.select(pictures::id).filter(pictures::deleted_date.is_null()).into_boxed();
         if let Some(picture_ids) = picture_ids {
-            pictures::table.filter(pictures::id.eq_any(picture_ids)).into_boxed()
-        } else {
-            pictures::table.into_boxed()
+            statement = statement.filter(pictures::id.eq_any(picture_ids.clone()));
         }
-        .filter(self.as_diesel_predicate())
-        .select(pictures::id)
-        .load(conn)
-        .map_err(|e| ErrorType::DatabaseError(e.to_string(), e).res())
+        statement.filter(self.as_diesel_predicate())
     }
     pub fn as_diesel_predicate(&self) -> BoxedExpr {
         let always_true = crate::database::schema::pictures::id.is_not_null();
@@ -65,6 +81,54 @@ impl StrategyFiltering {
         }
     }
 
+    /// Evaluate the filter on a picture from its known tags and groups, without querying the database.
+    /// Returns `None` when the result can't be known this way (EXIF filters, or groups whose content changed).
+    pub fn evaluate(&self, picture: &KnownPicture) -> Option<bool> {
+        match self {
+            StrategyFiltering::Or(filters) => {
+                let mut result = Some(false);
+                for filter in filters.iter() {
+                    match filter.evaluate(picture) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => result = None,
+                    }
+                }
+                if filters.is_empty() {
+                    Some(true)
+                } else {
+                    result
+                }
+            }
+            StrategyFiltering::And(filters) => {
+                let mut result = Some(true);
+                for filter in filters.iter() {
+                    match filter.evaluate(picture) {
+                        Some(false) => return Some(false),
+                        Some(true) => {}
+                        None => result = None,
+                    }
+                }
+                result
+            }
+            StrategyFiltering::Not(filter) => filter.evaluate(picture).map(|result| !result),
+            StrategyFiltering::Filter(FilterType::IncludeTags(tags)) => Some(tags.iter().any(|tag_id| picture.tag_ids.contains(tag_id))),
+            StrategyFiltering::Filter(FilterType::IncludeGroups(groups)) => {
+                if groups
+                    .iter()
+                    .any(|group_id| picture.group_ids.contains(group_id) && !picture.changed_group_ids.contains(group_id))
+                {
+                    Some(true)
+                } else if groups.iter().any(|group_id| picture.changed_group_ids.contains(group_id)) {
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            StrategyFiltering::Filter(_) => None,
+        }
+    }
+
     pub fn and(self, other: StrategyFiltering) -> StrategyFiltering {
         StrategyFiltering::And(Box::new(vec![self, other]))
     }
@@ -106,6 +170,15 @@ impl StrategyFiltering {
         }
         dependant_arrangements
     }
+    pub fn get_dependant_tags(&self) -> Vec<i32> {
+        let mut dependant_tags = Vec::new();
+        for filter in self.get_all_filter_types().iter() {
+            if let FilterType::IncludeTags(tags) = filter {
+                dependant_tags.extend(tags.iter().cloned());
+            }
+        }
+        dependant_tags
+    }
     pub fn is_groups_dependant(&self) -> bool {
         self.get_all_filter_types().iter().any(|f| match f {
             FilterType::IncludeGroups(_) => true,
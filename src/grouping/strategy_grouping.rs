@@ -1,11 +1,14 @@
 use crate::api::groups::arrangement;
 use crate::database::database::DBConn;
 use crate::database::group::arrangement::{Arrangement, ArrangementDetails};
+use crate::database::group::manual_override::ForcedMemberships;
 use crate::grouping::group_by_exif_interval::ExifIntervalGrouping;
-use crate::grouping::group_by_exif_value::ExifValuesGrouping;
+use crate::grouping::group_by_exif_value::{ExifValuesGrouping, ExifValuesGroupingRequest};
 use crate::grouping::group_by_filter::{FilterGrouping, FilterGroupingRequest};
 use crate::grouping::group_by_location::LocationGrouping;
+use crate::grouping::group_by_rating::{RatingGrouping, RatingGroupingRequest};
 use crate::grouping::group_by_tag::{TagGrouping, TagGroupingRequest};
+use crate::grouping::grouping_delta::GroupingDelta;
 use crate::utils::errors_catcher::ErrorResponder;
 use enum_kinds::EnumKind;
 use rocket::http::ext::IntoCollection;
@@ -24,12 +27,15 @@ pub trait StrategyGroupingTrait {
 
     /// Returns true if the strategy has been edited.
     /// Set ungroup to true if the pictures from picture_ids that do not match a group should be checked for removal.
+    /// Pictures forced out of a group by a manual override must not be added to it (see [`ForcedMemberships::without_forced_out`]).
     fn group_pictures(
         &mut self,
         conn: &mut DBConn,
+        delta: &mut GroupingDelta,
         arrangement_id: i32,
         preserve_unicity: bool,
         ungroup_record: &mut UngroupRecord,
+        forced: &ForcedMemberships,
         picture_ids: &HashSet<i64>,
     ) -> Result<bool, ErrorResponder>;
 
@@ -70,6 +76,7 @@ pub enum StrategyGrouping {
     GroupByExifValues(ExifValuesGrouping),
     GroupByExifInterval(ExifIntervalGrouping),
     GroupByLocation(LocationGrouping),
+    GroupByRating(RatingGrouping),
 }
 
 impl StrategyGrouping {
@@ -77,9 +84,10 @@ impl StrategyGrouping {
         match self {
             StrategyGrouping::GroupByFilter(sg) => sg.get_groups(),
             StrategyGrouping::GroupByTags(sg) => sg.get_groups(),
-            StrategyGrouping::GroupByExifValues(sg) => todo!(),
+            StrategyGrouping::GroupByExifValues(sg) => sg.get_groups(),
             StrategyGrouping::GroupByExifInterval(sg) => todo!(),
             StrategyGrouping::GroupByLocation(sg) => todo!(),
+            StrategyGrouping::GroupByRating(sg) => sg.get_groups(),
         }
     }
     pub fn get_dependant_groups(&self) -> Vec<i32> {
@@ -114,12 +122,17 @@ impl StrategyGrouping {
             _ => false,
         }
     }
+    pub(crate) fn is_ratings_dependant(&self) -> bool {
+        matches!(self, StrategyGrouping::GroupByRating(_))
+    }
 
     pub fn delete(&self, conn: &mut DBConn, arrangement_id: i32) -> Result<(), ErrorResponder> {
         match self {
             StrategyGrouping::GroupByFilter(f) => f.delete(conn, arrangement_id),
             StrategyGrouping::GroupByTags(t) => t.delete(conn, arrangement_id),
-            StrategyGrouping::GroupByExifValues(_) | StrategyGrouping::GroupByExifInterval(_) | StrategyGrouping::GroupByLocation(_) => todo!(),
+            StrategyGrouping::GroupByExifValues(e) => e.delete(conn, arrangement_id),
+            StrategyGrouping::GroupByRating(r) => r.delete(conn, arrangement_id),
+            StrategyGrouping::GroupByExifInterval(_) | StrategyGrouping::GroupByLocation(_) => todo!(),
         }
     }
 
@@ -140,6 +153,16 @@ impl StrategyGrouping {
                 new.edit(conn, arrangement_id, req)?;
                 Ok(StrategyGrouping::GroupByTags(new))
             }
+            (StrategyGrouping::GroupByExifValues(old), StrategyGroupingRequest::GroupByExifValues(req)) => {
+                let mut new = old.clone();
+                new.edit(conn, arrangement_id, req)?;
+                Ok(StrategyGrouping::GroupByExifValues(new))
+            }
+            (StrategyGrouping::GroupByRating(old), StrategyGroupingRequest::GroupByRating(req)) => {
+                let mut new = old.clone();
+                new.edit(conn, arrangement_id, req)?;
+                Ok(StrategyGrouping::GroupByRating(new))
+            }
             _ => {
                 // Different types - delete old and create new
                 self.delete(conn, arrangement_id)?;
@@ -154,6 +177,8 @@ impl StrategyGrouping {
 pub enum StrategyGroupingRequest {
     GroupByFilter(FilterGroupingRequest),
     GroupByTags(TagGroupingRequest),
+    GroupByExifValues(ExifValuesGroupingRequest),
+    GroupByRating(RatingGroupingRequest),
 }
 
 impl StrategyGroupingRequest {
@@ -167,6 +192,14 @@ impl StrategyGroupingRequest {
                 let grouping = TagGrouping::create(conn, arrangement_id, request)?;
                 Ok(StrategyGrouping::GroupByTags(*grouping))
             }
+            StrategyGroupingRequest::GroupByExifValues(request) => {
+                let grouping = ExifValuesGrouping::create(conn, arrangement_id, request)?;
+                Ok(StrategyGrouping::GroupByExifValues(*grouping))
+            }
+            StrategyGroupingRequest::GroupByRating(request) => {
+                let grouping = RatingGrouping::create(conn, arrangement_id, request)?;
+                Ok(StrategyGrouping::GroupByRating(*grouping))
+            }
         }
     }
 }
@@ -25,6 +25,9 @@ pub fn create_arrangement_with_dependant_arrangements(id: i32, dependant_arrange
             groups_dependant: false,
             tags_dependant: false,
             exif_dependant: false,
+            ratings_dependant: false,
+            edition_version: 0,
+            enabled: true,
         },
         strategy: ArrangementStrategy {
             filter: FilterType::IncludeGroups(vec![1, 5]).to_strategy(),
@@ -51,6 +54,9 @@ pub fn create_arrangement_with_dependant_groups(id: i32, groups: Vec<i32>, depen
             groups_dependant: false,
             tags_dependant: false,
             exif_dependant: false,
+            ratings_dependant: false,
+            edition_version: 0,
+            enabled: true,
         },
         strategy: ArrangementStrategy {
             filter: FilterType::IncludeGroups(groups.clone()).to_strategy(),
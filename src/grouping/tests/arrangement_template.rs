@@ -0,0 +1,161 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::tag::tag::Tag;
+use crate::database::tag::tag_group::{TagGroup, TagGroupWithTags};
+use crate::database::tests::test_database::{arrangement, group, tag};
+use crate::grouping::arrangement_strategy::{ArrangementStrategy, ArrangementStrategyRequest};
+use crate::grouping::arrangement_template::{ArrangementTemplate, TemplateReferences};
+use crate::grouping::group_by_filter::{FilterGrouping, FilterGroupingRequest, FilterGroupingValueRequest};
+use crate::grouping::group_by_tag::TagGrouping;
+use crate::grouping::strategy_filtering::{FilterType, StrategyFiltering};
+use crate::grouping::strategy_grouping::{StrategyGrouping, StrategyGroupingRequest};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use std::collections::BTreeMap;
+
+fn tag_group(id: i32, name: &str, tags: &[(i32, &str)]) -> TagGroupWithTags {
+    TagGroupWithTags {
+        tag_group: TagGroup {
+            id: Some(id),
+            user_id: 0,
+            name: name.to_string(),
+            multiple: true,
+            required: false,
+        },
+        tags: tags
+            .iter()
+            .enumerate()
+            .map(|(position, (tag_id, tag_name))| Tag {
+                name: tag_name.to_string(),
+                position: position as i32,
+                ..tag(*tag_id, id)
+            })
+            .collect(),
+    }
+}
+fn named_group(id: i32, name: &str) -> Group {
+    Group {
+        name: name.to_string(),
+        ..group(id, 1)
+    }
+}
+fn strategy_arrangement(name: &str, strategy: ArrangementStrategy) -> Arrangement {
+    Arrangement {
+        name: name.to_string(),
+        strong_match_conversion: true,
+        strategy: Arrangement::strategy_to_binary(&Some(strategy)).unwrap(),
+        ..arrangement(1)
+    }
+}
+fn tags_filter(tag_ids: &[i32]) -> StrategyFiltering {
+    FilterType::IncludeTags(tag_ids.to_vec()).to_strategy()
+}
+fn groups_filter(group_ids: &[i32]) -> StrategyFiltering {
+    FilterType::IncludeGroups(group_ids.to_vec()).to_strategy()
+}
+
+/// Strategy of the "Trips" arrangement, grouping by filter in groups `home_group_id` and `work_group_id`,
+/// from the ids of the "Home" and "Work" tags and of the "Best" group of the "Favorites" arrangement.
+fn trips_strategy(home_tag_id: i32, work_tag_id: i32, best_group_id: i32, home_group_id: i32, work_group_id: i32) -> ArrangementStrategy {
+    ArrangementStrategy {
+        filter: tags_filter(&[home_tag_id, work_tag_id]).or(groups_filter(&[best_group_id])),
+        groupings: StrategyGrouping::GroupByFilter(FilterGrouping {
+            filters: vec![
+                (home_group_id, tags_filter(&[home_tag_id])),
+                (work_group_id, tags_filter(&[work_tag_id]).and(groups_filter(&[best_group_id]).not())),
+            ],
+            other_group_id: None,
+        }),
+        preserve_unicity: false,
+    }
+}
+
+#[test]
+pub fn test_imported_template_creates_an_equivalent_arrangement() {
+    let exporter = TemplateReferences {
+        tag_groups: vec![tag_group(3, "Places", &[(10, "Home"), (11, "Work")])],
+        groups: vec![
+            ("Favorites".to_string(), named_group(50, "Best")),
+            ("Trips".to_string(), named_group(60, "At home")),
+            ("Trips".to_string(), named_group(61, "At work")),
+        ],
+    };
+    let template = ArrangementTemplate::export(&strategy_arrangement("Trips", trips_strategy(10, 11, 50, 60, 61)), &exporter).unwrap();
+    assert_eq!(template.tag_groups.len(), 1);
+    assert_eq!(template.tag_groups[0].name, "Places");
+
+    // A fresh user, with the same names but other ids (tags created in another order)
+    let mut importer = TemplateReferences {
+        tag_groups: vec![tag_group(7, "Places", &[(20, "Work"), (21, "Home")])],
+        groups: vec![("Favorites".to_string(), named_group(90, "Best"))],
+    };
+    assert!(template.missing_tag_groups(&importer).is_empty());
+
+    let request = template.strategy_request(&importer).unwrap().unwrap();
+    assert_eq!(
+        request,
+        ArrangementStrategyRequest {
+            filter: tags_filter(&[21, 20]).or(groups_filter(&[90])),
+            groupings: StrategyGroupingRequest::GroupByFilter(FilterGroupingRequest {
+                filters: vec![
+                    FilterGroupingValueRequest {
+                        id: 0,
+                        name: "At home".to_string(),
+                        filter: tags_filter(&[21]),
+                    },
+                    FilterGroupingValueRequest {
+                        id: 0,
+                        name: "At work".to_string(),
+                        filter: tags_filter(&[20]).and(groups_filter(&[90]).not()),
+                    },
+                ],
+            }),
+            preserve_unicity: false,
+        }
+    );
+
+    // The arrangement created from the request (with its new groups 100 and 101) exports to the same strategy,
+    // only the order of the tags of the importer's tag group differs
+    importer.groups.push(("Trips".to_string(), named_group(100, "At home")));
+    importer.groups.push(("Trips".to_string(), named_group(101, "At work")));
+    let created = strategy_arrangement("Trips", trips_strategy(21, 20, 90, 100, 101));
+    let exported = ArrangementTemplate::export(&created, &importer).unwrap();
+    assert_eq!(exported.strategy, template.strategy);
+    assert_eq!(
+        (exported.name, exported.strong_match_conversion),
+        (template.name, template.strong_match_conversion)
+    );
+}
+
+#[test]
+pub fn test_template_references_must_be_resolved_by_name() {
+    let exporter = TemplateReferences {
+        tag_groups: vec![tag_group(3, "People", &[(10, "Alice")])],
+        groups: vec![],
+    };
+    let strategy = ArrangementStrategy {
+        filter: StrategyFiltering::And(Box::default()),
+        groupings: StrategyGrouping::GroupByTags(TagGrouping {
+            tag_group_id: 3,
+            tag_id_to_group_id: BTreeMap::from([(10, 40)]),
+            other_group_id: None,
+            group_names_format: "{}".to_string(),
+        }),
+        preserve_unicity: true,
+    };
+    let template = ArrangementTemplate::export(&strategy_arrangement("People", strategy), &exporter).unwrap();
+
+    // The tag group is missing: it must be created before the template can be imported
+    let importer = TemplateReferences::default();
+    assert_eq!(template.missing_tag_groups(&importer), vec![&template.tag_groups[0]]);
+    let error = ErrorResponse::from(template.strategy_request(&importer).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::UnprocessableEntity));
+
+    let created = template.tag_groups[0].to_tag_group_with_tags(2);
+    assert_eq!(created.tags[0].name, "Alice");
+    let importer = TemplateReferences {
+        tag_groups: vec![tag_group(8, "People", &[(30, "Alice")])],
+        groups: vec![],
+    };
+    let request = template.strategy_request(&importer).unwrap().unwrap();
+    assert!(matches!(request.groupings, StrategyGroupingRequest::GroupByTags(tag_grouping) if tag_grouping.tag_group_id == 8));
+}
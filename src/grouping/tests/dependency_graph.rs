@@ -0,0 +1,35 @@
+use crate::grouping::dependency_graph::{DependencyGraph, DependencyGraphEdge};
+use crate::grouping::tests::arrangement_sort_algorithms::create_arrangement_with_dependant_arrangements;
+
+fn edge(arrangement_id: i32, depends_on: i32) -> DependencyGraphEdge {
+    DependencyGraphEdge { arrangement_id, depends_on }
+}
+
+#[test]
+pub fn test_dependency_graph_edges() {
+    let graph = DependencyGraph::new(&[
+        create_arrangement_with_dependant_arrangements(3, vec![1, 2]),
+        create_arrangement_with_dependant_arrangements(1, vec![]),
+        create_arrangement_with_dependant_arrangements(2, vec![1, 1]),
+    ]);
+
+    assert_eq!(graph.nodes.iter().map(|node| node.arrangement_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(graph.edges, vec![edge(2, 1), edge(3, 1), edge(3, 2)]);
+    assert!(!graph.has_cycle);
+}
+
+#[test]
+pub fn test_dependency_graph_cycle_is_flagged() {
+    let graph = DependencyGraph::new(&[
+        create_arrangement_with_dependant_arrangements(1, vec![3]),
+        create_arrangement_with_dependant_arrangements(2, vec![1]),
+        create_arrangement_with_dependant_arrangements(3, vec![2]),
+        create_arrangement_with_dependant_arrangements(4, vec![]),
+    ]);
+    assert_eq!(graph.edges, vec![edge(1, 3), edge(2, 1), edge(3, 2)]);
+    assert!(graph.has_cycle);
+
+    // An arrangement depending on its own groups
+    let graph = DependencyGraph::new(&[create_arrangement_with_dependant_arrangements(1, vec![1])]);
+    assert!(graph.has_cycle);
+}
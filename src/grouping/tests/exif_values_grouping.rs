@@ -0,0 +1,120 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::schema::*;
+use crate::database::tests::test_database::{insert_picture_created_at, insert_user, test_connection};
+use crate::grouping::arrangement_strategy::ExifDataTypeValue;
+use crate::grouping::default_arrangements::{create_default_arrangements, default_arrangements_requests};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::group_pictures;
+use crate::grouping::strategy_grouping::StrategyGroupingRequest;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use std::collections::BTreeMap;
+
+#[test]
+pub fn test_format_dates_by_month() {
+    let date = |d: u32, m: u32| NaiveDate::from_ymd_opt(2024, m, d).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let dates = ExifDataTypeValue::CreationDate(vec![date(1, 3), date(31, 3), date(1, 4)]);
+
+    assert_eq!(dates.len(), 3);
+    assert_eq!(dates.format_value(0, "%Y-%m"), Some("2024-03".to_string()));
+    assert_eq!(dates.format_value(0, "%Y-%m"), dates.format_value(1, "%Y-%m"));
+    assert_ne!(dates.format_value(1, "%Y-%m"), dates.format_value(2, "%Y-%m"));
+    assert_eq!(dates.format_value(3, "%Y-%m"), None);
+    // Empty format falls back to the default representation
+    assert_eq!(dates.format_value(0, ""), Some("2024-03-01 12:00:00".to_string()));
+}
+
+#[test]
+pub fn test_format_and_append_values() {
+    let mut models = ExifDataTypeValue::CameraModel(vec!["X100V".to_string()]);
+    assert_eq!(models.format_value(0, "{}"), Some("X100V".to_string()));
+    assert_eq!(models.format_value(0, "Camera {}"), Some("Camera X100V".to_string()));
+
+    assert!(models.append(ExifDataTypeValue::CameraModel(vec!["EOS R5".to_string()])));
+    assert!(!models.append(ExifDataTypeValue::CameraBrand(vec!["Canon".to_string()])));
+    assert_eq!(models.len(), 2);
+
+    let exposure = ExifDataTypeValue::ExposureTime(vec![(1, 250)]);
+    assert_eq!(exposure.format_value(0, "{} s"), Some("1/250 s".to_string()));
+}
+
+#[test]
+pub fn test_default_arrangements() {
+    let requests = default_arrangements_requests();
+    assert_eq!(requests.len(), 2);
+    assert!(matches!(
+        &requests[0].1.groupings,
+        StrategyGroupingRequest::GroupByExifValues(r) if matches!(r.data_type, ExifDataTypeValue::CreationDate(_))
+    ));
+    assert!(matches!(
+        &requests[1].1.groupings,
+        StrategyGroupingRequest::GroupByExifValues(r) if matches!(r.data_type, ExifDataTypeValue::CameraModel(_))
+    ));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_default_arrangements_group_pictures_by_month_and_camera() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "default_arrangements");
+    let date = |d: u32, m: u32| NaiveDate::from_ymd_opt(2024, m, d).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let picture_ids = [date(1, 3), date(31, 3), date(1, 4)].map(|creation_date| insert_picture_created_at(conn, user_id, creation_date));
+    diesel::update(pictures::table.filter(pictures::id.eq_any(&picture_ids[..2])))
+        .set(pictures::camera_model.eq("X100V"))
+        .execute(conn)
+        .unwrap();
+
+    // Created once: the user has arrangements on the second call
+    create_default_arrangements(conn, user_id).unwrap();
+    create_default_arrangements(conn, user_id).unwrap();
+    // Pictures of each arrangement by group name
+    let arrangements_groups = |conn: &mut DBConn| {
+        Arrangement::from_user_id_with_groups(conn, user_id)
+            .unwrap()
+            .into_iter()
+            .map(|(arrangement, groups)| {
+                let groups = groups
+                    .into_iter()
+                    .map(|group| {
+                        let mut picture_ids = Group::pictures_from_group_ids(conn, &vec![group.id]).unwrap();
+                        picture_ids.sort();
+                        (group.name, picture_ids)
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                (arrangement.name, groups)
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    assert_eq!(arrangements_groups(conn).len(), 2);
+
+    // Groups are created as values are met, pictures without value go to the "Other" group
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    let groups = arrangements_groups(conn);
+    assert_eq!(
+        groups["By Month"],
+        BTreeMap::from([
+            ("2024-03".to_string(), picture_ids[..2].to_vec()),
+            ("2024-04".to_string(), vec![picture_ids[2]])
+        ])
+    );
+    assert_eq!(
+        groups["By Camera"],
+        BTreeMap::from([
+            ("X100V".to_string(), picture_ids[..2].to_vec()),
+            ("Other".to_string(), vec![picture_ids[2]])
+        ])
+    );
+
+    // A picture moving to another month leaves its previous group, which is kept
+    diesel::update(pictures::table.find(picture_ids[2]))
+        .set(pictures::creation_date.eq(date(15, 3)))
+        .execute(conn)
+        .unwrap();
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, Some(&vec![picture_ids[2]]), None, None, true).unwrap();
+    assert_eq!(
+        arrangements_groups(conn)["By Month"],
+        BTreeMap::from([("2024-03".to_string(), picture_ids.to_vec()), ("2024-04".to_string(), vec![])])
+    );
+}
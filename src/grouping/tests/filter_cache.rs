@@ -0,0 +1,224 @@
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::schema::*;
+use crate::database::tests::test_database::{
+    count_queries, insert_filter_arrangement, insert_picture, insert_share, insert_tags, insert_user, test_connection,
+};
+use crate::grouping::arrangement_strategy::ExifDataTypeValue;
+use crate::grouping::filter_cache::{deleted_pictures_statement, FilterCache, FilterPlan};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::{group_add_pictures, group_pictures};
+use crate::grouping::strategy_filtering::{FilterType, KnownPicture, StrategyFiltering};
+use chrono::Utc;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+
+fn known_picture(tag_ids: &[i32], group_ids: &[i32]) -> KnownPicture {
+    KnownPicture {
+        tag_ids: tag_ids.iter().cloned().collect(),
+        group_ids: group_ids.iter().cloned().collect(),
+        changed_group_ids: HashSet::new(),
+        deleted: false,
+    }
+}
+fn tags_filter(tag_ids: &[i32]) -> StrategyFiltering {
+    FilterType::IncludeTags(tag_ids.to_vec()).to_strategy()
+}
+fn groups_filter(group_ids: &[i32]) -> StrategyFiltering {
+    FilterType::IncludeGroups(group_ids.to_vec()).to_strategy()
+}
+fn orientation_filter() -> StrategyFiltering {
+    FilterType::ExifEqualTo(ExifDataTypeValue::Orientation(vec![PictureOrientation::Normal])).to_strategy()
+}
+
+/// Plan the filters the way `group_pictures` does for arrangements whose groups are `10 * i..10 * i + 2`,
+/// returning the number of filter queries. The queries find no more matching pictures.
+fn count_grouping_queries(cache: &mut FilterCache, filters: &[StrategyFiltering]) -> usize {
+    let mut queries = 0;
+    for (i, filter) in filters.iter().enumerate() {
+        let group_ids = vec![10 * i as i32, 10 * i as i32 + 1];
+        let ungroup_filter = filter.clone().not().and(groups_filter(&group_ids));
+
+        for filter in [filter, &ungroup_filter] {
+            let result = match cache.plan(filter) {
+                FilterPlan::Known(result) => result,
+                FilterPlan::Query(matching, _) => {
+                    queries += 1;
+                    matching
+                }
+            };
+            cache.record(filter, &result);
+            cache.groups_changed(&group_ids, &result);
+        }
+    }
+    queries
+}
+
+#[test]
+pub fn test_single_picture_upload_skips_tag_filters() {
+    // A new picture, with a default tag, grouped against 50 tag-filtered arrangements: 100 filter queries without the cache.
+    let mut cache = FilterCache::new(Some(HashMap::from([(1, known_picture(&[3], &[]))])));
+    let filters = (0..50).map(|i| tags_filter(&[i, i + 100])).collect::<Vec<_>>();
+
+    assert_eq!(count_grouping_queries(&mut cache, &filters), 0);
+}
+
+#[test]
+pub fn test_identical_filters_are_queried_once() {
+    let mut cache = FilterCache::new(Some(HashMap::from([(1, known_picture(&[], &[]))])));
+    let filters = (0..50).map(|_| orientation_filter()).collect::<Vec<_>>();
+
+    // The EXIF filter is queried once, the ungroup filters are known as the picture is in none of the groups.
+    assert_eq!(count_grouping_queries(&mut cache, &filters), 1);
+}
+
+#[test]
+pub fn test_only_unknown_pictures_are_queried() {
+    let cache = FilterCache::new(Some(HashMap::from([
+        (1, known_picture(&[1], &[])),
+        (2, known_picture(&[2], &[])),
+        (3, known_picture(&[1], &[])),
+    ])));
+    let filter = tags_filter(&[1]).and(orientation_filter());
+
+    assert_eq!(cache.plan(&filter), FilterPlan::Query(HashSet::new(), Some(vec![1, 3])));
+    assert_eq!(
+        cache.plan(&tags_filter(&[1]).or(orientation_filter())),
+        FilterPlan::Query(HashSet::from([1, 3]), Some(vec![2]))
+    );
+    assert_eq!(cache.plan(&tags_filter(&[2]).not()), FilterPlan::Known(HashSet::from([1, 3])));
+}
+
+#[test]
+pub fn test_changed_groups_are_not_evaluated() {
+    let mut cache = FilterCache::new(Some(HashMap::from([(1, known_picture(&[], &[5])), (2, known_picture(&[], &[]))])));
+    assert_eq!(cache.plan(&groups_filter(&[5, 6])), FilterPlan::Known(HashSet::from([1])));
+    cache.record(&groups_filter(&[5, 6]), &HashSet::from([1]));
+
+    // Picture 2 may have been added to the group 6: the cached result is dropped, and only picture 2 must be queried.
+    cache.groups_changed(&[6], &HashSet::from([2]));
+    assert_eq!(cache.plan(&groups_filter(&[5, 6])), FilterPlan::Query(HashSet::from([1]), Some(vec![2])));
+}
+
+#[test]
+pub fn test_all_pictures_are_queried_without_list() {
+    let mut cache = FilterCache::new(None);
+    assert_eq!(cache.plan(&tags_filter(&[1])), FilterPlan::Query(HashSet::new(), None));
+
+    cache.record(&tags_filter(&[1]), &HashSet::from([4, 5]));
+    assert_eq!(cache.plan(&tags_filter(&[1])), FilterPlan::Known(HashSet::from([4, 5])));
+}
+
+#[test]
+pub fn test_deleted_picture_is_ungrouped_and_not_regrouped() {
+    // Picture 1 is in the trash, still in the group 10 of the tag 3, picture 2 is not deleted
+    let mut deleted = known_picture(&[3], &[10]);
+    deleted.deleted = true;
+    let cache = FilterCache::new(Some(HashMap::from([(1, deleted), (2, known_picture(&[3], &[10]))])));
+
+    // Not re-added: it matches neither the arrangement filter nor its negation
+    assert_eq!(cache.plan(&tags_filter(&[3])), FilterPlan::Known(HashSet::from([2])));
+    assert_eq!(cache.plan(&tags_filter(&[3]).not()), FilterPlan::Known(HashSet::new()));
+    assert_eq!(cache.plan(&orientation_filter()), FilterPlan::Query(HashSet::new(), Some(vec![2])));
+    // Removed from its groups by the ungrouping
+    assert_eq!(cache.known_deleted_pictures(&[10, 11]), Some(HashSet::from([1])));
+    assert_eq!(cache.known_deleted_pictures(&[11]), Some(HashSet::new()));
+    assert_eq!(FilterCache::new(None).known_deleted_pictures(&[10]), None);
+}
+
+#[test]
+pub fn test_deleted_pictures_are_excluded_from_filter_queries() {
+    let sql = debug_query::<Pg, _>(&tags_filter(&[3]).filter_pictures_statement(Some(&vec![1, 2]))).to_string();
+    assert!(sql.starts_with("SELECT \"pictures\".\"id\" FROM \"pictures\" WHERE (((\"pictures\".\"deleted_date\" IS NULL)"));
+    assert!(sql.contains("\"pictures\".\"id\" = ANY($1)"));
+    assert!(sql.ends_with("binds: [[1, 2], [3]]"));
+
+    // Deleted pictures still in the groups of the arrangement are found to be ungrouped
+    let sql = debug_query::<Pg, _>(&deleted_pictures_statement(&[10, 11])).to_string();
+    assert!(sql.contains("\"groups_pictures\".\"group_id\" = ANY($1)"));
+    assert!(sql.contains("\"pictures\".\"deleted_date\" IS NOT NULL"));
+    assert!(sql.ends_with("binds: [[10, 11]]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_single_picture_upload_runs_no_tag_filter_query() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "filter_cache");
+    let tag_ids = insert_tags(conn, user_id, 51);
+    let picture_id = insert_picture(conn, user_id, &tag_ids[..1]);
+    // 50 arrangements filtering on other tags, and one on the tag of the picture
+    for (i, tag_id) in tag_ids[1..].iter().enumerate() {
+        insert_filter_arrangement(conn, user_id, format!("Arrangement {}", i), tags_filter(&[*tag_id]));
+    }
+    let group_id = insert_filter_arrangement(conn, user_id, "Tagged".to_string(), tags_filter(&tag_ids[..1]));
+
+    let filter_queries = count_queries(
+        conn,
+        "SELECT \"pictures\".\"id\" FROM \"pictures\" WHERE ((\"pictures\".\"deleted_date\" IS NULL)",
+    );
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, Some(&vec![picture_id]), None, None, true).unwrap();
+    // Every filter is evaluated from the tags of the picture: the upload runs no filter query
+    assert_eq!(filter_queries.load(Ordering::SeqCst), 0);
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap(), vec![picture_id]);
+
+    // Regrouping all the pictures queries the filter and the ungroup filter of each arrangement
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    assert!(filter_queries.load(Ordering::SeqCst) >= 51);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_trashed_pictures_are_ungrouped_and_not_grouped_again() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "trash_owner");
+    let recipient_id = insert_user(conn, "trash_recipient");
+    let owner_tag_ids = insert_tags(conn, owner_id, 1);
+    let recipient_tag_ids = insert_tags(conn, recipient_id, 1);
+    let tag_ids = [owner_tag_ids[0], recipient_tag_ids[0]];
+    let (trashed_id, kept_id, trashed_unshared_id) = (
+        insert_picture(conn, owner_id, &tag_ids),
+        insert_picture(conn, owner_id, &tag_ids),
+        insert_picture(conn, owner_id, &tag_ids),
+    );
+    let owner_group_id = insert_filter_arrangement(conn, owner_id, "Tagged".to_string(), tags_filter(&owner_tag_ids));
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let shared_group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, shared_group.id, &vec![trashed_id, kept_id]).unwrap();
+    insert_share(conn, recipient_id, shared_group.id, true);
+    let recipient_group_id = insert_filter_arrangement(conn, recipient_id, "Tagged".to_string(), tags_filter(&recipient_tag_ids));
+    for user_id in [owner_id, recipient_id] {
+        group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    }
+    let sorted_pictures = |conn: &mut DBConn, group_id| {
+        let mut picture_ids = Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap();
+        picture_ids.sort();
+        picture_ids
+    };
+    assert_eq!(sorted_pictures(conn, owner_group_id), vec![trashed_id, kept_id, trashed_unshared_id]);
+    assert_eq!(sorted_pictures(conn, recipient_group_id), vec![trashed_id, kept_id]);
+
+    diesel::update(pictures::table.filter(pictures::id.eq_any(vec![trashed_id, trashed_unshared_id])))
+        .set(pictures::deleted_date.eq(Some(Utc::now().naive_utc())))
+        .execute(conn)
+        .unwrap();
+
+    // Regrouping all the pictures removes the trashed ones from the groups of the owner and of the recipient
+    for user_id in [owner_id, recipient_id] {
+        group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    }
+    assert_eq!(sorted_pictures(conn, owner_group_id), vec![kept_id]);
+    assert_eq!(sorted_pictures(conn, recipient_group_id), vec![kept_id]);
+
+    // Grouping them again doesn't add them back
+    group_pictures(conn, &mut GroupingDelta::new(), owner_id, Some(&vec![trashed_id]), None, None, true).unwrap();
+    assert_eq!(sorted_pictures(conn, owner_group_id), vec![kept_id]);
+
+    // Neither does sharing a trashed picture: it is not propagated to the groups of the recipient
+    group_add_pictures(conn, &mut GroupingDelta::new(), shared_group.id, &vec![trashed_unshared_id]).unwrap();
+    assert_eq!(sorted_pictures(conn, recipient_group_id), vec![kept_id]);
+}
@@ -0,0 +1,315 @@
+use crate::api::groups::arrangement::{apply_arrangement_edit, preview_strategy_edit};
+use crate::api::tags::{apply_picture_tags_edition, EditPictureTagsRequest};
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType};
+use crate::database::group::group::Group;
+use crate::database::tests::test_database::{insert_filter_arrangement, insert_picture, insert_tags, insert_user, test_connection};
+use crate::grouping::arrangement_strategy::ArrangementStrategyRequest;
+use crate::grouping::group_by_filter::{FilterGroupingRequest, FilterGroupingValueRequest};
+use crate::grouping::grouping_delta::{
+    grouping_transaction, grouping_transaction_emitting, GroupingDelta, GroupingPreview, GroupingProgress, GroupingProgressChannels,
+    PictureGroupsEvent,
+};
+use crate::grouping::grouping_process::{group_pictures, regroup_all};
+use crate::grouping::strategy_filtering::FilterType;
+use crate::grouping::strategy_grouping::StrategyGroupingRequest;
+use crate::utils::errors_catcher::ErrorType;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::sync::broadcast;
+
+#[test]
+pub fn test_intermediate_steps_cancel_out() {
+    let mut delta = GroupingDelta::new();
+    // Picture 1 gets tag B instead of tag A: added to the group of tag B, then ungrouped from the group of tag A.
+    delta.record_added(20, &[1]);
+    // Intermediate step: picture 2 temporarily moved to the "other" group then back to its group
+    delta.record_removed(10, &[2]);
+    delta.record_added(30, &[2]);
+    delta.record_removed(30, &[2]);
+    delta.record_added(10, &[2]);
+    delta.record_removed(10, &[1]);
+
+    let (events_channel, mut receiver) = broadcast::channel(16);
+    delta.emit(&events_channel);
+
+    assert_eq!(
+        receiver.try_recv().unwrap(),
+        vec![PictureGroupsEvent {
+            picture_id: 1,
+            added_groups: vec![20],
+            removed_groups: vec![10],
+        }]
+    );
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_tag_edit_emits_net_delta() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "delta");
+    let tag_ids = insert_tags(conn, user_id, 2);
+    let moved_picture_id = insert_picture(conn, user_id, &[tag_ids[0]]);
+    let other_picture_id = insert_picture(conn, user_id, &[tag_ids[0]]);
+    let group_a = insert_filter_arrangement(conn, user_id, "A".to_string(), FilterType::IncludeTags(vec![tag_ids[0]]).to_strategy());
+    let group_b = insert_filter_arrangement(conn, user_id, "B".to_string(), FilterType::IncludeTags(vec![tag_ids[1]]).to_strategy());
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+
+    // The picture gets tag B instead of tag A, the way the tags edition endpoint does it
+    let (events_channel, mut receiver) = broadcast::channel(16);
+    let request = EditPictureTagsRequest {
+        picture_ids: vec![moved_picture_id],
+        add_tag_ids: vec![tag_ids[1]],
+        remove_tag_ids: vec![tag_ids[0]],
+    };
+    grouping_transaction_emitting(conn, &events_channel, None, |conn, delta| {
+        apply_picture_tags_edition(conn, user_id, &request)?;
+        group_pictures(
+            conn,
+            delta,
+            user_id,
+            Some(&request.picture_ids),
+            None,
+            Some(&ArrangementDependencyType::new_tags_dependant()),
+            true,
+        )
+    })
+    .unwrap();
+
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_a]).unwrap(), vec![other_picture_id]);
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_b]).unwrap(), vec![moved_picture_id]);
+    // A single event, with the net changes of the moved picture only
+    assert_eq!(
+        receiver.try_recv().unwrap(),
+        vec![PictureGroupsEvent {
+            picture_id: moved_picture_id,
+            added_groups: vec![group_b],
+            removed_groups: vec![group_a],
+        }]
+    );
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+pub fn test_no_net_change_emits_nothing() {
+    let mut delta = GroupingDelta::new();
+    delta.record_added(20, &[1, 2]);
+    delta.record_removed(20, &[1, 2]);
+    assert!(delta.events().is_empty());
+}
+
+/// Pictures of each group of the user.
+fn user_memberships(conn: &mut DBConn, user_id: i32) -> HashMap<i32, HashSet<i64>> {
+    Group::from_user_id_all(conn, user_id)
+        .unwrap()
+        .into_iter()
+        .map(|group| {
+            let picture_ids = Group::pictures_from_group_ids(conn, &vec![group.id]).unwrap();
+            (group.id, HashSet::from_iter(picture_ids))
+        })
+        .collect()
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_preview_counts_the_net_changes_of_the_edit() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "preview_edit");
+    let tag_ids = insert_tags(conn, user_id, 2);
+    insert_picture(conn, user_id, &[tag_ids[0]]);
+    insert_picture(conn, user_id, &[tag_ids[0]]);
+    insert_picture(conn, user_id, &[tag_ids[1]]);
+    insert_picture(conn, user_id, &tag_ids);
+    insert_picture(conn, user_id, &[]);
+    let group_id = insert_filter_arrangement(conn, user_id, "A".to_string(), FilterType::IncludeTags(vec![tag_ids[0]]).to_strategy());
+    let other_group_id = insert_filter_arrangement(conn, user_id, "B".to_string(), FilterType::IncludeTags(vec![tag_ids[1]]).to_strategy());
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    let arrangement_id = Group::from_id(conn, group_id).unwrap().arrangement_id;
+
+    // The arrangement now only takes the pictures of tag B: its group A keeps the ones that also have tag A,
+    // and all of them go to a new group
+    let strategy = ArrangementStrategyRequest {
+        filter: FilterType::IncludeTags(vec![tag_ids[1]]).to_strategy(),
+        groupings: StrategyGroupingRequest::GroupByFilter(FilterGroupingRequest {
+            filters: vec![
+                FilterGroupingValueRequest {
+                    id: group_id,
+                    name: "A".to_string(),
+                    filter: FilterType::IncludeTags(vec![tag_ids[0]]).to_strategy(),
+                },
+                FilterGroupingValueRequest {
+                    id: 0,
+                    name: "Portraits".to_string(),
+                    filter: FilterType::IncludeTags(vec![tag_ids[1]]).to_strategy(),
+                },
+            ],
+        }),
+        preserve_unicity: false,
+    };
+    let arrangement = Arrangement::from_id_and_user_id(conn, arrangement_id, user_id).unwrap();
+    let preview = preview_strategy_edit(conn, user_id, &arrangement, &strategy).unwrap();
+    // Nothing is left of the previewed edit
+    let before = user_memberships(conn, user_id);
+    assert_eq!(before.len(), 2);
+    assert_eq!(Arrangement::from_id_and_user_id(conn, arrangement_id, user_id).unwrap(), arrangement);
+
+    // The edit itself
+    grouping_transaction(conn, |conn, delta| {
+        apply_arrangement_edit(conn, delta, user_id, &arrangement, &arrangement.name, false, Some(&strategy))
+    })
+    .unwrap();
+    let after = user_memberships(conn, user_id);
+    let new_groups = Group::from_user_id_all(conn, user_id)
+        .unwrap()
+        .into_iter()
+        .filter(|group| !before.contains_key(&group.id))
+        .map(|group| (group.id, group.name))
+        .collect::<HashMap<_, _>>();
+
+    // Counts of the pictures the edit actually added to and removed from each group
+    let mut expected = GroupingPreview::default();
+    for group_id in before.keys().chain(after.keys()).collect::<HashSet<_>>() {
+        let empty = HashSet::new();
+        let (before, after) = (before.get(group_id).unwrap_or(&empty), after.get(group_id).unwrap_or(&empty));
+        let (added, removed) = (after.difference(before).count(), before.difference(after).count());
+        if let Some(name) = new_groups.get(group_id) {
+            expected.new_groups_would_add.insert(name.clone(), added);
+            continue;
+        }
+        if added > 0 {
+            expected.would_add.insert(*group_id, added);
+        }
+        if removed > 0 {
+            expected.would_remove.insert(*group_id, removed);
+        }
+    }
+    assert_eq!(preview, expected);
+    // The new group is referenced by name: its id only existed in the rolled back transaction
+    assert_eq!(preview.new_groups_would_add, BTreeMap::from([("Portraits".to_string(), 2)]));
+    assert!(preview.would_add.is_empty());
+    assert_eq!(preview.would_remove, BTreeMap::from([(group_id, 2)]));
+    assert!(!preview.would_remove.contains_key(&other_group_id));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_multi_arrangement_regroup_emits_progress() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "regroup_progress");
+    let tag_ids = insert_tags(conn, user_id, 3);
+    insert_picture(conn, user_id, &tag_ids);
+    for tag_id in tag_ids.iter() {
+        insert_filter_arrangement(
+            conn,
+            user_id,
+            format!("Tag {}", tag_id),
+            FilterType::IncludeTags(vec![*tag_id]).to_strategy(),
+        );
+    }
+    let channels = GroupingProgressChannels::default();
+    let mut receiver = channels.subscribe(user_id);
+    let (events_channel, _events_receiver) = broadcast::channel(16);
+
+    let arrangement_ids = grouping_transaction_emitting(conn, &events_channel, Some((&channels, user_id)), |conn, delta| {
+        let arrangement_ids = regroup_all(conn, delta, user_id)?;
+        assert_eq!(delta.progress().len(), 3);
+        // Nothing is sent before the transaction is committed
+        assert!(receiver.try_recv().is_err());
+        Ok(arrangement_ids)
+    })
+    .unwrap();
+
+    // One event per arrangement, in the order they were grouped
+    let events = (0..3).map(|_| receiver.try_recv().unwrap()).collect::<Vec<_>>();
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(
+        events.iter().map(|event| (event.processed, event.total)).collect::<Vec<_>>(),
+        vec![(1, 3), (2, 3), (3, 3)]
+    );
+    let mut grouped_arrangement_ids = events.iter().map(|event| event.arrangement_id).collect::<Vec<_>>();
+    grouped_arrangement_ids.sort();
+    let mut arrangement_ids = arrangement_ids;
+    arrangement_ids.sort();
+    assert_eq!(grouped_arrangement_ids, arrangement_ids);
+
+    // A rolled back regrouping sends nothing
+    let result: Result<(), _> = grouping_transaction_emitting(conn, &events_channel, Some((&channels, user_id)), |conn, delta| {
+        regroup_all(conn, delta, user_id)?;
+        Err(ErrorType::InvalidInput("Rolled back".to_string()).res())
+    });
+    assert!(result.is_err());
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+pub fn test_preview_doesnt_emit_progress() {
+    // Previews and the operations whose endpoint didn't ask for the progress use a disabled reporter
+    let mut delta = GroupingDelta::new();
+    let mut progress = delta.take_progress(5, 2);
+    progress.arrangement_grouped(12);
+    progress.arrangement_grouped(10);
+    delta.restore_progress(progress);
+    assert!(delta.progress().is_empty());
+}
+
+#[test]
+pub fn test_nested_groupings_dont_report_their_progress() {
+    // The regroup-all endpoint asks for the progress of user 5, then group_pictures takes the reporter for its 3 arrangements
+    let mut delta = GroupingDelta::new();
+    delta.report_progress(5);
+    let mut progress = delta.take_progress(5, 3);
+    progress.arrangement_grouped(12);
+    // Grouping the first arrangement propagated pictures to user 6, whose grouping doesn't report its progress
+    let mut propagation = delta.take_progress(6, 2);
+    propagation.arrangement_grouped(40);
+    delta.restore_progress(propagation);
+    progress.arrangement_grouped(10);
+    delta.restore_progress(progress);
+    assert_eq!(
+        delta.progress().iter().map(|progress| progress.arrangement_id).collect::<Vec<_>>(),
+        vec![12, 10]
+    );
+
+    // The next grouping of the same operation reports its progress again
+    let mut progress = delta.take_progress(5, 1);
+    progress.arrangement_grouped(13);
+    delta.restore_progress(progress);
+    assert_eq!(
+        delta.progress().last(),
+        Some(&GroupingProgress {
+            arrangement_id: 13,
+            processed: 1,
+            total: 1,
+        })
+    );
+
+    // Published to the user once committed
+    let channels = GroupingProgressChannels::default();
+    let mut receiver = channels.subscribe(5);
+    let mut recipient_receiver = channels.subscribe(6);
+    delta.publish_progress(&channels, 5);
+    assert_eq!(
+        (0..3).map(|_| receiver.try_recv().unwrap().arrangement_id).collect::<Vec<_>>(),
+        vec![12, 10, 13]
+    );
+    assert!(receiver.try_recv().is_err());
+    assert!(recipient_receiver.try_recv().is_err());
+    assert!(delta.progress().is_empty());
+}
+
+#[test]
+pub fn test_progress_without_subscriber_is_dropped() {
+    let channels = GroupingProgressChannels::default();
+    let progress = |arrangement_id| GroupingProgress {
+        arrangement_id,
+        processed: 1,
+        total: 1,
+    };
+    channels.send(5, progress(1));
+
+    let receiver = channels.subscribe(5);
+    drop(receiver);
+    channels.send(5, progress(2));
+    // The channel of the user has been dropped with its last subscriber: a new subscriber only gets the next events
+    let mut receiver = channels.subscribe(5);
+    assert!(receiver.try_recv().is_err());
+}
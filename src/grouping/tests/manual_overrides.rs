@@ -0,0 +1,153 @@
+use crate::database::group::arrangement::Arrangement;
+use crate::database::group::group::Group;
+use crate::database::group::manual_override::{ForcedMemberships, ManualOverride};
+use crate::database::schema::*;
+use crate::database::tests::test_database::{
+    count_queries, insert_filter_arrangement, insert_picture, insert_share, insert_tags, insert_user, test_connection,
+};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::{group_pictures, ungroup_unaccessible_pictures};
+use crate::grouping::strategy_filtering::FilterType;
+use crate::grouping::strategy_grouping::UngroupRecord;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::Ordering;
+
+fn manual_override(group_id: i32, picture_id: i64, force_in: bool) -> ManualOverride {
+    ManualOverride {
+        group_id,
+        picture_id,
+        force_in,
+    }
+}
+
+#[test]
+pub fn test_forced_in_picture_stays_in_a_group_it_does_not_match() {
+    // The strategy doesn't match pictures 1 and 2 anymore: they are to be ungrouped from group 10
+    let mut ungroup_record = UngroupRecord::new(true);
+    ungroup_record.add(10, HashSet::from([1, 2]));
+    ungroup_record.add(11, HashSet::from([3]));
+
+    let overrides = vec![manual_override(10, 1, true), manual_override(11, 4, false)];
+    let forced = ManualOverride::forced_memberships(&overrides);
+    assert_eq!(
+        forced,
+        ForcedMemberships {
+            add: BTreeMap::from([(10, vec![1])]),
+            remove: BTreeMap::from([(11, vec![4])]),
+        }
+    );
+    assert_eq!(forced.picture_ids(), HashSet::from([1, 4]));
+
+    // Picture 1 is kept in group 10 and added back if it was not in it, picture 2 is still ungrouped
+    forced.keep_forced_in(&mut ungroup_record);
+    assert_eq!(ungroup_record.map[&10], HashSet::from([2]));
+    assert_eq!(ungroup_record.map[&11], HashSet::from([3]));
+
+    // The strategy never adds picture 4 to group 11, but still can add it to the other groups
+    assert_eq!(forced.without_forced_out(11, &vec![3, 4]), vec![3]);
+    assert_eq!(forced.without_forced_out(10, &vec![3, 4]), vec![3, 4]);
+}
+
+#[test]
+pub fn test_overrides_of_unaccessible_pictures_are_ignored() {
+    let sql = debug_query::<Pg, _>(&ManualOverride::from_group_ids_statement(2, &[10, 11], Some(&vec![1, 2]))).to_string();
+    // Pictures in the trash, or neither owned by the user nor shared with them, are left out
+    assert!(sql.contains("\"manual_overrides\".\"picture_id\" = ANY(SELECT \"pictures\".\"id\" FROM \"pictures\""));
+    assert!(sql.contains("(\"pictures\".\"deleted_date\" IS NULL)"));
+    assert!(sql.contains("(\"pictures\".\"owner_id\" = $2) OR EXISTS (SELECT"));
+    assert!(sql.contains("\"shared_groups\".\"user_id\" = $3"));
+    assert!(sql.ends_with("binds: [[10, 11], 2, 2, [1, 2]]"));
+
+    let sql = debug_query::<Pg, _>(&ManualOverride::from_group_ids_statement(2, &[10], None)).to_string();
+    assert!(sql.ends_with("binds: [[10], 2, 2]"));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_forced_in_picture_is_kept_across_regroupings() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "force_in");
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let picture_id = insert_picture(conn, user_id, &[tag_ids[0]]);
+    let other_picture_id = insert_picture(conn, user_id, &[]);
+    let group_id = insert_filter_arrangement(
+        conn,
+        user_id,
+        "Tagged".to_string(),
+        FilterType::IncludeTags(vec![tag_ids[0]]).to_strategy(),
+    );
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap(), vec![picture_id]);
+
+    // The first picture loses its tag, the other one never had it: both are forced in the group
+    ManualOverride::upsert(conn, group_id, &[picture_id, other_picture_id], true).unwrap();
+    diesel::delete(pictures_tags::table.filter(pictures_tags::picture_id.eq(picture_id)))
+        .execute(conn)
+        .unwrap();
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+
+    let mut grouped_picture_ids = Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap();
+    grouped_picture_ids.sort();
+    assert_eq!(grouped_picture_ids, vec![picture_id, other_picture_id]);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_forced_out_picture_is_never_added() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "force_out");
+    let tag_ids = insert_tags(conn, user_id, 1);
+    let picture_id = insert_picture(conn, user_id, &[tag_ids[0]]);
+    let group_id = insert_filter_arrangement(
+        conn,
+        user_id,
+        "Tagged".to_string(),
+        FilterType::IncludeTags(vec![tag_ids[0]]).to_strategy(),
+    );
+    ManualOverride::upsert(conn, group_id, &[picture_id], false).unwrap();
+
+    // The strategy matches the picture, but it is not even temporarily added, on every regrouping
+    let inserts = count_queries(conn, "INSERT INTO \"groups_pictures\"");
+    for _ in 0..2 {
+        let mut delta = GroupingDelta::new();
+        group_pictures(conn, &mut delta, user_id, None, None, None, true).unwrap();
+        assert!(delta.events().is_empty());
+    }
+    assert_eq!(inserts.load(Ordering::SeqCst), 0);
+    assert!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap().is_empty());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_forced_in_picture_is_not_put_back_once_unshared() {
+    let conn = &mut test_connection();
+    let owner_id = insert_user(conn, "force_in_owner");
+    let recipient_id = insert_user(conn, "force_in_recipient");
+    let picture_id = insert_picture(conn, owner_id, &[]);
+    let arrangement = Arrangement::new(conn, owner_id, "Shared".to_string(), false, None).unwrap();
+    let shared_group = Group::insert(conn, arrangement.id, "Shared group".to_string(), false, None).unwrap();
+    Group::add_pictures(conn, shared_group.id, &vec![picture_id]).unwrap();
+    insert_share(conn, recipient_id, shared_group.id, true);
+    // The recipient forces the shared picture in a group it doesn't match
+    let recipient_tag_ids = insert_tags(conn, recipient_id, 1);
+    let group_id = insert_filter_arrangement(
+        conn,
+        recipient_id,
+        "Recipient".to_string(),
+        FilterType::IncludeTags(recipient_tag_ids).to_strategy(),
+    );
+    ManualOverride::upsert(conn, group_id, &[picture_id], true).unwrap();
+    group_pictures(conn, &mut GroupingDelta::new(), recipient_id, None, None, None, true).unwrap();
+    assert_eq!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap(), vec![picture_id]);
+
+    // The share is revoked: the picture leaves the recipient's groups, and the next regrouping doesn't put it back
+    diesel::delete(shared_groups::table.filter(shared_groups::group_id.eq(shared_group.id)))
+        .execute(conn)
+        .unwrap();
+    ungroup_unaccessible_pictures(conn, &mut GroupingDelta::new(), recipient_id, &[picture_id]).unwrap();
+    group_pictures(conn, &mut GroupingDelta::new(), recipient_id, None, None, None, true).unwrap();
+    assert!(Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap().is_empty());
+}
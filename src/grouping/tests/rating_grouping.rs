@@ -0,0 +1,187 @@
+use crate::api::picture::rate_pictures_batch;
+use crate::database::database::DBConn;
+use crate::database::group::arrangement::{Arrangement, ArrangementDependencyType, ArrangementDetails};
+use crate::database::group::group::Group;
+use crate::database::picture::rating::Rating;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::grouping::arrangement_strategy::{ArrangementStrategy, ArrangementStrategyRequest};
+use crate::grouping::group_by_rating::{RatingGrouping, RatingGroupingRequest};
+use crate::grouping::grouping_delta::GroupingDelta;
+use crate::grouping::grouping_process::{group_pictures, select_arrangements_to_group};
+use crate::grouping::strategy_filtering::StrategyFiltering;
+use crate::grouping::strategy_grouping::{StrategyGrouping, StrategyGroupingRequest, StrategyGroupingTrait};
+use crate::grouping::tests::arrangement_sort_algorithms::create_arrangement_with_dependant_arrangements;
+use std::collections::{BTreeMap, HashSet};
+
+fn rating_grouping() -> RatingGrouping {
+    // Group of id 10 + r for the rating r, 20 for unrated pictures
+    RatingGrouping {
+        rating_to_group_id: (0..=5).map(|rating| (rating, 10 + rating as i32)).collect(),
+        unrated_group_id: 20,
+    }
+}
+fn rating(picture_id: i64, rating: i16) -> Rating {
+    Rating {
+        user_id: 1,
+        picture_id,
+        rating,
+    }
+}
+
+#[test]
+pub fn test_pictures_land_in_their_star_group() {
+    let grouping = rating_grouping();
+    let picture_ids: HashSet<i64> = HashSet::from([1, 2, 3, 4, 5]);
+    let ratings = vec![rating(1, 5), rating(2, 0), rating(3, 5), rating(4, 3), rating(99, 1)];
+
+    let groups = grouping.assign_groups(&picture_ids, &ratings);
+    assert_eq!(groups, BTreeMap::from([(10, vec![2]), (13, vec![4]), (15, vec![1, 3]), (20, vec![5])]));
+}
+
+#[test]
+pub fn test_out_of_range_rating_is_unrated() {
+    let grouping = rating_grouping();
+    let groups = grouping.assign_groups(&HashSet::from([1, 2]), &[rating(1, 6), rating(2, -1)]);
+    assert_eq!(groups, BTreeMap::from([(20, vec![1, 2])]));
+}
+
+#[test]
+pub fn test_rating_groups() {
+    let grouping = StrategyGrouping::GroupByRating(rating_grouping());
+    let groups = grouping.get_groups();
+    assert_eq!(groups, vec![10, 11, 12, 13, 14, 15, 20]);
+    assert!(!grouping.is_tags_dependant());
+    assert!(!grouping.is_exif_dependant());
+    assert!(!grouping.is_groups_dependant());
+
+    assert_eq!(RatingGrouping::format_group_name(1), "1 star");
+    assert_eq!(RatingGrouping::format_group_name(5), "5 stars");
+    assert_eq!(RatingGrouping::format_group_name(0), "0 stars");
+}
+
+#[test]
+pub fn test_rating_grouping_request_json() {
+    let request: StrategyGroupingRequest = serde_json::from_str(r#"{"GroupByRating": {}}"#).unwrap();
+    assert_eq!(request, StrategyGroupingRequest::GroupByRating(RatingGroupingRequest {}));
+}
+
+/// Arrangement with the given strategy, its dependency flags being derived from it
+fn arrangement_with_strategy(id: i32, strategy: ArrangementStrategy) -> ArrangementDetails {
+    let mut arrangement = create_arrangement_with_dependant_arrangements(id, vec![]);
+    let dependency_type = ArrangementDependencyType::from(&Some(strategy.clone()));
+    arrangement.arrangement.groups_dependant = dependency_type.groups_dependant;
+    arrangement.arrangement.tags_dependant = dependency_type.tags_dependant;
+    arrangement.arrangement.exif_dependant = dependency_type.exif_dependant;
+    arrangement.arrangement.ratings_dependant = dependency_type.ratings_dependant;
+    arrangement.strategy = strategy;
+    arrangement
+}
+
+#[test]
+pub fn test_rating_change_only_regroups_rating_arrangements() {
+    let rating_arrangement = arrangement_with_strategy(
+        1,
+        ArrangementStrategy {
+            filter: StrategyFiltering::And(Box::default()),
+            groupings: StrategyGrouping::GroupByRating(rating_grouping()),
+            preserve_unicity: true,
+        },
+    );
+    let tags_arrangement = create_arrangement_with_dependant_arrangements(2, vec![]);
+    let tags_arrangement = arrangement_with_strategy(2, tags_arrangement.strategy);
+    assert_eq!(
+        ArrangementDependencyType::from(&rating_arrangement),
+        ArrangementDependencyType::new_ratings_dependant()
+    );
+    assert!(!tags_arrangement.arrangement.ratings_dependant);
+
+    // Only the rating arrangement is regrouped on a rating change
+    let selected: Vec<i32> = select_arrangements_to_group(
+        vec![rating_arrangement.clone(), tags_arrangement],
+        0,
+        None,
+        Some(&ArrangementDependencyType::new_ratings_dependant()),
+    )
+    .unwrap()
+    .iter()
+    .map(|a| a.arrangement.id)
+    .collect();
+    assert_eq!(selected, vec![1]);
+
+    // The picture moves from the 3 stars group to the 5 stars group
+    let StrategyGrouping::GroupByRating(grouping) = &rating_arrangement.strategy.groupings else {
+        unreachable!()
+    };
+    let picture_ids = HashSet::from([1]);
+    assert_eq!(grouping.assign_groups(&picture_ids, &[rating(1, 3)]), BTreeMap::from([(13, vec![1])]));
+    assert_eq!(grouping.assign_groups(&picture_ids, &[rating(1, 5)]), BTreeMap::from([(15, vec![1])]));
+    // and to the unrated group once the rating is removed
+    assert_eq!(grouping.assign_groups(&picture_ids, &[]), BTreeMap::from([(20, vec![1])]));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_rated_pictures_land_in_their_star_group_and_move_on_rating_change() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "rating_grouping");
+    let picture_ids = [(); 3].map(|_| insert_picture(conn, user_id, &[]));
+
+    let mut arrangement = Arrangement::new(conn, user_id, "By Rating".to_string(), false, None).unwrap();
+    let strategy = ArrangementStrategyRequest {
+        filter: StrategyFiltering::And(Box::default()),
+        groupings: StrategyGroupingRequest::GroupByRating(RatingGroupingRequest {}),
+        preserve_unicity: true,
+    }
+    .create(conn, arrangement.id)
+    .unwrap();
+    arrangement.set_strategy(conn, Some(strategy.clone())).unwrap();
+    let StrategyGrouping::GroupByRating(grouping) = strategy.groupings else {
+        unreachable!()
+    };
+    // Pictures of each group of the arrangement
+    let groups_pictures = |conn: &mut DBConn| {
+        grouping
+            .get_groups()
+            .into_iter()
+            .map(|group_id| {
+                let mut picture_ids = Group::pictures_from_group_ids(conn, &vec![group_id]).unwrap();
+                picture_ids.sort();
+                (group_id, picture_ids)
+            })
+            .filter(|(_, picture_ids)| !picture_ids.is_empty())
+            .collect::<BTreeMap<_, _>>()
+    };
+    assert_eq!(Group::from_id(conn, grouping.rating_to_group_id[&5]).unwrap().name, "5 stars");
+
+    // Unrated pictures are grouped apart, rated ones in their star group
+    group_pictures(conn, &mut GroupingDelta::new(), user_id, None, None, None, true).unwrap();
+    rate_pictures_batch(conn, user_id, &picture_ids[..2], Some(3)).unwrap();
+    assert_eq!(
+        groups_pictures(conn),
+        BTreeMap::from([
+            (grouping.rating_to_group_id[&3], picture_ids[..2].to_vec()),
+            (grouping.unrated_group_id, vec![picture_ids[2]])
+        ])
+    );
+
+    // A rating change moves the picture to its new star group
+    rate_pictures_batch(conn, user_id, &[picture_ids[0]], Some(5)).unwrap();
+    assert_eq!(
+        groups_pictures(conn),
+        BTreeMap::from([
+            (grouping.rating_to_group_id[&3], vec![picture_ids[1]]),
+            (grouping.rating_to_group_id[&5], vec![picture_ids[0]]),
+            (grouping.unrated_group_id, vec![picture_ids[2]])
+        ])
+    );
+
+    // and back to the unrated group once the rating is removed
+    rate_pictures_batch(conn, user_id, &[picture_ids[1]], None).unwrap();
+    assert_eq!(
+        groups_pictures(conn),
+        BTreeMap::from([
+            (grouping.rating_to_group_id[&5], vec![picture_ids[0]]),
+            (grouping.unrated_group_id, picture_ids[1..].to_vec())
+        ])
+    );
+}
@@ -2,41 +2,96 @@
 extern crate rocket;
 extern crate tera;
 
+use crate::api::admin::admin::{
+    admin_backfill_perceptual_hashes, admin_integrity_scan, admin_list_users, admin_migrations_status, admin_recompute_storage, admin_reset_totp,
+    admin_set_storage_limit, admin_verify_sharing_consistency, okapi_add_operation_for_admin_backfill_perceptual_hashes_,
+    okapi_add_operation_for_admin_integrity_scan_, okapi_add_operation_for_admin_list_users_, okapi_add_operation_for_admin_migrations_status_,
+    okapi_add_operation_for_admin_recompute_storage_, okapi_add_operation_for_admin_reset_totp_, okapi_add_operation_for_admin_set_storage_limit_,
+    okapi_add_operation_for_admin_verify_sharing_consistency_,
+};
 use crate::api::auth::confirm::{
     auth_confirm_code, auth_confirm_token, okapi_add_operation_for_auth_confirm_code_, okapi_add_operation_for_auth_confirm_token_,
 };
 use crate::api::auth::signin::{auth_signin, auth_signin_email, okapi_add_operation_for_auth_signin_, okapi_add_operation_for_auth_signin_email_};
 use crate::api::auth::signup::{auth_signup, okapi_add_operation_for_auth_signup_};
 use crate::api::auth::status::{auth_status, okapi_add_operation_for_auth_status_};
+use crate::api::curate::{curate_pictures, okapi_add_operation_for_curate_pictures_};
+use crate::api::events::{grouping_events, okapi_add_operation_for_grouping_events_};
 use crate::api::groups::arrangement::{
-    create_arrangement, delete_arrangement, edit_arrangement, list_arrangements, okapi_add_operation_for_create_arrangement_,
-    okapi_add_operation_for_delete_arrangement_, okapi_add_operation_for_edit_arrangement_, okapi_add_operation_for_list_arrangements_,
+    create_arrangement, create_arrangement_from_template, delete_arrangement, edit_arrangement, flush_deleted_groups, get_arrangement,
+    get_arrangement_template, get_arrangements_dependency_graph, list_arrangements, merge_arrangements, okapi_add_operation_for_create_arrangement_,
+    okapi_add_operation_for_create_arrangement_from_template_, okapi_add_operation_for_delete_arrangement_,
+    okapi_add_operation_for_edit_arrangement_, okapi_add_operation_for_flush_deleted_groups_, okapi_add_operation_for_get_arrangement_,
+    okapi_add_operation_for_get_arrangement_template_, okapi_add_operation_for_get_arrangements_dependency_graph_,
+    okapi_add_operation_for_list_arrangements_, okapi_add_operation_for_merge_arrangements_, okapi_add_operation_for_preview_arrangement_edit_,
+    okapi_add_operation_for_recompute_arrangements_dependencies_, okapi_add_operation_for_regroup_all_arrangements_,
+    okapi_add_operation_for_set_arrangement_enabled_, preview_arrangement_edit, recompute_arrangements_dependencies, regroup_all_arrangements,
+    set_arrangement_enabled,
 };
 use crate::api::groups::manual_groups::{
-    add_pictures_to_group, create_manual_group, okapi_add_operation_for_add_pictures_to_group_, okapi_add_operation_for_create_manual_group_,
+    add_pictures_to_group, assign_pictures_to_groups, create_manual_group, okapi_add_operation_for_add_pictures_to_group_,
+    okapi_add_operation_for_assign_pictures_to_groups_, okapi_add_operation_for_create_manual_group_,
     okapi_add_operation_for_remove_pictures_from_group_, remove_pictures_from_group,
 };
+use crate::api::groups::manual_overrides::{
+    clear_manual_overrides, okapi_add_operation_for_clear_manual_overrides_, okapi_add_operation_for_set_manual_overrides_, set_manual_overrides,
+};
+use crate::api::groups::shares::{
+    accept_all_pending_shares, decline_all_pending_shares, list_group_shared_pictures, list_outgoing_shares, list_shared_arrangements,
+    okapi_add_operation_for_accept_all_pending_shares_, okapi_add_operation_for_decline_all_pending_shares_,
+    okapi_add_operation_for_list_group_shared_pictures_, okapi_add_operation_for_list_outgoing_shares_,
+    okapi_add_operation_for_list_shared_arrangements_, okapi_add_operation_for_update_share_permissions_, update_share_permissions,
+};
+use crate::api::metrics::{get_metrics, okapi_add_operation_for_get_metrics_};
 use crate::api::picture::{
-    add_picture, get_picture, get_picture_details, get_pictures_details, okapi_add_operation_for_add_picture_, okapi_add_operation_for_get_picture_,
-    okapi_add_operation_for_get_picture_details_, okapi_add_operation_for_get_pictures_details_,
+    add_picture, download_pictures_zip, edit_picture_comment, get_picture, get_picture_access, get_picture_details, get_picture_tag_compliance,
+    get_picture_visibility, get_pictures_by_ids, get_pictures_details, get_pictures_exif_stats, get_pictures_total_size, get_thumbnails_batch,
+    list_pictures_details, okapi_add_operation_for_add_picture_, okapi_add_operation_for_download_pictures_zip_,
+    okapi_add_operation_for_edit_picture_comment_, okapi_add_operation_for_get_picture_, okapi_add_operation_for_get_picture_access_,
+    okapi_add_operation_for_get_picture_details_, okapi_add_operation_for_get_picture_tag_compliance_,
+    okapi_add_operation_for_get_picture_visibility_, okapi_add_operation_for_get_pictures_by_ids_, okapi_add_operation_for_get_pictures_details_,
+    okapi_add_operation_for_get_pictures_exif_stats_, okapi_add_operation_for_get_pictures_total_size_,
+    okapi_add_operation_for_get_thumbnails_batch_, okapi_add_operation_for_list_pictures_details_, okapi_add_operation_for_rate_picture_,
+    okapi_add_operation_for_rate_pictures_, okapi_add_operation_for_remove_picture_rating_, okapi_add_operation_for_set_picture_favorite_,
+    rate_picture, rate_pictures, remove_picture_rating, set_picture_favorite,
+};
+use crate::api::query_pictures::{
+    get_picture_neighbors, okapi_add_operation_for_get_picture_neighbors_, okapi_add_operation_for_query_pictures_,
+    okapi_add_operation_for_query_pictures_calendar_, okapi_add_operation_for_query_pictures_changes_, okapi_add_operation_for_query_pictures_ids_,
+    okapi_add_operation_for_query_pictures_tag_facets_, okapi_add_operation_for_query_ungrouped_pictures_,
+    okapi_add_operation_for_restore_pictures_by_query_, query_pictures, query_pictures_calendar, query_pictures_changes, query_pictures_ids,
+    query_pictures_tag_facets, query_ungrouped_pictures, restore_pictures_by_query,
+};
+use crate::api::saved_searches::{
+    create_saved_search, delete_saved_search, list_saved_searches, okapi_add_operation_for_create_saved_search_,
+    okapi_add_operation_for_delete_saved_search_, okapi_add_operation_for_list_saved_searches_, okapi_add_operation_for_query_saved_search_,
+    query_saved_search,
 };
-use crate::api::query_pictures::{okapi_add_operation_for_query_pictures_, query_pictures};
 use crate::api::tags::{
-    create_tag_group, delete_tag_group, edit_picture_tags, list_tags, okapi_add_operation_for_create_tag_group_,
-    okapi_add_operation_for_delete_tag_group_, okapi_add_operation_for_edit_picture_tags_, okapi_add_operation_for_list_tags_,
-    okapi_add_operation_for_patch_tag_group_, patch_tag_group,
+    apply_default_tags, clear_tag_assignments, create_tag_group, create_tag_groups_batch, delete_tag_group, edit_picture_tags, list_tags,
+    okapi_add_operation_for_apply_default_tags_, okapi_add_operation_for_clear_tag_assignments_, okapi_add_operation_for_create_tag_group_,
+    okapi_add_operation_for_create_tag_groups_batch_, okapi_add_operation_for_delete_tag_group_, okapi_add_operation_for_edit_picture_tags_,
+    okapi_add_operation_for_list_tags_, okapi_add_operation_for_patch_tag_group_, okapi_add_operation_for_reorder_tags_,
+    okapi_add_operation_for_repair_tags_compliance_, patch_tag_group, reorder_tags, repair_tags_compliance,
+};
+use crate::api::user::{
+    get_user_profile, get_user_public_profile, okapi_add_operation_for_get_user_profile_, okapi_add_operation_for_get_user_public_profile_,
+    okapi_add_operation_for_patch_user_profile_, okapi_add_operation_for_patch_user_security_, patch_user_profile, patch_user_security,
 };
 use crate::database::database::{get_connection, get_connection_pool};
+use crate::database::migrations::run_boot_migrations;
 use crate::database::picture::picture::Picture;
+use crate::grouping::grouping_delta::GroupingProgressChannels;
+use crate::utils::compression::CompressionFairing;
+use crate::utils::config::CONFIG;
+use crate::utils::cors::cors_options;
 use crate::utils::errors_catcher::{bad_request, internal_error, not_found, unauthorized, unprocessable_entity};
+use crate::utils::maintenance::MaintenanceFairing;
+use crate::utils::metrics::MetricsFairing;
 use crate::utils::s3::PictureStorer;
 use crate::utils::thumbnail::create_temp_directories;
-use crate::utils::utils::{get_backend_host, get_frontend_host};
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenvy::dotenv;
-use rocket::http::Method;
 use rocket::log::private::LevelFilter;
-use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors, CorsOptions};
 use rocket_okapi::openapi_get_routes;
 use rocket_okapi::rapidoc::{make_rapidoc, GeneralConfig, HideShowConfig, RapiDocConfig};
 use rocket_okapi::settings::UrlObject;
@@ -55,6 +110,34 @@ pub mod api {
     pub mod groups {
         automod::dir!(pub "src/api/groups");
     }
+    pub mod tests {
+        #[cfg(test)]
+        pub mod arrangement_response;
+        #[cfg(test)]
+        pub mod curate;
+        #[cfg(test)]
+        pub mod group_assign;
+        #[cfg(test)]
+        pub mod pending_shares;
+        #[cfg(test)]
+        pub mod perceptual_hash_backfill;
+        #[cfg(test)]
+        pub mod pictures_by_ids;
+        #[cfg(test)]
+        pub mod pictures_zip;
+        #[cfg(test)]
+        pub mod regroup_all;
+        #[cfg(test)]
+        pub mod strict_thumbnails;
+        #[cfg(test)]
+        pub mod tags_batch;
+        #[cfg(test)]
+        pub mod thumbnails_batch;
+        #[cfg(test)]
+        pub mod user_profile;
+        #[cfg(test)]
+        pub mod user_public_profile;
+    }
 }
 pub mod database {
     automod::dir!(pub "src/database");
@@ -73,15 +156,79 @@ pub mod database {
     pub mod user {
         automod::dir!(pub "src/database/user");
     }
+    pub mod tests {
+        #[cfg(test)]
+        pub mod admin_users;
+        #[cfg(test)]
+        pub mod arrangement_dependencies;
+        #[cfg(test)]
+        pub mod arrangement_edition;
+        #[cfg(test)]
+        pub mod arrangement_enabled;
+        #[cfg(test)]
+        pub mod arrangement_ordering;
+        #[cfg(test)]
+        pub mod copied_share_storage;
+        #[cfg(test)]
+        pub mod default_tags;
+        #[cfg(test)]
+        pub mod group_flush;
+        #[cfg(test)]
+        pub mod group_membership;
+        #[cfg(test)]
+        pub mod incoming_shares;
+        #[cfg(test)]
+        pub mod inserted_ids;
+        #[cfg(test)]
+        pub mod integrity_scan;
+        #[cfg(test)]
+        pub mod known_devices;
+        #[cfg(test)]
+        pub mod migrations_status;
+        #[cfg(test)]
+        pub mod outgoing_shares;
+        #[cfg(test)]
+        pub mod picture_access;
+        #[cfg(test)]
+        pub mod picture_details;
+        #[cfg(test)]
+        pub mod picture_neighbors;
+        #[cfg(test)]
+        pub mod picture_query;
+        #[cfg(test)]
+        pub mod picture_ratings;
+        #[cfg(test)]
+        pub mod pictures_total_size;
+        #[cfg(test)]
+        pub mod saved_searches;
+        #[cfg(test)]
+        pub mod share_permissions;
+        #[cfg(test)]
+        pub mod sharing_consistency;
+        #[cfg(test)]
+        pub mod tag_assignments;
+        #[cfg(test)]
+        pub mod tag_order;
+        #[cfg(test)]
+        pub mod test_database;
+        #[cfg(test)]
+        pub mod user_limits;
+    }
 }
 pub mod grouping {
     //automod::dir!(pub "src/grouping");
     pub mod arrangement_strategy;
+    pub mod arrangement_template;
+    pub mod default_arrangements;
+    pub mod dependency_graph;
+    pub mod filter_cache;
     pub mod group_by_exif_interval;
     pub mod group_by_exif_value;
     pub mod group_by_filter;
     pub mod group_by_location;
+    pub mod group_by_rating;
     pub mod group_by_tag;
+    pub mod grouping_delta;
     pub mod grouping_process;
     pub mod strategy_filtering;
     pub mod strategy_grouping;
@@ -89,6 +236,20 @@ pub mod grouping {
     pub mod tests {
         #[cfg(test)]
         pub mod arrangement_sort_algorithms;
+        #[cfg(test)]
+        pub mod arrangement_template;
+        #[cfg(test)]
+        pub mod dependency_graph;
+        #[cfg(test)]
+        pub mod exif_values_grouping;
+        #[cfg(test)]
+        pub mod filter_cache;
+        #[cfg(test)]
+        pub mod grouping_delta;
+        #[cfg(test)]
+        pub mod manual_overrides;
+        #[cfg(test)]
+        pub mod rating_grouping;
     }
 }
 pub mod mailing {
@@ -96,10 +257,34 @@ pub mod mailing {
 }
 pub mod utils {
     automod::dir!(pub "src/utils");
+    pub mod tests {
+        #[cfg(test)]
+        pub mod byte_range;
+        #[cfg(test)]
+        pub mod color;
+        #[cfg(test)]
+        pub mod compression;
+        #[cfg(test)]
+        pub mod config;
+        #[cfg(test)]
+        pub mod cors;
+        #[cfg(test)]
+        pub mod exif;
+        #[cfg(test)]
+        pub mod metrics;
+        #[cfg(test)]
+        pub mod picture_format;
+        #[cfg(test)]
+        pub mod s3_errors;
+        #[cfg(test)]
+        pub mod s3_mock;
+        #[cfg(test)]
+        pub mod thumbnail;
+        #[cfg(test)]
+        pub mod validation;
+    }
 }
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
-
 /// Entry point of Archypix app backend
 #[launch]
 #[tokio::main]
@@ -113,11 +298,11 @@ async fn rocket() -> _ {
     info!("Starting Archypix app backend...");
     trace!("Backend version: {}", env!("CARGO_PKG_VERSION"));
     dotenv().ok();
+    lazy_static::initialize(&CONFIG);
+    info!("Configuration: {:?}", *CONFIG);
 
     // Migrate SQL database
-    let mut conn = get_connection();
-    let res = conn.run_pending_migrations(MIGRATIONS).unwrap();
-    info!("Migrations result: {:?}", res);
+    run_boot_migrations(&mut get_connection());
 
     // Load S3 Client
     let picture_storer = PictureStorer::new().await;
@@ -125,10 +310,11 @@ async fn rocket() -> _ {
     // Create pictures temp directories
     create_temp_directories();
 
-    let cors = cors_options();
+    let cors = cors_options(&CONFIG.cors_allowed_origins);
     rocket::build()
         .manage(picture_storer)
         .manage(get_connection_pool())
+        .manage(GroupingProgressChannels::default())
         .manage(UserAgentParser::from_path("./static/user_agent_regexes.yaml").unwrap())
         .mount(
             "/",
@@ -140,27 +326,97 @@ async fn rocket() -> _ {
                 auth_status,
                 auth_confirm_code,
                 auth_confirm_token,
+                // User
+                get_user_profile,
+                patch_user_profile,
+                patch_user_security,
+                get_user_public_profile,
                 // Picture
                 add_picture,
                 get_picture,
+                get_picture_access,
+                get_picture_visibility,
+                get_thumbnails_batch,
                 query_pictures,
+                query_pictures_tag_facets,
+                query_pictures_ids,
+                restore_pictures_by_query,
+                query_pictures_calendar,
+                get_picture_neighbors,
+                query_ungrouped_pictures,
+                query_pictures_changes,
                 get_pictures_details,
+                get_pictures_by_ids,
                 get_picture_details,
+                get_picture_tag_compliance,
+                list_pictures_details,
+                get_pictures_total_size,
+                get_pictures_exif_stats,
+                download_pictures_zip,
+                edit_picture_comment,
+                set_picture_favorite,
+                rate_picture,
+                rate_pictures,
+                remove_picture_rating,
+                // Saved searches
+                create_saved_search,
+                list_saved_searches,
+                delete_saved_search,
+                query_saved_search,
                 // Tags
                 list_tags,
                 create_tag_group,
+                create_tag_groups_batch,
                 patch_tag_group,
                 delete_tag_group,
+                reorder_tags,
                 edit_picture_tags,
+                curate_pictures,
+                clear_tag_assignments,
+                apply_default_tags,
+                repair_tags_compliance,
                 // Arrangements
                 list_arrangements,
+                get_arrangements_dependency_graph,
+                get_arrangement,
+                get_arrangement_template,
                 create_arrangement,
+                create_arrangement_from_template,
                 edit_arrangement,
+                preview_arrangement_edit,
+                set_arrangement_enabled,
+                recompute_arrangements_dependencies,
+                regroup_all_arrangements,
+                flush_deleted_groups,
+                merge_arrangements,
                 delete_arrangement,
                 // Groups
                 create_manual_group,
                 add_pictures_to_group,
-                remove_pictures_from_group
+                remove_pictures_from_group,
+                assign_pictures_to_groups,
+                set_manual_overrides,
+                clear_manual_overrides,
+                // Shares
+                accept_all_pending_shares,
+                decline_all_pending_shares,
+                list_outgoing_shares,
+                list_shared_arrangements,
+                list_group_shared_pictures,
+                update_share_permissions,
+                // Admin
+                admin_list_users,
+                admin_set_storage_limit,
+                admin_reset_totp,
+                admin_migrations_status,
+                admin_verify_sharing_consistency,
+                admin_integrity_scan,
+                admin_recompute_storage,
+                admin_backfill_perceptual_hashes,
+                // Events
+                grouping_events,
+                // Metrics
+                get_metrics
             ],
         )
         .mount(
@@ -187,23 +443,11 @@ async fn rocket() -> _ {
         )
         .mount("/", rocket_cors::catch_all_options_routes())
         .attach(cors.clone())
+        .attach(MetricsFairing)
+        .attach(CompressionFairing {
+            min_size_bytes: CONFIG.compression_min_size,
+        })
+        .attach(MaintenanceFairing)
         .manage(cors)
         .register("/", catchers![bad_request, unauthorized, not_found, unprocessable_entity, internal_error])
 }
-
-/// CORS configuration
-fn cors_options() -> Cors {
-    let origin = [get_frontend_host(), get_backend_host()];
-    CorsOptions {
-        allowed_origins: AllowedOrigins::some_exact(&origin),
-        allowed_methods: vec![Method::Get, Method::Post, Method::Put, Method::Patch, Method::Delete]
-            .into_iter()
-            .map(From::from)
-            .collect(),
-        allowed_headers: AllowedHeaders::all(),
-        allow_credentials: true,
-        ..Default::default()
-    }
-    .to_cors()
-    .expect("Error while building CORS")
-}
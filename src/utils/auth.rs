@@ -75,6 +75,35 @@ impl OpenApiFromRequest<'_> for User {
         ))
     }
 }
+/// Request Guard for an authenticated admin user.
+/// Uses the [`User`] request guard, then checks that the user has the admin status.
+/// - Throw the [`User`] request guard errors if the user is not authenticated.
+/// - Throw `UserNotAdmin` if the user is not an admin.
+pub struct AdminUser(pub User);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = ErrorResponder;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match User::from_request(request).await {
+            Outcome::Success(user) => {
+                if user.status != UserStatus::Admin {
+                    return Outcome::Error((Status::Unauthorized, ErrorType::UserNotAdmin.res_no_rollback()));
+                }
+                Outcome::Success(AdminUser(user))
+            }
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+/// OpenAPI documentation for the AdminUser request guard, same as the [`User`] one.
+impl OpenApiFromRequest<'_> for AdminUser {
+    fn from_request_input(gen: &mut OpenApiGenerator, name: String, required: bool) -> rocket_okapi::Result<RequestHeaderInput> {
+        User::from_request_input(gen, name, required)
+    }
+}
+
 /// Request Guard with the only purpose of extracting the user id and auth token from the headers.
 pub struct UserAuthInfo {
     pub user_id: Option<i32>,
@@ -0,0 +1,90 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Parameter, ParameterValue};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Single byte range requested with the HTTP `Range` header (multiple ranges are not supported).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteRange {
+    /// `bytes=start-end`, both included
+    Bounded { start: u64, end: u64 },
+    /// `bytes=start-`, up to the end
+    From { start: u64 },
+    /// `bytes=-length`, the last `length` bytes
+    Suffix { length: u64 },
+}
+
+impl FromStr for ByteRange {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let range = s.trim().strip_prefix("bytes=").ok_or(())?;
+        let (start, end) = range.split_once('-').ok_or(())?;
+        let parse = |bound: &str| bound.trim().parse::<u64>().map_err(|_| ());
+        match (start.trim().is_empty(), end.trim().is_empty()) {
+            (false, false) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if end < start {
+                    return Err(());
+                }
+                Ok(ByteRange::Bounded { start, end })
+            }
+            (false, true) => Ok(ByteRange::From { start: parse(start)? }),
+            (true, false) => match parse(end)? {
+                0 => Err(()),
+                length => Ok(ByteRange::Suffix { length }),
+            },
+            (true, true) => Err(()),
+        }
+    }
+}
+/// Formats the range as a `Range` header value.
+impl Display for ByteRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ByteRange::Bounded { start, end } => write!(f, "bytes={}-{}", start, end),
+            ByteRange::From { start } => write!(f, "bytes={}-", start),
+            ByteRange::Suffix { length } => write!(f, "bytes=-{}", length),
+        }
+    }
+}
+
+/// Request guard reading the `Range` header. Forwards if the header is missing or is not a single valid byte range,
+/// then the whole content should be returned, as an `Option<ByteRange>` is None.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ByteRange {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one("Range").map(ByteRange::from_str) {
+            Some(Ok(range)) => Outcome::Success(range),
+            _ => Outcome::Forward(Status::Ok),
+        }
+    }
+}
+/// OpenAPI documentation for the ByteRange request guard.
+impl OpenApiFromRequest<'_> for ByteRange {
+    fn from_request_input(gen: &mut OpenApiGenerator, _: String, _: bool) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: "Range".to_string(),
+            location: "header".to_string(),
+            description: Some("Single byte range to fetch, e.g. `bytes=0-1023`".to_string()),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema: gen.json_schema::<String>(),
+                example: None,
+                examples: None,
+            },
+            extensions: Default::default(),
+        }))
+    }
+}
@@ -0,0 +1,109 @@
+use serde::de::{Error, Unexpected, Visitor};
+use serde::{Deserializer, Serializer};
+use std::fmt;
+
+/// Serde (de)serialization of an RGB color stored as bytes.
+/// Colors are serialized as a `#RRGGBB` hex string. A hex string (with or without `#`) or an array of bytes is accepted when deserializing.
+/// The number of bytes is not checked here, see [`crate::database::tag::tag::Tag::validate_color`].
+pub mod hex_color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(color: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+/// Serde (de)serialization of an optional RGB color, see [`hex_color`]. `None` is (de)serialized as `null`.
+pub mod hex_color_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(color: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => serializer.serialize_some(&to_hex(color)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        deserializer.deserialize_option(OptionColorVisitor)
+    }
+}
+
+/// Colors given to the groups created by the grouping strategies, see [`palette_color`]
+pub const GROUPS_PALETTE: [[u8; 3]; 12] = [
+    [0xE5, 0x73, 0x73],
+    [0xF0, 0x62, 0x92],
+    [0xBA, 0x68, 0xC8],
+    [0x95, 0x75, 0xCD],
+    [0x79, 0x86, 0xCB],
+    [0x64, 0xB5, 0xF6],
+    [0x4D, 0xD0, 0xE1],
+    [0x4D, 0xB6, 0xAC],
+    [0x81, 0xC7, 0x84],
+    [0xDC, 0xE7, 0x75],
+    [0xFF, 0xB7, 0x4D],
+    [0xA1, 0x88, 0x7F],
+];
+
+/// Color of the palette for a value (e.g. the name of a group), always the same for the same value.
+/// Uses the FNV-1a hash, which doesn't depend on the platform nor on the Rust version, unlike the std hashers.
+pub fn palette_color(value: &str) -> Vec<u8> {
+    let hash = value
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    GROUPS_PALETTE[(hash % GROUPS_PALETTE.len() as u64) as usize].to_vec()
+}
+
+/// Formats bytes as a `#RRGGBB` like uppercase hex string
+pub fn to_hex(color: &[u8]) -> String {
+    format!("#{}", hex::encode_upper(color))
+}
+
+/// Parses a hex string, with or without a leading `#`, to bytes
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    hex::decode(hex.strip_prefix('#').unwrap_or(hex)).ok()
+}
+
+struct OptionColorVisitor;
+impl<'de> Visitor<'de> for OptionColorVisitor {
+    type Value = Option<Vec<u8>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("null, a #RRGGBB hex color string or an array of bytes")
+    }
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(ColorVisitor).map(Some)
+    }
+}
+
+struct ColorVisitor;
+impl<'de> Visitor<'de> for ColorVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a #RRGGBB hex color string or an array of bytes")
+    }
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        from_hex(value).ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+    }
+    fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(value.to_vec())
+    }
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
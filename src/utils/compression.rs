@@ -0,0 +1,112 @@
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header};
+use rocket::{Request, Response};
+use std::io::{Cursor, Write};
+
+/// Content encoding of a compressed response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Deflate,
+}
+impl ContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Encoding to use from an `Accept-Encoding` header value, gzip being preferred over deflate.
+    /// Encodings refused with `q=0` are skipped, `*` accepting the encodings that are not listed.
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let listed: Vec<(&str, bool)> = accept_encoding
+            .split(',')
+            .filter_map(|value| {
+                let mut parts = value.split(';').map(str::trim);
+                let name = parts.next().filter(|name| !name.is_empty())?;
+                let refused = parts.any(|param| param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()).is_some_and(|q| q <= 0.0));
+                Some((name, !refused))
+            })
+            .collect();
+        let listed_as = |name: &str| {
+            listed
+                .iter()
+                .find(|(listed, _)| listed.eq_ignore_ascii_case(name))
+                .map(|(_, accepted)| *accepted)
+        };
+        let accepts = |name: &str| listed_as(name).or_else(|| listed_as("*")).unwrap_or(false);
+        if accepts("gzip") {
+            Some(ContentEncoding::Gzip)
+        } else if accepts("deflate") {
+            Some(ContentEncoding::Deflate)
+        } else {
+            None
+        }
+    }
+
+    pub fn compress(&self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            ContentEncoding::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Fairing compressing the JSON responses of at least `min_size_bytes` bytes, with the encoding negotiated from the
+/// `Accept-Encoding` header of the request. Other responses, like the pictures and the archives, are left as they are.
+pub struct CompressionFairing {
+    pub min_size_bytes: usize,
+}
+
+#[rocket::async_trait]
+impl Fairing for CompressionFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "JSON responses compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if res.content_type() != Some(ContentType::JSON) || res.headers().contains("Content-Encoding") {
+            return;
+        }
+        // The body depends on the Accept-Encoding header of the request, even when it is left uncompressed
+        res.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+        let Some(encoding) = req.headers().get("Accept-Encoding").find_map(ContentEncoding::negotiate) else {
+            return;
+        };
+        let body = match res.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to read the response body to compress: {}", e);
+                return;
+            }
+        };
+        if body.len() < self.min_size_bytes {
+            res.set_sized_body(body.len(), Cursor::new(body));
+            return;
+        }
+        match encoding.compress(&body) {
+            Ok(compressed) => {
+                res.set_header(Header::new("Content-Encoding", encoding.as_str()));
+                res.set_sized_body(compressed.len(), Cursor::new(compressed));
+            }
+            Err(e) => {
+                warn!("Failed to compress the response: {}", e);
+                res.set_sized_body(body.len(), Cursor::new(body));
+            }
+        }
+    }
+}
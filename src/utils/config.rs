@@ -0,0 +1,238 @@
+use crate::database::schema::ConfirmationAction;
+use crate::utils::validation::PASSWORD_MAX_LENGTH;
+use lazy_static::lazy_static;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Maximum number of digits of a confirmation code: codes are stored as `i16` (max 32767), so only 4 full digits fit.
+pub const CONFIRMATION_CODE_MAX_DIGITS: u32 = 4;
+
+lazy_static! {
+    /// Backend configuration, read from the environment variables on first access (see [`Config::from_env`]).
+    /// Must be initialized after the `.env` file has been loaded.
+    pub static ref CONFIG: Config = Config::from_env();
+}
+
+/// Backend configuration settings that can be tuned through environment variables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Number of digits of the emailed confirmation codes (`CONFIRMATION_CODE_DIGITS`, 1 to 4)
+    pub confirmation_code_digits: u32,
+    /// Validity window of the signup confirmations in minutes (`CONFIRMATION_SIGNUP_MINUTES`)
+    pub confirmation_signup_minutes: i64,
+    /// Validity window of the signin confirmations in minutes (`CONFIRMATION_SIGNIN_MINUTES`)
+    pub confirmation_signin_minutes: i64,
+    /// Validity window of the account deletion confirmations in minutes (`CONFIRMATION_DELETE_ACCOUNT_MINUTES`)
+    pub confirmation_delete_account_minutes: i64,
+    /// Create the default arrangements ("By Month", "By Camera") for new users (`DEFAULT_ARRANGEMENTS`)
+    pub default_arrangements: bool,
+    /// Origins allowed to make CORS requests, comma-separated (`CORS_ALLOWED_ORIGINS`). Entries prefixed with `regex:` are regular expressions.
+    /// Defaults to the frontend and backend hosts when empty.
+    pub cors_allowed_origins: Vec<String>,
+    /// Formats accepted for uploaded pictures, detected from the file content, comma-separated (`SUPPORTED_PICTURE_FORMATS`).
+    /// Names are the lowercase `image` crate formats (`jpeg`, `png`, `tiff`...), plus `heif`.
+    pub supported_picture_formats: Vec<String>,
+    /// Pictures of confirmed copied shares count toward the storage usage of the recipient (`COPIED_SHARES_COUNT_STORAGE`)
+    pub copied_shares_count_storage: bool,
+    /// Number of pictures per page of the picture queries when the client does not request a page size (`DEFAULT_PAGE_SIZE`)
+    pub default_page_size: i64,
+    /// Maximum number of pictures per page of the picture queries, larger requested page sizes are clamped (`MAX_PAGE_SIZE`)
+    pub max_page_size: i64,
+    /// Base directory of the temporary files of the uploads and downloads (`TEMP_DIR`)
+    pub temp_dir: String,
+    /// Age in hours after which leftover temporary files are removed on startup (`TEMP_FILES_MAX_AGE_HOURS`)
+    pub temp_files_max_age_hours: i64,
+    /// Uploads fail and are rolled back when a thumbnail can't be generated or stored (`STRICT_THUMBNAILS`).
+    /// Otherwise, the picture is kept and the thumbnail error is reported in the response. Can be overridden per request.
+    pub strict_thumbnails: bool,
+    /// Minimum number of characters of the passwords (`PASSWORD_MIN_LENGTH`), at most [`PASSWORD_MAX_LENGTH`]
+    pub password_min_length: usize,
+    /// Passwords must contain a lowercase letter (`PASSWORD_REQUIRE_LOWERCASE`)
+    pub password_require_lowercase: bool,
+    /// Passwords must contain an uppercase letter (`PASSWORD_REQUIRE_UPPERCASE`)
+    pub password_require_uppercase: bool,
+    /// Passwords must contain a digit (`PASSWORD_REQUIRE_DIGIT`)
+    pub password_require_digit: bool,
+    /// Passwords must contain a special character, neither a letter nor a digit (`PASSWORD_REQUIRE_SPECIAL`)
+    pub password_require_special: bool,
+    /// Maximum number of arrangements of a user, default arrangements included (`MAX_ARRANGEMENTS_PER_USER`)
+    pub max_arrangements_per_user: i64,
+    /// Maximum number of tag groups of a user (`MAX_TAG_GROUPS_PER_USER`)
+    pub max_tag_groups_per_user: i64,
+    /// Maximum number of ids returned by a query of the ids of the matching pictures (`MAX_QUERY_IDS`), larger result sets are refused
+    pub max_query_ids: i64,
+    /// Maximum number of pictures of a bulk operation, like editing the tags or getting the details of several pictures (`MAX_BATCH_PICTURES`).
+    /// Larger lists are refused, clients must split them in several requests.
+    pub max_batch_pictures: usize,
+    /// Minimum size in bytes of the JSON responses compressed for the clients accepting it (`COMPRESSION_MIN_SIZE`).
+    /// Smaller responses are not worth compressing.
+    pub compression_min_size: usize,
+    /// New users must confirm their sign in with a code sent by email when they have no TOTP (`EMAIL_TFA_DEFAULT`)
+    pub email_tfa_default: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            confirmation_code_digits: 4,
+            confirmation_signup_minutes: 15,
+            confirmation_signin_minutes: 15,
+            confirmation_delete_account_minutes: 15,
+            default_arrangements: true,
+            cors_allowed_origins: vec![],
+            supported_picture_formats: ["jpeg", "png", "gif", "webp", "tiff", "bmp", "avif", "heif"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            copied_shares_count_storage: true,
+            default_page_size: 100,
+            max_page_size: 200,
+            temp_dir: String::from("./picture-temp"),
+            temp_files_max_age_hours: 24,
+            strict_thumbnails: false,
+            password_min_length: 8,
+            password_require_lowercase: true,
+            password_require_uppercase: true,
+            password_require_digit: true,
+            password_require_special: false,
+            max_arrangements_per_user: 200,
+            max_tag_groups_per_user: 200,
+            max_query_ids: 100_000,
+            max_batch_pictures: 1000,
+            compression_min_size: 1024,
+            email_tfa_default: false,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration from the environment variables, using the default value of each unset variable.
+    /// Panics if a variable is set with an invalid value.
+    pub fn from_env() -> Self {
+        let default = Config::default();
+        let config = Config {
+            confirmation_code_digits: env_or("CONFIRMATION_CODE_DIGITS", default.confirmation_code_digits),
+            confirmation_signup_minutes: env_or("CONFIRMATION_SIGNUP_MINUTES", default.confirmation_signup_minutes),
+            confirmation_signin_minutes: env_or("CONFIRMATION_SIGNIN_MINUTES", default.confirmation_signin_minutes),
+            confirmation_delete_account_minutes: env_or("CONFIRMATION_DELETE_ACCOUNT_MINUTES", default.confirmation_delete_account_minutes),
+            default_arrangements: env_or("DEFAULT_ARRANGEMENTS", default.default_arrangements),
+            cors_allowed_origins: env_list("CORS_ALLOWED_ORIGINS").unwrap_or(default.cors_allowed_origins),
+            supported_picture_formats: env_list("SUPPORTED_PICTURE_FORMATS").unwrap_or(default.supported_picture_formats),
+            copied_shares_count_storage: env_or("COPIED_SHARES_COUNT_STORAGE", default.copied_shares_count_storage),
+            default_page_size: env_or("DEFAULT_PAGE_SIZE", default.default_page_size),
+            max_page_size: env_or("MAX_PAGE_SIZE", default.max_page_size),
+            temp_dir: env_or("TEMP_DIR", default.temp_dir),
+            temp_files_max_age_hours: env_or("TEMP_FILES_MAX_AGE_HOURS", default.temp_files_max_age_hours),
+            strict_thumbnails: env_or("STRICT_THUMBNAILS", default.strict_thumbnails),
+            password_min_length: env_or("PASSWORD_MIN_LENGTH", default.password_min_length),
+            password_require_lowercase: env_or("PASSWORD_REQUIRE_LOWERCASE", default.password_require_lowercase),
+            password_require_uppercase: env_or("PASSWORD_REQUIRE_UPPERCASE", default.password_require_uppercase),
+            password_require_digit: env_or("PASSWORD_REQUIRE_DIGIT", default.password_require_digit),
+            password_require_special: env_or("PASSWORD_REQUIRE_SPECIAL", default.password_require_special),
+            max_arrangements_per_user: env_or("MAX_ARRANGEMENTS_PER_USER", default.max_arrangements_per_user),
+            max_tag_groups_per_user: env_or("MAX_TAG_GROUPS_PER_USER", default.max_tag_groups_per_user),
+            max_query_ids: env_or("MAX_QUERY_IDS", default.max_query_ids),
+            max_batch_pictures: env_or("MAX_BATCH_PICTURES", default.max_batch_pictures),
+            compression_min_size: env_or("COMPRESSION_MIN_SIZE", default.compression_min_size),
+            email_tfa_default: env_or("EMAIL_TFA_DEFAULT", default.email_tfa_default),
+        };
+        config.validate().unwrap_or_else(|e| panic!("Invalid configuration: {}", e));
+        config
+    }
+
+    /// Checks the consistency of the configuration values.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.confirmation_code_digits == 0 || self.confirmation_code_digits > CONFIRMATION_CODE_MAX_DIGITS {
+            return Err(format!(
+                "CONFIRMATION_CODE_DIGITS must be between 1 and {}, got {}",
+                CONFIRMATION_CODE_MAX_DIGITS, self.confirmation_code_digits
+            ));
+        }
+        if self.confirmation_signup_minutes <= 0 || self.confirmation_signin_minutes <= 0 || self.confirmation_delete_account_minutes <= 0 {
+            return Err("Confirmation validity windows must be positive".to_string());
+        }
+        if self.supported_picture_formats.is_empty() {
+            return Err("SUPPORTED_PICTURE_FORMATS must contain at least one format".to_string());
+        }
+        if self.default_page_size <= 0 || self.default_page_size > self.max_page_size {
+            return Err(format!(
+                "DEFAULT_PAGE_SIZE must be between 1 and MAX_PAGE_SIZE ({}), got {}",
+                self.max_page_size, self.default_page_size
+            ));
+        }
+        if self.temp_dir.trim().is_empty() {
+            return Err("TEMP_DIR must not be empty".to_string());
+        }
+        if self.temp_files_max_age_hours <= 0 {
+            return Err("TEMP_FILES_MAX_AGE_HOURS must be positive".to_string());
+        }
+        if self.password_min_length == 0 || self.password_min_length > PASSWORD_MAX_LENGTH {
+            return Err(format!(
+                "PASSWORD_MIN_LENGTH must be between 1 and {}, got {}",
+                PASSWORD_MAX_LENGTH, self.password_min_length
+            ));
+        }
+        if self.max_arrangements_per_user <= 0 || self.max_tag_groups_per_user <= 0 {
+            return Err("MAX_ARRANGEMENTS_PER_USER and MAX_TAG_GROUPS_PER_USER must be positive".to_string());
+        }
+        if self.max_query_ids <= 0 {
+            return Err("MAX_QUERY_IDS must be positive".to_string());
+        }
+        if self.max_batch_pictures == 0 {
+            return Err("MAX_BATCH_PICTURES must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Directory of the temporary original pictures, being uploaded or having their metadata stripped.
+    pub fn original_temp_dir(&self) -> PathBuf {
+        Path::new(&self.temp_dir).join("original")
+    }
+    /// Directory of the temporary thumbnails, being generated.
+    pub fn thumbs_temp_dir(&self) -> PathBuf {
+        Path::new(&self.temp_dir).join("thumbs")
+    }
+
+    /// Page size of a picture query: the requested one clamped between 1 and the maximum, or the default one.
+    pub fn page_size(&self, requested: Option<i64>) -> i64 {
+        requested.map_or(self.default_page_size, |page_size| page_size.clamp(1, self.max_page_size))
+    }
+
+    /// Validity window in minutes of a confirmation of the given action.
+    pub fn confirmation_max_minutes(&self, action: &ConfirmationAction) -> i64 {
+        match action {
+            ConfirmationAction::Signup => self.confirmation_signup_minutes,
+            ConfirmationAction::Signin => self.confirmation_signin_minutes,
+            ConfirmationAction::DeleteAccount => self.confirmation_delete_account_minutes,
+        }
+    }
+}
+
+/// Reads and parses an environment variable, returning `default` if it is not set.
+fn env_or<T: FromStr>(key: &str, default: T) -> T
+where
+    T::Err: Debug,
+{
+    match std::env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .unwrap_or_else(|e| panic!("Environment variable {} has an invalid value: {:?}", key, e)),
+        Err(_) => default,
+    }
+}
+
+/// Reads a comma-separated environment variable, ignoring empty entries. Returns `None` if it is not set.
+fn env_list(key: &str) -> Option<Vec<String>> {
+    std::env::var(key).ok().map(|value| parse_list(&value))
+}
+/// Splits a comma-separated list, trimming the entries and ignoring the empty ones.
+pub fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+        .collect()
+}
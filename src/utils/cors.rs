@@ -0,0 +1,38 @@
+use crate::utils::utils::{get_backend_host, get_frontend_host};
+use itertools::{Either, Itertools};
+use rocket::http::Method;
+use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors, CorsOptions};
+
+/// Prefix of the `CORS_ALLOWED_ORIGINS` entries that are regular expressions matched against the origin, instead of exact origins.
+pub const CORS_REGEX_PREFIX: &str = "regex:";
+
+/// Origins allowed to make CORS requests: the configured ones (`CORS_ALLOWED_ORIGINS`),
+/// or the frontend and backend hosts when none is configured.
+pub fn cors_allowed_origins(configured_origins: &[String]) -> AllowedOrigins {
+    if configured_origins.is_empty() {
+        return AllowedOrigins::some_exact(&[get_frontend_host(), get_backend_host()]);
+    }
+    let (regex, exact): (Vec<&str>, Vec<&str>) = configured_origins
+        .iter()
+        .partition_map(|origin| match origin.strip_prefix(CORS_REGEX_PREFIX) {
+            Some(regex) => Either::Left(regex),
+            None => Either::Right(origin.as_str()),
+        });
+    AllowedOrigins::some(&exact, &regex)
+}
+
+/// CORS configuration
+pub fn cors_options(configured_origins: &[String]) -> Cors {
+    CorsOptions {
+        allowed_origins: cors_allowed_origins(configured_origins),
+        allowed_methods: vec![Method::Get, Method::Post, Method::Put, Method::Patch, Method::Delete]
+            .into_iter()
+            .map(From::from)
+            .collect(),
+        allowed_headers: AllowedHeaders::all(),
+        allow_credentials: true,
+        ..Default::default()
+    }
+    .to_cors()
+    .expect("Error while building CORS")
+}
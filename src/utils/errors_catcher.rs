@@ -3,6 +3,7 @@ use diesel::result::Error;
 use diesel::Connection;
 use enum_kinds::EnumKind;
 use rexiv2::Rexiv2Error;
+use rocket::http::Header;
 use rocket::serde::json::Json;
 use rocket::Request;
 use rocket_okapi::gen::OpenApiGenerator;
@@ -21,10 +22,17 @@ pub enum ErrorResponder {
     Unauthorized(Json<ErrorResponse>),
     #[response(status = 404, content_type = "json")]
     NotFound(Json<ErrorResponse>),
+    #[response(status = 409, content_type = "json")]
+    Conflict(Json<ErrorResponse>),
+    /// Carries the `Content-Range: bytes */<size>` header of the requested resource.
+    #[response(status = 416, content_type = "json")]
+    RangeNotSatisfiable(Json<ErrorResponse>, Header<'static>),
     #[response(status = 422, content_type = "json")]
     UnprocessableEntity(Json<ErrorResponse>),
     #[response(status = 500, content_type = "json")]
     InternalError(Json<ErrorResponse>),
+    #[response(status = 503, content_type = "json")]
+    ServiceUnavailable(Json<ErrorResponse>),
 }
 /// Convert Diesel [`Error`] to [`ErrorResponder`]
 impl From<Error> for ErrorResponder {
@@ -40,8 +48,11 @@ impl ErrorResponder {
             ErrorResponder::BadRequest(json) => json,
             ErrorResponder::Unauthorized(json) => json,
             ErrorResponder::NotFound(json) => json,
+            ErrorResponder::Conflict(json) => json,
+            ErrorResponder::RangeNotSatisfiable(json, _) => json,
             ErrorResponder::UnprocessableEntity(json) => json,
             ErrorResponder::InternalError(json) => json,
+            ErrorResponder::ServiceUnavailable(json) => json,
         }
         .rollback
     }
@@ -62,6 +73,16 @@ impl ErrorResponder {
                 json.rollback = rollback;
                 ErrorResponder::NotFound(json)
             }
+            ErrorResponder::Conflict(json) => {
+                let mut json = Json(json.0.clone());
+                json.rollback = rollback;
+                ErrorResponder::Conflict(json)
+            }
+            ErrorResponder::RangeNotSatisfiable(json, content_range) => {
+                let mut json = Json(json.0.clone());
+                json.rollback = rollback;
+                ErrorResponder::RangeNotSatisfiable(json, content_range.clone())
+            }
             ErrorResponder::UnprocessableEntity(json) => {
                 let mut json = Json(json.0.clone());
                 json.rollback = rollback;
@@ -72,6 +93,11 @@ impl ErrorResponder {
                 json.rollback = rollback;
                 ErrorResponder::InternalError(json)
             }
+            ErrorResponder::ServiceUnavailable(json) => {
+                let mut json = Json(json.0.clone());
+                json.rollback = rollback;
+                ErrorResponder::ServiceUnavailable(json)
+            }
         }
     }
 }
@@ -96,8 +122,11 @@ impl From<ErrorResponder> for ErrorResponse {
             ErrorResponder::BadRequest(json) => json.into_inner(),
             ErrorResponder::Unauthorized(json) => json.into_inner(),
             ErrorResponder::NotFound(json) => json.into_inner(),
+            ErrorResponder::Conflict(json) => json.into_inner(),
+            ErrorResponder::RangeNotSatisfiable(json, _) => json.into_inner(),
             ErrorResponder::UnprocessableEntity(json) => json.into_inner(),
             ErrorResponder::InternalError(json) => json.into_inner(),
+            ErrorResponder::ServiceUnavailable(json) => json.into_inner(),
         }
     }
 }
@@ -109,8 +138,10 @@ pub enum ErrorType {
     BadRequest,
     Unauthorized,
     NotFound(String),
+    Conflict(String), // The resource has been modified concurrently
     UnprocessableEntity(String),
     InternalError(String),
+    ServiceUnavailable(String), // A backend service is unreachable, the request can be retried later
     // Form validation (see UnprocessableEntity for type check related errors)
     InvalidInput(String),
     // User request guard
@@ -136,6 +167,7 @@ pub enum ErrorType {
     // Pictures and files
     UnableToLoadExifMetadata(Rexiv2Error),
     S3Error(String),
+    RangeNotSatisfiable(u64), // Size of the picture, none of its bytes are in the requested range
     UnableToCreateThumbnail(String),
     UnableToCreateBlurhash(String),
     PictureNotFound,
@@ -172,10 +204,16 @@ impl ErrorType {
             ErrorType::BadRequest => ErrorResponder::BadRequest(Self::create_response("Bad request".to_string(), kind, rollback)),
             ErrorType::Unauthorized => ErrorResponder::Unauthorized(Self::create_response("Unauthorized".to_string(), kind, rollback)),
             ErrorType::NotFound(path) => ErrorResponder::NotFound(Self::create_response(format!("Not found: {}", path), kind, rollback)),
+            ErrorType::Conflict(msg) => ErrorResponder::Conflict(Self::create_response(msg, kind, rollback)),
             ErrorType::UnprocessableEntity(msg) => ErrorResponder::UnprocessableEntity(Self::create_response(msg, kind, rollback)),
             ErrorType::InternalError(msg) => {
                 ErrorResponder::InternalError(Self::create_response(format!("Internal error: {}", msg).to_string(), kind, rollback))
             }
+            ErrorType::ServiceUnavailable(msg) => ErrorResponder::ServiceUnavailable(Self::create_response(
+                format!("Service unavailable: {}, please retry later", msg),
+                kind,
+                rollback,
+            )),
             // Form validation (see UnprocessableEntity for type check related errors)
             ErrorType::InvalidInput(msg) => ErrorResponder::UnprocessableEntity(Self::create_response(msg, kind, rollback)),
             // Sign in / status types
@@ -217,6 +255,10 @@ impl ErrorType {
                 rollback,
             )),
             ErrorType::S3Error(msg) => ErrorResponder::InternalError(Self::create_response(format!("S3 error: {}", msg), kind, rollback)),
+            ErrorType::RangeNotSatisfiable(size) => ErrorResponder::RangeNotSatisfiable(
+                Self::create_response("Requested range not satisfiable".to_string(), kind, rollback),
+                Header::new("Content-Range", format!("bytes */{}", size)),
+            ),
             ErrorType::UnableToCreateThumbnail(msg) => {
                 ErrorResponder::InternalError(Self::create_response(format!("Unable to create thumbnail: {}", msg), kind, rollback))
             }
@@ -1,10 +1,29 @@
 use crate::database::picture::picture::Picture;
 use crate::database::schema::PictureOrientation;
+use crate::utils::config::CONFIG;
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
 use chrono::{Local, NaiveDateTime};
 use num_rational::Ratio;
+use rand::random;
 use rexiv2::Metadata;
 
+/// Personal information tags removed by [`strip_private_metadata`], in addition to the location tags.
+const PERSONAL_TAGS: [&str; 12] = [
+    "Exif.Image.Artist",
+    "Exif.Image.XPAuthor",
+    "Exif.Image.XPComment",
+    "Exif.Image.CameraSerialNumber",
+    "Exif.Image.HostComputer",
+    "Exif.Photo.CameraOwnerName",
+    "Exif.Photo.BodySerialNumber",
+    "Exif.Photo.LensSerialNumber",
+    "Exif.Photo.UserComment",
+    "Xmp.dc.creator",
+    "Xmp.aux.SerialNumber",
+    "Xmp.exifEX.BodySerialNumber",
+];
+
 impl From<Metadata> for Picture {
     /// Creates a Picture from a rexiv2 Metadata
     /// The picture id, name, owner_id and author_id are set to 0 or empty String.
@@ -21,6 +40,7 @@ impl From<Metadata> for Picture {
         let altitude = gps_info.map(|g| g.altitude as i16);
 
         let exposure_time = metadata.get_tag_rational("Exif.Photo.ExposureTime");
+        let (width, height) = clamp_dimensions(metadata.get_pixel_width(), metadata.get_pixel_height());
 
         let orientation = match metadata.get_tag_numeric("Exif.Image.Orientation") {
             1 => PictureOrientation::Normal,
@@ -48,8 +68,8 @@ impl From<Metadata> for Picture {
             longitude,
             altitude,
             orientation,
-            width: metadata.get_pixel_width() as i16,
-            height: metadata.get_pixel_height() as i16,
+            width,
+            height,
             camera_brand: metadata.get_tag_string("Exif.Image.Make").ok(),
             camera_model: metadata.get_tag_string("Exif.Image.Model").ok(),
             focal_length: rational_to_big_decimal(metadata.get_tag_rational("Exif.Photo.FocalLengthIn35mmFilm"), 2),
@@ -59,6 +79,8 @@ impl From<Metadata> for Picture {
             f_number: rational_to_big_decimal(metadata.get_tag_rational("Exif.Photo.FNumber"), 1),
             size_ko: 0,
             blurhash: None,
+            format: None,
+            favorite: false,
         }
     }
 }
@@ -96,10 +118,28 @@ impl From<Option<Metadata>> for Picture {
             f_number: None,
             size_ko: 0,
             blurhash: None,
+            format: None,
+            favorite: false,
         }
     }
 }
 
+/// Converts pixel dimensions to the stored `i16` dimensions. Dimensions above `i16::MAX` are scaled down
+/// proportionally, keeping the aspect ratio of the picture, and negative dimensions are set to 0.
+pub fn clamp_dimensions(width: i32, height: i32) -> (i16, i16) {
+    let (width, height) = (width.max(0) as i64, height.max(0) as i64);
+    let max = i16::MAX as i64;
+    if width <= max && height <= max {
+        return (width as i16, height as i16);
+    }
+    warn!("Picture dimensions {}x{} exceed {} pixels, scaling them down", width, height, max);
+    if width >= height {
+        (max as i16, (height * max / width) as i16)
+    } else {
+        ((width * max / height) as i16, max as i16)
+    }
+}
+
 /// Converts a GPS value to a big decimal with a given number of decimals
 /// and a modulo between -angle_max and angle_max
 fn gps_val_to_big_decimal(gps_val: Option<f64>, angle_max: i32, decimals: i64) -> Option<BigDecimal> {
@@ -147,3 +187,33 @@ fn extract_iso(metadata: &Metadata) -> Option<i32> {
     }
     None
 }
+
+/// Whether an EXIF or XMP tag holds the location of the picture or personal information.
+/// The orientation, dates and shooting parameters are kept.
+pub fn is_private_tag(tag: &str) -> bool {
+    tag.starts_with("Exif.GPSInfo.") || tag.starts_with("Xmp.exif.GPS") || PERSONAL_TAGS.contains(&tag)
+}
+
+/// Remove the location and personal information tags from a picture (see [`is_private_tag`]), and all its IPTC tags.
+/// The picture is written to a temporary file, exiv2 only saving metadata to files.
+pub fn strip_private_metadata(data: &[u8]) -> Result<Vec<u8>, ErrorResponder> {
+    let metadata = Metadata::new_from_buffer(data).map_err(|e| ErrorType::UnableToLoadExifMetadata(e).res())?;
+    let tags = metadata
+        .get_exif_tags()
+        .unwrap_or_default()
+        .into_iter()
+        .chain(metadata.get_xmp_tags().unwrap_or_default());
+    for tag in tags.filter(|tag| is_private_tag(tag)) {
+        metadata.clear_tag(&tag);
+    }
+    metadata.delete_gps_info();
+    metadata.clear_iptc();
+
+    let path = CONFIG.original_temp_dir().join(format!("{}-stripped", random::<u64>()));
+    let stripped = std::fs::write(&path, data)
+        .map_err(|e| ErrorType::InternalError(format!("Unable to write temporary picture: {}", e)).res())
+        .and_then(|_| metadata.save_to_file(&path).map_err(|e| ErrorType::UnableToLoadExifMetadata(e).res()))
+        .and_then(|_| std::fs::read(&path).map_err(|e| ErrorType::InternalError(format!("Unable to read temporary picture: {}", e)).res()));
+    let _ = std::fs::remove_file(&path);
+    stripped
+}
@@ -0,0 +1,54 @@
+use crate::database::database::DBPool;
+use crate::database::group::group::Group;
+use crate::utils::errors_catcher::{err_transaction, ErrorResponse};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Orbit, Rocket};
+use std::time::Duration;
+
+/// Interval between two runs of the maintenance tasks.
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Fairing spawning, once the server is launched, a background task periodically running the maintenance tasks:
+///  - Deleting the groups marked as to be deleted once they no longer contain any picture.
+pub struct MaintenanceFairing;
+
+#[rocket::async_trait]
+impl Fairing for MaintenanceFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Maintenance tasks",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(pool) = rocket.state::<DBPool>().cloned() else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+            loop {
+                interval.tick().await;
+                run_maintenance(&pool);
+            }
+        });
+    }
+}
+
+fn run_maintenance(pool: &DBPool) {
+    let conn = &mut match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!("Maintenance skipped, no database connection available: {}", e);
+            return;
+        }
+    };
+    match err_transaction(conn, |conn| Group::delete_to_be_deleted_empty(conn, None)) {
+        Ok(group_ids) if !group_ids.is_empty() => info!("Maintenance: deleted {} empty groups marked as to be deleted", group_ids.len()),
+        Ok(_) => {}
+        Err(e) => warn!(
+            "Maintenance: failed to delete empty groups marked as to be deleted: {:?}",
+            ErrorResponse::from(e)
+        ),
+    }
+}
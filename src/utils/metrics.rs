@@ -0,0 +1,135 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Status;
+use rocket::{Build, Data, Request, Response, Rocket};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Traffic metrics of a route, for a given method.
+#[derive(Debug, Default, Clone)]
+struct RouteMetrics {
+    requests: BTreeMap<u16, u64>, // status class (2 for 2xx...) -> number of requests
+    duration: Duration,
+    size_bytes: u64,
+}
+impl RouteMetrics {
+    fn count(&self) -> u64 {
+        self.requests.values().sum()
+    }
+}
+
+/// In-memory registry of the requests handled by the server, by method and route.
+/// Managed by Rocket once the [`MetricsFairing`] is attached.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    routes: Mutex<BTreeMap<(String, String), RouteMetrics>>, // (method, route) -> metrics
+}
+
+impl Metrics {
+    /// Record a handled request. `route` is the URI pattern of the matched route, to keep a bounded number of series.
+    pub fn record(&self, method: &str, route: &str, status: Status, duration: Duration, size_bytes: u64) {
+        let mut routes = self.routes.lock().unwrap();
+        let metrics = routes.entry((method.to_string(), route.to_string())).or_default();
+        *metrics.requests.entry(status.code / 100).or_default() += 1;
+        metrics.duration += duration;
+        metrics.size_bytes += size_bytes;
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap().clone();
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "# HELP archypix_http_requests_total Number of handled HTTP requests, by route and status class."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE archypix_http_requests_total counter").unwrap();
+        for ((method, route), metrics) in routes.iter() {
+            for (class, count) in metrics.requests.iter() {
+                writeln!(
+                    out,
+                    "archypix_http_requests_total{{{},status_class=\"{}xx\"}} {}",
+                    labels(method, route),
+                    class,
+                    count
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(
+            out,
+            "# HELP archypix_http_request_duration_seconds Time spent handling HTTP requests, by route."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE archypix_http_request_duration_seconds summary").unwrap();
+        for ((method, route), metrics) in routes.iter() {
+            let labels = labels(method, route);
+            writeln!(
+                out,
+                "archypix_http_request_duration_seconds_sum{{{}}} {}",
+                labels,
+                metrics.duration.as_secs_f64()
+            )
+            .unwrap();
+            writeln!(out, "archypix_http_request_duration_seconds_count{{{}}} {}", labels, metrics.count()).unwrap();
+        }
+
+        writeln!(out, "# HELP archypix_http_request_size_bytes Size of the HTTP requests bodies, by route.").unwrap();
+        writeln!(out, "# TYPE archypix_http_request_size_bytes summary").unwrap();
+        for ((method, route), metrics) in routes.iter() {
+            let labels = labels(method, route);
+            writeln!(out, "archypix_http_request_size_bytes_sum{{{}}} {}", labels, metrics.size_bytes).unwrap();
+            writeln!(out, "archypix_http_request_size_bytes_count{{{}}} {}", labels, metrics.count()).unwrap();
+        }
+        out
+    }
+}
+
+fn labels(method: &str, route: &str) -> String {
+    format!("method=\"{}\",route=\"{}\"", escape_label(method), escape_label(route))
+}
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Start time of a request, stored in the request local cache.
+struct RequestStart(Instant);
+
+/// Fairing recording every handled request into the [`Metrics`] registry, that it adds to the managed state.
+pub struct MetricsFairing;
+
+#[rocket::async_trait]
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        Ok(rocket.manage(Metrics::default()))
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(metrics) = req.rocket().state::<Metrics>() else {
+            return;
+        };
+        let duration = req.local_cache(|| RequestStart(Instant::now())).0.elapsed();
+        let route = req.route().map(|route| route.uri.to_string()).unwrap_or_else(|| "unmatched".to_string());
+        let size_bytes = req
+            .headers()
+            .get_one("Content-Length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        metrics.record(req.method().as_str(), &route, res.status(), duration, size_bytes);
+    }
+}
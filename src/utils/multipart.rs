@@ -0,0 +1,76 @@
+use rand::random;
+use rocket::futures::{Stream, StreamExt};
+use rocket::http::ContentType;
+use rocket::response::stream::ByteStream;
+use rocket::response::Responder;
+use rocket::{response, Request, Response};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::Responses;
+use rocket_okapi::response::OpenApiResponderInner;
+use std::pin::Pin;
+
+/// Part of a [`MultipartMixed`] body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultipartPart {
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+pub type MultipartPartStream = Pin<Box<dyn Stream<Item = MultipartPart> + Send>>;
+pub type MultipartByteStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// `multipart/mixed` response made of several binary parts, each with its own headers.
+/// Parts are streamed as they are produced: only the part being sent is held in memory.
+pub struct MultipartMixed {
+    pub boundary: String,
+    pub parts: MultipartPartStream,
+}
+
+impl MultipartMixed {
+    /// Multipart with a random boundary, long enough to not appear in the parts.
+    pub fn new(parts: MultipartPartStream) -> Self {
+        Self {
+            boundary: format!("archypix-{:032x}", random::<u128>()),
+            parts,
+        }
+    }
+    pub fn content_type(&self) -> String {
+        format!("multipart/mixed; boundary={}", self.boundary)
+    }
+    /// Encode a part of the multipart, preceded by its delimiter.
+    pub fn encode_part(boundary: &str, part: &MultipartPart) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(part.body.len() + 128);
+        bytes.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        for (name, value) in part.headers.iter() {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&part.body);
+        bytes.extend_from_slice(b"\r\n");
+        bytes
+    }
+    /// Closing delimiter of the multipart, sent after the last part.
+    pub fn encode_end(boundary: &str) -> Vec<u8> {
+        format!("--{}--\r\n", boundary).into_bytes()
+    }
+    /// Stream of the encoded body of the multipart, each part being encoded once it is produced.
+    pub fn into_byte_stream(self) -> MultipartByteStream {
+        let end = rocket::futures::stream::iter([Self::encode_end(&self.boundary)]);
+        let boundary = self.boundary;
+        Box::pin(self.parts.map(move |part| Self::encode_part(&boundary, &part)).chain(end))
+    }
+}
+
+impl<'r> Responder<'r, 'r> for MultipartMixed {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        let content_type = ContentType::parse_flexible(&self.content_type()).unwrap_or(ContentType::Binary);
+        let response = ByteStream(self.into_byte_stream()).respond_to(request)?;
+        Response::build_from(response).header(content_type).ok()
+    }
+}
+/// Dummy implementation for OpenApi
+impl OpenApiResponderInner for MultipartMixed {
+    fn responses(_: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        Ok(Responses::default())
+    }
+}
@@ -0,0 +1,52 @@
+use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use image::ImageFormat;
+use rocket::http::ContentType;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of bytes read at the start of a file to detect its format.
+pub const FORMAT_SNIFF_LENGTH: usize = 32;
+
+/// ISO base media file brands of the HEIF pictures (HEIC...), not detected by the `image` crate.
+const HEIF_BRANDS: [&[u8; 4]; 8] = [b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1"];
+
+/// Detect the format of a picture from its first bytes (magic bytes), as a lowercase name (`jpeg`, `png`, `tiff`, `heif`...).
+/// Returns None if the content is not a known picture format.
+pub fn sniff_picture_format(header: &[u8]) -> Option<String> {
+    if header.len() >= 12 && &header[4..8] == b"ftyp" && HEIF_BRANDS.iter().any(|brand| &header[8..12] == *brand) {
+        return Some("heif".to_string());
+    }
+    image::guess_format(header).ok().map(|format| format!("{:?}", format).to_lowercase())
+}
+
+/// Content type of a picture of the given format, as returned by [`sniff_picture_format`].
+/// Pictures of an unknown format (uploaded before their format was stored) are served as JPEG.
+pub fn format_content_type(format: Option<&str>) -> ContentType {
+    match format {
+        Some("heif") => ContentType::new("image", "heif"),
+        Some(format) => ImageFormat::from_extension(format)
+            .and_then(|format| ContentType::parse_flexible(format.to_mime_type()))
+            .unwrap_or(ContentType::JPEG),
+        None => ContentType::JPEG,
+    }
+}
+
+/// Check that the file is actually a picture of one of the supported formats, returning its format.
+pub fn check_picture_format(path: &Path, supported_formats: &[String]) -> Result<String, ErrorResponder> {
+    let mut header = Vec::with_capacity(FORMAT_SNIFF_LENGTH);
+    File::open(path)
+        .and_then(|file| file.take(FORMAT_SNIFF_LENGTH as u64).read_to_end(&mut header))
+        .map_err(|e| ErrorType::InternalError(format!("Unable to read file: {}", e)).res())?;
+
+    match sniff_picture_format(&header) {
+        Some(format) if supported_formats.iter().any(|supported| supported.eq_ignore_ascii_case(&format)) => Ok(format),
+        Some(format) => ErrorType::InvalidInput(format!(
+            "Unsupported picture format: {}, supported formats are {}",
+            format,
+            supported_formats.join(", ")
+        ))
+        .res_err(),
+        None => ErrorType::InvalidInput(format!("File is not a picture, supported formats are {}", supported_formats.join(", "))).res_err(),
+    }
+}
@@ -1,7 +1,11 @@
+use crate::utils::byte_range::ByteRange;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 use crate::utils::thumbnail::PictureThumbnail;
 use aws_config::BehaviorVersion;
+use aws_sdk_s3::config::http::HttpResponse;
 use aws_sdk_s3::config::Credentials;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::Client;
 use aws_smithy_types::byte_stream::ByteStream;
@@ -17,6 +21,7 @@ const BUCKETS: [&str; 4] = [
     "archypix-thumbnails-large",
 ];
 
+#[derive(Clone)]
 pub struct PictureStorer {
     client: Client,
 }
@@ -48,6 +53,20 @@ impl PictureStorer {
         picture_storer.create_buckets().await;
         picture_storer
     }
+    /// Storer of an S3 API served at the endpoint, without testing the connection nor creating the buckets.
+    #[cfg(test)]
+    pub fn with_endpoint(endpoint: &str) -> Self {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .force_path_style(true)
+            .region(aws_sdk_s3::config::Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("access_key", "secret_key", None, None, "Static"))
+            .endpoint_url(endpoint)
+            .build();
+        PictureStorer {
+            client: Client::from_conf(config),
+        }
+    }
     async fn create_buckets(&self) {
         let list_buckets_output = self.client.list_buckets().send().await.unwrap();
         let existing_bucket_names: Vec<String> = list_buckets_output
@@ -82,6 +101,15 @@ impl PictureStorer {
             .map_err(|_e| ErrorType::S3Error(String::from("Unable to store object")).res())
     }
 
+    /// Delete a picture and its thumbnails, e.g. to roll back a failed upload. Missing objects are ignored, failures are only logged.
+    pub async fn delete_picture(&self, id: i64) {
+        for bucket in BUCKETS.iter() {
+            if let Err(e) = self.client.delete_object().bucket(*bucket).key(id.to_string()).send().await {
+                warn!("Unable to delete object {} from bucket {}: {:?}", id, bucket, e);
+            }
+        }
+    }
+
     pub async fn get_picture(&self, picture_thumbnail: PictureThumbnail, id: i64) -> Result<ByteStream, ErrorResponder> {
         self.client
             .get_object()
@@ -90,6 +118,43 @@ impl PictureStorer {
             .send()
             .await
             .map(|output| output.body)
+            .map_err(get_object_error)
+    }
+
+    /// Get a byte range of a picture, returning the stream of the range and its `Content-Range` (`bytes start-end/size`).
+    /// Fails with [`ErrorType::RangeNotSatisfiable`] (416) if the range starts after the end of the picture.
+    pub async fn get_picture_range(
+        &self,
+        picture_thumbnail: PictureThumbnail,
+        id: i64,
+        range: ByteRange,
+    ) -> Result<(ByteStream, Option<String>), ErrorResponder> {
+        let result = self
+            .client
+            .get_object()
+            .bucket(BUCKETS[picture_thumbnail as usize])
+            .key(id.to_string())
+            .range(range.to_string())
+            .send()
+            .await;
+        match result {
+            Ok(output) => Ok((output.body, output.content_range)),
+            Err(e) if e.raw_response().is_some_and(|response| response.status().as_u16() == 416) => {
+                let size = self.get_picture_size(picture_thumbnail, id).await?;
+                Err(ErrorType::RangeNotSatisfiable(size).res())
+            }
+            Err(e) => Err(get_object_error(e)),
+        }
+    }
+    /// Size of a picture in bytes, without fetching it.
+    pub async fn get_picture_size(&self, picture_thumbnail: PictureThumbnail, id: i64) -> Result<u64, ErrorResponder> {
+        self.client
+            .head_object()
+            .bucket(BUCKETS[picture_thumbnail as usize])
+            .key(id.to_string())
+            .send()
+            .await
+            .map(|output| output.content_length.unwrap_or_default().max(0) as u64)
             .map_err(|_e| ErrorType::S3Error(String::from("Unable to retrieve object")).res())
     }
 
@@ -109,3 +174,16 @@ impl PictureStorer {
             .map_err(|_e| ErrorType::S3Error(String::from("Unable to retrieve object")).res())
     }
 }
+
+/// Map an S3 object retrieval failure: a missing object is a [`ErrorType::PictureNotFound`] (404),
+/// and an unreachable or overloaded S3 backend is a [`ErrorType::ServiceUnavailable`] (503) that can be retried.
+pub fn get_object_error(error: SdkError<GetObjectError, HttpResponse>) -> ErrorResponder {
+    let status = error.raw_response().map(|response| response.status().as_u16());
+    let not_found = error.as_service_error().is_some_and(|e| e.is_no_such_key()) || status == Some(404);
+    match error {
+        _ if not_found => ErrorType::PictureNotFound.res(),
+        SdkError::DispatchFailure(_) | SdkError::TimeoutError(_) => ErrorType::ServiceUnavailable(String::from("S3 storage is unreachable")).res(),
+        _ if status == Some(503) => ErrorType::ServiceUnavailable(String::from("S3 storage is overloaded")).res(),
+        _ => ErrorType::S3Error(String::from("Unable to retrieve object")).res(),
+    }
+}
@@ -0,0 +1,67 @@
+use crate::api::picture::{stream_picture, PictureStream};
+use crate::utils::byte_range::ByteRange;
+use crate::utils::errors_catcher::ErrorResponder;
+use crate::utils::s3::PictureStorer;
+use crate::utils::tests::s3_mock::picture_storer_serving;
+use crate::utils::thumbnail::PictureThumbnail;
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+use rocket::State;
+use std::str::FromStr;
+
+const PICTURE: &[u8] = b"0123456789";
+
+/// Streams the original of a picture like `get_picture` does once the access is checked.
+#[get("/picture")]
+async fn picture(range: Option<ByteRange>, picture_storer: &State<PictureStorer>) -> Result<PictureStream, ErrorResponder> {
+    stream_picture(picture_storer, 1, PictureThumbnail::Original, None, range).await
+}
+
+#[test]
+pub fn test_parse_range_header() {
+    assert_eq!(ByteRange::from_str("bytes=0-99"), Ok(ByteRange::Bounded { start: 0, end: 99 }));
+    assert_eq!(ByteRange::from_str("bytes=100-"), Ok(ByteRange::From { start: 100 }));
+    assert_eq!(ByteRange::from_str("bytes=-500"), Ok(ByteRange::Suffix { length: 500 }));
+    assert_eq!(ByteRange::from_str("bytes=100-99"), Err(()));
+    assert_eq!(ByteRange::from_str("bytes=-"), Err(()));
+    assert_eq!(ByteRange::from_str("bytes=-0"), Err(()));
+    assert_eq!(ByteRange::from_str("bytes=0-1,5-6"), Err(()));
+    assert_eq!(ByteRange::from_str("items=0-1"), Err(()));
+
+    // Formatted back for the S3 request
+    assert_eq!(ByteRange::Bounded { start: 0, end: 99 }.to_string(), "bytes=0-99");
+    assert_eq!(ByteRange::Suffix { length: 500 }.to_string(), "bytes=-500");
+}
+
+#[test]
+pub fn test_ranged_request_returns_partial_content() {
+    let client = Client::tracked(rocket::build().manage(picture_storer_serving(PICTURE)).mount("/", routes![picture])).unwrap();
+
+    let response = client.get("/picture").header(Header::new("Range", "bytes=2-5")).dispatch();
+    assert_eq!(response.status(), Status::PartialContent);
+    assert_eq!(response.headers().get_one("Content-Range"), Some("bytes 2-5/10"));
+    assert_eq!(response.headers().get_one("Accept-Ranges"), Some("bytes"));
+    assert_eq!(response.into_bytes().unwrap(), b"2345");
+
+    // A malformed range is ignored
+    let response = client.get("/picture").header(Header::new("Range", "bytes=5-2")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Content-Range"), None);
+    assert_eq!(response.into_bytes().unwrap(), PICTURE);
+}
+
+#[test]
+pub fn test_unsatisfiable_range_is_reported_with_the_size() {
+    let client = Client::tracked(rocket::build().manage(picture_storer_serving(PICTURE)).mount("/", routes![picture])).unwrap();
+
+    let response = client.get("/picture").header(Header::new("Range", "bytes=10-")).dispatch();
+    assert_eq!(response.status(), Status::RangeNotSatisfiable);
+    assert_eq!(response.headers().get_one("Content-Range"), Some("bytes */10"));
+    assert!(response.into_string().unwrap().contains("\"error_type\":\"RangeNotSatisfiable\""));
+
+    // The end of a range may go past the end of the picture
+    let response = client.get("/picture").header(Header::new("Range", "bytes=8-20")).dispatch();
+    assert_eq!(response.status(), Status::PartialContent);
+    assert_eq!(response.headers().get_one("Content-Range"), Some("bytes 8-9/10"));
+    assert_eq!(response.into_bytes().unwrap(), b"89");
+}
@@ -0,0 +1,67 @@
+use crate::database::group::group::Group;
+use crate::database::tag::tag::Tag;
+use crate::utils::color::{from_hex, palette_color, to_hex, GROUPS_PALETTE};
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+
+#[test]
+pub fn test_hex_conversions() {
+    assert_eq!(to_hex(&[255, 128, 0]), "#FF8000");
+    assert_eq!(from_hex("#FF8000"), Some(vec![255, 128, 0]));
+    assert_eq!(from_hex("ff8000"), Some(vec![255, 128, 0]));
+    assert_eq!(from_hex("#FF800"), None);
+    assert_eq!(from_hex("#GG8000"), None);
+}
+
+#[test]
+pub fn test_tag_color_serde() {
+    let tag: Tag = serde_json::from_str(r##"{"id": 1, "tag_group_id": 2, "name": "Red", "color": "#FF0000", "is_default": false}"##).unwrap();
+    assert_eq!(tag.color, vec![255, 0, 0]);
+    assert_eq!(serde_json::to_value(&tag).unwrap()["color"], "#FF0000");
+
+    // Byte arrays are still accepted
+    let tag: Tag = serde_json::from_str(r#"{"id": 1, "tag_group_id": 2, "name": "Red", "color": [255, 0, 0], "is_default": false}"#).unwrap();
+    assert_eq!(tag.color, vec![255, 0, 0]);
+
+    // Invalid hex strings are rejected
+    assert!(serde_json::from_str::<Tag>(r##"{"id": 1, "tag_group_id": 2, "name": "Red", "color": "#FF00ZZ", "is_default": false}"##).is_err());
+}
+
+#[test]
+pub fn test_validate_color() {
+    assert!(Tag::validate_color(&[12, 34, 56]).is_ok());
+
+    let error = ErrorResponse::from(Tag::validate_color(&[12, 34]).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    let error = ErrorResponse::from(Tag::validate_color(&[12, 34, 56, 78]).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    assert!(Tag::validate_color(&[]).is_err());
+}
+
+#[test]
+pub fn test_palette_color_is_deterministic() {
+    // The same value always yields the same color of the palette
+    assert_eq!(palette_color("2024-05"), palette_color("2024-05"));
+    assert_eq!(palette_color("Other"), palette_color("Other"));
+    assert!(GROUPS_PALETTE.iter().any(|color| color.to_vec() == palette_color("2024-05")));
+    // Values are spread over the palette
+    let colors: Vec<Vec<u8>> = (1..=12).map(|month| palette_color(&format!("2024-{:02}", month))).collect();
+    assert!(colors.iter().any(|color| *color != colors[0]));
+    // Stable across builds: colors are stored when the groups are created
+    assert_eq!(palette_color(""), GROUPS_PALETTE[(0xcbf29ce484222325u64 % 12) as usize].to_vec());
+}
+
+#[test]
+pub fn test_group_color_serde() {
+    let group: Group = serde_json::from_str(
+        r##"{"id": 1, "arrangement_id": 2, "share_match_conversion": false, "name": "Red", "to_be_deleted": false, "color": "#FF0000"}"##,
+    )
+    .unwrap();
+    assert_eq!(group.color, Some(vec![255, 0, 0]));
+    assert_eq!(serde_json::to_value(&group).unwrap()["color"], "#FF0000");
+
+    // Groups without color, such as manual groups
+    let group: Group =
+        serde_json::from_str(r#"{"id": 1, "arrangement_id": 2, "share_match_conversion": false, "name": "Red", "to_be_deleted": false}"#).unwrap();
+    assert_eq!(group.color, None);
+    assert_eq!(serde_json::to_value(&group).unwrap()["color"], serde_json::Value::Null);
+}
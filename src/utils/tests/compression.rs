@@ -0,0 +1,102 @@
+use crate::utils::compression::{CompressionFairing, ContentEncoding};
+use flate2::read::GzDecoder;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rocket::serde::json::Json;
+use std::io::Read;
+
+#[get("/large")]
+fn large() -> Json<Vec<String>> {
+    Json((0..500).map(|i| format!("picture-{}.jpg", i)).collect())
+}
+#[get("/small")]
+fn small() -> Json<Vec<i32>> {
+    Json(vec![1, 2, 3])
+}
+#[get("/thumbnail")]
+fn thumbnail() -> (ContentType, Vec<u8>) {
+    (ContentType::JPEG, vec![0xFF; 4096])
+}
+/// JSON response varying on the origin of the request, like the responses of the CORS fairing
+#[derive(Responder)]
+struct VaryOrigin {
+    inner: Json<Vec<String>>,
+    vary: Header<'static>,
+}
+#[get("/vary-origin")]
+fn vary_origin() -> VaryOrigin {
+    VaryOrigin {
+        inner: large(),
+        vary: Header::new("Vary", "Origin"),
+    }
+}
+
+fn client() -> Client {
+    Client::tracked(
+        rocket::build()
+            .mount("/", routes![large, small, thumbnail, vary_origin])
+            .attach(CompressionFairing { min_size_bytes: 1024 }),
+    )
+    .unwrap()
+}
+
+#[test]
+pub fn test_large_json_responses_are_compressed_when_accepted() {
+    let client = client();
+    let expected = serde_json::to_vec(&large().into_inner()).unwrap();
+
+    let response = client.get("/large").header(Header::new("Accept-Encoding", "br, gzip;q=0.8")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+    assert_eq!(response.headers().get_one("Vary"), Some("Accept-Encoding"));
+    let compressed = response.into_bytes().unwrap();
+    assert!(compressed.len() < expected.len());
+    let mut decompressed = Vec::new();
+    GzDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, expected);
+
+    // Without Accept-Encoding, the response is left as it is
+    let response = client.get("/large").dispatch();
+    assert_eq!(response.headers().get_one("Content-Encoding"), None);
+    assert_eq!(response.headers().get_one("Vary"), Some("Accept-Encoding"));
+    assert_eq!(response.into_bytes().unwrap(), expected);
+}
+
+#[test]
+pub fn test_vary_header_of_the_response_is_kept() {
+    let client = client();
+    let response = client.get("/vary-origin").header(Header::new("Accept-Encoding", "gzip")).dispatch();
+    assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+    assert_eq!(response.headers().get("Vary").collect::<Vec<_>>(), vec!["Origin", "Accept-Encoding"]);
+}
+
+#[test]
+pub fn test_small_and_binary_responses_are_not_compressed() {
+    let client = client();
+    let response = client.get("/small").header(Header::new("Accept-Encoding", "gzip")).dispatch();
+    assert_eq!(response.headers().get_one("Content-Encoding"), None);
+    assert_eq!(response.headers().get_one("Vary"), Some("Accept-Encoding"));
+    assert_eq!(response.into_string().unwrap(), "[1,2,3]");
+
+    let response = client.get("/thumbnail").header(Header::new("Accept-Encoding", "gzip")).dispatch();
+    assert_eq!(response.headers().get_one("Content-Encoding"), None);
+    assert_eq!(response.headers().get_one("Vary"), None);
+    assert_eq!(response.into_bytes().unwrap().len(), 4096);
+}
+
+#[test]
+pub fn test_negotiate_encoding() {
+    assert_eq!(ContentEncoding::negotiate("gzip, deflate"), Some(ContentEncoding::Gzip));
+    assert_eq!(ContentEncoding::negotiate("deflate"), Some(ContentEncoding::Deflate));
+    assert_eq!(ContentEncoding::negotiate("GZIP"), Some(ContentEncoding::Gzip));
+    assert_eq!(ContentEncoding::negotiate("*"), Some(ContentEncoding::Gzip));
+    // Refused encodings are skipped
+    assert_eq!(ContentEncoding::negotiate("gzip;q=0, deflate;q=0.5"), Some(ContentEncoding::Deflate));
+    // `*` doesn't accept the encodings refused explicitly
+    assert_eq!(ContentEncoding::negotiate("gzip;q=0, *"), Some(ContentEncoding::Deflate));
+    assert_eq!(ContentEncoding::negotiate("*, gzip;q=0, deflate;q=0"), None);
+    assert_eq!(ContentEncoding::negotiate("*;q=0"), None);
+    assert_eq!(ContentEncoding::negotiate("br"), None);
+    assert_eq!(ContentEncoding::negotiate("identity"), None);
+    assert_eq!(ContentEncoding::negotiate(""), None);
+}
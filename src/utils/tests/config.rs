@@ -0,0 +1,95 @@
+use crate::database::schema::ConfirmationAction;
+use crate::database::user::confirmation::Confirmation;
+use crate::utils::config::Config;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use chrono::{Duration, NaiveDateTime, Utc};
+
+fn create_confirmation(date: NaiveDateTime) -> Confirmation {
+    Confirmation {
+        user_id: 0,
+        action: ConfirmationAction::Signin,
+        used: false,
+        date,
+        token: vec![],
+        code_token: vec![],
+        code: 0,
+        code_trials: 0,
+        redirect_url: None,
+        device_string: None,
+        ip_address: None,
+    }
+}
+
+#[test]
+pub fn test_config_validation() {
+    assert!(Config::default().validate().is_ok());
+
+    let config = Config {
+        confirmation_code_digits: 5,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+    let config = Config {
+        confirmation_code_digits: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+    let config = Config {
+        confirmation_signin_minutes: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+    let config = Config {
+        temp_files_max_age_hours: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+    let config = Config {
+        password_min_length: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+}
+
+#[test]
+pub fn test_confirmation_custom_expiry() {
+    let config = Config {
+        confirmation_signin_minutes: 5,
+        ..Default::default()
+    };
+    let max_minutes = config.confirmation_max_minutes(&ConfirmationAction::Signin);
+    assert_eq!(max_minutes, 5);
+    assert_eq!(config.confirmation_max_minutes(&ConfirmationAction::Signup), 15);
+
+    let now = Utc::now().naive_utc();
+
+    // Exactly at the boundary: still valid
+    let confirmation = create_confirmation(now - Duration::minutes(max_minutes));
+    assert!(confirmation.check_not_expired(now, max_minutes).is_ok());
+
+    // Just after the boundary: expired
+    let confirmation = create_confirmation(now - Duration::minutes(max_minutes) - Duration::seconds(1));
+    let error = ErrorResponse::from(confirmation.check_not_expired(now, max_minutes).unwrap_err());
+    assert!(matches!(error.error_type, ErrorTypeKind::ConfirmationExpired));
+}
+
+#[test]
+pub fn test_page_size_is_clamped() {
+    let config = Config {
+        default_page_size: 50,
+        max_page_size: 200,
+        ..Default::default()
+    };
+    assert_eq!(config.page_size(None), 50);
+    assert_eq!(config.page_size(Some(120)), 120);
+    // Over-cap page sizes are clamped to the maximum rather than rejected
+    assert_eq!(config.page_size(Some(10_000)), 200);
+    assert_eq!(config.page_size(Some(0)), 1);
+
+    let config = Config {
+        default_page_size: 300,
+        max_page_size: 200,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,51 @@
+use crate::utils::config::parse_list;
+use crate::utils::cors::cors_options;
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+
+#[get("/ping")]
+fn ping() -> &'static str {
+    "pong"
+}
+
+fn client(configured_origins: &str) -> Client {
+    let rocket = rocket::build()
+        .mount("/", routes![ping])
+        .attach(cors_options(&parse_list(configured_origins)));
+    Client::tracked(rocket).unwrap()
+}
+fn allowed_origin(client: &Client, origin: &'static str) -> Option<String> {
+    let response = client.get("/ping").header(Header::new("Origin", origin)).dispatch();
+    if response.status() != Status::Ok {
+        return None;
+    }
+    response.headers().get_one("Access-Control-Allow-Origin").map(String::from)
+}
+
+#[test]
+pub fn test_parse_origins_list() {
+    assert_eq!(
+        parse_list(" https://a.example.com, ,https://b.example.com "),
+        vec!["https://a.example.com".to_string(), "https://b.example.com".to_string()]
+    );
+    assert!(parse_list("").is_empty());
+}
+
+#[test]
+pub fn test_configured_origins() {
+    let client = client("https://a.example.com,https://b.example.com,regex:^https://[a-z]+\\.tenant\\.example\\.com$");
+
+    assert_eq!(
+        allowed_origin(&client, "https://a.example.com"),
+        Some("https://a.example.com".to_string())
+    );
+    assert_eq!(
+        allowed_origin(&client, "https://b.example.com"),
+        Some("https://b.example.com".to_string())
+    );
+    assert_eq!(
+        allowed_origin(&client, "https://acme.tenant.example.com"),
+        Some("https://acme.tenant.example.com".to_string())
+    );
+    assert_eq!(allowed_origin(&client, "https://evil.example.com"), None);
+}
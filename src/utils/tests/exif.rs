@@ -0,0 +1,83 @@
+use crate::api::picture::{stream_stripped_picture, PictureStream};
+use crate::utils::errors_catcher::ErrorResponder;
+use crate::utils::exif::{clamp_dimensions, is_private_tag};
+use crate::utils::s3::PictureStorer;
+use crate::utils::tests::s3_mock::picture_storer_serving;
+use rexiv2::Metadata;
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+use rocket::State;
+
+/// JPEG tagged with a camera make and a GPS position.
+const GPS_PICTURE: &[u8] = include_bytes!("fixtures/gps.jpg");
+
+/// Streams the stripped original of a picture like `get_picture?strip_exif=true` does once the access is checked.
+#[get("/picture")]
+async fn stripped_picture(picture_storer: &State<PictureStorer>) -> Result<PictureStream, ErrorResponder> {
+    stream_stripped_picture(picture_storer, 1, Some("jpg".to_string())).await
+}
+
+#[test]
+pub fn test_location_and_personal_tags_are_private() {
+    for tag in [
+        "Exif.GPSInfo.GPSLatitude",
+        "Exif.GPSInfo.GPSLongitudeRef",
+        "Exif.GPSInfo.GPSAltitude",
+        "Xmp.exif.GPSLatitude",
+        "Exif.Image.Artist",
+        "Exif.Photo.BodySerialNumber",
+        "Exif.Photo.CameraOwnerName",
+        "Xmp.dc.creator",
+    ] {
+        assert!(is_private_tag(tag), "{} should be stripped", tag);
+    }
+}
+
+#[test]
+pub fn test_picture_rendering_tags_are_kept() {
+    for tag in [
+        "Exif.Image.Orientation",
+        "Exif.Image.Make",
+        "Exif.Image.Model",
+        "Exif.Photo.DateTimeOriginal",
+        "Exif.Photo.FNumber",
+        "Exif.Photo.ExposureTime",
+        "Exif.Photo.ISOSpeedRatings",
+        "Xmp.tiff.Orientation",
+    ] {
+        assert!(!is_private_tag(tag), "{} should be kept", tag);
+    }
+}
+
+#[test]
+pub fn test_large_dimensions_do_not_wrap_around() {
+    assert_eq!(clamp_dimensions(6000, 4000), (6000, 4000));
+    assert_eq!(clamp_dimensions(32767, 100), (32767, 100));
+    // 40000 as i16 would wrap around to -25536
+    assert_eq!(clamp_dimensions(40000, 20000), (32767, 16383));
+    assert_eq!(clamp_dimensions(1000, 65536), (499, 32767));
+    assert_eq!(clamp_dimensions(-1, 0), (0, 0));
+}
+
+#[test]
+#[ignore = "needs the gexiv2 library"]
+pub fn test_streamed_stripped_picture_has_no_location() {
+    let metadata = Metadata::new_from_buffer(GPS_PICTURE).unwrap();
+    assert!(metadata.get_gps_info().is_some());
+
+    let client = Client::tracked(
+        rocket::build()
+            .manage(picture_storer_serving(GPS_PICTURE))
+            .mount("/", routes![stripped_picture]),
+    )
+    .unwrap();
+    let response = client.get("/picture").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Accept-Ranges"), None);
+
+    let metadata = Metadata::new_from_buffer(&response.into_bytes().unwrap()).unwrap();
+    assert!(metadata.get_gps_info().is_none());
+    assert!(metadata.get_exif_tags().unwrap().iter().all(|tag| !tag.starts_with("Exif.GPSInfo.")));
+    // The other tags are kept
+    assert_eq!(metadata.get_tag_string("Exif.Image.Make").unwrap(), "Archypix");
+}
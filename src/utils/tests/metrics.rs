@@ -0,0 +1,37 @@
+use crate::api::metrics::get_metrics;
+use crate::utils::metrics::MetricsFairing;
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+
+#[get("/ping/<_id>")]
+fn ping(_id: i32) -> &'static str {
+    "pong"
+}
+
+fn metrics_output(client: &Client) -> String {
+    let response = client.get("/metrics").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.into_string().unwrap()
+}
+
+#[test]
+pub fn test_requests_are_counted() {
+    let client = Client::tracked(rocket::build().mount("/", routes![ping, get_metrics]).attach(MetricsFairing)).unwrap();
+
+    client.get("/ping/1").dispatch();
+    client.get("/ping/2").dispatch();
+    assert_eq!(client.get("/ping/abc").dispatch().status(), Status::UnprocessableEntity);
+
+    let output = metrics_output(&client);
+    // Requests are aggregated by route pattern and status class
+    assert!(output.contains("archypix_http_requests_total{method=\"GET\",route=\"/ping/<_id>\",status_class=\"2xx\"} 2\n"));
+    assert!(output.contains("archypix_http_requests_total{method=\"GET\",route=\"/ping/<_id>\",status_class=\"4xx\"} 1\n"));
+    assert!(output.contains("archypix_http_request_duration_seconds_count{method=\"GET\",route=\"/ping/<_id>\"} 3\n"));
+
+    // Every sample line is made of a metric name with labels and a numeric value
+    for line in output.lines().filter(|line| !line.starts_with('#')) {
+        let (series, value) = line.rsplit_once(' ').unwrap();
+        assert!(series.starts_with("archypix_http_request") && series.ends_with('}'), "{}", line);
+        assert!(value.parse::<f64>().is_ok(), "{}", line);
+    }
+}
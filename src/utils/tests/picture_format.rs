@@ -0,0 +1,86 @@
+use crate::api::picture::PictureStream;
+use crate::utils::config::Config;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use crate::utils::picture_format::{check_picture_format, format_content_type, sniff_picture_format};
+use crate::utils::thumbnail::PictureThumbnail;
+use aws_smithy_types::byte_stream::ByteStream;
+use rocket::http::ContentType;
+use rocket::local::blocking::Client;
+use std::path::PathBuf;
+
+const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("archypix-picture-format-{}-{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+pub fn test_sniff_picture_format() {
+    assert_eq!(sniff_picture_format(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").as_deref(), Some("png"));
+    assert_eq!(sniff_picture_format(&[0xff, 0xd8, 0xff, 0xe1, 0, 0]).as_deref(), Some("jpeg"));
+    assert_eq!(sniff_picture_format(b"II*\x00\x08\0\0\0").as_deref(), Some("tiff"));
+    assert_eq!(sniff_picture_format(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic").as_deref(), Some("heif"));
+
+    assert_eq!(sniff_picture_format(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3"), None);
+    assert_eq!(sniff_picture_format(b"Hello, world!"), None);
+    assert_eq!(sniff_picture_format(b""), None);
+}
+
+#[test]
+pub fn test_non_picture_upload_is_rejected() {
+    let supported_formats = Config::default().supported_picture_formats;
+
+    let path = temp_file("document.pdf", b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n1 0 obj");
+    let error = ErrorResponse::from(check_picture_format(&path, &supported_formats).unwrap_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    assert!(error.message.contains("File is not a picture"));
+    assert!(error.rollback);
+
+    let path = temp_file("picture.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    assert_eq!(check_picture_format(&path, &supported_formats).unwrap(), "png");
+    // Picture format that is not supported
+    let error = ErrorResponse::from(check_picture_format(&path, &["jpeg".to_string()]).unwrap_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(error.error_type, ErrorTypeKind::InvalidInput));
+    assert!(error.message.contains("Unsupported picture format: png"));
+}
+
+/// Serves a PNG original like `get_picture` does, with the format stored at upload.
+#[get("/picture/<format>")]
+fn png_picture(format: PictureThumbnail) -> PictureStream {
+    PictureStream {
+        picture_id: 1,
+        format,
+        original_format: sniff_picture_format(PNG_HEADER),
+        picture_stream: ByteStream::from_static(PNG_HEADER),
+        accept_ranges: false,
+        content_range: None,
+    }
+}
+
+#[test]
+pub fn test_png_original_is_served_as_png() {
+    let client = Client::tracked(rocket::build().mount("/", routes![png_picture])).unwrap();
+
+    let response = client.get("/picture/original").dispatch();
+    assert_eq!(response.content_type(), Some(ContentType::PNG));
+    assert_eq!(response.headers().get_one("Content-Type"), Some("image/png"));
+    assert_eq!(response.into_bytes().unwrap(), PNG_HEADER);
+
+    // Thumbnails are always WebP
+    let response = client.get("/picture/small").dispatch();
+    assert_eq!(response.content_type(), Some(ContentType::WEBP));
+}
+
+#[test]
+pub fn test_format_content_type() {
+    assert_eq!(format_content_type(Some("jpeg")), ContentType::JPEG);
+    assert_eq!(format_content_type(Some("gif")), ContentType::GIF);
+    assert_eq!(format_content_type(Some("tiff")).to_string(), "image/tiff");
+    assert_eq!(format_content_type(Some("heif")).to_string(), "image/heif");
+    // Pictures uploaded before the format was stored
+    assert_eq!(format_content_type(None), ContentType::JPEG);
+}
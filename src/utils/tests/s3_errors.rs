@@ -0,0 +1,42 @@
+use crate::utils::errors_catcher::{ErrorResponder, ErrorResponse, ErrorTypeKind};
+use crate::utils::s3::get_object_error;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{ConnectorError, SdkError};
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::types::error::NoSuchKey;
+use aws_smithy_types::body::SdkBody;
+use std::io;
+
+fn response(status: u16) -> HttpResponse {
+    HttpResponse::new(status.try_into().unwrap(), SdkBody::empty())
+}
+
+#[test]
+pub fn test_missing_object_is_not_found() {
+    let error = SdkError::service_error(GetObjectError::NoSuchKey(NoSuchKey::builder().build()), response(404));
+    let responder = get_object_error(error);
+    assert!(matches!(responder, ErrorResponder::NotFound(_)));
+    assert!(matches!(ErrorResponse::from(responder).error_type, ErrorTypeKind::PictureNotFound));
+}
+
+#[test]
+pub fn test_unreachable_backend_is_service_unavailable() {
+    let connection_refused = io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+    let error = SdkError::<GetObjectError, HttpResponse>::dispatch_failure(ConnectorError::io(Box::new(connection_refused)));
+    let responder = get_object_error(error);
+    assert!(matches!(responder, ErrorResponder::ServiceUnavailable(_)));
+    let response = ErrorResponse::from(responder);
+    assert!(matches!(response.error_type, ErrorTypeKind::ServiceUnavailable));
+    assert!(response.message.contains("retry"));
+
+    let error = SdkError::<GetObjectError, HttpResponse>::timeout_error("timed out");
+    assert!(matches!(get_object_error(error), ErrorResponder::ServiceUnavailable(_)));
+}
+
+#[test]
+pub fn test_other_s3_failures_are_internal_errors() {
+    let error = SdkError::service_error(GetObjectError::unhandled("access denied"), response(403));
+    let responder = get_object_error(error);
+    assert!(matches!(responder, ErrorResponder::InternalError(_)));
+    assert!(matches!(ErrorResponse::from(responder).error_type, ErrorTypeKind::S3Error));
+}
@@ -0,0 +1,70 @@
+use crate::utils::byte_range::ByteRange;
+use crate::utils::s3::PictureStorer;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::thread;
+
+/// Storer of a minimal S3 API served on a local port, returning `object` for any key of any bucket.
+/// Supports the `GetObject` (with a single byte `Range`) and `HeadObject` operations.
+pub fn picture_storer_serving(object: &'static [u8]) -> PictureStorer {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            respond(stream, object);
+        }
+    });
+    PictureStorer::with_endpoint(&endpoint)
+}
+
+fn respond(mut stream: TcpStream, object: &[u8]) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut range = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("range") {
+                range = ByteRange::from_str(value.trim()).ok();
+            }
+        }
+    }
+
+    let size = object.len() as u64;
+    let (status, headers, body): (&str, Vec<String>, &[u8]) = if request_line.starts_with("HEAD") {
+        ("200 OK", vec![], &[])
+    } else {
+        let bounds = match range {
+            None => None,
+            Some(ByteRange::Bounded { start, end }) => Some((start, end.min(size.saturating_sub(1)))),
+            Some(ByteRange::From { start }) => Some((start, size.saturating_sub(1))),
+            Some(ByteRange::Suffix { length }) => Some((size.saturating_sub(length), size.saturating_sub(1))),
+        };
+        match bounds {
+            None => ("200 OK", vec![], object),
+            Some((start, _)) if start >= size => (
+                "416 Requested Range Not Satisfiable",
+                vec!["Content-Type: application/xml".to_string()],
+                b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>InvalidRange</Code></Error>",
+            ),
+            Some((start, end)) => (
+                "206 Partial Content",
+                vec![format!("Content-Range: bytes {}-{}/{}", start, end, size)],
+                &object[start as usize..=end as usize],
+            ),
+        }
+    };
+    let content_length = if request_line.starts_with("HEAD") { size } else { body.len() as u64 };
+    let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n", status, content_length);
+    for header in headers {
+        response.push_str(&format!("{}\r\n", header));
+    }
+    response.push_str("\r\n");
+    stream.write_all(response.as_bytes()).unwrap();
+    stream.write_all(body).unwrap();
+}
@@ -0,0 +1,197 @@
+use crate::database::database::DBConn;
+use crate::database::picture::picture::{Picture, SIMILAR_PICTURES_MAX_DISTANCE};
+use crate::database::schema::pictures;
+use crate::database::tests::test_database::{insert_picture, insert_user, test_connection};
+use crate::utils::config::{Config, CONFIG};
+use crate::utils::thumbnail::{
+    extract_first_frame, generate_perceptual_hash, generate_thumbnail, perceptual_hash, perceptual_hash_distance, remove_stale_temp_files,
+    PictureThumbnail,
+};
+use chrono::Utc;
+use diesel::debug_query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use image::codecs::gif::GifEncoder;
+use image::codecs::webp::WebPDecoder;
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+use image::{Delay, Frame, ImageFormat, Rgba, RgbaImage};
+use rocket::http::ContentType;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("archypix-thumbnail-{}-{}", std::process::id(), name))
+}
+/// Writes a GIF of 200x100 pixels with a frame per color.
+fn write_gif(name: &str, colors: &[Rgba<u8>]) -> PathBuf {
+    let path = temp_path(name);
+    let frames = colors
+        .iter()
+        .map(|color| Frame::from_parts(RgbaImage::from_pixel(200, 100, *color), 0, 0, Delay::from_numer_denom_ms(100, 1)));
+    GifEncoder::new(std::fs::File::create(&path).unwrap()).encode_frames(frames).unwrap();
+    path
+}
+
+#[test]
+pub fn test_animated_gif_thumbnail_source_is_static_first_frame() {
+    let source = write_gif("animated.gif", &[Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255])]);
+    let first_frame = extract_first_frame(&source, &std::env::temp_dir()).unwrap().expect("animated GIF");
+
+    let format = image::ImageReader::open(&first_frame).unwrap().with_guessed_format().unwrap().format();
+    let picture = image::open(&first_frame).unwrap().to_rgba8();
+    std::fs::remove_file(&source).unwrap();
+    std::fs::remove_file(&first_frame).unwrap();
+
+    // A static PNG of the same size, from the first (red) frame
+    assert_eq!(format, Some(ImageFormat::Png));
+    assert_eq!(picture.dimensions(), (200, 100));
+    let pixel = picture.get_pixel(100, 50);
+    assert!(pixel[0] > 200 && pixel[2] < 50);
+}
+
+#[test]
+pub fn test_animated_gif_thumbnail_is_a_static_webp_of_the_first_frame() {
+    std::fs::create_dir_all(CONFIG.thumbs_temp_dir()).unwrap();
+    let source = write_gif("animated-thumbnail.gif", &[Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255])]);
+    let thumbnail = generate_thumbnail(PictureThumbnail::Small, &source).unwrap();
+
+    let decoder = WebPDecoder::new(BufReader::new(std::fs::File::open(&thumbnail).unwrap())).unwrap();
+    let animated = decoder.has_animation();
+    let picture = DynamicImage::from_decoder(decoder).unwrap().to_rgba8();
+    let first_frame_left = CONFIG
+        .thumbs_temp_dir()
+        .join(format!("{}.first-frame.png", source.file_name().unwrap().to_str().unwrap()))
+        .exists();
+    std::fs::remove_file(&source).unwrap();
+    std::fs::remove_file(&thumbnail).unwrap();
+
+    // A single still WebP frame, resized to the small thumbnail height, from the first (red) frame
+    assert!(!animated);
+    assert_eq!(picture.dimensions(), (200, 100));
+    let pixel = picture.get_pixel(100, 50);
+    assert!(pixel[0] > 200 && pixel[2] < 50);
+    // The temporary first frame is removed once read
+    assert!(!first_frame_left);
+}
+
+#[test]
+pub fn test_static_pictures_are_thumbnailed_as_is() {
+    // Single frame GIF
+    let source = write_gif("static.gif", &[Rgba([0, 255, 0, 255])]);
+    assert_eq!(extract_first_frame(&source, &std::env::temp_dir()).unwrap(), None);
+    std::fs::remove_file(&source).unwrap();
+
+    // Other formats
+    let source = temp_path("static.png");
+    RgbaImage::from_pixel(20, 10, Rgba([0, 255, 0, 255]))
+        .save_with_format(&source, ImageFormat::Png)
+        .unwrap();
+    assert_eq!(extract_first_frame(&source, &std::env::temp_dir()).unwrap(), None);
+    std::fs::remove_file(&source).unwrap();
+}
+
+#[test]
+pub fn test_thumbnail_content_type() {
+    assert_eq!(PictureThumbnail::Small.content_type(), ContentType::WEBP);
+    assert_eq!(PictureThumbnail::Large.content_type(), ContentType::WEBP);
+    assert_eq!(PictureThumbnail::Original.content_type(), ContentType::JPEG);
+}
+
+#[test]
+pub fn test_stale_temp_files_are_removed() {
+    let config = Config {
+        temp_dir: temp_path("temp-dir").to_str().unwrap().to_string(),
+        ..Default::default()
+    };
+    let dir = config.original_temp_dir();
+    assert_eq!(dir, temp_path("temp-dir").join("original"));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let stale = dir.join("stale.jpg");
+    let recent = dir.join("recent.jpg");
+    std::fs::write(&stale, b"stale").unwrap();
+    std::fs::write(&recent, b"recent").unwrap();
+    let file = std::fs::File::options().write(true).open(&stale).unwrap();
+    file.set_modified(SystemTime::now() - Duration::from_secs(48 * 3600)).unwrap();
+    drop(file);
+
+    let removed = remove_stale_temp_files(&dir, Duration::from_secs(24 * 3600));
+    let stale_exists = stale.exists();
+    let recent_exists = recent.exists();
+    std::fs::remove_dir_all(temp_path("temp-dir")).unwrap();
+
+    assert_eq!(removed, 1);
+    assert!(!stale_exists);
+    assert!(recent_exists);
+}
+
+/// Picture of 300x200 pixels with a horizontal gradient and a few bright stripes.
+fn landscape() -> RgbImage {
+    RgbImage::from_fn(300, 200, |x, y| {
+        let light = if (x / 40 + y / 50) % 3 == 0 { 60 } else { 0 };
+        let value = (x * 180 / 300 + light) as u8;
+        Rgb([value, value / 2, 255 - value])
+    })
+}
+
+#[test]
+pub fn test_near_identical_upload_is_reported_as_similar() {
+    let original = landscape();
+    let original_hash = perceptual_hash(&DynamicImage::ImageRgb8(original.clone()));
+
+    // The same picture uploaded again, resized and re-encoded as a lossy JPEG
+    let path = temp_path("reupload.jpg");
+    DynamicImage::ImageRgb8(imageops::resize(&original, 150, 100, imageops::FilterType::Triangle))
+        .save_with_format(&path, ImageFormat::Jpeg)
+        .unwrap();
+    let reupload_hash = generate_perceptual_hash(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(perceptual_hash_distance(original_hash, reupload_hash) <= SIMILAR_PICTURES_MAX_DISTANCE as u32);
+
+    // A different picture (mirrored) is far from the original
+    let mirrored_hash = perceptual_hash(&DynamicImage::ImageRgb8(imageops::flip_horizontal(&original)));
+    assert!(perceptual_hash_distance(original_hash, mirrored_hash) > SIMILAR_PICTURES_MAX_DISTANCE as u32);
+
+    // The original is found among the pictures of the user by the distance between the hashes
+    let sql = debug_query::<Pg, _>(&Picture::similar_pictures_statement(1, 42, reupload_hash)).to_string();
+    assert!(sql.contains("\"pictures\".\"id\" != $2"));
+    assert!(sql.contains("\"pictures\".\"deleted_date\" IS NULL"));
+    assert!(sql.contains("(length(replace(((\"pictures\".\"perceptual_hash\" # $3)::bit(64))::text, '0', '')) <= $4)"));
+    assert!(sql.contains("ORDER BY length(replace(((\"pictures\".\"perceptual_hash\" # $5)::bit(64))::text, '0', '')) ASC"));
+    assert!(sql.ends_with(&format!("binds: [1, 42, {0}, 6, {0}, 20]", reupload_hash)));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at TEST_DATABASE_URL"]
+pub fn test_similar_pictures_are_found_by_the_distance_between_the_hashes() {
+    let conn = &mut test_connection();
+    let user_id = insert_user(conn, "similar");
+    let other_user_id = insert_user(conn, "similar_other");
+    let hashed_picture = |conn: &mut DBConn, user_id: i32, hash: i64| {
+        let picture_id = insert_picture(conn, user_id, &[]);
+        Picture::set_perceptual_hash(conn, picture_id, hash).unwrap();
+        picture_id
+    };
+    // The hashes use all the 64 bits, the sign bit included
+    let original_hash = 0x0F0F_0F0F_0F0F_0F0Fu64 as i64 | i64::MIN;
+    let original_id = hashed_picture(conn, user_id, original_hash);
+    let closer_id = hashed_picture(conn, user_id, original_hash ^ 0b1);
+    hashed_picture(conn, user_id, !original_hash);
+    hashed_picture(conn, other_user_id, original_hash);
+    insert_picture(conn, user_id, &[]);
+    let trashed_id = hashed_picture(conn, user_id, original_hash);
+    diesel::update(pictures::table.find(trashed_id))
+        .set(pictures::deleted_date.eq(Some(Utc::now().naive_utc())))
+        .execute(conn)
+        .unwrap();
+
+    // The upload is 3 bits away from the original and 2 bits away from the closer picture, most similar first
+    let upload_hash = original_hash ^ 0b111;
+    let upload_id = hashed_picture(conn, user_id, upload_hash);
+    assert_eq!(perceptual_hash_distance(original_hash, upload_hash), 3);
+    assert_eq!(
+        Picture::similar_pictures(conn, user_id, upload_id, upload_hash).unwrap(),
+        vec![closer_id, original_id]
+    );
+}
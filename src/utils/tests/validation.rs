@@ -0,0 +1,140 @@
+use crate::utils::config::Config;
+use crate::utils::errors_catcher::{ErrorResponse, ErrorTypeKind};
+use crate::utils::validation::{
+    validate_password_with, validate_picture_batch_with, validate_picture_comment, validate_picture_rating, validate_user_name,
+    validation_error_to_responder, PASSWORD_MAX_LENGTH, PICTURE_COMMENT_MAX_LENGTH,
+};
+
+#[test]
+pub fn test_validate_user_name() {
+    assert!(validate_user_name("Jane Doe").is_ok());
+    assert!(validate_user_name("Élodie Dupont").is_ok());
+
+    // Empty or too short
+    assert!(validate_user_name("").is_err());
+    assert!(validate_user_name("Jane").is_err());
+    // Too long
+    assert!(validate_user_name(&"a".repeat(101)).is_err());
+    assert!(validate_user_name(&"a".repeat(100)).is_ok());
+    // Surrounding whitespace
+    assert!(validate_user_name(" Jane Doe").is_err());
+    assert!(validate_user_name("Jane Doe\n").is_err());
+    // Control characters
+    assert!(validate_user_name("Jane\u{0007}Doe").is_err());
+    assert!(validate_user_name("Jane\tDoe").is_err());
+}
+
+#[test]
+pub fn test_validation_error_to_responder() {
+    let error = validate_user_name("").unwrap_err();
+    let response = ErrorResponse::from(validation_error_to_responder("name", error));
+    assert!(matches!(response.error_type, ErrorTypeKind::InvalidInput));
+    assert!(response.message.starts_with("name: "));
+    assert!(!response.rollback);
+}
+
+#[test]
+pub fn test_validate_picture_comment() {
+    assert!(validate_picture_comment("").is_ok());
+    assert!(validate_picture_comment("Sunset over the lake.\nTaken from the north shore.").is_ok());
+    assert!(validate_picture_comment(&"é".repeat(PICTURE_COMMENT_MAX_LENGTH)).is_ok());
+    // Too long
+    assert!(validate_picture_comment(&"a".repeat(PICTURE_COMMENT_MAX_LENGTH + 1)).is_err());
+}
+
+#[test]
+pub fn test_validate_picture_rating() {
+    assert!(validate_picture_rating(0).is_ok());
+    assert!(validate_picture_rating(5).is_ok());
+    assert!(validate_picture_rating(-1).is_err());
+    assert!(validate_picture_rating(6).is_err());
+}
+
+#[test]
+pub fn test_validate_picture_batch() {
+    let config = Config {
+        max_batch_pictures: 3,
+        ..Config::default()
+    };
+    assert!(validate_picture_batch_with(&[], &config).is_ok());
+    assert!(validate_picture_batch_with(&[1, 2, 3], &config).is_ok());
+
+    // Over the cap, the request is refused before anything is done
+    let response = ErrorResponse::from(validate_picture_batch_with(&[1, 2, 3, 4], &config).unwrap_err());
+    assert!(matches!(response.error_type, ErrorTypeKind::InvalidInput));
+    assert!(response.message.starts_with("picture_ids: At most 3 pictures"));
+    assert!(!response.rollback);
+}
+
+/// Policy with no rule but a minimum length of 1
+fn lenient_policy() -> Config {
+    Config {
+        password_min_length: 1,
+        password_require_lowercase: false,
+        password_require_uppercase: false,
+        password_require_digit: false,
+        password_require_special: false,
+        ..Default::default()
+    }
+}
+fn password_error_code(password: &str, config: &Config) -> String {
+    validate_password_with(password, config).unwrap_err().code.to_string()
+}
+
+#[test]
+pub fn test_password_min_length() {
+    let config = Config {
+        password_min_length: 12,
+        ..lenient_policy()
+    };
+    assert_eq!(password_error_code("short", &config), "password_length");
+    assert!(validate_password_with("long enough pass", &config).is_ok());
+    // Characters are counted, not bytes
+    assert!(validate_password_with(&"é".repeat(12), &config).is_ok());
+    assert_eq!(password_error_code(&"a".repeat(PASSWORD_MAX_LENGTH + 1), &config), "password_length");
+}
+
+#[test]
+pub fn test_password_require_digit() {
+    let config = Config {
+        password_require_digit: true,
+        ..lenient_policy()
+    };
+    assert_eq!(password_error_code("no digits here", &config), "password_digit");
+    assert!(validate_password_with("one digit 1", &config).is_ok());
+}
+
+#[test]
+pub fn test_password_require_uppercase() {
+    let config = Config {
+        password_require_uppercase: true,
+        ..lenient_policy()
+    };
+    assert_eq!(password_error_code("all lowercase", &config), "password_uppercase");
+    assert!(validate_password_with("One uppercase", &config).is_ok());
+}
+
+#[test]
+pub fn test_password_require_special() {
+    let config = Config {
+        password_require_special: true,
+        ..lenient_policy()
+    };
+    assert_eq!(password_error_code("Letters4ndDigits", &config), "password_special");
+    assert!(validate_password_with("Letters4ndDigits!", &config).is_ok());
+}
+
+#[test]
+pub fn test_default_password_policy() {
+    let config = Config::default();
+    assert!(validate_password_with("Str0ngPassword", &config).is_ok());
+    assert_eq!(password_error_code("Sh0rt", &config), "password_length");
+    assert_eq!(password_error_code("N0LOWERCASE", &config), "password_lowercase");
+    assert_eq!(password_error_code("NoDigitsAtAll", &config), "password_digit");
+
+    // The error is scoped to the password field
+    let error = validate_password_with("weak", &config).unwrap_err();
+    let response = ErrorResponse::from(validation_error_to_responder("password", error));
+    assert!(matches!(response.error_type, ErrorTypeKind::InvalidInput));
+    assert!(response.message.starts_with("password: "));
+}
@@ -1,10 +1,20 @@
+use crate::utils::config::CONFIG;
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
+use crate::utils::picture_format::format_content_type;
+use image::codecs::gif::GifDecoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
 use image::GenericImageView;
+use image::{AnimationDecoder, Frame, ImageFormat};
 use magick_rust::{magick_wand_genesis, MagickWand};
+use rocket::http::ContentType;
 use rocket::request::FromParam;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 
@@ -24,6 +34,17 @@ impl PictureThumbnail {
             PictureThumbnail::Large => Some(1000),
         }
     }
+    /// Content type of the stored file: thumbnails are static WebP pictures, originals are stored as uploaded.
+    pub fn content_type(&self) -> ContentType {
+        self.content_type_with_original(None)
+    }
+    /// Content type of the stored file, originals being of `original_format` (see [`crate::database::picture::picture::Picture::format`]).
+    pub fn content_type_with_original(&self, original_format: Option<&str>) -> ContentType {
+        match self {
+            PictureThumbnail::Original => format_content_type(original_format),
+            _ => ContentType::WEBP,
+        }
+    }
 }
 impl FromParam<'_> for PictureThumbnail {
     type Error = ErrorResponder;
@@ -47,25 +68,64 @@ impl FromParam<'_> for PictureThumbnail {
         }
     }
 }
-pub const ORIGINAL_TEMP_DIR: &str = "./picture-temp/original";
-pub const THUMBS_TEMP_DIR: &str = "./picture-temp/thumbs";
-
+/// Create the temporary directories of the pictures if needed, and remove their files left over by interrupted uploads
+/// (older than `TEMP_FILES_MAX_AGE_HOURS`).
 pub fn create_temp_directories() {
-    if !Path::new(ORIGINAL_TEMP_DIR).exists() {
-        std::fs::create_dir_all(ORIGINAL_TEMP_DIR).expect("Unable to create temp directory");
-    }
-    if !Path::new(THUMBS_TEMP_DIR).exists() {
-        std::fs::create_dir_all(THUMBS_TEMP_DIR).expect("Unable to create temp directory");
+    let max_age = Duration::from_secs(CONFIG.temp_files_max_age_hours as u64 * 3600);
+    for dir in [CONFIG.original_temp_dir(), CONFIG.thumbs_temp_dir()] {
+        if !dir.exists() {
+            std::fs::create_dir_all(&dir).expect("Unable to create temp directory");
+        }
+        let removed = remove_stale_temp_files(&dir, max_age);
+        if removed > 0 {
+            info!("Removed {} stale temporary files from {}", removed, dir.display());
+        }
     }
 }
 
-/// Generate a thumbnail from a source file and stores it in THUMBS_TEMP_DIR/source_file_name
+/// Remove the files of `dir` last modified more than `max_age` ago, returning how many were removed.
+/// Files that can't be inspected or removed are skipped.
+pub fn remove_stale_temp_files(dir: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .metadata()
+                .ok()
+                .filter(|metadata| metadata.is_file())
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > max_age)
+        })
+        .filter(|entry| match std::fs::remove_file(entry.path()) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Unable to remove stale temporary file {}: {}", entry.path().display(), e);
+                false
+            }
+        })
+        .count()
+}
+
+/// Generate a static thumbnail from a source file and stores it in the thumbnails temp directory (see [`crate::utils::config::Config::thumbs_temp_dir`])
+/// Animated GIFs are thumbnailed from their first frame (see [`extract_first_frame`]).
 pub fn generate_thumbnail(thumbnail_type: PictureThumbnail, source_file: &Path) -> Result<PathBuf, ErrorResponder> {
     // Initialize the Magick Wand environment
     magick_wand_genesis();
 
+    let first_frame_file = extract_first_frame(source_file, &CONFIG.thumbs_temp_dir())?;
+    let read_file = first_frame_file.as_deref().unwrap_or(source_file);
+
     let mut wand = MagickWand::new();
-    if let Err(e) = wand.read_image(source_file.to_str().unwrap()) {
+    let read_result = wand.read_image(read_file.to_str().unwrap());
+    if let Some(first_frame_file) = &first_frame_file {
+        let _ = std::fs::remove_file(first_frame_file);
+    }
+    if let Err(e) = read_result {
         warn!("{:?}", e);
         return ErrorType::UnableToCreateThumbnail(String::from("Unable to read image")).res_err_no_rollback();
     }
@@ -84,7 +144,7 @@ pub fn generate_thumbnail(thumbnail_type: PictureThumbnail, source_file: &Path)
         return ErrorType::UnableToCreateThumbnail(String::from("Unable to set image format")).res_err_no_rollback();
     }
 
-    let dest_file = Path::new(THUMBS_TEMP_DIR).join(source_file.file_name().unwrap().to_str().unwrap());
+    let dest_file = CONFIG.thumbs_temp_dir().join(source_file.file_name().unwrap().to_str().unwrap());
     let dest_file_path = dest_file.to_str().unwrap();
 
     if let Err(e) = wand.write_image(dest_file_path) {
@@ -95,6 +155,62 @@ pub fn generate_thumbnail(thumbnail_type: PictureThumbnail, source_file: &Path)
     Ok(dest_file)
 }
 
+/// If the source file is an animated GIF, decode its first frame and store it as a static PNG in `dest_dir`, returning its path.
+/// Returns None for any other file, that can be thumbnailed as is.
+pub fn extract_first_frame(source_file: &Path, dest_dir: &Path) -> Result<Option<PathBuf>, ErrorResponder> {
+    let decoder = match File::open(source_file).map(BufReader::new).map(GifDecoder::new) {
+        Ok(Ok(decoder)) => decoder,
+        // Not a GIF file
+        _ => return Ok(None),
+    };
+    let frames = decoder
+        .into_frames()
+        .take(2)
+        .collect::<Result<Vec<Frame>, _>>()
+        .map_err(|e| ErrorType::UnableToCreateThumbnail(format!("Unable to decode GIF frames: {}", e)).res_no_rollback())?;
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+    let first_frame = frames.into_iter().next().unwrap();
+
+    let dest_file = dest_dir.join(format!("{}.first-frame.png", source_file.file_name().unwrap().to_str().unwrap()));
+    first_frame
+        .into_buffer()
+        .save_with_format(&dest_file, ImageFormat::Png)
+        .map_err(|e| ErrorType::UnableToCreateThumbnail(format!("Unable to save the first GIF frame: {}", e)).res_no_rollback())?;
+    Ok(Some(dest_file))
+}
+
+/// Perceptual hash of the image file, usually the small thumbnail of a picture (see [`perceptual_hash`]).
+pub fn generate_perceptual_hash(source_file: &Path) -> Result<i64, ErrorResponder> {
+    let image = image::open(source_file)
+        .map_err(|e| ErrorType::UnableToCreateThumbnail(format!("Unable to read image for its perceptual hash: {}", e)).res_no_rollback())?;
+    Ok(perceptual_hash(&image))
+}
+/// Perceptual hash of an encoded image, usually the small thumbnail of a picture read from S3 (see [`perceptual_hash`]).
+pub fn decode_perceptual_hash(data: &[u8]) -> Result<i64, ErrorResponder> {
+    let image = image::load_from_memory(data)
+        .map_err(|e| ErrorType::UnableToCreateThumbnail(format!("Unable to decode image for its perceptual hash: {}", e)).res_no_rollback())?;
+    Ok(perceptual_hash(&image))
+}
+/// Perceptual hash (difference hash) of an image: 64 bits telling, for each pixel of a 9x8 grayscale version of the image,
+/// whether it is brighter than the next one on its row. Near-identical images (re-encoded, resized, slightly edited)
+/// have hashes at a small Hamming distance (see [`perceptual_hash_distance`]).
+pub fn perceptual_hash(image: &DynamicImage) -> i64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = (hash << 1) | (small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0]) as u64;
+        }
+    }
+    hash as i64
+}
+/// Number of bits that differ between two perceptual hashes.
+pub fn perceptual_hash_distance(hash: i64, other_hash: i64) -> u32 {
+    (hash ^ other_hash).count_ones()
+}
+
 pub fn generate_blurhash(source_file: &Path) -> Result<String, ErrorResponder> {
     magick_wand_genesis();
 
@@ -23,6 +23,20 @@ pub fn left_pad(string: &str, char: char, target_length: usize) -> String {
     res
 }
 
+/// Builds a SQL `LIKE` pattern matching any string containing `fragment`,
+/// escaping the `%`, `_` and `\` characters of the fragment so that they match literally
+pub fn like_contains_pattern(fragment: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in fragment.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
 /// Gets the frontend host from the environment variable `FRONTEND_HOST`
 pub fn get_frontend_host() -> String {
     std::env::var("FRONTEND_HOST").expect("Environment variable FRONTEND_HOST must be set")
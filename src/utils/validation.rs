@@ -2,6 +2,8 @@ use rocket::serde::json::Json;
 use std::borrow::Cow;
 use validator::{Validate, ValidationError};
 
+use crate::database::picture::rating::RATING_MAX;
+use crate::utils::config::{Config, CONFIG};
 use crate::utils::errors_catcher::{ErrorResponder, ErrorType};
 
 /// Validate request data using the [`Validate`] trait from the `validator` crate.
@@ -28,31 +30,100 @@ pub fn validate_input<T: Validate>(data: &Json<T>) -> Result<(), ErrorResponder>
     Ok(())
 }
 
+/// Convert a [`ValidationError`] of a single field to an `InvalidInput` [`ErrorResponder`],
+/// using the same message format as [`validate_input`].
+pub fn validation_error_to_responder(field: &str, error: ValidationError) -> ErrorResponder {
+    let message = error.message.map(|s| s.to_string()).unwrap_or(error.code.to_string());
+    ErrorType::InvalidInput(format!("{}: {}", field, message)).res_no_rollback()
+}
+
 /// Custom validator for a username field
 /// - Must not start or end with whitespace
+/// - Must not contain control characters
 /// - Must have a length between 5 and 100 characters
 pub fn validate_user_name(value: &str) -> Result<(), ValidationError> {
     if value.starts_with(char::is_whitespace) || value.ends_with(char::is_whitespace) {
         return Err(ValidationError::new("name_whitespace").with_message(Cow::from("Name cannot start or end with whitespace")));
     }
+    if value.chars().any(char::is_control) {
+        return Err(ValidationError::new("name_control").with_message(Cow::from("Name cannot contain control characters")));
+    }
     if value.len() < 5 || value.len() > 100 {
         return Err(ValidationError::new("name_length").with_message(Cow::from("Name must be between 5 and 100 characters")));
     }
     Ok(())
 }
 
-/// Custom validator for a password field
-/// - Must have a length between 8 and 100 characters
-/// - Must contain at least one lowercase letter, one uppercase letter and one digit
+/// Maximum number of characters of a picture comment
+pub const PICTURE_COMMENT_MAX_LENGTH: usize = 2000;
+
+/// Custom validator for a picture comment field
+/// - Must have at most [`PICTURE_COMMENT_MAX_LENGTH`] characters (can be empty)
+pub fn validate_picture_comment(value: &str) -> Result<(), ValidationError> {
+    if value.chars().count() > PICTURE_COMMENT_MAX_LENGTH {
+        return Err(ValidationError::new("comment_length")
+            .with_message(Cow::from(format!("Comment must be at most {} characters", PICTURE_COMMENT_MAX_LENGTH))));
+    }
+    Ok(())
+}
+
+/// Custom validator for a picture rating field
+/// - Must be between 0 and [`RATING_MAX`] stars
+pub fn validate_picture_rating(value: i16) -> Result<(), ValidationError> {
+    if !(0..=RATING_MAX).contains(&value) {
+        return Err(ValidationError::new("rating_range").with_message(Cow::from(format!("Rating must be between 0 and {}", RATING_MAX))));
+    }
+    Ok(())
+}
+
+/// Check the number of pictures of a bulk operation against the configured maximum (see [`validate_picture_batch_with`]).
+pub fn validate_picture_batch(picture_ids: &[i64]) -> Result<(), ErrorResponder> {
+    validate_picture_batch_with(picture_ids, &CONFIG)
+}
+
+/// Refuse a bulk operation on more than `max_batch_pictures` pictures of `config`, with an `InvalidInput` error.
+pub fn validate_picture_batch_with(picture_ids: &[i64], config: &Config) -> Result<(), ErrorResponder> {
+    if picture_ids.len() > config.max_batch_pictures {
+        return ErrorType::InvalidInput(format!(
+            "picture_ids: At most {} pictures can be processed at once, got {}",
+            config.max_batch_pictures,
+            picture_ids.len()
+        ))
+        .res_err_no_rollback();
+    }
+    Ok(())
+}
+
+/// Maximum number of characters of a password
+pub const PASSWORD_MAX_LENGTH: usize = 100;
+
+/// Custom validator for a password field, following the password policy of the configuration (see [`validate_password_with`])
 pub fn validate_password(value: &str) -> Result<(), ValidationError> {
-    if value.len() < 8 || value.len() > 100 {
-        return Err(ValidationError::new("password_length").with_message(Cow::from("Password must be between 8 and 100 characters")));
-    }
-    if !value.chars().any(|c| c.is_ascii_lowercase()) || !value.chars().any(|c| c.is_ascii_uppercase()) || !value.chars().any(|c| c.is_ascii_digit())
-    {
-        return Err(ValidationError::new("password_requirements").with_message(Cow::from(
-            "Password must contain at least one lowercase letter, one uppercase letter and one digit",
-        )));
+    validate_password_with(value, &CONFIG)
+}
+
+/// Validate a password against the password policy of `config`:
+/// - Must have between `password_min_length` and [`PASSWORD_MAX_LENGTH`] characters
+/// - Must contain a lowercase letter, an uppercase letter, a digit and a special character, when required
+pub fn validate_password_with(value: &str, config: &Config) -> Result<(), ValidationError> {
+    let length = value.chars().count();
+    if length < config.password_min_length || length > PASSWORD_MAX_LENGTH {
+        return Err(ValidationError::new("password_length").with_message(Cow::from(format!(
+            "Password must be between {} and {} characters",
+            config.password_min_length, PASSWORD_MAX_LENGTH
+        ))));
+    }
+    if config.password_require_lowercase && !value.chars().any(|c| c.is_ascii_lowercase()) {
+        return Err(ValidationError::new("password_lowercase").with_message(Cow::from("Password must contain at least one lowercase letter")));
+    }
+    if config.password_require_uppercase && !value.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(ValidationError::new("password_uppercase").with_message(Cow::from("Password must contain at least one uppercase letter")));
+    }
+    if config.password_require_digit && !value.chars().any(|c| c.is_ascii_digit()) {
+        return Err(ValidationError::new("password_digit").with_message(Cow::from("Password must contain at least one digit")));
+    }
+    if config.password_require_special && value.chars().all(char::is_alphanumeric) {
+        return Err(ValidationError::new("password_special").with_message(Cow::from("Password must contain at least one special character")));
     }
     Ok(())
 }
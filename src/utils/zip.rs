@@ -0,0 +1,187 @@
+use crc32fast::Hasher;
+use rocket::futures::Stream;
+use rocket::http::ContentType;
+use rocket::response::stream::ByteStream;
+use rocket::response::Responder;
+use rocket::{response, Request, Response};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::Responses;
+use rocket_okapi::response::OpenApiResponderInner;
+use std::collections::HashSet;
+use std::pin::Pin;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+/// Version 2.0: stored entries with data descriptors
+const ZIP_VERSION: u16 = 20;
+/// Sizes and CRC are written in a data descriptor after the data (bit 3), names are UTF-8 (bit 11)
+const ZIP_FLAGS: u16 = 1 << 3 | 1 << 11;
+/// Entries are stored without compression, pictures being already compressed
+const STORED_METHOD: u16 = 0;
+/// Modification date of the entries, 1980-01-01 in MS-DOS format (the pictures dates are in the database, not in the archive)
+const DOS_DATE: u16 = 1 << 5 | 1;
+/// Maximum number of entries of an archive, without ZIP64 extensions
+pub const ZIP_MAX_ENTRIES: usize = u16::MAX as usize;
+
+/// Entry written in the central directory at the end of the archive.
+#[derive(Debug, Clone)]
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Encoder of a ZIP archive built while its entries are streamed: each entry is a local header, its data and a data descriptor,
+/// then the archive ends with the central directory. Nothing is buffered but the entries list.
+/// ZIP64 is not supported: the archive must stay under 4 GiB and [`ZIP_MAX_ENTRIES`] entries.
+#[derive(Debug, Default)]
+pub struct ZipEncoder {
+    offset: u32,
+    entries: Vec<ZipEntry>,
+    current: Option<(ZipEntry, Hasher)>,
+}
+
+impl ZipEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Start a new entry, returning its local header.
+    pub fn start_entry(&mut self, name: &str) -> Vec<u8> {
+        let mut header = Vec::with_capacity(30 + name.len());
+        push_u32(&mut header, LOCAL_FILE_HEADER_SIGNATURE);
+        push_u16(&mut header, ZIP_VERSION);
+        push_u16(&mut header, ZIP_FLAGS);
+        push_u16(&mut header, STORED_METHOD);
+        push_u16(&mut header, 0); // Time
+        push_u16(&mut header, DOS_DATE);
+        push_u32(&mut header, 0); // CRC, in the data descriptor
+        push_u32(&mut header, 0); // Compressed size, in the data descriptor
+        push_u32(&mut header, 0); // Size, in the data descriptor
+        push_u16(&mut header, name.len() as u16);
+        push_u16(&mut header, 0); // Extra field length
+        header.extend_from_slice(name.as_bytes());
+
+        let entry = ZipEntry {
+            name: name.to_string(),
+            crc: 0,
+            size: 0,
+            offset: self.offset,
+        };
+        self.current = Some((entry, Hasher::new()));
+        self.offset += header.len() as u32;
+        header
+    }
+    /// Account for data of the current entry, that is streamed as is.
+    pub fn add_data(&mut self, data: &[u8]) {
+        let (entry, hasher) = self.current.as_mut().expect("No ZIP entry started");
+        hasher.update(data);
+        entry.size += data.len() as u32;
+        self.offset += data.len() as u32;
+    }
+    /// End the current entry, returning its data descriptor.
+    pub fn end_entry(&mut self) -> Vec<u8> {
+        let (mut entry, hasher) = self.current.take().expect("No ZIP entry started");
+        entry.crc = hasher.finalize();
+
+        let mut descriptor = Vec::with_capacity(16);
+        push_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        push_u32(&mut descriptor, entry.crc);
+        push_u32(&mut descriptor, entry.size);
+        push_u32(&mut descriptor, entry.size);
+        self.offset += descriptor.len() as u32;
+        self.entries.push(entry);
+        descriptor
+    }
+    /// End the archive, returning its central directory.
+    pub fn finish(self) -> Vec<u8> {
+        let mut directory = Vec::new();
+        for entry in self.entries.iter() {
+            push_u32(&mut directory, CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+            push_u16(&mut directory, ZIP_VERSION); // Version made by
+            push_u16(&mut directory, ZIP_VERSION); // Version needed
+            push_u16(&mut directory, ZIP_FLAGS);
+            push_u16(&mut directory, STORED_METHOD);
+            push_u16(&mut directory, 0); // Time
+            push_u16(&mut directory, DOS_DATE);
+            push_u32(&mut directory, entry.crc);
+            push_u32(&mut directory, entry.size);
+            push_u32(&mut directory, entry.size);
+            push_u16(&mut directory, entry.name.len() as u16);
+            push_u16(&mut directory, 0); // Extra field length
+            push_u16(&mut directory, 0); // Comment length
+            push_u16(&mut directory, 0); // Disk number
+            push_u16(&mut directory, 0); // Internal attributes
+            push_u32(&mut directory, 0); // External attributes
+            push_u32(&mut directory, entry.offset);
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size = directory.len() as u32;
+        push_u32(&mut directory, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        push_u16(&mut directory, 0); // Disk number
+        push_u16(&mut directory, 0); // Disk of the central directory
+        push_u16(&mut directory, self.entries.len() as u16);
+        push_u16(&mut directory, self.entries.len() as u16);
+        push_u32(&mut directory, directory_size);
+        push_u32(&mut directory, self.offset);
+        push_u16(&mut directory, 0); // Comment length
+        directory
+    }
+}
+
+fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Names of the archive entries: path separators are replaced, and colliding names get a ` (n)` suffix before their extension.
+pub fn unique_entry_names(names: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    names
+        .iter()
+        .map(|name| {
+            let name = name.replace(['/', '\\'], "_");
+            let name = if name.is_empty() { "picture".to_string() } else { name };
+            let (stem, extension) = match name.rfind('.') {
+                Some(index) if index > 0 => (&name[..index], &name[index..]),
+                _ => (name.as_str(), ""),
+            };
+            let mut unique_name = name.clone();
+            let mut count = 1;
+            while used.contains(&unique_name) {
+                unique_name = format!("{} ({}){}", stem, count, extension);
+                count += 1;
+            }
+            used.insert(unique_name.clone());
+            unique_name
+        })
+        .collect()
+}
+
+pub type ZipByteStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// ZIP archive response, streamed as it is built, downloaded as `file_name`.
+pub struct ZipArchive {
+    pub file_name: String,
+    pub stream: ZipByteStream,
+}
+
+impl<'r> Responder<'r, 'r> for ZipArchive {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        let response = ByteStream(self.stream).respond_to(request)?;
+        Response::build_from(response)
+            .header(ContentType::ZIP)
+            .raw_header("Content-Disposition", format!("attachment; filename=\"{}\"", self.file_name))
+            .ok()
+    }
+}
+/// Dummy implementation for OpenApi
+impl OpenApiResponderInner for ZipArchive {
+    fn responses(_: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        Ok(Responses::default())
+    }
+}